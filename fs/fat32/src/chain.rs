@@ -0,0 +1,130 @@
+//! Cluster chains of files
+use crate::FatData;
+use alloc::vec::Vec;
+use libsys::error::Errno;
+use vfs::BlockDevice;
+
+/// Number of (index, cluster) pairs remembered by a [ClusterChain]
+pub const CHAIN_CACHE_SIZE: usize = 8;
+
+/// Chain of clusters holding file data. Remembers the clusters looked up
+/// recently, so that sequential reads and seeks close to them don't walk
+/// the FAT from the start of the chain.
+pub struct ClusterChain {
+    first: u32,
+    // Most recently used first
+    recent: Vec<(u32, u32)>,
+}
+
+impl ClusterChain {
+    /// Creates a chain starting at `first`. Zero means an empty chain.
+    pub const fn new(first: u32) -> Self {
+        Self {
+            first,
+            recent: Vec::new(),
+        }
+    }
+
+    /// Returns the first cluster of the chain
+    pub const fn first(&self) -> u32 {
+        self.first
+    }
+
+    /// Returns the `index`-th cluster of the chain, or `None` if the chain
+    /// is shorter than that
+    pub fn cluster_at(
+        &mut self,
+        data: &FatData,
+        dev: &dyn BlockDevice,
+        index: u32,
+    ) -> Result<Option<u32>, Errno> {
+        if self.first == 0 {
+            return Ok(None);
+        }
+
+        // Start from the closest known cluster before the one requested
+        let (mut pos, mut cluster) = self
+            .recent
+            .iter()
+            .copied()
+            .filter(|&(pos, _)| pos <= index)
+            .max_by_key(|&(pos, _)| pos)
+            .unwrap_or((0, self.first));
+        while pos < index {
+            match data.next_cluster(dev, cluster)? {
+                Some(next) => cluster = next,
+                None => return Ok(None),
+            }
+            pos += 1;
+        }
+
+        self.recent.retain(|&(pos, _)| pos != index);
+        self.recent.insert(0, (index, cluster));
+        self.recent.truncate(CHAIN_CACHE_SIZE);
+        Ok(Some(cluster))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::*, Bpb, OrderedWriter};
+
+    fn fat_data(dev: &RamDevice) -> FatData {
+        let bpb = Bpb::from_sector(&dev.snapshot()[..512]);
+        FatData::new(bpb, OrderedWriter::new())
+    }
+
+    #[test]
+    fn test_chain_walk() {
+        let mut data = image();
+        // Fragmented chain: 10 -> 20 -> 11 -> 30
+        set_fat(&mut data, 10, 20);
+        set_fat(&mut data, 20, 11);
+        set_fat(&mut data, 11, 30);
+        set_fat(&mut data, 30, 0x0FFFFFFF);
+        // Broken chain: 40 -> (free) 41
+        set_fat(&mut data, 40, 41);
+        let dev = RamDevice::with_data(data);
+        let fat = fat_data(&dev);
+
+        let mut chain = ClusterChain::new(10);
+        let clusters: Vec<_> = (0..5)
+            .map(|index| chain.cluster_at(&fat, &dev, index).unwrap())
+            .collect();
+        assert_eq!(clusters, [Some(10), Some(20), Some(11), Some(30), None]);
+        assert_eq!(chain.cluster_at(&fat, &dev, 1), Ok(Some(20)));
+
+        let mut chain = ClusterChain::new(40);
+        assert_eq!(chain.cluster_at(&fat, &dev, 2), Err(Errno::InvalidFile));
+        assert_eq!(ClusterChain::new(0).cluster_at(&fat, &dev, 0), Ok(None));
+    }
+
+    #[test]
+    fn test_chain_cached() {
+        let mut data = image();
+        // 10 -> 11 -> ... -> 109
+        for cluster in 10..109 {
+            set_fat(&mut data, cluster, cluster as u32 + 1);
+        }
+        set_fat(&mut data, 109, 0x0FFFFFFF);
+        let dev = RamDevice::with_data(data);
+        let fat = fat_data(&dev);
+        let mut chain = ClusterChain::new(10);
+
+        // Sequential access walks the chain once
+        for index in 0..100 {
+            assert_eq!(chain.cluster_at(&fat, &dev, index), Ok(Some(index + 10)));
+        }
+        // All the entries are in the same FAT sector
+        let reads = dev.reads();
+        assert_eq!(reads, 1);
+        assert_eq!(fat.fat.borrow().misses(), 1);
+
+        // Seeking back to a recently used position does not walk the FAT
+        assert_eq!(chain.cluster_at(&fat, &dev, 95), Ok(Some(105)));
+        assert_eq!(chain.cluster_at(&fat, &dev, 99), Ok(Some(109)));
+        assert_eq!(chain.cluster_at(&fat, &dev, 100), Ok(None));
+        assert_eq!(dev.reads(), reads);
+    }
+}
@@ -0,0 +1,252 @@
+//! Mount-time filesystem consistency check
+use crate::{
+    fat::{self, FAT_BAD, FAT_EOC, FAT_FREE},
+    Bpb, OrderedWriter,
+};
+use alloc::{vec, vec::Vec};
+use libsys::{
+    error::Errno,
+    mem::{read_le16, read_le32},
+};
+use vfs::BlockDevice;
+
+/// Results of a consistency check
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CheckReport {
+    /// Chains of allocated clusters not reachable from any directory entry
+    pub lost_chains: usize,
+    /// Total number of clusters in lost chains
+    pub lost_clusters: usize,
+    /// Chains pointing to free, bad or out of range clusters
+    pub broken_chains: usize,
+    /// Chains sharing clusters with another chain (or looping)
+    pub cross_links: usize,
+    /// Number of lost clusters released by the repair
+    pub repaired: usize,
+}
+
+struct Bitmap(Vec<u64>);
+
+struct Checker<'a> {
+    dev: &'a dyn BlockDevice,
+    bpb: &'a Bpb,
+    writer: &'a OrderedWriter,
+    report: CheckReport,
+    used: Bitmap,
+}
+
+impl Bitmap {
+    fn new(len: u32) -> Self {
+        Self(vec![0; (len as usize + 63) / 64])
+    }
+
+    fn get(&self, idx: u32) -> bool {
+        self.0[idx as usize / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn set(&mut self, idx: u32) {
+        self.0[idx as usize / 64] |= 1 << (idx % 64);
+    }
+}
+
+impl CheckReport {
+    /// Returns `true` if no inconsistencies were found
+    pub const fn is_clean(&self) -> bool {
+        self.lost_chains == 0 && self.broken_chains == 0 && self.cross_links == 0
+    }
+}
+
+impl Checker<'_> {
+    fn is_valid(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster < self.bpb.cluster_count() + 2
+    }
+
+    /// Marks clusters of a chain starting at `cluster` as used, returning
+    /// the list of them if `collect` is set
+    fn walk_chain(&mut self, mut cluster: u32, collect: bool) -> Result<Vec<u32>, Errno> {
+        let mut list = Vec::new();
+        loop {
+            if !self.is_valid(cluster) {
+                self.report.broken_chains += 1;
+                break;
+            }
+            if self.used.get(cluster) {
+                self.report.cross_links += 1;
+                break;
+            }
+            self.used.set(cluster);
+            if collect {
+                list.push(cluster);
+            }
+
+            let next = fat::read_entry(self.writer, self.dev, self.bpb, cluster)?;
+            if next >= FAT_EOC {
+                break;
+            }
+            if next == FAT_FREE || next == FAT_BAD {
+                self.report.broken_chains += 1;
+                break;
+            }
+            cluster = next;
+        }
+        Ok(list)
+    }
+
+    /// Walks the directory tree, marking all the clusters reachable from it
+    fn walk_tree(&mut self) -> Result<(), Errno> {
+        let mut dirs = vec![self.bpb.root_cluster()];
+        let mut buf = [0u8; 512];
+
+        while let Some(dir) = dirs.pop() {
+            'outer: for cluster in self.walk_chain(dir, true)? {
+                let base = self.bpb.cluster_base_sector(cluster);
+                for sector in base..base + self.bpb.sectors_per_cluster() as u32 {
+                    self.writer.read(self.dev, sector, &mut buf)?;
+                    for ent in buf.chunks_exact(32) {
+                        match ent[0] {
+                            // End of directory
+                            0x00 => break 'outer,
+                            // Deleted entry, "." and ".."
+                            0xE5 | b'.' => continue,
+                            _ => {}
+                        }
+                        let attrs = ent[11];
+                        // Long file name parts and volume labels
+                        if attrs == 0x0F || attrs & 0x08 != 0 {
+                            continue;
+                        }
+                        let first = ((read_le16(&ent[20..]) as u32) << 16)
+                            | (read_le16(&ent[26..]) as u32);
+                        if first == 0 {
+                            continue;
+                        }
+                        if attrs & 0x10 != 0 {
+                            dirs.push(first);
+                        } else {
+                            self.walk_chain(first, false)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans the FAT for allocated clusters not marked as used, returning them
+    fn find_lost(&mut self) -> Result<Vec<u32>, Errno> {
+        let count = self.bpb.cluster_count();
+        let mut lost = Vec::new();
+        let mut pointed = Bitmap::new(count + 2);
+        let mut buf = [0u8; 512];
+        let mut loaded = None;
+
+        for cluster in 2..count + 2 {
+            let (sector, off) = fat::entry_location(self.bpb, cluster);
+            if loaded != Some(sector) {
+                self.writer.read(self.dev, sector, &mut buf)?;
+                loaded = Some(sector);
+            }
+            let value = read_le32(&buf[off..]) & fat::FAT_ENTRY_MASK;
+            if value == FAT_FREE || value == FAT_BAD || self.used.get(cluster) {
+                continue;
+            }
+            lost.push(cluster);
+            if value < FAT_EOC && self.is_valid(value) {
+                pointed.set(value);
+            }
+        }
+
+        // Chain heads are lost clusters no other cluster points to
+        self.report.lost_clusters = lost.len();
+        self.report.lost_chains = lost.iter().filter(|&&c| !pointed.get(c)).count();
+        // Lost loops have no head, still count them
+        if self.report.lost_chains == 0 && !lost.is_empty() {
+            self.report.lost_chains = 1;
+        }
+        Ok(lost)
+    }
+}
+
+/// Checks the filesystem for lost, broken and cross-linked cluster chains.
+/// If `repair` is set, lost clusters are released and the updates are
+/// flushed to the device.
+pub fn check(
+    dev: &dyn BlockDevice,
+    bpb: &Bpb,
+    writer: &mut OrderedWriter,
+    repair: bool,
+) -> Result<CheckReport, Errno> {
+    let mut checker = Checker {
+        dev,
+        bpb,
+        writer,
+        report: CheckReport::default(),
+        used: Bitmap::new(bpb.cluster_count() + 2),
+    };
+    checker.walk_tree()?;
+    let lost = checker.find_lost()?;
+    let mut report = checker.report;
+
+    if repair && !lost.is_empty() {
+        for &cluster in lost.iter() {
+            fat::write_entry(writer, dev, bpb, cluster, FAT_FREE)?;
+        }
+        writer.barrier();
+        writer.flush(dev)?;
+        report.repaired = lost.len();
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_check_clean() {
+        let dev = RamDevice::with_data(image());
+        let bpb = Bpb::from_sector(&dev.snapshot()[..512]);
+        let mut writer = OrderedWriter::new();
+
+        assert_eq!(bpb.cluster_count(), 126);
+        let report = check(&dev, &bpb, &mut writer, false).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.lost_clusters, 0);
+    }
+
+    #[test]
+    fn test_check_repair() {
+        let mut data = image();
+        // Lost chain 10 -> 11
+        set_fat(&mut data, 10, 11);
+        set_fat(&mut data, 11, 0x0FFFFFFF);
+        // BROKEN.TXT: 20 -> (free) 21
+        set_dirent(&mut data, 1, b"BROKEN  TXT", 0x20, 20);
+        set_fat(&mut data, 20, 21);
+        let dev = RamDevice::with_data(data);
+        let bpb = Bpb::from_sector(&dev.snapshot()[..512]);
+        let mut writer = OrderedWriter::new();
+
+        let report = check(&dev, &bpb, &mut writer, false).unwrap();
+        assert_eq!(report.lost_chains, 1);
+        assert_eq!(report.lost_clusters, 2);
+        assert_eq!(report.broken_chains, 1);
+        assert_eq!(report.repaired, 0);
+
+        let report = check(&dev, &bpb, &mut writer, true).unwrap();
+        assert_eq!(report.repaired, 2);
+        assert!(writer.is_empty());
+        // Both FAT copies are updated
+        for index in 0..2 {
+            let off = (RESERVED + index) * 512 + 10 * 4;
+            assert_eq!(read_le32(&dev.snapshot()[off..]), 0);
+        }
+
+        let report = check(&dev, &bpb, &mut writer, false).unwrap();
+        assert_eq!(report.lost_chains, 0);
+        assert_eq!(report.broken_chains, 1);
+    }
+}
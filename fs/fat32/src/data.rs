@@ -1,4 +1,7 @@
-use libsys::mem::{read_le16, read_le32};
+use libsys::{
+    error::Errno,
+    mem::{read_le16, read_le32},
+};
 
 #[derive(Debug)]
 pub struct Bpb {
@@ -6,6 +9,8 @@ pub struct Bpb {
     reserved_sectors: u16,
     fat_count: u8,
     sectors_per_fat: u32,
+    total_sectors: u32,
+    root_cluster: u32,
 }
 
 impl Bpb {
@@ -15,16 +20,63 @@ impl Bpb {
             reserved_sectors: read_le16(&data[14..]),
             sectors_per_cluster: data[13],
             sectors_per_fat: read_le32(&data[36..]),
+            total_sectors: read_le32(&data[32..]),
+            root_cluster: read_le32(&data[44..]),
         }
     }
 
+    /// Checks the fields the geometry calculations rely on, so that a
+    /// malformed volume is rejected instead of causing arithmetic faults
+    pub fn validate(&self) -> Result<(), Errno> {
+        if !self.sectors_per_cluster.is_power_of_two()
+            || self.reserved_sectors == 0
+            || self.fat_count == 0
+        {
+            return Err(Errno::InvalidFile);
+        }
+        let first_data_sector = self.reserved_sectors as u64
+            + self.fat_count as u64 * self.sectors_per_fat as u64;
+        if first_data_sector >= self.total_sectors as u64 {
+            return Err(Errno::InvalidFile);
+        }
+        if self.root_cluster < 2 || self.root_cluster >= self.cluster_count() + 2 {
+            return Err(Errno::InvalidFile);
+        }
+        Ok(())
+    }
+
     pub const fn cluster_base_sector(&self, cluster: u32) -> u32 {
-        let first_data_sector =
-            self.reserved_sectors as u32 + (self.fat_count as u32 * self.sectors_per_fat as u32);
-        ((cluster - 2) * self.sectors_per_cluster as u32) + first_data_sector
+        ((cluster - 2) * self.sectors_per_cluster as u32) + self.first_data_sector()
+    }
+
+    pub const fn first_data_sector(&self) -> u32 {
+        self.reserved_sectors as u32 + (self.fat_count as u32 * self.sectors_per_fat as u32)
+    }
+
+    /// Returns the first sector of FAT copy `index`
+    pub const fn fat_base_sector(&self, index: u8) -> u32 {
+        self.reserved_sectors as u32 + index as u32 * self.sectors_per_fat
+    }
+
+    /// Returns the number of data clusters. Valid cluster numbers are
+    /// `2..cluster_count() + 2`.
+    pub const fn cluster_count(&self) -> u32 {
+        (self.total_sectors - self.first_data_sector()) / self.sectors_per_cluster as u32
+    }
+
+    pub const fn total_sectors(&self) -> u32 {
+        self.total_sectors
     }
 
     pub const fn sectors_per_cluster(&self) -> u8 {
         self.sectors_per_cluster
     }
+
+    pub const fn fat_count(&self) -> u8 {
+        self.fat_count
+    }
+
+    pub const fn root_cluster(&self) -> u32 {
+        self.root_cluster
+    }
 }
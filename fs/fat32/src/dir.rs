@@ -1,10 +1,9 @@
-use crate::{Bpb, FileInode};
-use alloc::{borrow::ToOwned, boxed::Box, string::String};
+use crate::{FatData, FileInode};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use libsys::{
     error::Errno,
-    ioctl::IoctlCmd,
     mem::{read_le16, read_le32},
-    stat::{OpenFlags, Stat},
+    stat::{DirectoryEntry, DirectoryEntryPlus, FileMode, OpenFlags, Stat},
 };
 use vfs::{BlockDevice, Vnode, VnodeImpl, VnodeKind, VnodeRef};
 
@@ -12,19 +11,47 @@ pub struct DirectoryInode {
     pub cluster: u32,
 }
 
+/// Iterator over the entries of a directory cluster. A read error, e.g. the
+/// media being removed, is yielded once and ends the iteration.
 pub struct FatIterator<'a> {
     dev: &'a dyn BlockDevice,
     sector: u32,
     sector_off: usize,
     len: u32,
-    lfn: [u8; 128],
-    lfn_len: u8,
+    lfn: [u16; 260],
+    lfn_len: u16,
+    lfn_checksum: u8,
     buf: [u8; 512],
 }
 
+/// Lowercase flags of the short name base and extension
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+/// Characters allowed in 8.3 names besides letters and digits
+const SHORT_NAME_SPECIAL: &[u8] = b"$%'-_@~`!(){}^#&";
+/// Characters not allowed in long names
+const LFN_INVALID: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+/// Maximum length of a long name, in UTF-16 units
+const LFN_MAX_LEN: usize = 255;
+
+/// 8.3 directory entry name chosen for a new file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShortName {
+    /// Name as stored in the entry, padded with spaces
+    pub raw: [u8; 11],
+    /// NT lowercase flags to store along with the name
+    pub case: u8,
+    /// `true` if the name does not represent the file name exactly and
+    /// LFN entries have to precede the short one
+    pub needs_lfn: bool,
+}
+
 #[derive(Debug)]
 pub struct Dirent {
+    /// Long name of the entry, or its short name if it has none
     pub name: String,
+    /// 8.3 name of the entry as stored on disk, e.g. "README~1.TXT"
+    pub short_name: String,
     pub size: u32,
     pub attrs: u8,
     pub cluster: u32,
@@ -32,17 +59,80 @@ pub struct Dirent {
 
 #[auto_inode]
 impl VnodeImpl for DirectoryInode {
+    fn open(&mut self, _node: VnodeRef, flags: OpenFlags) -> Result<usize, Errno> {
+        if flags & OpenFlags::O_ACCESS != OpenFlags::O_RDONLY {
+            return Err(Errno::IsADirectory);
+        }
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: VnodeRef) -> Result<(), Errno> {
+        Ok(())
+    }
+
+    fn stat(&mut self, _node: VnodeRef) -> Result<Stat, Errno> {
+        Ok(Stat {
+            mode: FileMode::default_dir(),
+            size: 0,
+            blksize: 512,
+            rdev: 0,
+        })
+    }
+
+    fn readdir(
+        &mut self,
+        node: VnodeRef,
+        pos: usize,
+        entries: &mut [DirectoryEntry],
+    ) -> Result<usize, Errno> {
+        self.for_each_dirent(&node, pos, entries.len(), |i, ent| {
+            entries[i] = DirectoryEntry::new(&ent.name, ent.stat().mode)?;
+            Ok(())
+        })
+    }
+
+    // Stat data comes with the directory entry itself, no need to load
+    // child vnodes
+    fn readdir_plus(
+        &mut self,
+        node: VnodeRef,
+        pos: usize,
+        entries: &mut [DirectoryEntryPlus],
+    ) -> Result<usize, Errno> {
+        self.for_each_dirent(&node, pos, entries.len(), |i, ent| {
+            let stat = ent.stat();
+            let entry = DirectoryEntry::new(&ent.name, stat.mode)?;
+            entries[i] = DirectoryEntryPlus::new(entry, Some(stat));
+            Ok(())
+        })
+    }
+
     fn lookup(&mut self, parent: VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
+        self.load_entry(parent, name)
+    }
+}
+
+impl DirectoryInode {
+    // Loads the directory entry matching `name`. The vnode is named after
+    // the entry, not after the name requested.
+    fn load_entry(&self, parent: VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
         let fs = parent.fs().unwrap();
         let dirent = {
             let dev = fs.clone().dev().unwrap();
             let fs_data = fs.data();
-            let bpb: &Bpb = fs_data.as_ref().and_then(|e| e.downcast_ref()).unwrap();
+            let fat: &FatData = fs_data.as_ref().and_then(|e| e.downcast_ref()).unwrap();
+            let bpb = &fat.bpb;
             let sector = bpb.cluster_base_sector(self.cluster);
 
-            FatIterator::new(dev, sector, bpb.sectors_per_cluster())
-                .find(|ent| ent.name == name)
-                .ok_or(Errno::DoesNotExist)
+            let mut found = None;
+            for ent in FatIterator::new(dev, sector, bpb.sectors_per_cluster()) {
+                let ent = ent?;
+                if !ent.is_volume_label() && ent.matches(name) {
+                    found = Some(ent);
+                    break;
+                }
+            }
+            found.ok_or(Errno::DoesNotExist)
         }?;
 
         let kind = if dirent.attrs & 0x10 != 0 {
@@ -57,28 +147,317 @@ impl VnodeImpl for DirectoryInode {
                 cluster: dirent.cluster,
             }));
         } else {
-            vnode.set_data(Box::new(FileInode {
-                cluster: dirent.cluster,
-                size: dirent.size,
-            }));
+            vnode.set_data(Box::new(FileInode::new(dirent.cluster, dirent.size)));
         }
         Ok(vnode)
     }
+
+    // Calls `f` for at most `limit` directory entries, skipping the
+    // first `pos` ones. Returns the count of entries visited.
+    fn for_each_dirent<F: FnMut(usize, &Dirent) -> Result<(), Errno>>(
+        &self,
+        node: &VnodeRef,
+        pos: usize,
+        limit: usize,
+        mut f: F,
+    ) -> Result<usize, Errno> {
+        let fs = node.fs().unwrap();
+        let dev = fs.clone().dev().unwrap();
+        let fs_data = fs.data();
+        let fat: &FatData = fs_data.as_ref().and_then(|e| e.downcast_ref()).unwrap();
+        let bpb = &fat.bpb;
+        let sector = bpb.cluster_base_sector(self.cluster);
+
+        let mut index = 0;
+        let mut count = 0;
+        for ent in FatIterator::new(dev, sector, bpb.sectors_per_cluster()) {
+            // Read errors count even in the entries skipped
+            let ent = ent?;
+            if count == limit {
+                break;
+            }
+            if ent.is_volume_label() {
+                continue;
+            }
+            if index >= pos {
+                f(count, &ent)?;
+                count += 1;
+            }
+            index += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Converts a raw 11-byte 8.3 name to "NAME.EXT" form, lowering the case
+/// of its parts as requested by `case` flags
+fn short_name(raw: &[u8], case: u8) -> String {
+    let mut base = [0; 8];
+    base.copy_from_slice(&raw[..8]);
+    // 0xE5 is a valid first byte of a name, stored as 0x05 since it also
+    // marks deleted entries
+    if base[0] == 0x05 {
+        base[0] = 0xE5;
+    }
+    let mut ext = [0; 3];
+    ext.copy_from_slice(&raw[8..11]);
+    if case & CASE_LOWER_BASE != 0 {
+        base.make_ascii_lowercase();
+    }
+    if case & CASE_LOWER_EXT != 0 {
+        ext.make_ascii_lowercase();
+    }
+
+    let trim = |part: &[u8]| -> String {
+        let len = part
+            .iter()
+            .rposition(|&c| c != b' ' && c != 0)
+            .map_or(0, |i| i + 1);
+        // Bytes above 0x7F are OEM codepage characters, which are not
+        // supported
+        part[..len]
+            .iter()
+            .map(|&c| if c.is_ascii() { c as char } else { '_' })
+            .collect()
+    };
+    let mut name = trim(&base);
+    let ext = trim(&ext);
+    if !ext.is_empty() {
+        name.push('.');
+        name.push_str(&ext);
+    }
+    name
+}
+
+/// Checks if `name` may be used as a FAT file name
+fn validate_long_name(name: &str) -> Result<(), Errno> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.encode_utf16().count() > LFN_MAX_LEN
+        || name.chars().any(|c| c < ' ' || LFN_INVALID.contains(&c))
+    {
+        Err(Errno::InvalidArgument)
+    } else {
+        Ok(())
+    }
+}
+
+/// Converts a char of a long name to its 8.3 form, returns `None` if it
+/// cannot be represented
+fn short_name_char(ch: char) -> Option<u8> {
+    if !ch.is_ascii() {
+        return None;
+    }
+    let byte = ch.to_ascii_uppercase() as u8;
+    if byte.is_ascii_alphanumeric() || SHORT_NAME_SPECIAL.contains(&byte) {
+        Some(byte)
+    } else {
+        None
+    }
+}
+
+/// Returns the lowercase flag for a part of the name, or `None` if the
+/// part mixes letter cases and cannot be described by the flags
+fn part_case(part: &str, flag: u8) -> Option<u8> {
+    if part.chars().any(|c| c.is_ascii_uppercase()) {
+        if part.chars().any(|c| c.is_ascii_lowercase()) {
+            None
+        } else {
+            Some(0)
+        }
+    } else if part.chars().any(|c| c.is_ascii_lowercase()) {
+        Some(flag)
+    } else {
+        Some(0)
+    }
+}
+
+/// Generates the 8.3 name for a new file `name`, the way Windows does.
+/// If the name does not fit as it is, a "~N" numeric tail is appended
+/// to its basis, using the lowest N for which `exists` returns `false`.
+pub fn generate_short_name<F: Fn(&[u8; 11]) -> bool>(
+    name: &str,
+    exists: F,
+) -> Result<ShortName, Errno> {
+    validate_long_name(name)?;
+
+    // Embedded spaces and leading periods are dropped
+    let stripped: String = name
+        .trim_start_matches('.')
+        .chars()
+        .filter(|&c| c != ' ')
+        .collect();
+    let mut lossy = stripped.len() != name.len();
+    let (base_part, ext_part) = match stripped.rfind('.') {
+        Some(pos) => (&stripped[..pos], &stripped[pos + 1..]),
+        None => (stripped.as_str(), ""),
+    };
+
+    let mut convert = |part: &str, max: usize| -> Vec<u8> {
+        let mut out = Vec::new();
+        // Periods other than the last one are dropped
+        for ch in part.chars().filter(|&c| c != '.') {
+            if out.len() == max {
+                lossy = true;
+                break;
+            }
+            out.push(short_name_char(ch).unwrap_or_else(|| {
+                lossy = true;
+                b'_'
+            }));
+        }
+        out
+    };
+    let mut base = convert(base_part, 8);
+    let ext = convert(ext_part, 3);
+    lossy |= base_part.contains('.');
+    if base.is_empty() {
+        base.push(b'_');
+        lossy = true;
+    }
+
+    let mut raw = [b' '; 11];
+    raw[8..8 + ext.len()].copy_from_slice(&ext);
+
+    if !lossy {
+        raw[..base.len()].copy_from_slice(&base);
+        if exists(&raw) {
+            return Err(Errno::AlreadyExists);
+        }
+        let case = part_case(base_part, CASE_LOWER_BASE)
+            .zip(part_case(ext_part, CASE_LOWER_EXT))
+            .map(|(base, ext)| base | ext);
+        return Ok(ShortName {
+            raw,
+            case: case.unwrap_or(0),
+            needs_lfn: case.is_none(),
+        });
+    }
+
+    let mut digits = [0u8; 7];
+    for n in 1..1000000u32 {
+        // "~N" tail
+        let mut len = 0;
+        let mut value = n;
+        while value != 0 {
+            digits[len] = b'0' + (value % 10) as u8;
+            value /= 10;
+            len += 1;
+        }
+        digits[len] = b'~';
+        len += 1;
+
+        let keep = base.len().min(8 - len);
+        raw[..8].fill(b' ');
+        raw[..keep].copy_from_slice(&base[..keep]);
+        for i in 0..len {
+            raw[keep + i] = digits[len - 1 - i];
+        }
+
+        if !exists(&raw) {
+            return Ok(ShortName {
+                raw,
+                case: 0,
+                needs_lfn: true,
+            });
+        }
+    }
+
+    Err(Errno::NoSpace)
+}
+
+/// Computes the checksum of a short name, stored in each of the LFN
+/// entries belonging to it
+pub fn lfn_checksum(raw: &[u8; 11]) -> u8 {
+    raw.iter().fold(0u8, |sum, &c| {
+        ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(c)
+    })
+}
+
+/// Builds the LFN entries for `name` belonging to short name `raw`, in
+/// the order they are stored on disk: the last part of the name first
+pub fn lfn_entries(name: &str, raw: &[u8; 11]) -> Vec<[u8; 32]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    let count = (units.len() + 12) / 13;
+    // The name is terminated with a NUL and padded with 0xFFFF, unless
+    // it fills the last entry exactly
+    if units.len() % 13 != 0 {
+        units.push(0);
+        units.resize(count * 13, 0xFFFF);
+    }
+    let checksum = lfn_checksum(raw);
+
+    (0..count)
+        .rev()
+        .map(|index| {
+            let mut ent = [0u8; 32];
+            ent[0] = (index + 1) as u8;
+            if index == count - 1 {
+                ent[0] |= 0x40;
+            }
+            ent[11] = 0x0F;
+            ent[13] = checksum;
+
+            let part = &units[index * 13..index * 13 + 13];
+            let offsets = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2));
+            for (&unit, off) in part.iter().zip(offsets) {
+                ent[off..off + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            ent
+        })
+        .collect()
+}
+
+impl Dirent {
+    /// Returns `true` if `name` refers to the entry. Both long and short
+    /// names are matched, regardless of letter case.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.short_name.eq_ignore_ascii_case(name)
+    }
+
+    /// Returns `true` if the entry holds volume label instead of a file
+    pub fn is_volume_label(&self) -> bool {
+        self.attrs & 0x08 != 0 && self.attrs & 0x10 == 0
+    }
+
+    /// Builds file status from the entry attributes
+    pub fn stat(&self) -> Stat {
+        let mode = if self.attrs & 0x10 != 0 {
+            FileMode::default_dir()
+        } else if self.attrs & 0x01 != 0 {
+            // Read-only
+            FileMode::default_reg() - FileMode::USER_WRITE
+        } else {
+            FileMode::default_reg()
+        };
+        Stat {
+            mode,
+            size: if self.attrs & 0x10 != 0 { 0 } else { self.size as u64 },
+            blksize: 512,
+            rdev: 0,
+        }
+    }
 }
 
 impl Iterator for FatIterator<'_> {
-    type Item = Dirent;
+    type Item = Result<Dirent, Errno>;
 
-    fn next(&mut self) -> Option<Dirent> {
+    fn next(&mut self) -> Option<Result<Dirent, Errno>> {
         loop {
             if self.len == 0 {
                 return None;
             }
 
             if self.sector_off == 0 {
-                self.dev
-                    .read(self.sector as usize * 512, &mut self.buf)
-                    .unwrap();
+                let pos = self.sector as usize * 512;
+                if let Err(err) = self.dev.read_blocks(pos, &mut self.buf) {
+                    self.len = 0;
+                    return Some(Err(err));
+                }
             }
 
             while self.sector_off < 512 {
@@ -89,73 +468,79 @@ impl Iterator for FatIterator<'_> {
                 }
                 self.sector_off += 32;
 
+                if self.buf[off] == 0xE5 {
+                    // Deleted entry
+                    self.lfn_len = 0;
+                    continue;
+                }
+
                 // Check for LFN entries
                 if self.buf[off + 11] == 0x0F {
                     let lfn_order = self.buf[off];
                     let lfn_index = (lfn_order & 0x3F) as usize;
-                    assert!(lfn_index > 0);
-                    let mut lfn8 = [0u8; 13];
+                    if lfn_index == 0 || lfn_index > 20 {
+                        // Corrupt sequence number, fall back to the 8.3 name
+                        self.lfn_len = 0;
+                        continue;
+                    }
+                    let mut lfn16 = [0u16; 13];
 
                     for j in 0..5 {
-                        lfn8[j] = self.buf[off + 1 + j * 2];
+                        lfn16[j] = read_le16(&self.buf[off + 1 + j * 2..]);
                     }
                     for j in 0..6 {
-                        lfn8[j + 5] = self.buf[off + 14 + j * 2];
+                        lfn16[j + 5] = read_le16(&self.buf[off + 14 + j * 2..]);
                     }
                     for j in 0..2 {
-                        lfn8[j + 11] = self.buf[off + 28 + j * 2];
+                        lfn16[j + 11] = read_le16(&self.buf[off + 28 + j * 2..]);
                     }
 
-                    let len = lfn8.iter().position(|&c| c == 0).unwrap_or(13);
-                    let off = (lfn_index - 1) * 13;
+                    let len = lfn16.iter().position(|&c| c == 0).unwrap_or(13);
+                    let pos = (lfn_index - 1) * 13;
 
                     if lfn_order & 0x40 != 0 {
                         // Last entry
-                        self.lfn_len = (off + len) as u8;
-                    } else {
-                        assert_eq!(len, 13);
+                        self.lfn_len = (pos + len) as u16;
+                        self.lfn_checksum = self.buf[off + 13];
+                    } else if len != 13 {
+                        // Only the last entry may hold a partial name
+                        self.lfn_len = 0;
+                        continue;
                     }
-                    self.lfn[off..off + len].copy_from_slice(&lfn8[..len]);
+                    self.lfn[pos..pos + len].copy_from_slice(&lfn16[..len]);
                 } else {
                     let size = read_le32(&self.buf[off + 28..]);
                     let attrs = self.buf[off + 11];
                     let cluster = ((read_le16(&self.buf[off + 20..]) as u32) << 16)
                         | (read_le16(&self.buf[off + 26..]) as u32);
+                    let short = short_name(&self.buf[off..off + 11], 0);
+                    let checksum = lfn_checksum(self.buf[off..off + 11].try_into().unwrap());
+
+                    // LFN entries left behind by a system unaware of them
+                    // do not belong to the entry
+                    if self.lfn_checksum != checksum {
+                        self.lfn_len = 0;
+                    }
 
-                    if self.lfn_len != 0 {
+                    let name = if self.lfn_len != 0 {
                         let len = self.lfn_len as usize;
                         self.lfn_len = 0;
-                        return Some(Dirent {
-                            name: core::str::from_utf8(&self.lfn[..len as usize])
-                                .unwrap()
-                                .to_owned(),
-                            attrs,
-                            size,
-                            cluster,
-                        });
+                        char::decode_utf16(self.lfn[..len].iter().copied())
+                            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                            .collect()
                     } else {
-                        let len = self.buf[off..off + 11]
-                            .iter()
-                            .position(|&c| (c == 0) || (c == b' '))
-                            .unwrap_or(11);
-                        let name =
-                            core::str::from_utf8(&self.buf[off..off + core::cmp::min(len, 8)])
-                                .unwrap()
-                                .to_owned();
-                        let ext = if len > 8 {
-                            ".".to_owned()
-                                + core::str::from_utf8(&self.buf[off + 8..off + len]).unwrap()
-                        } else {
-                            "".to_owned()
-                        };
-
-                        return Some(Dirent {
-                            name: name + &ext,
-                            attrs,
-                            size,
-                            cluster,
-                        });
-                    }
+                        // Case flags set by Windows NT for names like
+                        // "readme.txt", which fit into 8.3 without LFN
+                        short_name(&self.buf[off..off + 11], self.buf[off + 12])
+                    };
+
+                    return Some(Ok(Dirent {
+                        name,
+                        short_name: short,
+                        attrs,
+                        size,
+                        cluster,
+                    }));
                 }
             }
 
@@ -174,8 +559,199 @@ impl FatIterator<'_> {
             len: sectors_per_cluster as u32,
             sector_off: 0,
             lfn_len: 0,
-            lfn: [0; 128],
+            lfn: [0; 260],
+            lfn_checksum: 0,
             buf: [0; 512],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs_testing::RamDevice;
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(short_name(b"README  TXT", 0), "README.TXT");
+        assert_eq!(short_name(b"MAKEFILE   ", 0), "MAKEFILE");
+        assert_eq!(short_name(b"A B     C  ", 0), "A B.C");
+        assert_eq!(short_name(b"README  TXT", CASE_LOWER_BASE), "readme.TXT");
+        assert_eq!(
+            short_name(b"README  TXT", CASE_LOWER_BASE | CASE_LOWER_EXT),
+            "readme.txt"
+        );
+        assert_eq!(short_name(b"\x05BC        ", 0), "_BC");
+    }
+
+    fn generate(name: &str, taken: &[&[u8; 11]]) -> Result<ShortName, Errno> {
+        generate_short_name(name, |raw| taken.contains(&raw))
+    }
+
+    #[test]
+    fn test_short_name_exact() {
+        let name = generate("README.TXT", &[]).unwrap();
+        assert_eq!(&name.raw, b"README  TXT");
+        assert_eq!(name.case, 0);
+        assert!(!name.needs_lfn);
+
+        let name = generate("readme.txt", &[]).unwrap();
+        assert_eq!(&name.raw, b"README  TXT");
+        assert_eq!(name.case, CASE_LOWER_BASE | CASE_LOWER_EXT);
+        assert!(!name.needs_lfn);
+
+        let name = generate("Makefile", &[]).unwrap();
+        assert_eq!(&name.raw, b"MAKEFILE   ");
+        assert!(name.needs_lfn);
+
+        assert_eq!(
+            generate("README.TXT", &[b"README  TXT"]),
+            Err(Errno::AlreadyExists)
+        );
+    }
+
+    #[test]
+    fn test_short_name_tail() {
+        let cases: &[(&str, &[u8; 11])] = &[
+            ("MyDocument.txt", b"MYDOCU~1TXT"),
+            ("The quick brown.fox", b"THEQUI~1FOX"),
+            (".bashrc", b"BASHRC~1   "),
+            ("foo.bar.baz", b"FOOBAR~1BAZ"),
+            ("a+b.txt", b"A_B~1   TXT"),
+            ("r\u{e9}sum\u{e9}.doc", b"R_SUM_~1DOC"),
+            ("archive.tar.gz", b"ARCHIV~1GZ "),
+            ("file.html", b"FILE~1  HTM"),
+        ];
+        for &(long, short) in cases {
+            let name = generate(long, &[]).unwrap();
+            assert_eq!(&name.raw, short, "{}", long);
+            assert!(name.needs_lfn);
+        }
+
+        let taken: Vec<[u8; 11]> = (1..10)
+            .map(|n| {
+                let mut raw = *b"MYDOCU~0TXT";
+                raw[7] += n;
+                raw
+            })
+            .collect();
+        let taken: Vec<&[u8; 11]> = taken.iter().collect();
+        assert_eq!(
+            &generate("MyDocument.txt", &taken[..1]).unwrap().raw,
+            b"MYDOCU~2TXT"
+        );
+        assert_eq!(
+            &generate("MyDocument.txt", &taken).unwrap().raw,
+            b"MYDOC~10TXT"
+        );
+    }
+
+    #[test]
+    fn test_long_name_invalid() {
+        for name in ["", ".", "..", "a:b", "what?", "tab\t", "a/b"] {
+            assert_eq!(generate(name, &[]), Err(Errno::InvalidArgument));
+        }
+        let long = "x".repeat(LFN_MAX_LEN + 1);
+        assert_eq!(generate(&long, &[]), Err(Errno::InvalidArgument));
+        assert!(generate(&long[1..], &[]).is_ok());
+    }
+
+    #[test]
+    fn test_lfn_roundtrip() {
+        let names = [
+            "MyDocument.txt",
+            "exactly13char",
+            "A rather long name, spanning several LFN entries.text",
+            "\u{43f}\u{440}\u{438}\u{432}\u{435}\u{442}.txt",
+            "readme.txt",
+        ];
+        let mut data = vec![0u8; 2 * 512];
+        let mut off = 0;
+        let mut taken = Vec::new();
+        for name in names {
+            let short = generate_short_name(name, |raw| taken.contains(raw)).unwrap();
+            taken.push(short.raw);
+            if short.needs_lfn {
+                for ent in lfn_entries(name, &short.raw) {
+                    data[off..off + 32].copy_from_slice(&ent);
+                    off += 32;
+                }
+            }
+            data[off..off + 11].copy_from_slice(&short.raw);
+            data[off + 11] = 0x20;
+            data[off + 12] = short.case;
+            off += 32;
+        }
+        // Orphaned LFN entry followed by a deleted one and a plain entry
+        let stale = lfn_entries("stale", b"STALE      ");
+        data[off..off + 32].copy_from_slice(&stale[0]);
+        data[off + 32] = 0xE5;
+        data[off + 64..off + 75].copy_from_slice(b"PLAIN      ");
+
+        let dev = RamDevice::with_data(data).leak();
+        let found: Vec<Dirent> = FatIterator::new(dev, 0, 2).collect::<Result<_, _>>().unwrap();
+        let found: Vec<&str> = found.iter().map(|ent| ent.name.as_str()).collect();
+        assert_eq!(&found[..names.len()], &names);
+        assert_eq!(&found[names.len()..], &["PLAIN"]);
+    }
+
+    #[test]
+    fn test_lfn_corrupt() {
+        let names = [
+            "MyDocument.txt",
+            "A rather long name, spanning several LFN entries.text",
+        ];
+        let mut data = vec![0u8; 512];
+        let mut off = 0;
+        let mut expected = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let short = generate_short_name(name, |_| false).unwrap();
+            let mut entries = lfn_entries(name, &short.raw);
+            if i == 0 {
+                // Sequence number out of range
+                entries[0][0] = 0x40 | 25;
+            } else {
+                // Terminator in the middle of the name
+                let last = entries.len() - 1;
+                entries[last][1..3].fill(0);
+            }
+            for ent in entries {
+                data[off..off + 32].copy_from_slice(&ent);
+                off += 32;
+            }
+            data[off..off + 11].copy_from_slice(&short.raw);
+            data[off + 11] = 0x20;
+            off += 32;
+            expected.push(short_name(&short.raw, 0));
+        }
+
+        let dev = RamDevice::with_data(data).leak();
+        let found: Vec<Dirent> = FatIterator::new(dev, 0, 1).collect::<Result<_, _>>().unwrap();
+        let found: Vec<&str> = found.iter().map(|ent| ent.name.as_str()).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_dirent_matches() {
+        let ent = Dirent {
+            name: "ReadMe.txt".into(),
+            short_name: "README.TXT".into(),
+            size: 0,
+            attrs: 0x20,
+            cluster: 3,
+        };
+        assert!(ent.matches("readme.txt"));
+        assert!(ent.matches("README.TXT"));
+        assert!(!ent.matches("readme"));
+
+        let ent = Dirent {
+            name: "Long file name.text".into(),
+            short_name: "LONGFI~1.TEX".into(),
+            size: 0,
+            attrs: 0x20,
+            cluster: 4,
+        };
+        assert!(ent.matches("long FILE name.TEXT"));
+        assert!(ent.matches("longfi~1.tex"));
+    }
+}
@@ -0,0 +1,166 @@
+//! File allocation table access
+use crate::{Bpb, OrderedWriter};
+use alloc::{boxed::Box, vec::Vec};
+use libsys::{
+    error::Errno,
+    mem::read_le32,
+};
+use vfs::BlockDevice;
+
+/// Meaningful bits of a FAT32 entry
+pub const FAT_ENTRY_MASK: u32 = 0x0FFFFFFF;
+/// Free cluster marker
+pub const FAT_FREE: u32 = 0;
+/// Bad cluster marker
+pub const FAT_BAD: u32 = 0x0FFFFFF7;
+/// Entries starting from this value mark the end of a cluster chain
+pub const FAT_EOC: u32 = 0x0FFFFFF8;
+
+/// Number of FAT sectors kept by [FatCache] by default
+pub const FAT_CACHE_SECTORS: usize = 16;
+
+struct CachedSector {
+    sector: u32,
+    data: Box<[u8; 512]>,
+    stamp: u64,
+}
+
+/// Cache of recently used sectors of the first FAT. Following a chain
+/// mostly hits the same sector, as each one holds 128 entries.
+pub struct FatCache {
+    sectors: Vec<CachedSector>,
+    capacity: usize,
+    clock: u64,
+    misses: usize,
+}
+
+/// Returns the sector (within the first FAT) and byte offset of `cluster`'s entry
+pub const fn entry_location(bpb: &Bpb, cluster: u32) -> (u32, usize) {
+    (
+        bpb.fat_base_sector(0) + cluster / 128,
+        (cluster % 128) as usize * 4,
+    )
+}
+
+/// Reads FAT entry of `cluster`
+pub fn read_entry(
+    writer: &OrderedWriter,
+    dev: &dyn BlockDevice,
+    bpb: &Bpb,
+    cluster: u32,
+) -> Result<u32, Errno> {
+    let (sector, off) = entry_location(bpb, cluster);
+    let mut buf = [0u8; 512];
+    writer.read(dev, sector, &mut buf)?;
+    Ok(read_le32(&buf[off..]) & FAT_ENTRY_MASK)
+}
+
+/// Queues an update of `cluster`'s entry in all copies of the FAT
+pub fn write_entry(
+    writer: &mut OrderedWriter,
+    dev: &dyn BlockDevice,
+    bpb: &Bpb,
+    cluster: u32,
+    value: u32,
+) -> Result<(), Errno> {
+    let (sector, off) = entry_location(bpb, cluster);
+    let rel = sector - bpb.fat_base_sector(0);
+    let mut buf = [0u8; 512];
+
+    for index in 0..bpb.fat_count() {
+        let sector = bpb.fat_base_sector(index) + rel;
+        writer.read(dev, sector, &mut buf)?;
+        // Upper 4 bits are reserved and must be preserved
+        let old = read_le32(&buf[off..]);
+        let new = (old & !FAT_ENTRY_MASK) | (value & FAT_ENTRY_MASK);
+        buf[off..off + 4].copy_from_slice(&new.to_le_bytes());
+        writer.write(sector, &buf);
+    }
+
+    Ok(())
+}
+
+impl FatCache {
+    /// Creates a cache holding at most `capacity` sectors
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sectors: Vec::with_capacity(capacity),
+            capacity,
+            clock: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the number of sector loads done by the cache
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    fn sector(
+        &mut self,
+        writer: &OrderedWriter,
+        dev: &dyn BlockDevice,
+        sector: u32,
+    ) -> Result<&[u8; 512], Errno> {
+        self.clock += 1;
+        let index = match self.sectors.iter().position(|s| s.sector == sector) {
+            Some(index) => index,
+            None => {
+                let mut data = Box::new([0u8; 512]);
+                writer.read(dev, sector, &mut data)?;
+                self.misses += 1;
+
+                let entry = CachedSector {
+                    sector,
+                    data,
+                    stamp: 0,
+                };
+                if self.sectors.len() < self.capacity {
+                    self.sectors.push(entry);
+                    self.sectors.len() - 1
+                } else {
+                    // Replace the least recently used one
+                    let (index, _) = self
+                        .sectors
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, s)| s.stamp)
+                        .unwrap();
+                    self.sectors[index] = entry;
+                    index
+                }
+            }
+        };
+        let entry = &mut self.sectors[index];
+        entry.stamp = self.clock;
+        Ok(&entry.data)
+    }
+
+    /// Reads FAT entry of `cluster`, same as [read_entry]
+    pub fn read_entry(
+        &mut self,
+        writer: &OrderedWriter,
+        dev: &dyn BlockDevice,
+        bpb: &Bpb,
+        cluster: u32,
+    ) -> Result<u32, Errno> {
+        let (sector, off) = entry_location(bpb, cluster);
+        let data = self.sector(writer, dev, sector)?;
+        Ok(read_le32(&data[off..]) & FAT_ENTRY_MASK)
+    }
+
+    /// Queues an update of `cluster`'s entry, same as [write_entry], and
+    /// drops the stale copy of its sector
+    pub fn write_entry(
+        &mut self,
+        writer: &mut OrderedWriter,
+        dev: &dyn BlockDevice,
+        bpb: &Bpb,
+        cluster: u32,
+        value: u32,
+    ) -> Result<(), Errno> {
+        let (sector, _) = entry_location(bpb, cluster);
+        self.sectors.retain(|s| s.sector != sector);
+        write_entry(writer, dev, bpb, cluster, value)
+    }
+}
@@ -1,13 +1,12 @@
-use crate::Bpb;
+use crate::{ClusterChain, FatData};
 use libsys::{
-    stat::{Stat, OpenFlags},
-    ioctl::IoctlCmd,
+    stat::{FileMode, Stat, OpenFlags},
     error::Errno
 };
-use vfs::{VnodeImpl, VnodeKind, VnodeRef};
+use vfs::{VnodeImpl, VnodeRef};
 
 pub struct FileInode {
-    pub cluster: u32,
+    chain: ClusterChain,
     pub size: u32,
 }
 
@@ -20,6 +19,15 @@ impl VnodeImpl for FileInode {
         Ok(0)
     }
 
+    fn stat(&mut self, _node: VnodeRef) -> Result<Stat, Errno> {
+        Ok(Stat {
+            mode: FileMode::default_reg(),
+            size: self.size as u64,
+            blksize: 512,
+            rdev: 0,
+        })
+    }
+
     fn read(&mut self, node: VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
         let size = self.size as usize;
         if pos >= size {
@@ -29,19 +37,26 @@ impl VnodeImpl for FileInode {
         let fs = node.fs().unwrap();
         let dev = fs.clone().dev().unwrap();
         let fs_data = fs.data();
-        let bpb: &Bpb = fs_data.as_ref().and_then(|e| e.downcast_ref()).unwrap();
-        let base_sector = bpb.cluster_base_sector(self.cluster);
+        let fat: &FatData = fs_data.as_ref().and_then(|e| e.downcast_ref()).unwrap();
+        let cluster_size = fat.bpb.sectors_per_cluster() as usize * 512;
 
         let mut rem = core::cmp::min(size - pos, data.len());
         let mut off = 0usize;
         let mut buf = [0; 512];
 
         while rem != 0 {
-            let sector_index = (pos + off) / 512;
-            let sector_offset = (pos + off) % 512;
+            let cluster_index = (pos + off) / cluster_size;
+            let cluster_offset = (pos + off) % cluster_size;
+            let sector_offset = cluster_offset % 512;
             let count = core::cmp::min(rem, 512 - sector_offset);
 
-            dev.read((base_sector as usize + sector_index) * 512, &mut buf)?;
+            let cluster = self
+                .chain
+                .cluster_at(fat, dev, cluster_index as u32)?
+                .ok_or(Errno::InvalidFile)?;
+            let sector = fat.bpb.cluster_base_sector(cluster) as usize + cluster_offset / 512;
+
+            dev.read_blocks(sector * 512, &mut buf)?;
             let src = &buf[sector_offset..sector_offset + count];
             let dst = &mut data[off..off + count];
             dst.copy_from_slice(src);
@@ -53,3 +68,13 @@ impl VnodeImpl for FileInode {
         Ok(off)
     }
 }
+
+impl FileInode {
+    /// Creates an inode of a file of `size` bytes starting at `cluster`
+    pub const fn new(cluster: u32, size: u32) -> Self {
+        Self {
+            chain: ClusterChain::new(cluster),
+            size,
+        }
+    }
+}
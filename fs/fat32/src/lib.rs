@@ -12,11 +12,8 @@ extern crate alloc;
 use alloc::{boxed::Box, rc::Rc};
 use core::any::Any;
 use core::cell::{Ref, RefCell};
-use libsys::{
-    mem::read_le32,
-    error::Errno,
-};
-use vfs::{BlockDevice, Filesystem, Vnode, VnodeKind, VnodeRef};
+use libsys::error::Errno;
+use vfs::{BlockDevice, Filesystem, IoCounters, Vnode, VnodeKind, VnodeRef};
 
 pub mod dir;
 pub use dir::{DirectoryInode, Dirent as FatEntry, FatIterator};
@@ -24,11 +21,30 @@ pub mod file;
 pub use file::FileInode;
 pub mod data;
 pub use data::Bpb;
+pub mod fat;
+pub use fat::FatCache;
+pub mod chain;
+pub use chain::ClusterChain;
+pub mod order;
+pub use order::OrderedWriter;
+pub mod check;
+pub use check::CheckReport;
+#[cfg(test)]
+mod testing;
+
+/// Filesystem state shared with the inodes through [Filesystem::data()]
+pub struct FatData {
+    pub bpb: Bpb,
+    pub writer: RefCell<OrderedWriter>,
+    pub fat: RefCell<FatCache>,
+}
 
 pub struct Fat32 {
-    bpb: RefCell<Bpb>,
+    data: RefCell<FatData>,
     root: RefCell<Option<VnodeRef>>,
     dev: &'static dyn BlockDevice,
+    check_report: CheckReport,
+    counters: IoCounters,
 }
 
 impl Filesystem for Fat32 {
@@ -41,26 +57,88 @@ impl Filesystem for Fat32 {
     }
 
     fn data(&self) -> Option<Ref<dyn Any>> {
-        Some(self.bpb.borrow())
+        Some(self.data.borrow())
+    }
+
+    fn unmount(&self) {
+        self.root.borrow_mut().take();
+    }
+
+    fn io_counters(&self) -> Option<&IoCounters> {
+        Some(&self.counters)
+    }
+
+    // Names are matched the way DOS and Windows do
+    fn case_insensitive(&self) -> bool {
+        true
+    }
+}
+
+impl FatData {
+    pub fn new(bpb: Bpb, writer: OrderedWriter) -> Self {
+        Self {
+            bpb,
+            writer: RefCell::new(writer),
+            fat: RefCell::new(FatCache::new(fat::FAT_CACHE_SECTORS)),
+        }
+    }
+
+    /// Returns the cluster following `cluster` in its chain, or `None` at
+    /// the end of the chain
+    pub fn next_cluster(&self, dev: &dyn BlockDevice, cluster: u32) -> Result<Option<u32>, Errno> {
+        let writer = self.writer.borrow();
+        let next = self
+            .fat
+            .borrow_mut()
+            .read_entry(&writer, dev, &self.bpb, cluster)?;
+        if next >= fat::FAT_EOC {
+            Ok(None)
+        } else if next < 2 || next >= self.bpb.cluster_count() + 2 {
+            // Free, bad or out of range
+            Err(Errno::InvalidFile)
+        } else {
+            Ok(Some(next))
+        }
     }
 }
 
 impl Fat32 {
-    pub fn open(dev: &'static dyn BlockDevice) -> Result<Rc<Self>, Errno> {
+    /// Opens a FAT32 filesystem on `dev`, performing a quick consistency
+    /// check. If `repair` is set, lost cluster chains found are released.
+    pub fn open(dev: &'static dyn BlockDevice, repair: bool) -> Result<Rc<Self>, Errno> {
         let mut buf = [0u8; 512];
 
-        dev.read(0, &mut buf)?;
+        dev.read_blocks(0, &mut buf)?;
 
         if buf[0x42] != 0x28 && buf[0x42] != 0x29 {
-            panic!("Not a FAT32");
+            return Err(Errno::InvalidFile);
         }
 
-        let root_cluster = read_le32(&buf[44..]);
+        let bpb = Bpb::from_sector(&buf);
+        bpb.validate()?;
+        let root_cluster = bpb.root_cluster();
+        // Sectors are assumed to be 512 bytes, so must be the device's
+        // blocks
+        if dev.block_size() != 512 {
+            return Err(Errno::InvalidArgument);
+        }
+        match dev.size() {
+            Ok(size) if size < bpb.total_sectors() as usize * 512 => {
+                return Err(Errno::InvalidFile);
+            }
+            // Devices which don't know their size are trusted
+            Ok(_) | Err(Errno::NotImplemented) => (),
+            Err(e) => return Err(e),
+        }
+        let mut writer = OrderedWriter::new();
+        let check_report = check::check(dev, &bpb, &mut writer, repair)?;
 
         let res = Rc::new(Self {
-            bpb: RefCell::new(Bpb::from_sector(&buf)),
+            data: RefCell::new(FatData::new(bpb, writer)),
             dev,
             root: RefCell::new(None),
+            check_report,
+            counters: IoCounters::new(),
         });
 
         let root = Vnode::new("", VnodeKind::Directory, Vnode::SEEKABLE);
@@ -72,4 +150,57 @@ impl Fat32 {
 
         Ok(res)
     }
+
+    /// Returns the results of the mount-time consistency check
+    pub fn check_report(&self) -> CheckReport {
+        self.check_report
+    }
+
+    /// Writes out all the pending metadata updates in order
+    pub fn sync(&self) -> Result<(), Errno> {
+        self.data.borrow().writer.borrow_mut().flush(self.dev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{image, RamDevice};
+
+    #[test]
+    fn test_open_device_size() {
+        let dev = RamDevice::with_data(image()).leak();
+        assert!(Fat32::open(dev, false).is_ok());
+
+        // Image cut short of the size in the BPB
+        let mut data = image();
+        data.truncate(data.len() - 512);
+        let dev = RamDevice::with_data(data).leak();
+        assert_eq!(Fat32::open(dev, false).err(), Some(Errno::InvalidFile));
+    }
+
+    #[test]
+    fn test_open_bad_bpb() {
+        let patches: [(usize, &[u8]); 7] = [
+            // Sectors per cluster: zero, not a power of two
+            (13, &[0]),
+            (13, &[3]),
+            // Reserved sectors
+            (14, &[0, 0]),
+            // FAT count
+            (16, &[0]),
+            // Total sectors: less than the FATs take
+            (32, &[1, 0, 0, 0]),
+            // Root cluster: reserved, past the last cluster
+            (44, &[1, 0, 0, 0]),
+            (44, &[0, 0, 0, 0x0F]),
+        ];
+
+        for (offset, bytes) in patches {
+            let mut data = image();
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            let dev = RamDevice::with_data(data).leak();
+            assert_eq!(Fat32::open(dev, false).err(), Some(Errno::InvalidFile), "{}", offset);
+        }
+    }
 }
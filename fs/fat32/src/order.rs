@@ -0,0 +1,142 @@
+//! Ordering layer for metadata writes.
+//!
+//! Writes are queued in epochs separated by barriers. When flushed, every
+//! write of an epoch reaches the device before any write of the next one,
+//! so that e.g. FAT entries of a newly allocated chain are on the media
+//! before a directory entry referring to the chain is.
+use alloc::{boxed::Box, vec::Vec};
+use libsys::error::Errno;
+use vfs::BlockDevice;
+
+struct PendingSector {
+    sector: u32,
+    data: Box<[u8; 512]>,
+}
+
+/// Queue of ordered sector writes
+#[derive(Default)]
+pub struct OrderedWriter {
+    epochs: Vec<Vec<PendingSector>>,
+}
+
+impl OrderedWriter {
+    pub const fn new() -> Self {
+        Self { epochs: Vec::new() }
+    }
+
+    /// Returns `true` if there are no writes pending
+    pub fn is_empty(&self) -> bool {
+        self.epochs.iter().all(Vec::is_empty)
+    }
+
+    /// Queues a write of `data` to `sector`. Repeated writes to the same
+    /// sector within an epoch are merged.
+    pub fn write(&mut self, sector: u32, data: &[u8; 512]) {
+        if self.epochs.is_empty() {
+            self.epochs.push(Vec::new());
+        }
+        let epoch = self.epochs.last_mut().unwrap();
+        if let Some(pending) = epoch.iter_mut().find(|p| p.sector == sector) {
+            pending.data.copy_from_slice(data);
+        } else {
+            epoch.push(PendingSector {
+                sector,
+                data: Box::new(*data),
+            });
+        }
+    }
+
+    /// Makes sure all the writes queued so far reach the device before
+    /// any write queued after the barrier
+    pub fn barrier(&mut self) {
+        if self.epochs.last().map(|e| !e.is_empty()).unwrap_or(false) {
+            self.epochs.push(Vec::new());
+        }
+    }
+
+    /// Reads `sector` into `buf`, taking pending writes into account
+    pub fn read(&self, dev: &dyn BlockDevice, sector: u32, buf: &mut [u8; 512]) -> Result<(), Errno> {
+        for epoch in self.epochs.iter().rev() {
+            if let Some(pending) = epoch.iter().find(|p| p.sector == sector) {
+                buf.copy_from_slice(&pending.data[..]);
+                return Ok(());
+            }
+        }
+        dev.read_blocks(sector as usize * 512, buf)
+    }
+
+    /// Writes out all the pending sectors in order. If a write fails, the
+    /// epoch containing it and all the later ones are kept queued.
+    pub fn flush(&mut self, dev: &dyn BlockDevice) -> Result<(), Errno> {
+        while !self.epochs.is_empty() {
+            let epoch = &mut self.epochs[0];
+            epoch.sort_by_key(|p| p.sector);
+            while let Some(pending) = epoch.first() {
+                dev.write_blocks(pending.sector as usize * 512, &pending.data[..])?;
+                epoch.remove(0);
+            }
+            // The device may reorder writes sitting in its cache
+            dev.flush()?;
+            self.epochs.remove(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use std::vec::Vec;
+    use vfs::BlockError;
+
+    #[derive(Default)]
+    struct LogDevice {
+        log: RefCell<Vec<(usize, u8)>>,
+        // Length of the log at each flush
+        flushes: RefCell<Vec<usize>>,
+    }
+
+    impl BlockDevice for LogDevice {
+        fn read(&self, _pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+            buf.fill(0xAA);
+            Ok(())
+        }
+
+        fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+            self.log.borrow_mut().push((pos / 512, buf[0]));
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<(), libsys::error::Errno> {
+            self.flushes.borrow_mut().push(self.log.borrow().len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ordered_writes() {
+        let dev = LogDevice::default();
+        let mut writer = OrderedWriter::new();
+        let mut buf = [0u8; 512];
+
+        writer.write(20, &[1; 512]);
+        writer.write(10, &[2; 512]);
+        writer.write(20, &[3; 512]);
+        writer.barrier();
+        writer.barrier();
+        writer.write(5, &[4; 512]);
+
+        // Pending data is visible to reads
+        writer.read(&dev, 20, &mut buf).unwrap();
+        assert_eq!(buf[0], 3);
+        writer.read(&dev, 21, &mut buf).unwrap();
+        assert_eq!(buf[0], 0xAA);
+
+        writer.flush(&dev).unwrap();
+        assert!(writer.is_empty());
+        assert_eq!(&*dev.log.borrow(), &[(10, 2), (20, 3), (5, 4)]);
+        // Every epoch is flushed before the next one is written
+        assert_eq!(&*dev.flushes.borrow(), &[2, 3]);
+    }
+}
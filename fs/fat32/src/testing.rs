@@ -0,0 +1,49 @@
+//! Helpers for building FAT32 images in memory
+use alloc::vec::Vec;
+pub use fs_testing::RamDevice;
+
+/// Size of the test image in sectors
+pub const SECTORS: usize = 160;
+/// Number of sectors before the first FAT
+pub const RESERVED: usize = 32;
+
+/// Sets FAT entry of `cluster` in both copies of the FAT
+pub fn set_fat(data: &mut [u8], cluster: usize, value: u32) {
+    for index in 0..2 {
+        let off = (RESERVED + index) * 512 + cluster * 4;
+        data[off..off + 4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Sets directory entry `index` of the root directory
+pub fn set_dirent(data: &mut [u8], index: usize, name: &[u8; 11], attrs: u8, cluster: u32) {
+    // Root directory occupies cluster 2, the first data sector
+    let off = (RESERVED + 2) * 512 + index * 32;
+    data[off..off + 11].copy_from_slice(name);
+    data[off + 11] = attrs;
+    data[off + 20..off + 22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    data[off + 26..off + 28].copy_from_slice(&(cluster as u16).to_le_bytes());
+}
+
+/// Builds an image of 1 sector per cluster with 2 FATs of 1 sector each,
+/// containing a single file FILE.TXT of clusters 3 and 4
+pub fn image() -> Vec<u8> {
+    let mut data = vec![0u8; SECTORS * 512];
+    data[11..13].copy_from_slice(&512u16.to_le_bytes());
+    data[13] = 1;
+    data[14..16].copy_from_slice(&(RESERVED as u16).to_le_bytes());
+    data[16] = 2;
+    data[32..36].copy_from_slice(&(SECTORS as u32).to_le_bytes());
+    data[36..40].copy_from_slice(&1u32.to_le_bytes());
+    data[44..48].copy_from_slice(&2u32.to_le_bytes());
+    data[0x42] = 0x29;
+
+    set_fat(&mut data, 0, 0x0FFFFFF8);
+    set_fat(&mut data, 1, 0x0FFFFFFF);
+    set_fat(&mut data, 2, 0x0FFFFFFF);
+    // FILE.TXT: 3 -> 4
+    set_dirent(&mut data, 0, b"FILE    TXT", 0x20, 3);
+    set_fat(&mut data, 3, 4);
+    set_fat(&mut data, 4, 0x0FFFFFFF);
+    data
+}
@@ -0,0 +1,127 @@
+//! Tests against an image made and populated by Linux
+use fat32::{dir, Bpb, Fat32, FatIterator};
+use fs_testing::{walk, IoOp, RamDevice};
+use libsys::error::Errno;
+use std::rc::Rc;
+use vfs::{BlockDevice, BlockError, Filesystem};
+
+const IMAGE: &str = "fat32-mkfs.img";
+
+// Path, whether it's a directory, size
+const TREE: &[(&str, bool, u64)] = &[
+    ("CARGO.TOML", false, 274),
+    ("DIR0", true, 0),
+    ("FILENAME.TXT", false, 15),
+    ("LONGDIR1LONGDIR1.EXT", true, 0),
+    ("LONGDIR2LONGDIR2", true, 0),
+    ("LONGDIR3LONGDIR3LONGDIR3LONGDIR3", true, 0),
+    ("LONGDIRNAME0", true, 0),
+];
+
+fn open(dev: &'static RamDevice) -> Rc<Fat32> {
+    let fs = Fat32::open(dev, false).unwrap();
+    assert!(fs.check_report().is_clean());
+    fs
+}
+
+#[test]
+fn test_read_tree() {
+    let dev = RamDevice::from_image(IMAGE).leak();
+    let root = open(dev).root().unwrap();
+
+    let tree = walk(&root).unwrap();
+    let listed: Vec<_> = tree
+        .iter()
+        .map(|ent| (ent.path.as_str(), ent.is_dir(), ent.stat.size))
+        .collect();
+    assert_eq!(listed, TREE);
+
+    let file = tree.iter().find(|ent| ent.path == "FILENAME.TXT").unwrap();
+    assert_eq!(file.data.as_deref(), Some(&b"This is a file\n"[..]));
+    let file = tree.iter().find(|ent| ent.path == "CARGO.TOML").unwrap();
+    assert!(file
+        .data
+        .as_ref()
+        .unwrap()
+        .starts_with(b"[package]\nname = \"fat32\""));
+}
+
+#[test]
+fn test_lookup_names() {
+    let dev = RamDevice::from_image(IMAGE).leak();
+    let root = open(dev).root().unwrap();
+
+    let file = root.lookup_or_load("CARGO.TOML").unwrap();
+    for name in ["cargo.toml", "Cargo.Toml", "CARGO~1.TOM", "cargo~1.tom"] {
+        assert!(Rc::ptr_eq(&root.lookup_or_load(name).unwrap(), &file));
+    }
+    assert_eq!(file.name(), "CARGO.TOML");
+    assert_eq!(
+        root.lookup_or_load("CARGO.TOM").err(),
+        Some(Errno::DoesNotExist)
+    );
+}
+
+// Short names generated for new files match the ones Linux made for the
+// same long names
+#[test]
+fn test_short_names() {
+    let dev = RamDevice::from_image(IMAGE).leak();
+    let mut sector = [0u8; 512];
+    dev.read_blocks(0, &mut sector).unwrap();
+    let bpb = Bpb::from_sector(&sector);
+
+    let mut taken: Vec<[u8; 11]> = Vec::new();
+    let entries = FatIterator::new(
+        dev,
+        bpb.cluster_base_sector(bpb.root_cluster()),
+        bpb.sectors_per_cluster(),
+    );
+    for ent in entries.map(Result::unwrap).filter(|ent| !ent.is_volume_label()) {
+        let name = dir::generate_short_name(&ent.name, |raw| taken.contains(raw)).unwrap();
+        let (base, ext) = name.raw.split_at(8);
+        let base = std::str::from_utf8(base).unwrap().trim_end();
+        let ext = std::str::from_utf8(ext).unwrap().trim_end();
+        let short = if ext.is_empty() {
+            base.to_owned()
+        } else {
+            format!("{}.{}", base, ext)
+        };
+        assert_eq!(short, ent.short_name, "{}", ent.name);
+        assert_eq!(name.needs_lfn, ent.name != ent.short_name, "{}", ent.name);
+        taken.push(name.raw);
+    }
+    assert_eq!(taken.len(), TREE.len());
+}
+
+#[test]
+fn test_read_errors() {
+    let dev = RamDevice::from_image(IMAGE).leak();
+    let root = open(dev).root().unwrap();
+    let file = root.lookup_or_load("CARGO.TOML").unwrap();
+    let mut buf = [0u8; 274];
+
+    // Transient errors are retried
+    dev.inject(IoOp::Read, 0, 2, BlockError::Timeout);
+    assert_eq!(file.read(0, &mut buf), Ok(274));
+
+    dev.inject(IoOp::Read, 0, 1, BlockError::MediaGone);
+    assert_eq!(file.read(0, &mut buf), Err(Errno::DeviceError));
+    assert_eq!(file.read(0, &mut buf), Ok(274));
+
+    // Directory reads fail instead of panicking
+    dev.inject(IoOp::Read, 0, 1, BlockError::MediaGone);
+    assert_eq!(root.lookup_or_load("FILENAME.TXT").err(), Some(Errno::DeviceError));
+    assert!(root.lookup_or_load("FILENAME.TXT").is_ok());
+}
+
+#[test]
+fn test_device_geometry() {
+    let dev = RamDevice::from_image(IMAGE).block_size(4096).leak();
+    assert_eq!(Fat32::open(dev, false).err(), Some(Errno::InvalidArgument));
+
+    let mut data = RamDevice::from_image(IMAGE).snapshot();
+    data.truncate(data.len() / 2);
+    let dev = RamDevice::with_data(data).leak();
+    assert_eq!(Fat32::open(dev, false).err(), Some(Errno::InvalidFile));
+}
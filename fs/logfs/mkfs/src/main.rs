@@ -0,0 +1,81 @@
+//! Host tool for creating logfs images.
+//!
+//! Usage: mkfs-logfs IMAGE [SIZE_MIB]
+//!
+//! If SIZE_MIB is given, the image is created/resized to that size first.
+use libsys::error::Errno;
+use logfs::LogFs;
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::process::exit;
+use vfs::{BlockDevice, BlockError};
+
+struct FileDevice {
+    file: RefCell<File>,
+    size: usize,
+}
+
+impl BlockDevice for FileDevice {
+    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(pos as u64))
+            .and_then(|_| file.read_exact(buf))
+            .map_err(|_| BlockError::Other(Errno::DeviceError))
+    }
+
+    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(pos as u64))
+            .and_then(|_| file.write_all(buf))
+            .map_err(|_| BlockError::Other(Errno::DeviceError))
+    }
+
+    fn size(&self) -> Result<usize, Errno> {
+        Ok(self.size)
+    }
+
+    fn flush(&self) -> Result<(), Errno> {
+        self.file
+            .borrow()
+            .sync_data()
+            .map_err(|_| Errno::DeviceError)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: {} IMAGE [SIZE_MIB]", args[0]);
+        exit(1);
+    }
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(args.len() == 3)
+        .open(&args[1])
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", args[1], e);
+            exit(1);
+        });
+    if let Some(size) = args.get(2) {
+        let size: u64 = size.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid size: {}", size);
+            exit(1);
+        });
+        file.set_len(size << 20).unwrap();
+    }
+    let size = file.metadata().unwrap().len() as usize;
+
+    // The filesystem is only used during this process lifetime
+    let dev = Box::leak(Box::new(FileDevice {
+        file: RefCell::new(file),
+        size,
+    }));
+    if let Err(e) = LogFs::format(dev) {
+        eprintln!("Failed to create filesystem: {:?}", e);
+        exit(1);
+    }
+    dev.file.borrow_mut().sync_all().unwrap();
+}
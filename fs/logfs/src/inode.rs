@@ -0,0 +1,76 @@
+use crate::{layout::InodeKind, LogFs};
+use alloc::{boxed::Box, rc::Rc};
+use libsys::{
+    error::Errno,
+    stat::{OpenFlags, Stat},
+};
+use vfs::{Vnode, VnodeImpl, VnodeKind, VnodeRef};
+
+pub struct LogInode {
+    fs: Rc<LogFs>,
+    ino: u32,
+}
+
+#[auto_inode(error)]
+impl VnodeImpl for LogInode {
+    fn create(&mut self, _at: VnodeRef, name: &str, kind: VnodeKind) -> Result<VnodeRef, Errno> {
+        let (ino_kind, flags) = match kind {
+            VnodeKind::Regular => (InodeKind::Regular, Vnode::SEEKABLE),
+            VnodeKind::Directory => (InodeKind::Directory, Vnode::SEEKABLE | Vnode::CACHE_READDIR),
+            _ => return Err(Errno::NotImplemented),
+        };
+        let ino = self.fs.create(self.ino, name, ino_kind)?;
+        let vnode = Vnode::new(name, kind, flags);
+        vnode.set_data(Box::new(LogInode::new(self.fs.clone(), ino)));
+        Ok(vnode)
+    }
+
+    fn remove(&mut self, _at: VnodeRef, name: &str) -> Result<(), Errno> {
+        self.fs.remove(self.ino, name)
+    }
+
+    fn lookup(&mut self, _at: VnodeRef, _name: &str) -> Result<VnodeRef, Errno> {
+        // The whole tree is loaded at mount
+        Err(Errno::DoesNotExist)
+    }
+
+    fn open(&mut self, _node: VnodeRef, _opts: OpenFlags) -> Result<usize, Errno> {
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: VnodeRef) -> Result<(), Errno> {
+        Ok(())
+    }
+
+    fn read(&mut self, _node: VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
+        self.fs.log().borrow().read(self.ino, pos, data)
+    }
+
+    fn write(&mut self, _node: VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Errno> {
+        self.fs.log().borrow_mut().write(self.ino, pos, data)
+    }
+
+    fn truncate(&mut self, _node: VnodeRef, size: usize) -> Result<(), Errno> {
+        self.fs.log().borrow_mut().truncate(self.ino, size)
+    }
+
+    fn size(&mut self, _node: VnodeRef) -> Result<usize, Errno> {
+        Ok(self.fs.log().borrow().read_inode(self.ino)?.size as usize)
+    }
+
+    fn stat(&mut self, node: VnodeRef) -> Result<Stat, Errno> {
+        let props = node.props();
+        Ok(Stat {
+            size: self.fs.log().borrow().read_inode(self.ino)?.size,
+            blksize: crate::layout::BLOCK_SIZE as u32,
+            mode: props.mode,
+            rdev: 0,
+        })
+    }
+}
+
+impl LogInode {
+    pub fn new(fs: Rc<LogFs>, ino: u32) -> Self {
+        Self { fs, ino }
+    }
+}
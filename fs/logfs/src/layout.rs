@@ -0,0 +1,173 @@
+//! On-disk structures
+use alloc::vec::Vec;
+use libsys::mem::read_le32;
+
+/// Size of a filesystem block
+pub const BLOCK_SIZE: usize = 4096;
+/// Number of blocks in a segment, including the summary block
+pub const SEGMENT_BLOCKS: u64 = 32;
+/// Number of blocks in a segment available for data
+pub const SEGMENT_DATA_BLOCKS: usize = SEGMENT_BLOCKS as usize - 1;
+/// Inode number of the root directory
+pub const ROOT_INO: u32 = 1;
+/// Number of inode map entries in a block
+pub const IMAP_ENTRIES: usize = BLOCK_SIZE / 8;
+/// Maximum number of data blocks of an inode
+pub const INODE_BLOCKS: usize = (BLOCK_SIZE - INODE_HEADER) / 8;
+/// Size of a directory entry
+pub const DIRENT_SIZE: usize = 64;
+/// Maximum length of a file name
+pub const NAME_MAX: usize = DIRENT_SIZE - 5;
+
+pub const SUPERBLOCK_MAGIC: &[u8; 8] = b"LOGFS001";
+pub const CHECKPOINT_MAGIC: &[u8; 8] = b"LOGFSCKP";
+/// Block addresses of the two alternating checkpoint slots
+pub const CHECKPOINT_SLOTS: [u64; 2] = [1, 2];
+
+const INODE_HEADER: usize = 64;
+const CHECKPOINT_HEADER: usize = 40;
+/// Maximum number of inode map blocks referenced by a checkpoint
+pub const IMAP_BLOCKS_MAX: usize = (BLOCK_SIZE - CHECKPOINT_HEADER) / 8;
+
+/// Summary owner value for inode map blocks
+pub const OWNER_IMAP: u32 = u32::MAX;
+/// Summary block index value for inode blocks
+pub const INDEX_INODE: u32 = u32::MAX;
+
+pub fn read_le64(src: &[u8]) -> u64 {
+    (read_le32(src) as u64) | ((read_le32(&src[4..]) as u64) << 32)
+}
+
+pub fn write_le32(dst: &mut [u8], v: u32) {
+    dst[..4].copy_from_slice(&v.to_le_bytes());
+}
+
+pub fn write_le64(dst: &mut [u8], v: u64) {
+    dst[..8].copy_from_slice(&v.to_le_bytes());
+}
+
+/// Filesystem superblock (block 0)
+pub struct Superblock {
+    pub segment_count: u32,
+}
+
+/// Checkpoint: consistent snapshot of log metadata
+pub struct Checkpoint {
+    pub sequence: u64,
+    pub head: u64,
+    pub imap: Vec<u64>,
+}
+
+/// Inode kinds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InodeKind {
+    Regular = 1,
+    Directory = 2,
+}
+
+/// Inode, stored one per block
+pub struct RawInode {
+    pub kind: InodeKind,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub blocks: Vec<u64>,
+}
+
+impl Superblock {
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf.fill(0);
+        buf[..8].copy_from_slice(SUPERBLOCK_MAGIC);
+        write_le32(&mut buf[8..], self.segment_count);
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if &buf[..8] != SUPERBLOCK_MAGIC {
+            return None;
+        }
+        Some(Self {
+            segment_count: read_le32(&buf[8..]),
+        })
+    }
+}
+
+impl Checkpoint {
+    pub fn encode(&self, buf: &mut [u8]) {
+        assert!(self.imap.len() <= IMAP_BLOCKS_MAX);
+        buf.fill(0);
+        buf[..8].copy_from_slice(CHECKPOINT_MAGIC);
+        write_le64(&mut buf[8..], self.sequence);
+        write_le64(&mut buf[16..], self.head);
+        write_le32(&mut buf[24..], self.imap.len() as u32);
+        for (i, &addr) in self.imap.iter().enumerate() {
+            write_le64(&mut buf[CHECKPOINT_HEADER + i * 8..], addr);
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if &buf[..8] != CHECKPOINT_MAGIC {
+            return None;
+        }
+        let count = read_le32(&buf[24..]) as usize;
+        if count > IMAP_BLOCKS_MAX {
+            return None;
+        }
+        Some(Self {
+            sequence: read_le64(&buf[8..]),
+            head: read_le64(&buf[16..]),
+            imap: (0..count)
+                .map(|i| read_le64(&buf[CHECKPOINT_HEADER + i * 8..]))
+                .collect(),
+        })
+    }
+}
+
+impl RawInode {
+    pub fn new(kind: InodeKind, mode: u32) -> Self {
+        Self {
+            kind,
+            mode,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        assert!(self.blocks.len() <= INODE_BLOCKS);
+        buf.fill(0);
+        buf[0] = self.kind as u8;
+        write_le32(&mut buf[4..], self.mode);
+        write_le32(&mut buf[8..], self.uid);
+        write_le32(&mut buf[12..], self.gid);
+        write_le64(&mut buf[16..], self.size);
+        write_le32(&mut buf[24..], self.blocks.len() as u32);
+        for (i, &addr) in self.blocks.iter().enumerate() {
+            write_le64(&mut buf[INODE_HEADER + i * 8..], addr);
+        }
+    }
+
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let kind = match buf[0] {
+            1 => InodeKind::Regular,
+            2 => InodeKind::Directory,
+            _ => return None,
+        };
+        let count = read_le32(&buf[24..]) as usize;
+        if count > INODE_BLOCKS {
+            return None;
+        }
+        Some(Self {
+            kind,
+            mode: read_le32(&buf[4..]),
+            uid: read_le32(&buf[8..]),
+            gid: read_le32(&buf[12..]),
+            size: read_le64(&buf[16..]),
+            blocks: (0..count)
+                .map(|i| read_le64(&buf[INODE_HEADER + i * 8..]))
+                .collect(),
+        })
+    }
+}
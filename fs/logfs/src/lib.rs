@@ -0,0 +1,336 @@
+//! Experimental log-structured filesystem.
+//!
+//! All the updates are appended to the log, which is split into fixed-size
+//! segments, so the media sees mostly sequential writes. Inodes are located
+//! through an inode map written at checkpoints. Segments with few live
+//! blocks are cleaned by copying the live blocks to the head of the log.
+//!
+//! Limitations of the prototype: one inode per block, direct block pointers
+//! only (files up to ~2MiB), and the whole directory tree is loaded at mount.
+//! File data is only guaranteed to survive a crash after [LogFs::sync].
+#![no_std]
+
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+#[macro_use]
+extern crate fs_macros;
+
+extern crate alloc;
+
+use alloc::{boxed::Box, rc::Rc, string::String, vec, vec::Vec};
+use core::any::Any;
+use core::cell::{Ref, RefCell};
+use libsys::{
+    error::Errno,
+    mem::read_le32,
+    stat::{FileMode, GroupId, UserId},
+};
+use vfs::{BlockDevice, Filesystem, IoCounters, Vnode, VnodeKind, VnodeRef};
+
+pub mod layout;
+use layout::{InodeKind, RawInode, DIRENT_SIZE, NAME_MAX, ROOT_INO};
+mod log;
+pub use log::Log;
+mod inode;
+use inode::LogInode;
+
+/// Log-structured filesystem instance
+pub struct LogFs {
+    log: RefCell<Log>,
+    root: RefCell<Option<VnodeRef>>,
+    dev: &'static dyn BlockDevice,
+    counters: IoCounters,
+}
+
+impl Filesystem for LogFs {
+    fn root(self: Rc<Self>) -> Result<VnodeRef, Errno> {
+        self.root.borrow().clone().ok_or(Errno::DoesNotExist)
+    }
+
+    fn dev(self: Rc<Self>) -> Option<&'static dyn BlockDevice> {
+        Some(self.dev)
+    }
+
+    fn data(&self) -> Option<Ref<dyn Any>> {
+        None
+    }
+
+    fn unmount(&self) {
+        self.root.borrow_mut().take();
+    }
+
+    fn io_counters(&self) -> Option<&IoCounters> {
+        Some(&self.counters)
+    }
+}
+
+impl LogFs {
+    /// Creates an empty filesystem on `dev`
+    pub fn format(dev: &'static dyn BlockDevice) -> Result<(), Errno> {
+        Log::format(dev).map(|_| ())
+    }
+
+    /// Opens a filesystem on `dev`, loading its directory tree
+    pub fn open(dev: &'static dyn BlockDevice) -> Result<Rc<Self>, Errno> {
+        let res = Rc::new(Self {
+            log: RefCell::new(Log::open(dev)?),
+            root: RefCell::new(None),
+            dev,
+            counters: IoCounters::new(),
+        });
+
+        let root = res.clone().load_node("", ROOT_INO)?;
+        *res.root.borrow_mut() = Some(root);
+
+        Ok(res)
+    }
+
+    /// Writes out a checkpoint, making all the changes so far durable
+    pub fn sync(&self) -> Result<(), Errno> {
+        self.log.borrow_mut().checkpoint()
+    }
+
+    /// Runs the segment cleaner once. Returns `false` if there was nothing
+    /// to clean.
+    pub fn clean(&self) -> Result<bool, Errno> {
+        self.log.borrow_mut().clean()
+    }
+
+    pub(crate) fn log(&self) -> &RefCell<Log> {
+        &self.log
+    }
+
+    fn load_node(self: Rc<Self>, name: &str, ino: u32) -> Result<VnodeRef, Errno> {
+        let inode = self.log.borrow().read_inode(ino)?;
+        let node = self.clone().make_node(name, ino, &inode);
+
+        if inode.kind == InodeKind::Directory {
+            for (child_ino, child_name) in self.dir_entries(ino)? {
+                let child = self.clone().load_node(&child_name, child_ino)?;
+                node.attach(child);
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn make_node(self: Rc<Self>, name: &str, ino: u32, inode: &RawInode) -> VnodeRef {
+        let (kind, ty) = match inode.kind {
+            InodeKind::Regular => (VnodeKind::Regular, FileMode::S_IFREG),
+            InodeKind::Directory => (VnodeKind::Directory, FileMode::S_IFDIR),
+        };
+        let flags = match kind {
+            VnodeKind::Directory => Vnode::SEEKABLE | Vnode::CACHE_READDIR,
+            _ => Vnode::SEEKABLE,
+        };
+        let node = Vnode::new(name, kind, flags);
+        {
+            let mut props = node.props_mut();
+            props.mode = FileMode::from_bits_truncate(inode.mode) | ty;
+            props.uid = UserId::from(inode.uid);
+            props.gid = GroupId::from(inode.gid);
+        }
+        node.set_data(Box::new(LogInode::new(self.clone(), ino)));
+        node.set_fs(self);
+        node
+    }
+
+    fn dir_entries(&self, dir: u32) -> Result<Vec<(u32, String)>, Errno> {
+        let log = self.log.borrow();
+        let size = log.read_inode(dir)?.size as usize;
+        let mut data = vec![0u8; size];
+        log.read(dir, 0, &mut data)?;
+
+        let mut entries = Vec::new();
+        for ent in data.chunks_exact(DIRENT_SIZE) {
+            let ino = read_le32(ent);
+            if ino == 0 {
+                continue;
+            }
+            let name = core::str::from_utf8(dirent_name(ent)?).map_err(|_| Errno::InvalidArgument)?;
+            entries.push((ino, String::from(name)));
+        }
+        Ok(entries)
+    }
+
+    /// Finds directory entry slot containing `name`, or a free one if `name` is `None`
+    fn dir_find(&self, dir: u32, name: Option<&str>) -> Result<Option<usize>, Errno> {
+        let log = self.log.borrow();
+        let size = log.read_inode(dir)?.size as usize;
+        let mut ent = [0u8; DIRENT_SIZE];
+        for off in (0..size).step_by(DIRENT_SIZE) {
+            log.read(dir, off, &mut ent)?;
+            let ino = read_le32(&ent);
+            let found = match name {
+                None => ino == 0,
+                Some(name) => ino != 0 && dirent_name(&ent)? == name.as_bytes(),
+            };
+            if found {
+                return Ok(Some(off));
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn create(&self, dir: u32, name: &str, kind: InodeKind) -> Result<u32, Errno> {
+        if name.len() > NAME_MAX {
+            return Err(Errno::InvalidArgument);
+        }
+        let mode = match kind {
+            InodeKind::Regular => 0o644,
+            InodeKind::Directory => 0o755,
+        };
+        let ino = self.log.borrow_mut().create_inode(&RawInode::new(kind, mode))?;
+
+        let mut ent = [0u8; DIRENT_SIZE];
+        ent[..4].copy_from_slice(&ino.to_le_bytes());
+        ent[4] = name.len() as u8;
+        ent[5..5 + name.len()].copy_from_slice(name.as_bytes());
+        let off = match self.dir_find(dir, None)? {
+            Some(off) => off,
+            None => self.log.borrow().read_inode(dir)?.size as usize,
+        };
+        let mut log = self.log.borrow_mut();
+        log.write(dir, off, &ent)?;
+        log.checkpoint()?;
+        Ok(ino)
+    }
+
+    pub(crate) fn remove(&self, dir: u32, name: &str) -> Result<(), Errno> {
+        let off = self.dir_find(dir, Some(name))?.ok_or(Errno::DoesNotExist)?;
+        let mut ent = [0u8; DIRENT_SIZE];
+        let mut log = self.log.borrow_mut();
+        log.read(dir, off, &mut ent)?;
+        let ino = read_le32(&ent);
+        // The entry is erased first, so a crash can only leak the inode
+        log.write(dir, off, &[0u8; DIRENT_SIZE])?;
+        log.remove_inode(ino)?;
+        log.checkpoint()
+    }
+}
+
+fn dirent_name(ent: &[u8]) -> Result<&[u8], Errno> {
+    let len = ent[4] as usize;
+    if len > NAME_MAX {
+        return Err(Errno::InvalidFile);
+    }
+    Ok(&ent[5..5 + len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libsys::stat::OpenFlags;
+    use fs_testing::RamDevice;
+
+    fn device(segments: usize) -> &'static RamDevice {
+        let size = segments * layout::SEGMENT_BLOCKS as usize * layout::BLOCK_SIZE;
+        RamDevice::new(size).leak()
+    }
+
+    #[test]
+    fn test_create_remount() {
+        let dev = device(8);
+        LogFs::format(dev).unwrap();
+
+        let fs = LogFs::open(dev).unwrap();
+        let root = fs.clone().root().unwrap();
+        let dir = root
+            .create("dir", FileMode::default_dir(), VnodeKind::Directory)
+            .unwrap();
+        let file = dir
+            .create("file", FileMode::default_reg(), VnodeKind::Regular)
+            .unwrap();
+        let data = [0x5Au8; 5000];
+        assert_eq!(file.write(100, &data).unwrap(), 5000);
+        assert_eq!(file.size().unwrap(), 5100);
+        fs.sync().unwrap();
+
+        let fs = LogFs::open(dev).unwrap();
+        let root = fs.root().unwrap();
+        let dir = root.lookup("dir").unwrap();
+        let file = dir.lookup("file").unwrap();
+        let mut buf = [0xFFu8; 200];
+        assert_eq!(file.read(0, &mut buf).unwrap(), 200);
+        assert!(buf[..100].iter().all(|&b| b == 0));
+        assert!(buf[100..].iter().all(|&b| b == 0x5A));
+        assert!(file.open(OpenFlags::O_RDONLY).is_ok());
+
+        dir.unlink("file").unwrap();
+        let fs = LogFs::open(dev).unwrap();
+        let dir = fs.root().unwrap().lookup("dir").unwrap();
+        assert!(dir.lookup("file").is_none());
+    }
+
+    #[test]
+    fn test_cleaning() {
+        let dev = device(8);
+        LogFs::format(dev).unwrap();
+        let fs = LogFs::open(dev).unwrap();
+        let file = fs
+            .clone()
+            .root()
+            .unwrap()
+            .create("file", FileMode::default_reg(), VnodeKind::Regular)
+            .unwrap();
+
+        // Overwriting the same data many times needs several times the
+        // device capacity, so it only succeeds if segments get cleaned
+        let data = [1u8; 8192];
+        for i in 0..200u8 {
+            let data = [i; 8192];
+            file.write(0, &data).unwrap();
+        }
+        file.write(8192, &data).unwrap();
+        fs.sync().unwrap();
+        assert!(fs.log().borrow().free_segments() > 0);
+
+        let fs = LogFs::open(dev).unwrap();
+        let file = fs.root().unwrap().lookup("file").unwrap();
+        let mut buf = [0u8; 16384];
+        assert_eq!(file.read(0, &mut buf).unwrap(), 16384);
+        assert!(buf[..8192].iter().all(|&b| b == 199));
+        assert!(buf[8192..].iter().all(|&b| b == 1));
+    }
+
+    #[test]
+    fn test_bad_dirent() {
+        let dev = device(8);
+        LogFs::format(dev).unwrap();
+        let fs = LogFs::open(dev).unwrap();
+        fs.clone()
+            .root()
+            .unwrap()
+            .create("file", FileMode::default_reg(), VnodeKind::Regular)
+            .unwrap();
+
+        let mut ent = [0u8; DIRENT_SIZE];
+        let mut log = fs.log().borrow_mut();
+        log.read(ROOT_INO, 0, &mut ent).unwrap();
+        ent[4] = 200;
+        log.write(ROOT_INO, 0, &ent).unwrap();
+        log.checkpoint().unwrap();
+        drop(log);
+
+        assert!(matches!(LogFs::open(dev), Err(Errno::InvalidFile)));
+    }
+
+    #[test]
+    fn test_open_bad_address() {
+        let dev = device(8);
+        LogFs::format(dev).unwrap();
+
+        // Point the first inode map block of both checkpoints past the end
+        // of the filesystem
+        let mut data = dev.snapshot();
+        let addr = 100 * layout::SEGMENT_BLOCKS;
+        for &slot in layout::CHECKPOINT_SLOTS.iter() {
+            let off = slot as usize * layout::BLOCK_SIZE + 40;
+            data[off..off + 8].copy_from_slice(&addr.to_le_bytes());
+        }
+        let dev = RamDevice::with_data(data).leak();
+        assert!(matches!(LogFs::open(dev), Err(Errno::InvalidFile)));
+    }
+}
@@ -0,0 +1,454 @@
+//! Log management: block allocation, inode map, checkpoints and
+//! segment cleaning
+use crate::layout::*;
+use alloc::{vec, vec::Vec};
+use core::cmp::min;
+use libsys::{error::Errno, mem::read_le32};
+use vfs::BlockDevice;
+
+/// Minimum number of free segments kept for the cleaner
+const GC_RESERVE: usize = 3;
+
+/// In-memory state of the log
+pub struct Log {
+    dev: &'static dyn BlockDevice,
+    segment_count: u32,
+    sequence: u64,
+    /// Address of the next block to append
+    head: u64,
+    /// Inode number -> inode block address, 0 if unused
+    imap: Vec<u64>,
+    imap_blocks: Vec<u64>,
+    /// Number of live blocks in each segment
+    usage: Vec<u32>,
+    /// Owners of the blocks written to the current segment
+    summary: Vec<(u32, u32)>,
+    free: Vec<u32>,
+}
+
+const fn segment_of(addr: u64) -> u32 {
+    (addr / SEGMENT_BLOCKS) as u32
+}
+
+impl Log {
+    /// Creates a new empty filesystem on `dev` with a root directory
+    pub fn format(dev: &'static dyn BlockDevice) -> Result<Self, Errno> {
+        let size = dev.size()?;
+        let segment_count = (size / BLOCK_SIZE / SEGMENT_BLOCKS as usize) as u32;
+        if segment_count < 2 + GC_RESERVE as u32 {
+            return Err(Errno::NoSpace);
+        }
+
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        Superblock { segment_count }.encode(&mut buf);
+        dev.write_blocks(0, &buf)?;
+        // Invalidate both checkpoint slots
+        buf.fill(0);
+        for &slot in CHECKPOINT_SLOTS.iter() {
+            dev.write_blocks(slot as usize * BLOCK_SIZE, &buf)?;
+        }
+
+        let mut log = Self {
+            dev,
+            segment_count,
+            sequence: 0,
+            head: SEGMENT_BLOCKS,
+            imap: vec![0; ROOT_INO as usize + 1],
+            imap_blocks: Vec::new(),
+            usage: vec![0; segment_count as usize],
+            summary: Vec::new(),
+            free: (2..segment_count).rev().collect(),
+        };
+        log.write_inode(ROOT_INO, &RawInode::new(InodeKind::Directory, 0o755))?;
+        log.checkpoint()?;
+        Ok(log)
+    }
+
+    /// Loads the log state from the latest valid checkpoint
+    pub fn open(dev: &'static dyn BlockDevice) -> Result<Self, Errno> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        dev.read_blocks(0, &mut buf)?;
+        let sb = Superblock::decode(&buf).ok_or(Errno::InvalidArgument)?;
+
+        let mut latest: Option<Checkpoint> = None;
+        for &slot in CHECKPOINT_SLOTS.iter() {
+            dev.read_blocks(slot as usize * BLOCK_SIZE, &mut buf)?;
+            if let Some(cp) = Checkpoint::decode(&buf) {
+                if latest.as_ref().map(|l| cp.sequence > l.sequence).unwrap_or(true) {
+                    latest = Some(cp);
+                }
+            }
+        }
+        let cp = latest.ok_or(Errno::InvalidArgument)?;
+
+        let mut log = Self {
+            dev,
+            segment_count: sb.segment_count,
+            sequence: cp.sequence,
+            head: cp.head,
+            imap: Vec::new(),
+            imap_blocks: cp.imap,
+            usage: vec![0; sb.segment_count as usize],
+            summary: Vec::new(),
+            free: Vec::new(),
+        };
+        if segment_of(log.head) >= log.segment_count {
+            return Err(Errno::InvalidFile);
+        }
+
+        // Load the inode map and account live blocks
+        for i in 0..log.imap_blocks.len() {
+            let addr = log.imap_blocks[i];
+            log.account(addr)?;
+            log.read_block(addr, &mut buf)?;
+            for j in 0..IMAP_ENTRIES {
+                log.imap.push(read_le64(&buf[j * 8..]));
+            }
+        }
+        while log.imap.last() == Some(&0) {
+            log.imap.pop();
+        }
+        for ino in 0..log.imap.len() as u32 {
+            let addr = log.imap[ino as usize];
+            if addr == 0 {
+                continue;
+            }
+            log.account(addr)?;
+            for &block in log.read_inode(ino)?.blocks.iter() {
+                if block != 0 {
+                    log.account(block)?;
+                }
+            }
+        }
+
+        // Blocks appended after the checkpoint are discarded
+        let head_offset = (log.head % SEGMENT_BLOCKS) as usize;
+        if head_offset != 0 {
+            log.read_block(log.summary_addr(segment_of(log.head)), &mut buf)?;
+            for i in 0..head_offset {
+                log.summary
+                    .push((read_le32(&buf[i * 8..]), read_le32(&buf[i * 8 + 4..])));
+            }
+        }
+        log.collect_free();
+
+        Ok(log)
+    }
+
+    /// Returns the number of free segments
+    pub fn free_segments(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Reads block at `addr`
+    pub fn read_block(&self, addr: u64, buf: &mut [u8]) -> Result<(), Errno> {
+        self.dev.read_blocks(addr as usize * BLOCK_SIZE, buf)
+    }
+
+    /// Reads inode `ino`
+    pub fn read_inode(&self, ino: u32) -> Result<RawInode, Errno> {
+        let addr = *self
+            .imap
+            .get(ino as usize)
+            .filter(|&&a| a != 0)
+            .ok_or(Errno::DoesNotExist)?;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        self.read_block(addr, &mut buf)?;
+        RawInode::decode(&buf).ok_or(Errno::InvalidArgument)
+    }
+
+    /// Appends a new version of inode `ino`
+    pub fn write_inode(&mut self, ino: u32, inode: &RawInode) -> Result<(), Errno> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        inode.encode(&mut buf);
+        let addr = self.append(&buf, (ino, INDEX_INODE))?;
+        if self.imap.len() <= ino as usize {
+            self.imap.resize(ino as usize + 1, 0);
+        }
+        let old = core::mem::replace(&mut self.imap[ino as usize], addr);
+        self.kill(old);
+        Ok(())
+    }
+
+    /// Allocates an inode number and writes `inode` to it
+    pub fn create_inode(&mut self, inode: &RawInode) -> Result<u32, Errno> {
+        self.reserve()?;
+        let ino = (ROOT_INO + 1..self.imap.len() as u32)
+            .find(|&i| self.imap[i as usize] == 0)
+            .unwrap_or(self.imap.len() as u32);
+        if ino as usize >= IMAP_BLOCKS_MAX * IMAP_ENTRIES {
+            return Err(Errno::NoSpace);
+        }
+        self.write_inode(ino, inode)?;
+        Ok(ino)
+    }
+
+    /// Releases inode `ino` and all of its blocks
+    pub fn remove_inode(&mut self, ino: u32) -> Result<(), Errno> {
+        let inode = self.read_inode(ino)?;
+        for &block in inode.blocks.iter() {
+            self.kill(block);
+        }
+        let old = core::mem::replace(&mut self.imap[ino as usize], 0);
+        self.kill(old);
+        Ok(())
+    }
+
+    /// Reads data of inode `ino` from offset `pos`
+    pub fn read(&self, ino: u32, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
+        let inode = self.read_inode(ino)?;
+        let size = inode.size as usize;
+        if pos >= size {
+            return Ok(0);
+        }
+        let len = min(data.len(), size - pos);
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut off = 0;
+
+        while off < len {
+            let index = (pos + off) / BLOCK_SIZE;
+            let block_off = (pos + off) % BLOCK_SIZE;
+            let count = min(BLOCK_SIZE - block_off, len - off);
+            match inode.blocks.get(index) {
+                Some(&addr) if addr != 0 => {
+                    self.read_block(addr, &mut buf)?;
+                    data[off..off + count].copy_from_slice(&buf[block_off..block_off + count]);
+                }
+                // Holes read as zeros
+                _ => data[off..off + count].fill(0),
+            }
+            off += count;
+        }
+
+        Ok(len)
+    }
+
+    /// Writes data to inode `ino` at offset `pos`, appending new versions of
+    /// the affected blocks to the log
+    pub fn write(&mut self, ino: u32, pos: usize, data: &[u8]) -> Result<usize, Errno> {
+        if (pos + data.len() + BLOCK_SIZE - 1) / BLOCK_SIZE > INODE_BLOCKS {
+            return Err(Errno::NoSpace);
+        }
+        // Every chunk with its inode update fits into a single segment, so
+        // the cleaner never runs in the middle of an inode update
+        let chunk_size = (SEGMENT_DATA_BLOCKS - 1) * BLOCK_SIZE;
+        let mut off = 0;
+        while off < data.len() {
+            let end = min(off + chunk_size - (pos + off) % BLOCK_SIZE, data.len());
+            self.reserve()?;
+            self.write_chunk(ino, pos + off, &data[off..end])?;
+            off = end;
+        }
+        Ok(data.len())
+    }
+
+    fn write_chunk(&mut self, ino: u32, pos: usize, data: &[u8]) -> Result<(), Errno> {
+        let mut inode = self.read_inode(ino)?;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut off = 0;
+
+        while off < data.len() {
+            let index = (pos + off) / BLOCK_SIZE;
+            let block_off = (pos + off) % BLOCK_SIZE;
+            let count = min(BLOCK_SIZE - block_off, data.len() - off);
+            if inode.blocks.len() <= index {
+                inode.blocks.resize(index + 1, 0);
+            }
+
+            let old = inode.blocks[index];
+            if count != BLOCK_SIZE && old != 0 {
+                self.read_block(old, &mut buf)?;
+            } else {
+                buf.fill(0);
+            }
+            buf[block_off..block_off + count].copy_from_slice(&data[off..off + count]);
+            inode.blocks[index] = self.append(&buf, (ino, index as u32))?;
+            self.kill(old);
+
+            off += count;
+        }
+
+        let end = (pos + data.len()) as u64;
+        if end > inode.size {
+            inode.size = end;
+        }
+        self.write_inode(ino, &inode)
+    }
+
+    /// Changes the size of inode `ino`'s data
+    pub fn truncate(&mut self, ino: u32, size: usize) -> Result<(), Errno> {
+        let count = (size + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        if count > INODE_BLOCKS {
+            return Err(Errno::NoSpace);
+        }
+        self.reserve()?;
+        let mut inode = self.read_inode(ino)?;
+        while inode.blocks.len() > count {
+            let block = inode.blocks.pop().unwrap();
+            self.kill(block);
+        }
+        // Zero the tail of the last block so it doesn't reappear on extension
+        if size % BLOCK_SIZE != 0 && (size as u64) < inode.size {
+            if let Some(&addr) = inode.blocks.last().filter(|&&a| a != 0) {
+                let mut buf = vec![0u8; BLOCK_SIZE];
+                self.read_block(addr, &mut buf)?;
+                buf[size % BLOCK_SIZE..].fill(0);
+                *inode.blocks.last_mut().unwrap() =
+                    self.append(&buf, (ino, count as u32 - 1))?;
+                self.kill(addr);
+            }
+        }
+        inode.size = size as u64;
+        self.write_inode(ino, &inode)
+    }
+
+    /// Writes a consistent snapshot of the log metadata. Everything written
+    /// before the checkpoint survives a crash.
+    pub fn checkpoint(&mut self) -> Result<(), Errno> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+
+        // Inode map
+        let old_blocks = core::mem::take(&mut self.imap_blocks);
+        for (i, chunk) in self.imap.clone().chunks(IMAP_ENTRIES).enumerate() {
+            buf.fill(0);
+            for (j, &addr) in chunk.iter().enumerate() {
+                write_le64(&mut buf[j * 8..], addr);
+            }
+            let addr = self.append(&buf, (OWNER_IMAP, i as u32))?;
+            self.imap_blocks.push(addr);
+        }
+        for addr in old_blocks {
+            self.kill(addr);
+        }
+
+        // Summary of the current segment, rewritten in place until sealed
+        self.write_summary()?;
+
+        self.sequence += 1;
+        Checkpoint {
+            sequence: self.sequence,
+            head: self.head,
+            imap: self.imap_blocks.clone(),
+        }
+        .encode(&mut buf);
+        let slot = CHECKPOINT_SLOTS[(self.sequence % 2) as usize];
+        self.dev.write_blocks(slot as usize * BLOCK_SIZE, &buf)?;
+
+        // Segments emptied since the previous checkpoint can now be reused
+        self.collect_free();
+        Ok(())
+    }
+
+    /// Cleans the sealed segment with the least live blocks by copying them
+    /// to the head of the log. Returns `false` if there was nothing to clean.
+    pub fn clean(&mut self) -> Result<bool, Errno> {
+        let current = segment_of(self.head);
+        let victim = (1..self.segment_count)
+            .filter(|&s| s != current && !self.free.contains(&s))
+            .filter(|&s| (self.usage[s as usize] as usize) < SEGMENT_DATA_BLOCKS)
+            .min_by_key(|&s| self.usage[s as usize]);
+        let victim = match victim {
+            Some(v) => v,
+            None => return Ok(false),
+        };
+
+        let mut summary = vec![0u8; BLOCK_SIZE];
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        self.read_block(self.summary_addr(victim), &mut summary)?;
+
+        for i in 0..SEGMENT_DATA_BLOCKS {
+            let addr = victim as u64 * SEGMENT_BLOCKS + i as u64;
+            let owner = read_le32(&summary[i * 8..]);
+            let index = read_le32(&summary[i * 8 + 4..]);
+            // Inode map blocks are rewritten by the checkpoint below
+            if owner == OWNER_IMAP || self.imap.get(owner as usize).copied().unwrap_or(0) == 0 {
+                continue;
+            }
+
+            if index == INDEX_INODE {
+                if self.imap[owner as usize] == addr {
+                    let inode = self.read_inode(owner)?;
+                    self.write_inode(owner, &inode)?;
+                }
+            } else {
+                let mut inode = self.read_inode(owner)?;
+                if inode.blocks.get(index as usize) == Some(&addr) {
+                    self.read_block(addr, &mut buf)?;
+                    inode.blocks[index as usize] = self.append(&buf, (owner, index))?;
+                    self.kill(addr);
+                    self.write_inode(owner, &inode)?;
+                }
+            }
+        }
+
+        self.checkpoint()?;
+        Ok(true)
+    }
+
+    /// Makes sure there are enough free segments for the cleaner to work
+    fn reserve(&mut self) -> Result<(), Errno> {
+        while self.free.len() < GC_RESERVE {
+            let before = self.free.len();
+            if !self.clean()? || self.free.len() <= before {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, data: &[u8], owner: (u32, u32)) -> Result<u64, Errno> {
+        if self.summary.len() == SEGMENT_DATA_BLOCKS {
+            // Seal the current segment and move on to a free one
+            self.write_summary()?;
+            let next = self.free.pop().ok_or(Errno::NoSpace)?;
+            self.head = next as u64 * SEGMENT_BLOCKS;
+            self.summary.clear();
+        }
+
+        let addr = self.head;
+        self.dev.write_blocks(addr as usize * BLOCK_SIZE, data)?;
+        self.summary.push(owner);
+        self.usage[segment_of(addr) as usize] += 1;
+        self.head += 1;
+        Ok(addr)
+    }
+
+    /// Counts a live block found on the device, rejecting addresses outside
+    /// of the filesystem
+    fn account(&mut self, addr: u64) -> Result<(), Errno> {
+        let usage = self
+            .usage
+            .get_mut(segment_of(addr) as usize)
+            .ok_or(Errno::InvalidFile)?;
+        *usage += 1;
+        Ok(())
+    }
+
+    fn kill(&mut self, addr: u64) {
+        if addr != 0 {
+            self.usage[segment_of(addr) as usize] -= 1;
+        }
+    }
+
+    fn write_summary(&mut self) -> Result<(), Errno> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        for (i, &(owner, index)) in self.summary.iter().enumerate() {
+            write_le32(&mut buf[i * 8..], owner);
+            write_le32(&mut buf[i * 8 + 4..], index);
+        }
+        let addr = self.summary_addr(segment_of(self.head - 1));
+        self.dev.write_blocks(addr as usize * BLOCK_SIZE, &buf)
+    }
+
+    fn summary_addr(&self, segment: u32) -> u64 {
+        segment as u64 * SEGMENT_BLOCKS + SEGMENT_DATA_BLOCKS as u64
+    }
+
+    fn collect_free(&mut self) {
+        let current = segment_of(self.head);
+        self.free = (1..self.segment_count)
+            .rev()
+            .filter(|&s| s != current && self.usage[s as usize] == 0)
+            .collect();
+    }
+}
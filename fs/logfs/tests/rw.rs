@@ -0,0 +1,128 @@
+//! Write, rewrite and verify trees through remounts
+use fs_testing::{walk, IoOp, RamDevice};
+use libsys::stat::FileMode;
+use logfs::{layout, LogFs};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use vfs::{BlockError, Filesystem, VnodeKind, VnodeRef};
+
+// Path -> contents, None for directories
+type Tree = BTreeMap<String, Option<Vec<u8>>>;
+
+fn device(segments: usize) -> &'static RamDevice {
+    let size = segments * layout::SEGMENT_BLOCKS as usize * layout::BLOCK_SIZE;
+    let dev = RamDevice::new(size).block_size(layout::BLOCK_SIZE).leak();
+    LogFs::format(dev).unwrap();
+    dev
+}
+
+fn pattern(seed: u8, len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31) ^ seed)
+        .collect()
+}
+
+fn create(root: &VnodeRef, path: &str, data: Option<&[u8]>, tree: &mut Tree) {
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (walk_to(root, &path[..pos]), &path[pos + 1..]),
+        None => (root.clone(), path),
+    };
+    match data {
+        Some(data) => {
+            let file = dir
+                .create(name, FileMode::default_reg(), VnodeKind::Regular)
+                .unwrap();
+            assert_eq!(file.write(0, data), Ok(data.len()));
+        }
+        None => {
+            dir.create(name, FileMode::default_dir(), VnodeKind::Directory)
+                .unwrap();
+        }
+    }
+    tree.insert(path.to_owned(), data.map(<[u8]>::to_vec));
+}
+
+fn walk_to(root: &VnodeRef, path: &str) -> VnodeRef {
+    path.split('/').fold(root.clone(), |node, name| {
+        node.lookup_or_load(name).unwrap()
+    })
+}
+
+fn verify(fs: Rc<LogFs>, tree: &Tree) {
+    let found: Tree = walk(&fs.root().unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|ent| (ent.path, ent.data))
+        .collect();
+    assert_eq!(&found, tree);
+}
+
+fn populate(root: &VnodeRef) -> Tree {
+    let mut tree = Tree::new();
+    create(root, "empty", Some(&[]), &mut tree);
+    create(root, "dir", None, &mut tree);
+    create(root, "dir/one", Some(&pattern(1, 1)), &mut tree);
+    create(
+        root,
+        "dir/block",
+        Some(&pattern(2, layout::BLOCK_SIZE)),
+        &mut tree,
+    );
+    create(root, "dir/sub", None, &mut tree);
+    create(root, "dir/sub/large", Some(&pattern(3, 100000)), &mut tree);
+    create(root, "odd", Some(&pattern(4, 4097)), &mut tree);
+    tree
+}
+
+#[test]
+fn test_write_verify() {
+    let dev = device(16);
+    let fs = LogFs::open(dev).unwrap();
+    let mut tree = populate(&fs.clone().root().unwrap());
+    verify(fs.clone(), &tree);
+    fs.sync().unwrap();
+    verify(LogFs::open(dev).unwrap(), &tree);
+
+    // Rewrite parts of files, extending one of them
+    let fs = LogFs::open(dev).unwrap();
+    let root = fs.clone().root().unwrap();
+    let data = pattern(5, 5000);
+    walk_to(&root, "dir/sub/large").write(50000, &data).unwrap();
+    walk_to(&root, "odd").write(4000, &data).unwrap();
+    for (path, off) in [("dir/sub/large", 50000), ("odd", 4000)] {
+        let file = tree.get_mut(path).unwrap().as_mut().unwrap();
+        file.resize(file.len().max(off + data.len()), 0);
+        file[off..off + data.len()].copy_from_slice(&data);
+    }
+    root.unlink("empty").unwrap();
+    tree.remove("empty");
+    fs.sync().unwrap();
+
+    verify(fs, &tree);
+    verify(LogFs::open(dev).unwrap(), &tree);
+}
+
+#[test]
+fn test_power_loss() {
+    let dev = device(16);
+    let fs = LogFs::open(dev).unwrap();
+    let tree = populate(&fs.clone().root().unwrap());
+    fs.sync().unwrap();
+
+    // The device stops accepting writes partway through the changes,
+    // the state of the last checkpoint has to survive
+    dev.inject(IoOp::Write, 3, usize::MAX, BlockError::MediaGone);
+    let root = fs.clone().root().unwrap();
+    let res = root
+        .lookup_or_load("dir")
+        .unwrap()
+        .create("new", FileMode::default_reg(), VnodeKind::Regular)
+        .and_then(|file| file.write(0, &pattern(6, 20000)))
+        .and_then(|_| walk_to(&root, "odd").write(0, &pattern(7, 8192)))
+        .and_then(|_| fs.sync());
+    assert!(res.is_err());
+    drop((root, fs));
+
+    dev.clear_faults();
+    verify(LogFs::open(dev).unwrap(), &tree);
+}
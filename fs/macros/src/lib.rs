@@ -4,81 +4,103 @@ extern crate syn;
 extern crate quote;
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::ToTokens;
-use std::collections::HashSet;
-use syn::{parse_macro_input, ImplItem, ItemImpl, Ident};
+use std::collections::{HashMap, HashSet};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    ImplItem, ItemImpl, Ident, Path, Token,
+};
 
-fn impl_inode_fn<T: ToTokens>(name: &str, behavior: T) -> ImplItem {
-    // TODO somehow know if current crate is vfs or not?
+const REQUIRED_METHODS: &[&str] = &[
+    "create", "remove", "lookup", "open", "close", "truncate", "read", "write", "stat", "size",
+    "ioctl", "is_ready", "readdir",
+];
+// Methods of VnodeImpl with a default implementation in the trait. These
+// are only generated if a behavior is explicitly requested for them.
+const PROVIDED_METHODS: &[&str] = &["readdir_plus", "lookup_folded"];
+
+fn impl_inode_fn(name: &str, krate: &Path, behavior: &TokenStream2) -> ImplItem {
     ImplItem::Verbatim(match name {
         "create" => quote! {
-            fn create(&mut self, _at: VnodeRef, _name: &str, kind: VnodeKind) ->
-                Result<VnodeRef, libsys::error::Errno>
+            fn create(&mut self, _at: #krate::VnodeRef, _name: &str, _kind: #krate::VnodeKind) ->
+                Result<#krate::VnodeRef, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "remove" => quote! {
-            fn remove(&mut self, _at: VnodeRef, _name: &str) -> Result<(), libsys::error::Errno> {
+            fn remove(&mut self, _at: #krate::VnodeRef, _name: &str) ->
+                Result<(), libsys::error::Errno>
+            {
                 #behavior
             }
         },
         "lookup" => quote! {
-            fn lookup(&mut self, _at: VnodeRef, _name: &str) ->
-                Result<VnodeRef, libsys::error::Errno>
+            fn lookup(&mut self, _at: #krate::VnodeRef, _name: &str) ->
+                Result<#krate::VnodeRef, libsys::error::Errno>
+            {
+                #behavior
+            }
+        },
+        "lookup_folded" => quote! {
+            fn lookup_folded(&mut self, _at: #krate::VnodeRef, _name: &str) ->
+                Result<#krate::VnodeRef, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "stat" => quote! {
-            fn stat(&mut self, _at: VnodeRef) ->
+            fn stat(&mut self, _at: #krate::VnodeRef) ->
                 Result<libsys::stat::Stat, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "truncate" => quote! {
-            fn truncate(&mut self, _node: VnodeRef, _size: usize) ->
+            fn truncate(&mut self, _node: #krate::VnodeRef, _size: usize) ->
                 Result<(), libsys::error::Errno>
             {
                 #behavior
             }
         },
         "size" => quote! {
-            fn size(&mut self, _node: VnodeRef) -> Result<usize, libsys::error::Errno> {
+            fn size(&mut self, _node: #krate::VnodeRef) -> Result<usize, libsys::error::Errno> {
                 #behavior
             }
         },
         "read" => quote! {
-            fn read(&mut self, _node: VnodeRef, _pos: usize, _data: &mut [u8]) ->
+            fn read(&mut self, _node: #krate::VnodeRef, _pos: usize, _data: &mut [u8]) ->
                 Result<usize, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "write" => quote! {
-            fn write(&mut self, _node: VnodeRef, _pos: usize, _data: &[u8]) ->
+            fn write(&mut self, _node: #krate::VnodeRef, _pos: usize, _data: &[u8]) ->
                 Result<usize, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "open" => quote! {
-            fn open(&mut self, _node: VnodeRef, _flags: libsys::stat::OpenFlags) ->
+            fn open(&mut self, _node: #krate::VnodeRef, _flags: libsys::stat::OpenFlags) ->
                 Result<usize, libsys::error::Errno>
             {
                 #behavior
             }
         },
         "close" => quote! {
-            fn close(&mut self, _node: VnodeRef) -> Result<(), libsys::error::Errno> {
+            fn close(&mut self, _node: #krate::VnodeRef) -> Result<(), libsys::error::Errno> {
                 #behavior
             }
         },
         "ioctl" => quote! {
             fn ioctl(
                 &mut self,
-                _node: VnodeRef,
+                _node: #krate::VnodeRef,
                 _cmd: libsys::ioctl::IoctlCmd,
                 _ptr: usize,
                 _len: usize) ->
@@ -88,7 +110,7 @@ fn impl_inode_fn<T: ToTokens>(name: &str, behavior: T) -> ImplItem {
             }
         },
         "is_ready" => quote! {
-            fn is_ready(&mut self, _node: VnodeRef, _write: bool) ->
+            fn is_ready(&mut self, _node: #krate::VnodeRef, _write: bool) ->
                 Result<bool, libsys::error::Errno>
             {
                 #behavior
@@ -97,7 +119,7 @@ fn impl_inode_fn<T: ToTokens>(name: &str, behavior: T) -> ImplItem {
         "readdir" => quote! {
             fn readdir(
                 &mut self,
-                _node: VnodeRef,
+                _node: #krate::VnodeRef,
                 _pos: usize,
                 _entries: &mut [libsys::stat::DirectoryEntry]
             ) ->
@@ -106,56 +128,121 @@ fn impl_inode_fn<T: ToTokens>(name: &str, behavior: T) -> ImplItem {
                 #behavior
             }
         },
-        _ => panic!("TODO implement {:?}", name),
+        "readdir_plus" => quote! {
+            fn readdir_plus(
+                &mut self,
+                _node: #krate::VnodeRef,
+                _pos: usize,
+                _entries: &mut [libsys::stat::DirectoryEntryPlus]
+            ) ->
+                Result<usize, libsys::error::Errno>
+            {
+                #behavior
+            }
+        },
+        _ => panic!("Unknown VnodeImpl method: {:?}", name),
     })
 }
 
-#[proc_macro_attribute]
-pub fn auto_inode(attr: TokenStream, input: TokenStream) -> TokenStream {
-    let mut impl_item = parse_macro_input!(input as ItemImpl);
-    let mut missing = HashSet::<String>::new();
-    let behavior = if attr.is_empty() {
-        "unimplemented".to_string()
-    } else {
-        parse_macro_input!(attr as Ident).to_string()
-    };
-    let behavior = match behavior.as_str() {
+fn behavior_body(behavior: &Ident) -> TokenStream2 {
+    match behavior.to_string().as_str() {
         "unimplemented" => quote! { unimplemented!() },
         "panic" => quote! { panic!() },
         "error" => quote! { Err(libsys::error::Errno::NotImplemented) },
-        _ => panic!("Unknown #[auto_inode] behavior: {:?}", behavior)
-    };
+        "readonly" => quote! { Err(libsys::error::Errno::ReadOnly) },
+        _ => panic!("Unknown #[auto_inode] behavior: {:?}", behavior.to_string()),
+    }
+}
+
+enum AutoInodeArg {
+    // Behavior of all the methods not mentioned explicitly
+    Default(Ident),
+    // Path to the vfs crate, `vfs` unless specified
+    Crate(Path),
+    // Behavior of a single method
+    Method(Ident, Ident),
+}
+
+impl Parse for AutoInodeArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![crate]) {
+            input.parse::<Token![crate]>()?;
+            input.parse::<Token![=]>()?;
+            return Ok(Self::Crate(Path::parse_mod_style(input)?));
+        }
+        let name: Ident = input.parse()?;
+        if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Ok(Self::Method(name, input.parse()?))
+        } else {
+            Ok(Self::Default(name))
+        }
+    }
+}
+
+/// Fills in [VnodeImpl] methods missing from an `impl` block.
+///
+/// Arguments (all optional, comma-separated):
+///
+/// * `behavior` - body of the generated methods: `unimplemented` (default),
+///   `panic`, `error` (`Errno::NotImplemented`) or `readonly`
+///   (`Errno::ReadOnly`);
+/// * `method = behavior` - overrides the behavior for a single method.
+///   Methods the trait provides a default for are only generated this way;
+/// * `crate = path` - path to the vfs crate (`crate` within vfs itself).
+///
+/// Example: `#[auto_inode(error, write = readonly, ioctl = unimplemented)]`
+#[proc_macro_attribute]
+pub fn auto_inode(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let mut impl_item = parse_macro_input!(input as ItemImpl);
+    let args = parse_macro_input!(attr with Punctuated::<AutoInodeArg, Token![,]>::parse_terminated);
 
-    missing.insert("create".to_string());
-    missing.insert("remove".to_string());
-    missing.insert("lookup".to_string());
-    missing.insert("open".to_string());
-    missing.insert("close".to_string());
-    missing.insert("truncate".to_string());
-    missing.insert("read".to_string());
-    missing.insert("write".to_string());
-    missing.insert("stat".to_string());
-    missing.insert("size".to_string());
-    missing.insert("ioctl".to_string());
-    missing.insert("is_ready".to_string());
-    missing.insert("readdir".to_string());
+    let mut behavior = Ident::new("unimplemented", proc_macro2::Span::call_site());
+    let mut krate: Path = syn::parse_quote!(vfs);
+    let mut overrides = HashMap::<String, Ident>::new();
+    for arg in args {
+        match arg {
+            AutoInodeArg::Default(name) => behavior = name,
+            AutoInodeArg::Crate(path) => krate = path,
+            AutoInodeArg::Method(name, value) => {
+                overrides.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    let mut missing: HashSet<String> = REQUIRED_METHODS.iter().map(|name| name.to_string()).collect();
+    for name in overrides.keys() {
+        if !missing.contains(name) && !PROVIDED_METHODS.contains(&name.as_str()) {
+            panic!("Unknown VnodeImpl method: {:?}", name);
+        }
+    }
+    missing.extend(
+        PROVIDED_METHODS
+            .iter()
+            .filter(|name| overrides.contains_key(**name))
+            .map(|name| name.to_string()),
+    );
 
     for item in &impl_item.items {
         match item {
             ImplItem::Method(method) => {
                 let name = &method.sig.ident.to_string();
-                if missing.contains(name) {
-                    missing.remove(name);
+                if overrides.contains_key(name) {
+                    panic!("#[auto_inode] behavior given for implemented method {:?}", name);
                 }
+                missing.remove(name);
             }
             _ => panic!("Unexpected impl item"),
         }
     }
 
+    let mut missing: Vec<_> = missing.into_iter().collect();
+    missing.sort();
     for item in &missing {
+        let body = behavior_body(overrides.get(item).unwrap_or(&behavior));
         impl_item
             .items
-            .push(impl_inode_fn(item, behavior.clone()));
+            .push(impl_inode_fn(item, &krate, &body));
     }
 
     impl_item.to_token_stream().into()
@@ -23,8 +23,10 @@ impl<'a, A: BlockAllocator + Copy> BlockRef<'a, A> {
     pub fn new(alloc: A) -> Result<Self, Errno> {
         assert!(size_of::<A>() == 0);
         let ptr = alloc.alloc();
+        // Reported the same way a disk-backed filesystem reports running
+        // out of blocks
         if ptr.is_null() {
-            Err(Errno::OutOfMemory)
+            Err(Errno::NoSpace)
         } else {
             Ok(unsafe { Self::from_raw(alloc, ptr) })
         }
@@ -157,6 +157,26 @@ impl<'a, A: BlockAllocator + Copy> Bvec<'a, A> {
         self.capacity = cap;
         Ok(())
     }
+    /// Changes the size of the vector. Blocks past the new end are freed,
+    /// extended region is zero-filled.
+    pub fn truncate(&mut self, size: usize) -> Result<(), Errno> {
+        #[cfg(feature = "cow")]
+        if self.is_cow() {
+            self.drop_cow();
+        }
+
+        let mut pos = self.size;
+        self.resize((size + block::SIZE - 1) / block::SIZE)?;
+        self.size = size;
+
+        while pos < size {
+            let off = pos % block::SIZE;
+            let count = min(block::SIZE - off, size - pos);
+            self[pos / block::SIZE][off..off + count].fill(0);
+            pos += count;
+        }
+        Ok(())
+    }
     pub fn write(&mut self, mut pos: usize, data: &[u8]) -> Result<usize, Errno> {
         if pos > self.size {
             return Err(Errno::InvalidFile);
@@ -278,12 +298,10 @@ impl<'a, A: BlockAllocator + Copy> IndexMut<usize> for Bvec<'a, A> {
 }
 impl<'a, A: BlockAllocator + Copy> Drop for Bvec<'a, A> {
     fn drop(&mut self) {
-        for i in 0..min(L0_BLOCKS, self.capacity) {
-            unsafe {
-                self.l0[i].assume_init_drop();
-            }
+        // Releases direct and indirect blocks
+        if self.capacity != 0 {
+            self.resize(0).unwrap();
         }
-        if self.capacity > L0_BLOCKS {}
     }
 }
 
@@ -742,4 +760,26 @@ mod bvec_tests {
             off += count;
         }
     }
+    #[test]
+    fn bvec_truncate() {
+        let mut bvec = Bvec::new(TestAlloc {});
+        let mut buf = [0xFFu8; 16];
+        const N: usize = block::SIZE * (L0_BLOCKS + 3);
+        bvec.truncate(N).unwrap();
+        assert_eq!(bvec.size(), N);
+        assert_eq!(bvec.capacity, L0_BLOCKS + 3);
+
+        bvec.write(N - 8, b"testdata").unwrap();
+        bvec.truncate(N - 4).unwrap();
+        assert_eq!(bvec.size(), N - 4);
+        bvec.truncate(block::SIZE + 2).unwrap();
+        assert_eq!(bvec.capacity, 2);
+        unsafe {
+            assert!(bvec.l1[0].assume_init_ref().is_null());
+        }
+
+        bvec.truncate(block::SIZE + 16).unwrap();
+        assert_eq!(bvec.read(block::SIZE, &mut buf).unwrap(), 16);
+        assert!(buf.iter().all(|&b| b == 0));
+    }
 }
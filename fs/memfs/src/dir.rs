@@ -15,11 +15,19 @@ impl<A: BlockAllocator + Copy + 'static> VnodeImpl for DirInode<A> {
         name: &str,
         kind: VnodeKind,
     ) -> Result<VnodeRef, Errno> {
-        let vnode = Vnode::new(name, kind, Vnode::SEEKABLE | Vnode::CACHE_READDIR);
+        let vnode = match kind {
+            VnodeKind::Directory | VnodeKind::Regular => {
+                Vnode::new(name, kind, Vnode::SEEKABLE | Vnode::CACHE_READDIR)
+            }
+            // Special nodes only carry their properties
+            VnodeKind::Char | VnodeKind::Block | VnodeKind::Fifo => {
+                Vnode::new(name, kind, Vnode::CACHE_STAT)
+            }
+        };
         match kind {
             VnodeKind::Directory => vnode.set_data(Box::new(DirInode { alloc: self.alloc })),
             VnodeKind::Regular => vnode.set_data(Box::new(FileInode::new(Bvec::new(self.alloc)))),
-            _ => todo!(),
+            _ => {}
         }
         Ok(vnode)
     }
@@ -38,6 +46,7 @@ impl<A: BlockAllocator + Copy + 'static> VnodeImpl for DirInode<A> {
             size: 0,
             blksize: 4096,
             mode: props.mode,
+            rdev: 0,
         })
     }
 }
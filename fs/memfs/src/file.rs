@@ -3,7 +3,7 @@ use libsys::{
     error::Errno,
     stat::{OpenFlags, Stat},
 };
-use vfs::{VnodeImpl, VnodeKind, VnodeRef};
+use vfs::{VnodeImpl, VnodeRef};
 
 pub struct FileInode<'a, A: BlockAllocator + Copy + 'static> {
     data: Bvec<'a, A>,
@@ -28,7 +28,7 @@ impl<'a, A: BlockAllocator + Copy + 'static> VnodeImpl for FileInode<'a, A> {
     }
 
     fn truncate(&mut self, _node: VnodeRef, size: usize) -> Result<(), Errno> {
-        self.data.resize((size + 4095) / 4096)
+        self.data.truncate(size)
     }
 
     fn size(&mut self, _node: VnodeRef) -> Result<usize, Errno> {
@@ -40,7 +40,8 @@ impl<'a, A: BlockAllocator + Copy + 'static> VnodeImpl for FileInode<'a, A> {
         Ok(Stat {
             size: self.data.size() as u64,
             blksize: 4096,
-            mode: props.mode
+            mode: props.mode,
+            rdev: 0,
         })
     }
 }
@@ -22,7 +22,7 @@ use libsys::{
     path::{path_component_left, path_component_right},
     stat::FileMode,
 };
-use vfs::{BlockDevice, Filesystem, Vnode, VnodeKind, VnodeRef};
+use vfs::{BlockDevice, Filesystem, IoCounters, Vnode, VnodeKind, VnodeRef};
 
 mod block;
 pub use block::{BlockAllocator, BlockRef};
@@ -38,6 +38,7 @@ use dir::DirInode;
 pub struct Ramfs<A: BlockAllocator + Copy + 'static> {
     root: RefCell<Option<VnodeRef>>,
     alloc: A,
+    counters: IoCounters,
 }
 
 impl<A: BlockAllocator + Copy + 'static> Filesystem for Ramfs<A> {
@@ -49,6 +50,14 @@ impl<A: BlockAllocator + Copy + 'static> Filesystem for Ramfs<A> {
         None
     }
 
+    fn unmount(&self) {
+        self.root.borrow_mut().take();
+    }
+
+    fn io_counters(&self) -> Option<&IoCounters> {
+        Some(&self.counters)
+    }
+
     fn dev(self: Rc<Self>) -> Option<&'static dyn BlockDevice> {
         None
     }
@@ -62,21 +71,57 @@ impl<A: BlockAllocator + Copy + 'static> Ramfs<A> {
         let res = Rc::new(Self {
             root: RefCell::new(None),
             alloc,
+            counters: IoCounters::new(),
         });
         *res.root.borrow_mut() = Some(res.clone().load_tar(base, size)?);
         Ok(res)
     }
 
+    /// Creates an empty filesystem, its root directory gets `mode`
+    /// permissions
+    pub fn empty(mode: FileMode, alloc: A) -> Rc<Self> {
+        let res = Rc::new(Self {
+            root: RefCell::new(None),
+            alloc,
+            counters: IoCounters::new(),
+        });
+        let root = res.clone().create_root();
+        root.props_mut().mode = mode | FileMode::S_IFDIR;
+        *res.root.borrow_mut() = Some(root);
+        res
+    }
+
+    fn create_root(self: Rc<Self>) -> VnodeRef {
+        let root = Vnode::new(
+            "",
+            VnodeKind::Directory,
+            Vnode::SEEKABLE | Vnode::CACHE_READDIR,
+        );
+        root.set_fs(self.clone());
+        root.set_data(Box::new(DirInode::new(self.alloc)));
+        root
+    }
+
     fn create_node_initial(self: Rc<Self>, name: &str, tar: &Tar) -> VnodeRef {
         let kind = tar.node_kind();
-        let node = Vnode::new(name, kind, Vnode::SEEKABLE | Vnode::CACHE_READDIR);
-        node.props_mut().mode = tar.mode();
+        let flags = match kind {
+            VnodeKind::Fifo => Vnode::CACHE_STAT,
+            _ => Vnode::SEEKABLE | Vnode::CACHE_READDIR,
+        };
+        let node = Vnode::new(name, kind, flags);
+        {
+            let mut props = node.props_mut();
+            props.mode = tar.mode();
+            props.uid = tar.uid();
+            props.gid = tar.gid();
+        }
         node.set_fs(self.clone());
         match kind {
             VnodeKind::Directory => node.set_data(Box::new(DirInode::new(self.alloc))),
             VnodeKind::Regular => {}
             VnodeKind::Char => todo!(),
             VnodeKind::Block => todo!(),
+            VnodeKind::Fifo => {}
         };
         node
     }
@@ -113,9 +158,7 @@ impl<A: BlockAllocator + Copy + 'static> Ramfs<A> {
     }
 
     unsafe fn load_tar(self: Rc<Self>, base: *const u8, size: usize) -> Result<VnodeRef, Errno> {
-        let root = Vnode::new("", VnodeKind::Directory, Vnode::SEEKABLE | Vnode::CACHE_READDIR);
-        root.set_fs(self.clone());
-        root.set_data(Box::new(DirInode::new(self.alloc)));
+        let root = self.clone().create_root();
         root.props_mut().mode = FileMode::default_dir();
 
         // 1. Create all the paths in TAR
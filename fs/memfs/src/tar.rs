@@ -1,4 +1,7 @@
-use libsys::{error::Errno, stat::FileMode};
+use libsys::{
+    error::Errno,
+    stat::{FileMode, GroupId, UserId},
+};
 use vfs::VnodeKind;
 
 #[repr(packed)]
@@ -68,6 +71,14 @@ impl Tar {
         from_octal(&self.size)
     }
 
+    pub fn uid(&self) -> UserId {
+        UserId::from(from_octal(&self.uid) as u32)
+    }
+
+    pub fn gid(&self) -> GroupId {
+        GroupId::from(from_octal(&self.gid) as u32)
+    }
+
     pub fn path(&self) -> Result<&str, Errno> {
         let zero_index = self.name.iter().position(|&c| c == 0).unwrap();
         core::str::from_utf8(&self.name[..zero_index]).map_err(|_| Errno::InvalidArgument)
@@ -77,6 +88,7 @@ impl Tar {
         match self.type_ {
             0 | b'0' => VnodeKind::Regular,
             b'5' => VnodeKind::Directory,
+            b'6' => VnodeKind::Fifo,
             p => panic!("Unrecognized tar entry type: '{}'", p as char),
         }
     }
@@ -85,6 +97,7 @@ impl Tar {
         let t = match self.node_kind() {
             VnodeKind::Regular => FileMode::S_IFREG,
             VnodeKind::Directory => FileMode::S_IFDIR,
+            VnodeKind::Fifo => FileMode::S_IFIFO,
             _ => todo!()
         };
         FileMode::from_bits(from_octal(&self.mode) as u32).unwrap() | t
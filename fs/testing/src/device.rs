@@ -0,0 +1,233 @@
+use libsys::error::Errno;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::time::Duration;
+use vfs::{BlockDevice, BlockError, BlockErrorCounters};
+
+/// Kind of a device request
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoOp {
+    Read,
+    Write,
+}
+
+struct Fault {
+    op: IoOp,
+    // Requests to let through before failing
+    skip: usize,
+    // Requests to fail
+    count: usize,
+    error: BlockError,
+}
+
+/// Block device backed by a buffer in memory. Requests have to be aligned
+/// to its block size. Failures and latency can be injected to exercise
+/// error paths of filesystems.
+pub struct RamDevice {
+    data: RefCell<Vec<u8>>,
+    block_size: usize,
+    faults: RefCell<Vec<Fault>>,
+    latency: Cell<Duration>,
+    reads: Cell<usize>,
+    writes: Cell<usize>,
+    flushes: Cell<usize>,
+    discards: Cell<usize>,
+    counters: BlockErrorCounters,
+}
+
+impl RamDevice {
+    /// Creates a zero-filled device of `size` bytes with 512-byte blocks
+    pub fn new(size: usize) -> Self {
+        Self::with_data(vec![0; size])
+    }
+
+    /// Creates a device holding `data`
+    pub fn with_data(data: Vec<u8>) -> Self {
+        Self {
+            data: RefCell::new(data),
+            block_size: 512,
+            faults: RefCell::new(Vec::new()),
+            latency: Cell::new(Duration::ZERO),
+            reads: Cell::new(0),
+            writes: Cell::new(0),
+            flushes: Cell::new(0),
+            discards: Cell::new(0),
+            counters: BlockErrorCounters::new(),
+        }
+    }
+
+    /// Creates a device holding a copy of golden image `name`, see
+    /// [image_path](crate::image_path)
+    pub fn from_image(name: &str) -> Self {
+        Self::from_file(&crate::image_path(name))
+    }
+
+    /// Creates a device holding a copy of the file at `path`
+    pub fn from_file(path: &Path) -> Self {
+        let data = std::fs::read(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        Self::with_data(data)
+    }
+
+    /// Sets the block size of the device
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        assert!(block_size.is_power_of_two());
+        self.block_size = block_size;
+        self
+    }
+
+    /// Leaks the device to obtain a `'static` reference, as required by
+    /// filesystems
+    pub fn leak(self) -> &'static Self {
+        Box::leak(Box::new(self))
+    }
+
+    /// Makes `count` requests of kind `op` fail with `error`, after `skip`
+    /// more ones succeed. Failed writes leave the data intact, so
+    /// `usize::MAX` failing writes act as a power loss.
+    pub fn inject(&self, op: IoOp, skip: usize, count: usize, error: BlockError) {
+        self.faults.borrow_mut().push(Fault {
+            op,
+            skip,
+            count,
+            error,
+        });
+    }
+
+    /// Drops all the pending injected failures
+    pub fn clear_faults(&self) {
+        self.faults.borrow_mut().clear();
+    }
+
+    /// Makes every request take at least `latency`
+    pub fn set_latency(&self, latency: Duration) {
+        self.latency.set(latency);
+    }
+
+    /// Returns a copy of the device contents
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+
+    /// Returns the number of successful read requests
+    pub fn reads(&self) -> usize {
+        self.reads.get()
+    }
+
+    /// Returns the number of successful write requests
+    pub fn writes(&self) -> usize {
+        self.writes.get()
+    }
+
+    /// Returns the number of flush requests
+    pub fn flushes(&self) -> usize {
+        self.flushes.get()
+    }
+
+    /// Returns the number of discard requests
+    pub fn discards(&self) -> usize {
+        self.discards.get()
+    }
+
+    fn request(&self, op: IoOp, pos: usize, len: usize) -> Result<(), BlockError> {
+        if !self.latency.get().is_zero() {
+            std::thread::sleep(self.latency.get());
+        }
+        if pos % self.block_size != 0
+            || len % self.block_size != 0
+            || pos + len > self.data.borrow().len()
+        {
+            return Err(BlockError::Other(Errno::InvalidArgument));
+        }
+
+        let mut faults = self.faults.borrow_mut();
+        if let Some(index) = faults.iter().position(|f| f.op == op) {
+            let fault = &mut faults[index];
+            if fault.skip != 0 {
+                fault.skip -= 1;
+            } else {
+                let error = fault.error;
+                fault.count -= 1;
+                if fault.count == 0 {
+                    faults.remove(index);
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for RamDevice {
+    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.request(IoOp::Read, pos, buf.len())?;
+        buf.copy_from_slice(&self.data.borrow()[pos..pos + buf.len()]);
+        self.reads.set(self.reads.get() + 1);
+        Ok(())
+    }
+
+    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+        self.request(IoOp::Write, pos, buf.len())?;
+        self.data.borrow_mut()[pos..pos + buf.len()].copy_from_slice(buf);
+        self.writes.set(self.writes.get() + 1);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize, Errno> {
+        Ok(self.data.borrow().len())
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn flush(&self) -> Result<(), Errno> {
+        self.flushes.set(self.flushes.get() + 1);
+        Ok(())
+    }
+
+    // Discarded blocks read as zeros
+    fn discard(&self, pos: usize, len: usize) -> Result<(), Errno> {
+        if pos % self.block_size != 0 || len % self.block_size != 0 {
+            return Err(Errno::InvalidArgument);
+        }
+        self.data.borrow_mut()[pos..pos + len].fill(0);
+        self.discards.set(self.discards.get() + 1);
+        Ok(())
+    }
+
+    fn error_counters(&self) -> Option<&BlockErrorCounters> {
+        Some(&self.counters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_injection() {
+        let dev = RamDevice::new(4096);
+        let mut buf = [0u8; 512];
+
+        dev.write_blocks(512, &[1; 512]).unwrap();
+        dev.inject(IoOp::Write, 1, usize::MAX, BlockError::MediaGone);
+        dev.write_blocks(1024, &[2; 512]).unwrap();
+        assert_eq!(dev.write_blocks(512, &[3; 512]), Err(Errno::DeviceError));
+        dev.read_blocks(512, &mut buf).unwrap();
+        assert_eq!(buf[0], 1);
+        dev.clear_faults();
+
+        // Transient errors are retried
+        dev.inject(IoOp::Read, 0, 2, BlockError::Crc);
+        dev.read_blocks(1024, &mut buf).unwrap();
+        assert_eq!(buf[0], 2);
+        assert_eq!(dev.error_counters().unwrap().recovered(), 1);
+        assert_eq!(dev.reads(), 2);
+        assert_eq!(dev.writes(), 2);
+
+        assert_eq!(dev.read_blocks(100, &mut buf), Err(Errno::InvalidArgument));
+        dev.discard(1024, 512).unwrap();
+        dev.read_blocks(1024, &mut buf).unwrap();
+        assert_eq!(buf[0], 0);
+    }
+}
@@ -0,0 +1,21 @@
+//! Host-side support for filesystem tests: an in-memory block device with
+//! fault injection, golden images of real filesystems and a directory
+//! tree walker.
+//!
+//! Golden images live in `images/`:
+//!
+//! * `fat32-mkfs.img`: 64MiB FAT32 made by Linux mkfs.fat and populated
+//!   by the Linux vfat driver, with long names and generated short names
+use std::path::PathBuf;
+
+mod device;
+pub use device::{IoOp, RamDevice};
+mod tree;
+pub use tree::{walk, TreeEntry};
+
+/// Returns the path of golden image `name`
+pub fn image_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("images")
+        .join(name)
+}
@@ -0,0 +1,77 @@
+use libsys::{
+    error::Errno,
+    stat::{DirectoryEntryPlus, FileMode, OpenFlags, Stat},
+};
+use vfs::VnodeRef;
+
+/// Node found while walking a directory tree
+#[derive(Debug)]
+pub struct TreeEntry {
+    /// Path relative to the walk root, e.g. "dir/file"
+    pub path: String,
+    /// Status reported by the parent directory listing
+    pub stat: Stat,
+    /// Contents of a regular file
+    pub data: Option<Vec<u8>>,
+}
+
+impl TreeEntry {
+    /// Returns `true` if the entry is a directory
+    pub fn is_dir(&self) -> bool {
+        self.stat.mode & FileMode::FILE_TYPE == FileMode::S_IFDIR
+    }
+}
+
+/// Lists the whole tree under `root`, reading all the regular files. The
+/// status of every node is checked to match between the directory listing
+/// and the node itself. Entries are sorted by path.
+pub fn walk(root: &VnodeRef) -> Result<Vec<TreeEntry>, Errno> {
+    let mut out = Vec::new();
+    walk_dir(root, "", &mut out)?;
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(out)
+}
+
+fn walk_dir(dir: &VnodeRef, prefix: &str, out: &mut Vec<TreeEntry>) -> Result<(), Errno> {
+    // Listed the way processes do, as some filesystems only list the
+    // cached tree
+    let file = dir.open(OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY)?;
+    let mut buf = [DirectoryEntryPlus::empty(); 8];
+    loop {
+        let count = file.borrow_mut().readdir_plus(&mut buf)?;
+        if count == 0 {
+            return Ok(());
+        }
+
+        for item in &buf[..count] {
+            let name = item.as_str();
+            if name == "." || name == ".." {
+                continue;
+            }
+            let node = dir.lookup_or_load(name)?;
+            let stat = node.stat()?;
+            if let Some(listed) = item.stat() {
+                assert_eq!(listed.mode, stat.mode, "{}{}", prefix, name);
+                assert_eq!(listed.size, stat.size, "{}{}", prefix, name);
+            }
+
+            let path = format!("{}{}", prefix, name);
+            let mut data = None;
+            if stat.mode & FileMode::FILE_TYPE == FileMode::S_IFDIR {
+                walk_dir(&node, &format!("{}/", path), out)?;
+            } else if stat.mode & FileMode::FILE_TYPE == FileMode::S_IFREG {
+                let mut buf = vec![0; stat.size as usize];
+                let mut off = 0;
+                while off < buf.len() {
+                    match node.read(off, &mut buf[off..])? {
+                        0 => break,
+                        count => off += count,
+                    }
+                }
+                assert_eq!(off, buf.len(), "{}: short read", path);
+                data = Some(buf);
+            }
+            out.push(TreeEntry { path, stat, data });
+        }
+    }
+}
@@ -1,10 +1,392 @@
-use libsys::error::Errno;
+use crate::{VnodeImpl, VnodeRef};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use libsys::{
+    error::Errno,
+    ioctl::IoctlCmd,
+    stat::{OpenFlags, Stat},
+};
+
+/// Maximum number of retries for a request failing with a transient error
+pub const MAX_RETRIES: usize = 3;
+
+/// Block device I/O error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockError {
+    /// Device did not complete the request in time
+    Timeout,
+    /// Data or command transfer failed integrity check
+    Crc,
+    /// Media was removed from the device
+    MediaGone,
+    /// Any other error
+    Other(Errno),
+}
+
+/// Per-device I/O error counters
+#[derive(Default)]
+pub struct BlockErrorCounters {
+    transient: AtomicUsize,
+    permanent: AtomicUsize,
+    recovered: AtomicUsize,
+}
 
 /// Block device interface
 pub trait BlockDevice {
     /// Reads blocks at offset `pos` into `buf`
-    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), Errno>;
+    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError>;
     /// Writes blocks at offset `pos` from `buf`
-    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), Errno>;
-    // TODO ioctl and stuff
+    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError>;
+    /// Returns the size of the device in bytes
+    fn size(&self) -> Result<usize, Errno> {
+        Err(Errno::NotImplemented)
+    }
+    /// Returns the size of the device's block
+    fn block_size(&self) -> usize {
+        512
+    }
+    /// Returns the number of blocks of the device
+    fn block_count(&self) -> Result<usize, Errno> {
+        Ok(self.size()? / self.block_size())
+    }
+    /// Makes sure all the completed writes reach the media, in case the
+    /// device keeps them in a volatile cache
+    fn flush(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+    /// Tells the device the blocks at offset `pos` and `len` bytes long
+    /// no longer hold data, so it can release the storage behind them.
+    /// Reading discarded blocks returns undefined data.
+    fn discard(&self, _pos: usize, _len: usize) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
+    /// Returns `true` if the device has removable media
+    fn is_removable(&self) -> bool {
+        false
+    }
+    /// Returns `true` if media is present in the device
+    fn is_media_present(&self) -> bool {
+        true
+    }
+    /// Notifies the driver of media insertion or removal, so it can drop
+    /// cached state referring to the old media
+    fn media_changed(&self, _present: bool) {}
+    /// Returns I/O error counters of the device, if it keeps any
+    fn error_counters(&self) -> Option<&BlockErrorCounters> {
+        None
+    }
+    /// Performs a device-specific request
+    fn ioctl(&self, _cmd: IoctlCmd, _ptr: usize, _len: usize) -> Result<usize, Errno> {
+        Err(Errno::InvalidArgument)
+    }
+
+    /// Reads blocks at offset `pos` into `buf`, retrying requests which
+    /// fail with transient errors
+    fn read_blocks(&self, pos: usize, buf: &mut [u8]) -> Result<(), Errno> {
+        with_retries(self.error_counters(), || self.read(pos, buf))
+    }
+    /// Writes blocks at offset `pos` from `buf`, retrying requests which
+    /// fail with transient errors
+    fn write_blocks(&self, pos: usize, buf: &[u8]) -> Result<(), Errno> {
+        with_retries(self.error_counters(), || self.write(pos, buf))
+    }
+}
+
+fn with_retries<F: FnMut() -> Result<(), BlockError>>(
+    counters: Option<&BlockErrorCounters>,
+    mut f: F,
+) -> Result<(), Errno> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Ok(()) => {
+                if attempt != 0 {
+                    if let Some(counters) = counters {
+                        counters.recovered.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                return Ok(());
+            }
+            Err(err) if err.is_transient() && attempt < MAX_RETRIES => {
+                if let Some(counters) = counters {
+                    counters.transient.fetch_add(1, Ordering::Relaxed);
+                }
+                attempt += 1;
+            }
+            Err(err) => {
+                if let Some(counters) = counters {
+                    counters.permanent.fetch_add(1, Ordering::Relaxed);
+                }
+                return Err(err.into());
+            }
+        }
+    }
+}
+
+impl BlockError {
+    /// Returns `true` if the failed request may succeed if retried
+    pub const fn is_transient(self) -> bool {
+        matches!(self, Self::Timeout | Self::Crc)
+    }
+}
+
+impl From<Errno> for BlockError {
+    fn from(e: Errno) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl From<BlockError> for Errno {
+    fn from(e: BlockError) -> Self {
+        match e {
+            BlockError::Timeout => Errno::TimedOut,
+            BlockError::Crc | BlockError::MediaGone => Errno::DeviceError,
+            BlockError::Other(e) => e,
+        }
+    }
+}
+
+impl BlockErrorCounters {
+    /// Constructs a zeroed set of counters
+    pub const fn new() -> Self {
+        Self {
+            transient: AtomicUsize::new(0),
+            permanent: AtomicUsize::new(0),
+            recovered: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of transient errors, including retried ones
+    pub fn transient(&self) -> usize {
+        self.transient.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests failed with a permanent error (or
+    /// after running out of retries)
+    pub fn permanent(&self) -> usize {
+        self.permanent.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests which succeeded after being retried
+    pub fn recovered(&self) -> usize {
+        self.recovered.load(Ordering::Relaxed)
+    }
+}
+
+/// Wrapper struct to attach [VnodeImpl] implementation
+/// to [BlockDevice]s
+pub struct BlockDeviceWrapper {
+    device: &'static dyn BlockDevice,
+}
+
+#[auto_inode(error, crate = crate)]
+impl VnodeImpl for BlockDeviceWrapper {
+    fn open(&mut self, _node: VnodeRef, _opts: OpenFlags) -> Result<usize, Errno> {
+        Ok(0)
+    }
+
+    // Data written through the node is durable once it's closed
+    fn close(&mut self, _node: VnodeRef) -> Result<(), Errno> {
+        self.device.flush()
+    }
+
+    fn read(&mut self, _node: VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
+        let len = self.clamp(pos, data.len())?;
+        let data = &mut data[..len];
+        let bs = self.device.block_size();
+        let mut block = [0u8; 4096];
+        assert!(bs <= block.len());
+        let mut off = 0;
+
+        while off < data.len() {
+            let block_pos = (pos + off) / bs * bs;
+            let block_off = (pos + off) % bs;
+            let count = core::cmp::min(bs - block_off, data.len() - off);
+
+            self.device.read_blocks(block_pos, &mut block[..bs])?;
+            data[off..off + count].copy_from_slice(&block[block_off..block_off + count]);
+            off += count;
+        }
+
+        Ok(off)
+    }
+
+    fn write(&mut self, _node: VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Errno> {
+        let len = self.clamp(pos, data.len())?;
+        if len == 0 && !data.is_empty() {
+            return Err(Errno::NoSpace);
+        }
+        let data = &data[..len];
+        let bs = self.device.block_size();
+        let mut block = [0u8; 4096];
+        assert!(bs <= block.len());
+        let mut off = 0;
+
+        while off < data.len() {
+            let block_pos = (pos + off) / bs * bs;
+            let block_off = (pos + off) % bs;
+            let count = core::cmp::min(bs - block_off, data.len() - off);
+
+            if count != bs {
+                self.device.read_blocks(block_pos, &mut block[..bs])?;
+            }
+            block[block_off..block_off + count].copy_from_slice(&data[off..off + count]);
+            self.device.write_blocks(block_pos, &block[..bs])?;
+            off += count;
+        }
+
+        Ok(off)
+    }
+
+    fn size(&mut self, _node: VnodeRef) -> Result<usize, Errno> {
+        self.device.size()
+    }
+    fn stat(&mut self, node: VnodeRef) -> Result<Stat, Errno> {
+        let props = node.props();
+        Ok(Stat {
+            size: self.device.size().unwrap_or(0) as u64,
+            blksize: self.device.block_size() as u32,
+            mode: props.mode,
+            rdev: props.rdev,
+        })
+    }
+
+    fn ioctl(
+        &mut self,
+        _node: VnodeRef,
+        cmd: IoctlCmd,
+        ptr: usize,
+        len: usize,
+    ) -> Result<usize, Errno> {
+        self.device.ioctl(cmd, ptr, len)
+    }
+}
+
+impl BlockDeviceWrapper {
+    /// Creates a wrapper for static [BlockDevice] trait object to
+    /// auto-implement [VnodeImpl] trait for the device
+    pub const fn new(device: &'static dyn BlockDevice) -> Self {
+        Self { device }
+    }
+
+    // Limits a request of `len` bytes at `pos` to the end of the device
+    fn clamp(&self, pos: usize, len: usize) -> Result<usize, Errno> {
+        match self.device.size() {
+            Ok(size) => Ok(core::cmp::min(len, size.saturating_sub(pos))),
+            // Size unknown, leave it up to the driver
+            Err(Errno::NotImplemented) => Ok(len),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Vnode, VnodeKind};
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use core::cell::{Cell, RefCell};
+
+    struct FlakyDevice {
+        failures: Cell<usize>,
+        error: BlockError,
+        counters: BlockErrorCounters,
+    }
+
+    impl BlockDevice for FlakyDevice {
+        fn read(&self, _pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+            if self.failures.get() != 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(self.error);
+            }
+            buf.fill(1);
+            Ok(())
+        }
+
+        fn write(&self, _pos: usize, _buf: &[u8]) -> Result<(), BlockError> {
+            Err(self.error)
+        }
+
+        fn error_counters(&self) -> Option<&BlockErrorCounters> {
+            Some(&self.counters)
+        }
+    }
+
+    #[test]
+    fn test_retries() {
+        let mut buf = [0u8; 512];
+
+        let dev = FlakyDevice {
+            failures: Cell::new(MAX_RETRIES),
+            error: BlockError::Crc,
+            counters: BlockErrorCounters::new(),
+        };
+        assert!(dev.read_blocks(0, &mut buf).is_ok());
+        assert_eq!(buf[0], 1);
+        assert_eq!(dev.counters.transient(), MAX_RETRIES);
+        assert_eq!(dev.counters.recovered(), 1);
+        assert_eq!(dev.counters.permanent(), 0);
+
+        // Out of retries
+        dev.failures.set(MAX_RETRIES + 1);
+        assert_eq!(dev.read_blocks(0, &mut buf), Err(Errno::DeviceError));
+        assert_eq!(dev.counters.permanent(), 1);
+
+        // Permanent errors are not retried
+        let dev = FlakyDevice {
+            failures: Cell::new(1),
+            error: BlockError::MediaGone,
+            counters: BlockErrorCounters::new(),
+        };
+        assert_eq!(dev.read_blocks(0, &mut buf), Err(Errno::DeviceError));
+        assert_eq!(dev.counters.transient(), 0);
+        assert_eq!(dev.counters.permanent(), 1);
+        assert_eq!(dev.write_blocks(0, &buf), Err(Errno::DeviceError));
+    }
+
+    struct MemDevice {
+        data: RefCell<Vec<u8>>,
+    }
+
+    impl BlockDevice for MemDevice {
+        fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+            let data = self.data.borrow();
+            let src = data
+                .get(pos..pos + buf.len())
+                .ok_or(BlockError::Other(Errno::InvalidArgument))?;
+            buf.copy_from_slice(src);
+            Ok(())
+        }
+
+        fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+            let mut data = self.data.borrow_mut();
+            let dst = data
+                .get_mut(pos..pos + buf.len())
+                .ok_or(BlockError::Other(Errno::InvalidArgument))?;
+            dst.copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn size(&self) -> Result<usize, Errno> {
+            Ok(self.data.borrow().len())
+        }
+    }
+
+    #[test]
+    fn test_wrapper_clamp() {
+        let dev = Box::leak(Box::new(MemDevice {
+            data: RefCell::new(vec![1; 4 * 512]),
+        }));
+        let node = Vnode::new("blk", VnodeKind::Block, 0);
+        let mut wrapper = BlockDeviceWrapper::new(dev);
+        let mut buf = [0u8; 1024];
+
+        assert_eq!(wrapper.read(node.clone(), 3 * 512 + 100, &mut buf), Ok(412));
+        assert_eq!(wrapper.read(node.clone(), 4 * 512, &mut buf), Ok(0));
+        assert_eq!(wrapper.read(node.clone(), 8 * 512, &mut buf), Ok(0));
+
+        assert_eq!(wrapper.write(node.clone(), 4 * 512 - 12, &[2; 100]), Ok(12));
+        assert_eq!(dev.data.borrow()[4 * 512 - 1], 2);
+        assert_eq!(wrapper.write(node.clone(), 4 * 512, &[2; 100]), Err(Errno::NoSpace));
+        assert_eq!(wrapper.write(node, 4 * 512, &[]), Ok(0));
+    }
 }
@@ -0,0 +1,29 @@
+//! Page cache interface
+//!
+//! When a [PageCache] is attached to a vnode, regular file data I/O on it
+//! goes through the cache. The cache performs the actual I/O using
+//! [Vnode::read_direct], [Vnode::write_direct] and [Vnode::truncate_direct].
+use crate::VnodeRef;
+use libsys::{error::Errno, stat::FileAdvice};
+
+/// Cache of vnode data pages
+pub trait PageCache {
+    /// Reads data from offset `pos` of `node` into `buf`
+    fn read(&self, node: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Errno>;
+    /// Writes data from `buf` to offset `pos` of `node`. The data may not
+    /// reach the underlying storage until [PageCache::sync] is called.
+    fn write(&self, node: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno>;
+    /// Resizes `node` data, dropping the cached pages past `size`
+    fn truncate(&self, node: &VnodeRef, size: usize) -> Result<(), Errno>;
+    /// Writes all the modified cached pages of `node` back to storage
+    fn sync(&self, node: &VnodeRef) -> Result<(), Errno>;
+    /// Applies access pattern `advice` to `node` data in range
+    /// `pos..pos + len` (`len` of zero extends the range to the end of file)
+    fn advise(
+        &self,
+        node: &VnodeRef,
+        pos: usize,
+        len: usize,
+        advice: FileAdvice,
+    ) -> Result<(), Errno>;
+}
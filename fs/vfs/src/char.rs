@@ -1,4 +1,4 @@
-use crate::{VnodeImpl, VnodeKind, VnodeRef};
+use crate::{VnodeImpl, VnodeRef};
 use libsys::{error::Errno, ioctl::IoctlCmd, stat::OpenFlags};
 
 /// Generic character device trait
@@ -29,7 +29,7 @@ pub struct CharDeviceWrapper {
     device: &'static dyn CharDevice,
 }
 
-#[auto_inode(error)]
+#[auto_inode(error, crate = crate)]
 impl VnodeImpl for CharDeviceWrapper {
     fn open(&mut self, _node: VnodeRef, _opts: OpenFlags) -> Result<usize, Errno> {
         Ok(0)
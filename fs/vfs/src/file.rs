@@ -1,12 +1,11 @@
-use crate::{VnodeKind, VnodeRef, Vnode};
-use alloc::rc::Rc;
+use crate::{limits, Pipe, VnodeKind, VnodeRef, Vnode};
+use alloc::{rc::Rc, vec};
 use core::cell::RefCell;
 use core::cmp::min;
-use core::str::FromStr;
 use libsys::{
     error::Errno,
-    stat::DirectoryEntry,
-    traits::{Read, Seek, SeekDir, Write},
+    stat::{DirectoryEntry, DirectoryEntryPlus, FileMode},
+    traits::{RandomRead, RandomWrite, Read, Seek, SeekDir, Write},
 };
 
 struct NormalFile {
@@ -14,8 +13,20 @@ struct NormalFile {
     pos: usize,
 }
 
+impl NormalFile {
+    fn is_positional(&self) -> bool {
+        matches!(self.vnode.kind(), VnodeKind::Regular | VnodeKind::Block)
+    }
+}
+
+struct FifoFile {
+    vnode: VnodeRef,
+    pipe: Rc<Pipe>,
+}
+
 enum FileInner {
     Normal(NormalFile),
+    Fifo(FifoFile),
     // TODO
     #[allow(dead_code)]
     Socket,
@@ -24,7 +35,10 @@ enum FileInner {
 /// Convenience wrapper type for a [File] struct reference
 pub type FileRef = Rc<RefCell<File>>;
 
-/// Structure representing a file/socket opened for access
+/// Structure representing a file/socket opened for access (an "open file
+/// description"). Position and status flags are shared by all the descriptors
+/// referring to the same [FileRef], e.g. after dup() or fork(). Per-descriptor
+/// flags (close-on-exec) are kept by descriptor tables instead.
 pub struct File {
     inner: FileInner,
     flags: u32,
@@ -44,6 +58,7 @@ impl Read for File {
                 }
                 Ok(count)
             }
+            FileInner::Fifo(inner) => inner.pipe.read(data),
             _ => unimplemented!(),
         }
     }
@@ -57,17 +72,51 @@ impl Write for File {
 
         match &mut self.inner {
             FileInner::Normal(inner) => {
+                if self.flags & Self::APPEND != 0 {
+                    inner.pos = inner.vnode.size()?;
+                }
                 let count = inner.vnode.write(inner.pos, data)?;
                 if inner.vnode.kind() != VnodeKind::Char {
                     inner.pos += count;
                 }
                 Ok(count)
             }
+            FileInner::Fifo(inner) => inner.pipe.write(data),
             _ => unimplemented!(),
         }
     }
 }
 
+impl RandomRead for File {
+    /// Reads from position `pos` without moving the file position. Only
+    /// supported for regular files and block devices.
+    fn pread(&mut self, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
+        if self.flags & Self::READ == 0 {
+            return Err(Errno::InvalidOperation);
+        }
+
+        match &self.inner {
+            FileInner::Normal(inner) if inner.is_positional() => inner.vnode.read(pos, data),
+            _ => Err(Errno::InvalidOperation),
+        }
+    }
+}
+
+impl RandomWrite for File {
+    /// Writes at position `pos` without moving the file position. Only
+    /// supported for regular files and block devices.
+    fn pwrite(&mut self, pos: usize, data: &[u8]) -> Result<usize, Errno> {
+        if self.flags & Self::WRITE == 0 {
+            return Err(Errno::ReadOnly);
+        }
+
+        match &self.inner {
+            FileInner::Normal(inner) if inner.is_positional() => inner.vnode.write(pos, data),
+            _ => Err(Errno::InvalidOperation),
+        }
+    }
+}
+
 impl Seek for File {
     fn seek(&mut self, off: isize, whence: SeekDir) -> Result<usize, Errno> {
         match &mut self.inner {
@@ -86,6 +135,7 @@ impl Seek for File {
 
                 Ok(pos)
             }
+            FileInner::Fifo(_) => Err(Errno::InvalidOperation),
             _ => unimplemented!(),
         }
     }
@@ -96,8 +146,10 @@ impl File {
     pub const READ: u32 = 1 << 0;
     /// File can be written
     pub const WRITE: u32 = 1 << 1;
-    /// File has to be closed on execve() calls
-    pub const CLOEXEC: u32 = 1 << 2;
+    /// Writes always happen at the end of the file
+    pub const APPEND: u32 = 1 << 2;
+    /// Operations on the file never suspend the caller
+    pub const NONBLOCK: u32 = 1 << 3;
 
     /// Special position for cache-readdir: "." entry
     pub const POS_CACHE_DOT: usize = usize::MAX - 1;
@@ -106,30 +158,72 @@ impl File {
 
     /// Constructs a new file handle for a regular file
     pub fn normal(vnode: VnodeRef, pos: usize, flags: u32) -> FileRef {
+        limits::FILES.acquire();
+        vnode.open_count.set(vnode.open_count.get() + 1);
         Rc::new(RefCell::new(Self {
             inner: FileInner::Normal(NormalFile { vnode, pos }),
             flags,
         }))
     }
 
+    /// Constructs a new file handle for a named pipe
+    pub fn fifo(vnode: VnodeRef, pipe: Rc<Pipe>, flags: u32) -> FileRef {
+        limits::FILES.acquire();
+        pipe.open(flags & Self::READ != 0, flags & Self::WRITE != 0);
+        vnode.open_count.set(vnode.open_count.get() + 1);
+        Rc::new(RefCell::new(Self {
+            inner: FileInner::Fifo(FifoFile { vnode, pipe }),
+            flags,
+        }))
+    }
+
     /// Returns [VnodeRef] associated with this file, if available
     pub fn node(&self) -> Option<VnodeRef> {
         match &self.inner {
             FileInner::Normal(inner) => Some(inner.vnode.clone()),
+            FileInner::Fifo(inner) => Some(inner.vnode.clone()),
             _ => None,
         }
     }
 
-    /// Returns `true` if the file has to be closed when running execve() family
-    /// of system calls
-    pub fn is_cloexec(&self) -> bool {
-        self.flags & Self::CLOEXEC != 0
+    /// Returns `true` if the file is a named pipe end
+    pub fn is_fifo(&self) -> bool {
+        matches!(self.inner, FileInner::Fifo(_))
+    }
+
+    /// Returns `true` if the file was opened for writing
+    pub fn is_writable(&self) -> bool {
+        self.flags & Self::WRITE != 0
+    }
+
+    /// Returns `true` if operations on the file should not block
+    pub fn is_nonblocking(&self) -> bool {
+        self.flags & Self::NONBLOCK != 0
+    }
+
+    /// Checks if the opposite end of a named pipe is present. Returns
+    /// [Errno::WouldBlock] if opening the file has to wait for it.
+    pub fn check_fifo_peer(&self) -> Result<(), Errno> {
+        match &self.inner {
+            FileInner::Fifo(inner) => {
+                if self.flags & (Self::READ | Self::WRITE) == Self::READ | Self::WRITE
+                    || inner.pipe.is_connected()
+                    || (self.flags & Self::WRITE == 0 && self.is_nonblocking())
+                {
+                    Ok(())
+                } else {
+                    Err(Errno::WouldBlock)
+                }
+            }
+            _ => Ok(()),
+        }
     }
 
     /// Returns `true` if the file is ready for an operation
     pub fn is_ready(&self, write: bool) -> Result<bool, Errno> {
         match &self.inner {
             FileInner::Normal(inner) => inner.vnode.is_ready(write),
+            FileInner::Fifo(inner) => Ok(inner.pipe.is_ready(write)),
             _ => todo!(),
         }
     }
@@ -143,7 +237,7 @@ impl File {
                 return Ok(offset);
             }
 
-            entries[offset] = DirectoryEntry::from_str(".").unwrap();
+            entries[offset] = DirectoryEntry::new(".", FileMode::S_IFDIR).unwrap();
             inner.pos = Self::POS_CACHE_DOT_DOT;
 
             offset += 1;
@@ -155,7 +249,7 @@ impl File {
                 return Ok(offset);
             }
 
-            entries[offset] = DirectoryEntry::from_str("..").unwrap();
+            entries[offset] = DirectoryEntry::new("..", FileMode::S_IFDIR).unwrap();
             inner.pos = 0;
 
             offset += 1;
@@ -167,7 +261,7 @@ impl File {
         }
 
         let count = inner.vnode.for_each_entry(inner.pos, count, |i, e| {
-            entries[offset + i] = DirectoryEntry::from_str(e.name()).unwrap();
+            entries[offset + i] = DirectoryEntry::new(e.name(), e.kind().file_type()).unwrap();
         });
         inner.pos += count;
         Ok(offset + count)
@@ -176,26 +270,58 @@ impl File {
     /// Reads directory entries into the target buffer
     pub fn readdir(&mut self, entries: &mut [DirectoryEntry]) -> Result<usize, Errno> {
         match &mut self.inner {
-            FileInner::Normal(inner) => {
-                assert_eq!(inner.vnode.kind(), VnodeKind::Directory);
-
+            FileInner::Normal(inner) if inner.vnode.kind() == VnodeKind::Directory => {
                 if inner.vnode.flags() & Vnode::CACHE_READDIR != 0 {
                     Self::cache_readdir(inner, entries)
                 } else {
-                    todo!();
+                    let count = inner.vnode.readdir(inner.pos, entries)?;
+                    inner.pos += count;
+                    Ok(count)
                 }
             },
-            _ => todo!(),
+            _ => Err(Errno::NotADirectory),
+        }
+    }
+
+    /// Reads directory entries along with their file status into the
+    /// target buffer
+    pub fn readdir_plus(&mut self, entries: &mut [DirectoryEntryPlus]) -> Result<usize, Errno> {
+        match &mut self.inner {
+            FileInner::Normal(inner) if inner.vnode.kind() == VnodeKind::Directory => {
+                if inner.vnode.flags() & Vnode::CACHE_READDIR != 0 {
+                    let mut names = vec![DirectoryEntry::empty(); entries.len()];
+                    let count = Self::cache_readdir(inner, &mut names)?;
+                    for (name, entry) in names.iter().zip(entries.iter_mut()).take(count) {
+                        let stat = inner.vnode.entry_stat(name.as_str()).ok();
+                        *entry = DirectoryEntryPlus::new(*name, stat);
+                    }
+                    Ok(count)
+                } else {
+                    let count = inner.vnode.readdir_plus(inner.pos, entries)?;
+                    inner.pos += count;
+                    Ok(count)
+                }
+            },
+            _ => Err(Errno::NotADirectory),
         }
     }
 }
 
 impl Drop for File {
     fn drop(&mut self) {
+        limits::FILES.release();
+        if let Some(node) = self.node() {
+            node.open_count.set(node.open_count.get() - 1);
+        }
         match &mut self.inner {
             FileInner::Normal(inner) => {
                 inner.vnode.close().ok();
             }
+            FileInner::Fifo(inner) => {
+                inner
+                    .pipe
+                    .close(self.flags & Self::READ != 0, self.flags & Self::WRITE != 0);
+            }
             _ => unimplemented!(),
         }
     }
@@ -205,13 +331,13 @@ impl Drop for File {
 mod tests {
     use super::*;
     use crate::{Vnode, VnodeImpl, VnodeKind, VnodeRef};
-    use libsys::{stat::OpenFlags, ioctl::IoctlCmd, stat::Stat};
+    use libsys::{stat::{FileMode, OpenFlags}, ioctl::IoctlCmd, stat::Stat};
     use alloc::boxed::Box;
     use alloc::rc::Rc;
 
     struct DummyInode;
 
-    #[auto_inode]
+    #[auto_inode(crate = crate)]
     impl VnodeImpl for DummyInode {
         fn create(
             &mut self,
@@ -271,4 +397,85 @@ mod tests {
             assert_eq!(((i + 96) & 0xFF) as u8, buf[i]);
         }
     }
+
+    #[test]
+    fn test_normal_pread() {
+        let node = Vnode::new("", VnodeKind::Regular, 0);
+        node.set_data(Box::new(DummyInode {}));
+        let file = node.open(OpenFlags::O_RDONLY).unwrap();
+        let mut buf = [0u8; 16];
+
+        assert_eq!(file.borrow_mut().pread(100, &mut buf).unwrap(), 16);
+        for i in 0..16 {
+            assert_eq!((i + 100) as u8, buf[i]);
+        }
+        assert_eq!(file.borrow_mut().pread(120, &mut buf).unwrap(), 3);
+        // File position is not affected
+        assert_eq!(file.borrow_mut().read(&mut buf[0..4]).unwrap(), 4);
+        assert_eq!(buf[0], 0);
+        assert_eq!(file.borrow_mut().pwrite(0, &buf).unwrap_err(), Errno::ReadOnly);
+    }
+
+    #[test]
+    fn test_cache_readdir_plus() {
+        let root = Vnode::new("", VnodeKind::Directory, Vnode::CACHE_READDIR | Vnode::CACHE_STAT);
+        let child = Vnode::new("a", VnodeKind::Regular, Vnode::CACHE_STAT);
+        child.props_mut().mode = FileMode::default_reg();
+        root.attach(child);
+
+        let file = root.open(OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY).unwrap();
+        let mut buf = [DirectoryEntryPlus::empty(); 2];
+
+        assert_eq!(file.borrow_mut().readdir_plus(&mut buf).unwrap(), 2);
+        assert_eq!(buf[0].as_str(), ".");
+        assert_eq!(buf[1].as_str(), "..");
+        assert!(buf[0].stat().is_some());
+
+        assert_eq!(file.borrow_mut().readdir_plus(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0].as_str(), "a");
+        assert_eq!(buf[0].stat().unwrap().mode, FileMode::default_reg());
+
+        assert_eq!(file.borrow_mut().readdir_plus(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_readdir_not_directory() {
+        let node = Vnode::new("", VnodeKind::Regular, 0);
+        node.set_data(Box::new(DummyInode {}));
+        let file = node.open(OpenFlags::O_RDONLY).unwrap();
+        let mut buf = [DirectoryEntry::empty(); 2];
+        let mut buf_plus = [DirectoryEntryPlus::empty(); 2];
+
+        assert_eq!(file.borrow_mut().readdir(&mut buf), Err(Errno::NotADirectory));
+        assert_eq!(
+            file.borrow_mut().readdir_plus(&mut buf_plus),
+            Err(Errno::NotADirectory)
+        );
+
+        let fifo = Vnode::new("fifo", VnodeKind::Fifo, 0);
+        let file = File::fifo(fifo, Pipe::new().unwrap(), File::READ);
+        assert_eq!(file.borrow_mut().readdir(&mut buf), Err(Errno::NotADirectory));
+        assert_eq!(
+            file.borrow_mut().readdir_plus(&mut buf_plus),
+            Err(Errno::NotADirectory)
+        );
+    }
+
+    #[test]
+    fn test_cache_readdir_types() {
+        let root = Vnode::new("", VnodeKind::Directory, Vnode::CACHE_READDIR);
+        root.attach(Vnode::new("dir", VnodeKind::Directory, 0));
+        root.attach(Vnode::new("fifo", VnodeKind::Fifo, 0));
+
+        let file = root.open(OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY).unwrap();
+        let mut buf = [DirectoryEntry::empty(); 4];
+
+        assert_eq!(file.borrow_mut().readdir(&mut buf).unwrap(), 4);
+        assert!(buf[0].is_directory() && buf[1].is_directory());
+        assert_eq!(buf[2].as_str(), "dir");
+        assert!(buf[2].is_directory());
+        assert_eq!(buf[3].as_str(), "fifo");
+        assert_eq!(buf[3].file_type(), Some(FileMode::S_IFIFO));
+        assert_eq!(DirectoryEntry::empty().file_type(), None);
+    }
 }
@@ -2,7 +2,18 @@ use crate::{BlockDevice, VnodeRef};
 use alloc::rc::Rc;
 use core::any::Any;
 use core::cell::Ref;
-use libsys::error::Errno;
+use core::sync::atomic::{AtomicU64, Ordering};
+use libsys::{error::Errno, stat::IoStatistics};
+
+/// Counters of data read from and written to vnodes of a filesystem (or
+/// by a process), whether or not the request reached the storage device
+#[derive(Default)]
+pub struct IoCounters {
+    read_bytes: AtomicU64,
+    write_bytes: AtomicU64,
+    read_calls: AtomicU64,
+    write_calls: AtomicU64,
+}
 
 /// General filesystem interface
 pub trait Filesystem {
@@ -12,4 +23,58 @@ pub trait Filesystem {
     fn dev(self: Rc<Self>) -> Option<&'static dyn BlockDevice>;
     /// Returns filesystem's private data struct (if any)
     fn data(&self) -> Option<Ref<dyn Any>>;
+    /// Called before an unused `node` is dropped from the vnode cache
+    /// (see [VnodeLru](crate::VnodeLru)) to write back or dispose of its
+    /// inode state. Returning an error keeps the node cached.
+    fn evict(&self, _node: &VnodeRef) -> Result<(), Errno> {
+        Ok(())
+    }
+    /// Called once the filesystem's vnode tree is released on unmount.
+    /// The filesystem has to drop the references it holds to its vnodes
+    /// (such as the root), or it will never be freed.
+    fn unmount(&self) {}
+    /// Returns `true` if the filesystem matches names regardless of their
+    /// (ASCII) letter case. Names are still stored and reported in the
+    /// case they were created with.
+    fn case_insensitive(&self) -> bool {
+        false
+    }
+    /// Returns I/O counters of the filesystem, if it keeps any
+    fn io_counters(&self) -> Option<&IoCounters> {
+        None
+    }
+}
+
+impl IoCounters {
+    /// Constructs a zeroed set of counters
+    pub const fn new() -> Self {
+        Self {
+            read_bytes: AtomicU64::new(0),
+            write_bytes: AtomicU64::new(0),
+            read_calls: AtomicU64::new(0),
+            write_calls: AtomicU64::new(0),
+        }
+    }
+
+    /// Accounts a read request which returned `count` bytes
+    pub fn account_read(&self, count: usize) {
+        self.read_bytes.fetch_add(count as u64, Ordering::Relaxed);
+        self.read_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Accounts a write request which accepted `count` bytes
+    pub fn account_write(&self, count: usize) {
+        self.write_bytes.fetch_add(count as u64, Ordering::Relaxed);
+        self.write_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the counters
+    pub fn statistics(&self) -> IoStatistics {
+        IoStatistics {
+            read_bytes: self.read_bytes.load(Ordering::Relaxed),
+            write_bytes: self.write_bytes.load(Ordering::Relaxed),
+            read_calls: self.read_calls.load(Ordering::Relaxed),
+            write_calls: self.write_calls.load(Ordering::Relaxed),
+        }
+    }
 }
@@ -1,73 +1,255 @@
-use crate::{FileRef, VnodeKind, VnodeRef};
+use crate::{FileRef, MountNamespace, VnodeKind, VnodeRef};
+use alloc::{rc::Rc, string::String, vec::Vec};
+use core::cell::RefCell;
 use libsys::{
     error::Errno,
     path::{path_component_left, path_component_right},
-    stat::{FileMode, GroupId, OpenFlags, UserId},
+    proc::Capabilities,
+    stat::{AccessMode, FileMode, GroupId, MountFlags, OpenFlags, UserId},
 };
 
+/// Path resolution behavior of an [Ioctx]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResolveOptions {
+    /// Whether symbolic links in the last path component are followed
+    /// unless a call says otherwise
+    pub follow: bool,
+    /// Maximum number of components walked while resolving a path
+    pub max_components: usize,
+    /// Fall back to case-insensitive name matching on filesystems which
+    /// support it
+    pub case_fold: bool,
+}
+
+/// Builder for [Ioctx] with non-default resolution options
+pub struct IoctxBuilder {
+    root: VnodeRef,
+    uid: UserId,
+    gid: GroupId,
+    caps: Capabilities,
+    options: ResolveOptions,
+}
+
 /// I/O context structure
 #[derive(Clone)]
 pub struct Ioctx {
     root: VnodeRef,
     cwd: VnodeRef,
-    /// Process user ID
+    mounts: Option<Rc<RefCell<MountNamespace>>>,
+    options: ResolveOptions,
+    /// Process (real) user ID
     pub uid: UserId,
-    /// Process group ID
+    /// Process (real) group ID
     pub gid: GroupId,
+    /// Effective user ID, used for permission checks
+    pub euid: UserId,
+    /// Effective group ID, used for permission checks
+    pub egid: GroupId,
+    /// Capabilities of the process, the ones relevant for filesystem
+    /// access are DAC_OVERRIDE and FOWNER
+    pub caps: Capabilities,
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        Self {
+            follow: true,
+            // As many as fit in a 4096-byte path
+            max_components: 2048,
+            case_fold: false,
+        }
+    }
+}
+
+impl IoctxBuilder {
+    /// Sets the user and group IDs of the context, root by default
+    pub fn ids(mut self, uid: UserId, gid: GroupId) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// Sets the capabilities of the context, none by default
+    pub fn caps(mut self, caps: Capabilities) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Sets whether symbolic links are followed by default
+    pub fn follow(mut self, follow: bool) -> Self {
+        self.options.follow = follow;
+        self
+    }
+
+    /// Limits the number of components walked while resolving a path
+    pub fn max_components(mut self, limit: usize) -> Self {
+        self.options.max_components = limit;
+        self
+    }
+
+    /// Enables case-insensitive name matching on filesystems supporting it
+    pub fn case_fold(mut self, case_fold: bool) -> Self {
+        self.options.case_fold = case_fold;
+        self
+    }
+
+    /// Creates the context
+    pub fn build(self) -> Ioctx {
+        Ioctx {
+            cwd: self.root.clone(),
+            mounts: None,
+            options: self.options,
+            uid: self.uid,
+            gid: self.gid,
+            euid: self.uid,
+            egid: self.gid,
+            caps: self.caps,
+            root: self.root,
+        }
+    }
 }
 
 impl Ioctx {
-    /// Creates a new I/O context with given root node
+    /// Creates a new I/O context with given root node and default
+    /// resolution options
     pub fn new(root: VnodeRef, uid: UserId, gid: GroupId) -> Self {
-        Self {
-            cwd: root.clone(),
-            uid,
-            gid,
+        Self::builder(root).ids(uid, gid).build()
+    }
+
+    /// Starts building an I/O context with given root node
+    pub fn builder(root: VnodeRef) -> IoctxBuilder {
+        IoctxBuilder {
             root,
+            uid: UserId::root(),
+            gid: GroupId::root(),
+            caps: Capabilities::empty(),
+            options: ResolveOptions::default(),
         }
     }
 
-    fn _find(&self, mut at: VnodeRef, path: &str, follow: bool) -> Result<VnodeRef, Errno> {
-        let mut element;
-        let mut rest = path;
+    /// Returns path resolution options of the context
+    pub fn options(&self) -> &ResolveOptions {
+        &self.options
+    }
 
-        loop {
-            (element, rest) = path_component_left(rest);
+    /// Returns whether the last path component is followed if it is a
+    /// symbolic link, given `nofollow` requested by the caller
+    pub fn follows(&self, nofollow: bool) -> bool {
+        self.options.follow && !nofollow
+    }
 
-            if !at.is_directory() {
-                return Err(Errno::NotADirectory);
+    /// Detaches the context from its current mount namespace, giving it a
+    /// private copy of the mount table. Clones of the context made
+    /// afterwards share the new table.
+    pub fn unshare_mounts(&mut self) {
+        let ns = match &self.mounts {
+            Some(ns) => ns.borrow().clone(),
+            None => MountNamespace::new(),
+        };
+        self.mounts = Some(Rc::new(RefCell::new(ns)));
+    }
+
+    /// Attaches filesystem `root` at `at` directory, either within the
+    /// private mount namespace of the context or globally
+    pub fn mount(&self, at: VnodeRef, root: VnodeRef, flags: MountFlags) -> Result<(), Errno> {
+        if let Some(ns) = &self.mounts {
+            ns.borrow_mut().mount(at, root.clone())?;
+        } else {
+            at.mount(root.clone())?;
+        }
+        root.set_mount_flags(flags);
+        Ok(())
+    }
+
+    /// Detaches the filesystem `root` (as returned by path lookup of the
+    /// mount point) from wherever it is mounted
+    pub fn unmount(&self, root: &VnodeRef) -> Result<(), Errno> {
+        let at = root.parent();
+        let is_root = |target: Option<VnodeRef>| target.map_or(false, |t| Rc::ptr_eq(&t, root));
+
+        if let Some(ns) = &self.mounts {
+            let mut ns = ns.borrow_mut();
+            if is_root(ns.target(&at)) {
+                return ns.unmount(&at);
             }
+        }
+        if is_root(at.target()) {
+            at.unmount()
+        } else {
+            Err(Errno::InvalidArgument)
+        }
+    }
 
-            match element {
-                ".." => {
-                    at = at.parent();
-                }
-                "." => {}
-                _ => break,
+    fn target(&self, node: &VnodeRef) -> Option<VnodeRef> {
+        if let Some(ns) = &self.mounts {
+            if let Some(target) = ns.borrow().target(node) {
+                return Some(target);
             }
         }
+        node.target()
+    }
+
+    fn lookup(&self, at: &VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
+        if self.options.case_fold {
+            at.lookup_or_load_folded(name)
+        } else {
+            at.lookup_or_load(name)
+        }
+    }
 
-        while let Some(target) = at.target() {
+    // TODO `follow` decides whether the last component is dereferenced
+    //      once there are symbolic links
+    fn _find(&self, mut at: VnodeRef, path: &str, _follow: bool) -> Result<VnodeRef, Errno> {
+        let mut element;
+        let mut rest = path;
+        let mut components = 0;
+
+        while let Some(target) = self.target(&at) {
             assert!(at.kind() == VnodeKind::Directory);
             at = target;
         }
 
-        if element.is_empty() && rest.is_empty() {
-            return Ok(at);
-        }
-        assert!(!element.is_empty());
+        // Iterative, so that long paths don't eat up the kernel stack
+        loop {
+            loop {
+                (element, rest) = path_component_left(rest);
 
-        let mut node = at.lookup_or_load(element)?;
+                components += 1;
+                if components > self.options.max_components {
+                    return Err(Errno::InvalidArgument);
+                }
+                if !at.is_directory() {
+                    return Err(Errno::NotADirectory);
+                }
 
-        while let Some(target) = node.target() {
-            assert!(node.kind() == VnodeKind::Directory);
-            node = target;
-        }
+                match element {
+                    // Never leave the root of the context
+                    ".." if !Rc::ptr_eq(&at, &self.root) => {
+                        at = at.parent();
+                    }
+                    "." | ".." => {}
+                    _ => break,
+                }
+            }
 
-        if rest.is_empty() {
-            Ok(node)
-        } else {
-            self._find(node, rest, follow)
+            // ".." out of a mounted root leads to the directory it is
+            // mounted at, which must not be followed into the mount again
+            if element.is_empty() && rest.is_empty() {
+                return Ok(at);
+            }
+            assert!(!element.is_empty());
+
+            let mut node = self.lookup(&at, element)?;
+
+            while let Some(target) = self.target(&node) {
+                assert!(node.kind() == VnodeKind::Directory);
+                node = target;
+            }
+
+            if rest.is_empty() {
+                return Ok(node);
+            }
+            at = node;
         }
     }
 
@@ -87,7 +269,12 @@ impl Ioctx {
             self.cwd.clone()
         };
 
-        self._find(at, path, follow)
+        let node = self._find(at, path, follow)?;
+        // Trailing slash only makes sense for directories
+        if path.ends_with('/') && !node.is_directory() {
+            return Err(Errno::NotADirectory);
+        }
+        Ok(node)
     }
 
     /// Creates a new directory
@@ -98,13 +285,70 @@ impl Ioctx {
         mode: FileMode,
     ) -> Result<VnodeRef, Errno> {
         let (parent, name) = path_component_right(path);
-        self.find(at, parent, true)?.create(
+        self.find(at, parent, true)?.create_as(
+            self,
             name.trim_start_matches('/'),
             mode,
             VnodeKind::Directory,
         )
     }
 
+    /// Creates a special (device or FIFO) or regular file node.
+    /// Node type is determined by the file type bits of `mode`.
+    pub fn mknod(
+        &self,
+        at: Option<VnodeRef>,
+        path: &str,
+        mode: FileMode,
+        rdev: u32,
+    ) -> Result<VnodeRef, Errno> {
+        let kind = match mode & FileMode::FILE_TYPE {
+            FileMode::S_IFCHR => VnodeKind::Char,
+            FileMode::S_IFBLK => VnodeKind::Block,
+            FileMode::S_IFIFO => VnodeKind::Fifo,
+            FileMode::S_IFREG => VnodeKind::Regular,
+            _ => return Err(Errno::InvalidArgument),
+        };
+        let (parent, name) = path_component_right(path);
+        let parent = self.find(at, parent, true)?;
+        parent.check_access(self, AccessMode::W_OK)?;
+
+        let node = parent.create_as(self, name.trim_start_matches('/'), mode, kind)?;
+        if kind == VnodeKind::Char || kind == VnodeKind::Block {
+            node.props_mut().rdev = rdev;
+        }
+        Ok(node)
+    }
+
+    /// Removes a filesystem node. Directories are only removed if `dir` is
+    /// set and they're empty.
+    pub fn unlink(&self, at: Option<VnodeRef>, path: &str, dir: bool) -> Result<(), Errno> {
+        let (parent, name) = path_component_right(path);
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(Errno::InvalidArgument);
+        }
+        let parent = self.find(at, parent, true)?;
+        parent.check_access(self, AccessMode::W_OK)?;
+
+        let node = self.lookup(&parent, name)?;
+        parent.check_sticky(&node, self)?;
+        if node.is_directory() {
+            if !dir {
+                return Err(Errno::IsADirectory);
+            }
+            if node.has_children() {
+                return Err(Errno::NotEmpty);
+            }
+        } else if dir {
+            return Err(Errno::NotADirectory);
+        }
+        if node.is_open() || node.target().is_some() || self.target(&node).is_some() {
+            return Err(Errno::Busy);
+        }
+
+        parent.unlink(name)
+    }
+
     /// Opens (and possibly creates) a filesystem path for access
     pub fn open(
         &self,
@@ -113,11 +357,12 @@ impl Ioctx {
         mode: FileMode,
         opts: OpenFlags,
     ) -> Result<FileRef, Errno> {
-        let node = match self.find(at.clone(), path, true) {
+        let follow = self.follows(opts.contains(OpenFlags::O_NOFOLLOW));
+        let node = match self.find(at.clone(), path, follow) {
             Err(Errno::DoesNotExist) => {
                 let (parent, name) = path_component_right(path);
                 let at = self.find(at, parent, true)?;
-                at.create(name, mode, VnodeKind::Regular)
+                at.create_as(self, name, mode, VnodeKind::Regular)
             }
             o => o,
         }?;
@@ -134,18 +379,52 @@ impl Ioctx {
         self.cwd = node;
         Ok(())
     }
+
+    /// Returns the absolute path of current working directory
+    pub fn cwd_path(&self) -> String {
+        let mut elements = Vec::new();
+        let mut node = self.cwd.clone();
+        while !Rc::ptr_eq(&node, &self.root) {
+            let parent = node.parent();
+            if Rc::ptr_eq(&parent, &node) {
+                break;
+            }
+            // Roots of mounted filesystems are named after their mount point
+            let mounted = self
+                .target(&parent)
+                .map_or(false, |target| Rc::ptr_eq(&target, &node));
+            if !mounted {
+                elements.push(node);
+            }
+            node = parent;
+        }
+
+        let mut path = String::new();
+        for node in elements.iter().rev() {
+            path.push('/');
+            path.push_str(node.name());
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Vnode, VnodeImpl, VnodeKind};
-    use alloc::{boxed::Box, rc::Rc};
+    use crate::mock::{MockFilesystem, MockOp};
+    use libsys::traits::Write;
+    use crate::{Filesystem, Vnode, VnodeImpl, VnodeKind};
+    use alloc::{borrow::ToOwned, boxed::Box, rc::Rc, string::String};
+    use core::time::Duration;
+    use std::time::Instant;
     use libsys::{ioctl::IoctlCmd, stat::OpenFlags, stat::Stat};
 
     pub struct DummyInode;
 
-    #[auto_inode]
+    #[auto_inode(crate = crate)]
     impl VnodeImpl for DummyInode {
         fn create(
             &mut self,
@@ -161,6 +440,18 @@ mod tests {
         fn lookup(&mut self, _at: VnodeRef, _name: &str) -> Result<VnodeRef, Errno> {
             Err(Errno::DoesNotExist)
         }
+
+        fn remove(&mut self, _at: VnodeRef, _name: &str) -> Result<(), Errno> {
+            Ok(())
+        }
+
+        fn open(&mut self, _node: VnodeRef, _opts: OpenFlags) -> Result<usize, Errno> {
+            Ok(0)
+        }
+
+        fn close(&mut self, _node: VnodeRef) -> Result<(), Errno> {
+            Ok(())
+        }
     }
 
     #[test]
@@ -178,7 +469,7 @@ mod tests {
         d0.attach(d0f0.clone());
         d1.attach(d1f0.clone());
 
-        let ioctx = Ioctx::new(root.clone());
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
 
         assert!(Rc::ptr_eq(&root, &ioctx.find(None, "/", false).unwrap()));
         assert!(Rc::ptr_eq(&root, &ioctx.find(None, "/.", false).unwrap()));
@@ -251,7 +542,7 @@ mod tests {
         root.attach(d0.clone());
         d0.attach(d0f0.clone());
 
-        let ioctx = Ioctx::new(root.clone());
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
 
         assert_eq!(
             ioctx.find(None, "/dir0/file0/.", false).unwrap_err(),
@@ -269,7 +560,7 @@ mod tests {
     #[test]
     fn test_mkdir() {
         let root = Vnode::new("", VnodeKind::Directory, 0);
-        let ioctx = Ioctx::new(root.clone());
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
 
         root.set_data(Box::new(DummyInode {}));
 
@@ -292,7 +583,7 @@ mod tests {
         root_outer.clone().attach(dir0.clone());
         root_inner.clone().attach(dir1.clone());
 
-        let ioctx = Ioctx::new(root_outer.clone());
+        let ioctx = Ioctx::new(root_outer.clone(), UserId::root(), GroupId::root());
 
         assert_eq!(
             ioctx.find(None, "/dir0/dir1", false).unwrap_err(),
@@ -322,4 +613,345 @@ mod tests {
             &ioctx.find(None, "/dir0/dir1/../../..", false).unwrap()
         ));
     }
+
+    #[test]
+    fn test_cwd_path() {
+        let root_outer = Vnode::new("", VnodeKind::Directory, 0);
+        let dir0 = Vnode::new("dir0", VnodeKind::Directory, 0);
+        let root_inner = Vnode::new("", VnodeKind::Directory, 0);
+        let dir1 = Vnode::new("dir1", VnodeKind::Directory, 0);
+
+        root_outer.clone().attach(dir0.clone());
+        root_inner.clone().attach(dir1.clone());
+        dir0.mount(root_inner.clone()).unwrap();
+
+        let mut ioctx = Ioctx::new(root_outer.clone(), UserId::root(), GroupId::root());
+        assert_eq!(ioctx.cwd_path(), "/");
+
+        ioctx.chdir("/dir0").unwrap();
+        assert_eq!(ioctx.cwd_path(), "/dir0");
+        ioctx.chdir("dir1").unwrap();
+        assert_eq!(ioctx.cwd_path(), "/dir0/dir1");
+        ioctx.chdir("../..").unwrap();
+        assert_eq!(ioctx.cwd_path(), "/dir0");
+        ioctx.chdir("/").unwrap();
+        assert_eq!(ioctx.cwd_path(), "/");
+    }
+
+    #[test]
+    fn test_private_mount() {
+        let root_outer = Vnode::new("", VnodeKind::Directory, 0);
+        let dir0 = Vnode::new("dir0", VnodeKind::Directory, 0);
+        let root_inner = Vnode::new("", VnodeKind::Directory, 0);
+        let dir1 = Vnode::new("dir1", VnodeKind::Directory, 0);
+
+        root_outer.attach(dir0.clone());
+        root_inner.attach(dir1.clone());
+
+        let global = Ioctx::new(root_outer.clone(), UserId::root(), GroupId::root());
+        let mut private = global.clone();
+        private.unshare_mounts();
+
+        private
+            .mount(dir0.clone(), root_inner.clone(), MountFlags::empty())
+            .unwrap();
+
+        assert!(Rc::ptr_eq(
+            &dir1,
+            &private.find(None, "/dir0/dir1", false).unwrap()
+        ));
+        assert!(Rc::ptr_eq(
+            &dir0,
+            &private.find(None, "/dir0/dir1/../..", false).unwrap()
+        ));
+        assert_eq!(
+            global.find(None, "/dir0/dir1", false).unwrap_err(),
+            Errno::DoesNotExist
+        );
+        assert!(dir0.target().is_none());
+    }
+
+    #[test]
+    fn test_unlink() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(DummyInode {}));
+        root.props_mut().mode = FileMode::default_dir();
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
+
+        let d0 = root.create("dir0", FileMode::default_dir(), VnodeKind::Directory).unwrap();
+        d0.create("file0", FileMode::default_reg(), VnodeKind::Regular).unwrap();
+
+        assert_eq!(ioctx.unlink(None, "/dir0", false), Err(Errno::IsADirectory));
+        assert_eq!(ioctx.unlink(None, "/dir0", true), Err(Errno::NotEmpty));
+        assert_eq!(ioctx.unlink(None, "/dir0/file0", true), Err(Errno::NotADirectory));
+        ioctx.unlink(None, "/dir0/file0", false).unwrap();
+        assert_eq!(ioctx.find(None, "/dir0/file0", false).unwrap_err(), Errno::DoesNotExist);
+
+        let dir = d0.open(OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY).unwrap();
+        assert_eq!(ioctx.unlink(None, "/dir0", true), Err(Errno::Busy));
+        drop(dir);
+        ioctx.unlink(None, "/dir0", true).unwrap();
+        assert!(!root.has_children());
+    }
+
+    #[test]
+    fn test_unlink_sticky() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(DummyInode {}));
+        root.props_mut().mode =
+            FileMode::from_bits(0o777).unwrap() | FileMode::STICKY | FileMode::S_IFDIR;
+        let user = Ioctx::new(root.clone(), UserId::from(1000), GroupId::from(100));
+        let other = Ioctx::new(root.clone(), UserId::from(1001), GroupId::from(100));
+
+        let flags = OpenFlags::O_WRONLY | OpenFlags::O_CREAT;
+        let node = user
+            .open(None, "/file0", FileMode::default_reg(), flags)
+            .unwrap()
+            .borrow()
+            .node()
+            .unwrap();
+        assert_eq!(node.props().uid, UserId::from(1000));
+        assert_eq!(node.props().gid, GroupId::from(100));
+
+        assert_eq!(other.unlink(None, "/file0", false), Err(Errno::PermissionDenied));
+        user.unlink(None, "/file0", false).unwrap();
+    }
+
+    #[test]
+    fn test_create_setgid() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(DummyInode {}));
+        root.props_mut().mode = FileMode::from_bits(0o777).unwrap() | FileMode::S_IFDIR;
+        let user = Ioctx::new(root.clone(), UserId::from(1000), GroupId::from(100));
+        let setid = FileMode::from_bits(0o755).unwrap() | FileMode::SETUID | FileMode::SETGID;
+
+        // Own group: both bits are kept
+        let node = user.mknod(None, "/file0", setid | FileMode::S_IFREG, 0).unwrap();
+        assert_eq!(node.props().mode, setid | FileMode::S_IFREG);
+
+        // Set-group-ID directory of a group the creator is not in
+        root.props_mut().mode |= FileMode::SETGID;
+        root.props_mut().gid = GroupId::from(200);
+        let node = user.mknod(None, "/file1", setid | FileMode::S_IFREG, 0).unwrap();
+        assert_eq!(node.props().gid, GroupId::from(200));
+        assert_eq!(
+            node.props().mode,
+            (setid & !FileMode::SETGID) | FileMode::S_IFREG
+        );
+        let dir = user.mkdir(None, "/dir0", FileMode::default_dir()).unwrap();
+        assert_eq!(dir.props().gid, GroupId::from(200));
+        assert!(dir.props().mode.contains(FileMode::SETGID));
+    }
+
+    #[test]
+    fn test_builder_options() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        let d0 = Vnode::new("Dir0", VnodeKind::Directory, 0);
+        let d0f0 = Vnode::new("FILE0", VnodeKind::Regular, 0);
+        root.attach(d0.clone());
+        d0.attach(d0f0.clone());
+
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
+        assert_eq!(ioctx.options(), &ResolveOptions::default());
+        assert!(ioctx.follows(false));
+        assert!(!ioctx.follows(true));
+        assert_eq!(
+            ioctx.find(None, "/dir0/file0", false).unwrap_err(),
+            Errno::DoesNotExist
+        );
+
+        let folded = Ioctx::builder(root.clone())
+            .ids(UserId::from(1), GroupId::from(1))
+            .case_fold(true)
+            .follow(false)
+            .max_components(3)
+            .build();
+        assert_eq!(folded.uid, UserId::from(1));
+        assert!(!folded.follows(false));
+        assert!(Rc::ptr_eq(&d0f0, &folded.find(None, "/dir0/file0", true).unwrap()));
+        assert!(Rc::ptr_eq(&d0f0, &folded.find(None, "/DIR0/./File0", true).unwrap()));
+        assert_eq!(
+            folded.find(None, "/dir0/./../dir0/file0", true).unwrap_err(),
+            Errno::InvalidArgument
+        );
+    }
+
+    fn mock_ioctx() -> (Rc<MockFilesystem>, Ioctx) {
+        let fs = MockFilesystem::new();
+        fs.add_dir("dir0");
+        fs.add_dir("dir0/dir1");
+        fs.add_file("dir0/file0", b"hello");
+        fs.add_file("file1", b"");
+        let root = fs.clone().root().unwrap();
+        (fs, Ioctx::new(root, UserId::root(), GroupId::root()))
+    }
+
+    fn find_name(ioctx: &Ioctx, path: &str) -> Result<String, Errno> {
+        ioctx.find(None, path, false).map(|node| node.name().to_owned())
+    }
+
+    #[test]
+    fn test_find_dotdot_at_root() {
+        let (_fs, mut ioctx) = mock_ioctx();
+
+        assert_eq!(find_name(&ioctx, "/.."), Ok("".to_owned()));
+        assert_eq!(find_name(&ioctx, "/../../dir0"), Ok("dir0".to_owned()));
+        assert_eq!(find_name(&ioctx, "/dir0/dir1/../../.."), Ok("".to_owned()));
+
+        // ".." does not escape a context rooted below the filesystem root
+        let dir0 = ioctx.find(None, "/dir0", false).unwrap();
+        let mut chroot = Ioctx::new(dir0.clone(), UserId::root(), GroupId::root());
+        assert!(Rc::ptr_eq(&chroot.find(None, "/..", false).unwrap(), &dir0));
+        chroot.chdir("dir1").unwrap();
+        assert!(Rc::ptr_eq(&chroot.find(None, "../../..", false).unwrap(), &dir0));
+        assert_eq!(find_name(&chroot, "../../../file1"), Err(Errno::DoesNotExist));
+
+        ioctx.chdir("/dir0/dir1").unwrap();
+        assert_eq!(find_name(&ioctx, "../../file1"), Ok("file1".to_owned()));
+    }
+
+    #[test]
+    fn test_find_slashes() {
+        let (_fs, ioctx) = mock_ioctx();
+
+        assert_eq!(find_name(&ioctx, "//dir0///dir1"), Ok("dir1".to_owned()));
+        assert_eq!(find_name(&ioctx, "/dir0/dir1/"), Ok("dir1".to_owned()));
+        assert_eq!(find_name(&ioctx, "/dir0/dir1//"), Ok("dir1".to_owned()));
+        assert_eq!(find_name(&ioctx, "dir0/./dir1/."), Ok("dir1".to_owned()));
+        assert_eq!(find_name(&ioctx, "/dir0/file0"), Ok("file0".to_owned()));
+        assert_eq!(find_name(&ioctx, "/dir0/file0/"), Err(Errno::NotADirectory));
+        assert_eq!(find_name(&ioctx, "/dir0/file0/."), Err(Errno::NotADirectory));
+        assert_eq!(find_name(&ioctx, "/file1/dir0"), Err(Errno::NotADirectory));
+        // Empty path refers to the starting directory
+        assert_eq!(find_name(&ioctx, ""), Ok("".to_owned()));
+        assert_eq!(find_name(&ioctx, "///"), Ok("".to_owned()));
+    }
+
+    #[test]
+    fn test_find_long_path() {
+        const DEPTH: usize = 512;
+        let (fs, ioctx) = mock_ioctx();
+        let mut path = String::new();
+        for _ in 0..DEPTH {
+            path.push_str("d/");
+            fs.add_dir(path.trim_end_matches('/'));
+        }
+
+        let node = ioctx.find(None, &path, false).unwrap();
+        assert!(node.is_directory());
+        assert_eq!(fs.calls(MockOp::Lookup), DEPTH);
+
+        // A lot of components which don't load anything
+        let mut dots = String::from("/dir0");
+        for _ in 0..DEPTH {
+            dots.push_str("/./dir1/..");
+        }
+        assert_eq!(find_name(&ioctx, &dots), Ok("dir0".to_owned()));
+        assert_eq!(fs.calls(MockOp::Lookup), DEPTH + 2);
+    }
+
+    #[test]
+    fn test_find_errors() {
+        let (fs, ioctx) = mock_ioctx();
+
+        fs.fail(MockOp::Lookup, "dir0/dir1", Errno::DeviceError);
+        assert_eq!(find_name(&ioctx, "/dir0/dir1"), Err(Errno::DeviceError));
+        assert_eq!(find_name(&ioctx, "/dir0/dir2"), Err(Errno::DoesNotExist));
+        // Failed lookups leave nothing behind in the tree
+        let dir0 = ioctx.find(None, "/dir0", false).unwrap();
+        assert!(dir0.lookup("dir1").is_none());
+        assert!(dir0.lookup("dir2").is_none());
+
+        fs.clear_faults();
+        assert_eq!(find_name(&ioctx, "/dir0/dir1"), Ok("dir1".to_owned()));
+
+        // Cached vnodes are not affected by later device failures
+        fs.fail(MockOp::Lookup, "dir0/dir1", Errno::DeviceError);
+        assert_eq!(find_name(&ioctx, "/dir0/dir1"), Ok("dir1".to_owned()));
+    }
+
+    #[test]
+    fn test_find_cached() {
+        let (fs, ioctx) = mock_ioctx();
+        fs.set_delay(Duration::from_millis(20));
+
+        let start = Instant::now();
+        let file = ioctx.find(None, "/dir0/file0", false).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(fs.calls(MockOp::Lookup), 2);
+
+        // Loaded vnodes are found in memory from now on
+        let start = Instant::now();
+        assert!(Rc::ptr_eq(&file, &ioctx.find(None, "/dir0/file0", false).unwrap()));
+        assert!(start.elapsed() < Duration::from_millis(20));
+        assert_eq!(fs.calls(MockOp::Lookup), 2);
+
+        fs.set_delay(Duration::ZERO);
+        let mut buf = [0; 8];
+        assert_eq!(file.read(1, &mut buf), Ok(4));
+        assert_eq!(&buf[..4], b"ello");
+    }
+
+    #[test]
+    fn test_open_create() {
+        let (fs, ioctx) = mock_ioctx();
+
+        fs.fail(MockOp::Create, "dir0/file2", Errno::NoSpace);
+        let res = ioctx.open(None, "/dir0/file2", FileMode::default_reg(), OpenFlags::O_RDWR);
+        assert_eq!(res.err(), Some(Errno::NoSpace));
+        fs.clear_faults();
+
+        let res = ioctx.open(None, "/dir0/file0/", FileMode::default_reg(), OpenFlags::O_RDWR);
+        assert_eq!(res.err(), Some(Errno::NotADirectory));
+
+        let file = ioctx
+            .open(None, "/dir0/file2", FileMode::default_reg(), OpenFlags::O_RDWR)
+            .unwrap();
+        file.borrow_mut().write(b"data").unwrap();
+        assert_eq!(fs.data("dir0/file2"), Some(b"data".to_vec()));
+    }
+
+    // Not a real benchmark harness (no_std crate), just reports timings
+    // with `cargo test -- --nocapture bench_`
+    #[test]
+    fn bench_deep_path_lookup() {
+        use alloc::format;
+
+        const DEPTH: usize = 32;
+        const SIBLINGS: usize = 256;
+        const ITERATIONS: usize = 1000;
+
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        let mut at = root.clone();
+        let mut path = String::new();
+        for level in 0..DEPTH {
+            // The directory to descend into is the last one in each level,
+            // all the names have the same length and a common prefix
+            for i in 0..SIBLINGS {
+                let name = format!("component{:06}", i);
+                at.attach(Vnode::new(&name, VnodeKind::Regular, 0));
+            }
+            let name = format!("component{:06}", SIBLINGS + level);
+            let dir = Vnode::new(&name, VnodeKind::Directory, 0);
+            at.attach(dir.clone());
+            path.push('/');
+            path.push_str(dir.name());
+            at = dir;
+        }
+
+        let ioctx = Ioctx::new(root.clone(), UserId::root(), GroupId::root());
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            assert!(Rc::ptr_eq(&at, &ioctx.find(None, &path, false).unwrap()));
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "{} lookups of {}-component path, {} entries per directory: {:?} per lookup",
+            ITERATIONS,
+            DEPTH,
+            SIBLINGS + 1,
+            elapsed / ITERATIONS as u32
+        );
+    }
 }
@@ -0,0 +1,410 @@
+//! Write-ahead (redo) journal facility for filesystems.
+//!
+//! The journal occupies a range of blocks of the underlying [BlockDevice]:
+//!
+//! * block 0: journal superblock (magic, sequence number of the next
+//!   transaction)
+//! * block 1: descriptor of the current transaction (magic, sequence, list of
+//!   target blocks)
+//! * blocks 2..2 + N: contents of the blocks written by the transaction
+//! * block 2 + N: commit record (magic, sequence, checksum)
+//!
+//! Committing a transaction first writes all the records to the journal,
+//! then the blocks to their home locations, and finally advances the
+//! sequence number in the superblock. If interrupted after the commit record
+//! was written, the transaction is replayed when the journal is opened.
+use crate::BlockDevice;
+use alloc::{boxed::Box, vec, vec::Vec};
+use libsys::{error::Errno, mem::read_le32};
+
+const SUPERBLOCK_MAGIC: &[u8; 4] = b"JRNL";
+const DESCRIPTOR_MAGIC: &[u8; 4] = b"JTXD";
+const COMMIT_MAGIC: &[u8; 4] = b"JTXC";
+// magic + sequence + count
+const DESCRIPTOR_HEADER: usize = 16;
+
+/// Journal stored in a block range of a device
+pub struct Journal<'a> {
+    dev: &'a dyn BlockDevice,
+    start: usize,
+    len: usize,
+    block_size: usize,
+    sequence: u64,
+}
+
+/// Set of block writes to be committed atomically
+pub struct Transaction {
+    block_size: usize,
+    records: Vec<(usize, Box<[u8]>)>,
+}
+
+fn read_le64(src: &[u8]) -> u64 {
+    (read_le32(src) as u64) | ((read_le32(&src[4..]) as u64) << 32)
+}
+
+fn checksum(mut hash: u32, data: &[u8]) -> u32 {
+    // 32-bit FNV-1a
+    for &byte in data {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+impl Transaction {
+    /// Records a write of `data` to device block `block`. Later writes of
+    /// the same block within a transaction replace earlier ones. `data`
+    /// must be exactly one block long.
+    pub fn write(&mut self, block: usize, data: &[u8]) -> Result<(), Errno> {
+        if data.len() != self.block_size {
+            return Err(Errno::InvalidArgument);
+        }
+        if let Some((_, record)) = self.records.iter_mut().find(|(b, _)| *b == block) {
+            record.copy_from_slice(data);
+        } else {
+            self.records.push((block, data.into()));
+        }
+        Ok(())
+    }
+
+    /// Returns the number of blocks written by the transaction
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if the transaction contains no writes
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl<'a> Journal<'a> {
+    /// Initializes an empty journal in `len` blocks of `dev` starting at `start`
+    pub fn format(dev: &'a dyn BlockDevice, start: usize, len: usize) -> Result<Self, Errno> {
+        let mut journal = Self::new(dev, start, len)?;
+        journal.write_superblock()?;
+        Ok(journal)
+    }
+
+    /// Opens a journal in the block range, replaying a transaction which was
+    /// committed, but not completely written to its home location. Returns
+    /// the journal and the number of blocks replayed.
+    pub fn open(dev: &'a dyn BlockDevice, start: usize, len: usize) -> Result<(Self, usize), Errno> {
+        let mut journal = Self::new(dev, start, len)?;
+        let mut buf = vec![0u8; journal.block_size];
+
+        journal.read(0, &mut buf)?;
+        if &buf[..4] != SUPERBLOCK_MAGIC {
+            return Err(Errno::InvalidArgument);
+        }
+        journal.sequence = read_le64(&buf[8..]);
+
+        let replayed = journal.replay()?;
+        Ok((journal, replayed))
+    }
+
+    /// Returns the maximum number of blocks a transaction may write
+    pub fn capacity(&self) -> usize {
+        core::cmp::min(
+            (self.block_size - DESCRIPTOR_HEADER) / 8,
+            self.len - 3,
+        )
+    }
+
+    /// Starts a new transaction
+    pub fn begin(&self) -> Transaction {
+        Transaction {
+            block_size: self.block_size,
+            records: Vec::new(),
+        }
+    }
+
+    /// Atomically applies the writes of `tx`
+    pub fn commit(&mut self, tx: Transaction) -> Result<(), Errno> {
+        if tx.len() > self.capacity() {
+            return Err(Errno::InvalidArgument);
+        }
+        if tx.is_empty() {
+            return Ok(());
+        }
+        for (block, _) in tx.records.iter() {
+            if !self.is_valid_target(*block)? {
+                return Err(Errno::InvalidArgument);
+            }
+        }
+        let bs = self.block_size;
+        let mut buf = vec![0u8; bs];
+
+        // Descriptor
+        buf[..4].copy_from_slice(DESCRIPTOR_MAGIC);
+        buf[4..12].copy_from_slice(&self.sequence.to_le_bytes());
+        buf[12..16].copy_from_slice(&(tx.len() as u32).to_le_bytes());
+        for (i, (block, _)) in tx.records.iter().enumerate() {
+            let off = DESCRIPTOR_HEADER + i * 8;
+            buf[off..off + 8].copy_from_slice(&(*block as u64).to_le_bytes());
+        }
+        let mut hash = checksum(0x811C9DC5, &buf);
+        self.write(1, &buf)?;
+
+        // Redo records
+        for (i, (_, data)) in tx.records.iter().enumerate() {
+            hash = checksum(hash, data);
+            self.write(2 + i, data)?;
+        }
+
+        // The records have to be on the media before the commit record is
+        self.dev.flush()?;
+
+        // Commit record: the transaction is durable from this point
+        buf.fill(0);
+        buf[..4].copy_from_slice(COMMIT_MAGIC);
+        buf[4..12].copy_from_slice(&self.sequence.to_le_bytes());
+        buf[12..16].copy_from_slice(&hash.to_le_bytes());
+        self.write(2 + tx.len(), &buf)?;
+        self.dev.flush()?;
+
+        // Checkpoint
+        for (block, data) in tx.records.iter() {
+            self.dev.write_blocks(block * bs, data)?;
+        }
+        // Retiring the transaction before the checkpoint is durable would
+        // lose it
+        self.dev.flush()?;
+        self.sequence += 1;
+        self.write_superblock()
+    }
+
+    fn new(dev: &'a dyn BlockDevice, start: usize, len: usize) -> Result<Self, Errno> {
+        let block_size = dev.block_size();
+        if len < 4 || block_size < DESCRIPTOR_HEADER + 8 {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok(Self {
+            dev,
+            start,
+            len,
+            block_size,
+            sequence: 1,
+        })
+    }
+
+    fn replay(&mut self) -> Result<usize, Errno> {
+        let bs = self.block_size;
+        let mut desc = vec![0u8; bs];
+        let mut buf = vec![0u8; bs];
+
+        self.read(1, &mut desc)?;
+        let count = read_le32(&desc[12..]) as usize;
+        if &desc[..4] != DESCRIPTOR_MAGIC
+            || read_le64(&desc[4..]) != self.sequence
+            || count == 0
+            || count > self.capacity()
+        {
+            return Ok(0);
+        }
+
+        let mut hash = checksum(0x811C9DC5, &desc);
+        for i in 0..count {
+            self.read(2 + i, &mut buf)?;
+            hash = checksum(hash, &buf);
+        }
+        self.read(2 + count, &mut buf)?;
+        if &buf[..4] != COMMIT_MAGIC
+            || read_le64(&buf[4..]) != self.sequence
+            || read_le32(&buf[12..]) != hash
+        {
+            // Transaction was not committed
+            return Ok(0);
+        }
+
+        let blocks: Vec<usize> = (0..count)
+            .map(|i| read_le64(&desc[DESCRIPTOR_HEADER + i * 8..]) as usize)
+            .collect();
+        // A corrupt descriptor must not direct writes over arbitrary blocks
+        for &block in blocks.iter() {
+            if !self.is_valid_target(block)? {
+                return Err(Errno::InvalidFile);
+            }
+        }
+
+        for (i, &block) in blocks.iter().enumerate() {
+            self.read(2 + i, &mut buf)?;
+            self.dev.write_blocks(block * bs, &buf)?;
+        }
+        // Same as in commit(), the checkpoint has to be durable first
+        self.dev.flush()?;
+        self.sequence += 1;
+        self.write_superblock()?;
+        Ok(count)
+    }
+
+    // Checks that `block` is outside of the journal and within the device
+    fn is_valid_target(&self, block: usize) -> Result<bool, Errno> {
+        if block >= self.start && block < self.start + self.len {
+            return Ok(false);
+        }
+        match self.dev.block_count() {
+            Ok(count) => Ok(block < count),
+            // Size unknown, only the journal area can be checked
+            Err(Errno::NotImplemented) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write_superblock(&mut self) -> Result<(), Errno> {
+        let mut buf = vec![0u8; self.block_size];
+        buf[..4].copy_from_slice(SUPERBLOCK_MAGIC);
+        buf[8..16].copy_from_slice(&self.sequence.to_le_bytes());
+        self.write(0, &buf)
+    }
+
+    fn read(&self, index: usize, buf: &mut [u8]) -> Result<(), Errno> {
+        self.dev.read_blocks((self.start + index) * self.block_size, buf)
+    }
+
+    fn write(&self, index: usize, buf: &[u8]) -> Result<(), Errno> {
+        self.dev.write_blocks((self.start + index) * self.block_size, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockError;
+    use core::cell::{Cell, RefCell};
+
+    struct MemDevice {
+        data: RefCell<Vec<u8>>,
+        // Number of writes to allow before "losing power"
+        writes_left: Cell<usize>,
+    }
+
+    impl MemDevice {
+        fn new(blocks: usize) -> Self {
+            Self {
+                data: RefCell::new(vec![0; blocks * 512]),
+                writes_left: Cell::new(usize::MAX),
+            }
+        }
+
+        fn block(&self, block: usize) -> u8 {
+            self.data.borrow()[block * 512]
+        }
+    }
+
+    impl BlockDevice for MemDevice {
+        fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+            buf.copy_from_slice(&self.data.borrow()[pos..pos + buf.len()]);
+            Ok(())
+        }
+
+        fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+            if self.writes_left.get() == 0 {
+                return Err(BlockError::MediaGone);
+            }
+            self.writes_left.set(self.writes_left.get() - 1);
+            self.data.borrow_mut()[pos..pos + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn size(&self) -> Result<usize, Errno> {
+            Ok(self.data.borrow().len())
+        }
+    }
+
+    #[test]
+    fn test_commit() {
+        let dev = MemDevice::new(64);
+        let mut journal = Journal::format(&dev, 32, 16).unwrap();
+        assert_eq!(journal.capacity(), 13);
+
+        let mut tx = journal.begin();
+        tx.write(1, &[1; 512]).unwrap();
+        tx.write(2, &[2; 512]).unwrap();
+        tx.write(1, &[3; 512]).unwrap();
+        assert_eq!(tx.len(), 2);
+        journal.commit(tx).unwrap();
+        assert_eq!(dev.block(1), 3);
+        assert_eq!(dev.block(2), 2);
+
+        // Nothing to replay after a complete commit
+        let (_, replayed) = Journal::open(&dev, 32, 16).unwrap();
+        assert_eq!(replayed, 0);
+    }
+
+    #[test]
+    fn test_bad_target() {
+        let dev = MemDevice::new(64);
+        let mut journal = Journal::format(&dev, 32, 16).unwrap();
+
+        for block in [32, 47, 64] {
+            let mut tx = journal.begin();
+            tx.write(block, &[1; 512]).unwrap();
+            assert_eq!(journal.commit(tx), Err(Errno::InvalidArgument));
+        }
+
+        // Committed transaction with its descriptor pointing into the
+        // journal, checksum fixed up to match
+        dev.writes_left.set(4);
+        let mut tx = journal.begin();
+        tx.write(1, &[1; 512]).unwrap();
+        assert!(journal.commit(tx).is_err());
+        dev.writes_left.set(usize::MAX);
+        let desc = 33 * 512;
+        dev.data.borrow_mut()[desc + DESCRIPTOR_HEADER..desc + DESCRIPTOR_HEADER + 8]
+            .copy_from_slice(&40u64.to_le_bytes());
+        let hash = {
+            let data = dev.data.borrow();
+            checksum(checksum(0x811C9DC5, &data[desc..desc + 512]), &data[34 * 512..35 * 512])
+        };
+        dev.data.borrow_mut()[35 * 512 + 12..35 * 512 + 16].copy_from_slice(&hash.to_le_bytes());
+        assert_eq!(Journal::open(&dev, 32, 16).err(), Some(Errno::InvalidFile));
+        assert_eq!(dev.block(40), 0);
+    }
+
+    #[test]
+    fn test_write_bad_size() {
+        let dev = MemDevice::new(64);
+        let journal = Journal::format(&dev, 32, 16).unwrap();
+
+        let mut tx = journal.begin();
+        assert_eq!(tx.write(1, &[1; 100]), Err(Errno::InvalidArgument));
+        tx.write(1, &[1; 512]).unwrap();
+        assert_eq!(tx.write(1, &[2; 1024]), Err(Errno::InvalidArgument));
+        assert_eq!(tx.len(), 1);
+    }
+
+    #[test]
+    fn test_replay() {
+        let dev = MemDevice::new(64);
+        let mut journal = Journal::format(&dev, 32, 16).unwrap();
+
+        // Crash after the commit record, before checkpoint is complete:
+        // descriptor + 2 records + commit + 1 home write
+        dev.writes_left.set(5);
+        let mut tx = journal.begin();
+        tx.write(1, &[1; 512]).unwrap();
+        tx.write(2, &[2; 512]).unwrap();
+        assert!(journal.commit(tx).is_err());
+        assert_eq!(dev.block(1), 1);
+        assert_eq!(dev.block(2), 0);
+
+        dev.writes_left.set(usize::MAX);
+        let (mut journal, replayed) = Journal::open(&dev, 32, 16).unwrap();
+        assert_eq!(replayed, 2);
+        assert_eq!(dev.block(2), 2);
+
+        // Crash before the commit record: the transaction is discarded
+        dev.writes_left.set(2);
+        let mut tx = journal.begin();
+        tx.write(3, &[3; 512]).unwrap();
+        tx.write(4, &[4; 512]).unwrap();
+        assert!(journal.commit(tx).is_err());
+
+        dev.writes_left.set(usize::MAX);
+        let (_, replayed) = Journal::open(&dev, 32, 16).unwrap();
+        assert_eq!(replayed, 0);
+        assert_eq!(dev.block(3), 0);
+        assert_eq!(dev.block(4), 0);
+    }
+}
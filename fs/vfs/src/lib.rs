@@ -16,14 +16,27 @@ extern crate alloc;
 // pub use libsys::ioctl::IoctlCmd;
 
 mod block;
-pub use block::BlockDevice;
+pub use block::{BlockDevice, BlockDeviceWrapper, BlockError, BlockErrorCounters, MAX_RETRIES};
+mod cache;
+pub use cache::PageCache;
 mod fs;
-pub use fs::Filesystem;
+pub use fs::{Filesystem, IoCounters};
+mod journal;
+pub use journal::{Journal, Transaction};
 mod node;
 pub use node::{Vnode, VnodeImpl, VnodeKind, VnodeRef};
 mod ioctx;
-pub use ioctx::Ioctx;
+pub use ioctx::{Ioctx, IoctxBuilder, ResolveOptions};
+pub mod limits;
+mod lru;
+pub use lru::VnodeLru;
+mod ns;
+pub use ns::MountNamespace;
 mod file;
 pub use file::{File, FileRef};
+mod pipe;
+pub use pipe::Pipe;
 mod char;
 pub use crate::char::{CharDevice, CharDeviceWrapper};
+#[cfg(test)]
+mod mock;
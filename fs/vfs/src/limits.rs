@@ -0,0 +1,77 @@
+//! System-wide limits on open files, vnodes and pipes
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Usage counter with an adjustable upper bound. The bound is only checked
+/// by callers about to create a new object, so lowering it below current
+/// usage makes creation fail until enough objects are released.
+pub struct Limit {
+    used: AtomicUsize,
+    max: AtomicUsize,
+}
+
+/// Open file handles
+pub static FILES: Limit = Limit::new(4096);
+/// Vnodes present in memory
+pub static VNODES: Limit = Limit::new(16384);
+/// Pipe buffers
+pub static PIPES: Limit = Limit::new(256);
+
+impl Limit {
+    /// Constructs an unused counter with `max` bound
+    pub const fn new(max: usize) -> Self {
+        Self {
+            used: AtomicUsize::new(0),
+            max: AtomicUsize::new(max),
+        }
+    }
+
+    /// Returns the number of objects in use
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the upper bound
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Changes the upper bound
+    pub fn set_max(&self, max: usize) {
+        self.max.store(max, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if no more objects can be created
+    pub fn is_exhausted(&self) -> bool {
+        self.used() >= self.max()
+    }
+
+    pub(crate) fn acquire(&self) {
+        self.used.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn release(&self) {
+        self.used.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit() {
+        let limit = Limit::new(2);
+        assert!(!limit.is_exhausted());
+        limit.acquire();
+        limit.acquire();
+        assert_eq!(limit.used(), 2);
+        assert!(limit.is_exhausted());
+
+        limit.set_max(1);
+        limit.release();
+        assert!(limit.is_exhausted());
+        limit.release();
+        assert!(!limit.is_exhausted());
+        assert_eq!(limit.used(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+//! Reclaim of cached vnodes which are no longer in use
+use crate::{Vnode, VnodeRef};
+use alloc::{
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::cell::{Cell, RefCell};
+use libsys::error::Errno;
+
+/// List of vnodes loaded from real filesystems, ordered by the time they
+/// were last looked up. Vnodes referenced by nothing but their parent
+/// directory can be dropped from the in-memory tree and loaded again
+/// when needed.
+pub struct VnodeLru {
+    stamp: Cell<u64>,
+    list: RefCell<BTreeMap<u64, Weak<Vnode>>>,
+}
+
+impl VnodeLru {
+    /// Constructs an empty list
+    pub const fn new() -> Self {
+        Self {
+            stamp: Cell::new(0),
+            list: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the number of vnodes in the list
+    pub fn len(&self) -> usize {
+        self.list.borrow().len()
+    }
+
+    /// Returns `true` if the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.list.borrow().is_empty()
+    }
+
+    /// Adds `node` to the list or marks it as most recently used
+    pub fn touch(&self, node: &VnodeRef) {
+        let stamp = self.stamp.get() + 1;
+        self.stamp.set(stamp);
+
+        let mut list = self.list.borrow_mut();
+        if let Some(old) = node.lru_stamp.replace(Some(stamp)) {
+            list.remove(&old);
+        }
+        list.insert(stamp, Rc::downgrade(node));
+    }
+
+    /// Removes `node` from the list
+    pub fn remove(&self, node: &Vnode) {
+        if let Some(stamp) = node.lru_stamp.take() {
+            self.list.borrow_mut().remove(&stamp);
+        }
+    }
+
+    /// Drops up to `count` least recently used vnodes which are not in
+    /// use. Their page cache contents are written back and the filesystem
+    /// gets to dispose of the inode state first (see
+    /// [Filesystem::evict](crate::Filesystem::evict)). Returns the number
+    /// of vnodes dropped.
+    pub fn reclaim(&self, count: usize) -> usize {
+        let mut reclaimed = 0;
+        let mut busy = Vec::new();
+
+        while reclaimed < count {
+            let (stamp, node) = {
+                let mut list = self.list.borrow_mut();
+                match list.keys().next().copied() {
+                    Some(stamp) => (stamp, list.remove(&stamp).unwrap()),
+                    None => break,
+                }
+            };
+            let node = match node.upgrade() {
+                Some(node) => node,
+                None => continue,
+            };
+            node.lru_stamp.set(None);
+
+            if !node.is_reclaimable() || Self::evict(&node).is_err() {
+                busy.push((stamp, node));
+                continue;
+            }
+
+            node.detach();
+            reclaimed += 1;
+        }
+
+        // Return the nodes still in use to their places
+        let mut list = self.list.borrow_mut();
+        for (stamp, node) in busy {
+            node.lru_stamp.set(Some(stamp));
+            list.insert(stamp, Rc::downgrade(&node));
+        }
+
+        reclaimed
+    }
+
+    fn evict(node: &VnodeRef) -> Result<(), Errno> {
+        node.sync()?;
+        if let Some(fs) = node.fs() {
+            fs.evict(node)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VnodeImpl, VnodeKind};
+    use alloc::boxed::Box;
+
+    pub struct LoadingInode;
+
+    #[auto_inode(crate = crate)]
+    impl VnodeImpl for LoadingInode {
+        fn lookup(&mut self, _at: VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
+            let node = Vnode::new(name, VnodeKind::Regular, 0);
+            node.set_data(Box::new(LoadingInode));
+            Ok(node)
+        }
+    }
+
+    #[test]
+    fn test_reclaim() {
+        let lru: &'static VnodeLru = Box::leak(Box::new(VnodeLru::new()));
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(LoadingInode));
+        root.set_lru(lru);
+
+        let file0 = root.lookup_or_load("file0").unwrap();
+        let file1 = root.lookup_or_load("file1").unwrap();
+        drop(root.lookup_or_load("file2").unwrap());
+        assert_eq!(lru.len(), 3);
+
+        // file0 becomes the most recently used one
+        assert!(Rc::ptr_eq(&root.lookup_or_load("file0").unwrap(), &file0));
+        let weak0 = Rc::downgrade(&file0);
+        drop(file0);
+
+        // file1 is still referenced and must survive
+        assert_eq!(lru.reclaim(1), 1);
+        assert!(root.lookup("file2").is_none());
+        assert!(weak0.upgrade().is_some());
+
+        assert_eq!(lru.reclaim(8), 1);
+        assert!(weak0.upgrade().is_none());
+        assert!(Rc::ptr_eq(&root.lookup("file1").unwrap(), &file1));
+        assert_eq!(lru.len(), 1);
+
+        // Reclaimed nodes get loaded again
+        let file0 = root.lookup_or_load("file0").unwrap();
+        assert_eq!(file0.name(), "file0");
+        assert_eq!(lru.len(), 2);
+    }
+}
@@ -0,0 +1,310 @@
+//! Scriptable in-memory filesystem for unit tests of vfs internals
+use crate::{BlockDevice, Filesystem, IoCounters, Vnode, VnodeImpl, VnodeKind, VnodeRef};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    collections::BTreeMap,
+    rc::Rc,
+    string::String,
+    vec::Vec,
+};
+use core::any::Any;
+use core::cell::{Cell, Ref, RefCell};
+use core::time::Duration;
+use libsys::{
+    error::Errno,
+    ioctl::IoctlCmd,
+    stat::{DirectoryEntry, FileMode, OpenFlags, Stat},
+};
+
+/// Filesystem operations which can be counted and scripted to fail
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MockOp {
+    Lookup,
+    Create,
+    Remove,
+    Open,
+    Close,
+    Read,
+    Write,
+    Truncate,
+    Readdir,
+    Stat,
+}
+
+struct MockEntry {
+    kind: VnodeKind,
+    data: Vec<u8>,
+}
+
+/// Filesystem keeping its entries in a path-indexed map. Every operation
+/// is counted and can be slowed down or made to fail for a given path.
+pub struct MockFilesystem {
+    root: RefCell<Option<VnodeRef>>,
+    entries: RefCell<BTreeMap<String, MockEntry>>,
+    faults: RefCell<Vec<(MockOp, String, Errno)>>,
+    calls: RefCell<BTreeMap<MockOp, usize>>,
+    delay: Cell<Duration>,
+    counters: IoCounters,
+}
+
+/// Inode of [MockFilesystem], refers to its entry by full path
+pub struct MockVnode {
+    fs: Rc<MockFilesystem>,
+    path: String,
+}
+
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_owned()
+    } else {
+        let mut path = dir.to_owned();
+        path.push('/');
+        path.push_str(name);
+        path
+    }
+}
+
+impl MockFilesystem {
+    /// Constructs a filesystem with an empty root directory
+    pub fn new() -> Rc<Self> {
+        let fs = Rc::new(Self {
+            root: RefCell::new(None),
+            entries: RefCell::new(BTreeMap::new()),
+            faults: RefCell::new(Vec::new()),
+            calls: RefCell::new(BTreeMap::new()),
+            delay: Cell::new(Duration::ZERO),
+            counters: IoCounters::new(),
+        });
+        let root = fs.clone().node("", "", VnodeKind::Directory);
+        *fs.root.borrow_mut() = Some(root);
+        fs
+    }
+
+    /// Adds a directory at `path` (relative to the root, no leading slash)
+    pub fn add_dir(&self, path: &str) {
+        self.add(path, VnodeKind::Directory, &[]);
+    }
+
+    /// Adds a regular file with `data` at `path`
+    pub fn add_file(&self, path: &str, data: &[u8]) {
+        self.add(path, VnodeKind::Regular, data);
+    }
+
+    /// Makes `op` on `path` fail with `err` until [MockFilesystem::clear_faults]
+    pub fn fail(&self, op: MockOp, path: &str, err: Errno) {
+        self.faults.borrow_mut().push((op, path.to_owned(), err));
+    }
+
+    /// Removes all scripted failures
+    pub fn clear_faults(&self) {
+        self.faults.borrow_mut().clear();
+    }
+
+    /// Makes every operation take at least `delay`, like a slow device
+    pub fn set_delay(&self, delay: Duration) {
+        self.delay.set(delay);
+    }
+
+    /// Returns the number of times `op` was performed
+    pub fn calls(&self, op: MockOp) -> usize {
+        self.calls.borrow().get(&op).copied().unwrap_or(0)
+    }
+
+    /// Returns contents of the file at `path`
+    pub fn data(&self, path: &str) -> Option<Vec<u8>> {
+        self.entries.borrow().get(path).map(|e| e.data.clone())
+    }
+
+    fn add(&self, path: &str, kind: VnodeKind, data: &[u8]) {
+        let entry = MockEntry {
+            kind,
+            data: data.to_owned(),
+        };
+        assert!(self.entries.borrow_mut().insert(path.to_owned(), entry).is_none());
+    }
+
+    fn node(self: Rc<Self>, name: &str, path: &str, kind: VnodeKind) -> VnodeRef {
+        let node = Vnode::new(name, kind, Vnode::SEEKABLE);
+        node.props_mut().mode = match kind {
+            VnodeKind::Directory => FileMode::default_dir(),
+            _ => FileMode::default_reg(),
+        };
+        node.set_fs(self.clone());
+        node.set_data(Box::new(MockVnode {
+            fs: self,
+            path: path.to_owned(),
+        }));
+        node
+    }
+
+    fn enter(&self, op: MockOp, path: &str) -> Result<(), Errno> {
+        *self.calls.borrow_mut().entry(op).or_default() += 1;
+        let delay = self.delay.get();
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        match self.faults.borrow().iter().find(|(o, p, _)| *o == op && p == path) {
+            Some(&(_, _, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Filesystem for MockFilesystem {
+    fn root(self: Rc<Self>) -> Result<VnodeRef, Errno> {
+        self.root.borrow().clone().ok_or(Errno::DoesNotExist)
+    }
+
+    fn dev(self: Rc<Self>) -> Option<&'static dyn BlockDevice> {
+        None
+    }
+
+    fn data(&self) -> Option<Ref<dyn Any>> {
+        None
+    }
+
+    fn unmount(&self) {
+        self.root.borrow_mut().take();
+    }
+
+    fn io_counters(&self) -> Option<&IoCounters> {
+        Some(&self.counters)
+    }
+}
+
+impl MockVnode {
+    fn entry<T, F: FnOnce(&mut MockEntry) -> T>(&self, f: F) -> Result<T, Errno> {
+        let mut entries = self.fs.entries.borrow_mut();
+        if self.path.is_empty() {
+            // Root directory has no entry
+            let mut root = MockEntry {
+                kind: VnodeKind::Directory,
+                data: Vec::new(),
+            };
+            return Ok(f(&mut root));
+        }
+        entries.get_mut(&self.path).map(f).ok_or(Errno::DoesNotExist)
+    }
+}
+
+impl VnodeImpl for MockVnode {
+    fn create(&mut self, _at: VnodeRef, name: &str, kind: VnodeKind) -> Result<VnodeRef, Errno> {
+        let path = join(&self.path, name);
+        self.fs.enter(MockOp::Create, &path)?;
+        if self.fs.entries.borrow().contains_key(&path) {
+            return Err(Errno::AlreadyExists);
+        }
+        self.fs.add(&path, kind, &[]);
+        Ok(self.fs.clone().node(name, &path, kind))
+    }
+
+    fn remove(&mut self, _at: VnodeRef, name: &str) -> Result<(), Errno> {
+        let path = join(&self.path, name);
+        self.fs.enter(MockOp::Remove, &path)?;
+        self.fs
+            .entries
+            .borrow_mut()
+            .remove(&path)
+            .map(|_| ())
+            .ok_or(Errno::DoesNotExist)
+    }
+
+    fn lookup(&mut self, _at: VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
+        let path = join(&self.path, name);
+        self.fs.enter(MockOp::Lookup, &path)?;
+        let kind = self
+            .fs
+            .entries
+            .borrow()
+            .get(&path)
+            .map(|e| e.kind)
+            .ok_or(Errno::DoesNotExist)?;
+        Ok(self.fs.clone().node(name, &path, kind))
+    }
+
+    fn open(&mut self, _node: VnodeRef, _opts: OpenFlags) -> Result<usize, Errno> {
+        self.fs.enter(MockOp::Open, &self.path)?;
+        Ok(0)
+    }
+
+    fn close(&mut self, _node: VnodeRef) -> Result<(), Errno> {
+        self.fs.enter(MockOp::Close, &self.path)
+    }
+
+    fn truncate(&mut self, _node: VnodeRef, size: usize) -> Result<(), Errno> {
+        self.fs.enter(MockOp::Truncate, &self.path)?;
+        self.entry(|e| e.data.resize(size, 0))
+    }
+
+    fn read(&mut self, _node: VnodeRef, pos: usize, data: &mut [u8]) -> Result<usize, Errno> {
+        self.fs.enter(MockOp::Read, &self.path)?;
+        self.entry(|e| {
+            let src = e.data.get(pos..).unwrap_or(&[]);
+            let count = src.len().min(data.len());
+            data[..count].copy_from_slice(&src[..count]);
+            count
+        })
+    }
+
+    fn write(&mut self, _node: VnodeRef, pos: usize, data: &[u8]) -> Result<usize, Errno> {
+        self.fs.enter(MockOp::Write, &self.path)?;
+        self.entry(|e| {
+            if e.data.len() < pos + data.len() {
+                e.data.resize(pos + data.len(), 0);
+            }
+            e.data[pos..pos + data.len()].copy_from_slice(data);
+            data.len()
+        })
+    }
+
+    fn readdir(
+        &mut self,
+        _node: VnodeRef,
+        pos: usize,
+        data: &mut [DirectoryEntry],
+    ) -> Result<usize, Errno> {
+        self.fs.enter(MockOp::Readdir, &self.path)?;
+        let prefix = join(&self.path, "");
+        let entries = self.fs.entries.borrow();
+        let names = entries
+            .iter()
+            .filter_map(|(path, entry)| Some((path.strip_prefix(&prefix)?, entry.kind)))
+            .filter(|(name, _)| !name.is_empty() && !name.contains('/'));
+        let mut count = 0;
+        for (slot, (name, kind)) in data.iter_mut().zip(names.skip(pos)) {
+            *slot = DirectoryEntry::new(name, kind.file_type())?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn stat(&mut self, node: VnodeRef) -> Result<Stat, Errno> {
+        self.fs.enter(MockOp::Stat, &self.path)?;
+        let size = self.entry(|e| e.data.len() as u64)?;
+        Ok(Stat {
+            mode: node.props().mode,
+            size,
+            blksize: 512,
+            rdev: 0,
+        })
+    }
+
+    fn size(&mut self, _node: VnodeRef) -> Result<usize, Errno> {
+        self.entry(|e| e.data.len())
+    }
+
+    fn is_ready(&mut self, _node: VnodeRef, _write: bool) -> Result<bool, Errno> {
+        Ok(true)
+    }
+
+    fn ioctl(
+        &mut self,
+        _node: VnodeRef,
+        _cmd: IoctlCmd,
+        _ptr: usize,
+        _len: usize,
+    ) -> Result<usize, Errno> {
+        Err(Errno::InvalidOperation)
+    }
+}
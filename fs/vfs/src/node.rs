@@ -1,11 +1,23 @@
-use crate::{File, FileRef, Filesystem, Ioctx};
-use alloc::{borrow::ToOwned, boxed::Box, rc::Rc, string::String, vec::Vec};
-use core::cell::{Ref, RefCell, RefMut};
+use crate::{limits, File, FileRef, Filesystem, Ioctx, IoCounters, PageCache, Pipe, VnodeLru};
+use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::fmt;
 use libsys::{
     error::Errno,
     ioctl::IoctlCmd,
-    stat::{AccessMode, DirectoryEntry, FileMode, OpenFlags, Stat},
+    proc::Capabilities,
+    stat::{
+        AccessMode, DirectoryEntry, DirectoryEntryPlus, FileAdvice, FileMode, GroupId, MountFlags,
+        OpenFlags, Stat, UserId,
+    },
 };
 
 /// Convenience type alias for [Rc<Vnode>]
@@ -22,23 +34,51 @@ pub enum VnodeKind {
     Char,
     /// Node is a block device
     Block,
+    /// Node is a named pipe
+    Fifo,
+}
+
+impl VnodeKind {
+    /// Returns the file type bits of [FileMode] corresponding to the kind
+    pub const fn file_type(self) -> FileMode {
+        match self {
+            Self::Directory => FileMode::S_IFDIR,
+            Self::Regular => FileMode::S_IFREG,
+            Self::Char => FileMode::S_IFCHR,
+            Self::Block => FileMode::S_IFBLK,
+            Self::Fifo => FileMode::S_IFIFO,
+        }
+    }
 }
 
 pub(crate) struct TreeNode {
-    parent: Option<VnodeRef>,
+    // Weak, so that subtrees are only owned from above: by the parent's
+    // `children`, by a mount point or by whoever holds the tree root
+    parent: Option<Weak<Vnode>>,
     children: Vec<VnodeRef>,
+    // Children grouped by name hash, so that lookups only compare names
+    // of (almost always a single) entry with the same hash. Weak, so that
+    // `children` holds the only reference the tree has to a child.
+    index: BTreeMap<u32, Vec<Weak<Vnode>>>,
 }
 
 /// File property cache struct
 pub struct VnodeProps {
     /// Node permissions and type
     pub mode: FileMode,
+    /// Device number, if the node is a character or block device
+    pub rdev: u32,
+    /// Owner user ID
+    pub uid: UserId,
+    /// Owner group ID
+    pub gid: GroupId,
 }
 
 /// Virtual filesystem node struct, generalizes access to
 /// underlying real filesystems
 pub struct Vnode {
     name: String,
+    name_hash: u32,
     tree: RefCell<TreeNode>,
     props: RefCell<VnodeProps>,
 
@@ -48,6 +88,24 @@ pub struct Vnode {
     target: RefCell<Option<VnodeRef>>,
     fs: RefCell<Option<Rc<dyn Filesystem>>>,
     data: RefCell<Option<Box<dyn VnodeImpl>>>,
+    fifo: RefCell<Option<Rc<Pipe>>>,
+    mount_flags: Cell<Option<MountFlags>>,
+    pub(crate) open_count: Cell<usize>,
+    data_version: Cell<usize>,
+    page_cache: Cell<Option<&'static dyn PageCache>>,
+    lru: Cell<Option<&'static VnodeLru>>,
+    pub(crate) lru_stamp: Cell<Option<u64>>,
+}
+
+/// FNV-1a hash of a path component. Letter case is ignored, so that names
+/// differing only in case land in the same index bucket.
+fn name_hash(name: &str) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    for &byte in name.as_bytes() {
+        hash ^= byte.to_ascii_lowercase() as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
 }
 
 /// Interface for "inode" of a real filesystem
@@ -61,6 +119,12 @@ pub trait VnodeImpl {
     /// Looks up a corresponding directory entry for `name`. If present, loads its inode from
     /// storage medium and returns a new vnode associated with it.
     fn lookup(&mut self, at: VnodeRef, name: &str) -> Result<VnodeRef, Errno>;
+    /// Same as [VnodeImpl::lookup], but ignores letter case when matching
+    /// `name`. Used when case folding is enabled in [crate::ResolveOptions],
+    /// filesystems with case-sensitive names need not implement it.
+    fn lookup_folded(&mut self, _at: VnodeRef, _name: &str) -> Result<VnodeRef, Errno> {
+        Err(Errno::NotImplemented)
+    }
 
     /// Opens a vnode for access. Returns initial file position.
     fn open(&mut self, node: VnodeRef, opts: OpenFlags) -> Result<usize, Errno>;
@@ -83,6 +147,18 @@ pub trait VnodeImpl {
         data: &mut [DirectoryEntry],
     ) -> Result<usize, Errno>;
 
+    /// Reads directory entries along with their file status. Filesystems
+    /// keeping both in the same on-disk structure should implement this,
+    /// otherwise [Vnode::readdir_plus] looks up and stats entries one by one.
+    fn readdir_plus(
+        &mut self,
+        _node: VnodeRef,
+        _pos: usize,
+        _data: &mut [DirectoryEntryPlus],
+    ) -> Result<usize, Errno> {
+        Err(Errno::NotImplemented)
+    }
+
     /// Retrieves file status
     fn stat(&mut self, node: VnodeRef) -> Result<Stat, Errno>;
 
@@ -112,23 +188,39 @@ impl Vnode {
     /// If set, stat() uses only in-memory stat data
     pub const CACHE_STAT: u32 = 1 << 2;
 
+    /// Number of unused vnodes dropped at once when the vnode limit is hit
+    const RECLAIM_BATCH: usize = 64;
+
     /// Constructs a new [Vnode], wrapping it in [Rc]. The resulting node
     /// then needs to have [Vnode::set_data()] called on it to be usable.
     pub fn new(name: &str, kind: VnodeKind, flags: u32) -> VnodeRef {
+        limits::VNODES.acquire();
         Rc::new(Self {
             name: name.to_owned(),
+            name_hash: name_hash(name),
             kind,
             flags,
             props: RefCell::new(VnodeProps {
                 mode: FileMode::empty(),
+                rdev: 0,
+                uid: UserId::root(),
+                gid: GroupId::root(),
             }),
             tree: RefCell::new(TreeNode {
                 parent: None,
                 children: Vec::new(),
+                index: BTreeMap::new(),
             }),
             target: RefCell::new(None),
             fs: RefCell::new(None),
             data: RefCell::new(None),
+            fifo: RefCell::new(None),
+            mount_flags: Cell::new(None),
+            open_count: Cell::new(0),
+            data_version: Cell::new(0),
+            page_cache: Cell::new(None),
+            lru: Cell::new(None),
+            lru_stamp: Cell::new(None),
         })
     }
 
@@ -157,6 +249,27 @@ impl Vnode {
         *self.fs.borrow_mut() = Some(fs);
     }
 
+    /// Attaches a [PageCache] to the [Vnode]. Child vnodes attached later
+    /// inherit it.
+    pub fn set_page_cache(&self, cache: &'static dyn PageCache) {
+        self.page_cache.set(Some(cache));
+    }
+
+    /// Attaches a [VnodeLru] to the [Vnode]. Child vnodes later loaded
+    /// from the filesystem are put on it and can be reclaimed when unused.
+    pub fn set_lru(&self, lru: &'static VnodeLru) {
+        self.lru.set(Some(lru));
+    }
+
+    /// Returns the [PageCache] used for data I/O, if any
+    pub fn page_cache(&self) -> Option<&'static dyn PageCache> {
+        if self.kind == VnodeKind::Regular {
+            self.page_cache.get()
+        } else {
+            None
+        }
+    }
+
     /// Returns a reference to the associated [VnodeImpl]
     pub fn data(&self) -> RefMut<Option<Box<dyn VnodeImpl>>> {
         self.data.borrow_mut()
@@ -183,6 +296,17 @@ impl Vnode {
         self.kind
     }
 
+    /// Returns `true` if there are files referring to the vnode
+    #[inline(always)]
+    pub fn is_open(&self) -> bool {
+        self.open_count.get() != 0
+    }
+
+    /// Returns `true` if the directory has child nodes in in-memory tree
+    pub fn has_children(&self) -> bool {
+        !self.tree.borrow().children.is_empty()
+    }
+
     /// Returns flags of the vnode
     #[inline(always)]
     pub const fn flags(&self) -> u32 {
@@ -195,20 +319,38 @@ impl Vnode {
     /// actually perform any real filesystem operations. Used to build
     /// hierarchies for in-memory or volatile filesystems.
     pub fn attach(self: &VnodeRef, child: VnodeRef) {
-        let parent_clone = self.clone();
         let mut parent_borrow = self.tree.borrow_mut();
         assert!(child
             .tree
             .borrow_mut()
             .parent
-            .replace(parent_clone)
+            .replace(Rc::downgrade(self))
             .is_none());
+        if child.page_cache.get().is_none() {
+            child.page_cache.set(self.page_cache.get());
+        }
+        if child.lru.get().is_none() {
+            child.lru.set(self.lru.get());
+        }
+        parent_borrow
+            .index
+            .entry(child.name_hash)
+            .or_default()
+            .push(Rc::downgrade(&child));
         parent_borrow.children.push(child);
     }
 
-    fn detach(self: &VnodeRef) {
+    pub(crate) fn detach(self: &VnodeRef) {
+        if let Some(lru) = self.lru.get() {
+            lru.remove(self);
+        }
+
         let mut self_borrow = self.tree.borrow_mut();
-        let parent = self_borrow.parent.take().unwrap();
+        let parent = match self_borrow.parent.take().unwrap().upgrade() {
+            Some(parent) => parent,
+            // Parent is gone along with its child list
+            None => return,
+        };
         let mut parent_borrow = parent.tree.borrow_mut();
         let index = parent_borrow
             .children
@@ -216,33 +358,140 @@ impl Vnode {
             .position(|it| Rc::ptr_eq(it, self))
             .unwrap();
         parent_borrow.children.remove(index);
+
+        let bucket = parent_borrow.index.get_mut(&self.name_hash).unwrap();
+        bucket.retain(|it| !core::ptr::eq(it.as_ptr(), Rc::as_ptr(self)));
+        if bucket.is_empty() {
+            parent_borrow.index.remove(&self.name_hash);
+        }
     }
 
-    /// Attaches some filesystem's root directory node at another directory
-    pub fn mount(self: &VnodeRef, root: VnodeRef) -> Result<(), Errno> {
+    /// Returns `true` if the vnode is only referenced by its parent
+    /// (and the caller) and can be dropped from the tree
+    pub(crate) fn is_reclaimable(self: &VnodeRef) -> bool {
+        Rc::strong_count(self) == 2
+            && !self.is_open()
+            && !self.has_children()
+            && self.target.borrow().is_none()
+            && self.parent_ref().is_some()
+    }
+
+    /// Checks if `root` can be mounted at this vnode
+    pub(crate) fn mount_check(&self, root: &VnodeRef) -> Result<(), Errno> {
         if !self.is_directory() {
             return Err(Errno::NotADirectory);
         }
         if !root.is_directory() {
             return Err(Errno::NotADirectory);
         }
-        if self.target.borrow().is_some() {
+        if root.parent_ref().is_some() {
             return Err(Errno::Busy);
         }
+        Ok(())
+    }
 
-        let mut child_borrow = root.tree.borrow_mut();
-        if child_borrow.parent.is_some() {
+    pub(crate) fn set_mount_parent(&self, parent: &VnodeRef) {
+        self.tree.borrow_mut().parent = Some(Rc::downgrade(parent));
+    }
+
+    /// Attaches some filesystem's root directory node at another directory
+    pub fn mount(self: &VnodeRef, root: VnodeRef) -> Result<(), Errno> {
+        if self.target.borrow().is_some() {
             return Err(Errno::Busy);
         }
-        child_borrow.parent = Some(self.clone());
-        *self.target.borrow_mut() = Some(root.clone());
+        self.mount_check(&root)?;
+
+        root.set_mount_parent(self);
+        *self.target.borrow_mut() = Some(root);
 
         Ok(())
     }
 
+    /// Detaches the filesystem mounted at this directory. If the mounted
+    /// root belongs to a [Filesystem], its cached vnode tree is written
+    /// back and released, so that the filesystem itself can be dropped.
+    pub fn unmount(self: &VnodeRef) -> Result<(), Errno> {
+        let root = self.target().ok_or(Errno::InvalidArgument)?;
+        root.unmount_check(true)?;
+        *self.target.borrow_mut() = None;
+        root.release_mount();
+        Ok(())
+    }
+
+    /// Checks that no vnode of the mounted tree is in use and writes back
+    /// their cached data
+    pub(crate) fn unmount_check(self: &VnodeRef, is_root: bool) -> Result<(), Errno> {
+        // Besides the tree itself, only the caller may refer to the vnode.
+        // The root is also referred to by the mount and possibly its
+        // filesystem, so just opened files are checked for it.
+        if self.is_open()
+            || self.target.borrow().is_some()
+            || (!is_root && Rc::strong_count(self) > 1)
+        {
+            return Err(Errno::Busy);
+        }
+        for child in self.tree.borrow().children.iter() {
+            child.unmount_check(false)?;
+        }
+        self.sync()
+    }
+
+    /// Releases a root vnode removed from its mount point
+    pub(crate) fn release_mount(self: &VnodeRef) {
+        self.tree.borrow_mut().parent = None;
+        // In-memory trees without a Filesystem are owned by their creator
+        if let Some(fs) = self.fs() {
+            self.release_tree();
+            fs.unmount();
+        }
+    }
+
+    // Breaks all references the subtree has to its children, inodes and
+    // the filesystem
+    fn release_tree(&self) {
+        let children = {
+            let mut tree = self.tree.borrow_mut();
+            tree.index.clear();
+            tree.parent = None;
+            core::mem::take(&mut tree.children)
+        };
+        for child in children {
+            if let Some(lru) = child.lru.get() {
+                lru.remove(&child);
+            }
+            child.release_tree();
+        }
+        self.data.borrow_mut().take();
+        self.fifo.borrow_mut().take();
+        self.fs.borrow_mut().take();
+    }
+
+    pub(crate) fn set_mount_flags(&self, flags: MountFlags) {
+        self.mount_flags.set(Some(flags));
+    }
+
+    /// Returns flags of the mount this vnode belongs to
+    pub fn mount_flags(self: &VnodeRef) -> MountFlags {
+        let mut node = self.clone();
+        loop {
+            if let Some(flags) = node.mount_flags.get() {
+                return flags;
+            }
+            let parent = node.parent();
+            if Rc::ptr_eq(&parent, &node) {
+                return MountFlags::empty();
+            }
+            node = parent;
+        }
+    }
+
     /// Returns this vnode's parent or itself if it has none
     pub fn parent(self: &VnodeRef) -> VnodeRef {
-        self.tree.borrow().parent.as_ref().unwrap_or(self).clone()
+        self.parent_ref().unwrap_or_else(|| self.clone())
+    }
+
+    fn parent_ref(&self) -> Option<VnodeRef> {
+        self.tree.borrow().parent.as_ref().and_then(Weak::upgrade)
     }
 
     /// Returns this vnode's mount target (for directories)
@@ -250,18 +499,30 @@ impl Vnode {
         self.target.borrow().clone()
     }
 
-    /// Looks up a child `name` in in-memory tree cache
+    /// Looks up a child `name` in in-memory tree cache. On filesystems
+    /// which are [case-insensitive](Filesystem::case_insensitive), letter
+    /// case of `name` is ignored.
     pub fn lookup(self: &VnodeRef, name: &str) -> Option<VnodeRef> {
         assert!(self.is_directory());
+        let fold = self.fs().map_or(false, |fs| fs.case_insensitive());
         self.tree
             .borrow()
-            .children
+            .index
+            .get(&name_hash(name))?
             .iter()
-            .find(|e| e.name == name)
-            .cloned()
+            .filter_map(Weak::upgrade)
+            .find(|e| {
+                if fold {
+                    e.name.eq_ignore_ascii_case(name)
+                } else {
+                    e.name == name
+                }
+            })
     }
 
-    pub(crate) fn for_each_entry<F: FnMut(usize, &VnodeRef)>(
+    /// Calls `f` for at most `limit` in-memory children of the node,
+    /// starting at `offset`. Returns the count of entries visited.
+    pub fn for_each_entry<F: FnMut(usize, &VnodeRef)>(
         &self,
         offset: usize,
         limit: usize,
@@ -288,19 +549,78 @@ impl Vnode {
     /// vnode and will load it from disk if it's missing.
     pub fn lookup_or_load(self: &VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
         if let Some(node) = self.lookup(name) {
-            Ok(node)
-        } else if let Some(ref mut data) = *self.data() {
+            if node.lru_stamp.get().is_some() {
+                if let Some(lru) = node.lru.get() {
+                    lru.touch(&node);
+                }
+            }
+            return Ok(node);
+        }
+
+        self.reserve_vnode()?;
+        if let Some(ref mut data) = *self.data() {
             let vnode = data.lookup(self.clone(), name)?;
+            // Found through an alias (such as a FAT short name) of a node
+            // cached under its real name
+            if vnode.name != name {
+                if let Some(node) = self.lookup(&vnode.name) {
+                    return Ok(node);
+                }
+            }
             if let Some(fs) = self.fs() {
                 vnode.set_fs(fs);
             }
             self.attach(vnode.clone());
+            if let Some(lru) = vnode.lru.get() {
+                lru.touch(&vnode);
+            }
             Ok(vnode)
         } else {
             Err(Errno::DoesNotExist)
         }
     }
 
+    /// Same as [Vnode::lookup_or_load], but falls back to matching `name`
+    /// regardless of letter case if there's no exact match
+    pub fn lookup_or_load_folded(self: &VnodeRef, name: &str) -> Result<VnodeRef, Errno> {
+        match self.lookup_or_load(name) {
+            Err(Errno::DoesNotExist) => (),
+            res => return res,
+        }
+
+        let cached = self
+            .tree
+            .borrow()
+            .children
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .cloned();
+        if let Some(node) = cached {
+            return Ok(node);
+        }
+
+        self.reserve_vnode()?;
+        let vnode = match *self.data() {
+            Some(ref mut data) => match data.lookup_folded(self.clone(), name) {
+                Err(Errno::NotImplemented) => Err(Errno::DoesNotExist),
+                res => res,
+            },
+            None => Err(Errno::DoesNotExist),
+        }?;
+        // Loaded under its real name, which may be cached already
+        if let Some(node) = self.lookup(&vnode.name) {
+            return Ok(node);
+        }
+        if let Some(fs) = self.fs() {
+            vnode.set_fs(fs);
+        }
+        self.attach(vnode.clone());
+        if let Some(lru) = vnode.lru.get() {
+            lru.touch(&vnode);
+        }
+        Ok(vnode)
+    }
+
     /// Creates a new node `name` in `self`
     pub fn create(
         self: &VnodeRef,
@@ -320,6 +640,7 @@ impl Vnode {
             Ok(_) => return Err(Errno::AlreadyExists),
             e => return e,
         };
+        self.reserve_vnode()?;
 
         if let Some(ref mut data) = *self.data() {
             let vnode = data.create(self.clone(), name, kind)?;
@@ -334,6 +655,48 @@ impl Vnode {
         }
     }
 
+    /// Creates a new node `name` in `self` on behalf of the process with
+    /// credentials of `ioctx`. The node is owned by the effective user ID
+    /// and, unless the directory is set-group-ID, by the effective group
+    /// ID. In a set-group-ID directory the node inherits its group and
+    /// new subdirectories inherit the bit itself.
+    ///
+    /// SETGID is dropped from non-directories if their group is not the
+    /// creator's one and the creator lacks FOWNER capability.
+    pub fn create_as(
+        self: &VnodeRef,
+        ioctx: &Ioctx,
+        name: &str,
+        mut mode: FileMode,
+        kind: VnodeKind,
+    ) -> Result<VnodeRef, Errno> {
+        let gid = {
+            let props = self.props.borrow();
+            if props.mode.contains(FileMode::SETGID) {
+                if kind == VnodeKind::Directory {
+                    mode |= FileMode::SETGID;
+                }
+                props.gid
+            } else {
+                ioctx.egid
+            }
+        };
+        if kind != VnodeKind::Directory
+            && gid != ioctx.egid
+            && !ioctx.caps.contains(Capabilities::FOWNER)
+        {
+            mode.remove(FileMode::SETGID);
+        }
+
+        let vnode = self.create(name, mode, kind)?;
+        {
+            let mut props = vnode.props.borrow_mut();
+            props.uid = ioctx.euid;
+            props.gid = gid;
+        }
+        Ok(vnode)
+    }
+
     /// Removes a directory entry `name` from `self`
     pub fn unlink(self: &VnodeRef, name: &str) -> Result<(), Errno> {
         if self.kind != VnodeKind::Directory {
@@ -343,8 +706,8 @@ impl Vnode {
             return Err(Errno::InvalidArgument);
         }
 
+        let vnode = self.lookup_or_load(name)?;
         if let Some(ref mut data) = *self.data() {
-            let vnode = self.lookup(name).ok_or(Errno::DoesNotExist)?;
             data.remove(self.clone(), name)?;
             vnode.detach();
             Ok(())
@@ -378,11 +741,25 @@ impl Vnode {
             }
         }
 
-        if flags.contains(OpenFlags::O_CLOEXEC) {
-            open_flags |= File::CLOEXEC;
+        if flags.contains(OpenFlags::O_APPEND) {
+            open_flags |= File::APPEND;
+        }
+        if flags.contains(OpenFlags::O_NONBLOCK) {
+            open_flags |= File::NONBLOCK;
         }
 
-        if self.kind == VnodeKind::Directory && self.flags & Vnode::CACHE_READDIR != 0 {
+        if limits::FILES.is_exhausted() {
+            return Err(Errno::TooManyDescriptors);
+        }
+
+        if self.kind == VnodeKind::Fifo {
+            let mut fifo = self.fifo.borrow_mut();
+            let pipe = match fifo.as_ref() {
+                Some(pipe) => pipe.clone(),
+                None => fifo.insert(Pipe::new()?).clone(),
+            };
+            Ok(File::fifo(self.clone(), pipe, open_flags))
+        } else if self.kind == VnodeKind::Directory && self.flags & Vnode::CACHE_READDIR != 0 {
             Ok(File::normal(self.clone(), File::POS_CACHE_DOT, open_flags))
         } else if let Some(ref mut data) = *self.data() {
             let pos = data.open(self.clone(), flags)?;
@@ -405,6 +782,74 @@ impl Vnode {
 
     /// Reads data from offset `pos` into `buf`
     pub fn read(self: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        let count = if let Some(cache) = self.page_cache() {
+            cache.read(self, pos, buf)
+        } else {
+            self.read_direct(pos, buf)
+        }?;
+        self.account(|counters| counters.account_read(count));
+        Ok(count)
+    }
+
+    /// Writes data from `buf` to offset `pos`
+    pub fn write(self: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno> {
+        let count = if let Some(cache) = self.page_cache() {
+            cache.write(self, pos, buf)
+        } else {
+            self.write_direct(pos, buf)
+        }?;
+        self.account(|counters| counters.account_write(count));
+        Ok(count)
+    }
+
+    fn account<F: FnOnce(&IoCounters)>(&self, f: F) {
+        if let Some(fs) = self.fs() {
+            if let Some(counters) = fs.io_counters() {
+                f(counters);
+            }
+        }
+    }
+
+    /// Resizes the vnode data
+    pub fn truncate(self: &VnodeRef, size: usize) -> Result<(), Errno> {
+        if let Some(cache) = self.page_cache() {
+            cache.truncate(self, size)
+        } else {
+            self.truncate_direct(size)
+        }
+    }
+
+    /// Writes modified cached data of the vnode back to storage and
+    /// flushes the storage device of its filesystem
+    pub fn sync(self: &VnodeRef) -> Result<(), Errno> {
+        if let Some(cache) = self.page_cache() {
+            cache.sync(self)?;
+        }
+        match self.fs().and_then(|fs| fs.dev()) {
+            Some(dev) => dev.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Passes file access pattern `advice` for range `pos..pos + len` to
+    /// the page cache. Uncached vnodes ignore the advice.
+    pub fn advise(
+        self: &VnodeRef,
+        pos: usize,
+        len: usize,
+        advice: FileAdvice,
+    ) -> Result<(), Errno> {
+        if self.kind != VnodeKind::Regular {
+            Err(Errno::InvalidArgument)
+        } else if let Some(cache) = self.page_cache() {
+            cache.advise(self, pos, len, advice)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads data from offset `pos` into `buf`, bypassing the page cache
+    pub fn read_direct(self: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Errno> {
         if self.kind == VnodeKind::Directory {
             Err(Errno::IsADirectory)
         } else if let Some(ref mut data) = *self.data() {
@@ -414,28 +859,38 @@ impl Vnode {
         }
     }
 
-    /// Writes data from `buf` to offset `pos`
-    pub fn write(self: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno> {
+    /// Writes data from `buf` to offset `pos`, bypassing the page cache
+    pub fn write_direct(self: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno> {
         if self.kind == VnodeKind::Directory {
             Err(Errno::IsADirectory)
         } else if let Some(ref mut data) = *self.data() {
-            data.write(self.clone(), pos, buf)
+            let res = data.write(self.clone(), pos, buf);
+            self.data_version.set(self.data_version.get() + 1);
+            res
         } else {
             Err(Errno::NotImplemented)
         }
     }
 
-    /// Resizes the vnode data
-    pub fn truncate(self: &VnodeRef, size: usize) -> Result<(), Errno> {
+    /// Resizes the vnode data, bypassing the page cache
+    pub fn truncate_direct(self: &VnodeRef, size: usize) -> Result<(), Errno> {
         if self.kind != VnodeKind::Regular {
             Err(Errno::IsADirectory)
         } else if let Some(ref mut data) = *self.data() {
-            data.truncate(self.clone(), size)
+            let res = data.truncate(self.clone(), size);
+            self.data_version.set(self.data_version.get() + 1);
+            res
         } else {
             Err(Errno::NotImplemented)
         }
     }
 
+    /// Returns a counter which changes every time the vnode data is
+    /// modified through [Vnode::write_direct] or [Vnode::truncate_direct]
+    pub fn data_version(&self) -> usize {
+        self.data_version.get()
+    }
+
     /// Returns current vnode data size
     pub fn size(self: &VnodeRef) -> Result<usize, Errno> {
         if let Some(ref mut data) = *self.data() {
@@ -445,6 +900,51 @@ impl Vnode {
         }
     }
 
+    /// Reads directory entries starting at `pos`
+    pub fn readdir(
+        self: &VnodeRef,
+        pos: usize,
+        entries: &mut [DirectoryEntry],
+    ) -> Result<usize, Errno> {
+        if let Some(ref mut data) = *self.data() {
+            data.readdir(self.clone(), pos, entries)
+        } else {
+            Err(Errno::NotImplemented)
+        }
+    }
+
+    /// Reads directory entries starting at `pos` along with their file status
+    pub fn readdir_plus(
+        self: &VnodeRef,
+        pos: usize,
+        entries: &mut [DirectoryEntryPlus],
+    ) -> Result<usize, Errno> {
+        let res = if let Some(ref mut data) = *self.data() {
+            data.readdir_plus(self.clone(), pos, entries)
+        } else {
+            Err(Errno::NotImplemented)
+        };
+        if res != Err(Errno::NotImplemented) {
+            return res;
+        }
+
+        let mut names = vec![DirectoryEntry::empty(); entries.len()];
+        let count = self.readdir(pos, &mut names)?;
+        for (name, entry) in names.iter().zip(entries.iter_mut()).take(count) {
+            *entry = DirectoryEntryPlus::new(*name, self.entry_stat(name.as_str()).ok());
+        }
+        Ok(count)
+    }
+
+    /// Reports file status of directory entry `name`
+    pub fn entry_stat(self: &VnodeRef, name: &str) -> Result<Stat, Errno> {
+        match name {
+            "." => self.stat(),
+            ".." => self.parent().stat(),
+            _ => self.lookup_or_load(name)?.stat(),
+        }
+    }
+
     /// Reports file status
     pub fn stat(self: &VnodeRef) -> Result<Stat, Errno> {
         if self.flags & Self::CACHE_STAT != 0 {
@@ -453,6 +953,7 @@ impl Vnode {
                 blksize: 0,
                 size: 0,
                 mode: props.mode,
+                rdev: props.rdev,
             })
         } else if let Some(ref mut data) = *self.data() {
             data.stat(self.clone())
@@ -479,37 +980,111 @@ impl Vnode {
         }
     }
 
-    /// Checks if given [Ioctx] has `access` permissions to the vnode
-    pub fn check_access(&self, _ioctx: &Ioctx, access: AccessMode) -> Result<(), Errno> {
-        let props = self.props.borrow();
-        let mode = props.mode;
+    // Makes room for a vnode about to be loaded or created, dropping
+    // unused cached ones if the limit is reached
+    fn reserve_vnode(&self) -> Result<(), Errno> {
+        if limits::VNODES.is_exhausted() {
+            if let Some(lru) = self.lru.get() {
+                lru.reclaim(Self::RECLAIM_BATCH);
+            }
+            if limits::VNODES.is_exhausted() {
+                return Err(Errno::OutOfMemory);
+            }
+        }
+        Ok(())
+    }
 
+    /// Checks if the process with effective credentials of `ioctx` is
+    /// allowed to perform `access` on the node
+    pub fn check_access(self: &VnodeRef, ioctx: &Ioctx, access: AccessMode) -> Result<(), Errno> {
+        let dac_override = ioctx.caps.contains(Capabilities::DAC_OVERRIDE);
+        self.check_access_as(ioctx.euid, ioctx.egid, access, dac_override)
+    }
+
+    /// Checks if a process with `uid` and `gid` credentials is allowed to
+    /// perform `access` on the node. `dac_override` bypasses read/write
+    /// permission bits and grants execute access if any execute bit is set.
+    pub fn check_access_as(
+        self: &VnodeRef,
+        uid: UserId,
+        gid: GroupId,
+        access: AccessMode,
+        dac_override: bool,
+    ) -> Result<(), Errno> {
         if access.contains(AccessMode::F_OK) {
             if access.intersects(AccessMode::R_OK | AccessMode::W_OK | AccessMode::X_OK) {
                 return Err(Errno::InvalidArgument);
             }
-        } else {
-            if access.contains(AccessMode::F_OK) {
-                return Err(Errno::InvalidArgument);
-            }
+            return Ok(());
+        }
 
-            // Check user
-            if access.contains(AccessMode::R_OK) && !mode.contains(FileMode::USER_READ) {
-                return Err(Errno::PermissionDenied);
-            }
-            if access.contains(AccessMode::W_OK) && !mode.contains(FileMode::USER_WRITE) {
-                return Err(Errno::PermissionDenied);
-            }
-            if access.contains(AccessMode::X_OK) && !mode.contains(FileMode::USER_EXEC) {
+        let mount_flags = self.mount_flags();
+        if access.contains(AccessMode::W_OK) && mount_flags.contains(MountFlags::MS_RDONLY) {
+            return Err(Errno::ReadOnly);
+        }
+        if access.contains(AccessMode::X_OK)
+            && !self.is_directory()
+            && mount_flags.contains(MountFlags::MS_NOEXEC)
+        {
+            return Err(Errno::PermissionDenied);
+        }
+
+        let props = self.props.borrow();
+        let mode = props.mode;
+
+        if dac_override {
+            if access.contains(AccessMode::X_OK)
+                && !self.is_directory()
+                && !mode.intersects(FileMode::USER_EXEC | FileMode::GROUP_EXEC | FileMode::OTHER_EXEC)
+            {
                 return Err(Errno::PermissionDenied);
             }
+            return Ok(());
+        }
 
-            // TODO check group
-            // TODO check other
+        let (r, w, x) = if props.uid == uid {
+            (FileMode::USER_READ, FileMode::USER_WRITE, FileMode::USER_EXEC)
+        } else if props.gid == gid {
+            (FileMode::GROUP_READ, FileMode::GROUP_WRITE, FileMode::GROUP_EXEC)
+        } else {
+            (FileMode::OTHER_READ, FileMode::OTHER_WRITE, FileMode::OTHER_EXEC)
+        };
+
+        if (access.contains(AccessMode::R_OK) && !mode.contains(r))
+            || (access.contains(AccessMode::W_OK) && !mode.contains(w))
+            || (access.contains(AccessMode::X_OK) && !mode.contains(x))
+        {
+            return Err(Errno::PermissionDenied);
         }
 
         Ok(())
     }
+
+    /// Checks if the process with credentials of `ioctx` may remove `node`
+    /// from the directory. Entries of sticky directories may only be
+    /// removed by their owner, the directory owner or a process with
+    /// FOWNER capability.
+    pub fn check_sticky(self: &VnodeRef, node: &VnodeRef, ioctx: &Ioctx) -> Result<(), Errno> {
+        let uid = ioctx.euid;
+        let props = self.props.borrow();
+        if !props.mode.contains(FileMode::STICKY)
+            || ioctx.caps.contains(Capabilities::FOWNER)
+            || props.uid == uid
+        {
+            return Ok(());
+        }
+        if node.props.borrow().uid == uid {
+            Ok(())
+        } else {
+            Err(Errno::PermissionDenied)
+        }
+    }
+}
+
+impl Drop for Vnode {
+    fn drop(&mut self) {
+        limits::VNODES.release();
+    }
 }
 
 impl fmt::Debug for Vnode {
@@ -525,7 +1100,7 @@ mod tests {
     use libsys::{ioctl::IoctlCmd, stat::OpenFlags, stat::Stat};
     pub struct DummyInode;
 
-    #[auto_inode]
+    #[auto_inode(crate = crate)]
     impl VnodeImpl for DummyInode {
         fn create(
             &mut self,
@@ -547,6 +1122,60 @@ mod tests {
         }
     }
 
+    struct ZeroCache;
+
+    impl PageCache for ZeroCache {
+        fn read(&self, _node: &VnodeRef, _pos: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+            buf.fill(0);
+            Ok(buf.len())
+        }
+
+        fn write(&self, _node: &VnodeRef, _pos: usize, _buf: &[u8]) -> Result<usize, Errno> {
+            Err(Errno::ReadOnly)
+        }
+
+        fn truncate(&self, _node: &VnodeRef, _size: usize) -> Result<(), Errno> {
+            Err(Errno::ReadOnly)
+        }
+
+        fn sync(&self, _node: &VnodeRef) -> Result<(), Errno> {
+            Ok(())
+        }
+
+        fn advise(
+            &self,
+            _node: &VnodeRef,
+            _pos: usize,
+            _len: usize,
+            _advice: FileAdvice,
+        ) -> Result<(), Errno> {
+            Ok(())
+        }
+    }
+
+    static ZERO_CACHE: ZeroCache = ZeroCache;
+
+    #[test]
+    fn test_page_cache() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(DummyInode {}));
+        root.set_page_cache(&ZERO_CACHE);
+
+        let dir = Vnode::new("dir0", VnodeKind::Directory, 0);
+        let file = Vnode::new("file0", VnodeKind::Regular, 0);
+        file.set_data(Box::new(DummyInode {}));
+        root.attach(dir.clone());
+        dir.attach(file.clone());
+
+        // Directories are never cached, but pass the cache on to children
+        assert!(dir.page_cache().is_none());
+        let mut buf = [1u8; 16];
+        assert_eq!(file.read(0, &mut buf), Ok(16));
+        assert_eq!(buf, [0u8; 16]);
+        assert_eq!(file.write(0, &buf), Err(Errno::ReadOnly));
+        assert!(Vnode::new("file1", VnodeKind::Regular, 0).page_cache().is_none());
+    }
+
     #[test]
     fn test_parent() {
         let root = Vnode::new("", VnodeKind::Directory, 0);
@@ -596,11 +1225,11 @@ mod tests {
         assert!(Rc::ptr_eq(&dir1, &root.lookup("dir1").unwrap()));
         assert!(Rc::ptr_eq(
             &root,
-            dir0.tree.borrow().parent.as_ref().unwrap()
+            &dir0.tree.borrow().parent.as_ref().unwrap().upgrade().unwrap()
         ));
         assert!(Rc::ptr_eq(
             &root,
-            dir1.tree.borrow().parent.as_ref().unwrap()
+            &dir1.tree.borrow().parent.as_ref().unwrap().upgrade().unwrap()
         ));
         assert!(root.lookup("dir2").is_none());
 
@@ -609,10 +1238,274 @@ mod tests {
         assert!(Rc::ptr_eq(&dir1, &root.lookup("dir1").unwrap()));
         assert!(Rc::ptr_eq(
             &root,
-            dir1.tree.borrow().parent.as_ref().unwrap()
+            &dir1.tree.borrow().parent.as_ref().unwrap().upgrade().unwrap()
         ));
         assert!(dir0.tree.borrow().parent.is_none());
         assert!(root.lookup("dir0").is_none());
         assert!(root.lookup("dir2").is_none());
     }
+
+    #[test]
+    fn test_check_access() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        let file = Vnode::new("file0", VnodeKind::Regular, 0);
+        root.attach(file.clone());
+        {
+            let mut props = file.props_mut();
+            props.mode = FileMode::from_bits(0o750).unwrap() | FileMode::S_IFREG;
+            props.uid = UserId::from(1000);
+            props.gid = GroupId::from(100);
+        }
+        let (owner, group, other) = (UserId::from(1000), GroupId::from(100), UserId::from(1001));
+
+        assert!(file.check_access_as(owner, group, AccessMode::W_OK | AccessMode::X_OK, false).is_ok());
+        assert!(file.check_access_as(other, group, AccessMode::X_OK, false).is_ok());
+        assert_eq!(
+            file.check_access_as(other, group, AccessMode::W_OK, false),
+            Err(Errno::PermissionDenied)
+        );
+        assert_eq!(
+            file.check_access_as(other, GroupId::from(101), AccessMode::R_OK, false),
+            Err(Errno::PermissionDenied)
+        );
+        assert!(file.check_access_as(other, GroupId::from(101), AccessMode::F_OK, false).is_ok());
+        assert!(file.check_access_as(other, GroupId::from(101), AccessMode::R_OK, true).is_ok());
+
+        // check_access() takes the override from the context capabilities
+        let ioctx = Ioctx::new(root.clone(), other, GroupId::from(101));
+        assert_eq!(file.check_access(&ioctx, AccessMode::R_OK), Err(Errno::PermissionDenied));
+        let ioctx = Ioctx::builder(root.clone())
+            .ids(other, GroupId::from(101))
+            .caps(Capabilities::DAC_OVERRIDE)
+            .build();
+        assert!(file.check_access(&ioctx, AccessMode::R_OK).is_ok());
+
+        root.set_mount_flags(MountFlags::MS_NOEXEC | MountFlags::MS_RDONLY);
+        assert_eq!(
+            file.check_access_as(owner, group, AccessMode::X_OK, false),
+            Err(Errno::PermissionDenied)
+        );
+        assert_eq!(
+            file.check_access_as(owner, group, AccessMode::W_OK, true),
+            Err(Errno::ReadOnly)
+        );
+    }
+
+    #[test]
+    fn test_check_sticky() {
+        let dir = Vnode::new("tmp", VnodeKind::Directory, 0);
+        let file = Vnode::new("file0", VnodeKind::Regular, 0);
+        dir.attach(file.clone());
+        let (owner, other) = (UserId::from(1000), UserId::from(1001));
+        let ctx = |uid| Ioctx::new(dir.clone(), uid, GroupId::from(100));
+        file.props_mut().uid = owner;
+        dir.props_mut().uid = UserId::from(1);
+        dir.props_mut().mode = FileMode::from_bits(0o777).unwrap() | FileMode::S_IFDIR;
+
+        assert!(dir.check_sticky(&file, &ctx(other)).is_ok());
+
+        dir.props_mut().mode |= FileMode::STICKY;
+        assert_eq!(dir.check_sticky(&file, &ctx(other)), Err(Errno::PermissionDenied));
+        assert!(dir.check_sticky(&file, &ctx(owner)).is_ok());
+        // Being root is not enough, the capability is
+        assert_eq!(
+            dir.check_sticky(&file, &ctx(UserId::root())),
+            Err(Errno::PermissionDenied)
+        );
+        let fowner = Ioctx::builder(dir.clone())
+            .ids(other, GroupId::from(100))
+            .caps(Capabilities::FOWNER)
+            .build();
+        assert!(dir.check_sticky(&file, &fowner).is_ok());
+
+        dir.props_mut().uid = other;
+        assert!(dir.check_sticky(&file, &ctx(other)).is_ok());
+    }
+
+    struct TreeFs {
+        root: RefCell<Option<VnodeRef>>,
+        fold: bool,
+    }
+
+    // Refers back to its filesystem like inodes of real filesystems do
+    struct TreeInode {
+        _fs: Rc<TreeFs>,
+    }
+
+    #[auto_inode(crate = crate)]
+    impl VnodeImpl for TreeInode {
+        fn create(
+            &mut self,
+            _at: VnodeRef,
+            name: &str,
+            kind: VnodeKind,
+        ) -> Result<VnodeRef, Errno> {
+            let node = Vnode::new(name, kind, 0);
+            node.set_data(Box::new(TreeInode {
+                _fs: self._fs.clone(),
+            }));
+            Ok(node)
+        }
+
+        fn lookup(&mut self, _at: VnodeRef, _name: &str) -> Result<VnodeRef, Errno> {
+            Err(Errno::DoesNotExist)
+        }
+    }
+
+    impl Filesystem for TreeFs {
+        fn root(self: Rc<Self>) -> Result<VnodeRef, Errno> {
+            self.root.borrow().clone().ok_or(Errno::DoesNotExist)
+        }
+
+        fn dev(self: Rc<Self>) -> Option<&'static dyn crate::BlockDevice> {
+            None
+        }
+
+        fn data(&self) -> Option<Ref<dyn core::any::Any>> {
+            None
+        }
+
+        fn unmount(&self) {
+            self.root.borrow_mut().take();
+        }
+
+        fn case_insensitive(&self) -> bool {
+            self.fold
+        }
+    }
+
+    fn tree_fs() -> (Weak<TreeFs>, VnodeRef) {
+        tree_fs_with(false)
+    }
+
+    fn tree_fs_with(fold: bool) -> (Weak<TreeFs>, VnodeRef) {
+        let fs = Rc::new(TreeFs {
+            root: RefCell::new(None),
+            fold,
+        });
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        root.set_data(Box::new(TreeInode { _fs: fs.clone() }));
+        root.set_fs(fs.clone());
+        *fs.root.borrow_mut() = Some(root.clone());
+
+        let dir = root
+            .create("dir0", FileMode::default_dir(), VnodeKind::Directory)
+            .unwrap();
+        dir.create("file0", FileMode::default_reg(), VnodeKind::Regular)
+            .unwrap();
+
+        (Rc::downgrade(&fs), fs.root().unwrap())
+    }
+
+    #[test]
+    fn test_tree_no_cycles() {
+        let root = Vnode::new("", VnodeKind::Directory, 0);
+        let dir = Vnode::new("dir0", VnodeKind::Directory, 0);
+        let file = Vnode::new("file0", VnodeKind::Regular, 0);
+        root.attach(dir.clone());
+        dir.attach(file.clone());
+
+        let weak_dir = Rc::downgrade(&dir);
+        let weak_file = Rc::downgrade(&file);
+        drop((dir, file));
+        // Still owned from above
+        assert!(weak_file.upgrade().is_some());
+
+        drop(root);
+        assert!(weak_dir.upgrade().is_none());
+        assert!(weak_file.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_unmount_release() {
+        let mnt_root = Vnode::new("", VnodeKind::Directory, 0);
+        let mnt = Vnode::new("mnt", VnodeKind::Directory, 0);
+        mnt_root.attach(mnt.clone());
+
+        let (fs, root) = tree_fs();
+        let file = root.lookup("dir0").unwrap().lookup("file0").unwrap();
+        let weak_root = Rc::downgrade(&root);
+        let weak_file = Rc::downgrade(&file);
+        mnt.mount(root).unwrap();
+
+        // The vnode tree keeps the filesystem alive while mounted
+        drop(file);
+        assert!(fs.upgrade().is_some());
+        assert!(Rc::ptr_eq(
+            &weak_root.upgrade().unwrap().parent(),
+            &mnt
+        ));
+
+        mnt.unmount().unwrap();
+        assert!(mnt.target().is_none());
+        assert!(weak_root.upgrade().is_none());
+        assert!(weak_file.upgrade().is_none());
+        assert!(fs.upgrade().is_none());
+        assert_eq!(mnt.unmount(), Err(Errno::InvalidArgument));
+    }
+
+    #[test]
+    fn test_lookup_case_insensitive() {
+        let (_fs, root) = tree_fs();
+        assert!(root.lookup("DIR0").is_none());
+        root.create("Dir0", FileMode::default_dir(), VnodeKind::Directory)
+            .unwrap();
+
+        let (_fs, root) = tree_fs_with(true);
+        let dir = root.lookup("DIR0").unwrap();
+        // Stored case is kept
+        assert_eq!(dir.name(), "dir0");
+        assert!(Rc::ptr_eq(
+            &dir.lookup("FILE0").unwrap(),
+            &dir.lookup("file0").unwrap()
+        ));
+        assert_eq!(
+            root.create("Dir0", FileMode::default_dir(), VnodeKind::Directory)
+                .err(),
+            Some(Errno::AlreadyExists)
+        );
+    }
+
+    #[test]
+    fn test_unmount_busy() {
+        let mnt = Vnode::new("", VnodeKind::Directory, 0);
+        let (fs, root) = tree_fs();
+        mnt.mount(root.clone()).unwrap();
+
+        // Referenced from outside the tree (e.g. a working directory)
+        let dir = root.lookup("dir0").unwrap();
+        assert_eq!(mnt.unmount(), Err(Errno::Busy));
+        assert!(Rc::ptr_eq(&mnt.target().unwrap(), &root));
+
+        dir.open_count.set(1);
+        drop(dir);
+        assert_eq!(mnt.unmount(), Err(Errno::Busy));
+
+        root.lookup("dir0").unwrap().open_count.set(0);
+        drop(root);
+        mnt.unmount().unwrap();
+        assert!(fs.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_io_accounting() {
+        use crate::mock::{MockFilesystem, MockOp};
+
+        let fs = MockFilesystem::new();
+        fs.add_file("file0", b"hello");
+        let file = fs.clone().root().unwrap().lookup_or_load("file0").unwrap();
+        let mut buf = [0; 16];
+
+        assert_eq!(file.read(0, &mut buf).unwrap(), 5);
+        assert_eq!(file.write(5, b" world").unwrap(), 6);
+        fs.fail(MockOp::Read, "file0", Errno::DeviceError);
+        assert!(file.read(0, &mut buf).is_err());
+
+        // Failed requests are not accounted
+        let stats = fs.io_counters().unwrap().statistics();
+        assert_eq!(stats.read_bytes, 5);
+        assert_eq!(stats.read_calls, 1);
+        assert_eq!(stats.write_bytes, 6);
+        assert_eq!(stats.write_calls, 1);
+    }
 }
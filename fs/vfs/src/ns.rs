@@ -0,0 +1,53 @@
+use crate::VnodeRef;
+use alloc::{rc::Rc, vec::Vec};
+use libsys::error::Errno;
+
+/// Private mount table, shared by an [Ioctx](crate::Ioctx) and its clones.
+///
+/// Mounts performed within a namespace are only visible to I/O contexts
+/// referring to it. Mounts performed globally are still visible to every
+/// namespace unless shadowed by a private mount at the same location.
+#[derive(Clone, Default)]
+pub struct MountNamespace {
+    mounts: Vec<(VnodeRef, VnodeRef)>,
+}
+
+impl MountNamespace {
+    /// Constructs an empty mount namespace
+    pub const fn new() -> Self {
+        Self { mounts: Vec::new() }
+    }
+
+    /// Returns the filesystem root mounted at `node` within this namespace
+    pub fn target(&self, node: &VnodeRef) -> Option<VnodeRef> {
+        self.mounts
+            .iter()
+            .find(|(at, _)| Rc::ptr_eq(at, node))
+            .map(|(_, root)| root.clone())
+    }
+
+    /// Attaches `root` at `at` directory within this namespace
+    pub fn mount(&mut self, at: VnodeRef, root: VnodeRef) -> Result<(), Errno> {
+        if self.target(&at).is_some() {
+            return Err(Errno::Busy);
+        }
+        at.mount_check(&root)?;
+        root.set_mount_parent(&at);
+        self.mounts.push((at, root));
+        Ok(())
+    }
+
+    /// Detaches the filesystem mounted at `at` within this namespace, see
+    /// [Vnode::unmount](crate::Vnode::unmount)
+    pub fn unmount(&mut self, at: &VnodeRef) -> Result<(), Errno> {
+        let index = self
+            .mounts
+            .iter()
+            .position(|(node, _)| Rc::ptr_eq(node, at))
+            .ok_or(Errno::InvalidArgument)?;
+        self.mounts[index].1.unmount_check(true)?;
+        let (_, root) = self.mounts.remove(index);
+        root.release_mount();
+        Ok(())
+    }
+}
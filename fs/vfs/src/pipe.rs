@@ -0,0 +1,169 @@
+use crate::limits;
+use alloc::{boxed::Box, rc::Rc};
+use core::cell::RefCell;
+use core::cmp::min;
+use libsys::error::Errno;
+
+struct PipeInner {
+    data: Box<[u8; Pipe::SIZE]>,
+    rd: usize,
+    len: usize,
+    readers: usize,
+    writers: usize,
+}
+
+/// In-memory FIFO buffer backing named pipes
+pub struct Pipe {
+    inner: RefCell<PipeInner>,
+}
+
+impl Pipe {
+    /// Capacity of the pipe buffer
+    pub const SIZE: usize = 4096;
+
+    /// Creates a new empty pipe. Fails with [Errno::OutOfMemory] if the
+    /// system-wide pipe cap is reached.
+    pub fn new() -> Result<Rc<Self>, Errno> {
+        if limits::PIPES.is_exhausted() {
+            return Err(Errno::OutOfMemory);
+        }
+        limits::PIPES.acquire();
+        Ok(Rc::new(Self {
+            inner: RefCell::new(PipeInner {
+                data: Box::new([0; Self::SIZE]),
+                rd: 0,
+                len: 0,
+                readers: 0,
+                writers: 0,
+            }),
+        }))
+    }
+
+    /// Registers a new reader and/or writer end
+    pub fn open(&self, read: bool, write: bool) {
+        let mut inner = self.inner.borrow_mut();
+        inner.readers += read as usize;
+        inner.writers += write as usize;
+    }
+
+    /// Unregisters a reader and/or writer end
+    pub fn close(&self, read: bool, write: bool) {
+        let mut inner = self.inner.borrow_mut();
+        inner.readers -= read as usize;
+        inner.writers -= write as usize;
+    }
+
+    /// Returns `true` if both reader and writer ends are present
+    pub fn is_connected(&self) -> bool {
+        let inner = self.inner.borrow();
+        inner.readers != 0 && inner.writers != 0
+    }
+
+    /// Returns `true` if the read (or `write`) operation will not block
+    pub fn is_ready(&self, write: bool) -> bool {
+        let inner = self.inner.borrow();
+        if write {
+            inner.readers == 0 || inner.len != Self::SIZE
+        } else {
+            inner.writers == 0 || inner.len != 0
+        }
+    }
+
+    /// Reads data from the pipe buffer. Returns [Errno::WouldBlock] if
+    /// the buffer is empty, but writers are still present.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.len == 0 {
+            return if inner.writers == 0 {
+                Ok(0)
+            } else {
+                Err(Errno::WouldBlock)
+            };
+        }
+
+        let count = min(buf.len(), inner.len);
+        for (i, byte) in buf.iter_mut().take(count).enumerate() {
+            *byte = inner.data[(inner.rd + i) % Self::SIZE];
+        }
+        inner.rd = (inner.rd + count) % Self::SIZE;
+        inner.len -= count;
+        Ok(count)
+    }
+
+    /// Writes data into the pipe buffer. Returns [Errno::WouldBlock] if
+    /// the buffer is full.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, Errno> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.readers == 0 {
+            return Err(Errno::BrokenPipe);
+        }
+        if inner.len == Self::SIZE {
+            return Err(Errno::WouldBlock);
+        }
+
+        let count = min(buf.len(), Self::SIZE - inner.len);
+        let wr = inner.rd + inner.len;
+        for (i, &byte) in buf.iter().take(count).enumerate() {
+            inner.data[(wr + i) % Self::SIZE] = byte;
+        }
+        inner.len += count;
+        Ok(count)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        limits::PIPES.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_wraparound() {
+        let pipe = Pipe::new().unwrap();
+        let mut buf = [0u8; 3000];
+        pipe.open(true, true);
+
+        assert_eq!(pipe.write(&[1; 3000]).unwrap(), 3000);
+        assert_eq!(pipe.read(&mut buf).unwrap(), 3000);
+        assert_eq!(pipe.write(&[2; 3000]).unwrap(), 3000);
+        assert_eq!(pipe.write(&[3; 3000]).unwrap(), Pipe::SIZE - 3000);
+        assert_eq!(pipe.write(&[4]), Err(Errno::WouldBlock));
+        assert_eq!(pipe.read(&mut buf).unwrap(), 3000);
+        assert!(buf.iter().all(|&b| b == 2));
+    }
+
+    #[test]
+    fn test_pipe_ends() {
+        let pipe = Pipe::new().unwrap();
+        let mut buf = [0u8; 16];
+
+        pipe.open(false, true);
+        assert_eq!(pipe.write(b"abc"), Err(Errno::BrokenPipe));
+        pipe.open(true, false);
+        assert_eq!(pipe.read(&mut buf), Err(Errno::WouldBlock));
+        assert_eq!(pipe.write(b"abc").unwrap(), 3);
+        pipe.close(false, true);
+        assert_eq!(pipe.read(&mut buf).unwrap(), 3);
+        assert_eq!(pipe.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pipe_ready() {
+        let pipe = Pipe::new().unwrap();
+        pipe.open(true, false);
+        pipe.open(false, true);
+
+        assert!(pipe.is_connected());
+        assert!(!pipe.is_ready(false));
+        assert!(pipe.is_ready(true));
+        pipe.write(&[0; Pipe::SIZE]).unwrap();
+        assert!(pipe.is_ready(false));
+        assert!(!pipe.is_ready(true));
+        pipe.close(false, true);
+        assert!(!pipe.is_connected());
+    }
+}
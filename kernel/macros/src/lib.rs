@@ -4,7 +4,12 @@ extern crate syn;
 extern crate quote;
 
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, DeriveInput};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::{
+    parse_macro_input, DeriveInput, FnArg, GenericArgument, Ident, ImplItem, ImplItemMethod,
+    ItemImpl, PathArguments, ReturnType, Type,
+};
 
 #[proc_macro_derive(TtyCharDevice)]
 pub fn derive_tty_char_device(input: TokenStream) -> TokenStream {
@@ -40,3 +45,162 @@ pub fn derive_tty_char_device(input: TokenStream) -> TokenStream {
     }
     .into()
 }
+
+enum IoctlArg {
+    None,
+    In(Type),
+    InOut(Type),
+}
+
+fn ioctl_result_type(method: &ImplItemMethod) -> Option<Type> {
+    let ty = match &method.sig.output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => panic!("ioctl handler {} must return Result", method.sig.ident),
+    };
+    let args = match &**ty {
+        Type::Path(path) => match &path.path.segments.last().unwrap().arguments {
+            PathArguments::AngleBracketed(args) if path.path.segments.last().unwrap().ident == "Result" => args,
+            _ => panic!("ioctl handler {} must return Result", method.sig.ident),
+        },
+        _ => panic!("ioctl handler {} must return Result", method.sig.ident),
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(Type::Tuple(tuple))) if tuple.elems.is_empty() => None,
+        Some(GenericArgument::Type(ty)) => Some(ty.clone()),
+        _ => panic!("ioctl handler {} must return Result", method.sig.ident),
+    }
+}
+
+fn ioctl_arg_type(method: &ImplItemMethod) -> IoctlArg {
+    let mut inputs = method.sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(recv)) if recv.reference.is_some() && recv.mutability.is_none() => {}
+        _ => panic!("ioctl handler {} must take &self", method.sig.ident),
+    }
+    let arg = match inputs.next() {
+        Some(FnArg::Typed(arg)) => arg,
+        None => return IoctlArg::None,
+        _ => unreachable!(),
+    };
+    if inputs.next().is_some() {
+        panic!("ioctl handler {} takes too many arguments", method.sig.ident);
+    }
+    match &*arg.ty {
+        Type::Reference(r) if r.mutability.is_some() => IoctlArg::InOut((*r.elem).clone()),
+        Type::Reference(r) => IoctlArg::In((*r.elem).clone()),
+        _ => panic!("ioctl handler {} argument must be a reference", method.sig.ident),
+    }
+}
+
+fn ioctl_dispatch_arm(method: &ImplItemMethod) -> TokenStream2 {
+    let name = &method.sig.ident;
+    let check_len = |ty: &Type| {
+        quote! {
+            if len != core::mem::size_of::<#ty>() {
+                return Err(libsys::error::Errno::InvalidArgument);
+            }
+        }
+    };
+    match (ioctl_arg_type(method), ioctl_result_type(method)) {
+        (IoctlArg::None, None) => quote! {
+            self.#name()?;
+            Ok(0)
+        },
+        (IoctlArg::None, Some(ty)) => {
+            let check = check_len(&ty);
+            quote! {
+                #check
+                let value = self.#name()?;
+                *crate::syscall::arg::struct_mut::<#ty>(ptr)? = value;
+                Ok(core::mem::size_of::<#ty>())
+            }
+        }
+        (IoctlArg::In(ty), None) => {
+            let check = check_len(&ty);
+            quote! {
+                #check
+                let value = crate::syscall::arg::struct_copy::<#ty>(ptr)?;
+                self.#name(&value)?;
+                Ok(core::mem::size_of::<#ty>())
+            }
+        }
+        (IoctlArg::InOut(ty), None) => {
+            let check = check_len(&ty);
+            quote! {
+                #check
+                let mut value = crate::syscall::arg::struct_copy::<#ty>(ptr)?;
+                self.#name(&mut value)?;
+                *crate::syscall::arg::struct_mut::<#ty>(ptr)? = value;
+                Ok(core::mem::size_of::<#ty>())
+            }
+        }
+        _ => panic!(
+            "ioctl handler {} cannot both take an argument and return a value",
+            name
+        ),
+    }
+}
+
+/// Generates ioctl request dispatch for handler methods of an `impl`
+/// block annotated with `#[ioctl(Command)]`. The generated method (named
+/// by the attribute argument, `ioctl` by default) checks the argument
+/// size, copies the argument between user and kernel memory and calls
+/// the handler. Depending on its signature, a handler:
+///
+/// * `fn(&self) -> Result<(), Errno>` takes no argument;
+/// * `fn(&self) -> Result<T, Errno>` returns a structure to the user;
+/// * `fn(&self, &T) -> Result<(), Errno>` receives a (validated) copy of
+///   the user structure;
+/// * `fn(&self, &mut T) -> Result<(), Errno>` receives the copy and
+///   writes it back afterwards.
+///
+/// Requests without a handler fail with `Errno::InvalidArgument`.
+#[proc_macro_attribute]
+pub fn ioctl_dispatch(attr: TokenStream, input: TokenStream) -> TokenStream {
+    let mut impl_item = parse_macro_input!(input as ItemImpl);
+    let dispatch_name = if attr.is_empty() {
+        Ident::new("ioctl", proc_macro2::Span::call_site())
+    } else {
+        parse_macro_input!(attr as Ident)
+    };
+
+    let mut commands = Vec::<Ident>::new();
+    let mut arms = Vec::new();
+    for item in impl_item.items.iter_mut() {
+        let method = match item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        let index = match method.attrs.iter().position(|a| a.path.is_ident("ioctl")) {
+            Some(index) => index,
+            None => continue,
+        };
+        let attr = method.attrs.remove(index);
+        let cmd: Ident = attr.parse_args().unwrap();
+        if commands.contains(&cmd) {
+            panic!("Duplicate handler for ioctl {}", cmd);
+        }
+
+        let arm = ioctl_dispatch_arm(method);
+        arms.push(quote! {
+            libsys::ioctl::IoctlCmd::#cmd => { #arm }
+        });
+        commands.push(cmd);
+    }
+
+    let dispatch = quote! {
+        /// Performs a control request, dispatching it to its handler
+        pub fn #dispatch_name(&self, cmd: libsys::ioctl::IoctlCmd, ptr: usize, len: usize) ->
+            Result<usize, libsys::error::Errno>
+        {
+            #[allow(unreachable_patterns)]
+            match cmd {
+                #(#arms)*
+                _ => Err(libsys::error::Errno::InvalidArgument),
+            }
+        }
+    };
+    impl_item.items.push(ImplItem::Verbatim(dispatch));
+
+    impl_item.to_token_stream().into()
+}
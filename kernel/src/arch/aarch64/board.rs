@@ -0,0 +1,129 @@
+//! Runtime board identification and machine quirks.
+//!
+//! The board is looked up by the root node's "compatible" strings of the
+//! device tree. Board code consults the resulting quirks instead of
+//! assuming a particular board variant.
+use crate::dev::fdt::{find_prop, DeviceTree};
+use crate::mem::phys;
+use crate::util::InitOnce;
+use fdt_rs::prelude::*;
+
+bitflags! {
+    /// Board-specific behavior adjustments
+    pub struct Quirks: u32 {
+        /// ESC received on the console resets the board, for boards used
+        /// in development without an easily reachable reset button
+        const CONSOLE_ESC_RESET = 1 << 0;
+    }
+}
+
+/// Description of a known board
+#[derive(Debug)]
+pub struct BoardInfo {
+    /// Human-readable board name
+    pub name: &'static str,
+    /// Root node "compatible" strings identifying the board
+    pub compatible: &'static [&'static str],
+    /// Adjustments needed by the board
+    pub quirks: Quirks,
+    /// Physical memory at or above this address is not used
+    pub mem_limit: Option<usize>,
+}
+
+static BOARDS: &[BoardInfo] = &[
+    BoardInfo {
+        name: "QEMU virt",
+        compatible: &["linux,dummy-virt"],
+        quirks: Quirks::empty(),
+        mem_limit: None,
+    },
+    BoardInfo {
+        name: "Xunlong Orange Pi 3",
+        compatible: &["xunlong,orangepi-3"],
+        quirks: Quirks::CONSOLE_ESC_RESET,
+        mem_limit: None,
+    },
+    BoardInfo {
+        name: "Raspberry Pi 3",
+        compatible: &["raspberrypi,3-model-b", "raspberrypi,3-model-b-plus"],
+        quirks: Quirks::empty(),
+        // Peripheral window, firmware memory split must not reach it
+        mem_limit: Some(0x3F000000),
+    },
+];
+
+static BOARD: InitOnce<&'static BoardInfo> = InitOnce::new();
+
+/// Identifies the board from the device tree. Without a device tree or
+/// for an unknown board, no quirks are applied.
+pub fn init(fdt: Option<&DeviceTree>) {
+    let compatible = fdt
+        .and_then(|fdt| fdt.node_by_path("/"))
+        .and_then(|root| find_prop(root, "compatible"));
+    let compatible = match compatible {
+        Some(prop) => prop,
+        None => {
+            warnln!("Board: not identified, no device tree");
+            return;
+        }
+    };
+
+    let mut names = compatible.iter_str();
+    while let Ok(Some(name)) = names.next() {
+        if let Some(info) = BOARDS.iter().find(|b| b.compatible.contains(&name)) {
+            infoln!("Board: {} ({})", info.name, name);
+            BOARD.init(info);
+            return;
+        }
+    }
+    warnln!(
+        "Board: unknown, compatible = {:?}",
+        compatible.str().unwrap_or("")
+    );
+}
+
+/// Returns the identified board, if any
+pub fn info() -> Option<&'static BoardInfo> {
+    BOARD.get_if_initialized().map(|board| *board)
+}
+
+/// Returns `true` if the board needs `quirk`
+pub fn has_quirk(quirk: Quirks) -> bool {
+    info().map_or(false, |b| b.quirks.contains(quirk))
+}
+
+/// Clamps a physical memory region at `base` of `size` bytes to the
+/// board's memory limit, returns the new size
+pub fn limit_memory(base: usize, size: usize) -> usize {
+    match info().and_then(|b| b.mem_limit) {
+        Some(limit) if base + size > limit => {
+            warnln!("Board: memory limited to {:#x}", limit);
+            limit.saturating_sub(base)
+        }
+        _ => size,
+    }
+}
+
+/// Initializes physical memory manager. Uses the EFI memory map if booted
+/// through the EFI stub, otherwise the region at `base` of `size` bytes.
+/// Either is clamped to the board's memory limit.
+///
+/// # Safety
+///
+/// Unsafe: see [phys::init_from_iter].
+pub unsafe fn init_memory(base: usize, size: usize) {
+    #[cfg(feature = "efi")]
+    if let Some(efi) = super::boot::efi::boot_info() {
+        let limit = info().and_then(|b| b.mem_limit).unwrap_or(usize::MAX);
+        phys::init_from_iter(
+            efi.memory_regions()
+                .filter(move |r| r.start < limit)
+                .map(move |r| phys::MemoryRegion {
+                    start: r.start,
+                    end: core::cmp::min(r.end, limit),
+                }),
+        );
+        return;
+    }
+    phys::init_from_region(base, limit_memory(base, size));
+}
@@ -0,0 +1,449 @@
+//! EFI stub: lets the kernel image be started by UEFI firmware.
+//!
+//! [__aa64_efi_main] runs as an EFI application, before the image has been
+//! moved to its link address. It must only use PC-relative addressing: no
+//! absolute pointers to kernel data, no trait objects, no formatting and
+//! nothing that can panic. It collects the boot information the rest of
+//! the kernel needs, leaves boot services and enters the image through
+//! the regular `_entry` path with the device tree from the firmware.
+use crate::config::{ConfigKey, CONFIG};
+use crate::mem::phys::MemoryRegion;
+use core::ffi::c_void;
+use core::mem::size_of;
+use core::ptr::{self, null, null_mut};
+
+type Handle = *mut c_void;
+type Status = usize;
+
+const EFI_SUCCESS: Status = 0;
+const EFI_LOAD_ERROR: Status = (1 << 63) | 1;
+const EFI_BUFFER_TOO_SMALL: Status = (1 << 63) | 5;
+
+const ALLOCATE_ANY_PAGES: u32 = 0;
+const ALLOCATE_ADDRESS: u32 = 2;
+
+const EFI_LOADER_CODE: u32 = 1;
+const EFI_LOADER_DATA: u32 = 2;
+const EFI_BOOT_SERVICES_CODE: u32 = 3;
+const EFI_CONVENTIONAL_MEMORY: u32 = 7;
+
+const EFI_PAGE_SIZE: usize = 0x1000;
+
+/// Marks [BOOT_INFO] as filled in by the stub
+const EFI_BOOT_MAGIC: u64 = 0x4546_4942_4F4F_5421;
+/// Maximum number of usable memory regions kept from the memory map
+const MAX_REGIONS: usize = 64;
+/// Size of the buffer the firmware memory map is read into
+const MEMORY_MAP_SIZE: usize = 16384;
+
+#[derive(Clone, Copy, PartialEq)]
+#[repr(C)]
+struct Guid(u32, u16, u16, [u8; 8]);
+
+const DEVICE_TREE_GUID: Guid = Guid(
+    0xb1b621d5,
+    0xf19c,
+    0x41a5,
+    [0x83, 0x0b, 0xd9, 0x15, 0x2c, 0x69, 0xaa, 0xe0],
+);
+const GRAPHICS_OUTPUT_GUID: Guid = Guid(
+    0x9042a9de,
+    0x23dc,
+    0x4a38,
+    [0x96, 0xfb, 0x7a, 0xde, 0xd0, 0x80, 0x51, 0x6a],
+);
+const LOAD_FILE2_GUID: Guid = Guid(
+    0x4006c0c1,
+    0xfcb3,
+    0x403e,
+    [0x99, 0x6d, 0x4a, 0x6c, 0x87, 0x24, 0xe0, 0x6d],
+);
+const LINUX_INITRD_MEDIA_GUID: Guid = Guid(
+    0x5568e427,
+    0x68fc,
+    0x4f3d,
+    [0xac, 0x74, 0xca, 0x55, 0x52, 0x31, 0xcc, 0x68],
+);
+
+#[repr(C)]
+struct TableHeader {
+    signature: u64,
+    revision: u32,
+    header_size: u32,
+    crc32: u32,
+    reserved: u32,
+}
+
+#[repr(C)]
+struct SystemTable {
+    hdr: TableHeader,
+    firmware_vendor: *const u16,
+    firmware_revision: u32,
+    console_in_handle: Handle,
+    con_in: *const c_void,
+    console_out_handle: Handle,
+    con_out: *const c_void,
+    standard_error_handle: Handle,
+    std_err: *const c_void,
+    runtime_services: *const c_void,
+    boot_services: *const BootServices,
+    table_count: usize,
+    config_tables: *const ConfigurationTable,
+}
+
+#[repr(C)]
+struct ConfigurationTable {
+    guid: Guid,
+    table: *const c_void,
+}
+
+// Only the services used by the stub are typed, the rest are kept as
+// placeholders to preserve the layout. EFI calling convention is the
+// regular C one on aarch64.
+#[repr(C)]
+struct BootServices {
+    hdr: TableHeader,
+    _tpl: [usize; 2],
+    allocate_pages: extern "C" fn(u32, u32, usize, *mut u64) -> Status,
+    _free_pages: usize,
+    get_memory_map: extern "C" fn(*mut usize, *mut u8, *mut usize, *mut usize, *mut u32) -> Status,
+    _pool: [usize; 2],
+    _event: [usize; 6],
+    _protocol_interface: [usize; 3],
+    handle_protocol: extern "C" fn(Handle, *const Guid, *mut *mut c_void) -> Status,
+    _reserved: usize,
+    _register_protocol_notify: usize,
+    _locate_handle: usize,
+    locate_device_path: extern "C" fn(*const Guid, *mut *const c_void, *mut Handle) -> Status,
+    _install_configuration_table: usize,
+    _image: [usize; 4],
+    exit_boot_services: extern "C" fn(Handle, usize) -> Status,
+    _misc: [usize; 3],
+    _driver: [usize; 2],
+    _open_protocol: [usize; 3],
+    _protocols_per_handle: usize,
+    _locate_handle_buffer: usize,
+    locate_protocol: extern "C" fn(*const Guid, *const c_void, *mut *mut c_void) -> Status,
+}
+
+#[repr(C)]
+struct MemoryDescriptor {
+    kind: u32,
+    physical_start: u64,
+    virtual_start: u64,
+    page_count: u64,
+    attribute: u64,
+}
+
+#[repr(C)]
+struct GraphicsOutput {
+    _query_mode: usize,
+    _set_mode: usize,
+    _blt: usize,
+    mode: *const GraphicsMode,
+}
+
+#[repr(C)]
+struct GraphicsMode {
+    max_mode: u32,
+    mode: u32,
+    info: *const GraphicsModeInfo,
+    info_size: usize,
+    framebuffer_base: u64,
+    framebuffer_size: usize,
+}
+
+#[repr(C)]
+struct GraphicsModeInfo {
+    version: u32,
+    width: u32,
+    height: u32,
+    pixel_format: u32,
+    pixel_mask: [u32; 4],
+    pixels_per_scan_line: u32,
+}
+
+#[repr(C)]
+struct LoadFile2 {
+    load_file: extern "C" fn(*mut LoadFile2, *const c_void, u8, *mut usize, *mut u8) -> Status,
+}
+
+// Vendor media device path the Linux initrd is published under
+#[repr(C, packed)]
+struct InitrdDevicePath {
+    kind: u8,
+    subtype: u8,
+    length: [u8; 2],
+    guid: Guid,
+    end_kind: u8,
+    end_subtype: u8,
+    end_length: [u8; 2],
+}
+
+/// Linear framebuffer set up by the firmware
+#[derive(Clone, Copy, Debug)]
+pub struct Framebuffer {
+    /// Physical base address
+    pub base: usize,
+    /// Size in bytes
+    pub size: usize,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Pixels per scan line
+    pub stride: u32,
+    /// GOP pixel format: 0 - RGBX, 1 - BGRX, 2 - bit mask
+    pub format: u32,
+}
+
+/// Information collected by the EFI stub before leaving boot services
+pub struct EfiBootInfo {
+    magic: u64,
+    initrd: Option<(usize, usize)>,
+    framebuffer: Option<Framebuffer>,
+    region_count: usize,
+    regions: [(usize, usize); MAX_REGIONS],
+}
+
+// Forced into .data: the stub writes it into the relocated image, .bss is
+// zeroed on entry
+#[link_section = ".data.efi_boot_info"]
+static mut BOOT_INFO: EfiBootInfo = EfiBootInfo {
+    magic: 0,
+    initrd: None,
+    framebuffer: None,
+    region_count: 0,
+    regions: [(0, 0); MAX_REGIONS],
+};
+static mut MEMORY_MAP: [u8; MEMORY_MAP_SIZE] = [0; MEMORY_MAP_SIZE];
+
+impl EfiBootInfo {
+    /// Returns usable physical memory regions from the EFI memory map
+    pub fn memory_regions(&self) -> impl Iterator<Item = MemoryRegion> + Clone + '_ {
+        self.regions
+            .iter()
+            .take(self.region_count)
+            .map(|&(start, end)| MemoryRegion { start, end })
+    }
+
+    /// Returns the framebuffer from Graphics Output Protocol, if any
+    pub fn framebuffer(&self) -> Option<Framebuffer> {
+        self.framebuffer
+    }
+
+    /// Returns physical base and size of the initrd loaded through
+    /// LoadFile2 protocol, if any
+    pub fn initrd(&self) -> Option<(usize, usize)> {
+        self.initrd
+    }
+}
+
+/// Returns the information passed by the EFI stub, if the kernel was
+/// booted through it
+pub fn boot_info() -> Option<&'static EfiBootInfo> {
+    let info = unsafe { &*ptr::addr_of!(BOOT_INFO) };
+    (info.magic == EFI_BOOT_MAGIC).then(|| info)
+}
+
+/// Applies the information passed by the EFI stub: initrd location and
+/// framebuffer
+pub fn init() {
+    let info = match boot_info() {
+        Some(info) => info,
+        None => return,
+    };
+    infoln!("Booted through EFI stub");
+
+    if let Some((base, size)) = info.initrd() {
+        let mut cfg = CONFIG.lock();
+        cfg.set_usize(ConfigKey::InitrdBase, base);
+        cfg.set_usize(ConfigKey::InitrdSize, size);
+    }
+    if let Some(fb) = info.framebuffer() {
+        infoln!(
+            "EFI framebuffer: {}x{} at {:#x}, stride {}, format {}",
+            fb.width,
+            fb.height,
+            fb.base,
+            fb.stride,
+            fb.format
+        );
+    }
+}
+
+unsafe fn find_config_table(st: &SystemTable, guid: &Guid) -> *const c_void {
+    for i in 0..st.table_count {
+        let table = &*st.config_tables.add(i);
+        if table.guid == *guid {
+            return table.table;
+        }
+    }
+    null()
+}
+
+unsafe fn locate_framebuffer(bs: &BootServices) -> Option<Framebuffer> {
+    let mut gop: *mut c_void = null_mut();
+    if (bs.locate_protocol)(&GRAPHICS_OUTPUT_GUID, null(), &mut gop) != EFI_SUCCESS {
+        return None;
+    }
+    let mode = (*(gop as *const GraphicsOutput)).mode.as_ref()?;
+    let info = mode.info.as_ref()?;
+    Some(Framebuffer {
+        base: mode.framebuffer_base as usize,
+        size: mode.framebuffer_size,
+        width: info.width,
+        height: info.height,
+        stride: info.pixels_per_scan_line,
+        format: info.pixel_format,
+    })
+}
+
+unsafe fn load_initrd(bs: &BootServices) -> Option<(usize, usize)> {
+    let path = InitrdDevicePath {
+        kind: 4,
+        subtype: 3,
+        length: [20, 0],
+        guid: LINUX_INITRD_MEDIA_GUID,
+        end_kind: 0x7F,
+        end_subtype: 0xFF,
+        end_length: [4, 0],
+    };
+    let path = &path as *const InitrdDevicePath as *const c_void;
+
+    let mut remaining = path;
+    let mut handle: Handle = null_mut();
+    if (bs.locate_device_path)(&LOAD_FILE2_GUID, &mut remaining, &mut handle) != EFI_SUCCESS {
+        return None;
+    }
+    let mut proto: *mut c_void = null_mut();
+    if (bs.handle_protocol)(handle, &LOAD_FILE2_GUID, &mut proto) != EFI_SUCCESS {
+        return None;
+    }
+    let proto = proto as *mut LoadFile2;
+
+    let mut size = 0;
+    if ((*proto).load_file)(proto, path, 0, &mut size, null_mut()) != EFI_BUFFER_TOO_SMALL {
+        return None;
+    }
+    let mut base = 0u64;
+    let pages = (size + EFI_PAGE_SIZE - 1) / EFI_PAGE_SIZE;
+    if (bs.allocate_pages)(ALLOCATE_ANY_PAGES, EFI_LOADER_DATA, pages, &mut base) != EFI_SUCCESS {
+        return None;
+    }
+    if ((*proto).load_file)(proto, path, 0, &mut size, base as *mut u8) != EFI_SUCCESS {
+        return None;
+    }
+    Some((base as usize, size))
+}
+
+// Loader data and boot services data are left out: they hold the
+// initrd, the device tree and other firmware tables, which are not
+// reserved otherwise
+fn is_usable(kind: u32) -> bool {
+    matches!(
+        kind,
+        EFI_LOADER_CODE | EFI_BOOT_SERVICES_CODE | EFI_CONVENTIONAL_MEMORY
+    )
+}
+
+unsafe fn parse_memory_map(info: &mut EfiBootInfo, size: usize, desc_size: usize) {
+    let map = ptr::addr_of!(MEMORY_MAP) as *const u8;
+    let mut offset = 0;
+    while offset + size_of::<MemoryDescriptor>() <= size {
+        let desc = &*(map.add(offset) as *const MemoryDescriptor);
+        offset += desc_size;
+
+        if !is_usable(desc.kind) {
+            continue;
+        }
+        let start = desc.physical_start as usize;
+        let end = start + desc.page_count as usize * EFI_PAGE_SIZE;
+
+        // Merge with the previous region if contiguous
+        if info.region_count != 0 {
+            let last = &mut info.regions[info.region_count - 1];
+            if last.1 == start {
+                last.1 = end;
+                continue;
+            }
+        }
+        if info.region_count == MAX_REGIONS {
+            break;
+        }
+        info.regions[info.region_count] = (start, end);
+        info.region_count += 1;
+    }
+}
+
+extern "C" {
+    fn __aa64_efi_exit(fdt: usize, base: usize, size: usize) -> !;
+}
+
+/// EFI application entry point, called by `__aa64_efi_entry` with the
+/// image's current `base` and the link-time layout of the image
+#[no_mangle]
+unsafe extern "C" fn __aa64_efi_main(
+    image: Handle,
+    st: *const SystemTable,
+    base: usize,
+    load_base: usize,
+    image_size: usize,
+    file_size: usize,
+) -> Status {
+    let st = &*st;
+    let bs = &*st.boot_services;
+
+    let fdt = find_config_table(st, &DEVICE_TREE_GUID) as usize;
+    let framebuffer = locate_framebuffer(bs);
+    let initrd = load_initrd(bs);
+
+    // Move the image to its link address unless loaded there already
+    if base != load_base {
+        let mut dst = load_base as u64;
+        let pages = (image_size + EFI_PAGE_SIZE - 1) / EFI_PAGE_SIZE;
+        if (bs.allocate_pages)(ALLOCATE_ADDRESS, EFI_LOADER_DATA, pages, &mut dst) != EFI_SUCCESS {
+            return EFI_LOAD_ERROR;
+        }
+        ptr::copy_nonoverlapping(base as *const u8, load_base as *mut u8, file_size);
+    }
+
+    // Retry once: the memory map changes if the key turns out stale
+    let mut map_size = 0;
+    let mut desc_size = 0;
+    let mut exited = false;
+    for _ in 0..2 {
+        let mut key = 0;
+        let mut desc_version = 0;
+        map_size = MEMORY_MAP_SIZE;
+        let status = (bs.get_memory_map)(
+            &mut map_size,
+            ptr::addr_of_mut!(MEMORY_MAP) as *mut u8,
+            &mut key,
+            &mut desc_size,
+            &mut desc_version,
+        );
+        if status != EFI_SUCCESS {
+            return status;
+        }
+        if (bs.exit_boot_services)(image, key) == EFI_SUCCESS {
+            exited = true;
+            break;
+        }
+    }
+    if !exited {
+        return EFI_LOAD_ERROR;
+    }
+
+    // Boot services are gone from here on, fill in the relocated image's
+    // copy of BOOT_INFO
+    let offset = ptr::addr_of!(BOOT_INFO) as usize - base;
+    let info = &mut *((load_base + offset) as *mut EfiBootInfo);
+    info.region_count = 0;
+    parse_memory_map(info, map_size, desc_size);
+    info.initrd = initrd;
+    info.framebuffer = framebuffer;
+    info.magic = EFI_BOOT_MAGIC;
+
+    __aa64_efi_exit(fdt, load_base, image_size)
+}
@@ -1,18 +1,22 @@
 //! aarch64 common boot logic
 
 use crate::arch::{
-    aarch64::reg::{CNTKCTL_EL1, CPACR_EL1},
+    aarch64::{
+        board,
+        reg::{CNTKCTL_EL1, CPACR_EL1},
+    },
     machine,
 };
 use crate::config::{ConfigKey, CONFIG};
+use crate::debug;
 use crate::dev::{
-    fdt::{find_prop, DeviceTree},
+    self,
+    fdt::{find_prop, prop_usize, DeviceTree},
     irq::IntSource,
-    Device,
 };
-use crate::fs::devfs;
-use crate::dev::pseudo;
-use libsys::error::Errno;
+use crate::fs::{self, devfs};
+use crate::dev::{crash, kmsg, led, pseudo, sysrq, uevent, zram};
+use libsys::{error::Errno, stat::makedev};
 //use crate::debug::Level;
 use crate::mem::{
     self, heap,
@@ -24,6 +28,9 @@ use cortex_a::asm::barrier::{self, dsb, isb};
 use cortex_a::registers::{SCTLR_EL1, VBAR_EL1};
 use tock_registers::interfaces::{ReadWriteable, Writeable};
 
+#[cfg(feature = "efi")]
+pub mod efi;
+
 fn init_device_tree(fdt_base_phys: usize) -> Result<Option<DeviceTree>, Errno> {
     use fdt_rs::prelude::*;
 
@@ -34,20 +41,35 @@ fn init_device_tree(fdt_base_phys: usize) -> Result<Option<DeviceTree>, Errno> {
         return Ok(None);
     };
 
+    unsafe {
+        phys::reserve_boot_region("fdt", fdt_base_phys, fdt_base_phys + fdt.size()).ok();
+        for (base, size) in fdt.reserved_regions() {
+            phys::reserve_boot_region("fdt-reserved", base, base + size).ok();
+        }
+    }
+
     let mut cfg = CONFIG.lock();
 
     if let Some(chosen) = fdt.node_by_path("/chosen") {
-        if let Some(initrd_start) = find_prop(chosen.clone(), "linux,initrd-start") {
-            let initrd_end = find_prop(chosen.clone(), "linux,initrd-end").unwrap();
-            let initrd_start = initrd_start.u32(0).unwrap() as usize;
-            let initrd_end = initrd_end.u32(0).unwrap() as usize;
-
-            cfg.set_usize(ConfigKey::InitrdBase, initrd_start);
-            cfg.set_usize(ConfigKey::InitrdSize, initrd_end - initrd_start);
+        let initrd_start = find_prop(chosen.clone(), "linux,initrd-start");
+        let initrd_end = find_prop(chosen.clone(), "linux,initrd-end");
+        match (initrd_start, initrd_end) {
+            (Some(start), Some(end)) => match (prop_usize(&start), prop_usize(&end)) {
+                (Some(start), Some(end)) if end > start => {
+                    cfg.set_usize(ConfigKey::InitrdBase, start);
+                    cfg.set_usize(ConfigKey::InitrdSize, end - start);
+                }
+                _ => warnln!("Ignoring malformed initrd location in /chosen"),
+            },
+            (None, None) => (),
+            _ => warnln!("Ignoring initrd: /chosen lacks either of linux,initrd-start/end"),
         }
 
         if let Some(cmdline) = find_prop(chosen, "bootargs") {
-            cfg.set_cmdline(cmdline.str().unwrap());
+            match cmdline.str() {
+                Ok(cmdline) => cfg.set_cmdline(cmdline),
+                Err(_) => warnln!("Ignoring malformed bootargs in /chosen"),
+            }
         }
     }
 
@@ -83,6 +105,12 @@ extern "C" fn __aa64_bsp_main(fdt_base: usize) -> ! {
     virt::enable().expect("Failed to initialize virtual memory");
 
     let fdt = init_device_tree(fdt_base).expect("Device tree init failed");
+    debug::configure_console(CONFIG.lock().get_str(ConfigKey::Console));
+    sysrq::set_enabled(CONFIG.lock().get_usize(ConfigKey::SysRq) != 0);
+    #[cfg(feature = "efi")]
+    efi::init();
+    board::init(fdt.as_ref());
+    machine::select(fdt.as_ref()).expect("No machine support for the board");
 
     // Most basic machine init: initialize proper debug output
     // physical memory
@@ -96,9 +124,10 @@ extern "C" fn __aa64_bsp_main(fdt_base: usize) -> ! {
         heap::init(heap_base_virt, 16 * 1024 * 1024);
     }
 
-    devfs::init();
+    fs::init();
+    kmsg::KMSG.init().expect("Failed to allocate kernel message ring");
 
-    machine::init_board().unwrap();
+    machine::init_board(fdt.as_ref()).unwrap();
 
     #[cfg(feature = "verbose")]
     if let Some(fdt) = fdt {
@@ -106,13 +135,24 @@ extern "C" fn __aa64_bsp_main(fdt_base: usize) -> ! {
         fdt.dump(Level::Debug);
     }
 
-    devfs::add_named_char_device(&pseudo::ZERO, "zero").unwrap();
-    devfs::add_named_char_device(&pseudo::RANDOM, "random").unwrap();
+    devfs::add_named_char_device(&pseudo::ZERO, "zero", makedev(devfs::MAJOR_MEM, 5)).unwrap();
+    devfs::add_named_char_device(&pseudo::RANDOM, "random", makedev(devfs::MAJOR_MEM, 8))
+        .unwrap();
+    devfs::add_named_char_device(&uevent::UEVENT, "uevent", makedev(devfs::MAJOR_MISC, 0))
+        .unwrap();
+    devfs::add_named_char_device(&led::LED_CONTROL, "leds", makedev(devfs::MAJOR_MISC, 2))
+        .unwrap();
+    devfs::add_named_char_device(&kmsg::KMSG, "kmsg", makedev(devfs::MAJOR_MISC, 3)).unwrap();
+    devfs::add_named_char_device(&crash::CRASH, "crash", makedev(devfs::MAJOR_MISC, 4)).unwrap();
+    devfs::add_named_block_device(&zram::ZRAM, "zram0", makedev(devfs::MAJOR_ZRAM, 0)).unwrap();
 
     infoln!("Machine init finished");
 
+    #[cfg(feature = "fault_inject")]
+    crate::fault::init();
+
     unsafe {
-        machine::local_timer().enable().unwrap();
+        dev::enable(machine::local_timer()).unwrap();
         machine::local_timer().init_irqs().unwrap();
 
         proc::enter();
@@ -121,4 +161,6 @@ extern "C" fn __aa64_bsp_main(fdt_base: usize) -> ! {
 
 global_asm!(include_str!("macros.S"));
 global_asm!(include_str!("uboot.S"));
+#[cfg(feature = "efi")]
+global_asm!(include_str!("efi.S"));
 global_asm!(include_str!("upper.S"));
@@ -2,12 +2,16 @@
 
 use crate::arch::machine;
 use crate::debug::Level;
-use crate::dev::irq::{IntController, IrqContext};
+use crate::dev::{
+    crash,
+    irq::{IntController, IrqContext},
+};
 use crate::mem;
-use crate::proc::{sched, Process, Thread};
+use crate::proc::{sched, Process, ProcessRef, Thread, ThreadRef};
 use crate::syscall;
+use crate::time;
 use cortex_a::registers::{ESR_EL1, FAR_EL1};
-use libsys::{abi::SystemCall, signal::Signal, error::Errno};
+use libsys::{abi::SystemCall, error::Errno, proc::CrashRecord, signal::Signal};
 use tock_registers::interfaces::Readable;
 
 /// Trapped SIMD/FP functionality
@@ -79,6 +83,34 @@ fn dump_data_abort(level: Level, esr: u64, far: u64) {
     println!(level, "\x1B[0m");
 }
 
+/// Describes the fault of `thread` to userspace crash handlers
+fn report_crash(
+    proc: &ProcessRef,
+    thread: &ThreadRef,
+    exc: &ExceptionFrame,
+    signal: Signal,
+    esr: u64,
+    far: u64,
+) {
+    let mut x = [0; 31];
+    for (dst, &src) in x.iter_mut().zip(exc.x.iter()) {
+        *dst = src as u64;
+    }
+    crash::report(CrashRecord {
+        pid: u32::from(proc.id()),
+        tid: u32::from(thread.id()),
+        signal: signal as u32,
+        esr: esr as u32,
+        far,
+        pc: exc.elr_el1,
+        sp: exc.sp_el0,
+        pstate: exc.spsr_el1,
+        x,
+        time: time::realtime().as_secs(),
+        name: proc.name(),
+    });
+}
+
 #[no_mangle]
 extern "C" fn __aa64_exc_sync_handler(exc: &mut ExceptionFrame) {
     let esr = ESR_EL1.get();
@@ -90,13 +122,14 @@ extern "C" fn __aa64_exc_sync_handler(exc: &mut ExceptionFrame) {
             let far = FAR_EL1.get() as usize;
             let iss = esr & 0x1FFFFFF;
 
-            if iss & (1 << 6) != 0 && far < mem::KERNEL_OFFSET && sched::is_ready() {
+            if far < mem::KERNEL_OFFSET && sched::is_ready() {
                 let thread = Thread::current();
                 let proc = thread.owner().unwrap();
                 let asid = proc.asid();
+                let write = iss & (1 << 6) != 0;
 
                 let res = proc.manipulate_space(|space| {
-                    space.try_cow_copy(far)?;
+                    space.resolve_fault(far, write)?;
                     Process::invalidate_asid(asid);
                     Result::<(), Errno>::Ok(())
                 });
@@ -105,6 +138,18 @@ extern "C" fn __aa64_exc_sync_handler(exc: &mut ExceptionFrame) {
                     // Kill program
                     errorln!("Data abort from {:#x}", exc.elr_el1);
                     dump_data_abort(Level::Error, esr, far as u64);
+                    // Kernel faults on user addresses have no user state
+                    // worth reporting
+                    if err_code == EC_DATA_ABORT_EL0 {
+                        report_crash(
+                            &proc,
+                            &thread,
+                            exc,
+                            Signal::SegmentationFault,
+                            esr,
+                            far as u64,
+                        );
+                    }
                     proc.enter_fault_signal(thread, Signal::SegmentationFault);
                 }
 
@@ -123,7 +168,7 @@ extern "C" fn __aa64_exc_sync_handler(exc: &mut ExceptionFrame) {
             let num = num.unwrap();
 
             if num == SystemCall::Fork {
-                match unsafe { syscall::sys_fork(exc) } {
+                match syscall::filter(num).and_then(|_| unsafe { syscall::sys_fork(exc) }) {
                     Ok(pid) => exc.x[0] = u32::from(pid) as usize,
                     Err(err) => {
                         exc.x[0] = err.to_negative_isize() as usize;
@@ -1,5 +1,6 @@
 use crate::dev::irq::IrqContext;
 use crate::mem::virt::DeviceMemoryIo;
+use crate::sync::IrqSafeSpinLock;
 use tock_registers::interfaces::{Readable, Writeable};
 use tock_registers::registers::ReadWrite;
 use tock_registers::{register_bitfields, register_structs};
@@ -32,13 +33,23 @@ register_structs! {
     }
 }
 
+/// CPU interface state preserved across suspend
+struct GiccContext {
+    ctlr: u32,
+    pmr: u32,
+}
+
 pub(super) struct Gicc {
     regs: DeviceMemoryIo<GiccRegs>,
+    context: IrqSafeSpinLock<GiccContext>,
 }
 
 impl Gicc {
     pub const unsafe fn new(regs: DeviceMemoryIo<GiccRegs>) -> Self {
-        Self { regs }
+        Self {
+            regs,
+            context: IrqSafeSpinLock::new(GiccContext { ctlr: 0, pmr: 0 }),
+        }
     }
 
     pub unsafe fn enable(&self) {
@@ -47,6 +58,18 @@ impl Gicc {
         self.regs.PMR.write(PMR::Priority.val(0xFF));
     }
 
+    pub unsafe fn suspend(&self) {
+        let mut context = self.context.lock();
+        context.ctlr = self.regs.CTLR.get();
+        context.pmr = self.regs.PMR.get();
+    }
+
+    pub unsafe fn resume(&self) {
+        let context = self.context.lock();
+        self.regs.PMR.set(context.pmr);
+        self.regs.CTLR.set(context.ctlr);
+    }
+
     pub fn pending_irq_number<'q>(&'q self, _ic: &IrqContext<'q>) -> usize {
         self.regs.IAR.read(IAR::InterruptID) as usize
     }
@@ -1,7 +1,7 @@
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use tock_registers::interfaces::{Readable, Writeable};
-use tock_registers::registers::{ReadOnly, ReadWrite};
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
 use tock_registers::{register_bitfields, register_structs};
 
 register_bitfields! {
@@ -12,6 +12,14 @@ register_bitfields! {
     TYPER [
         ITLinesNumber OFFSET(0) NUMBITS(5) []
     ],
+    SGIR [
+        TargetListFilter OFFSET(24) NUMBITS(2) [
+            List = 0,
+            Others = 1,
+            Local = 2
+        ],
+        INTID OFFSET(0) NUMBITS(4) []
+    ],
     ITARGETSR [
         Offset3 OFFSET(24) NUMBITS(8) [],
         Offset2 OFFSET(16) NUMBITS(8) [],
@@ -28,10 +36,14 @@ register_structs! {
         (0x008 => _res0),
         (0x104 => ISENABLER: [ReadWrite<u32>; 31]),
         (0x180 => _res1),
+        (0x184 => ICENABLER: [ReadWrite<u32>; 31]),
+        (0x200 => _res2),
         (0x820 => ITARGETSR: [ReadWrite<u32, ITARGETSR::Register>; 248]),
-        (0xC00 => _res2),
+        (0xC00 => _res3),
         (0xC08 => ICFGR: [ReadWrite<u32>; 62]),
-        (0xC0C => @END),
+        (0xD00 => _res4),
+        (0xF00 => SGIR: WriteOnly<u32, SGIR::Register>),
+        (0xF04 => @END),
     }
 }
 
@@ -41,8 +53,10 @@ register_structs! {
         (0x000 => _res0),
         (0x100 => ISENABLER: ReadWrite<u32>),
         (0x104 => _res1),
+        (0x180 => ICENABLER: ReadWrite<u32>),
+        (0x184 => _res2),
         (0x800 => ITARGETSR: [ReadOnly<u32, ITARGETSR::Register>; 8]),
-        (0x804 => _res2),
+        (0x804 => _res3),
         (0xC00 => ICFGR: [ReadWrite<u32>; 2]),
         (0xC04 => @END),
     }
@@ -62,9 +76,34 @@ impl GicdSharedRegs {
     }
 }
 
+/// Distributor state preserved across suspend. Priorities are not saved,
+/// the driver leaves them at their reset values.
+struct GicdContext {
+    ctlr: u32,
+    banked_enable: u32,
+    banked_cfg: u32,
+    enable: [u32; 31],
+    targets: [u32; 248],
+    cfg: [u32; 62],
+}
+
 pub(super) struct Gicd {
     shared_regs: IrqSafeSpinLock<DeviceMemoryIo<GicdSharedRegs>>,
     banked_regs: DeviceMemoryIo<GicdBankedRegs>,
+    context: IrqSafeSpinLock<GicdContext>,
+}
+
+impl GicdContext {
+    const fn new() -> Self {
+        Self {
+            ctlr: 0,
+            banked_enable: 0,
+            banked_cfg: 0,
+            enable: [0; 31],
+            targets: [0; 248],
+            cfg: [0; 62],
+        }
+    }
 }
 
 impl Gicd {
@@ -75,6 +114,7 @@ impl Gicd {
         Self {
             shared_regs: IrqSafeSpinLock::new(shared_mmio),
             banked_regs: banked_mmio,
+            context: IrqSafeSpinLock::new(GicdContext::new()),
         }
     }
 
@@ -109,6 +149,35 @@ impl Gicd {
         self.enable_irq_inner(irq);
     }
 
+    pub fn disable_irq(&self, irq: super::IrqNumber) {
+        let irq = irq.get();
+        let reg = irq >> 5;
+        let bit = 1u32 << (irq & 0x1F);
+
+        // Writing zeros to ICENABLER has no effect
+        match reg {
+            // Private
+            0 => self.banked_regs.ICENABLER.set(bit),
+            // Shared
+            _ => self.shared_regs.lock().ICENABLER[reg - 1].set(bit),
+        }
+    }
+
+    pub fn set_edge_triggered(&self, irq: super::IrqNumber) {
+        let irq = irq.get();
+        assert!(irq >= 32);
+        let regs = self.shared_regs.lock();
+        // ICFGR0/1 are banked, the array starts from ICFGR2
+        let reg = &regs.ICFGR[(irq >> 4) - 2];
+        reg.set(reg.get() | (2 << ((irq & 0xF) * 2)));
+    }
+
+    pub fn raise_local_sgi(&self, irq: super::IrqNumber) {
+        self.shared_regs.lock().SGIR.write(
+            SGIR::TargetListFilter::Local + SGIR::INTID.val(irq.get() as u32),
+        );
+    }
+
     pub unsafe fn enable(&self) {
         let mask = self.local_gic_target_mask();
         let regs = self.shared_regs.lock();
@@ -125,4 +194,54 @@ impl Gicd {
             );
         }
     }
+
+    /// Saves IRQ enables, targets and trigger modes, including the ones
+    /// of the current CPU's private IRQs
+    pub unsafe fn suspend(&self) {
+        let regs = self.shared_regs.lock();
+        let mut context = self.context.lock();
+        let spi_count = regs.num_irqs() - 32;
+
+        context.ctlr = regs.CTLR.get();
+        context.banked_enable = self.banked_regs.ISENABLER.get();
+        // ICFGR0 (SGIs) is read-only
+        context.banked_cfg = self.banked_regs.ICFGR[1].get();
+
+        for (index, reg) in regs.ISENABLER[..spi_count / 32].iter().enumerate() {
+            context.enable[index] = reg.get();
+        }
+        for (index, reg) in regs.ITARGETSR[..spi_count / 4].iter().enumerate() {
+            context.targets[index] = reg.get();
+        }
+        for (index, reg) in regs.ICFGR[..spi_count / 16].iter().enumerate() {
+            context.cfg[index] = reg.get();
+        }
+    }
+
+    /// Restores the state saved by [Gicd::suspend]
+    pub unsafe fn resume(&self) {
+        let regs = self.shared_regs.lock();
+        let context = self.context.lock();
+        let spi_count = regs.num_irqs() - 32;
+
+        // Nothing is forwarded while the state is half-restored
+        regs.CTLR.set(0);
+
+        self.banked_regs.ICFGR[1].set(context.banked_cfg);
+        self.banked_regs.ICENABLER.set(u32::MAX);
+        self.banked_regs.ISENABLER.set(context.banked_enable);
+
+        for (reg, &value) in regs.ICFGR[..spi_count / 16].iter().zip(&context.cfg) {
+            reg.set(value);
+        }
+        for (reg, &value) in regs.ITARGETSR[..spi_count / 4].iter().zip(&context.targets) {
+            reg.set(value);
+        }
+        for (index, &value) in context.enable[..spi_count / 32].iter().enumerate() {
+            regs.ICENABLER[index].set(u32::MAX);
+            regs.ISENABLER[index].set(value);
+        }
+
+        regs.CTLR.set(context.ctlr);
+    }
 }
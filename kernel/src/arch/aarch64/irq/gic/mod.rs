@@ -1,49 +1,38 @@
 //! ARM Generic Interrupt Controller
 
 use crate::dev::{
-    irq::{IntController, IntSource, IrqContext},
+    irq::{IntController, IntSource, IrqAllocator, IrqContext, IrqKind, IrqTable},
     Device,
 };
 use crate::mem::virt::{DeviceMemory, DeviceMemoryIo};
-use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
 use libsys::error::Errno;
 
+pub use super::IrqNumber;
+
 mod gicc;
 use gicc::Gicc;
 mod gicd;
 use gicd::Gicd;
+mod v2m;
+use v2m::V2m;
 
 /// Maximum available IRQ number
 pub const MAX_IRQ: usize = 300;
-
-/// Range-checked IRQ number type
-#[repr(transparent)]
-#[derive(Copy, Clone)]
-pub struct IrqNumber(usize);
+/// Number of software-generated interrupts
+const SGI_COUNT: usize = 16;
 
 /// ARM Generic Interrupt Controller, version 2
 pub struct Gic {
     gicc: InitOnce<Gicc>,
     gicd: InitOnce<Gicd>,
+    v2m: InitOnce<V2m>,
     gicd_base: usize,
     gicc_base: usize,
-    table: IrqSafeSpinLock<[Option<&'static (dyn IntSource + Sync)>; MAX_IRQ]>,
-}
-
-impl IrqNumber {
-    /// Returns numeric representation for given [IrqNumber]
-    #[inline(always)]
-    pub const fn get(self) -> usize {
-        self.0
-    }
-
-    /// Checks and wraps an IRQ number
-    #[inline(always)]
-    pub const fn new(v: usize) -> Self {
-        assert!(v < MAX_IRQ);
-        Self(v)
-    }
+    v2m_base: Option<usize>,
+    table: IrqTable<MAX_IRQ>,
+    sgis: IrqAllocator,
+    msis: InitOnce<IrqAllocator>,
 }
 
 impl Device for Gic {
@@ -66,6 +55,27 @@ impl Device for Gic {
         self.gicd.init(gicd);
         self.gicc.init(gicc);
 
+        if let Some(v2m_base) = self.v2m_base {
+            let v2m = V2m::new(DeviceMemoryIo::map("GICv2m MSI frame", v2m_base, 1)?, v2m_base);
+            let (start, count) = v2m.spi_range();
+            let count = core::cmp::min(count, 128);
+            debugln!("GICv2m: MSI SPIs {}..{}", start, start + count);
+            self.msis.init(IrqAllocator::new(start, count));
+            self.v2m.init(v2m);
+        }
+
+        Ok(())
+    }
+
+    unsafe fn suspend(&self) -> Result<(), Errno> {
+        self.gicd.try_get()?.suspend();
+        self.gicc.try_get()?.suspend();
+        Ok(())
+    }
+
+    unsafe fn resume(&self) -> Result<(), Errno> {
+        self.gicd.try_get()?.resume();
+        self.gicc.try_get()?.resume();
         Ok(())
     }
 }
@@ -78,6 +88,60 @@ impl IntController for Gic {
         Ok(())
     }
 
+    fn disable_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno> {
+        self.gicd.get().disable_irq(irq);
+        Ok(())
+    }
+
+    fn allocate_irq(&self, kind: IrqKind) -> Result<Self::IrqNumber, Errno> {
+        match kind {
+            IrqKind::Software => self.sgis.alloc().map(IrqNumber::new),
+            IrqKind::Msi => {
+                let msis = self.msis.get_if_initialized().ok_or(Errno::NotImplemented)?;
+                let irq = IrqNumber::new(msis.alloc()?);
+                // GICv2m signals MSIs as edges
+                self.gicd.get().set_edge_triggered(irq);
+                Ok(irq)
+            }
+        }
+    }
+
+    fn free_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno> {
+        let allocator = if self.sgis.contains(irq.get()) {
+            &self.sgis
+        } else if let Some(msis) = self
+            .msis
+            .get_if_initialized()
+            .filter(|msis| msis.contains(irq.get()))
+        {
+            msis
+        } else {
+            return Err(Errno::InvalidArgument);
+        };
+        allocator.free(irq.get())?;
+        if !self.sgis.contains(irq.get()) {
+            self.gicd.get().disable_irq(irq);
+        }
+        self.table.unregister_all(irq.get());
+        Ok(())
+    }
+
+    fn raise_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno> {
+        if !self.sgis.contains(irq.get()) {
+            return Err(Errno::InvalidArgument);
+        }
+        self.gicd.get().raise_local_sgi(irq);
+        Ok(())
+    }
+
+    fn msi_message(&self, irq: Self::IrqNumber) -> Result<(usize, u32), Errno> {
+        let msis = self.msis.get_if_initialized();
+        if !msis.map_or(false, |msis| msis.contains(irq.get())) {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok((self.v2m.get().doorbell(), irq.get() as u32))
+    }
+
     fn handle_pending_irqs<'irq_context>(&'irq_context self, ic: &IrqContext<'irq_context>) {
         let gicc = self.gicc.get();
         let irq_number = gicc.pending_irq_number(ic);
@@ -87,16 +151,7 @@ impl IntController for Gic {
 
         gicc.clear_irq(irq_number as u32, ic);
 
-        {
-            let table = self.table.lock();
-            match table[irq_number] {
-                None => panic!("No handler registered for irq{}", irq_number),
-                Some(handler) => {
-                    drop(table);
-                    handler.handle_irq().expect("irq handler failed")
-                }
-            }
-        }
+        self.table.handle(irq_number);
     }
 
     fn register_handler(
@@ -104,15 +159,9 @@ impl IntController for Gic {
         irq: Self::IrqNumber,
         handler: &'static (dyn IntSource + Sync),
     ) -> Result<(), Errno> {
-        let mut table = self.table.lock();
         let irq = irq.get();
-        if table[irq].is_some() {
-            return Err(Errno::AlreadyExists);
-        }
-
+        self.table.register(irq, handler)?;
         debugln!("Bound irq{} to {:?}", irq, Device::name(handler));
-        table[irq] = Some(handler);
-
         Ok(())
     }
 }
@@ -127,9 +176,25 @@ impl Gic {
         Self {
             gicc: InitOnce::new(),
             gicd: InitOnce::new(),
+            v2m: InitOnce::new(),
             gicd_base,
             gicc_base,
-            table: IrqSafeSpinLock::new([None; MAX_IRQ]),
+            v2m_base: None,
+            table: IrqTable::new(),
+            sgis: IrqAllocator::new(0, SGI_COUNT),
+            msis: InitOnce::new(),
         }
     }
+
+    /// Constructs an instance of GICv2 with a GICv2m frame at `v2m_base`
+    /// translating MSI writes into SPIs.
+    ///
+    /// # Safety
+    ///
+    /// Does not perform base address validation.
+    pub const unsafe fn with_v2m(gicd_base: usize, gicc_base: usize, v2m_base: usize) -> Self {
+        let mut gic = Self::new(gicd_base, gicc_base);
+        gic.v2m_base = Some(v2m_base);
+        gic
+    }
 }
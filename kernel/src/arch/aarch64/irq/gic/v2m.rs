@@ -0,0 +1,48 @@
+use crate::mem::virt::DeviceMemoryIo;
+use tock_registers::interfaces::Readable;
+use tock_registers::registers::ReadOnly;
+use tock_registers::{register_bitfields, register_structs};
+
+register_bitfields! {
+    u32,
+    TYPER [
+        Base OFFSET(16) NUMBITS(10) [],
+        Number OFFSET(0) NUMBITS(10) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    pub(super) V2mRegs {
+        (0x00 => _res0),
+        (0x08 => TYPER: ReadOnly<u32, TYPER::Register>),
+        (0x0C => @END),
+    }
+}
+
+/// Offset of the register MSIs are written to
+const SETSPI_NS: usize = 0x40;
+
+pub(super) struct V2m {
+    regs: DeviceMemoryIo<V2mRegs>,
+    base: usize,
+}
+
+impl V2m {
+    pub const unsafe fn new(regs: DeviceMemoryIo<V2mRegs>, base: usize) -> Self {
+        Self { regs, base }
+    }
+
+    /// Returns the first SPI and the number of SPIs reserved for MSIs
+    pub fn spi_range(&self) -> (usize, usize) {
+        (
+            self.regs.TYPER.read(TYPER::Base) as usize,
+            self.regs.TYPER.read(TYPER::Number) as usize,
+        )
+    }
+
+    /// Returns physical address of the doorbell register
+    pub fn doorbell(&self) -> usize {
+        self.base + SETSPI_NS
+    }
+}
@@ -1,3 +1,24 @@
 //! AArch64-specific IRQ handling functionality
 
 pub mod gic;
+
+/// Range-checked IRQ number type. The meaning of the number is defined by
+/// the interrupt controller of the machine.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IrqNumber(usize);
+
+impl IrqNumber {
+    /// Returns numeric representation for given [IrqNumber]
+    #[inline(always)]
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    /// Checks and wraps an IRQ number
+    #[inline(always)]
+    pub const fn new(v: usize) -> Self {
+        assert!(v < gic::MAX_IRQ);
+        Self(v)
+    }
+}
@@ -0,0 +1,73 @@
+use crate::dev::Device;
+use crate::mem::virt::DeviceMemoryIo;
+use crate::sync::IrqSafeSpinLock;
+use crate::util::InitOnce;
+use libsys::error::Errno;
+use tock_registers::{
+    interfaces::{ReadWriteable, Readable},
+    register_bitfields, register_structs,
+    registers::ReadWrite,
+};
+
+register_bitfields! {
+    u32,
+    UART_BGR [
+        /// Reset deassert, one bit per UART
+        RST OFFSET(16) NUMBITS(4) [],
+        /// Bus clock gating, one bit per UART
+        GATING OFFSET(0) NUMBITS(4) []
+    ]
+}
+
+register_structs! {
+    #[allow(non_snake_case)]
+    CcuRegs {
+        (0x000 => _res0),
+        (0x90C => UART_BGR: ReadWrite<u32, UART_BGR::Register>),
+        (0x910 => @END),
+    }
+}
+
+pub(super) struct Ccu {
+    inner: InitOnce<IrqSafeSpinLock<DeviceMemoryIo<CcuRegs>>>,
+    base: usize,
+}
+
+impl Device for Ccu {
+    fn name(&self) -> &'static str {
+        "Allwinner H6 CCU"
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        self.inner.init(IrqSafeSpinLock::new(DeviceMemoryIo::map(
+            self.name(),
+            self.base,
+            1,
+        )?));
+        Ok(())
+    }
+}
+
+impl Ccu {
+    /// Ungates the bus clock of UART `index` and takes it out of reset
+    pub fn enable_uart(&self, index: usize) {
+        let regs = self.inner.get().lock();
+        let bit = 1 << index;
+        let bgr = regs.UART_BGR.read(UART_BGR::GATING);
+        regs.UART_BGR.modify(UART_BGR::GATING.val(bgr | bit));
+        let rst = regs.UART_BGR.read(UART_BGR::RST);
+        regs.UART_BGR.modify(UART_BGR::RST.val(rst | bit));
+    }
+
+    /// Constructs an instance of CCU peripheral.
+    ///
+    /// # Safety
+    ///
+    /// Does not perform `base` validation.
+    pub const unsafe fn new(base: usize) -> Self {
+        Self {
+            inner: InitOnce::new(),
+            base,
+        }
+    }
+}
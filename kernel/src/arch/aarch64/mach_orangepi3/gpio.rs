@@ -5,14 +5,23 @@
 //! 1. CPUS-PORT (TODO PL, PM)
 //! 2. CPUX-PORT (PC, PD, PF, PG, PH)
 //!
+//! Pin functions are selected through [PinmuxDevice] by their names from
+//! the per-pin tables below. Current claims can be read as text lines of
+//! form `<pin> <function> <owner>` from `/dev/pinmux`.
 use crate::dev::{
-    gpio::{GpioDevice, PinConfig, PinMode, PullMode},
+    gpio::{
+        GpioDevice, PinClaim, PinClaims, PinConfig, PinFunction, PinMode, PinmuxDevice, PullMode,
+    },
     Device,
 };
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use libsys::error::Errno;
+use alloc::format;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use libsys::{error::Errno, ioctl::IoctlCmd};
+use vfs::CharDevice;
 use tock_registers::interfaces::{Readable, Writeable};
 use tock_registers::register_structs;
 use tock_registers::registers::ReadWrite;
@@ -35,13 +44,45 @@ struct CpuxGpio {
 pub struct Gpio {
     cpux: InitOnce<IrqSafeSpinLock<CpuxGpio>>,
     cpux_base: usize,
+    claims: IrqSafeSpinLock<PinClaims<PinAddress>>,
+    // Next line of /dev/pinmux to be read
+    cursor: AtomicUsize,
 }
 
 /// Structure combining bank and pin numbers
 #[repr(transparent)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PinAddress(u32);
 
+const fn func(name: &'static str, mux: u32) -> PinFunction {
+    PinFunction { name, mux }
+}
+
+/// Functions available on any CPUX-PORT pin
+static GPIO_FUNCTIONS: [PinFunction; 3] = [
+    func("gpio_in", 0),
+    func("gpio_out", 1),
+    func("disabled", 7),
+];
+
+/// Peripheral functions of CPUX-PORT pins: (bank, pin, functions)
+static PIN_FUNCTIONS: &[(u32, u32, &[PinFunction])] = &[
+    (3, 19, &[func("uart2_tx", 4)]),
+    (3, 20, &[func("uart2_rx", 4)]),
+    (3, 21, &[func("uart2_rts", 4)]),
+    (3, 22, &[func("uart2_cts", 4)]),
+    (3, 23, &[func("uart3_tx", 4)]),
+    (3, 24, &[func("uart3_rx", 4)]),
+    (3, 25, &[func("uart3_rts", 4), func("i2c0_scl", 5)]),
+    (3, 26, &[func("uart3_cts", 4), func("i2c0_sda", 5)]),
+    (6, 6, &[func("uart1_tx", 2)]),
+    (6, 7, &[func("uart1_rx", 2)]),
+    (6, 8, &[func("uart1_rts", 2)]),
+    (6, 9, &[func("uart1_cts", 2)]),
+    (7, 0, &[func("uart0_tx", 2)]),
+    (7, 1, &[func("uart0_rx", 2)]),
+];
+
 impl PinAddress {
     /// Constructs a new pin address from `bank` and `pin` numbers
     #[inline(always)]
@@ -63,6 +104,12 @@ impl PinAddress {
     }
 }
 
+impl fmt::Display for PinAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "P{}{}", (b'A' + self.bank() as u8) as char, self.pin())
+    }
+}
+
 impl CpuxPortRegs {
     #[inline]
     fn set_pin_cfg_inner(&self, pin: u32, cfg: u32) {
@@ -82,6 +129,11 @@ impl CpuxPortRegs {
 }
 
 impl CpuxGpio {
+    #[inline(always)]
+    fn set_pin_mux(&self, bank: usize, pin: u32, mux: u32) {
+        self.regs[bank].set_pin_cfg_inner(pin, mux);
+    }
+
     unsafe fn set_pin_config(&self, bank: usize, pin: u32, cfg: &PinConfig) -> Result<(), Errno> {
         let regs = &self.regs[bank];
 
@@ -199,16 +251,91 @@ impl GpioDevice for Gpio {
     }
 }
 
-impl Gpio {
-    pub unsafe fn cfg_uart0_ph0_ph1(&self) -> Result<(), Errno> {
-        self.set_pin_config(PinAddress::new(7, 0), &PinConfig::alt(2))?;
-        self.set_pin_config(PinAddress::new(7, 1), &PinConfig::alt(2))
+impl PinmuxDevice for Gpio {
+    fn pin_functions(&self, pin: PinAddress) -> &'static [PinFunction] {
+        PIN_FUNCTIONS
+            .iter()
+            .find(|&&(bank, index, _)| pin == PinAddress::new(bank, index))
+            .map_or(&[], |&(_, _, functions)| functions)
+    }
+
+    unsafe fn claim_pin(
+        &self,
+        pin: PinAddress,
+        function: &str,
+        owner: &'static str,
+    ) -> Result<(), Errno> {
+        let selected = GPIO_FUNCTIONS
+            .iter()
+            .chain(self.pin_functions(pin))
+            .find(|f| f.name == function)
+            .ok_or_else(|| {
+                warnln!("{}: no function {:?}", pin, function);
+                Errno::InvalidArgument
+            })?;
+
+        let mut claims = self.claims.lock();
+        if let Err(err) = claims.check(pin, owner) {
+            warnln!("{}: {} conflicts with existing claim", pin, owner);
+            return Err(err);
+        }
+
+        match pin.bank() {
+            0 | 1 | 4 => return Err(Errno::NotImplemented),
+            bank => self.cpux.get().lock().set_pin_mux(bank, pin.pin(), selected.mux),
+        }
+        claims.claim(
+            pin,
+            PinClaim {
+                function: selected.name,
+                owner,
+            },
+        )
+    }
+
+    fn release_pin(&self, pin: PinAddress, owner: &str) -> Result<(), Errno> {
+        self.claims.lock().release(pin, owner)
+    }
+}
+
+impl CharDevice for Gpio {
+    /// Reads a single line of the claim table. After the last line a
+    /// read returns 0 and the next one starts over.
+    fn read(&self, _blocking: bool, data: &mut [u8]) -> Result<usize, Errno> {
+        let index = self.cursor.fetch_add(1, Ordering::AcqRel);
+        let (pin, claim) = match self.claims.lock().nth(index) {
+            Some(entry) => entry,
+            None => {
+                self.cursor.store(0, Ordering::Release);
+                return Ok(0);
+            }
+        };
+        let line = format!("{} {} {}\n", pin, claim.function, claim.owner);
+        let count = core::cmp::min(line.len(), data.len());
+        data[..count].copy_from_slice(&line.as_bytes()[..count]);
+        Ok(count)
     }
 
+    fn write(&self, _blocking: bool, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::InvalidOperation)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Errno> {
+        Ok(!write)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _ptr: usize, _lim: usize) -> Result<usize, Errno> {
+        Err(Errno::InvalidArgument)
+    }
+}
+
+impl Gpio {
     pub const unsafe fn new(cpux_base: usize) -> Self {
         Self {
             cpux: InitOnce::new(),
             cpux_base,
+            claims: IrqSafeSpinLock::new(PinClaims::new()),
+            cursor: AtomicUsize::new(0),
         }
     }
 }
@@ -1,25 +1,32 @@
 //! Xunlong Orange Pi 3, with Allwinner H6 SoC
 
 use crate::arch::aarch64::{
+    board,
     irq::gic::{self, Gic},
+    machine::Machine,
     timer::GenericTimer,
 };
+use crate::debug;
 use crate::dev::{
-    gpio::{GpioDevice, PinConfig},
+    self,
+    fdt::DeviceTree,
+    gpio::{GpioDevice, PinConfig, PinMapEntry, PinmuxDevice},
+    led::{self, GpioLed, LedTrigger},
     irq::{IntController, IntSource},
     serial::SerialDevice,
     timer::TimestampSource,
-    Device,
 };
 use crate::fs::devfs::{self, CharDeviceType};
-use crate::mem::phys;
-use libsys::error::Errno;
+use alloc::format;
+use libsys::{error::Errno, stat::makedev};
 
+mod ccu;
 mod gpio;
 mod rtc;
 mod uart;
 mod wdog;
 
+use ccu::Ccu;
 pub use gic::IrqNumber;
 use gpio::Gpio;
 pub use gpio::PinAddress;
@@ -27,30 +34,44 @@ use rtc::Rtc;
 use uart::Uart;
 use wdog::RWdog;
 
-pub fn init_board_early() -> Result<(), Errno> {
+fn init_board_early() -> Result<(), Errno> {
     unsafe {
-        UART0.enable()?;
+        dev::enable(&UART0)?;
+        debug::enable_console();
 
-        phys::init_from_region(0x80000000, 0x10000000);
+        board::init_memory(PHYS_BASE, PHYS_SIZE);
     }
     Ok(())
 }
 
-pub fn init_board() -> Result<(), Errno> {
+fn init_board(_fdt: Option<&DeviceTree>) -> Result<(), Errno> {
     unsafe {
-        GIC.enable()?;
-        GPIO.enable()?;
+        dev::enable(&GIC)?;
+        dev::enable(&GPIO)?;
+        dev::enable(&CCU)?;
+
+        GPIO.apply_pin_map(PIN_MAP)?;
+        devfs::add_named_char_device(&GPIO, "pinmux", makedev(devfs::MAJOR_MISC, 1))?;
 
         UART0.init_irqs()?;
         devfs::add_char_device(&UART0, CharDeviceType::TtySerial)?;
 
-        R_WDOG.enable()?;
+        for (index, uart) in [&UART1, &UART2, &UART3].into_iter().enumerate() {
+            CCU.enable_uart(index + 1);
+            dev::enable(uart)?;
+            uart.init_irqs()?;
+            devfs::add_char_device(uart, CharDeviceType::TtySerial)?;
+            debug::attach_console(&format!("ttyS{}", index + 1), uart);
+        }
+
+        dev::enable(&R_WDOG)?;
 
-        GPIO.cfg_uart0_ph0_ph1()?;
-        GPIO.set_pin_config(PinAddress::new(3, 26), &PinConfig::out_pull_down())?;
+        GPIO.set_pin_config(STATUS_LED_PIN, &PinConfig::out_pull_down())?;
+        led::register("status", &STATUS_LED, LedTrigger::Heartbeat)?;
 
-        RTC.enable()?;
+        dev::enable(&RTC)?;
         RTC.init_irqs()?;
+        crate::dev::rtc::register(&RTC);
     }
     Ok(())
 }
@@ -60,39 +81,73 @@ pub fn init_board() -> Result<(), Errno> {
 /// # Safety
 ///
 /// Unsafe: may interrupt critical processes
-pub unsafe fn reset_board() -> ! {
+unsafe fn reset_board() -> ! {
     R_WDOG.reset_board()
 }
 
+const PHYS_BASE: usize = 0x80000000;
+const PHYS_SIZE: usize = 0x10000000;
 const LOCAL_TIMER_IRQ: IrqNumber = IrqNumber::new(30);
 const R_WDOG_BASE: usize = 0x07020400;
+const CCU_BASE: usize = 0x03001000;
+const STATUS_LED_PIN: PinAddress = PinAddress::new(3, 26);
 const UART0_BASE: usize = 0x05000000;
+const UART1_BASE: usize = 0x05000400;
+const UART2_BASE: usize = 0x05000800;
+const UART3_BASE: usize = 0x05000C00;
+/// APB2 clock, feeds all the UARTs
+const UART_CLOCK: u32 = 24000000;
 const RTC_BASE: usize = 0x07000000;
 const RTC_IRQ: IrqNumber = IrqNumber::new(133);
 const PIO_BASE: usize = 0x0300B000;
 const GICD_BASE: usize = 0x03021000;
 const GICC_BASE: usize = 0x03022000;
 
-/// Returns primary console for this machine
-#[inline]
-pub fn console() -> &'static impl SerialDevice {
+/// Pin functions used by the board. UART1 is wired to the Bluetooth
+/// module, UART2 and UART3 are on the 26-pin header.
+static PIN_MAP: &[PinMapEntry<PinAddress>] = &[
+    PinMapEntry::new(PinAddress::new(7, 0), "uart0_tx", "uart0"),
+    PinMapEntry::new(PinAddress::new(7, 1), "uart0_rx", "uart0"),
+    PinMapEntry::new(PinAddress::new(6, 6), "uart1_tx", "uart1"),
+    PinMapEntry::new(PinAddress::new(6, 7), "uart1_rx", "uart1"),
+    PinMapEntry::new(PinAddress::new(3, 19), "uart2_tx", "uart2"),
+    PinMapEntry::new(PinAddress::new(3, 20), "uart2_rx", "uart2"),
+    PinMapEntry::new(PinAddress::new(3, 23), "uart3_tx", "uart3"),
+    PinMapEntry::new(PinAddress::new(3, 24), "uart3_rx", "uart3"),
+    PinMapEntry::new(STATUS_LED_PIN, "gpio_out", "status_led"),
+];
+
+fn console() -> &'static dyn SerialDevice {
     &UART0
 }
 
-/// Returns the timer used as CPU-local periodic IRQ source
-#[inline]
-pub fn local_timer() -> &'static GenericTimer {
+fn local_timer() -> &'static GenericTimer {
     &LOCAL_TIMER
 }
 
-/// Returns CPU's interrupt controller device
-#[inline]
-pub fn intc() -> &'static impl IntController<IrqNumber = IrqNumber> {
+fn intc() -> &'static dyn IntController<IrqNumber = IrqNumber> {
     &GIC
 }
 
+/// Orange Pi 3 machine backend
+pub static MACHINE: Machine = Machine {
+    name: "Xunlong Orange Pi 3",
+    compatible: &["xunlong,orangepi-3"],
+    init_board_early,
+    init_board,
+    console,
+    local_timer,
+    intc,
+    reset_board: Some(reset_board),
+};
+
 static R_WDOG: RWdog = unsafe { RWdog::new(R_WDOG_BASE) };
-static UART0: Uart = unsafe { Uart::new(UART0_BASE, IrqNumber::new(32)) };
+static STATUS_LED: GpioLed<Gpio> = GpioLed::new(&GPIO, STATUS_LED_PIN, false);
+static CCU: Ccu = unsafe { Ccu::new(CCU_BASE) };
+static UART0: Uart = unsafe { Uart::new(UART0_BASE, IrqNumber::new(32), UART_CLOCK) };
+static UART1: Uart = unsafe { Uart::new(UART1_BASE, IrqNumber::new(33), UART_CLOCK) };
+static UART2: Uart = unsafe { Uart::new(UART2_BASE, IrqNumber::new(34), UART_CLOCK) };
+static UART3: Uart = unsafe { Uart::new(UART3_BASE, IrqNumber::new(35), UART_CLOCK) };
 static LOCAL_TIMER: GenericTimer = GenericTimer::new(LOCAL_TIMER_IRQ);
 pub(super) static GPIO: Gpio = unsafe { Gpio::new(PIO_BASE) };
 static RTC: Rtc = unsafe { Rtc::new(RTC_BASE, RTC_IRQ) };
@@ -7,7 +7,7 @@ use crate::dev::{
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use libsys::error::Errno;
+use libsys::{error::Errno, time::DateTime};
 use tock_registers::{
     interfaces::{Readable, Writeable},
     register_bitfields, register_structs,
@@ -24,9 +24,24 @@ register_bitfields! {
     ],
     ALARM0_IRQ_STA [
         ALARM0_IRQ_PEND OFFSET(0) NUMBITS(1) []
+    ],
+    RTC_YY_MM_DD [
+        DAY OFFSET(0) NUMBITS(5) [],
+        MONTH OFFSET(8) NUMBITS(4) [],
+        YEAR OFFSET(16) NUMBITS(6) [],
+        LEAP OFFSET(22) NUMBITS(1) []
+    ],
+    RTC_HH_MM_SS [
+        SECOND OFFSET(0) NUMBITS(6) [],
+        MINUTE OFFSET(8) NUMBITS(6) [],
+        HOUR OFFSET(16) NUMBITS(5) [],
+        WEEKDAY OFFSET(29) NUMBITS(3) []
     ]
 }
 
+// Year 0 of the counter, same as in Linux sun6i driver
+const YEAR_BASE: u32 = 1970;
+
 register_structs! {
     #[allow(non_snake_case)]
     Regs {
@@ -34,8 +49,8 @@ register_structs! {
         (0x04 => LOSC_AUTO_SWT_STA: ReadWrite<u32>),
         (0x08 => INTOSC_CLK_PRESCAL: ReadWrite<u32>),
         (0x0C => INTOSC_CLK_AUTO_CALI: ReadWrite<u32>),
-        (0x10 => RTC_YY_MM_DD: ReadWrite<u32>),
-        (0x14 => RTC_HH_MM_SS: ReadWrite<u32>),
+        (0x10 => RTC_YY_MM_DD: ReadWrite<u32, RTC_YY_MM_DD::Register>),
+        (0x14 => RTC_HH_MM_SS: ReadWrite<u32, RTC_HH_MM_SS::Register>),
         (0x18 => _res0),
         (0x20 => ALARM0_COUNTER: ReadWrite<u32>),
         (0x24 => ALARM0_CUR_VLU: ReadOnly<u32>),
@@ -66,7 +81,56 @@ impl Regs {
     }
 }
 
-impl RtcDevice for Rtc {}
+impl RtcDevice for Rtc {
+    fn read_time(&self) -> Result<u64, Errno> {
+        let regs = self.regs.get().lock();
+        let (date, time) = loop {
+            // Re-read if the date changed at midnight in between
+            let date = regs.RTC_YY_MM_DD.extract();
+            let time = regs.RTC_HH_MM_SS.extract();
+            if regs.RTC_YY_MM_DD.get() == date.get() {
+                break (date, time);
+            }
+        };
+
+        let value = DateTime {
+            year: YEAR_BASE + date.read(RTC_YY_MM_DD::YEAR),
+            month: date.read(RTC_YY_MM_DD::MONTH) as u8,
+            day: date.read(RTC_YY_MM_DD::DAY) as u8,
+            hour: time.read(RTC_HH_MM_SS::HOUR) as u8,
+            minute: time.read(RTC_HH_MM_SS::MINUTE) as u8,
+            second: time.read(RTC_HH_MM_SS::SECOND) as u8,
+            weekday: 0,
+        };
+        if !value.is_valid() {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok(value.timestamp())
+    }
+
+    fn set_time(&self, secs: u64) -> Result<(), Errno> {
+        let value = DateTime::from_timestamp(secs);
+        // Only 6 bits of year
+        if value.year >= YEAR_BASE + 64 {
+            return Err(Errno::InvalidArgument);
+        }
+
+        let regs = self.regs.get().lock();
+        regs.RTC_HH_MM_SS.write(
+            RTC_HH_MM_SS::SECOND.val(value.second as u32)
+                + RTC_HH_MM_SS::MINUTE.val(value.minute as u32)
+                + RTC_HH_MM_SS::HOUR.val(value.hour as u32)
+                + RTC_HH_MM_SS::WEEKDAY.val(value.weekday as u32),
+        );
+        regs.RTC_YY_MM_DD.write(
+            RTC_YY_MM_DD::DAY.val(value.day as u32)
+                + RTC_YY_MM_DD::MONTH.val(value.month as u32)
+                + RTC_YY_MM_DD::YEAR.val(value.year - YEAR_BASE)
+                + RTC_YY_MM_DD::LEAP.val(DateTime::is_leap_year(value.year) as u32),
+        );
+        Ok(())
+    }
+}
 
 impl IntSource for Rtc {
     fn handle_irq(&self) -> Result<(), Errno> {
@@ -1,14 +1,15 @@
+use crate::arch::aarch64::board::{self, Quirks};
 use crate::arch::machine::{self, IrqNumber};
 use crate::dev::{
     irq::{IntController, IntSource},
-    serial::SerialDevice,
+    serial::{Parity, SerialConfig, SerialDevice},
     tty::{CharRing, TtyDevice},
     Device,
 };
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use libsys::error::Errno;
+use libsys::{error::Errno, termios::ModemLines};
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 use tock_registers::registers::{Aliased, ReadOnly, ReadWrite};
 use tock_registers::{register_bitfields, register_structs};
@@ -39,6 +40,33 @@ register_bitfields! [
             CharacterTimeout = 12
         ]
     ],
+    LCR [
+        /// Divisor latch access
+        DLAB OFFSET(7) NUMBITS(1) [],
+        /// Break control
+        BC OFFSET(6) NUMBITS(1) [],
+        /// Even parity select
+        EPS OFFSET(4) NUMBITS(1) [],
+        /// Parity enable
+        PEN OFFSET(3) NUMBITS(1) [],
+        /// Two stop bits
+        STOP OFFSET(2) NUMBITS(1) [],
+        /// Data length minus 5
+        DLS OFFSET(0) NUMBITS(2) [],
+    ],
+    MCR [
+        RTS OFFSET(1) NUMBITS(1) [],
+        DTR OFFSET(0) NUMBITS(1) [],
+    ],
+    MSR [
+        DCD OFFSET(7) NUMBITS(1) [],
+        RI OFFSET(6) NUMBITS(1) [],
+        DSR OFFSET(5) NUMBITS(1) [],
+        CTS OFFSET(4) NUMBITS(1) [],
+    ],
+    USR [
+        BUSY OFFSET(0) NUMBITS(1) [],
+    ],
     LSR [
         FIFOERR OFFSET(7) NUMBITS(1) [],
         TEMT OFFSET(6) NUMBITS(1) [],
@@ -57,13 +85,13 @@ register_structs! {
         (0x0000 => DR_DLL: Aliased<u32>),
         (0x0004 => IER_DLH: ReadWrite<u32, IER::Register>),
         (0x0008 => IIR_FCR: Aliased<u32, IIR::Register, ()>),
-        (0x000C => LCR: ReadWrite<u32>),
-        (0x0010 => MCR: ReadWrite<u32>),
+        (0x000C => LCR: ReadWrite<u32, LCR::Register>),
+        (0x0010 => MCR: ReadWrite<u32, MCR::Register>),
         (0x0014 => LSR: ReadOnly<u32, LSR::Register>),
-        (0x0018 => MSR: ReadOnly<u32>),
+        (0x0018 => MSR: ReadOnly<u32, MSR::Register>),
         (0x001C => SCH: ReadWrite<u32>),
         (0x0020 => _res0),
-        (0x007C => USR: ReadOnly<u32>),
+        (0x007C => USR: ReadOnly<u32, USR::Register>),
         (0x0080 => TFL: ReadWrite<u32>),
         (0x0084 => RFL: ReadWrite<u32>),
         (0x0088 => HSK: ReadWrite<u32>),
@@ -73,8 +101,19 @@ register_structs! {
     }
 }
 
+/// Register values preserved across suspend
+#[derive(Clone, Copy, Default)]
+struct UartContext {
+    divisor: u32,
+    lcr: u32,
+    ier: u32,
+    mcr: u32,
+    fifo_enabled: bool,
+}
+
 struct UartInner {
     regs: DeviceMemoryIo<Regs>,
+    context: UartContext,
 }
 
 #[derive(TtyCharDevice)]
@@ -83,6 +122,69 @@ pub(super) struct Uart {
     ring: CharRing<16>,
     base: usize,
     irq: IrqNumber,
+    clock: u32,
+}
+
+impl UartInner {
+    fn set_config(&mut self, config: &SerialConfig, clock: u32) -> Result<(), Errno> {
+        let div = (clock + 8 * config.baud) / (16 * config.baud);
+        if div == 0 || div > 0xFFFF || !(5..=8).contains(&config.data_bits) {
+            return Err(Errno::InvalidArgument);
+        }
+
+        // LCR and divisor latches are only writable when the UART is idle
+        while self.regs.USR.matches_all(USR::BUSY::SET) {
+            core::hint::spin_loop();
+        }
+
+        self.regs.LCR.modify(LCR::DLAB::SET);
+        self.regs.DR_DLL.set(div & 0xFF);
+        self.regs.IER_DLH.set(div >> 8);
+        self.regs.LCR.modify(
+            LCR::DLAB::CLEAR
+                + LCR::DLS.val(config.data_bits as u32 - 5)
+                + LCR::STOP.val(config.two_stop_bits as u32)
+                + LCR::PEN.val((config.parity != Parity::None) as u32)
+                + LCR::EPS.val((config.parity == Parity::Even) as u32),
+        );
+        Ok(())
+    }
+
+    fn wait_idle(&self) {
+        while self.regs.USR.matches_all(USR::BUSY::SET) {
+            core::hint::spin_loop();
+        }
+    }
+
+    fn suspend(&mut self) {
+        self.wait_idle();
+        // Divisor latches share the addresses of DR and IER
+        self.regs.LCR.modify(LCR::DLAB::SET);
+        let divisor = (self.regs.DR_DLL.get() & 0xFF) | ((self.regs.IER_DLH.get() & 0xFF) << 8);
+        self.regs.LCR.modify(LCR::DLAB::CLEAR);
+
+        self.context = UartContext {
+            divisor,
+            lcr: self.regs.LCR.get(),
+            ier: self.regs.IER_DLH.get(),
+            mcr: self.regs.MCR.get(),
+            // FCR is write-only, its FIFO enable bit is mirrored in IIR
+            fifo_enabled: self.regs.IIR_FCR.matches_all(IIR::FEFLAG::Enable),
+        };
+    }
+
+    fn resume(&mut self) {
+        let context = self.context;
+        self.wait_idle();
+        self.regs.LCR.modify(LCR::DLAB::SET);
+        self.regs.DR_DLL.set(context.divisor & 0xFF);
+        self.regs.IER_DLH.set(context.divisor >> 8);
+        self.regs.LCR.set(context.lcr);
+
+        self.regs.IIR_FCR.set(context.fifo_enabled as u32);
+        self.regs.MCR.set(context.mcr);
+        self.regs.IER_DLH.set(context.ier);
+    }
 }
 
 impl Device for Uart {
@@ -93,20 +195,31 @@ impl Device for Uart {
     unsafe fn enable(&self) -> Result<(), Errno> {
         let mut inner = UartInner {
             regs: DeviceMemoryIo::map(self.name(), self.base, 1)?,
+            context: UartContext::default(),
         };
-        // TODO
+        // Only UART0 is set up by the bootloader
+        inner.set_config(&SerialConfig::DEFAULT, self.clock)?;
         self.inner.init(IrqSafeSpinLock::new(inner));
         Ok(())
     }
+
+    unsafe fn suspend(&self) -> Result<(), Errno> {
+        self.inner.try_get()?.lock().suspend();
+        Ok(())
+    }
+
+    unsafe fn resume(&self) -> Result<(), Errno> {
+        self.inner.try_get()?.lock().resume();
+        Ok(())
+    }
 }
 
 impl SerialDevice for Uart {
     fn send(&self, byte: u8) -> Result<(), Errno> {
-        if !self.inner.is_initialized() {
-            return Ok(());
-        }
-
-        let inner = self.inner.get().lock();
+        let inner = match self.inner.get_if_initialized() {
+            Some(inner) => inner.lock(),
+            None => return Ok(()),
+        };
         while !inner.regs.LSR.matches_all(LSR::THRE::SET) {
             cortex_a::asm::nop();
         }
@@ -115,12 +228,54 @@ impl SerialDevice for Uart {
     }
 
     fn recv(&self, _blocking: bool) -> Result<u8, Errno> {
-        let inner = self.inner.get().lock();
+        let inner = self.inner.try_get()?.lock();
         while !inner.regs.LSR.matches_all(LSR::DR::SET) {
             cortex_a::asm::nop();
         }
         Ok(inner.regs.DR_DLL.get() as u8)
     }
+
+    unsafe fn take_over(&self) {
+        if let Some(inner) = self.inner.get_if_initialized() {
+            inner.force_unlock();
+        }
+    }
+
+    fn set_config(&self, config: &SerialConfig) -> Result<(), Errno> {
+        self.inner.try_get()?.lock().set_config(config, self.clock)
+    }
+
+    fn modem_lines(&self) -> Result<ModemLines, Errno> {
+        let inner = self.inner.try_get()?.lock();
+        let mcr = inner.regs.MCR.extract();
+        let msr = inner.regs.MSR.extract();
+        let mut lines = ModemLines::empty();
+        lines.set(ModemLines::DTR, mcr.is_set(MCR::DTR));
+        lines.set(ModemLines::RTS, mcr.is_set(MCR::RTS));
+        lines.set(ModemLines::CTS, msr.is_set(MSR::CTS));
+        lines.set(ModemLines::DSR, msr.is_set(MSR::DSR));
+        lines.set(ModemLines::DCD, msr.is_set(MSR::DCD));
+        lines.set(ModemLines::RI, msr.is_set(MSR::RI));
+        Ok(lines)
+    }
+
+    fn set_modem_lines(&self, lines: ModemLines) -> Result<(), Errno> {
+        self.inner.get().lock().regs.MCR.modify(
+            MCR::DTR.val(lines.contains(ModemLines::DTR) as u32)
+                + MCR::RTS.val(lines.contains(ModemLines::RTS) as u32),
+        );
+        Ok(())
+    }
+
+    fn set_break(&self, enable: bool) -> Result<(), Errno> {
+        self.inner
+            .get()
+            .lock()
+            .regs
+            .LCR
+            .modify(LCR::BC.val(enable as u32));
+        Ok(())
+    }
 }
 
 impl TtyDevice<16> for Uart {
@@ -131,9 +286,20 @@ impl TtyDevice<16> for Uart {
 
 impl IntSource for Uart {
     fn handle_irq(&self) -> Result<(), Errno> {
-        let byte = self.inner.get().lock().regs.DR_DLL.get();
+        let inner = self.inner.get().lock();
+        let lsr = inner.regs.LSR.extract();
+        let byte = inner.regs.DR_DLL.get();
+        drop(inner);
+
+        if lsr.is_set(LSR::BI) {
+            self.recv_break();
+            return Ok(());
+        }
 
-        if byte == 0x1B {
+        if byte == 0x1B
+            && self.base == super::UART0_BASE
+            && board::has_quirk(Quirks::CONSOLE_ESC_RESET)
+        {
             debugln!("Received ESC, resetting");
             unsafe {
                 machine::reset_board();
@@ -154,12 +320,13 @@ impl IntSource for Uart {
 }
 
 impl Uart {
-    pub const unsafe fn new(base: usize, irq: IrqNumber) -> Self {
+    pub const unsafe fn new(base: usize, irq: IrqNumber, clock: u32) -> Self {
         Self {
             inner: InitOnce::new(),
             ring: CharRing::new(),
             base,
             irq,
+            clock,
         }
     }
 }
@@ -1,18 +1,24 @@
 //! QEMU virt machine
 
 use crate::arch::aarch64::{
+    board,
     irq::gic::{self, Gic},
+    machine::Machine,
     timer::GenericTimer,
 };
+use crate::debug;
 use crate::dev::{
+    self,
+    fdt::{self, DeviceTree},
     irq::{IntController, IntSource},
-    pci::pcie::gpex::GenericPcieHost,
-    rtc::pl031::Pl031,
+    pci::{pcie::gpex::GenericPcieHost, PciHostDevice},
+    rtc::{self, pl031::Pl031},
     serial::{pl011::Pl011, SerialDevice},
-    Device,
+    virtio,
 };
 use crate::fs::devfs::{self, CharDeviceType};
-use crate::mem::phys;
+use alloc::{boxed::Box, format};
+use fdt_rs::prelude::*;
 use libsys::error::Errno;
 
 pub use gic::IrqNumber;
@@ -21,63 +27,111 @@ pub use gic::IrqNumber;
 const LOCAL_TIMER_IRQ: IrqNumber = IrqNumber::new(30);
 const UART0_BASE: usize = 0x09000000;
 const UART0_IRQ: IrqNumber = IrqNumber::new(33);
+const UART0_CLOCK: u32 = 24000000;
 const RTC_BASE: usize = 0x09010000;
 const RTC_IRQ: IrqNumber = IrqNumber::new(34);
 const GICD_BASE: usize = 0x08000000;
 const GICC_BASE: usize = 0x08010000;
+const GICV2M_BASE: usize = 0x08020000;
 const ECAM_BASE: usize = 0x4010000000;
+const VIRTIO_MMIO_BASE: usize = 0x0A000000;
+const VIRTIO_MMIO_COUNT: usize = 32;
 
 const PHYS_BASE: usize = 0x40000000;
 const PHYS_SIZE: usize = 0x10000000;
 
 /// Performs early board initialization (debug output and physical memory)
-pub fn init_board_early() -> Result<(), Errno> {
+fn init_board_early() -> Result<(), Errno> {
     unsafe {
         // Enable UART early on
-        UART0.enable()?;
+        dev::enable(&UART0)?;
+        debug::enable_console();
 
-        phys::init_from_region(PHYS_BASE, PHYS_SIZE);
+        board::init_memory(PHYS_BASE, PHYS_SIZE);
+    }
+    Ok(())
+}
+
+/// Probes PL011 UARTs other than UART0 described in the device tree
+fn probe_uarts(fdt: &DeviceTree) -> Result<(), Errno> {
+    let mut index = 1;
+    for node in fdt.compatible_nodes("arm,pl011") {
+        let base = match fdt::reg_base(node.clone()) {
+            Some(base) if base != UART0_BASE => base,
+            _ => continue,
+        };
+        // <type number flags>, type 0 is SPI
+        let irq = match fdt::find_prop(node, "interrupts").map(|p| (p.u32(0), p.u32(1))) {
+            Some((Ok(0), Ok(spi))) => IrqNumber::new(spi + 32),
+            _ => {
+                warnln!("PL011 at {:#x} has no usable interrupt, skipping", base);
+                continue;
+            }
+        };
+
+        let uart: &'static Pl011 =
+            Box::leak(Box::new(unsafe { Pl011::new(base, irq, UART0_CLOCK) }));
+        unsafe {
+            dev::enable(uart)?;
+        }
+        uart.init_irqs()?;
+        devfs::add_char_device(uart, CharDeviceType::TtySerial)?;
+        debug::attach_console(&format!("ttyS{}", index), uart);
+        index += 1;
     }
     Ok(())
 }
 
 /// Performs board hardware init
-pub fn init_board() -> Result<(), Errno> {
+fn init_board(fdt: Option<&DeviceTree>) -> Result<(), Errno> {
     unsafe {
-        GIC.enable()?;
+        dev::enable(&GIC)?;
 
         UART0.init_irqs()?;
         devfs::add_char_device(&UART0, CharDeviceType::TtySerial)?;
+    }
+    if let Some(fdt) = fdt {
+        probe_uarts(fdt)?;
+    }
+    unsafe {
 
-        RTC.enable()?;
+        dev::enable(&RTC)?;
         RTC.init_irqs()?;
+        rtc::register(&RTC);
 
-        PCIE.enable()?;
-        // PCIE.map()?;
+        dev::enable(&PCIE)?;
+        PCIE.map()?;
     }
+    virtio::probe_mmio(VIRTIO_MMIO_BASE, VIRTIO_MMIO_COUNT)?;
     Ok(())
 }
 
-/// Returns primary console for this machine
-#[inline]
-pub fn console() -> &'static impl SerialDevice {
+fn console() -> &'static dyn SerialDevice {
     &UART0
 }
 
-/// Returns the timer used as CPU-local periodic IRQ source
-#[inline]
-pub fn local_timer() -> &'static GenericTimer {
+fn local_timer() -> &'static GenericTimer {
     &LOCAL_TIMER
 }
 
-/// Returns CPU's interrupt controller device
-#[inline]
-pub fn intc() -> &'static impl IntController<IrqNumber = IrqNumber> {
+fn intc() -> &'static dyn IntController<IrqNumber = IrqNumber> {
     &GIC
 }
 
-static UART0: Pl011 = unsafe { Pl011::new(UART0_BASE, UART0_IRQ) };
+/// QEMU virt machine backend
+pub static MACHINE: Machine = Machine {
+    name: "QEMU virt",
+    compatible: &["linux,dummy-virt"],
+    init_board_early,
+    init_board,
+    console,
+    local_timer,
+    intc,
+    reset_board: None,
+};
+
+static UART0: Pl011 = unsafe { Pl011::new(UART0_BASE, UART0_IRQ, UART0_CLOCK) };
 static RTC: Pl031 = unsafe { Pl031::new(RTC_BASE, RTC_IRQ) };
-static GIC: Gic = unsafe { Gic::new(GICD_BASE, GICC_BASE) };
+static GIC: Gic = unsafe { Gic::with_v2m(GICD_BASE, GICC_BASE, GICV2M_BASE) };
 static PCIE: GenericPcieHost = unsafe { GenericPcieHost::new(ECAM_BASE, 8) };
 static LOCAL_TIMER: GenericTimer = GenericTimer::new(LOCAL_TIMER_IRQ);
@@ -1,9 +1,9 @@
-use crate::arch::machine::{self, Bcm283xMailbox};
+use super::{Bcm283xMailbox, BCM_MBOX};
 use crate::dev::sd::{
     SdCardIdentification, SdCardStatus, SdCommand, SdCommandNumber, SdCommandTransfer,
     SdHostController, SdResponse, SdResponseType,
 };
-use crate::dev::Device;
+use crate::dev::{led, Device};
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
@@ -11,7 +11,7 @@ use libsys::error::Errno;
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 use tock_registers::registers::{ReadOnly, ReadWrite};
 use tock_registers::{register_bitfields, register_structs};
-use vfs::BlockDevice;
+use vfs::{BlockDevice, BlockError, BlockErrorCounters};
 
 register_bitfields! {
     u32,
@@ -129,6 +129,19 @@ struct MmcInner {
 pub struct MassMediaController {
     inner: InitOnce<IrqSafeSpinLock<MmcInner>>,
     base: usize,
+    counters: BlockErrorCounters,
+}
+
+/// Converts error bits of INTERRUPT register to an error code:
+/// [Errno::TimedOut] for timeouts, [Errno::DeviceError] for CRC errors
+fn status_error(irq_status: u32) -> Errno {
+    if INTERRUPT::CTO_ERR.is_set(irq_status) || INTERRUPT::DTO_ERR.is_set(irq_status) {
+        Errno::TimedOut
+    } else if INTERRUPT::CCRC_ERR.is_set(irq_status) || INTERRUPT::DCRC_ERR.is_set(irq_status) {
+        Errno::DeviceError
+    } else {
+        Errno::InvalidArgument
+    }
 }
 
 fn clock_divider(f_base: u32, f_target: u32) -> u32 {
@@ -171,14 +184,14 @@ fn clock_divider(f_base: u32, f_target: u32) -> u32 {
 
 impl MmcInner {
     fn power_on(&mut self) -> Result<(), Errno> {
-        machine::BCM_MBOX.set_power_state(
+        BCM_MBOX.set_power_state(
             Bcm283xMailbox::POWER_SD_CARD,
             Bcm283xMailbox::POWER_STATE_ON | Bcm283xMailbox::POWER_STATE_WAIT,
         )
     }
 
     fn base_clock(&mut self) -> Result<u32, Errno> {
-        machine::BCM_MBOX.clock_rate(Bcm283xMailbox::CLOCK_EMMC)
+        BCM_MBOX.clock_rate(Bcm283xMailbox::CLOCK_EMMC)
     }
 
     // TODO generalize flag setting
@@ -251,7 +264,7 @@ impl MmcInner {
 
         if irq_status & 0xFFFF0000 != 0 {
             warnln!("SD error: irq_status={:#x}", irq_status);
-            return Err(Errno::InvalidArgument);
+            return Err(status_error(irq_status));
         }
         if !INTERRUPT::CMD_DONE.is_set(irq_status) {
             warnln!("SD command did not report properly");
@@ -287,7 +300,7 @@ impl MmcInner {
 
                     if irq_status & 0xFFFF0000 != 0 {
                         warnln!("SD error during data read: irq_status={:#x}", irq_status);
-                        return Err(Errno::InvalidArgument);
+                        return Err(status_error(irq_status));
                     }
                     if !INTERRUPT::READ_RDY.is_set(irq_status) {
                         warnln!("SD did not respond with data blocks");
@@ -394,26 +407,76 @@ impl MmcInner {
 }
 
 impl BlockDevice for MassMediaController {
-    fn read(&self, pos: usize, data: &mut [u8]) -> Result<(), Errno> {
-        // TODO check card status
+    fn read(&self, pos: usize, data: &mut [u8]) -> Result<(), BlockError> {
         if data.len() % 512 != 0 || pos % 512 != 0 {
             todo!()
         }
+        if !self.is_phys_inserted() {
+            // Card was either removed or is not initialized yet after insertion
+            if !self.is_media_present() {
+                return Err(BlockError::MediaGone);
+            }
+            self.reset_card()?;
+            if !self.is_phys_inserted() {
+                return Err(BlockError::MediaGone);
+            }
+        }
 
+        led::mmc_activity();
         for i in 0..(data.len() / 512) {
             let s = i * 512;
             self.send_cmd(&mut SdCommand {
                 number: SdCommandNumber::Cmd17,
                 argument: (pos / 512 + i) as u32,
                 transfer: SdCommandTransfer::Read(&mut data[s..(s + 512)], 512),
+            })
+            .map_err(|e| match e {
+                Errno::TimedOut => BlockError::Timeout,
+                Errno::DeviceError => BlockError::Crc,
+                e => BlockError::Other(e),
             })?;
         }
         Ok(())
     }
 
-    fn write(&self, _pos: usize, _data: &[u8]) -> Result<(), Errno> {
+    fn write(&self, _pos: usize, _data: &[u8]) -> Result<(), BlockError> {
         todo!()
     }
+
+    fn size(&self) -> Result<usize, Errno> {
+        let inner = self.inner.get().lock();
+        match inner.status.id {
+            Some(ref id) => Ok(id.capacity as usize),
+            // Not known until the card is initialized
+            None => Err(Errno::DoesNotExist),
+        }
+    }
+
+    fn is_removable(&self) -> bool {
+        true
+    }
+
+    fn is_media_present(&self) -> bool {
+        self.inner
+            .get()
+            .lock()
+            .regs
+            .STATUS
+            .matches_all(STATUS::MISC_INSERTED::SET)
+    }
+
+    fn media_changed(&self, present: bool) {
+        if !present {
+            // Drop the state of the removed card, it will be
+            // re-initialized on next access if a card is inserted again
+            let mut inner = self.inner.get().lock();
+            inner.status = SdCardStatus::invalid();
+        }
+    }
+
+    fn error_counters(&self) -> Option<&BlockErrorCounters> {
+        Some(&self.counters)
+    }
 }
 
 impl SdHostController for MassMediaController {
@@ -471,6 +534,7 @@ impl MassMediaController {
         Self {
             inner: InitOnce::new(),
             base,
+            counters: BlockErrorCounters::new(),
         }
     }
 }
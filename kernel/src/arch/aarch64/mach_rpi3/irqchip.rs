@@ -1,11 +1,10 @@
 use crate::dev::{
-    irq::{IntController, IntSource, IrqContext},
+    irq::{IntController, IntSource, IrqContext, IrqTable},
     Device,
 };
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use core::fmt;
 use cortex_a::registers::MPIDR_EL1;
 use libsys::error::Errno;
 
@@ -24,7 +23,10 @@ register_structs! {
         (0x210 => ENABLE1: ReadWrite<u32>),
         (0x214 => ENABLE2: ReadWrite<u32>),
         (0x218 => ENABLE_BASIC: ReadWrite<u32>),
-        (0x21C => @END),
+        (0x21C => DISABLE1: ReadWrite<u32>),
+        (0x220 => DISABLE2: ReadWrite<u32>),
+        (0x224 => DISABLE_BASIC: ReadWrite<u32>),
+        (0x228 => @END),
     }
 }
 
@@ -39,48 +41,36 @@ register_structs! {
     }
 }
 
-#[derive(Clone, Copy)]
-#[repr(transparent)]
-pub struct IrqNumber(u32);
+pub use crate::arch::aarch64::irq::IrqNumber;
 
-impl IrqNumber {
-    pub const MAX: u32 = 64 + 32;
+/// Number of IRQ lines: QA7 local ones followed by BCM peripheral ones
+const MAX_IRQ: usize = 64 + 32;
 
+impl IrqNumber {
     pub const fn bcm_irq(n: u32) -> Self {
         assert!(n < 64);
-        Self(n + 32)
+        Self::new(n as usize + 32)
     }
 
     pub const fn qa7_irq(n: u32) -> Self {
         assert!(n < 32);
-        Self(n)
+        Self::new(n as usize)
     }
 
     pub const fn is_bcm_irq(self) -> bool {
-        self.0 >= 32
+        self.get() >= 32
     }
 
     pub const fn number(self) -> u32 {
         if self.is_bcm_irq() {
-            self.0 - 32
+            self.get() as u32 - 32
         } else {
-            self.0
+            self.get() as u32
         }
     }
 
     pub const fn index(self) -> usize {
-        self.0 as usize
-    }
-}
-
-impl fmt::Debug for IrqNumber {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}_irq{}",
-            if self.is_bcm_irq() { "bcm" } else { "qa7" },
-            self.number()
-        )
+        self.get()
     }
 }
 
@@ -94,7 +84,7 @@ struct Qa7IrqchipInner {
 pub struct Bcm283xIrqchip {
     bcm_inner: InitOnce<IrqSafeSpinLock<BcmIrqchipInner>>,
     qa7_inner: InitOnce<IrqSafeSpinLock<Qa7IrqchipInner>>,
-    table: IrqSafeSpinLock<[Option<&'static (dyn IntSource + Sync)>; IrqNumber::MAX as usize]>,
+    table: IrqTable<MAX_IRQ>,
 }
 
 impl BcmIrqchipInner {
@@ -110,6 +100,18 @@ impl BcmIrqchipInner {
         Ok(())
     }
 
+    fn disable_irq(&self, n: u32) -> Result<(), Errno> {
+        let (reg, bit) = if n < 32 {
+            (&self.regs.DISABLE1, 1 << n)
+        } else if n < 64 {
+            (&self.regs.DISABLE2, 1 << (n - 32))
+        } else {
+            return Err(Errno::InvalidArgument);
+        };
+        reg.set(bit);
+        Ok(())
+    }
+
     fn pending_irq(&self) -> Option<u32> {
         let status = self.regs.PENDING2.get();
         for bit in 0..32 {
@@ -135,6 +137,17 @@ impl Qa7IrqchipInner {
         Ok(())
     }
 
+    fn disable_irq(&self, n: u32) -> Result<(), Errno> {
+        let core_id = MPIDR_EL1.get() & 0x3;
+        let (reg, bit) = if n < 4 {
+            (&self.regs.CORE_IRQ_EN[core_id as usize], 1 << n)
+        } else {
+            return Err(Errno::InvalidArgument);
+        };
+        reg.set(reg.get() & !bit);
+        Ok(())
+    }
+
     fn pending_irq(&self) -> Option<u32> {
         let core_id = MPIDR_EL1.get() & 0x3;
         let reg = &self.regs.CORE_IRQ_SRC[core_id as usize];
@@ -178,15 +191,13 @@ impl IntController for Bcm283xIrqchip {
         irq: IrqNumber,
         handler: &'static (dyn IntSource + Sync),
     ) -> Result<(), Errno> {
-        let mut table = self.table.lock();
-        let irqi = irq.index();
-        if table[irqi as usize].is_some() {
-            return Err(Errno::AlreadyExists);
-        }
-
-        debugln!("Bound {:?} to {:?}", irq, Device::name(handler));
-        table[irqi as usize] = Some(handler);
-
+        self.table.register(irq.index(), handler)?;
+        debugln!(
+            "Bound {}_irq{} to {:?}",
+            if irq.is_bcm_irq() { "bcm" } else { "qa7" },
+            irq.number(),
+            Device::name(handler)
+        );
         Ok(())
     }
 
@@ -198,6 +209,14 @@ impl IntController for Bcm283xIrqchip {
         }
     }
 
+    fn disable_irq(&self, irq: IrqNumber) -> Result<(), Errno> {
+        if irq.is_bcm_irq() {
+            self.bcm_inner.get().lock().disable_irq(irq.number())
+        } else {
+            self.qa7_inner.get().lock().disable_irq(irq.number())
+        }
+    }
+
     fn handle_pending_irqs<'q>(&'q self, _ic: &IrqContext<'q>) {
         let qa7 = self.qa7_inner.get().lock();
         let bcm = self.bcm_inner.get().lock();
@@ -217,16 +236,7 @@ impl IntController for Bcm283xIrqchip {
             drop(qa7);
         }
 
-        {
-            let table = self.table.lock();
-            match table[irq_number] {
-                None => panic!("No handler registered for irq{}", irq_number),
-                Some(handler) => {
-                    drop(table);
-                    handler.handle_irq().expect("irq handler failed")
-                }
-            }
-        }
+        self.table.handle(irq_number);
     }
 }
 
@@ -235,7 +245,7 @@ impl Bcm283xIrqchip {
         Self {
             bcm_inner: InitOnce::new(),
             qa7_inner: InitOnce::new(),
-            table: IrqSafeSpinLock::new([None; IrqNumber::MAX as usize]),
+            table: IrqTable::new(),
         }
     }
 }
@@ -1,11 +1,13 @@
-use crate::arch::aarch64::timer::GenericTimer;
+use crate::arch::aarch64::{board, machine::Machine, timer::GenericTimer};
+use crate::debug;
 use crate::dev::{
-    irq::IntSource,
+    self,
+    fdt::DeviceTree,
+    irq::{IntController, IntSource},
     serial::{pl011::Pl011, SerialDevice},
-    Device,
 };
-use crate::mem::phys;
-use libsys::error::Errno;
+use crate::fs::devfs;
+use libsys::{error::Errno, stat::makedev};
 
 pub mod irqchip;
 pub use irqchip::{Bcm283xIrqchip, IrqNumber};
@@ -18,50 +20,60 @@ const UART_BASE: usize = 0x3F201000;
 const EMMC_BASE: usize = 0x3F300000;
 const BCM_MBOX_BASE: usize = 0x3F00B880;
 const UART_IRQ: IrqNumber = IrqNumber::bcm_irq(57);
+const UART_CLOCK: u32 = 48000000;
 const LOCAL_TIMER_IRQ: IrqNumber = IrqNumber::qa7_irq(1);
 
-pub fn init_board_early() -> Result<(), Errno> {
+fn init_board_early() -> Result<(), Errno> {
     unsafe {
-        UART.enable()?;
-        BCM_MBOX.enable()?;
+        dev::enable(&UART)?;
+        debug::enable_console();
+        dev::enable(&BCM_MBOX)?;
 
         let memory = BCM_MBOX.memory_split()?;
         infoln!("Memory split: {:#x}", memory);
 
-        phys::init_from_region(0, memory);
+        board::init_memory(0, memory);
     }
     Ok(())
 }
 
-pub fn init_board() -> Result<(), Errno> {
+fn init_board(_fdt: Option<&DeviceTree>) -> Result<(), Errno> {
     unsafe {
-        IRQCHIP.enable()?;
+        dev::enable(&IRQCHIP)?;
         UART.init_irqs()?;
 
-        EMMC.enable()?;
+        dev::enable(&EMMC)?;
+        devfs::add_named_block_device(&EMMC, "mmcblk0", makedev(devfs::MAJOR_MMC, 0))?;
     }
     Ok(())
 }
 
-#[inline]
-pub fn intc() -> &'static Bcm283xIrqchip {
+fn intc() -> &'static dyn IntController<IrqNumber = IrqNumber> {
     &IRQCHIP
 }
 
-/// Returns primary console for this machine
-#[inline]
-pub fn console() -> &'static impl SerialDevice {
+fn console() -> &'static dyn SerialDevice {
     &UART
 }
 
-/// Returns the timer used as CPU-local periodic IRQ source
-#[inline]
-pub fn local_timer() -> &'static GenericTimer {
+fn local_timer() -> &'static GenericTimer {
     &LOCAL_TIMER
 }
 
+/// Raspberry Pi 3 machine backend
+pub static MACHINE: Machine = Machine {
+    name: "Raspberry Pi 3",
+    compatible: &["raspberrypi,3-model-b", "raspberrypi,3-model-b-plus"],
+    init_board_early,
+    init_board,
+    console,
+    local_timer,
+    intc,
+    reset_board: None,
+};
+
 static IRQCHIP: Bcm283xIrqchip = Bcm283xIrqchip::new();
 pub static EMMC: MassMediaController = unsafe { MassMediaController::new(EMMC_BASE) };
-static UART: Pl011 = unsafe { Pl011::new(UART_BASE, UART_IRQ) };
+static UART: Pl011 = unsafe { Pl011::new(UART_BASE, UART_IRQ, UART_CLOCK) };
 pub(self) static BCM_MBOX: Bcm283xMailbox = unsafe { Bcm283xMailbox::new(BCM_MBOX_BASE) };
 static LOCAL_TIMER: GenericTimer = GenericTimer::new(LOCAL_TIMER_IRQ);
@@ -0,0 +1,121 @@
+//! Machine selection and dispatch.
+//!
+//! Any number of `mach_*` backends can be compiled in, each describing
+//! itself with a [Machine] table. The one matching the device tree's root
+//! "compatible" strings is selected at boot, the functions of this module
+//! forward to it.
+use crate::arch::aarch64::timer::GenericTimer;
+use crate::dev::{
+    fdt::{find_prop, DeviceTree},
+    irq::IntController,
+    serial::SerialDevice,
+};
+use crate::util::InitOnce;
+use fdt_rs::prelude::*;
+use libsys::error::Errno;
+
+pub use crate::arch::aarch64::irq::IrqNumber;
+
+/// Machine backend operations
+pub struct Machine {
+    /// Human-readable machine name
+    pub name: &'static str,
+    /// Root node "compatible" strings the backend supports
+    pub compatible: &'static [&'static str],
+    /// Performs early board initialization (debug output and physical
+    /// memory)
+    pub init_board_early: fn() -> Result<(), Errno>,
+    /// Performs board hardware init
+    pub init_board: fn(Option<&DeviceTree>) -> Result<(), Errno>,
+    /// Returns primary console
+    pub console: fn() -> &'static dyn SerialDevice,
+    /// Returns the timer used as CPU-local periodic IRQ source
+    pub local_timer: fn() -> &'static GenericTimer,
+    /// Returns CPU's interrupt controller device
+    pub intc: fn() -> &'static dyn IntController<IrqNumber = IrqNumber>,
+    /// Performs board reset, if supported
+    pub reset_board: Option<unsafe fn() -> !>,
+}
+
+static MACHINES: &[&Machine] = &[
+    #[cfg(feature = "mach_qemu")]
+    &super::mach_qemu::MACHINE,
+    #[cfg(feature = "mach_orangepi3")]
+    &super::mach_orangepi3::MACHINE,
+    #[cfg(feature = "mach_rpi3")]
+    &super::mach_rpi3::MACHINE,
+];
+
+static CURRENT: InitOnce<&'static Machine> = InitOnce::new();
+
+fn find(fdt: &DeviceTree) -> Option<&'static Machine> {
+    let root = fdt.node_by_path("/")?;
+    let compatible = find_prop(root, "compatible")?;
+    let mut names = compatible.iter_str();
+    while let Ok(Some(name)) = names.next() {
+        if let Some(&mach) = MACHINES.iter().find(|m| m.compatible.contains(&name)) {
+            return Some(mach);
+        }
+    }
+    None
+}
+
+/// Selects the machine backend for the board described by `fdt`. With
+/// a single backend compiled in, it is used when the board is not
+/// recognized.
+pub fn select(fdt: Option<&DeviceTree>) -> Result<(), Errno> {
+    let mach = match fdt.and_then(find) {
+        Some(mach) => mach,
+        None if MACHINES.len() == 1 => MACHINES[0],
+        None => return Err(Errno::DoesNotExist),
+    };
+    CURRENT.init(mach);
+    infoln!("Machine: {}", mach.name);
+    Ok(())
+}
+
+/// Returns the selected machine backend
+#[inline]
+pub fn current() -> &'static Machine {
+    *CURRENT.get()
+}
+
+/// Performs early board initialization (debug output and physical memory)
+pub fn init_board_early() -> Result<(), Errno> {
+    (current().init_board_early)()
+}
+
+/// Performs board hardware init
+pub fn init_board(fdt: Option<&DeviceTree>) -> Result<(), Errno> {
+    (current().init_board)(fdt)
+}
+
+/// Returns primary console for this machine, if one has been selected
+#[inline]
+pub fn console() -> Option<&'static dyn SerialDevice> {
+    CURRENT.get_if_initialized().map(|machine| (machine.console)())
+}
+
+/// Returns the timer used as CPU-local periodic IRQ source
+#[inline]
+pub fn local_timer() -> &'static GenericTimer {
+    (current().local_timer)()
+}
+
+/// Returns CPU's interrupt controller device
+#[inline]
+pub fn intc() -> &'static dyn IntController<IrqNumber = IrqNumber> {
+    (current().intc)()
+}
+
+/// Performs board reset
+///
+/// # Safety
+///
+/// Unsafe: may interrupt critical processes
+pub unsafe fn reset_board() -> ! {
+    match current().reset_board {
+        Some(reset) => reset(),
+        None => panic!("{}: board reset is not supported", current().name),
+    }
+}
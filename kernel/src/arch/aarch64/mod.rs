@@ -3,6 +3,7 @@
 use cortex_a::registers::DAIF;
 use tock_registers::interfaces::{Readable, Writeable};
 
+pub mod board;
 pub mod boot;
 pub mod context;
 pub mod exception;
@@ -10,21 +11,14 @@ pub mod irq;
 pub mod reg;
 pub mod timer;
 
-cfg_if! {
-    if #[cfg(feature = "mach_qemu")] {
-        pub mod mach_qemu;
+pub mod machine;
 
-        pub use mach_qemu as machine;
-    } else if #[cfg(feature = "mach_orangepi3")] {
-        pub mod mach_orangepi3;
-
-        pub use mach_orangepi3 as machine;
-    } else if #[cfg(feature = "mach_rpi3")] {
-        pub mod mach_rpi3;
-
-        pub use mach_rpi3 as machine;
-    }
-}
+#[cfg(feature = "mach_qemu")]
+pub mod mach_qemu;
+#[cfg(feature = "mach_orangepi3")]
+pub mod mach_orangepi3;
+#[cfg(feature = "mach_rpi3")]
+pub mod mach_rpi3;
 
 /// Masks IRQs and returns previous IRQ mask state
 ///
@@ -3,48 +3,64 @@
 use crate::arch::machine::{self, IrqNumber};
 use crate::proc;
 use crate::dev::{
-    pseudo,
+    kmsg, pseudo,
     irq::{IntController, IntSource},
-    timer::TimestampSource,
+    timer::{OneShotTimer, TimestampSource},
     Device,
 };
+use core::sync::atomic::{AtomicU64, Ordering};
 use core::time::Duration;
-use cortex_a::registers::{CNTFRQ_EL0, CNTPCT_EL0, CNTP_CTL_EL0, CNTP_TVAL_EL0};
+use cortex_a::registers::{CNTFRQ_EL0, CNTPCT_EL0, CNTP_CTL_EL0, CNTP_CVAL_EL0};
 use libsys::error::Errno;
 use tock_registers::interfaces::{Readable, Writeable};
 
-/// Generic timer struct
+/// Generic timer struct. The timer is used in one-shot mode, programmed
+/// for the nearest scheduling or wait deadline.
 pub struct GenericTimer {
     irq: IrqNumber,
+    saved_ctl: AtomicU64,
+    saved_cval: AtomicU64,
 }
 
-/// Duration of a single timer period
-pub const TIMER_TICK: u64 = 1000000;
-
 impl Device for GenericTimer {
     fn name(&self) -> &'static str {
         "ARM Generic Timer"
     }
 
     unsafe fn enable(&self) -> Result<(), Errno> {
-        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET);
+        // Stays masked until a deadline is armed
+        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::SET);
+        Ok(())
+    }
+
+    unsafe fn suspend(&self) -> Result<(), Errno> {
+        self.saved_ctl.store(CNTP_CTL_EL0.get(), Ordering::Relaxed);
+        self.saved_cval
+            .store(CNTP_CVAL_EL0.get(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    unsafe fn resume(&self) -> Result<(), Errno> {
+        // A deadline which has passed while suspended fires right away
+        CNTP_CVAL_EL0.set(self.saved_cval.load(Ordering::Relaxed));
+        CNTP_CTL_EL0.set(self.saved_ctl.load(Ordering::Relaxed));
         Ok(())
     }
 }
 
 impl IntSource for GenericTimer {
     fn handle_irq(&self) -> Result<(), Errno> {
-        CNTP_TVAL_EL0.set(TIMER_TICK);
-        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET);
         proc::wait::tick();
-        proc::switch();
+        kmsg::KMSG.notify();
+        // Has to be armed before switching away from the current thread
+        self.set_deadline(proc::wait::next_deadline(self.timestamp()?))?;
         pseudo::RANDOM.set_state(CNTPCT_EL0.get() as u32);
+        proc::switch();
         Ok(())
     }
 
     fn init_irqs(&'static self) -> Result<(), Errno> {
         machine::intc().register_handler(self.irq, self)?;
-        CNTP_TVAL_EL0.set(TIMER_TICK);
         machine::intc().enable_irq(self.irq)?;
         Ok(())
     }
@@ -60,9 +76,35 @@ impl TimestampSource for GenericTimer {
     }
 }
 
+impl OneShotTimer for GenericTimer {
+    fn set_deadline(&self, deadline: Option<Duration>) -> Result<(), Errno> {
+        if let Some(deadline) = deadline {
+            let frq = CNTFRQ_EL0.get() as u128;
+            CNTP_CVAL_EL0.set((deadline.as_nanos() * frq / 1_000_000_000) as u64);
+            CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET);
+        } else {
+            CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::SET);
+        }
+        Ok(())
+    }
+
+    fn deadline(&self) -> Option<Duration> {
+        if CNTP_CTL_EL0.is_set(CNTP_CTL_EL0::IMASK) {
+            return None;
+        }
+        let cnt = (CNTP_CVAL_EL0.get() as u128) * 1_000_000_000u128;
+        let frq = CNTFRQ_EL0.get() as u128;
+        Some(Duration::from_nanos((cnt / frq) as u64))
+    }
+}
+
 impl GenericTimer {
     /// Constructs a new instance of ARM Generic Timer
     pub const fn new(irq: IrqNumber) -> Self {
-        Self { irq }
+        Self {
+            irq,
+            saved_ctl: AtomicU64::new(0),
+            saved_cval: AtomicU64::new(0),
+        }
     }
 }
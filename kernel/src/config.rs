@@ -6,8 +6,12 @@ use core::fmt;
 #[derive(Debug)]
 pub struct Config {
     cmdline: ConfigString<256>,
-    console: ConfigString<16>,
+    console: ConfigString<64>,
+    root: ConfigString<64>,
+    root_fs_type: ConfigString<16>,
+    panic: ConfigString<8>,
     mem_limit: usize,
+    sysrq: usize,
     initrd_base: usize,
     initrd_size: usize,
 }
@@ -18,7 +22,11 @@ pub struct Config {
 pub enum ConfigKey {
     Cmdline,
     Console,
+    Root,
+    RootFsType,
+    Panic,
     MemLimit,
+    SysRq,
     InitrdBase,
     InitrdSize,
 }
@@ -36,7 +44,11 @@ impl const Default for Config {
         Self {
             cmdline: ConfigString::empty(),
             console: ConfigString::empty(),
+            root: ConfigString::empty(),
+            root_fs_type: ConfigString::empty(),
+            panic: ConfigString::empty(),
             mem_limit: usize::MAX,
+            sysrq: 0,
             initrd_base: 0,
             initrd_size: 0,
         }
@@ -50,6 +62,7 @@ impl Config {
             ConfigKey::InitrdBase => self.initrd_base = value,
             ConfigKey::InitrdSize => self.initrd_size = value,
             ConfigKey::MemLimit => self.mem_limit = value,
+            ConfigKey::SysRq => self.sysrq = value,
             _ => panic!("Invalid usize key: {:?}", key),
         }
     }
@@ -58,6 +71,10 @@ impl Config {
     pub fn set_str(&mut self, key: ConfigKey, value: &str) {
         match key {
             ConfigKey::Cmdline => self.cmdline.set_from_str(value),
+            ConfigKey::Console => self.console.set_from_str(value),
+            ConfigKey::Root => self.root.set_from_str(value),
+            ConfigKey::RootFsType => self.root_fs_type.set_from_str(value),
+            ConfigKey::Panic => self.panic.set_from_str(value),
             _ => panic!("Invalid str key: {:?}", key),
         }
     }
@@ -68,6 +85,7 @@ impl Config {
             ConfigKey::InitrdBase => self.initrd_base,
             ConfigKey::InitrdSize => self.initrd_size,
             ConfigKey::MemLimit => self.mem_limit,
+            ConfigKey::SysRq => self.sysrq,
             _ => panic!("Invalid usize key: {:?}", key),
         }
     }
@@ -77,14 +95,29 @@ impl Config {
         match key {
             ConfigKey::Cmdline => self.cmdline.as_str(),
             ConfigKey::Console => self.console.as_str(),
+            ConfigKey::Root => self.root.as_str(),
+            ConfigKey::RootFsType => self.root_fs_type.as_str(),
+            ConfigKey::Panic => self.panic.as_str(),
             _ => panic!("Invalid str key: {:?}", key),
         }
     }
 
     /// Parses command line options provided to the kernel and
     /// sets appropriate config keys
-    pub fn set_cmdline(&self, _cmdline: &str) {
-        // TODO
+    pub fn set_cmdline(&mut self, cmdline: &str) {
+        self.cmdline.set_from_str(cmdline);
+        for option in cmdline.split_whitespace() {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "console" => self.console.set_from_str(value),
+                "root" => self.root.set_from_str(value),
+                "rootfstype" => self.root_fs_type.set_from_str(value),
+                "panic" => self.panic.set_from_str(value),
+                // Either "sysrq" or "sysrq=1" enables the commands
+                "sysrq" => self.sysrq = (value != "0") as usize,
+                _ => (),
+            }
+        }
     }
 }
 
@@ -101,9 +134,13 @@ impl<const N: usize> ConfigString<N> {
     }
 
     pub fn set_from_str(&mut self, data: &str) {
-        let bytes = data.as_bytes();
-        self.buf[..bytes.len()].copy_from_slice(bytes);
-        self.len = bytes.len();
+        // Truncate at a character boundary
+        let mut len = core::cmp::min(data.len(), N);
+        while !data.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buf[..len].copy_from_slice(&data.as_bytes()[..len]);
+        self.len = len;
     }
 }
 
@@ -10,13 +10,38 @@
 //! * [infoln!]
 //! * [warnln!]
 //! * [errorln!]
+//!
+//! Until the console is enabled by the board, output goes to a fixed-size
+//! early buffer, which is replayed to the console once it's usable.
+//!
+//! Output is routed to the sinks listed by `console=` kernel parameter:
+//! a comma-separated list of `<name>[:<level>]` (e.g. `ttyS0:info,fb0`),
+//! `both` for `ttyS0,fb0` or `none`. Messages below a sink's level are not
+//! sent to it. Without the parameter, everything goes to `ttyS0`. Sink
+//! devices are attached with [attach_console] as they come up.
+//!
+//! Messages are not sent to the sinks by the code logging them. They are
+//! appended to an output buffer, which takes the lock (and masks IRQs) only
+//! for copying them in. Whoever finds the output idle becomes its drainer:
+//! it swaps the two output buffers and sends the filled one to the sinks
+//! with IRQs enabled, repeating until no more output is pending.
+//!
+//! Printing never waits for a lock, so it is safe from IRQ and exception
+//! handlers which may have interrupted another print. If the output buffer
+//! is locked by the interrupted context, the message goes to a lock-free
+//! emergency buffer instead, which the drainer sends out first. If the
+//! sink list or the output buffer is locked when draining, the output is
+//! left pending for the next message.
 
 use crate::dev::serial::SerialDevice;
+use crate::sync::IrqSafeSpinLock;
 use libsys::debug::TraceLevel;
+use core::cell::UnsafeCell;
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 /// Kernel logging levels
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
 pub enum Level {
     /// Debugging information
     Debug,
@@ -40,11 +65,40 @@ impl From<TraceLevel> for Level {
     }
 }
 
-struct SerialOutput<T: 'static + SerialDevice> {
+impl Level {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Debug,
+            1 => Self::Info,
+            2 => Self::Warn,
+            _ => Self::Error,
+        }
+    }
+
+    fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ConsoleSink {
+    name: [u8; 8],
+    name_len: usize,
+    level: Level,
+    device: Option<&'static dyn SerialDevice>,
+}
+
+struct SerialOutput<T: 'static + SerialDevice + ?Sized> {
     inner: &'static T,
 }
 
-impl<T: SerialDevice> fmt::Write for SerialOutput<T> {
+impl<T: SerialDevice + ?Sized> fmt::Write for SerialOutput<T> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for &byte in s.as_bytes() {
             if byte == b'\n' {
@@ -57,6 +111,322 @@ impl<T: SerialDevice> fmt::Write for SerialOutput<T> {
     }
 }
 
+// Records of the active buffer are only written with OUTPUT_STATE held,
+// the other buffer is only read by the drainer
+struct OutputBuffers {
+    data: UnsafeCell<[[u8; OUTPUT_BUFFER_SIZE]; 2]>,
+}
+
+struct OutputState {
+    /// Index of the buffer messages are appended to
+    active: usize,
+    len: usize,
+    /// Bytes of messages which did not fit
+    dropped: usize,
+}
+
+/// Formats a message into output records of at most [RECORD_MAX] bytes
+struct RecordWriter {
+    level: Level,
+    buf: [u8; RECORD_MAX],
+    len: usize,
+}
+
+// Lock-free: writers reserve their space with a single atomic add
+struct EarlyBuffer {
+    data: UnsafeCell<[u8; EARLY_BUFFER_SIZE]>,
+    pos: AtomicUsize,
+}
+
+struct EarlyOutput;
+
+// Lock-free as well, records are pushed by reserving their space. The
+// drainer only takes the data once every reservation has been filled in.
+struct EmergencyBuffer {
+    data: UnsafeCell<[u8; EMERGENCY_BUFFER_SIZE]>,
+    reserved: AtomicUsize,
+    committed: AtomicUsize,
+    /// Bytes of messages which did not fit
+    lost: AtomicUsize,
+}
+
+/// Size of the buffer for output produced before the console is enabled
+const EARLY_BUFFER_SIZE: usize = 16384;
+/// Size of each of the two console output buffers
+const OUTPUT_BUFFER_SIZE: usize = 4096;
+/// Size of the buffer for messages logged while the output buffer is locked
+const EMERGENCY_BUFFER_SIZE: usize = 2048;
+/// Maximum length of an output record's text, longer messages are split
+const RECORD_MAX: usize = 255;
+
+static EARLY_BUFFER: EarlyBuffer = EarlyBuffer {
+    data: UnsafeCell::new([0; EARLY_BUFFER_SIZE]),
+    pos: AtomicUsize::new(0),
+};
+static CONSOLE_ENABLED: AtomicBool = AtomicBool::new(false);
+static EARLY_REPLAYED: AtomicBool = AtomicBool::new(false);
+
+static OUTPUT: OutputBuffers = OutputBuffers {
+    data: UnsafeCell::new([[0; OUTPUT_BUFFER_SIZE]; 2]),
+};
+static OUTPUT_STATE: IrqSafeSpinLock<OutputState> = IrqSafeSpinLock::new(OutputState {
+    active: 0,
+    len: 0,
+    dropped: 0,
+});
+static EMERGENCY: EmergencyBuffer = EmergencyBuffer {
+    data: UnsafeCell::new([0; EMERGENCY_BUFFER_SIZE]),
+    reserved: AtomicUsize::new(0),
+    committed: AtomicUsize::new(0),
+    lost: AtomicUsize::new(0),
+};
+static DRAINING: AtomicBool = AtomicBool::new(false);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Maximum number of console sinks
+const MAX_SINKS: usize = 4;
+
+static SINKS: IrqSafeSpinLock<[Option<ConsoleSink>; MAX_SINKS]> =
+    IrqSafeSpinLock::new([Some(ConsoleSink::new(b"ttyS0", Level::Debug)), None, None, None]);
+
+unsafe impl Sync for EarlyBuffer {}
+unsafe impl Sync for OutputBuffers {}
+unsafe impl Sync for EmergencyBuffer {}
+
+impl ConsoleSink {
+    const fn new(name: &[u8], level: Level) -> Self {
+        let mut buf = [0; 8];
+        let mut i = 0;
+        while i < name.len() && i < buf.len() {
+            buf[i] = name[i];
+            i += 1;
+        }
+        Self {
+            name: buf,
+            name_len: i,
+            level,
+            device: None,
+        }
+    }
+
+    fn name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+}
+
+impl fmt::Write for EarlyOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = EARLY_BUFFER.pos.fetch_add(s.len(), Ordering::AcqRel);
+        if start < EARLY_BUFFER_SIZE {
+            let count = core::cmp::min(s.len(), EARLY_BUFFER_SIZE - start);
+            let data = unsafe { &mut *EARLY_BUFFER.data.get() };
+            data[start..start + count].copy_from_slice(&s.as_bytes()[..count]);
+        }
+        Ok(())
+    }
+}
+
+impl EmergencyBuffer {
+    fn push(&self, level: Level, text: &[u8]) {
+        let size = text.len() + 2;
+        let mut start = self.reserved.load(Ordering::Acquire);
+        loop {
+            if start + size > EMERGENCY_BUFFER_SIZE {
+                self.lost.fetch_add(text.len(), Ordering::AcqRel);
+                return;
+            }
+            match self.reserved.compare_exchange_weak(
+                start,
+                start + size,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => start = current,
+            }
+        }
+        let data = unsafe { &mut *self.data.get() };
+        data[start] = level as u8;
+        data[start + 1] = text.len() as u8;
+        data[start + 2..start + size].copy_from_slice(text);
+        self.committed.fetch_add(size, Ordering::AcqRel);
+    }
+
+    /// Returns `true` if there are records ready to be sent
+    fn is_ready(&self) -> bool {
+        let reserved = self.reserved.load(Ordering::Acquire);
+        reserved != 0 && self.committed.load(Ordering::Acquire) == reserved
+    }
+
+    /// Sends the records to `sinks` and empties the buffer. Does nothing if
+    /// a record is still being written: its writer drains it afterwards.
+    fn drain(&self, sinks: &[Option<ConsoleSink>]) {
+        let mut sent = 0;
+        loop {
+            let reserved = self.reserved.load(Ordering::Acquire);
+            if reserved == 0 || self.committed.load(Ordering::Acquire) != reserved {
+                break;
+            }
+            let data = unsafe { &(*self.data.get())[sent..reserved] };
+            send_records(sinks, data);
+            sent = reserved;
+            // Fails if more records have been pushed in the meantime
+            if self
+                .reserved
+                .compare_exchange(reserved, 0, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                self.committed.fetch_sub(reserved, Ordering::AcqRel);
+                break;
+            }
+        }
+
+        let lost = self.lost.swap(0, Ordering::AcqRel);
+        if lost != 0 {
+            report_lost(sinks, lost);
+        }
+    }
+}
+
+impl RecordWriter {
+    const fn new(level: Level) -> Self {
+        Self {
+            level,
+            buf: [0; RECORD_MAX],
+            len: 0,
+        }
+    }
+
+    /// Appends the record to the active output buffer
+    fn commit(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+        let mut state = match OUTPUT_STATE.lock_nowait() {
+            Some(state) => state,
+            // Held by the context this one has interrupted
+            None => {
+                EMERGENCY.push(self.level, &self.buf[..self.len]);
+                self.len = 0;
+                return;
+            }
+        };
+        let start = state.len;
+        if start + self.len + 2 > OUTPUT_BUFFER_SIZE {
+            state.dropped += self.len;
+        } else {
+            let data = unsafe { &mut (*OUTPUT.data.get())[state.active] };
+            data[start] = self.level as u8;
+            data[start + 1] = self.len as u8;
+            data[start + 2..start + 2 + self.len].copy_from_slice(&self.buf[..self.len]);
+            state.len += self.len + 2;
+        }
+        self.len = 0;
+    }
+}
+
+impl fmt::Write for RecordWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut bytes = s.as_bytes();
+        while !bytes.is_empty() {
+            if self.len == RECORD_MAX {
+                self.commit();
+            }
+            let count = core::cmp::min(bytes.len(), RECORD_MAX - self.len);
+            self.buf[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+            self.len += count;
+            bytes = &bytes[count..];
+        }
+        Ok(())
+    }
+}
+
+fn send_bytes(device: &dyn SerialDevice, data: &[u8]) {
+    for &byte in data {
+        if byte == b'\n' {
+            device.send(b'\r').ok();
+        }
+        device.send(byte).ok();
+    }
+}
+
+/// Sends a sequence of output records to the sinks selecting their level
+fn send_records(sinks: &[Option<ConsoleSink>], data: &[u8]) {
+    let mut pos = 0;
+    while pos < data.len() {
+        let level = Level::from_u8(data[pos]);
+        let text = &data[pos + 2..pos + 2 + data[pos + 1] as usize];
+        for sink in sinks.iter().flatten() {
+            if let Some(device) = sink.device {
+                if level >= sink.level {
+                    send_bytes(device, text);
+                }
+            }
+        }
+        pos += text.len() + 2;
+    }
+}
+
+fn report_lost(sinks: &[Option<ConsoleSink>], count: usize) {
+    use fmt::Write;
+
+    for device in sinks.iter().flatten().filter_map(|sink| sink.device) {
+        let mut output = SerialOutput { inner: device };
+        writeln!(output, "\n... {} bytes of console output lost", count).ok();
+    }
+}
+
+/// Sends pending output to the sinks until there is none left. Returns
+/// right away if another context is already doing so, unless `force` is
+/// set.
+fn drain(force: bool) {
+    if DRAINING.swap(true, Ordering::AcqRel) && !force {
+        return;
+    }
+
+    loop {
+        // Either lock may be held by the context this one has interrupted,
+        // waiting for it would never end. The output is left pending.
+        let sinks = match SINKS.lock_nowait() {
+            Some(sinks) => *sinks,
+            None => {
+                DRAINING.store(false, Ordering::Release);
+                return;
+            }
+        };
+        EMERGENCY.drain(&sinks);
+
+        let (index, len, dropped) = {
+            let mut state = match OUTPUT_STATE.lock_nowait() {
+                Some(state) => state,
+                None => {
+                    DRAINING.store(false, Ordering::Release);
+                    return;
+                }
+            };
+            if state.len == 0 {
+                if EMERGENCY.is_ready() {
+                    continue;
+                }
+                // Cleared with the lock held, so that a writer either sees
+                // the flag cleared or has its record picked up here
+                DRAINING.store(false, Ordering::Release);
+                return;
+            }
+            let index = state.active;
+            state.active ^= 1;
+            let len = core::mem::replace(&mut state.len, 0);
+            (index, len, core::mem::replace(&mut state.dropped, 0))
+        };
+
+        let data = unsafe { &(*OUTPUT.data.get())[index][..len] };
+        send_records(&sinks, data);
+        if dropped != 0 {
+            report_lost(&sinks, dropped);
+        }
+    }
+}
+
 /// Writes a formatted message to output stream
 #[macro_export]
 macro_rules! print {
@@ -113,14 +483,133 @@ macro_rules! errorln {
     )
 }
 
-#[doc(hidden)]
-pub fn _debug(_level: Level, args: fmt::Arguments) {
+fn replay_early(console: &'static dyn SerialDevice) {
+    use fmt::Write;
+
+    let pos = EARLY_BUFFER.pos.load(Ordering::Acquire);
+    let len = core::cmp::min(pos, EARLY_BUFFER_SIZE);
+    let data = unsafe { &(*EARLY_BUFFER.data.get())[..len] };
+    send_bytes(console, data);
+    if pos > EARLY_BUFFER_SIZE {
+        let mut output = SerialOutput { inner: console };
+        writeln!(output, "\n... {} bytes of early output lost", pos - EARLY_BUFFER_SIZE).ok();
+    }
+}
+
+/// Sets up console sinks from `console=` kernel parameter value. Sinks
+/// attached before are detached.
+pub fn configure_console(spec: &str) {
+    let mut sinks = [None; MAX_SINKS];
+    let mut count = 0;
+    let mut add = |name: &str, level: Level| {
+        if count == MAX_SINKS || name.is_empty() || name.len() > 8 {
+            return false;
+        }
+        sinks[count] = Some(ConsoleSink::new(name.as_bytes(), level));
+        count += 1;
+        true
+    };
+
+    let mut valid = true;
+    match spec {
+        "" => valid = add("ttyS0", Level::Debug),
+        "none" => (),
+        "both" => valid = add("ttyS0", Level::Debug) && add("fb0", Level::Debug),
+        _ => {
+            for item in spec.split(',') {
+                let (name, level) = match item.split_once(':') {
+                    Some((name, level)) => (name, Level::from_str(level)),
+                    None => (item, Some(Level::Debug)),
+                };
+                valid &= level.map_or(false, |level| add(name, level));
+            }
+        }
+    }
+
+    *SINKS.lock() = sinks;
+    if !valid {
+        warnln!("Ignoring invalid console= entries in {:?}", spec);
+    }
+}
+
+/// Attaches `device` as console sink `name` if it's selected by
+/// `console=`. The output logged before the first sink was attached is
+/// replayed to it.
+pub fn attach_console(name: &str, device: &'static dyn SerialDevice) {
+    let mut sinks = SINKS.lock();
+    let sink = match sinks.iter_mut().flatten().find(|s| s.name() == name.as_bytes()) {
+        Some(sink) => sink,
+        None => return,
+    };
+    sink.device = Some(device);
+    replay_early(device);
+    EARLY_REPLAYED.store(true, Ordering::Release);
+    CONSOLE_ENABLED.store(true, Ordering::Release);
+}
+
+/// Marks the machine's console as usable, attaching it as `ttyS0`
+pub fn enable_console() {
+    use crate::arch::machine;
+
+    if let Some(console) = machine::console() {
+        attach_console("ttyS0", console);
+    }
+}
+
+/// Replays the early output to the machine's console if it has not been
+/// replayed to any sink. Used to report failures during early init.
+pub fn flush_early() {
     use crate::arch::machine;
+
+    if EARLY_REPLAYED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    if let Some(console) = machine::console() {
+        replay_early(console);
+    }
+}
+
+/// Takes over the console for the panic handler: locks held by the
+/// interrupted context are broken, pending output is sent and from now on
+/// every message is sent out before returning. Returns the machine's
+/// console, if there is one.
+///
+/// # Safety
+///
+/// Unsafe: only to be called with IRQs masked, when no other context will
+/// run again
+pub unsafe fn take_over() -> Option<&'static dyn SerialDevice> {
+    use crate::arch::machine;
+
+    PANICKING.store(true, Ordering::Release);
+    SINKS.force_unlock();
+    OUTPUT_STATE.force_unlock();
+    for device in SINKS.lock().iter().flatten().filter_map(|sink| sink.device) {
+        device.take_over();
+    }
+
+    let console = machine::console()?;
+    console.take_over();
+    if !CONSOLE_ENABLED.load(Ordering::Acquire) {
+        enable_console();
+    }
+    drain(true);
+    Some(console)
+}
+
+#[doc(hidden)]
+pub fn _debug(level: Level, args: fmt::Arguments) {
     use fmt::Write;
 
-    SerialOutput {
-        inner: machine::console(),
+    crate::dev::kmsg::KMSG.log(level, args);
+
+    if !CONSOLE_ENABLED.load(Ordering::Acquire) {
+        EarlyOutput.write_fmt(args).ok();
+        return;
     }
-    .write_fmt(args)
-    .ok();
+
+    let mut writer = RecordWriter::new(level);
+    writer.write_fmt(args).ok();
+    writer.commit();
+    drain(PANICKING.load(Ordering::Acquire));
 }
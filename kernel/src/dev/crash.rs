@@ -0,0 +1,94 @@
+//! Process crash notifications for userspace.
+//!
+//! When a process is killed by a fault, a [CrashRecord] describing it is
+//! queued to the `/dev/crash` character device. Each read returns a single
+//! record, blocking reads wait for the next crash.
+use crate::dev::Device;
+use crate::proc::wait::{Wait, WAIT_SELECT};
+use crate::sync::IrqSafeSpinLock;
+use alloc::collections::LinkedList;
+use core::mem::size_of;
+use libsys::{error::Errno, ioctl::IoctlCmd, proc::CrashRecord};
+use vfs::CharDevice;
+
+/// Character device for reading crash records
+pub struct CrashDevice {
+    queue: IrqSafeSpinLock<LinkedList<CrashRecord>>,
+    wait: Wait,
+}
+
+/// Maximum number of undelivered records, older ones are discarded
+const QUEUE_LIMIT: usize = 8;
+
+impl CrashDevice {
+    const fn new() -> Self {
+        Self {
+            queue: IrqSafeSpinLock::new(LinkedList::new()),
+            wait: Wait::new("crash"),
+        }
+    }
+
+    fn push(&self, record: CrashRecord) {
+        {
+            let mut queue = self.queue.lock();
+            if queue.len() == QUEUE_LIMIT {
+                queue.pop_front();
+            }
+            queue.push_back(record);
+        }
+        self.wait.wakeup_all();
+        WAIT_SELECT.wakeup_all();
+    }
+}
+
+impl Device for CrashDevice {
+    fn name(&self) -> &'static str {
+        "Crash notifier"
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+impl CharDevice for CrashDevice {
+    /// Reads a single record. `data` must be large enough to hold it.
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Errno> {
+        const SIZE: usize = size_of::<CrashRecord>();
+        if data.len() < SIZE {
+            return Err(Errno::InvalidArgument);
+        }
+        loop {
+            if let Some(record) = self.queue.lock().pop_front() {
+                let bytes =
+                    unsafe { core::slice::from_raw_parts(&record as *const _ as *const u8, SIZE) };
+                data[..SIZE].copy_from_slice(bytes);
+                return Ok(SIZE);
+            }
+            if !blocking {
+                return Err(Errno::WouldBlock);
+            }
+            self.wait.wait(None)?;
+        }
+    }
+
+    fn write(&self, _blocking: bool, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::InvalidOperation)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Errno> {
+        Ok(!write && !self.queue.lock().is_empty())
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _ptr: usize, _lim: usize) -> Result<usize, Errno> {
+        Err(Errno::InvalidArgument)
+    }
+}
+
+/// Queues `record` to be read by userspace
+pub fn report(record: CrashRecord) {
+    CRASH.push(record);
+}
+
+/// Crash notifier instance
+pub static CRASH: CrashDevice = CrashDevice::new();
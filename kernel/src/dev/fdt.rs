@@ -94,15 +94,75 @@ pub fn find_prop<'a>(at: INode<'a>, name: &str) -> Option<IProp<'a>> {
     at.props().find(|p| p.name().unwrap() == name)
 }
 
-// fn read_cells(prop: &IProp, off: usize, cells: u32) -> Option<u64> {
-//     Some(match cells {
-//         1 => prop.u32(off).ok()? as u64,
-//         2 => (prop.u32(off).ok()? as u64) | ((prop.u32(off + 1).ok()? as u64) << 32),
-//         _ => todo!(),
-//     })
-// }
+/// Reads a one- or two-cell property as a single number
+pub fn prop_usize(prop: &IProp) -> Option<usize> {
+    match prop.length() {
+        4 => Some(prop.u32(0).ok()? as usize),
+        8 => Some(((prop.u32(0).ok()? as usize) << 32) | prop.u32(1).ok()? as usize),
+        _ => None,
+    }
+}
+
+fn read_cells(prop: &IProp, off: usize, cells: u32) -> Option<usize> {
+    match cells {
+        1 => Some(prop.u32(off).ok()? as usize),
+        2 => Some(((prop.u32(off).ok()? as usize) << 32) | prop.u32(off + 1).ok()? as usize),
+        _ => None,
+    }
+}
+
+fn parent_cells(at: &INode, name: &str, default: u32) -> Option<u32> {
+    at.parent()
+        .and_then(|parent| find_prop(parent, name))
+        .map_or(Some(default), |prop| prop.u32(0).ok())
+}
+
+/// Returns base address and size of the node's first `reg` entry
+pub fn reg_region(at: INode) -> Option<(usize, usize)> {
+    let address_cells = parent_cells(&at, "#address-cells", 2)?;
+    let size_cells = parent_cells(&at, "#size-cells", 1)?;
+    let reg = find_prop(at, "reg")?;
+    let base = read_cells(&reg, 0, address_cells)?;
+    let size = read_cells(&reg, address_cells as usize, size_cells)?;
+    Some((base, size))
+}
+
+/// Returns the base address of the node's first `reg` entry
+pub fn reg_base(at: INode) -> Option<usize> {
+    let cells = parent_cells(&at, "#address-cells", 2)?;
+    let reg = find_prop(at, "reg")?;
+    read_cells(&reg, 0, cells)
+}
 
 impl DeviceTree {
+    /// Returns an iterator over the nodes listing `compatible` in their
+    /// "compatible" property
+    pub fn compatible_nodes<'a>(&'a self, compatible: &'a str) -> impl Iterator<Item = INode<'a>> {
+        self.index
+            .compatible_nodes(compatible)
+            .map(|node| -> INode<'a> { node })
+    }
+
+    /// Returns the size of the device tree blob
+    pub fn size(&self) -> usize {
+        self.tree.totalsize()
+    }
+
+    /// Returns base and size of the memory regions the device tree marks
+    /// as reserved: /memreserve/ entries and /reserved-memory children
+    pub fn reserved_regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let entries = self
+            .tree
+            .reserved_entries()
+            .map(|e| (u64::from(e.address) as usize, u64::from(e.size) as usize));
+        let nodes = self
+            .node_by_path("/reserved-memory")
+            .into_iter()
+            .flat_map(|node| node.children())
+            .filter_map(reg_region);
+        entries.chain(nodes)
+    }
+
     /// Dumps contents of the device tree
     pub fn dump(&self, level: Level) {
         dump_node(level, &self.index.root(), 0);
@@ -1,6 +1,7 @@
 //! GPIO and pin control interfaces
 
 use crate::dev::Device;
+use alloc::collections::BTreeMap;
 use libsys::error::Errno;
 
 /// Pin function mode
@@ -60,6 +61,135 @@ pub trait GpioDevice: Device {
     fn read_pin(&self, pin: Self::PinAddress) -> Result<bool, Errno>;
 }
 
+/// Named function a pin can be switched to
+#[derive(Clone, Copy, Debug)]
+pub struct PinFunction {
+    /// Signal name, like "uart0_tx"
+    pub name: &'static str,
+    /// Controller-specific multiplexer value
+    pub mux: u32,
+}
+
+/// Board pin map entry: `pin` is switched to `function` for `owner`
+pub struct PinMapEntry<P> {
+    /// Pin to configure
+    pub pin: P,
+    /// Name of the function to select
+    pub function: &'static str,
+    /// Device using the pin
+    pub owner: &'static str,
+}
+
+/// Function selected for a pin and the device it was selected for
+#[derive(Clone, Copy, Debug)]
+pub struct PinClaim {
+    /// Name of the selected function
+    pub function: &'static str,
+    /// Device using the pin
+    pub owner: &'static str,
+}
+
+/// Book-keeping of pins claimed by devices
+pub struct PinClaims<P> {
+    claims: BTreeMap<P, PinClaim>,
+}
+
+/// Pin multiplexing interface of a GPIO controller
+pub trait PinmuxDevice: GpioDevice {
+    /// Returns peripheral functions `pin` can be switched to
+    fn pin_functions(&self, pin: Self::PinAddress) -> &'static [PinFunction];
+
+    /// Switches `pin` to `function` on behalf of `owner`.
+    ///
+    /// Fails with [Errno::Busy] if the pin is already used by another
+    /// owner.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: changes physical pin configuration
+    unsafe fn claim_pin(
+        &self,
+        pin: Self::PinAddress,
+        function: &str,
+        owner: &'static str,
+    ) -> Result<(), Errno>;
+
+    /// Releases `owner`'s claim of `pin`, leaving its configuration as is
+    fn release_pin(&self, pin: Self::PinAddress, owner: &str) -> Result<(), Errno>;
+
+    /// Claims every pin of a board pin map
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: changes physical pin configuration
+    unsafe fn apply_pin_map(&self, map: &[PinMapEntry<Self::PinAddress>]) -> Result<(), Errno>
+    where
+        Self::PinAddress: Copy,
+    {
+        for entry in map {
+            self.claim_pin(entry.pin, entry.function, entry.owner)?;
+        }
+        Ok(())
+    }
+}
+
+impl<P> PinMapEntry<P> {
+    /// Constructs a pin map entry
+    pub const fn new(pin: P, function: &'static str, owner: &'static str) -> Self {
+        Self {
+            pin,
+            function,
+            owner,
+        }
+    }
+}
+
+impl<P: Ord + Copy> PinClaims<P> {
+    /// Constructs an empty claim table
+    pub const fn new() -> Self {
+        Self {
+            claims: BTreeMap::new(),
+        }
+    }
+
+    /// Records `claim` of `pin`, fails with [Errno::Busy] if the pin is
+    /// claimed by a different owner
+    pub fn claim(&mut self, pin: P, claim: PinClaim) -> Result<(), Errno> {
+        match self.claims.get(&pin) {
+            Some(old) if old.owner != claim.owner => Err(Errno::Busy),
+            _ => {
+                self.claims.insert(pin, claim);
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks whether `owner` may claim `pin`
+    pub fn check(&self, pin: P, owner: &str) -> Result<(), Errno> {
+        match self.claims.get(&pin) {
+            Some(old) if old.owner != owner => Err(Errno::Busy),
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes `owner`'s claim of `pin`
+    pub fn release(&mut self, pin: P, owner: &str) -> Result<(), Errno> {
+        match self.claims.get(&pin) {
+            None => Err(Errno::DoesNotExist),
+            Some(old) if old.owner != owner => Err(Errno::PermissionDenied),
+            Some(_) => {
+                self.claims.remove(&pin);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the `index`th claimed pin in pin order
+    pub fn nth(&self, index: usize) -> Option<(P, PinClaim)> {
+        self.claims.iter().nth(index).map(|(&pin, &claim)| (pin, claim))
+    }
+}
+
 impl PinConfig {
     /// Alternative (peripheral) pin configuration
     pub const fn alt(func: u32) -> Self {
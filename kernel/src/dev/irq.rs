@@ -1,28 +1,124 @@
 //! Interrupt controller and handler interfaces
-use crate::dev::Device;
+use crate::arch::machine;
+use crate::dev::{timer::TimestampSource, Device};
+use crate::proc::{kthread, sched, wait::Wait};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{boxed::Box, format, vec::Vec};
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
 use libsys::error::Errno;
 
+/// Maximum number of handlers sharing a single IRQ line
+pub const MAX_SHARED_HANDLERS: usize = 4;
+
+/// Interval after which an IRQ thread rechecks its line even without a
+/// wakeup, in case one was lost between the check and the wait
+const IRQ_THREAD_TIMEOUT: Duration = Duration::from_millis(100);
+
+type IrqThreadEntry = (&'static str, Box<dyn FnOnce()>);
+
+// IRQ threads registered before the scheduler was initialized
+static PENDING_THREADS: IrqSafeSpinLock<Vec<IrqThreadEntry>> = IrqSafeSpinLock::new(Vec::new());
+
 /// Token to indicate the local core is running in IRQ context
 pub struct IrqContext<'irq_context> {
     _0: PhantomData<&'irq_context ()>,
 }
 
+/// Kind of a dynamically allocated IRQ line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IrqKind {
+    /// Software-generated interrupt, raised with [IntController::raise_irq]
+    Software,
+    /// Message-signalled interrupt, raised by a device writing the message
+    /// returned by [IntController::msi_message]
+    Msi,
+}
+
+/// Allocator of IRQ numbers from a range of lines which are not wired to
+/// fixed devices. Ranges of at most 128 lines are supported.
+pub struct IrqAllocator {
+    start: usize,
+    count: usize,
+    used: IrqSafeSpinLock<u128>,
+}
+
+/// Table of handlers bound to IRQ lines of an interrupt controller.
+/// Several handlers may share a single line.
+pub struct IrqTable<const N: usize> {
+    lines: IrqSafeSpinLock<[[Option<&'static (dyn IntSource + Sync)>; MAX_SHARED_HANDLERS]; N]>,
+}
+
 /// Interrupt controller interface
 pub trait IntController: Device {
     /// Implementation-specific definition for "IRQ line"
     type IrqNumber;
 
-    /// Binds a handler [IntSource] to a specific `irq` line
+    /// Binds a handler [IntSource] to a specific `irq` line. The line may
+    /// already have other handlers bound, all of them are invoked when the
+    /// IRQ arrives.
     fn register_handler(
         &self,
         irq: Self::IrqNumber,
         handler: &'static (dyn IntSource + Sync),
     ) -> Result<(), Errno>;
 
+    /// Binds a handler [IntSource] to `irq` line, to be run in a dedicated
+    /// kernel thread. When the IRQ arrives, the line is only masked and
+    /// acknowledged, the thread runs the handler and unmasks the line.
+    fn register_threaded_handler(
+        &'static self,
+        irq: Self::IrqNumber,
+        handler: &'static (dyn IntSource + Sync),
+    ) -> Result<(), Errno>
+    where
+        Self: Sized + Sync,
+        Self::IrqNumber: Copy + Sync + 'static,
+    {
+        let thread: &'static IrqThread<Self> = Box::leak(Box::new(IrqThread {
+            intc: self,
+            irq,
+            handler,
+            pending: AtomicBool::new(false),
+            wait: Wait::new("irq_thread"),
+        }));
+        self.register_handler(irq, thread)?;
+        spawn_irq_thread((handler.name(), Box::new(move || irq_thread_fn(thread))))
+    }
+
     /// Enables/unmasks `irq` line
     fn enable_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno>;
 
+    /// Allocates an unused IRQ line of given `kind`
+    fn allocate_irq(&self, kind: IrqKind) -> Result<Self::IrqNumber, Errno> {
+        let _ = kind;
+        Err(Errno::NotImplemented)
+    }
+
+    /// Releases an IRQ line obtained from [IntController::allocate_irq],
+    /// masking it and unbinding its handlers
+    fn free_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno> {
+        let _ = irq;
+        Err(Errno::NotImplemented)
+    }
+
+    /// Raises software-generated interrupt `irq` on the local CPU
+    fn raise_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno> {
+        let _ = irq;
+        Err(Errno::NotImplemented)
+    }
+
+    /// Returns physical address and data a device has to write to raise
+    /// message-signalled interrupt `irq`
+    fn msi_message(&self, irq: Self::IrqNumber) -> Result<(usize, u32), Errno> {
+        let _ = irq;
+        Err(Errno::NotImplemented)
+    }
+
+    /// Disables/masks `irq` line
+    fn disable_irq(&self, irq: Self::IrqNumber) -> Result<(), Errno>;
+
     /// Handles all pending IRQs for this interrupt controller
     fn handle_pending_irqs<'irq_context>(&'irq_context self, ic: &IrqContext<'irq_context>);
 }
@@ -31,13 +127,188 @@ pub trait IntController: Device {
 pub trait IntSource: Device {
     /// Handles pending IRQs, if any, of this [IntSource].
     ///
-    /// If no IRQ is pending, returns [Errno::DoesNotExist]
+    /// If no IRQ is pending, returns [Errno::DoesNotExist]: the IRQ was
+    /// raised by another device sharing the line
     fn handle_irq(&self) -> Result<(), Errno>;
 
     ///
     fn init_irqs(&'static self) -> Result<(), Errno>;
 }
 
+impl IrqAllocator {
+    /// Constructs an allocator for lines `start..start + count`
+    pub const fn new(start: usize, count: usize) -> Self {
+        assert!(count <= 128);
+        Self {
+            start,
+            count,
+            used: IrqSafeSpinLock::new(0),
+        }
+    }
+
+    /// Returns `true` if `irq` belongs to the range of this allocator
+    #[inline]
+    pub fn contains(&self, irq: usize) -> bool {
+        irq >= self.start && irq < self.start + self.count
+    }
+
+    /// Allocates an unused line
+    pub fn alloc(&self) -> Result<usize, Errno> {
+        let mut used = self.used.lock();
+        let index = (0..self.count)
+            .find(|&i| *used & (1 << i) == 0)
+            .ok_or(Errno::Busy)?;
+        *used |= 1 << index;
+        Ok(self.start + index)
+    }
+
+    /// Releases a line obtained from [IrqAllocator::alloc]
+    pub fn free(&self, irq: usize) -> Result<(), Errno> {
+        if !self.contains(irq) {
+            return Err(Errno::InvalidArgument);
+        }
+        let bit = 1 << (irq - self.start);
+        let mut used = self.used.lock();
+        if *used & bit == 0 {
+            return Err(Errno::InvalidArgument);
+        }
+        *used &= !bit;
+        Ok(())
+    }
+}
+
+impl<const N: usize> IrqTable<N> {
+    /// Constructs an empty table
+    pub const fn new() -> Self {
+        Self {
+            lines: IrqSafeSpinLock::new([[None; MAX_SHARED_HANDLERS]; N]),
+        }
+    }
+
+    /// Adds `handler` to the list of `irq` line handlers
+    pub fn register(
+        &self,
+        irq: usize,
+        handler: &'static (dyn IntSource + Sync),
+    ) -> Result<(), Errno> {
+        let mut lines = self.lines.lock();
+        let line = &mut lines[irq];
+        let handler_ptr = handler as *const _ as *const ();
+        if line
+            .iter()
+            .flatten()
+            .any(|&h| h as *const _ as *const () == handler_ptr)
+        {
+            return Err(Errno::AlreadyExists);
+        }
+        let slot = line.iter_mut().find(|h| h.is_none()).ok_or(Errno::Busy)?;
+        *slot = Some(handler);
+        Ok(())
+    }
+
+    /// Unbinds all the handlers of `irq` line
+    pub fn unregister_all(&self, irq: usize) {
+        self.lines.lock()[irq] = [None; MAX_SHARED_HANDLERS];
+    }
+
+    /// Invokes all the handlers bound to `irq` line
+    pub fn handle(&self, irq: usize) {
+        // Handlers may register other handlers, don't hold the lock
+        let line = self.lines.lock()[irq];
+        if line[0].is_none() {
+            panic!("No handler registered for irq{}", irq);
+        }
+
+        let mut handled = false;
+        for handler in line.iter().flatten() {
+            match handler.handle_irq() {
+                Ok(()) => handled = true,
+                Err(Errno::DoesNotExist) => {}
+                Err(err) => panic!("{}: irq handler failed: {:?}", handler.name(), err),
+            }
+        }
+        if !handled {
+            warnln!("Spurious irq{}: no handler claimed it", irq);
+        }
+    }
+}
+
+/// Top half of a threaded IRQ handler
+struct IrqThread<I: IntController + 'static> {
+    intc: &'static I,
+    irq: I::IrqNumber,
+    handler: &'static (dyn IntSource + Sync),
+    pending: AtomicBool,
+    wait: Wait,
+}
+
+impl<I: IntController + 'static> Device for IrqThread<I> {
+    fn name(&self) -> &'static str {
+        self.handler.name()
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+impl<I: IntController + 'static> IntSource for IrqThread<I>
+where
+    I::IrqNumber: Copy,
+{
+    fn handle_irq(&self) -> Result<(), Errno> {
+        self.intc.disable_irq(self.irq)?;
+        self.pending.store(true, Ordering::Release);
+        self.wait.wakeup_one();
+        Ok(())
+    }
+
+    fn init_irqs(&'static self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+fn irq_thread_fn<I: IntController + 'static>(thread: &'static IrqThread<I>) -> !
+where
+    I::IrqNumber: Copy,
+{
+    loop {
+        if thread.pending.swap(false, Ordering::Acquire) {
+            match thread.handler.handle_irq() {
+                Ok(()) | Err(Errno::DoesNotExist) => {}
+                Err(err) => warnln!("{}: irq handler failed: {:?}", thread.name(), err),
+            }
+            thread.intc.enable_irq(thread.irq).unwrap();
+            continue;
+        }
+
+        let deadline = machine::local_timer().timestamp().unwrap() + IRQ_THREAD_TIMEOUT;
+        match thread.wait.wait(Some(deadline)) {
+            Ok(()) | Err(Errno::TimedOut) | Err(Errno::Interrupt) => {}
+            Err(err) => panic!("irq thread wait failed: {:?}", err),
+        }
+    }
+}
+
+fn spawn_irq_thread(entry: IrqThreadEntry) -> Result<(), Errno> {
+    if sched::is_ready() {
+        kthread::spawn(&format!("irq/{}", entry.0), entry.1)?;
+    } else {
+        PENDING_THREADS.lock().push(entry);
+    }
+    Ok(())
+}
+
+/// Starts the threads of threaded IRQ handlers registered before the
+/// scheduler was initialized
+pub fn start_irq_threads() -> Result<(), Errno> {
+    let threads = core::mem::take(&mut *PENDING_THREADS.lock());
+    for (name, entry) in threads {
+        kthread::spawn(&format!("irq/{}", name), entry)?;
+    }
+    Ok(())
+}
+
 impl<'q> IrqContext<'q> {
     /// Constructs an IRQ context token
     ///
@@ -0,0 +1,168 @@
+//! Kernel message log.
+//!
+//! All kernel log output, including userspace trace messages, is recorded
+//! into a [SharedRing], one record per message with its [TraceLevel] as the
+//! record kind. The ring is read through `/dev/kmsg`: a read returns one
+//! record, blocking reads wait for new ones (follow mode). For high
+//! message rates, [IoctlCmd::RingMap] maps the ring into the caller, whose
+//! records can then be consumed without a system call per record. Either
+//! way the ring has a single read position, so only one reader should be
+//! active.
+use crate::arch::machine;
+use crate::debug::Level;
+use crate::dev::{timer::TimestampSource, Device};
+use crate::mem::ring::SharedRing;
+use crate::proc::{
+    wait::{Wait, WAIT_SELECT},
+    Process,
+};
+use crate::sync::IrqSafeSpinLock;
+use crate::util::InitOnce;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use libsys::{debug::TraceLevel, error::Errno, ioctl::IoctlCmd, ring::RingMapping};
+use vfs::CharDevice;
+
+/// Kernel message log device
+pub struct Kmsg {
+    ring: InitOnce<SharedRing>,
+    producer: IrqSafeSpinLock<()>,
+    pending: AtomicBool,
+    wait: Wait,
+}
+
+struct RecordBuffer {
+    data: [u8; RECORD_MAX],
+    len: usize,
+}
+
+/// Size of the ring's data area, in pages
+const RING_PAGES: usize = 16;
+/// Maximum length of a single message, longer ones are truncated
+const RECORD_MAX: usize = 256;
+/// Upper bound of the delay between a message and a blocked reader
+/// noticing it
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl fmt::Write for RecordBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = core::cmp::min(s.len(), RECORD_MAX - self.len);
+        self.data[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+#[ioctl_dispatch(dispatch_ioctl)]
+impl Kmsg {
+    const fn new() -> Self {
+        Self {
+            ring: InitOnce::new(),
+            producer: IrqSafeSpinLock::new(()),
+            pending: AtomicBool::new(false),
+            wait: Wait::new("kmsg"),
+        }
+    }
+
+    /// Allocates the message ring. Messages logged before are not
+    /// recorded.
+    pub fn init(&self) -> Result<(), Errno> {
+        self.ring.init(SharedRing::new(RING_PAGES)?);
+        Ok(())
+    }
+
+    /// Records a log message. Readers are not woken up here, as messages
+    /// can be logged with any locks held, see [Kmsg::notify].
+    pub fn log(&self, level: Level, args: fmt::Arguments) {
+        use fmt::Write;
+
+        // Logging must not report anything itself, so no try_get() here
+        let ring = match self.ring.get_if_initialized() {
+            Some(ring) => ring,
+            None => return,
+        };
+        let mut buf = RecordBuffer {
+            data: [0; RECORD_MAX],
+            len: 0,
+        };
+        buf.write_fmt(args).ok();
+
+        let kind = match level {
+            Level::Debug => TraceLevel::Debug,
+            Level::Info => TraceLevel::Info,
+            Level::Warn => TraceLevel::Warn,
+            Level::Error => TraceLevel::Error,
+        };
+        // Messages logged while pushing one (e.g. a panic) are dropped
+        if let Some(_guard) = self.producer.lock_nowait() {
+            unsafe {
+                ring.push(kind as u32, &buf.data[..buf.len]);
+            }
+            self.pending.store(true, Ordering::Release);
+        }
+    }
+
+    /// Wakes up readers if new messages have been logged. Called from the
+    /// timer interrupt.
+    pub fn notify(&self) {
+        if self.pending.swap(false, Ordering::AcqRel) {
+            self.wait.wakeup_all();
+            WAIT_SELECT.wakeup_all();
+        }
+    }
+
+    #[ioctl(RingMap)]
+    fn map_ring(&self) -> Result<RingMapping, Errno> {
+        let ring = self.ring.get_if_initialized().ok_or(Errno::DoesNotExist)?;
+        Process::current().manipulate_space(|space| ring.map_user(space))
+    }
+}
+
+impl Device for Kmsg {
+    fn name(&self) -> &'static str {
+        "Kernel message log"
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+impl CharDevice for Kmsg {
+    /// Reads a single message. If `data` is too short, the message is
+    /// truncated.
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Errno> {
+        let ring = self.ring.get_if_initialized().ok_or(Errno::DoesNotExist)?;
+        loop {
+            if let Some((_, len)) = ring.consumer().pop(data) {
+                return Ok(core::cmp::min(len, data.len()));
+            }
+            if !blocking {
+                return Err(Errno::WouldBlock);
+            }
+            // The timer may be idle for long, don't rely on notify() alone
+            let deadline = machine::local_timer().timestamp()? + POLL_INTERVAL;
+            match self.wait.wait(Some(deadline)) {
+                Ok(()) | Err(Errno::TimedOut) => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn write(&self, _blocking: bool, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::InvalidOperation)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Errno> {
+        let ring = self.ring.get_if_initialized();
+        Ok(!write && ring.map_or(false, |ring| !ring.is_empty()))
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, ptr: usize, len: usize) -> Result<usize, Errno> {
+        self.dispatch_ioctl(cmd, ptr, len)
+    }
+}
+
+/// Kernel message log instance
+pub static KMSG: Kmsg = Kmsg::new();
@@ -0,0 +1,275 @@
+//! LED control and software triggers.
+//!
+//! LEDs are registered by board code and driven either manually or by a
+//! trigger. State and triggers are exposed through `/dev/leds`: reading
+//! gives lines of form `<name> <trigger> <on|off>`, writing
+//! `<name> <trigger>` or `<name> on|off` reconfigures an LED.
+use crate::dev::{gpio::GpioDevice, Device};
+use crate::proc::wait;
+use crate::sync::IrqSafeSpinLock;
+use alloc::{format, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+use libsys::{error::Errno, ioctl::IoctlCmd};
+use vfs::CharDevice;
+
+/// Single LED which can be turned on and off
+pub trait Led {
+    /// Turns the LED on or off
+    fn set(&self, on: bool);
+}
+
+/// LED connected to a GPIO pin
+pub struct GpioLed<G: GpioDevice + 'static> {
+    gpio: &'static G,
+    pin: G::PinAddress,
+    active_low: bool,
+}
+
+/// Source of LED state changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LedTrigger {
+    /// Only changed manually
+    None,
+    /// Always on
+    DefaultOn,
+    /// Double blink every second while the kernel is alive
+    Heartbeat,
+    /// Blinks on SD/MMC I/O
+    Mmc,
+    /// Lit on kernel panic
+    Panic,
+}
+
+struct LedEntry {
+    name: &'static str,
+    led: &'static dyn Led,
+    trigger: LedTrigger,
+    on: bool,
+}
+
+/// Character device for reading and changing LED configuration
+pub struct LedControl {
+    // Next line to be read
+    cursor: AtomicUsize,
+}
+
+/// Interval between trigger updates
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+/// Heartbeat LED state for each tick of a one second period
+const HEARTBEAT_PATTERN: [bool; 10] = [
+    true, false, true, false, false, false, false, false, false, false,
+];
+
+static LEDS: IrqSafeSpinLock<Vec<LedEntry>> = IrqSafeSpinLock::new(Vec::new());
+static MMC_ACTIVITY: AtomicBool = AtomicBool::new(false);
+
+impl<G: GpioDevice + 'static> GpioLed<G>
+where
+    G::PinAddress: Copy,
+{
+    /// Constructs an LED on `pin` of `gpio`. The pin has to be configured
+    /// as an output by the board.
+    pub const fn new(gpio: &'static G, pin: G::PinAddress, active_low: bool) -> Self {
+        Self {
+            gpio,
+            pin,
+            active_low,
+        }
+    }
+}
+
+impl<G: GpioDevice + 'static> Led for GpioLed<G>
+where
+    G::PinAddress: Copy,
+{
+    fn set(&self, on: bool) {
+        self.gpio.write_pin(self.pin, on != self.active_low);
+    }
+}
+
+impl LedTrigger {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::DefaultOn => "default-on",
+            Self::Heartbeat => "heartbeat",
+            Self::Mmc => "mmc",
+            Self::Panic => "panic",
+        }
+    }
+
+    fn from_str(name: &str) -> Option<Self> {
+        [
+            Self::None,
+            Self::DefaultOn,
+            Self::Heartbeat,
+            Self::Mmc,
+            Self::Panic,
+        ]
+        .into_iter()
+        .find(|t| t.as_str() == name)
+    }
+}
+
+impl LedEntry {
+    fn set(&mut self, on: bool) {
+        if self.on != on {
+            self.on = on;
+            self.led.set(on);
+        }
+    }
+}
+
+/// Registers an LED under `name`, driven by `trigger`
+pub fn register(name: &'static str, led: &'static dyn Led, trigger: LedTrigger) -> Result<(), Errno> {
+    let mut leds = LEDS.lock();
+    if leds.iter().any(|e| e.name == name) {
+        return Err(Errno::AlreadyExists);
+    }
+    let on = trigger == LedTrigger::DefaultOn;
+    led.set(on);
+    leds.push(LedEntry {
+        name,
+        led,
+        trigger,
+        on,
+    });
+    Ok(())
+}
+
+/// Changes the trigger of LED `name`
+pub fn set_trigger(name: &str, trigger: LedTrigger) -> Result<(), Errno> {
+    let mut leds = LEDS.lock();
+    let entry = leds
+        .iter_mut()
+        .find(|e| e.name == name)
+        .ok_or(Errno::DoesNotExist)?;
+    entry.trigger = trigger;
+    entry.set(trigger == LedTrigger::DefaultOn);
+    Ok(())
+}
+
+/// Turns LED `name` on or off, detaching it from its trigger
+pub fn set_brightness(name: &str, on: bool) -> Result<(), Errno> {
+    let mut leds = LEDS.lock();
+    let entry = leds
+        .iter_mut()
+        .find(|e| e.name == name)
+        .ok_or(Errno::DoesNotExist)?;
+    entry.trigger = LedTrigger::None;
+    entry.set(on);
+    Ok(())
+}
+
+/// Reports SD/MMC I/O for [LedTrigger::Mmc]. Does not take any locks, so
+/// can be called from I/O paths.
+#[inline]
+pub fn mmc_activity() {
+    MMC_ACTIVITY.store(true, Ordering::Relaxed);
+}
+
+/// Lights the [LedTrigger::Panic] LEDs, does nothing if the LED list is
+/// locked
+pub fn panic() {
+    if let Some(mut leds) = LEDS.lock_nowait() {
+        for entry in leds.iter_mut() {
+            if entry.trigger == LedTrigger::Panic {
+                entry.set(true);
+            }
+        }
+    }
+}
+
+fn tick(step: usize) {
+    let mmc = MMC_ACTIVITY.swap(false, Ordering::Relaxed);
+    let heartbeat = HEARTBEAT_PATTERN[step % HEARTBEAT_PATTERN.len()];
+    for entry in LEDS.lock().iter_mut() {
+        match entry.trigger {
+            LedTrigger::Heartbeat => entry.set(heartbeat),
+            LedTrigger::Mmc => entry.set(mmc),
+            _ => (),
+        }
+    }
+}
+
+/// Kernel process function which updates triggered LEDs
+pub fn led_fn() -> ! {
+    let mut step = 0;
+    loop {
+        tick(step);
+        step = step.wrapping_add(1);
+        let mut remaining = Duration::ZERO;
+        wait::sleep(TICK_INTERVAL, &mut remaining).ok();
+    }
+}
+
+impl LedControl {
+    const fn new() -> Self {
+        Self {
+            cursor: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Device for LedControl {
+    fn name(&self) -> &'static str {
+        "LED control"
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+impl CharDevice for LedControl {
+    /// Reads a single LED line. After the last line a read returns 0 and
+    /// the next one starts over.
+    fn read(&self, _blocking: bool, data: &mut [u8]) -> Result<usize, Errno> {
+        let index = self.cursor.fetch_add(1, Ordering::AcqRel);
+        let line = match LEDS.lock().get(index) {
+            Some(entry) => format!(
+                "{} {} {}\n",
+                entry.name,
+                entry.trigger.as_str(),
+                if entry.on { "on" } else { "off" }
+            ),
+            None => {
+                self.cursor.store(0, Ordering::Release);
+                return Ok(0);
+            }
+        };
+        let count = core::cmp::min(line.len(), data.len());
+        data[..count].copy_from_slice(&line.as_bytes()[..count]);
+        Ok(count)
+    }
+
+    fn write(&self, _blocking: bool, data: &[u8]) -> Result<usize, Errno> {
+        let text = core::str::from_utf8(data).map_err(|_| Errno::InvalidArgument)?;
+        let mut words = text.split_whitespace();
+        let (name, value) = match (words.next(), words.next(), words.next()) {
+            (Some(name), Some(value), None) => (name, value),
+            _ => return Err(Errno::InvalidArgument),
+        };
+        match value {
+            "on" => set_brightness(name, true)?,
+            "off" => set_brightness(name, false)?,
+            _ => set_trigger(
+                name,
+                LedTrigger::from_str(value).ok_or(Errno::InvalidArgument)?,
+            )?,
+        }
+        Ok(data.len())
+    }
+
+    fn is_ready(&self, _write: bool) -> Result<bool, Errno> {
+        Ok(true)
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _ptr: usize, _lim: usize) -> Result<usize, Errno> {
+        Err(Errno::InvalidArgument)
+    }
+}
+
+/// LED control device instance
+pub static LED_CONTROL: LedControl = LedControl::new();
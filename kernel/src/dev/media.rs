@@ -0,0 +1,82 @@
+//! Removable media change detection
+use crate::dev::uevent::{self, UeventAction};
+use crate::proc::{kthread, wait};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use vfs::BlockDevice;
+
+struct RemovableDevice {
+    name: String,
+    dev: &'static dyn BlockDevice,
+    present: bool,
+}
+
+/// Interval between media presence checks
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static DEVICES: IrqSafeSpinLock<Vec<RemovableDevice>> = IrqSafeSpinLock::new(Vec::new());
+// Set once kernel processes can be created
+static POLLER_ENABLED: AtomicBool = AtomicBool::new(false);
+static POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Adds a block device with removable media to the polling list, starting
+/// the poller if this is the first one
+pub fn register(dev: &'static dyn BlockDevice, name: &str) {
+    assert!(dev.is_removable());
+    DEVICES.lock().push(RemovableDevice {
+        name: String::from(name),
+        dev,
+        present: dev.is_media_present(),
+    });
+    if POLLER_ENABLED.load(Ordering::SeqCst) {
+        spawn_poller();
+    }
+}
+
+/// Allows the poller to be started, starting it right away if removable
+/// devices were registered during boot. Systems without removable media
+/// never run it.
+pub fn enable_poller() {
+    POLLER_ENABLED.store(true, Ordering::SeqCst);
+    if !DEVICES.lock().is_empty() {
+        spawn_poller();
+    }
+}
+
+fn spawn_poller() {
+    if !POLLER_STARTED.swap(true, Ordering::SeqCst) {
+        kthread::spawn("mediad", || poll_fn()).unwrap();
+    }
+}
+
+/// Checks all the removable devices for media change. On change, the
+/// driver is notified to drop any cached state and a uevent is emitted.
+pub fn poll() {
+    let mut devices = DEVICES.lock();
+    for entry in devices.iter_mut() {
+        let present = entry.dev.is_media_present();
+        if present == entry.present {
+            continue;
+        }
+        entry.present = present;
+        entry.dev.media_changed(present);
+        if present {
+            uevent::notify(UeventAction::Add, &entry.name);
+        } else {
+            // I/O to the old media fails with MediaGone from now on
+            warnln!("Media removed from {}", entry.name);
+            uevent::notify(UeventAction::Remove, &entry.name);
+        }
+    }
+}
+
+/// Kernel process function which periodically polls removable devices
+fn poll_fn() -> ! {
+    loop {
+        poll();
+        let mut remaining = Duration::ZERO;
+        wait::sleep(POLL_INTERVAL, &mut remaining).ok();
+    }
+}
@@ -1,18 +1,33 @@
 //! Module for device interfaces and drivers
 
-use libsys::error::Errno;
+use crate::sync::IrqSafeSpinLock;
+use alloc::vec::Vec;
+use libsys::{
+    dev::{DeviceInfo, DeviceState},
+    error::Errno,
+    FixedStr,
+};
 
 // Device classes
+pub mod crash;
 pub mod fdt;
 pub mod gpio;
 pub mod irq;
+pub mod kmsg;
+pub mod led;
+pub mod media;
 pub mod pci;
 pub mod rtc;
 pub mod sd;
 pub mod serial;
+pub mod sysrq;
 pub mod timer;
 pub mod pseudo;
 pub mod tty;
+pub mod uevent;
+#[cfg(feature = "virtio")]
+pub mod virtio;
+pub mod zram;
 
 /// Generic device trait
 pub trait Device {
@@ -26,4 +41,132 @@ pub trait Device {
     /// Marked unsafe as it may cause direct hardware-specific side-effects.
     /// Additionally, may be called twice with undefined results.
     unsafe fn enable(&self) -> Result<(), Errno>;
+
+    /// Saves the device state before the system is suspended, after which
+    /// the device may lose power. Devices without support for this are
+    /// left running.
+    ///
+    /// # Safety
+    ///
+    /// Marked unsafe as it may cause direct hardware-specific side-effects.
+    unsafe fn suspend(&self) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
+
+    /// Restores the device state saved by [Device::suspend].
+    ///
+    /// # Safety
+    ///
+    /// Must only be called after a successful [Device::suspend].
+    unsafe fn resume(&self) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
+}
+
+/// Maximum number of devices tracked in the device list. Devices are
+/// enabled before the heap is available, so the list has a fixed size.
+const DEVICE_LIST_MAX: usize = 64;
+
+type DeviceEntry = (&'static dyn Device, DeviceState);
+
+static DEVICES: IrqSafeSpinLock<[Option<DeviceEntry>; DEVICE_LIST_MAX]> =
+    IrqSafeSpinLock::new([None; DEVICE_LIST_MAX]);
+
+fn same_device(a: &'static dyn Device, b: &'static dyn Device) -> bool {
+    core::ptr::eq(a as *const _ as *const (), b as *const _ as *const ())
+}
+
+/// Enables `dev` and records it in the device list along with the outcome.
+///
+/// # Safety
+///
+/// See [Device::enable].
+pub unsafe fn enable(dev: &'static dyn Device) -> Result<(), Errno> {
+    let res = dev.enable();
+    let state = match res {
+        Ok(()) => DeviceState::Enabled,
+        Err(_) => DeviceState::Failed,
+    };
+    set_state(dev, state);
+    res
+}
+
+/// Updates the state of `dev` in the device list, adding it if needed
+pub fn set_state(dev: &'static dyn Device, state: DeviceState) {
+    let mut devices = DEVICES.lock();
+    if let Some((_, old)) = devices
+        .iter_mut()
+        .flatten()
+        .find(|(d, _)| same_device(*d, dev))
+    {
+        *old = state;
+    } else if let Some(slot) = devices.iter_mut().find(|slot| slot.is_none()) {
+        *slot = Some((dev, state));
+    } else {
+        warnln!("Device list is full, not tracking {}", dev.name());
+    }
+}
+
+/// Suspends enabled devices in the device list, in reverse order of
+/// enabling. If a device fails to suspend, the ones already suspended are
+/// resumed.
+///
+/// # Safety
+///
+/// Must be called with IRQs disabled, and the devices must not be used
+/// until [resume_all].
+pub unsafe fn suspend_all() -> Result<(), Errno> {
+    let devices = *DEVICES.lock();
+    for &(dev, state) in devices.iter().rev().flatten() {
+        if state != DeviceState::Enabled {
+            continue;
+        }
+        match dev.suspend() {
+            Ok(()) => set_state(dev, DeviceState::Suspended),
+            Err(Errno::NotImplemented) => debugln!("{}: cannot be suspended", dev.name()),
+            Err(err) => {
+                errorln!("{}: suspend failed: {:?}", dev.name(), err);
+                resume_all();
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resumes suspended devices in the device list, in order of enabling.
+/// Devices which fail to resume are marked as failed.
+///
+/// # Safety
+///
+/// Must be called with IRQs disabled.
+pub unsafe fn resume_all() {
+    let devices = *DEVICES.lock();
+    for &(dev, state) in devices.iter().flatten() {
+        if state != DeviceState::Suspended {
+            continue;
+        }
+        let state = match dev.resume() {
+            Ok(()) => DeviceState::Enabled,
+            Err(err) => {
+                errorln!("{}: resume failed: {:?}", dev.name(), err);
+                DeviceState::Failed
+            }
+        };
+        set_state(dev, state);
+    }
+}
+
+/// Returns the names and states of known devices, in the order they
+/// were enabled
+pub fn list() -> Vec<DeviceInfo> {
+    DEVICES
+        .lock()
+        .iter()
+        .flatten()
+        .map(|&(dev, state)| DeviceInfo {
+            state,
+            name: FixedStr::from_str_truncated(dev.name()),
+        })
+        .collect()
 }
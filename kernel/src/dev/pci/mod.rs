@@ -1,12 +1,24 @@
 //! PCI bus host and device interfaces
 
 use crate::dev::Device;
+use crate::sync::IrqSafeSpinLock;
+use alloc::vec::Vec;
 use core::fmt;
-use libsys::error::Errno;
+use libsys::{
+    dev::{PciBar, PciBarFlags, PciFunction, PCI_BAR_COUNT},
+    error::Errno,
+};
 
 pub mod pcie;
 
 macro_rules! ecam_field {
+    ($getter:ident, $off:expr, u32) => {
+        #[inline(always)]
+        #[allow(missing_docs)]
+        fn $getter(&self) -> u32 {
+            self.readl($off)
+        }
+    };
     ($getter:ident, $off:expr, u16) => {
         #[inline(always)]
         #[allow(missing_docs)]
@@ -70,7 +82,11 @@ pub trait PciCfgSpace {
 
     ecam_field! { vendor_id, 0x00, u16 }
     ecam_field! { device_id, 0x02, u16 }
+    ecam_field! { command, 0x04, u16 }
+    ecam_field! { class, 0x08, u32 }
     ecam_field! { header_type, 0x0E, u8 }
+    ecam_field! { irq_line, 0x3C, u8 }
+    ecam_field! { irq_pin, 0x3D, u8 }
 
     /// Returns `true` if device this config describes is
     /// present on the bus
@@ -80,6 +96,9 @@ pub trait PciCfgSpace {
     }
 }
 
+/// Functions discovered by host controllers
+static FUNCTIONS: IrqSafeSpinLock<Vec<PciFunction>> = IrqSafeSpinLock::new(Vec::new());
+
 /// PCI host controller interface
 pub trait PciHostDevice: Device {
     /// Initializes and enables devices attached to the bus
@@ -132,3 +151,102 @@ impl fmt::Debug for PciAddress {
         )
     }
 }
+
+/// Reads a base address register at `off`, returning its value and the
+/// mask of address bits the device decodes
+unsafe fn size_bar<C: PciCfgSpace>(cfg: &C, off: usize) -> (u32, u32) {
+    let value = cfg.readl(off);
+    cfg.writel(off, 0xFFFFFFFF);
+    let mask = cfg.readl(off);
+    cfg.writel(off, value);
+    (value, mask)
+}
+
+/// Reads and sizes the first `count` base address registers of `cfg`.
+///
+/// # Safety
+///
+/// Temporarily disables decoding of the function's regions, so the
+/// function must not be in use.
+unsafe fn probe_bars<C: PciCfgSpace>(cfg: &C, count: usize) -> [PciBar; PCI_BAR_COUNT] {
+    let mut bars = [PciBar::default(); PCI_BAR_COUNT];
+    // Only the command half of the register is written, writing the status
+    // half back would clear its bits
+    let command = cfg.command();
+    cfg.writel(0x04, (command & !0x3) as u32);
+
+    let mut index = 0;
+    while index < count {
+        let (value, mask) = size_bar(cfg, 0x10 + index * 4);
+
+        if value & 0x1 != 0 {
+            // Upper half of I/O BARs may be hardwired to zero
+            let mask = (mask & !0x3) | if mask >> 16 == 0 { 0xFFFF0000 } else { 0 };
+            if mask & 0xFFFF != 0 {
+                bars[index] = PciBar {
+                    address: (value & !0x3) as u64,
+                    size: (!mask).wrapping_add(1) as u64,
+                    flags: PciBarFlags::IO,
+                };
+            }
+            index += 1;
+            continue;
+        }
+
+        let mut flags = PciBarFlags::empty();
+        let mut address = (value & !0xF) as u64;
+        let mut mask = (mask & !0xF) as u64 | 0xFFFFFFFF00000000;
+        if value & 0x8 != 0 {
+            flags |= PciBarFlags::PREFETCH;
+        }
+        let is_64bit = (value >> 1) & 0x3 == 0x2 && index + 1 < count;
+        if is_64bit {
+            let (high, high_mask) = size_bar(cfg, 0x14 + index * 4);
+            flags |= PciBarFlags::MEM64;
+            address |= (high as u64) << 32;
+            mask = (mask & 0xFFFFFFFF) | ((high_mask as u64) << 32);
+        }
+        if mask & 0xFFFFFFF0 != 0 {
+            bars[index] = PciBar {
+                address,
+                size: (!mask).wrapping_add(1),
+                flags,
+            };
+        }
+        index += if is_64bit { 2 } else { 1 };
+    }
+
+    cfg.writel(0x04, command as u32);
+    bars
+}
+
+/// Records the function at `addr` in the PCI function list, along with its
+/// resources.
+///
+/// # Safety
+///
+/// Probes the function's base address registers, must only be called
+/// before a driver is attached to it.
+pub unsafe fn add_function<C: PciCfgSpace>(addr: PciAddress, cfg: &C) {
+    // Bridges only have two BARs, other header types have none
+    let bar_count = match cfg.header_type() & 0x7F {
+        0 => 6,
+        1 => 2,
+        _ => 0,
+    };
+    let function = PciFunction {
+        address: addr.value,
+        vendor_id: cfg.vendor_id(),
+        device_id: cfg.device_id(),
+        class: cfg.class(),
+        irq_pin: cfg.irq_pin(),
+        irq_line: cfg.irq_line(),
+        bars: probe_bars(cfg, bar_count),
+    };
+    FUNCTIONS.lock().push(function);
+}
+
+/// Returns the PCI functions discovered so far
+pub fn functions() -> Vec<PciFunction> {
+    FUNCTIONS.lock().clone()
+}
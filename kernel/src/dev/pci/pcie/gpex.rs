@@ -1,7 +1,7 @@
 //! Generic PCIe host driver
 
 use crate::dev::{
-    pci::{pcie::EcamCfgSpace, PciAddress, PciCfgSpace, PciHostDevice},
+    pci::{self, pcie::EcamCfgSpace, PciAddress, PciCfgSpace, PciHostDevice},
     Device,
 };
 use crate::mem::virt::DeviceMemory;
@@ -56,6 +56,9 @@ impl GenericPcieHost {
             cfg.vendor_id(),
             cfg.device_id()
         );
+        unsafe {
+            pci::add_function(addr, &cfg);
+        }
         Ok(())
     }
 
@@ -83,7 +86,7 @@ impl GenericPcieHost {
     }
 
     fn map_bus(&self, bus: u8) -> Result<(), Errno> {
-        for dev in 0u8..=255 {
+        for dev in 0u8..32 {
             self.map_device(PciAddress::new(bus, dev, 0))?;
         }
 
@@ -1,11 +1,40 @@
 //! Interfaces and drivers for real-time clock devices
 
 use crate::dev::Device;
+use crate::time;
+use crate::util::InitOnce;
+use core::time::Duration;
+use libsys::error::Errno;
 
 #[cfg(feature = "pl031")]
 pub mod pl031;
 
-// TODO define what RTC devices can do
-//      alarms? read real time?
+// TODO alarms?
 /// Interface for generic RTC device
-pub trait RtcDevice: Device {}
+pub trait RtcDevice: Device {
+    /// Reads current time in seconds since the Unix epoch
+    fn read_time(&self) -> Result<u64, Errno>;
+
+    /// Sets current time to `secs` since the Unix epoch
+    fn set_time(&self, secs: u64) -> Result<(), Errno>;
+}
+
+static SYSTEM_RTC: InitOnce<&'static dyn RtcDevice> = InitOnce::new();
+
+/// Makes `dev` the clock the wall time is read from at boot and written
+/// back to when it is set
+pub fn register(dev: &'static dyn RtcDevice) {
+    match dev.read_time() {
+        Ok(secs) => time::set_realtime(Duration::from_secs(secs)),
+        Err(err) => warnln!("{}: failed to read time: {:?}", dev.name(), err),
+    }
+    SYSTEM_RTC.init(dev);
+}
+
+/// Writes the wall clock `value` back to the system RTC, if there is one
+pub fn write_time(value: Duration) -> Result<(), Errno> {
+    match SYSTEM_RTC.get_if_initialized() {
+        Some(rtc) => rtc.set_time(value.as_secs()),
+        None => Ok(()),
+    }
+}
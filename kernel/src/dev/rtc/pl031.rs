@@ -54,7 +54,20 @@ pub struct Pl031 {
     irq: IrqNumber,
 }
 
-impl RtcDevice for Pl031 {}
+impl RtcDevice for Pl031 {
+    fn read_time(&self) -> Result<u64, Errno> {
+        Ok(self.inner.get().lock().regs.DR.get() as u64)
+    }
+
+    fn set_time(&self, secs: u64) -> Result<(), Errno> {
+        let secs = u32::try_from(secs).map_err(|_| Errno::InvalidArgument)?;
+        let inner = self.inner.get().lock();
+        inner.regs.LR.set(secs);
+        // Keep the match interrupt coming every second
+        inner.regs.MR.set(secs.wrapping_add(1));
+        Ok(())
+    }
+}
 
 impl IntSource for Pl031 {
     fn handle_irq(&self) -> Result<(), Errno> {
@@ -132,7 +132,11 @@ pub trait SdHostController: Device + BlockDevice {
                 let c_size_mult = (cmd9[1] >> 7) & 0x7;
                 ((c_size + 1) as u64) << (c_size_mult + 9 /* Block size is 512 */ + 2)
             }
-            1 => todo!(),
+            1 => {
+                // SDHC/SDXC: capacity is counted in 512K units
+                let c_size = (cmd9[1] >> 8) & 0x3FFFFF;
+                ((c_size + 1) as u64) << 19
+            }
             _ => {
                 warnln!("Invalid CSD version: {}", csd_structure);
                 return Err(Errno::DeviceError);
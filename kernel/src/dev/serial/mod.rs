@@ -1,11 +1,69 @@
 //! Module for serial device drivers
 
 use crate::dev::Device;
-use libsys::error::Errno;
+use libsys::{
+    error::Errno,
+    termios::{ModemLines, Termios, TermiosCflag},
+};
 
 #[cfg(feature = "pl011")]
 pub mod pl011;
 
+/// Parity bit mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parity {
+    /// No parity bit
+    None,
+    /// Even parity
+    Even,
+    /// Odd parity
+    Odd,
+}
+
+/// Serial line configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SerialConfig {
+    /// Line speed, in bits per second
+    pub baud: u32,
+    /// Character size, 5 to 8 bits
+    pub data_bits: u8,
+    /// Parity bit mode
+    pub parity: Parity,
+    /// Use two stop bits instead of one
+    pub two_stop_bits: bool,
+}
+
+impl SerialConfig {
+    /// 115200 baud, 8 data bits, no parity, one stop bit
+    pub const DEFAULT: Self = Self {
+        baud: 115200,
+        data_bits: 8,
+        parity: Parity::None,
+        two_stop_bits: false,
+    };
+
+    /// Extracts line configuration from terminal attributes
+    pub fn from_termios(termios: &Termios) -> Result<Self, Errno> {
+        if termios.speed == 0 {
+            return Err(Errno::InvalidArgument);
+        }
+        let cflag = termios.cflag;
+        let parity = if !cflag.contains(TermiosCflag::PARENB) {
+            Parity::None
+        } else if cflag.contains(TermiosCflag::PARODD) {
+            Parity::Odd
+        } else {
+            Parity::Even
+        };
+        Ok(Self {
+            baud: termios.speed,
+            data_bits: 5 + (cflag & TermiosCflag::CSIZE).bits() as u8,
+            parity,
+            two_stop_bits: cflag.contains(TermiosCflag::CSTOPB),
+        })
+    }
+}
+
 /// Generic interface for serial devices
 pub trait SerialDevice: Device {
     /// Transmits (blocking) a byte through the serial device
@@ -15,4 +73,30 @@ pub trait SerialDevice: Device {
     /// If `blocking` is `false` and there's no data in device's queue,
     /// will return [Errno::WouldBlock].
     fn recv(&self, blocking: bool) -> Result<u8, Errno>;
+
+    /// Prepares the device for exclusive use by the panic handler, breaking
+    /// any locks held by the interrupted context
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: only to be called with IRQs masked, when no other context
+    /// will run again
+    unsafe fn take_over(&self) {}
+
+    /// Changes line speed and character framing
+    fn set_config(&self, _config: &SerialConfig) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
+    /// Returns current state of the modem control and status lines
+    fn modem_lines(&self) -> Result<ModemLines, Errno> {
+        Err(Errno::NotImplemented)
+    }
+    /// Sets modem control outputs (DTR and RTS), other lines are ignored
+    fn set_modem_lines(&self, _lines: ModemLines) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
+    /// Starts (`true`) or stops transmitting a break condition
+    fn set_break(&self, _enable: bool) -> Result<(), Errno> {
+        Err(Errno::NotImplemented)
+    }
 }
@@ -3,14 +3,14 @@
 use crate::arch::machine::{self, IrqNumber};
 use crate::dev::{
     irq::{IntController, IntSource},
-    serial::SerialDevice,
+    serial::{Parity, SerialConfig, SerialDevice},
     tty::{CharRing, TtyDevice},
     Device,
 };
 use crate::mem::virt::DeviceMemoryIo;
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use libsys::error::Errno;
+use libsys::{error::Errno, termios::ModemLines};
 use tock_registers::{
     interfaces::{ReadWriteable, Readable, Writeable},
     register_bitfields, register_structs,
@@ -19,17 +19,51 @@ use tock_registers::{
 
 register_bitfields! {
     u32,
+    /// Data register
+    DR [
+        /// Break error: break condition was detected
+        BE OFFSET(10) NUMBITS(1) [],
+        /// Received/transmitted character
+        DATA OFFSET(0) NUMBITS(8) [],
+    ],
     /// Flag register
     FR [
+        /// Ring indicator
+        RI OFFSET(8) NUMBITS(1) [],
         /// Transmit FIFO full
         TXFF OFFSET(5) NUMBITS(1) [],
         /// Receive FIFO empty
         RXFE OFFSET(4) NUMBITS(1) [],
         /// UART busy
         BUSY OFFSET(3) NUMBITS(1) [],
+        /// Data carrier detect
+        DCD OFFSET(2) NUMBITS(1) [],
+        /// Data set ready
+        DSR OFFSET(1) NUMBITS(1) [],
+        /// Clear to send
+        CTS OFFSET(0) NUMBITS(1) [],
+    ],
+    /// Line control register
+    LCR_H [
+        /// Word length minus 5
+        WLEN OFFSET(5) NUMBITS(2) [],
+        /// Enable FIFOs
+        FEN OFFSET(4) NUMBITS(1) [],
+        /// Two stop bits
+        STP2 OFFSET(3) NUMBITS(1) [],
+        /// Even parity
+        EPS OFFSET(2) NUMBITS(1) [],
+        /// Enable parity
+        PEN OFFSET(1) NUMBITS(1) [],
+        /// Send break
+        BRK OFFSET(0) NUMBITS(1) [],
     ],
     /// Control register
     CR [
+        /// Request to send
+        RTS OFFSET(11) NUMBITS(1) [],
+        /// Data transmit ready
+        DTR OFFSET(10) NUMBITS(1) [],
         /// Enable UART receiver
         RXE OFFSET(9) NUMBITS(1) [],
         /// Enable UART transmitter
@@ -53,13 +87,17 @@ register_structs! {
     #[allow(non_snake_case)]
     Regs {
         /// Data register
-        (0x00 => DR: ReadWrite<u32>),
+        (0x00 => DR: ReadWrite<u32, DR::Register>),
         (0x04 => _res1),
         /// Flag register
         (0x18 => FR: ReadOnly<u32, FR::Register>),
         (0x1C => _res2),
+        /// Integer baud rate divisor
+        (0x24 => IBRD: ReadWrite<u32>),
+        /// Fractional baud rate divisor
+        (0x28 => FBRD: ReadWrite<u32>),
         /// Line control register
-        (0x2C => LCR_H: ReadWrite<u32>),
+        (0x2C => LCR_H: ReadWrite<u32, LCR_H::Register>),
         /// Control register
         (0x30 => CR: ReadWrite<u32, CR::Register>),
         (0x34 => IFLS: ReadWrite<u32>),
@@ -67,12 +105,24 @@ register_structs! {
         (0x3C => _res3),
         /// Interrupt clear register
         (0x44 => ICR: WriteOnly<u32, ICR::Register>),
-        (0x04 => @END),
+        (0x48 => @END),
     }
 }
 
+/// Register values preserved across suspend
+#[derive(Clone, Copy, Default)]
+struct Pl011Context {
+    ibrd: u32,
+    fbrd: u32,
+    lcr_h: u32,
+    cr: u32,
+    ifls: u32,
+    imsc: u32,
+}
+
 struct Pl011Inner {
     regs: DeviceMemoryIo<Regs>,
+    context: Pl011Context,
 }
 
 /// Device struct for PL011
@@ -82,6 +132,7 @@ pub struct Pl011 {
     ring: CharRing<16>,
     base: usize,
     irq: IrqNumber,
+    clock: u32,
 }
 
 impl Pl011Inner {
@@ -107,6 +158,76 @@ impl Pl011Inner {
         Ok(self.regs.DR.get() as u8)
     }
 
+    pub unsafe fn set_config(&mut self, config: &SerialConfig, clock: u32) -> Result<(), Errno> {
+        // Divisor is clock / (16 * baud), with 6 fractional bits
+        let baud = config.baud as u64;
+        let div = (clock as u64 * 4 + baud / 2) / baud;
+        let (ibrd, fbrd) = (div >> 6, div & 0x3F);
+        if ibrd == 0 || ibrd > 0xFFFF || !(5..=8).contains(&config.data_bits) {
+            return Err(Errno::InvalidArgument);
+        }
+
+        // Let the transmission in progress finish
+        while self.regs.FR.matches_all(FR::BUSY::SET) {
+            core::hint::spin_loop();
+        }
+        let cr = self.regs.CR.get();
+        self.regs.CR.set(0);
+
+        self.regs.IBRD.set(ibrd as u32);
+        self.regs.FBRD.set(fbrd as u32);
+        // Write to LCR_H latches the divisors
+        self.regs.LCR_H.modify(
+            LCR_H::WLEN.val(config.data_bits as u32 - 5)
+                + LCR_H::STP2.val(config.two_stop_bits as u32)
+                + LCR_H::PEN.val((config.parity != Parity::None) as u32)
+                + LCR_H::EPS.val((config.parity == Parity::Even) as u32),
+        );
+
+        self.regs.CR.set(cr);
+        Ok(())
+    }
+
+    pub fn modem_lines(&self) -> ModemLines {
+        let fr = self.regs.FR.extract();
+        let cr = self.regs.CR.extract();
+        let mut lines = ModemLines::empty();
+        lines.set(ModemLines::DTR, cr.is_set(CR::DTR));
+        lines.set(ModemLines::RTS, cr.is_set(CR::RTS));
+        lines.set(ModemLines::CTS, fr.is_set(FR::CTS));
+        lines.set(ModemLines::DSR, fr.is_set(FR::DSR));
+        lines.set(ModemLines::DCD, fr.is_set(FR::DCD));
+        lines.set(ModemLines::RI, fr.is_set(FR::RI));
+        lines
+    }
+
+    pub unsafe fn suspend(&mut self) {
+        while self.regs.FR.matches_all(FR::BUSY::SET) {
+            core::hint::spin_loop();
+        }
+        self.context = Pl011Context {
+            ibrd: self.regs.IBRD.get(),
+            fbrd: self.regs.FBRD.get(),
+            lcr_h: self.regs.LCR_H.get(),
+            cr: self.regs.CR.get(),
+            ifls: self.regs.IFLS.get(),
+            imsc: self.regs.IMSC.get(),
+        };
+    }
+
+    pub unsafe fn resume(&mut self) {
+        let context = self.context;
+        self.regs.CR.set(0);
+        self.regs.ICR.write(ICR::ALL::CLEAR);
+        self.regs.IBRD.set(context.ibrd);
+        self.regs.FBRD.set(context.fbrd);
+        // Write to LCR_H latches the divisors
+        self.regs.LCR_H.set(context.lcr_h);
+        self.regs.IFLS.set(context.ifls);
+        self.regs.IMSC.set(context.imsc);
+        self.regs.CR.set(context.cr);
+    }
+
     pub unsafe fn enable(&mut self) {
         self.regs.CR.set(0);
         self.regs.ICR.write(ICR::ALL::CLEAR);
@@ -132,10 +253,14 @@ impl IntSource for Pl011 {
         let inner = self.inner.get().lock();
         inner.regs.ICR.write(ICR::ALL::CLEAR);
 
-        let byte = inner.regs.DR.get();
+        let data = inner.regs.DR.extract();
         drop(inner);
 
-        self.recv_byte(byte as u8);
+        if data.is_set(DR::BE) {
+            self.recv_break();
+        } else {
+            self.recv_byte(data.read(DR::DATA) as u8);
+        }
         // self.ring.putc(byte as u8, false).ok();
 
         Ok(())
@@ -152,18 +277,49 @@ impl IntSource for Pl011 {
 
 impl SerialDevice for Pl011 {
     fn send(&self, byte: u8) -> Result<(), Errno> {
-        if !self.inner.is_initialized() {
-            // TODO early output here
-            return Ok(());
-        }
-        unsafe {
-            self.inner.get().lock().send(byte);
+        // Output before the device is set up is silently dropped
+        if let Some(inner) = self.inner.get_if_initialized() {
+            unsafe {
+                inner.lock().send(byte);
+            }
         }
         Ok(())
     }
 
     fn recv(&self, blocking: bool) -> Result<u8, Errno> {
-        unsafe { self.inner.get().lock().recv(blocking) }
+        unsafe { self.inner.try_get()?.lock().recv(blocking) }
+    }
+
+    unsafe fn take_over(&self) {
+        if let Some(inner) = self.inner.get_if_initialized() {
+            inner.force_unlock();
+        }
+    }
+
+    fn set_config(&self, config: &SerialConfig) -> Result<(), Errno> {
+        unsafe { self.inner.try_get()?.lock().set_config(config, self.clock) }
+    }
+
+    fn modem_lines(&self) -> Result<ModemLines, Errno> {
+        Ok(self.inner.try_get()?.lock().modem_lines())
+    }
+
+    fn set_modem_lines(&self, lines: ModemLines) -> Result<(), Errno> {
+        self.inner.get().lock().regs.CR.modify(
+            CR::DTR.val(lines.contains(ModemLines::DTR) as u32)
+                + CR::RTS.val(lines.contains(ModemLines::RTS) as u32),
+        );
+        Ok(())
+    }
+
+    fn set_break(&self, enable: bool) -> Result<(), Errno> {
+        self.inner
+            .get()
+            .lock()
+            .regs
+            .LCR_H
+            .modify(LCR_H::BRK.val(enable as u32));
+        Ok(())
     }
 }
 
@@ -197,6 +353,7 @@ impl Device for Pl011 {
     unsafe fn enable(&self) -> Result<(), Errno> {
         let mut inner = Pl011Inner {
             regs: DeviceMemoryIo::map(self.name(), self.base, 1)?,
+            context: Pl011Context::default(),
         };
         inner.enable();
 
@@ -204,20 +361,32 @@ impl Device for Pl011 {
 
         Ok(())
     }
+
+    unsafe fn suspend(&self) -> Result<(), Errno> {
+        self.inner.try_get()?.lock().suspend();
+        Ok(())
+    }
+
+    unsafe fn resume(&self) -> Result<(), Errno> {
+        self.inner.try_get()?.lock().resume();
+        Ok(())
+    }
 }
 
 impl Pl011 {
-    /// Constructs an instance of PL011 device.
+    /// Constructs an instance of PL011 device, `clock` is the UART
+    /// reference clock frequency in Hz.
     ///
     /// # Safety
     ///
     /// Does not perform `base` validation.
-    pub const unsafe fn new(base: usize, irq: IrqNumber) -> Self {
+    pub const unsafe fn new(base: usize, irq: IrqNumber, clock: u32) -> Self {
         Self {
             inner: InitOnce::new(),
             ring: CharRing::new(),
             base,
             irq,
+            clock,
         }
     }
 }
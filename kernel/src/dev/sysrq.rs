@@ -0,0 +1,91 @@
+//! Magic SysRq-style debug commands over serial TTYs.
+//!
+//! Enabled with `sysrq` kernel parameter. A command is entered by sending
+//! a break or the escape character (^O) followed by the command key. The
+//! escape character sent twice is passed through as input. Commands run in
+//! the TTY interrupt handler, so they work even if userspace is wedged:
+//!
+//! * `h`: list the commands
+//! * `m`: dump physical memory statistics
+//! * `q`: dump the scheduler run queue
+//! * `p`: dump the process list
+//! * `k`: kill the foreground process group of the TTY
+//! * `z`: save and restore the state of devices, without powering them
+//!   down, to test suspend support
+//! * `b`: reset the board immediately
+use crate::arch::machine;
+use crate::debug::Level;
+use crate::dev;
+use crate::dev::tty::CharRing;
+use crate::mem::phys;
+use crate::proc::{self, Process};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use libsys::signal::Signal;
+
+/// Character starting a command
+pub const ESCAPE: u8 = 0x0F;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+struct LogOutput;
+
+impl fmt::Write for LogOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        print!(Level::Info, "{}", s);
+        Ok(())
+    }
+}
+
+/// Enables or disables the commands
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Release);
+}
+
+/// Returns `true` if the commands are enabled
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Performs the command bound to `key`, received on TTY `ring`
+pub fn handle<const N: usize>(key: u8, ring: &CharRing<N>) {
+    match key {
+        b'm' => match phys::try_statistics() {
+            Some(stat) => println!(Level::Info, "SysRq: physical memory: {:#?}", stat),
+            None => println!(Level::Info, "SysRq: page manager is locked"),
+        },
+        b'q' => {
+            println!(Level::Info, "SysRq: run queue:");
+            proc::dump_run_queue(&mut LogOutput);
+        }
+        b'p' => {
+            println!(Level::Info, "SysRq: processes:");
+            if !Process::dump(&mut LogOutput) {
+                println!(Level::Info, "Process table is locked");
+            }
+        }
+        b'k' => {
+            println!(Level::Info, "SysRq: killing the foreground process group");
+            ring.signal_foreground(Signal::Kill);
+        }
+        b'z' => {
+            println!(Level::Info, "SysRq: suspending devices");
+            match unsafe { dev::suspend_all() } {
+                Ok(()) => {
+                    unsafe { dev::resume_all() };
+                    println!(Level::Info, "SysRq: devices resumed");
+                }
+                Err(err) => println!(Level::Info, "SysRq: suspend failed: {:?}", err),
+            }
+        }
+        b'b' => {
+            println!(Level::Info, "SysRq: resetting the board");
+            unsafe { machine::reset_board() }
+        }
+        _ => println!(
+            Level::Info,
+            "SysRq: m: memory, q: run queue, p: processes, k: kill foreground, \
+             z: suspend test, b: reboot"
+        ),
+    }
+}
@@ -9,3 +9,21 @@ pub trait TimestampSource: Device {
     /// Reads current timestamp as a [Duration] from system start time
     fn timestamp(&self) -> Result<Duration, Errno>;
 }
+
+/// Interface for timers raising an IRQ once a programmed time is reached
+pub trait OneShotTimer: TimestampSource {
+    /// Arms the timer to raise an IRQ at `deadline` timestamp, replacing
+    /// the previously armed one. `None` disarms the timer.
+    fn set_deadline(&self, deadline: Option<Duration>) -> Result<(), Errno>;
+
+    /// Returns the currently armed deadline, if any
+    fn deadline(&self) -> Option<Duration>;
+
+    /// Arms the timer for `deadline` unless an earlier one is armed already
+    fn arm_before(&self, deadline: Duration) -> Result<(), Errno> {
+        match self.deadline() {
+            Some(current) if current <= deadline => Ok(()),
+            _ => self.set_deadline(Some(deadline)),
+        }
+    }
+}
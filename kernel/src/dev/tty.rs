@@ -1,16 +1,24 @@
 //! Teletype (TTY) device facilities
-use crate::dev::serial::SerialDevice;
-use crate::proc::{Process, wait::{Wait, WAIT_SELECT}};
+use crate::dev::serial::{SerialConfig, SerialDevice};
+use crate::dev::sysrq;
+use crate::proc::{Process, wait::{self, Wait, WAIT_SELECT}};
 use crate::sync::IrqSafeSpinLock;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
 use libsys::error::Errno;
 use libsys::{
-    termios::{Termios, TermiosIflag, TermiosLflag, TermiosOflag},
+    termios::{
+        FlushQueue, ModemLines, Termios, TermiosIflag, TermiosLflag, TermiosOflag, WindowSize,
+    },
     proc::Pid,
     signal::Signal,
     ioctl::IoctlCmd
 };
-use core::mem::size_of;
-use crate::syscall::arg;
+
+/// Break duration used when zero is requested
+const DEFAULT_BREAK_MS: u32 = 250;
+/// Longest break which can be requested
+const MAX_BREAK_MS: u32 = 5000;
 
 #[derive(Debug)]
 struct CharRingInner<const N: usize> {
@@ -19,6 +27,7 @@ struct CharRingInner<const N: usize> {
     data: [u8; N],
     flags: u8,
     fg_pgid: Option<Pid>,
+    winsize: WindowSize,
 }
 
 /// Ring buffer for TTYs
@@ -27,6 +36,8 @@ pub struct CharRing<const N: usize> {
     wait_write: Wait,
     config: IrqSafeSpinLock<Termios>,
     inner: IrqSafeSpinLock<CharRingInner<N>>,
+    /// Next byte received is a SysRq command
+    sysrq_pending: AtomicBool,
 }
 
 /// Generic teletype device interface
@@ -45,25 +56,26 @@ pub trait TtyDevice<const N: usize>: SerialDevice {
     }
 
     /// Performs a TTY control request
-    fn tty_ioctl(&self, cmd: IoctlCmd, ptr: usize, _len: usize) -> Result<usize, Errno> {
-        match cmd {
-            IoctlCmd::TtyGetAttributes => {
-                // TODO validate size
-                let res = arg::struct_mut::<Termios>(ptr)?;
-                *res = self.ring().config.lock().clone();
-                Ok(size_of::<Termios>())
-            },
-            IoctlCmd::TtySetAttributes => {
-                let src = arg::struct_ref::<Termios>(ptr)?;
-                *self.ring().config.lock() = src.clone();
-                Ok(size_of::<Termios>())
-            },
-            IoctlCmd::TtySetPgrp => {
-                let src = arg::struct_ref::<u32>(ptr)?;
-                self.ring().inner.lock().fg_pgid = Some(Pid::try_from(*src)?);
-                Ok(0)
-            },
-            _ => Err(Errno::InvalidArgument)
+    fn tty_ioctl(&self, cmd: IoctlCmd, ptr: usize, len: usize) -> Result<usize, Errno> {
+        TtyRequests::<Self, N>(self).ioctl(cmd, ptr, len)
+    }
+
+    /// Handles a break condition detected on the line
+    fn recv_break(&self) {
+        let ring = self.ring();
+        if sysrq::is_enabled() {
+            ring.sysrq_pending.store(true, Ordering::Release);
+            return;
+        }
+        let iflag = ring.config.lock().iflag;
+
+        if iflag.contains(TermiosIflag::IGNBRK) {
+            return;
+        }
+        if iflag.contains(TermiosIflag::BRKINT) {
+            ring.interrupt_foreground();
+        } else {
+            ring.putc(0, false).ok();
         }
     }
 
@@ -81,15 +93,18 @@ pub trait TtyDevice<const N: usize>: SerialDevice {
     /// Receives input bytes and processes them
     fn recv_byte(&self, mut byte: u8) {
         let ring = self.ring();
-        let config = ring.config.lock();
-
-        if byte == b'@' {
-            use crate::mem::phys;
-            let stat = phys::statistics();
-            debugln!("Physical memory stats:");
-            debugln!("{:#?}", stat);
-            return;
+        if sysrq::is_enabled() {
+            if ring.sysrq_pending.swap(false, Ordering::AcqRel) {
+                if byte != sysrq::ESCAPE {
+                    sysrq::handle(byte, ring);
+                    return;
+                }
+            } else if byte == sysrq::ESCAPE {
+                ring.sysrq_pending.store(true, Ordering::Release);
+                return;
+            }
         }
+        let config = ring.config.lock();
 
         if byte == b'\r' && config.iflag.contains(TermiosIflag::ICRNL) {
             byte = b'\n';
@@ -121,14 +136,7 @@ pub trait TtyDevice<const N: usize>: SerialDevice {
 
         if byte == 0x3 && config.lflag.contains(TermiosLflag::ISIG) {
             drop(config);
-            let pgid = ring.inner.lock().fg_pgid;
-            if let Some(pgid) = pgid {
-                // TODO send to pgid
-                let proc = Process::get(pgid);
-                if let Some(proc) = proc {
-                    proc.set_signal(Signal::Interrupt);
-                }
-            }
+            ring.interrupt_foreground();
             return;
         }
 
@@ -255,6 +263,7 @@ impl<const N: usize> CharRing<N> {
         Self {
             inner: IrqSafeSpinLock::new(CharRingInner {
                 fg_pgid: None,
+                winsize: WindowSize::new(),
                 rd: 0,
                 wr: 0,
                 data: [0; N],
@@ -263,6 +272,7 @@ impl<const N: usize> CharRing<N> {
             config: IrqSafeSpinLock::new(Termios::new()),
             wait_read: Wait::new("tty_read"),
             wait_write: Wait::new("tty_write"),
+            sysrq_pending: AtomicBool::new(false),
         }
     }
 
@@ -318,6 +328,31 @@ impl<const N: usize> CharRing<N> {
         Ok(byte)
     }
 
+    /// Discards received data which has not been read yet
+    pub fn flush_input(&self) {
+        let mut lock = self.inner.lock();
+        lock.rd = lock.wr;
+        drop(lock);
+        self.wait_write.wakeup_one();
+    }
+
+    /// Sends [Signal::Interrupt] to the foreground process group
+    pub fn interrupt_foreground(&self) {
+        self.signal_foreground(Signal::Interrupt);
+    }
+
+    /// Sends `signal` to the foreground process group
+    pub fn signal_foreground(&self, signal: Signal) {
+        let pgid = self.inner.lock().fg_pgid;
+        if let Some(pgid) = pgid {
+            // TODO send to pgid
+            let proc = Process::get(pgid);
+            if let Some(proc) = proc {
+                proc.set_signal(signal);
+            }
+        }
+    }
+
     /// Puts a single byte to the buffer
     pub fn putc(&self, ch: u8, blocking: bool) -> Result<(), Errno> {
         let mut lock = self.inner.lock();
@@ -331,3 +366,94 @@ impl<const N: usize> CharRing<N> {
         Ok(())
     }
 }
+
+// Control requests of a TTY device
+struct TtyRequests<'a, T: ?Sized, const N: usize>(&'a T);
+
+#[ioctl_dispatch]
+impl<'a, T: TtyDevice<N> + ?Sized, const N: usize> TtyRequests<'a, T, N> {
+    #[ioctl(TtyGetAttributes)]
+    fn get_attributes(&self) -> Result<Termios, Errno> {
+        Ok(self.0.ring().config.lock().clone())
+    }
+
+    #[ioctl(TtySetAttributes)]
+    fn set_attributes(&self, termios: &Termios) -> Result<(), Errno> {
+        let config = &self.0.ring().config;
+        let old = config.lock().clone();
+        if old.speed != termios.speed || old.cflag != termios.cflag {
+            self.0.set_config(&SerialConfig::from_termios(termios)?)?;
+        }
+        *config.lock() = termios.clone();
+        Ok(())
+    }
+
+    #[ioctl(TtySetPgrp)]
+    fn set_pgrp(&self, pgid: &u32) -> Result<(), Errno> {
+        self.0.ring().inner.lock().fg_pgid = Some(Pid::try_from(*pgid)?);
+        Ok(())
+    }
+
+    #[ioctl(TtyGetPgrp)]
+    fn get_pgrp(&self) -> Result<u32, Errno> {
+        let pgid = self.0.ring().inner.lock().fg_pgid;
+        pgid.map(u32::from).ok_or(Errno::DoesNotExist)
+    }
+
+    #[ioctl(TtyDrain)]
+    fn drain(&self) -> Result<(), Errno> {
+        // Output is transmitted synchronously by write()
+        Ok(())
+    }
+
+    #[ioctl(TtyFlush)]
+    fn flush(&self, queue: &u32) -> Result<(), Errno> {
+        match FlushQueue::try_from(*queue)? {
+            FlushQueue::Input | FlushQueue::Both => self.0.ring().flush_input(),
+            // Nothing is queued for output
+            FlushQueue::Output => (),
+        }
+        Ok(())
+    }
+
+    #[ioctl(TtyGetWindowSize)]
+    fn get_window_size(&self) -> Result<WindowSize, Errno> {
+        Ok(self.0.ring().inner.lock().winsize)
+    }
+
+    #[ioctl(TtySetWindowSize)]
+    fn set_window_size(&self, winsize: &WindowSize) -> Result<(), Errno> {
+        if winsize.rows == 0 || winsize.cols == 0 {
+            return Err(Errno::InvalidArgument);
+        }
+        let ring = self.0.ring();
+        let old = core::mem::replace(&mut ring.inner.lock().winsize, *winsize);
+        if old != *winsize {
+            ring.signal_foreground(Signal::WindowChange);
+        }
+        Ok(())
+    }
+
+    #[ioctl(TtyGetModemLines)]
+    fn get_modem_lines(&self) -> Result<ModemLines, Errno> {
+        self.0.modem_lines()
+    }
+
+    #[ioctl(TtySetModemLines)]
+    fn set_modem_lines(&self, lines: &ModemLines) -> Result<(), Errno> {
+        self.0.set_modem_lines(*lines)
+    }
+
+    #[ioctl(TtySendBreak)]
+    fn send_break(&self, duration_ms: &u32) -> Result<(), Errno> {
+        let duration_ms = match *duration_ms {
+            0 => DEFAULT_BREAK_MS,
+            ms => ms.min(MAX_BREAK_MS),
+        };
+        let mut remaining = Duration::ZERO;
+        self.0.set_break(true)?;
+        let res = wait::sleep(Duration::from_millis(duration_ms as u64), &mut remaining);
+        self.0.set_break(false)?;
+        res
+    }
+}
@@ -0,0 +1,110 @@
+//! Device event notifications for userspace.
+//!
+//! Events are delivered as text lines of form `<action>@<device>` through
+//! `/dev/uevent` character device.
+use crate::dev::Device;
+use crate::proc::wait::{Wait, WAIT_SELECT};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{collections::LinkedList, format, string::String};
+use libsys::{error::Errno, ioctl::IoctlCmd};
+use vfs::CharDevice;
+
+/// Device event kinds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UeventAction {
+    /// Device or media has appeared
+    Add,
+    /// Device or media has disappeared
+    Remove,
+    /// Device state has changed
+    Change,
+}
+
+/// Character device for reading device events
+pub struct UeventDevice {
+    queue: IrqSafeSpinLock<LinkedList<String>>,
+    wait: Wait,
+}
+
+/// Maximum number of undelivered events, older ones are discarded
+const QUEUE_LIMIT: usize = 32;
+
+impl UeventAction {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Change => "change",
+        }
+    }
+}
+
+impl UeventDevice {
+    const fn new() -> Self {
+        Self {
+            queue: IrqSafeSpinLock::new(LinkedList::new()),
+            wait: Wait::new("uevent"),
+        }
+    }
+
+    fn push(&self, event: String) {
+        {
+            let mut queue = self.queue.lock();
+            if queue.len() == QUEUE_LIMIT {
+                queue.pop_front();
+            }
+            queue.push_back(event);
+        }
+        self.wait.wakeup_all();
+        WAIT_SELECT.wakeup_all();
+    }
+}
+
+impl Device for UeventDevice {
+    fn name(&self) -> &'static str {
+        "Device event notifier"
+    }
+
+    unsafe fn enable(&self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+impl CharDevice for UeventDevice {
+    /// Reads a single event line. If `data` is too short, the line is truncated.
+    fn read(&self, blocking: bool, data: &mut [u8]) -> Result<usize, Errno> {
+        loop {
+            if let Some(event) = self.queue.lock().pop_front() {
+                let bytes = event.as_bytes();
+                let count = core::cmp::min(bytes.len(), data.len());
+                data[..count].copy_from_slice(&bytes[..count]);
+                return Ok(count);
+            }
+            if !blocking {
+                return Err(Errno::WouldBlock);
+            }
+            self.wait.wait(None)?;
+        }
+    }
+
+    fn write(&self, _blocking: bool, _data: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::InvalidOperation)
+    }
+
+    fn is_ready(&self, write: bool) -> Result<bool, Errno> {
+        Ok(!write && !self.queue.lock().is_empty())
+    }
+
+    fn ioctl(&self, _cmd: IoctlCmd, _ptr: usize, _lim: usize) -> Result<usize, Errno> {
+        Err(Errno::InvalidArgument)
+    }
+}
+
+/// Queues an event for `device` to be read by userspace
+pub fn notify(action: UeventAction, device: &str) {
+    infoln!("uevent: {}@{}", action.as_str(), device);
+    UEVENT.push(format!("{}@{}\n", action.as_str(), device));
+}
+
+/// Device event notifier instance
+pub static UEVENT: UeventDevice = UeventDevice::new();
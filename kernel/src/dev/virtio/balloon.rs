@@ -0,0 +1,155 @@
+//! VirtIO memory balloon driver
+//!
+//! The host sets the desired balloon size in the device config. The driver
+//! inflates the balloon by taking pages offline and reporting their frame
+//! numbers to the host, and deflates it by reporting frames being returned
+//! to the page allocator.
+use super::{Virtqueue, VirtioMmio};
+use crate::mem::{
+    phys::{self, PageUsage},
+    virtualize, PAGE_SIZE,
+};
+use crate::proc::wait;
+use crate::sync::IrqSafeSpinLock;
+use crate::util::InitOnce;
+use alloc::vec::Vec;
+use core::time::Duration;
+use libsys::error::Errno;
+
+const CONFIG_NUM_PAGES: usize = 0x00;
+const CONFIG_ACTUAL: usize = 0x04;
+
+const QUEUE_INFLATE: u32 = 0;
+const QUEUE_DEFLATE: u32 = 1;
+
+/// Maximum number of page frames reported in a single request
+const BATCH: usize = 256;
+/// Number of free pages the balloon never takes away from the system
+const MIN_FREE_PAGES: usize = 1024;
+/// Interval between balloon size checks
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+struct Balloon {
+    transport: VirtioMmio,
+    inflate: Virtqueue,
+    deflate: Virtqueue,
+    // Page holding the frame number array sent to the host
+    pfn_page: usize,
+    pages: Vec<usize>,
+}
+
+static BALLOON: InitOnce<IrqSafeSpinLock<Balloon>> = InitOnce::new();
+
+impl Balloon {
+    fn report(&mut self, inflate: bool, pages: &[usize]) -> Result<(), Errno> {
+        let pfns =
+            unsafe { core::slice::from_raw_parts_mut(virtualize(self.pfn_page) as *mut u32, BATCH) };
+        for (pfn, &page) in pfns.iter_mut().zip(pages.iter()) {
+            *pfn = (page / PAGE_SIZE) as u32;
+        }
+
+        let queue = if inflate {
+            &mut self.inflate
+        } else {
+            &mut self.deflate
+        };
+        queue.push(self.pfn_page, pages.len() * 4, false);
+        self.transport.notify(queue);
+        queue.wait()
+    }
+
+    fn do_inflate(&mut self, count: usize) -> Result<(), Errno> {
+        let available = phys::statistics().available;
+        let count = count
+            .min(BATCH)
+            .min(available.saturating_sub(MIN_FREE_PAGES));
+        let mut batch = Vec::with_capacity(count);
+        for _ in 0..count {
+            match phys::offline_page() {
+                Ok(page) => batch.push(page),
+                Err(_) => break,
+            }
+        }
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        if let Err(err) = self.report(true, &batch) {
+            for page in batch {
+                unsafe {
+                    phys::online_page(page).unwrap();
+                }
+            }
+            return Err(err);
+        }
+        self.pages.extend_from_slice(&batch);
+        Ok(())
+    }
+
+    fn do_deflate(&mut self, count: usize) -> Result<(), Errno> {
+        let count = count.min(BATCH);
+        let batch = self.pages.split_off(self.pages.len() - count);
+        // The host must learn about the pages before the guest reuses them
+        if let Err(err) = self.report(false, &batch) {
+            self.pages.extend_from_slice(&batch);
+            return Err(err);
+        }
+        for page in batch {
+            unsafe {
+                phys::online_page(page).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<(), Errno> {
+        self.transport.ack_interrupt();
+        let target = self.transport.config_read32(CONFIG_NUM_PAGES) as usize;
+        let current = self.pages.len();
+
+        if target > current {
+            self.do_inflate(target - current)?;
+        } else if target < current {
+            self.do_deflate(current - target)?;
+        }
+
+        self.transport
+            .config_write32(CONFIG_ACTUAL, self.pages.len() as u32);
+        Ok(())
+    }
+}
+
+/// Initializes the balloon device attached to `transport`
+pub fn init(transport: VirtioMmio) -> Result<(), Errno> {
+    transport.init(0)?;
+    let inflate = transport.setup_queue(QUEUE_INFLATE, 16)?;
+    let deflate = transport.setup_queue(QUEUE_DEFLATE, 16)?;
+    let pfn_page = phys::alloc_page(PageUsage::Kernel)?;
+    transport.set_driver_ok();
+
+    infoln!("virtio-balloon initialized");
+    BALLOON.init(IrqSafeSpinLock::new(Balloon {
+        transport,
+        inflate,
+        deflate,
+        pfn_page,
+        pages: Vec::new(),
+    }));
+    Ok(())
+}
+
+/// Returns `true` if a balloon device was found
+pub fn is_present() -> bool {
+    BALLOON.is_initialized()
+}
+
+/// Kernel process function which follows balloon size requests from the host
+pub fn balloon_fn() -> ! {
+    loop {
+        if let Err(err) = BALLOON.get().lock().poll() {
+            warnln!("virtio-balloon: {:?}", err);
+        }
+        let mut remaining = Duration::ZERO;
+        wait::sleep(POLL_INTERVAL, &mut remaining).ok();
+    }
+}
@@ -0,0 +1,210 @@
+//! VirtIO over MMIO transport
+//!
+//! Both legacy (version 1) and modern (version 2) transports are supported.
+//! Virtqueues are used synchronously: a request is submitted and the driver
+//! waits for the device to consume it.
+use crate::mem::virt::DeviceMemory;
+use core::sync::atomic::{fence, Ordering};
+use libsys::error::Errno;
+use tock_registers::{
+    interfaces::{Readable, Writeable},
+    register_structs,
+    registers::{ReadOnly, ReadWrite, WriteOnly},
+};
+
+pub mod balloon;
+mod queue;
+
+pub use queue::Virtqueue;
+
+register_structs! {
+    #[allow(non_snake_case)]
+    Regs {
+        (0x000 => MagicValue: ReadOnly<u32>),
+        (0x004 => Version: ReadOnly<u32>),
+        (0x008 => DeviceID: ReadOnly<u32>),
+        (0x00C => VendorID: ReadOnly<u32>),
+        (0x010 => DeviceFeatures: ReadOnly<u32>),
+        (0x014 => DeviceFeaturesSel: WriteOnly<u32>),
+        (0x018 => _res0),
+        (0x020 => DriverFeatures: WriteOnly<u32>),
+        (0x024 => DriverFeaturesSel: WriteOnly<u32>),
+        (0x028 => GuestPageSize: WriteOnly<u32>),
+        (0x02C => _res1),
+        (0x030 => QueueSel: WriteOnly<u32>),
+        (0x034 => QueueNumMax: ReadOnly<u32>),
+        (0x038 => QueueNum: WriteOnly<u32>),
+        (0x03C => QueueAlign: WriteOnly<u32>),
+        (0x040 => QueuePFN: ReadWrite<u32>),
+        (0x044 => QueueReady: ReadWrite<u32>),
+        (0x048 => _res2),
+        (0x050 => QueueNotify: WriteOnly<u32>),
+        (0x054 => _res3),
+        (0x060 => InterruptStatus: ReadOnly<u32>),
+        (0x064 => InterruptACK: WriteOnly<u32>),
+        (0x068 => _res4),
+        (0x070 => Status: ReadWrite<u32>),
+        (0x074 => _res5),
+        (0x080 => QueueDescLow: WriteOnly<u32>),
+        (0x084 => QueueDescHigh: WriteOnly<u32>),
+        (0x088 => _res6),
+        (0x090 => QueueDriverLow: WriteOnly<u32>),
+        (0x094 => QueueDriverHigh: WriteOnly<u32>),
+        (0x098 => _res7),
+        (0x0A0 => QueueDeviceLow: WriteOnly<u32>),
+        (0x0A4 => QueueDeviceHigh: WriteOnly<u32>),
+        (0x0A8 => _res8),
+        (0x0FC => ConfigGeneration: ReadOnly<u32>),
+        (0x100 => @END),
+    }
+}
+
+const MAGIC: u32 = 0x74726976;
+const STATUS_ACKNOWLEDGE: u32 = 1 << 0;
+const STATUS_DRIVER: u32 = 1 << 1;
+const STATUS_DRIVER_OK: u32 = 1 << 2;
+const STATUS_FEATURES_OK: u32 = 1 << 3;
+/// Feature bit 32: device conforms to VirtIO 1.0+
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+/// Size of a single transport register window
+const SLOT_SIZE: usize = 0x200;
+
+/// Device ID of the memory balloon
+const DEVICE_BALLOON: u32 = 5;
+
+/// Single VirtIO MMIO transport instance
+pub struct VirtioMmio {
+    base: usize,
+    version: u32,
+}
+
+impl VirtioMmio {
+    #[inline(always)]
+    fn regs(&self) -> &Regs {
+        unsafe { &*(self.base as *const Regs) }
+    }
+
+    /// Returns the type of the device attached to the transport
+    pub fn device_id(&self) -> u32 {
+        self.regs().DeviceID.get()
+    }
+
+    /// Resets the device and negotiates `features` (low 32 bits) with it
+    pub fn init(&self, features: u32) -> Result<(), Errno> {
+        let regs = self.regs();
+        regs.Status.set(0);
+        regs.Status.set(STATUS_ACKNOWLEDGE);
+        regs.Status.set(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        regs.DeviceFeaturesSel.set(0);
+        let offered = regs.DeviceFeatures.get();
+        regs.DriverFeaturesSel.set(0);
+        regs.DriverFeatures.set(offered & features);
+
+        if self.version == 1 {
+            regs.GuestPageSize.set(0x1000);
+            return Ok(());
+        }
+
+        regs.DeviceFeaturesSel.set(1);
+        if regs.DeviceFeatures.get() & FEATURE_VERSION_1 == 0 {
+            return Err(Errno::NotImplemented);
+        }
+        regs.DriverFeaturesSel.set(1);
+        regs.DriverFeatures.set(FEATURE_VERSION_1);
+
+        regs.Status.set(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if regs.Status.get() & STATUS_FEATURES_OK == 0 {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Marks the driver as ready to operate the device
+    pub fn set_driver_ok(&self) {
+        let regs = self.regs();
+        regs.Status.set(regs.Status.get() | STATUS_DRIVER_OK);
+    }
+
+    /// Allocates and registers virtqueue `index` with the device
+    pub fn setup_queue(&self, index: u32, max_size: u16) -> Result<Virtqueue, Errno> {
+        let regs = self.regs();
+        regs.QueueSel.set(index);
+        let size = core::cmp::min(regs.QueueNumMax.get(), max_size as u32) as u16;
+        if size == 0 {
+            return Err(Errno::DoesNotExist);
+        }
+        let queue = Virtqueue::new(index, size)?;
+        regs.QueueNum.set(size as u32);
+
+        if self.version == 1 {
+            regs.QueueAlign.set(0x1000);
+            regs.QueuePFN.set((queue.desc_phys() >> 12) as u32);
+        } else {
+            let (desc, driver, device) = (queue.desc_phys(), queue.avail_phys(), queue.used_phys());
+            regs.QueueDescLow.set(desc as u32);
+            regs.QueueDescHigh.set((desc >> 32) as u32);
+            regs.QueueDriverLow.set(driver as u32);
+            regs.QueueDriverHigh.set((driver >> 32) as u32);
+            regs.QueueDeviceLow.set(device as u32);
+            regs.QueueDeviceHigh.set((device >> 32) as u32);
+            regs.QueueReady.set(1);
+        }
+
+        Ok(queue)
+    }
+
+    /// Notifies the device of new buffers in `queue`
+    pub fn notify(&self, queue: &Virtqueue) {
+        fence(Ordering::SeqCst);
+        self.regs().QueueNotify.set(queue.index());
+    }
+
+    /// Acknowledges pending interrupts, returning their status bits
+    pub fn ack_interrupt(&self) -> u32 {
+        let regs = self.regs();
+        let status = regs.InterruptStatus.get();
+        regs.InterruptACK.set(status);
+        status
+    }
+
+    /// Reads a 32-bit field at `offset` in device-specific configuration space
+    pub fn config_read32(&self, offset: usize) -> u32 {
+        unsafe { core::ptr::read_volatile((self.base + 0x100 + offset) as *const u32) }
+    }
+
+    /// Writes a 32-bit field at `offset` in device-specific configuration space
+    pub fn config_write32(&self, offset: usize, value: u32) {
+        unsafe { core::ptr::write_volatile((self.base + 0x100 + offset) as *mut u32, value) }
+    }
+}
+
+/// Scans `count` transport slots starting at physical address `base` and
+/// initializes the drivers for devices found there
+pub fn probe_mmio(base: usize, count: usize) -> Result<(), Errno> {
+    let pages = (count * SLOT_SIZE + 0xFFF) / 0x1000;
+    let mmio = DeviceMemory::map("virtio-mmio", base, pages)?;
+
+    for i in 0..count {
+        let regs = unsafe { &*((mmio.base() + i * SLOT_SIZE) as *const Regs) };
+        if regs.MagicValue.get() != MAGIC || regs.DeviceID.get() == 0 {
+            continue;
+        }
+        let transport = VirtioMmio {
+            base: mmio.base() + i * SLOT_SIZE,
+            version: regs.Version.get(),
+        };
+        let id = transport.device_id();
+
+        if id == DEVICE_BALLOON {
+            if let Err(e) = balloon::init(transport) {
+                warnln!("virtio-balloon init failed: {:?}", e);
+            }
+        } else {
+            infoln!("virtio-mmio@{:#x}: unsupported device {}", base + i * SLOT_SIZE, id);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Split virtqueue implementation
+use crate::mem::{
+    phys::{self, PageUsage},
+    virtualize, PAGE_SIZE,
+};
+use core::sync::atomic::{fence, Ordering};
+use libsys::{error::Errno, mem::memset};
+
+const DESC_F_WRITE: u16 = 1 << 1;
+
+/// Number of polling iterations before a request is considered lost
+const SPIN_LIMIT: usize = 10000000;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// Virtqueue laid out as required by legacy transports: descriptor table,
+/// available ring and a page-aligned used ring in one contiguous region
+pub struct Virtqueue {
+    index: u32,
+    size: u16,
+    phys: usize,
+    avail_idx: u16,
+}
+
+fn align_up(value: usize) -> usize {
+    (value + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+impl Virtqueue {
+    /// Allocates a zeroed virtqueue of `size` entries
+    pub fn new(index: u32, size: u16) -> Result<Self, Errno> {
+        let size_bytes = align_up(16 * size as usize + 6 + 2 * size as usize)
+            + align_up(6 + 8 * size as usize);
+        let phys = phys::alloc_contiguous_pages(PageUsage::Kernel, size_bytes / PAGE_SIZE)?;
+        unsafe {
+            memset(virtualize(phys) as *mut u8, 0, size_bytes);
+        }
+        Ok(Self {
+            index,
+            size,
+            phys,
+            avail_idx: 0,
+        })
+    }
+
+    /// Returns the index of this queue within its device
+    pub const fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the physical address of the descriptor table
+    pub const fn desc_phys(&self) -> usize {
+        self.phys
+    }
+
+    /// Returns the physical address of the available ring
+    pub const fn avail_phys(&self) -> usize {
+        self.phys + 16 * self.size as usize
+    }
+
+    /// Returns the physical address of the used ring
+    pub fn used_phys(&self) -> usize {
+        self.phys + align_up(16 * self.size as usize + 6 + 2 * self.size as usize)
+    }
+
+    #[inline]
+    fn ptr<T>(&self, phys: usize) -> *mut T {
+        virtualize(phys) as *mut T
+    }
+
+    /// Places a single buffer at `buf_phys..buf_phys + len` into the queue.
+    /// If `device_writes` is set, the buffer is written by the device.
+    /// The caller must then notify the device and call [Virtqueue::wait].
+    pub fn push(&mut self, buf_phys: usize, len: usize, device_writes: bool) {
+        // Only one request is in flight at a time, so descriptor 0 is always free
+        unsafe {
+            self.ptr::<Descriptor>(self.phys).write_volatile(Descriptor {
+                addr: buf_phys as u64,
+                len: len as u32,
+                flags: if device_writes { DESC_F_WRITE } else { 0 },
+                next: 0,
+            });
+
+            let avail = self.avail_phys();
+            let slot = 4 + 2 * (self.avail_idx % self.size) as usize;
+            self.ptr::<u16>(avail + slot).write_volatile(0);
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            self.ptr::<u16>(avail + 2).write_volatile(self.avail_idx);
+        }
+    }
+
+    /// Waits until the device has consumed all the submitted buffers
+    pub fn wait(&self) -> Result<(), Errno> {
+        let used_idx = self.ptr::<u16>(self.used_phys() + 2);
+        for _ in 0..SPIN_LIMIT {
+            fence(Ordering::SeqCst);
+            if unsafe { used_idx.read_volatile() } == self.avail_idx {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(Errno::TimedOut)
+    }
+}
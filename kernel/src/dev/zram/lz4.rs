@@ -0,0 +1,158 @@
+//! Minimal LZ4 block format compressor/decompressor
+//!
+//! Only single blocks of up to 64K are supported, which is enough for
+//! compressing individual pages.
+
+const MIN_MATCH: usize = 4;
+// Matches may not start within the last 12 bytes, the last 5 bytes are
+// always literals (LZ4 block format end-of-block conditions)
+const MF_LIMIT: usize = 12;
+const LAST_LITERALS: usize = 5;
+const HASH_BITS: usize = 12;
+
+struct Writer<'a> {
+    dst: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, byte: u8) -> Option<()> {
+        *self.dst.get_mut(self.pos)? = byte;
+        self.pos += 1;
+        Some(())
+    }
+
+    fn extend(&mut self, data: &[u8]) -> Option<()> {
+        self.dst
+            .get_mut(self.pos..self.pos + data.len())?
+            .copy_from_slice(data);
+        self.pos += data.len();
+        Some(())
+    }
+
+    fn push_length(&mut self, mut len: usize) -> Option<()> {
+        while len >= 255 {
+            self.push(255)?;
+            len -= 255;
+        }
+        self.push(len as u8)
+    }
+
+    fn sequence(&mut self, literals: &[u8], m: Option<(usize, usize)>) -> Option<()> {
+        let lit_len = literals.len();
+        let match_len = m.map(|(_, len)| len - MIN_MATCH).unwrap_or(0);
+        self.push(((lit_len.min(15) as u8) << 4) | match_len.min(15) as u8)?;
+        if lit_len >= 15 {
+            self.push_length(lit_len - 15)?;
+        }
+        self.extend(literals)?;
+
+        if let Some((offset, _)) = m {
+            self.extend(&(offset as u16).to_le_bytes())?;
+            if match_len >= 15 {
+                self.push_length(match_len - 15)?;
+            }
+        }
+        Some(())
+    }
+}
+
+#[inline]
+fn read_u32(src: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], src[pos + 3]])
+}
+
+#[inline]
+fn hash(value: u32) -> usize {
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses `src` into `dst`. Returns the compressed size or `None` if
+/// the result does not fit into `dst`.
+pub fn compress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    assert!(src.len() < 0x10000);
+    let mut out = Writer { dst, pos: 0 };
+    let mut table = [0u16; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    while pos + MF_LIMIT < src.len() {
+        let value = read_u32(src, pos);
+        let slot = &mut table[hash(value)];
+        let candidate = *slot as usize;
+        *slot = pos as u16;
+
+        if candidate >= pos || read_u32(src, candidate) != value {
+            pos += 1;
+            continue;
+        }
+
+        let max_len = src.len() - LAST_LITERALS - pos;
+        let mut len = MIN_MATCH;
+        while len < max_len && src[candidate + len] == src[pos + len] {
+            len += 1;
+        }
+
+        out.sequence(&src[anchor..pos], Some((pos - candidate, len)))?;
+        pos += len;
+        anchor = pos;
+    }
+
+    out.sequence(&src[anchor..], None)?;
+    Some(out.pos)
+}
+
+fn read_length(src: &[u8], pos: &mut usize, mut len: usize) -> Option<usize> {
+    loop {
+        let byte = *src.get(*pos)?;
+        *pos += 1;
+        len += byte as usize;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+/// Decompresses `src` into `dst`. Returns the decompressed size or `None`
+/// if the input is malformed or does not fit into `dst`.
+pub fn decompress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut ip = 0;
+    let mut op = 0;
+
+    loop {
+        let token = *src.get(ip)?;
+        ip += 1;
+
+        let mut lit_len = (token >> 4) as usize;
+        if lit_len == 15 {
+            lit_len = read_length(src, &mut ip, lit_len)?;
+        }
+        dst.get_mut(op..op + lit_len)?
+            .copy_from_slice(src.get(ip..ip + lit_len)?);
+        ip += lit_len;
+        op += lit_len;
+
+        if ip == src.len() {
+            return Some(op);
+        }
+
+        let offset = u16::from_le_bytes([*src.get(ip)?, *src.get(ip + 1)?]) as usize;
+        ip += 2;
+        if offset == 0 || offset > op {
+            return None;
+        }
+        let mut match_len = (token & 0xF) as usize;
+        if match_len == 15 {
+            match_len = read_length(src, &mut ip, match_len)?;
+        }
+        match_len += MIN_MATCH;
+        if op + match_len > dst.len() {
+            return None;
+        }
+        // Byte-wise: the match may overlap the data being written
+        for i in op..op + match_len {
+            dst[i] = dst[i - offset];
+        }
+        op += match_len;
+    }
+}
@@ -0,0 +1,211 @@
+//! Compressed RAM block device
+//!
+//! Every 4K block is stored LZ4-compressed on the kernel heap. Blocks filled
+//! with a single byte value take no storage, and blocks which do not
+//! compress well are kept in whole physical pages so they don't take
+//! up the heap.
+use crate::mem::{
+    phys::{self, PageUsage},
+    virtualize, PAGE_SIZE,
+};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{boxed::Box, vec::Vec};
+use libsys::{
+    error::Errno,
+    ioctl::{IoctlCmd, ZramStats},
+};
+use vfs::{BlockDevice, BlockError};
+
+mod lz4;
+
+enum Slot {
+    Same(u8),
+    Compressed(Box<[u8]>),
+    Raw(usize),
+}
+
+struct ZramInner {
+    // Unwritten slots are `None`, read as zeros
+    slots: Vec<Option<Slot>>,
+    stats: ZramStats,
+}
+
+/// Compressed RAM block device
+pub struct Zram {
+    inner: IrqSafeSpinLock<ZramInner>,
+    size: usize,
+    heap_limit: usize,
+}
+
+/// Blocks compressed to more than this size are stored uncompressed
+const MAX_COMPRESSED: usize = PAGE_SIZE * 3 / 4;
+
+/// zram0 instance: 32MiB of blocks, with at most 4MiB of kernel heap
+/// used for compressed data
+pub static ZRAM: Zram = Zram::new(32 * 1024 * 1024, 4 * 1024 * 1024);
+
+impl Slot {
+    fn read(&self, buf: &mut [u8]) {
+        match self {
+            Slot::Same(byte) => buf.fill(*byte),
+            Slot::Compressed(data) => {
+                let len = lz4::decompress(data, buf);
+                assert_eq!(len, Some(PAGE_SIZE));
+            }
+            Slot::Raw(page) => {
+                buf.copy_from_slice(unsafe {
+                    core::slice::from_raw_parts(virtualize(*page) as *const u8, PAGE_SIZE)
+                });
+            }
+        }
+    }
+}
+
+impl ZramInner {
+    fn store(&mut self, index: usize, data: &[u8], heap_limit: usize) -> Result<(), Errno> {
+        let slot = if data.iter().all(|&b| b == data[0]) {
+            Slot::Same(data[0])
+        } else {
+            let mut buf = [0u8; MAX_COMPRESSED];
+            match lz4::compress(data, &mut buf) {
+                Some(len) => {
+                    if self.stats.compressed_size as usize + len > heap_limit {
+                        return Err(Errno::NoSpace);
+                    }
+                    Slot::Compressed(Box::from(&buf[..len]))
+                }
+                None => {
+                    let page = phys::alloc_page(PageUsage::Filesystem)?;
+                    unsafe {
+                        core::slice::from_raw_parts_mut(virtualize(page) as *mut u8, PAGE_SIZE)
+                    }
+                    .copy_from_slice(data);
+                    Slot::Raw(page)
+                }
+            }
+        };
+
+        self.account(&slot, true);
+        if let Some(old) = self.slots[index].replace(slot) {
+            self.release(old);
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, slot: Slot) {
+        self.account(&slot, false);
+        if let Slot::Raw(page) = slot {
+            unsafe {
+                phys::free_page(page).unwrap();
+            }
+        }
+    }
+
+    fn account(&mut self, slot: &Slot, add: bool) {
+        let stats = &mut self.stats;
+        let (count, bytes) = match slot {
+            Slot::Same(_) => (&mut stats.same_pages, 0),
+            Slot::Compressed(data) => (&mut stats.compressed_pages, data.len()),
+            Slot::Raw(_) => (&mut stats.raw_pages, 0),
+        };
+        if add {
+            *count += 1;
+            stats.compressed_size += bytes as u64;
+            stats.data_size += PAGE_SIZE as u64;
+        } else {
+            *count -= 1;
+            stats.compressed_size -= bytes as u64;
+            stats.data_size -= PAGE_SIZE as u64;
+        }
+    }
+}
+
+#[ioctl_dispatch(dispatch_ioctl)]
+impl Zram {
+    /// Constructs a device of `size` bytes, using at most `heap_limit` bytes
+    /// of the kernel heap for compressed data
+    pub const fn new(size: usize, heap_limit: usize) -> Self {
+        Self {
+            inner: IrqSafeSpinLock::new(ZramInner {
+                slots: Vec::new(),
+                stats: ZramStats {
+                    data_size: 0,
+                    compressed_size: 0,
+                    same_pages: 0,
+                    compressed_pages: 0,
+                    raw_pages: 0,
+                },
+            }),
+            size,
+            heap_limit,
+        }
+    }
+
+    /// Returns current usage and compression statistics
+    pub fn stats(&self) -> ZramStats {
+        self.inner.lock().stats
+    }
+
+    #[ioctl(ZramGetStats)]
+    fn get_stats(&self) -> Result<ZramStats, Errno> {
+        Ok(self.stats())
+    }
+
+    fn check_range(&self, pos: usize, len: usize) -> Result<(), BlockError> {
+        if pos % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 || pos + len > self.size {
+            Err(BlockError::Other(Errno::InvalidArgument))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl BlockDevice for Zram {
+    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.check_range(pos, buf.len())?;
+        let inner = self.inner.lock();
+        for (i, block) in buf.chunks_exact_mut(PAGE_SIZE).enumerate() {
+            match inner.slots.get(pos / PAGE_SIZE + i) {
+                Some(Some(slot)) => slot.read(block),
+                _ => block.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+        self.check_range(pos, buf.len())?;
+        let mut inner = self.inner.lock();
+        if inner.slots.is_empty() {
+            inner.slots.resize_with(self.size / PAGE_SIZE, || None);
+        }
+        for (i, block) in buf.chunks_exact(PAGE_SIZE).enumerate() {
+            inner.store(pos / PAGE_SIZE + i, block, self.heap_limit)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize, Errno> {
+        Ok(self.size)
+    }
+
+    fn block_size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    // Discarded blocks are released and read as zeros
+    fn discard(&self, pos: usize, len: usize) -> Result<(), Errno> {
+        self.check_range(pos, len)?;
+        let mut inner = self.inner.lock();
+        for index in pos / PAGE_SIZE..(pos + len) / PAGE_SIZE {
+            if let Some(slot) = inner.slots.get_mut(index).and_then(Option::take) {
+                inner.release(slot);
+            }
+        }
+        Ok(())
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, ptr: usize, len: usize) -> Result<usize, Errno> {
+        self.dispatch_ioctl(cmd, ptr, len)
+    }
+}
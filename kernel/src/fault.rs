@@ -0,0 +1,158 @@
+//! Deterministic fault injection for testing error handling paths.
+//!
+//! Faults are injected pseudo-randomly, driven by a xorshift generator.
+//! The generator seed is printed at boot, and can be fixed at build time
+//! by setting `FAULT_SEED` environment variable to reproduce a run.
+use crate::sync::IrqSafeSpinLock;
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_a::registers::CNTPCT_EL0;
+use libsys::{error::Errno, ioctl::IoctlCmd};
+use tock_registers::interfaces::Readable;
+use vfs::{BlockDevice, BlockError, BlockErrorCounters};
+
+/// Kinds of injectable faults
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FaultKind {
+    /// Physical page allocation failure
+    Alloc,
+    /// Block device I/O error
+    BlockIo,
+    /// Read returning less data than requested
+    ShortRead,
+}
+
+impl FaultKind {
+    /// Probability of injection, 1 in N
+    const fn rate(self) -> u64 {
+        match self {
+            Self::Alloc => 256,
+            Self::BlockIo => 64,
+            Self::ShortRead => 8,
+        }
+    }
+}
+
+/// [BlockDevice] wrapper which fails I/O requests with [FaultKind::BlockIo]
+pub struct FaultyBlockDevice {
+    inner: &'static dyn BlockDevice,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STATE: IrqSafeSpinLock<u64> = IrqSafeSpinLock::new(0);
+
+fn next() -> u64 {
+    let mut state = STATE.lock();
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *state = x;
+    x
+}
+
+/// Returns `true` if a fault of `kind` should be injected at the call site
+pub fn should_fail(kind: FaultKind) -> bool {
+    if !ENABLED.load(Ordering::Acquire) {
+        return false;
+    }
+    if next() % kind.rate() == 0 {
+        debugln!("Injected fault: {:?}", kind);
+        true
+    } else {
+        false
+    }
+}
+
+/// Picks a random transient or permanent block device error
+fn block_error() -> BlockError {
+    match next() % 3 {
+        0 => BlockError::Timeout,
+        1 => BlockError::Crc,
+        _ => BlockError::Other(Errno::DeviceError),
+    }
+}
+
+/// Possibly truncates `buf` to simulate a short read. Empty and
+/// single-byte buffers are left intact.
+pub fn short_buffer(buf: &mut [u8]) -> &mut [u8] {
+    if buf.len() > 1 && should_fail(FaultKind::ShortRead) {
+        let len = 1 + (next() as usize) % (buf.len() - 1);
+        &mut buf[..len]
+    } else {
+        buf
+    }
+}
+
+impl FaultyBlockDevice {
+    /// Wraps `inner` device, leaking the wrapper to obtain a `'static`
+    /// reference to it
+    pub fn wrap(inner: &'static dyn BlockDevice) -> &'static dyn BlockDevice {
+        Box::leak(Box::new(Self { inner }))
+    }
+}
+
+impl BlockDevice for FaultyBlockDevice {
+    fn read(&self, pos: usize, buf: &mut [u8]) -> Result<(), BlockError> {
+        if should_fail(FaultKind::BlockIo) {
+            return Err(block_error());
+        }
+        self.inner.read(pos, buf)
+    }
+
+    fn write(&self, pos: usize, buf: &[u8]) -> Result<(), BlockError> {
+        if should_fail(FaultKind::BlockIo) {
+            return Err(block_error());
+        }
+        self.inner.write(pos, buf)
+    }
+
+    fn is_removable(&self) -> bool {
+        self.inner.is_removable()
+    }
+
+    fn is_media_present(&self) -> bool {
+        self.inner.is_media_present()
+    }
+
+    fn media_changed(&self, present: bool) {
+        self.inner.media_changed(present)
+    }
+
+    fn error_counters(&self) -> Option<&BlockErrorCounters> {
+        self.inner.error_counters()
+    }
+
+    fn size(&self) -> Result<usize, Errno> {
+        self.inner.size()
+    }
+
+    fn flush(&self) -> Result<(), Errno> {
+        self.inner.flush()
+    }
+
+    fn discard(&self, pos: usize, len: usize) -> Result<(), Errno> {
+        self.inner.discard(pos, len)
+    }
+
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, ptr: usize, len: usize) -> Result<usize, Errno> {
+        self.inner.ioctl(cmd, ptr, len)
+    }
+}
+
+/// Seeds the generator and enables fault injection. Faults are never
+/// injected before this function is called, so early boot is unaffected.
+pub fn init() {
+    let seed = option_env!("FAULT_SEED")
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| CNTPCT_EL0.get());
+    // xorshift state must be non-zero
+    let seed = if seed == 0 { 1 } else { seed };
+    *STATE.lock() = seed;
+    ENABLED.store(true, Ordering::Release);
+    infoln!("Fault injection enabled, seed: {}", seed);
+}
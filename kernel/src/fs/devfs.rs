@@ -1,9 +1,25 @@
 //! Device list pseudo-filesystem
+use crate::dev::media;
+use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec::Vec};
 use core::sync::atomic::{AtomicUsize, Ordering};
-use libsys::{stat::FileMode, error::Errno};
-use vfs::{CharDevice, CharDeviceWrapper, Vnode, VnodeKind, VnodeRef};
+use libsys::{
+    error::Errno,
+    stat::{makedev, FileMode},
+};
+use vfs::{BlockDevice, BlockDeviceWrapper, CharDevice, CharDeviceWrapper, Vnode, VnodeKind, VnodeRef};
+
+/// Major number of memory pseudo-devices (zero, random)
+pub const MAJOR_MEM: u32 = 1;
+/// Major number of serial TTYs
+pub const MAJOR_TTY: u32 = 4;
+/// Major number of miscellaneous character devices
+pub const MAJOR_MISC: u32 = 10;
+/// Major number of SD/MMC block devices
+pub const MAJOR_MMC: u32 = 179;
+/// Major number of compressed RAM block devices
+pub const MAJOR_ZRAM: u32 = 252;
 
 /// Possible character device kinds
 #[derive(Debug)]
@@ -13,12 +29,54 @@ pub enum CharDeviceType {
 }
 
 static DEVFS_ROOT: InitOnce<VnodeRef> = InitOnce::new();
+static BLOCK_DEVICES: IrqSafeSpinLock<Vec<(u32, &'static dyn BlockDevice)>> =
+    IrqSafeSpinLock::new(Vec::new());
+
+fn new_directory(name: &str) -> VnodeRef {
+    let node = Vnode::new(name, VnodeKind::Directory, Vnode::CACHE_READDIR | Vnode::CACHE_STAT);
+    node.props_mut().mode = FileMode::default_dir();
+    node
+}
 
 /// Initializes devfs
 pub fn init() {
-    let node = Vnode::new("", VnodeKind::Directory, Vnode::CACHE_READDIR | Vnode::CACHE_STAT);
-    node.props_mut().mode = FileMode::default_dir();
-    DEVFS_ROOT.init(node);
+    DEVFS_ROOT.init(new_directory(""));
+}
+
+/// Attaches `node` at `path` relative to devfs root, creating any missing
+/// intermediate directories (e.g. "input/event0")
+fn attach_at(path: &str, node: VnodeRef) -> Result<(), Errno> {
+    let mut dir = DEVFS_ROOT.get().clone();
+    let mut components = path.split('/').filter(|c| !c.is_empty()).peekable();
+    let mut name = None;
+
+    while let Some(component) = components.next() {
+        if components.peek().is_none() {
+            name = Some(component);
+            break;
+        }
+        dir = match dir.lookup(component) {
+            Some(child) if child.is_directory() => child,
+            Some(_) => return Err(Errno::NotADirectory),
+            None => {
+                let child = new_directory(component);
+                dir.attach(child.clone());
+                child
+            }
+        };
+    }
+
+    let name = name.ok_or(Errno::InvalidArgument)?;
+    if dir.lookup(name).is_some() {
+        return Err(Errno::AlreadyExists);
+    }
+    dir.attach(node);
+    Ok(())
+}
+
+/// Returns the last component of a devfs `path`, used as the node name
+fn base_name(path: &str) -> &str {
+    path.trim_end_matches('/').rsplit('/').next().unwrap()
 }
 
 /// Returns devfs root node reference
@@ -26,16 +84,73 @@ pub fn root() -> &'static VnodeRef {
     DEVFS_ROOT.get()
 }
 
-pub fn add_named_char_device(dev: &'static dyn CharDevice, name: &str) -> Result<(), Errno> {
+fn find_in(dir: &VnodeRef, kind: VnodeKind, rdev: u32) -> Option<VnodeRef> {
+    let mut res = None;
+    dir.for_each_entry(0, usize::MAX, |_, node| {
+        if res.is_some() {
+            return;
+        }
+        if node.kind() == kind && node.props().rdev == rdev {
+            res = Some(node.clone());
+        } else if node.is_directory() {
+            res = find_in(node, kind, rdev);
+        }
+    });
+    res
+}
+
+/// Finds a device node of given `kind` with device number `rdev`,
+/// searching subdirectories as well
+pub fn find_device(kind: VnodeKind, rdev: u32) -> Option<VnodeRef> {
+    find_in(DEVFS_ROOT.get(), kind, rdev)
+}
+
+/// Returns the block device registered with device number `rdev`
+pub fn block_device(rdev: u32) -> Option<&'static dyn BlockDevice> {
+    BLOCK_DEVICES
+        .lock()
+        .iter()
+        .find(|(num, _)| *num == rdev)
+        .map(|&(_, dev)| dev)
+}
+
+/// Returns the block device at devfs `path`, either relative to devfs
+/// root or starting with "/dev/"
+pub fn find_block_device(path: &str) -> Option<&'static dyn BlockDevice> {
+    let path = path.strip_prefix("/dev/").unwrap_or(path);
+    let mut node = DEVFS_ROOT.get().clone();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        if !node.is_directory() {
+            return None;
+        }
+        node = node.lookup(component)?;
+    }
+    if node.kind() != VnodeKind::Block {
+        return None;
+    }
+    let rdev = node.props().rdev;
+    block_device(rdev)
+}
+
+/// Adds a character device node with device number `rdev` and an explicit `name`.
+/// A path-like `name` (e.g. "pts/0") places the node in a subdirectory,
+/// which is created if missing.
+pub fn add_named_char_device(
+    dev: &'static dyn CharDevice,
+    name: &str,
+    rdev: u32,
+) -> Result<(), Errno> {
     infoln!("Add char device: {}", name);
 
-    let node = Vnode::new(name, VnodeKind::Char, Vnode::CACHE_STAT);
-    node.props_mut().mode = FileMode::from_bits(0o600).unwrap() | FileMode::S_IFCHR;
+    let node = Vnode::new(base_name(name), VnodeKind::Char, Vnode::CACHE_STAT);
+    {
+        let mut props = node.props_mut();
+        props.mode = FileMode::from_bits(0o600).unwrap() | FileMode::S_IFCHR;
+        props.rdev = rdev;
+    }
     node.set_data(Box::new(CharDeviceWrapper::new(dev)));
 
-    DEVFS_ROOT.get().attach(node);
-
-    Ok(())
+    attach_at(name, node)
 }
 
 /// Adds a character device node to the filesystem
@@ -56,5 +171,31 @@ pub fn add_char_device(dev: &'static dyn CharDevice, kind: CharDeviceType) -> Re
 
     let name = core::str::from_utf8(&buf[..=prefix.len()]).map_err(|_| Errno::InvalidArgument)?;
 
-    add_named_char_device(dev, name)
+    add_named_char_device(dev, name, makedev(MAJOR_TTY, 64 + value as u32))
+}
+
+/// Adds a block device node with device number `rdev` to the filesystem.
+/// Like with [add_named_char_device], `name` may contain subdirectories.
+pub fn add_named_block_device(
+    dev: &'static dyn BlockDevice,
+    name: &str,
+    rdev: u32,
+) -> Result<(), Errno> {
+    infoln!("Add block device: {}", name);
+    #[cfg(feature = "fault_inject")]
+    let dev = crate::fault::FaultyBlockDevice::wrap(dev);
+    let node = Vnode::new(base_name(name), VnodeKind::Block, Vnode::SEEKABLE);
+    {
+        let mut props = node.props_mut();
+        props.mode = FileMode::from_bits(0o600).unwrap() | FileMode::S_IFBLK;
+        props.rdev = rdev;
+    }
+    node.set_data(Box::new(BlockDeviceWrapper::new(dev)));
+    attach_at(name, node)?;
+    BLOCK_DEVICES.lock().push((rdev, dev));
+    if dev.is_removable() {
+        media::register(dev, name);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,75 @@
+//! Blocking I/O on named pipes
+use crate::proc::wait::{Wait, WAIT_SELECT};
+use libsys::{
+    error::Errno,
+    traits::{Read, Write},
+};
+use vfs::FileRef;
+
+/// Wait channel for processes blocked on named pipe I/O
+pub static WAIT_FIFO: Wait = Wait::new("fifo");
+
+/// Completes opening of a named pipe end: unless the file is
+/// non-blocking, suspends the caller until the opposite end is opened
+pub fn open(file: &FileRef) -> Result<(), Errno> {
+    if !file.borrow().is_fifo() {
+        return Ok(());
+    }
+
+    notify();
+    loop {
+        let res = file.borrow().check_fifo_peer();
+        match res {
+            Err(Errno::WouldBlock) if !file.borrow().is_nonblocking() => WAIT_FIFO.wait(None)?,
+            res => break res,
+        }
+    }
+}
+
+/// Reads data from `file`, suspending the caller while a named pipe
+/// has no data available
+pub fn read(file: &FileRef, buf: &mut [u8]) -> Result<usize, Errno> {
+    if !file.borrow().is_fifo() {
+        return file.borrow_mut().read(buf);
+    }
+
+    loop {
+        let res = file.borrow_mut().read(buf);
+        match res {
+            Err(Errno::WouldBlock) if !file.borrow().is_nonblocking() => WAIT_FIFO.wait(None)?,
+            res => {
+                notify();
+                break res;
+            }
+        }
+    }
+}
+
+/// Writes data to `file`, suspending the caller while a named pipe
+/// buffer is full
+pub fn write(file: &FileRef, buf: &[u8]) -> Result<usize, Errno> {
+    if !file.borrow().is_fifo() {
+        return file.borrow_mut().write(buf);
+    }
+
+    let mut off = 0;
+    while off < buf.len() {
+        let res = file.borrow_mut().write(&buf[off..]);
+        match res {
+            Ok(count) => {
+                off += count;
+                notify();
+            }
+            Err(Errno::WouldBlock) if !file.borrow().is_nonblocking() => WAIT_FIFO.wait(None)?,
+            Err(Errno::WouldBlock) if off != 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(off)
+}
+
+/// Wakes up processes waiting for named pipe state changes
+pub fn notify() {
+    WAIT_FIFO.wakeup_all();
+    WAIT_SELECT.wakeup_all();
+}
@@ -3,11 +3,23 @@ use crate::mem::{
     self,
     phys::{self, PageUsage},
 };
-use libsys::{error::Errno, stat::MountOptions};
-use vfs::VnodeRef;
+use crate::util::InitOnce;
+use alloc::rc::Rc;
+use fat32::Fat32;
+use libsys::{
+    error::Errno,
+    stat::{FileLimits, FileUsage, MountOptions},
+};
+use logfs::LogFs;
+use vfs::{limits, BlockDevice, Filesystem, VnodeLru, VnodeRef};
 use memfs::BlockAllocator;
 
 pub mod devfs;
+pub mod fifo;
+pub mod pcache;
+pub mod tmpfs;
+
+static VNODE_LRU: InitOnce<VnodeLru> = InitOnce::new();
 
 /// Allocator implementation for memfs
 #[derive(Clone, Copy)]
@@ -28,13 +40,85 @@ unsafe impl BlockAllocator for MemfsBlockAlloc {
     }
 }
 
+/// Initializes kernel filesystem facilities and devfs
+pub fn init() {
+    VNODE_LRU.init(VnodeLru::new());
+    devfs::init();
+}
+
+/// Drops up to `count` least recently used vnodes no longer in use,
+/// returns the number of vnodes dropped
+pub fn reclaim_vnodes(count: usize) -> usize {
+    VNODE_LRU.get().reclaim(count)
+}
+
+/// Returns the system-wide caps on open files, vnodes and pipes
+pub fn file_limits() -> FileLimits {
+    FileLimits {
+        max_files: limits::FILES.max(),
+        max_vnodes: limits::VNODES.max(),
+        max_pipes: limits::PIPES.max(),
+    }
+}
+
+/// Changes the system-wide caps. Objects already in use are kept, only
+/// creation of new ones is affected.
+pub fn set_file_limits(caps: FileLimits) -> Result<(), Errno> {
+    if caps.max_files == 0 || caps.max_vnodes == 0 || caps.max_pipes == 0 {
+        return Err(Errno::InvalidArgument);
+    }
+    limits::FILES.set_max(caps.max_files);
+    limits::VNODES.set_max(caps.max_vnodes);
+    limits::PIPES.set_max(caps.max_pipes);
+    Ok(())
+}
+
+/// Returns the number of open files, vnodes and pipes
+pub fn file_usage() -> FileUsage {
+    FileUsage {
+        files: limits::FILES.used(),
+        vnodes: limits::VNODES.used(),
+        pipes: limits::PIPES.used(),
+    }
+}
+
+/// Opens a filesystem of type `fs_name` on `dev` and returns its root. If
+/// no type is given, the supported ones are probed in turn.
+pub fn open_filesystem(
+    fs_name: Option<&str>,
+    dev: &'static dyn BlockDevice,
+) -> Result<VnodeRef, Errno> {
+    let fs: Rc<dyn Filesystem> = match fs_name {
+        Some("fat32" | "vfat") => Fat32::open(dev, false)?,
+        Some("logfs") => LogFs::open(dev)?,
+        Some(_) => return Err(Errno::InvalidArgument),
+        None => match Fat32::open(dev, false) {
+            Ok(fs) => fs,
+            Err(_) => LogFs::open(dev)?,
+        },
+    };
+    let root = fs.root()?;
+
+    // File data of filesystems on block devices goes through the page cache
+    root.set_page_cache(&pcache::PAGE_CACHE);
+    root.set_lru(VNODE_LRU.get());
+
+    Ok(root)
+}
+
 /// Creates a filesystem instance based on `options`
 pub fn create_filesystem(options: &MountOptions) -> Result<VnodeRef, Errno> {
-    let fs_name = options.fs.unwrap();
+    let fs_name = options.fs.ok_or(Errno::InvalidArgument)?;
 
-    if fs_name == "devfs" {
-        Ok(devfs::root().clone())
+    let root = if fs_name == "devfs" {
+        devfs::root().clone()
+    } else if fs_name == "tmpfs" {
+        tmpfs::create(options.size)?
     } else {
-        todo!();
-    }
+        let device = options.device.ok_or(Errno::InvalidArgument)?;
+        let dev = devfs::find_block_device(device).ok_or(Errno::DoesNotExist)?;
+        open_filesystem(Some(fs_name), dev)?
+    };
+
+    Ok(root)
 }
@@ -0,0 +1,606 @@
+//! Page cache of file data
+//!
+//! Pages are keyed by (vnode, file offset) and shared by all the users of
+//! file data: read-only segments of executables are mapped straight from
+//! cached pages, and vnodes with [PAGE_CACHE] attached serve their reads
+//! and writes from it as well.
+//!
+//! A cached page stays valid while its vnode is alive and the vnode data
+//! has not been modified bypassing the cache. Writes within the current
+//! file size only modify the cached page, which is written back by
+//! [PageCache::sync] or periodically by [sync_all]. Writes extending the
+//! file go straight to the vnode.
+//!
+//! Reads which miss the page following the requested range read ahead a
+//! window of pages, sized by the access pattern set with
+//! [PageCache::advise].
+//!
+//! The amount of dirty data is bounded by [DirtyLimits]: past the
+//! background threshold [flushd_fn] starts writing back, past the throttling
+//! threshold (global or the writer's own share of it) writers have to write
+//! back their data before their write returns.
+use crate::arch::machine;
+use crate::dev::timer::TimestampSource;
+use crate::mem::{
+    self,
+    phys::{self, PageUsage},
+    PAGE_SIZE,
+};
+use crate::proc::{wait::Wait, Thread};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use libsys::{
+    error::Errno,
+    proc::{DirtyLimits, Pid},
+    stat::FileAdvice,
+};
+use vfs::{PageCache, Vnode, VnodeRef};
+
+/// Number of pages read ahead for the default access pattern
+const READ_AHEAD: usize = 4;
+/// Number of pages read ahead for sequentially accessed files
+const READ_AHEAD_SEQUENTIAL: usize = 16;
+/// Maximum age of dirty data when there's little of it
+const FLUSH_INTERVAL: Duration = Duration::from_millis(1000);
+
+struct CachedPage {
+    // Keeps the vnode allocation (and thus the key address) from being
+    // reused while the entry exists
+    node: Weak<Vnode>,
+    version: usize,
+    phys: usize,
+    dirty: bool,
+    // Process which dirtied the page
+    owner: Option<Pid>,
+}
+
+/// Page cache for vnodes backed by real storage
+pub struct Cache;
+
+/// Page cache instance attached to block device filesystems
+pub static PAGE_CACHE: Cache = Cache;
+
+impl CachedPage {
+    fn is_valid(&self) -> bool {
+        self.node
+            .upgrade()
+            .map(|node| node.data_version() == self.version)
+            .unwrap_or(false)
+    }
+
+    fn data(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(mem::virtualize(self.phys) as *mut u8, PAGE_SIZE) }
+    }
+}
+
+static CACHE: IrqSafeSpinLock<BTreeMap<(usize, usize), CachedPage>> =
+    IrqSafeSpinLock::new(BTreeMap::new());
+// Access patterns other than [FileAdvice::Normal], keyed the same way
+static PATTERNS: IrqSafeSpinLock<BTreeMap<usize, (Weak<Vnode>, FileAdvice)>> =
+    IrqSafeSpinLock::new(BTreeMap::new());
+
+static DIRTY: AtomicUsize = AtomicUsize::new(0);
+static DIRTY_BY_OWNER: IrqSafeSpinLock<BTreeMap<Pid, usize>> =
+    IrqSafeSpinLock::new(BTreeMap::new());
+static LIMITS: IrqSafeSpinLock<DirtyLimits> = IrqSafeSpinLock::new(DirtyLimits {
+    throttle_ratio: 20,
+    background_ratio: 10,
+    process_ratio: 50,
+});
+static FLUSH_WAIT: Wait = Wait::new("flushd");
+
+fn mark_dirty(entry: &mut CachedPage, owner: Option<Pid>) {
+    if entry.dirty {
+        return;
+    }
+    entry.dirty = true;
+    entry.owner = owner;
+    DIRTY.fetch_add(1, Ordering::Relaxed);
+    if let Some(pid) = owner {
+        *DIRTY_BY_OWNER.lock().entry(pid).or_default() += 1;
+    }
+}
+
+fn mark_clean(entry: &mut CachedPage) {
+    if !entry.dirty {
+        return;
+    }
+    entry.dirty = false;
+    DIRTY.fetch_sub(1, Ordering::Relaxed);
+    if let Some(pid) = entry.owner.take() {
+        let mut owners = DIRTY_BY_OWNER.lock();
+        let count = owners.get_mut(&pid).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            owners.remove(&pid);
+        }
+    }
+}
+
+/// Releases the cache's reference to a removed entry's page
+fn release(mut entry: CachedPage) {
+    mark_clean(&mut entry);
+    unsafe {
+        phys::free_page(entry.phys).unwrap();
+    }
+}
+
+#[inline]
+fn node_key(node: &VnodeRef) -> usize {
+    Rc::as_ptr(node) as usize
+}
+
+/// Reads a page of `node` data, repeating short reads until the page is
+/// full or the end of file is reached
+fn read_page(node: &VnodeRef, offset: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+    let mut len = 0;
+    while len < buf.len() {
+        match node.read_direct(offset + len, &mut buf[len..])? {
+            0 => break,
+            count => len += count,
+        }
+    }
+    Ok(len)
+}
+
+/// Returns a page holding `node` data at `offset`, reading it in if it
+/// is not cached yet. The page reference is owned by the caller and is
+/// released with [phys::free_page].
+pub fn get_page(node: &VnodeRef, offset: usize) -> Result<usize, Errno> {
+    assert_eq!(offset % PAGE_SIZE, 0);
+    let key = (node_key(node), offset);
+
+    if let Some(page) = lookup(key)? {
+        return Ok(page);
+    }
+
+    // Read the page without holding the lock, the vnode may block
+    let version = node.data_version();
+    let page = phys::alloc_page(PageUsage::UserPrivate)?;
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut(mem::virtualize(page) as *mut u8, PAGE_SIZE) };
+    let len = match read_page(node, offset, buf) {
+        Ok(len) => len,
+        Err(err) => {
+            unsafe {
+                phys::free_page(page)?;
+            }
+            return Err(err);
+        }
+    };
+    buf[len..].fill(0);
+
+    let mut cache = CACHE.lock();
+    if cache.contains_key(&key) || node.data_version() != version {
+        // Lost a race against another reader or a writer, don't cache
+        return Ok(page);
+    }
+    cache.insert(
+        key,
+        CachedPage {
+            node: Rc::downgrade(node),
+            version,
+            phys: page,
+            dirty: false,
+            owner: None,
+        },
+    );
+    // One reference for the cache, one for the caller
+    unsafe { phys::fork_page(page) }
+}
+
+fn lookup(key: (usize, usize)) -> Result<Option<usize>, Errno> {
+    let mut cache = CACHE.lock();
+    match cache.get(&key) {
+        Some(entry) if entry.is_valid() => unsafe { phys::fork_page(entry.phys).map(Some) },
+        Some(_) => {
+            release(cache.remove(&key).unwrap());
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+fn is_cached(key: (usize, usize)) -> bool {
+    CACHE
+        .lock()
+        .get(&key)
+        .map(CachedPage::is_valid)
+        .unwrap_or(false)
+}
+
+fn access_pattern(node: &VnodeRef) -> FileAdvice {
+    match PATTERNS.lock().get(&node_key(node)) {
+        Some((weak, advice)) if weak.ptr_eq(&Rc::downgrade(node)) => *advice,
+        _ => FileAdvice::Normal,
+    }
+}
+
+fn set_access_pattern(node: &VnodeRef, advice: FileAdvice) {
+    let mut patterns = PATTERNS.lock();
+    if advice == FileAdvice::Normal {
+        patterns.remove(&node_key(node));
+    } else {
+        patterns.insert(node_key(node), (Rc::downgrade(node), advice));
+    }
+}
+
+/// Reads the pages of `node` in range `start..end` into the cache
+fn populate(node: &VnodeRef, start: usize, end: usize) -> Result<(), Errno> {
+    let key = node_key(node);
+    for offset in (start..end).step_by(PAGE_SIZE) {
+        if !is_cached((key, offset)) {
+            let page = get_page(node, offset)?;
+            unsafe {
+                phys::free_page(page)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads ahead the pages following `pos` if the next one is not cached yet
+fn read_ahead(node: &VnodeRef, pos: usize, size: usize) -> Result<(), Errno> {
+    let window = match access_pattern(node) {
+        FileAdvice::Random => return Ok(()),
+        FileAdvice::Sequential => READ_AHEAD_SEQUENTIAL,
+        _ => READ_AHEAD,
+    };
+    let start = (pos + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+    if start >= size || is_cached((node_key(node), start)) {
+        return Ok(());
+    }
+    populate(node, start, core::cmp::min(size, start + window * PAGE_SIZE))
+}
+
+/// Drops the clean cached pages of `node` in range `start..end` which are
+/// not mapped anywhere
+fn evict(node: &VnodeRef, start: usize, end: usize) {
+    let key = node_key(node);
+    let mut cache = CACHE.lock();
+    let unused: Vec<usize> = cache
+        .range((key, start)..(key, end))
+        .filter(|(_, entry)| !entry.dirty && phys::page_info(entry.phys).1 == 1)
+        .map(|(&(_, offset), _)| offset)
+        .collect();
+    for offset in unused {
+        release(cache.remove(&(key, offset)).unwrap());
+    }
+}
+
+/// Brings the cached pages of `node` up to date after its data was
+/// modified directly. Pages which were already stale before the change
+/// (their version differs from `old_version`) are dropped, `update` is
+/// called for the rest.
+fn revalidate<F: FnMut(usize, &mut CachedPage) -> bool>(
+    node: &VnodeRef,
+    old_version: usize,
+    mut update: F,
+) {
+    let version = node.data_version();
+    let start = node_key(node);
+    let mut cache = CACHE.lock();
+    let mut dropped = Vec::new();
+
+    for (&(_, offset), entry) in cache.range_mut((start, 0)..=(start, usize::MAX)) {
+        if entry.version != old_version || !update(offset, entry) {
+            dropped.push(offset);
+        } else {
+            entry.version = version;
+        }
+    }
+
+    for offset in dropped {
+        release(cache.remove(&(start, offset)).unwrap());
+    }
+}
+
+/// Writes `buf` to `node` directly, updating the cached pages it overlaps
+fn write_through(node: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno> {
+    let old_version = node.data_version();
+    let count = node.write_direct(pos, buf)?;
+    let end = pos + count;
+    revalidate(node, old_version, |offset, entry| {
+        let start = core::cmp::max(offset, pos);
+        let stop = core::cmp::min(offset + PAGE_SIZE, end);
+        if start < stop {
+            entry.data()[start - offset..stop - offset]
+                .copy_from_slice(&buf[start - pos..stop - pos]);
+        }
+        true
+    });
+    Ok(count)
+}
+
+impl PageCache for Cache {
+    fn read(&self, node: &VnodeRef, pos: usize, buf: &mut [u8]) -> Result<usize, Errno> {
+        let size = node.size()?;
+        if pos >= size {
+            return Ok(0);
+        }
+        let len = core::cmp::min(buf.len(), size - pos);
+
+        let mut off = 0;
+        while off < len {
+            let page_off = (pos + off) % PAGE_SIZE;
+            let count = core::cmp::min(len - off, PAGE_SIZE - page_off);
+            let page = get_page(node, pos + off - page_off)?;
+            let src = unsafe {
+                core::slice::from_raw_parts((mem::virtualize(page) + page_off) as *const u8, count)
+            };
+            buf[off..off + count].copy_from_slice(src);
+            unsafe {
+                phys::free_page(page)?;
+            }
+            off += count;
+        }
+
+        read_ahead(node, pos + len, size)?;
+        Ok(len)
+    }
+
+    fn write(&self, node: &VnodeRef, pos: usize, buf: &[u8]) -> Result<usize, Errno> {
+        if pos + buf.len() > node.size()? {
+            return write_through(node, pos, buf);
+        }
+        let owner = Thread::current().owner_id();
+
+        let mut off = 0;
+        while off < buf.len() {
+            let page_off = (pos + off) % PAGE_SIZE;
+            let count = core::cmp::min(buf.len() - off, PAGE_SIZE - page_off);
+            let offset = pos + off - page_off;
+            let page = get_page(node, offset)?;
+            let dst = unsafe {
+                core::slice::from_raw_parts_mut((mem::virtualize(page) + page_off) as *mut u8, count)
+            };
+            dst.copy_from_slice(&buf[off..off + count]);
+
+            let cached = match CACHE.lock().get_mut(&(node_key(node), offset)) {
+                Some(entry) if entry.phys == page => {
+                    mark_dirty(entry, owner);
+                    true
+                }
+                _ => false,
+            };
+            unsafe {
+                phys::free_page(page)?;
+            }
+            if !cached {
+                write_through(node, pos + off, &buf[off..off + count])?;
+            }
+            off += count;
+        }
+
+        throttle(node, owner)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, node: &VnodeRef, size: usize) -> Result<(), Errno> {
+        let old_version = node.data_version();
+        node.truncate_direct(size)?;
+        revalidate(node, old_version, |offset, entry| {
+            if offset >= size {
+                return false;
+            }
+            if offset + PAGE_SIZE > size {
+                entry.data()[size - offset..].fill(0);
+            }
+            true
+        });
+        Ok(())
+    }
+
+    fn sync(&self, node: &VnodeRef) -> Result<(), Errno> {
+        let start = node_key(node);
+        let dirty: Vec<(usize, usize)> = CACHE
+            .lock()
+            .range_mut((start, 0)..=(start, usize::MAX))
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(&(_, offset), entry)| {
+                mark_clean(entry);
+                (offset, unsafe { phys::fork_page(entry.phys).unwrap() })
+            })
+            .collect();
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        let old_version = node.data_version();
+        let size = node.size()?;
+        let mut result = Ok(());
+        let mut failed = Vec::new();
+        for &(offset, page) in dirty.iter() {
+            if result.is_ok() && offset < size {
+                let len = core::cmp::min(PAGE_SIZE, size - offset);
+                let data = unsafe {
+                    core::slice::from_raw_parts(mem::virtualize(page) as *const u8, len)
+                };
+                if let Err(err) = node.write_direct(offset, data) {
+                    result = Err(err);
+                }
+            }
+            if result.is_err() {
+                failed.push(offset);
+            }
+            unsafe {
+                phys::free_page(page)?;
+            }
+        }
+
+        // Our own writes don't invalidate the cache
+        revalidate(node, old_version, |offset, entry| {
+            if failed.contains(&offset) {
+                mark_dirty(entry, None);
+            }
+            true
+        });
+        result
+    }
+
+    fn advise(
+        &self,
+        node: &VnodeRef,
+        pos: usize,
+        len: usize,
+        advice: FileAdvice,
+    ) -> Result<(), Errno> {
+        let start = pos & !(PAGE_SIZE - 1);
+        let end = if len == 0 {
+            usize::MAX
+        } else {
+            pos.checked_add(len).ok_or(Errno::InvalidArgument)?
+        };
+
+        match advice {
+            FileAdvice::Normal | FileAdvice::Random | FileAdvice::Sequential => {
+                set_access_pattern(node, advice);
+                Ok(())
+            }
+            FileAdvice::WillNeed => populate(node, start, core::cmp::min(end, node.size()?)),
+            FileAdvice::DontNeed => {
+                self.sync(node)?;
+                evict(node, start, end);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes back the modified cached pages of all the vnodes
+pub fn sync_all() {
+    let nodes: Vec<VnodeRef> = {
+        let cache = CACHE.lock();
+        let mut nodes: Vec<VnodeRef> = Vec::new();
+        for entry in cache.values().filter(|entry| entry.dirty) {
+            if let Some(node) = entry.node.upgrade() {
+                if !nodes.iter().any(|n| Rc::ptr_eq(n, &node)) {
+                    nodes.push(node);
+                }
+            }
+        }
+        nodes
+    };
+
+    for node in nodes {
+        if let Err(err) = node.sync() {
+            warnln!("Failed to write back {:?}: {:?}", node.name(), err);
+        }
+    }
+}
+
+/// Drops cached pages which are clean and no longer mapped anywhere or
+/// have become stale. Returns the number of pages freed.
+pub fn shrink() -> usize {
+    let mut cache = CACHE.lock();
+    let mut freed = 0;
+    cache.retain(|_, entry| {
+        let refcount = phys::page_info(entry.phys).1;
+        if entry.is_valid() && (refcount > 1 || entry.dirty) {
+            return true;
+        }
+        mark_clean(entry);
+        unsafe {
+            phys::free_page(entry.phys).unwrap();
+        }
+        if refcount == 1 {
+            freed += 1;
+        }
+        false
+    });
+    drop(cache);
+
+    PATTERNS
+        .lock()
+        .retain(|_, (node, _)| node.strong_count() != 0);
+    freed
+}
+
+/// Returns the number of cached pages and how many of them are dirty
+pub fn statistics() -> (usize, usize) {
+    (CACHE.lock().len(), DIRTY.load(Ordering::Relaxed))
+}
+
+/// Returns the writeback thresholds in pages: throttling, background and
+/// per-process throttling
+fn dirty_thresholds() -> (usize, usize, usize) {
+    let limits = *LIMITS.lock();
+    let stat = phys::statistics();
+    // Cached file data is allocated as user pages
+    let memory = stat.available + stat.user_private;
+    let throttle = memory * limits.throttle_ratio / 100;
+    (
+        throttle,
+        memory * limits.background_ratio / 100,
+        throttle * limits.process_ratio / 100,
+    )
+}
+
+/// Starts background writeback or makes the writer write back its data
+/// if the amount of dirty data exceeds the thresholds
+fn throttle(node: &VnodeRef, owner: Option<Pid>) -> Result<(), Errno> {
+    let (throttle, background, process) = dirty_thresholds();
+    let dirty = DIRTY.load(Ordering::Relaxed);
+    if dirty > background {
+        FLUSH_WAIT.wakeup_one();
+    }
+
+    let own = owner
+        .and_then(|pid| DIRTY_BY_OWNER.lock().get(&pid).copied())
+        .unwrap_or(0);
+    if dirty > throttle || own > process {
+        node.sync()?;
+        if DIRTY.load(Ordering::Relaxed) > throttle {
+            sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Returns the current writeback thresholds
+pub fn dirty_limits() -> DirtyLimits {
+    *LIMITS.lock()
+}
+
+/// Changes the writeback thresholds
+pub fn set_dirty_limits(limits: DirtyLimits) -> Result<(), Errno> {
+    if !(1..=100).contains(&limits.throttle_ratio)
+        || limits.background_ratio > limits.throttle_ratio
+        || !(1..=100).contains(&limits.process_ratio)
+    {
+        return Err(Errno::InvalidArgument);
+    }
+    *LIMITS.lock() = limits;
+    FLUSH_WAIT.wakeup_one();
+    Ok(())
+}
+
+/// Kernel process function writing back the page cache: periodically, and
+/// as soon as dirty data exceeds the background threshold
+pub fn flushd_fn() -> ! {
+    loop {
+        sync_all();
+
+        let deadline = machine::local_timer().timestamp().unwrap() + FLUSH_INTERVAL;
+        while DIRTY.load(Ordering::Relaxed) <= dirty_thresholds().1 {
+            match FLUSH_WAIT.wait(Some(deadline)) {
+                Ok(()) | Err(Errno::Interrupt) => {}
+                Err(Errno::TimedOut) => break,
+                Err(err) => panic!("flushd wait failed: {:?}", err),
+            }
+        }
+    }
+}
+
+/// Writes back all the modified cached pages and drops the ones not mapped
+/// anywhere. Returns the number of pages freed.
+pub fn drop_caches() -> usize {
+    sync_all();
+    shrink()
+}
@@ -0,0 +1,62 @@
+//! Memory-backed scratch filesystem (memfs instance without an image).
+//!
+//! memfs block allocators carry no state, so all the instances share a
+//! single page budget, set by the last mount which specifies a size.
+use crate::mem::{
+    self,
+    phys::{self, PageUsage},
+};
+use alloc::rc::Rc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use libsys::{error::Errno, stat::FileMode};
+use memfs::{BlockAllocator, Ramfs};
+use vfs::{Filesystem, VnodeRef};
+
+/// Page budget used when the mount does not specify a size
+const DEFAULT_LIMIT_PAGES: usize = 4096;
+
+static USED_PAGES: AtomicUsize = AtomicUsize::new(0);
+static LIMIT_PAGES: AtomicUsize = AtomicUsize::new(DEFAULT_LIMIT_PAGES);
+
+/// Allocator for tmpfs data, fails once the page budget is exhausted
+#[derive(Clone, Copy)]
+pub struct TmpfsBlockAlloc;
+
+unsafe impl BlockAllocator for TmpfsBlockAlloc {
+    fn alloc(&self) -> *mut u8 {
+        let limit = LIMIT_PAGES.load(Ordering::Relaxed);
+        if USED_PAGES
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                (used < limit).then(|| used + 1)
+            })
+            .is_err()
+        {
+            return core::ptr::null_mut();
+        }
+
+        if let Ok(page) = phys::alloc_page(PageUsage::Filesystem) {
+            mem::virtualize(page) as *mut u8
+        } else {
+            USED_PAGES.fetch_sub(1, Ordering::Relaxed);
+            core::ptr::null_mut()
+        }
+    }
+
+    unsafe fn dealloc(&self, data: *mut u8) {
+        let phys = (data as usize) - mem::KERNEL_OFFSET;
+        phys::free_page(phys).unwrap();
+        USED_PAGES.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Creates an empty tmpfs instance with a world-writable sticky root.
+/// Non-zero `size` (in bytes) replaces the page budget.
+pub fn create(size: usize) -> Result<VnodeRef, Errno> {
+    if size != 0 {
+        let pages = (size + mem::PAGE_SIZE - 1) / mem::PAGE_SIZE;
+        LIMIT_PAGES.store(pages, Ordering::Relaxed);
+    }
+    let mode = unsafe { FileMode::from_bits_unchecked(0o777) } | FileMode::STICKY;
+    let fs: Rc<dyn Filesystem> = Ramfs::empty(mode, TmpfsBlockAlloc);
+    fs.root()
+}
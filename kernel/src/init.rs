@@ -1,16 +1,53 @@
 //! Kernel initialization process
 
 use crate::config::{ConfigKey, CONFIG};
-use crate::fs::{devfs, MemfsBlockAlloc};
+use crate::fs::{self as kfs, devfs, MemfsBlockAlloc};
 use crate::mem;
 use crate::proc::{elf, Process};
-use libsys::stat::{FileDescriptor, OpenFlags, UserId, GroupId};
+use alloc::rc::Rc;
+use libsys::{
+    error::Errno,
+    proc::ProcessName,
+    stat::{FileDescriptor, GroupId, MountFlags, OpenFlags, UserId},
+};
 use memfs::Ramfs;
-use vfs::{Filesystem, Ioctx};
+use vfs::{Filesystem, Ioctx, VnodeRef};
+
+/// Mounts the filesystem on block device `path` given by root=
+fn mount_root(path: &str, fs_type: &str) -> Result<VnodeRef, Errno> {
+    let dev = devfs::find_block_device(path).ok_or(Errno::DoesNotExist)?;
+    let fs_type = if fs_type.is_empty() {
+        None
+    } else {
+        Some(fs_type)
+    };
+    kfs::open_filesystem(fs_type, dev)
+}
+
+/// Prepares a root filesystem other than the initrd: devfs is mounted at
+/// its /dev and the initrd is kept at /initrd, if these directories exist
+fn setup_root(ioctx: &Ioctx, initrd: Option<VnodeRef>) {
+    match ioctx.find(None, "/dev", true) {
+        Ok(at) => {
+            if let Err(e) = ioctx.mount(at, devfs::root().clone(), MountFlags::MS_NOEXEC) {
+                warnln!("Failed to mount devfs at /dev: {:?}", e);
+            }
+        }
+        Err(_) => warnln!("Root filesystem has no /dev"),
+    }
+
+    if let Some(initrd) = initrd {
+        if let Ok(at) = ioctx.find(None, "/initrd", true) {
+            if let Err(e) = ioctx.mount(at, initrd, MountFlags::MS_RDONLY) {
+                warnln!("Failed to mount initrd at /initrd: {:?}", e);
+            }
+        }
+    }
+}
 
 /// Kernel init process function
 #[inline(never)]
-pub extern "C" fn init_fn(_arg: usize) -> ! {
+pub fn init_fn() -> ! {
     let proc = Process::current();
 
     debugln!("Running kernel init process");
@@ -19,17 +56,44 @@ pub extern "C" fn init_fn(_arg: usize) -> ! {
     let initrd_start = cfg.get_usize(ConfigKey::InitrdBase);
     let initrd_size = cfg.get_usize(ConfigKey::InitrdSize);
     let console = cfg.get_str(ConfigKey::Console);
+    let root_dev = cfg.get_str(ConfigKey::Root);
 
-    if initrd_start == 0 {
-        panic!("No initrd specified");
-    }
+    let initrd = if initrd_start != 0 {
+        let initrd_start = mem::virtualize(initrd_start);
+        let fs = unsafe {
+            Ramfs::open(initrd_start as *mut u8, initrd_size, MemfsBlockAlloc {}).unwrap()
+        };
+        Some(fs.root().unwrap())
+    } else {
+        None
+    };
 
-    let initrd_start = mem::virtualize(initrd_start);
-    let fs =
-        unsafe { Ramfs::open(initrd_start as *mut u8, initrd_size, MemfsBlockAlloc {}).unwrap() };
-    let root = fs.root().unwrap();
+    // Drivers are up by now, so the device given by root= can be mounted
+    let root = if root_dev.is_empty() {
+        initrd.clone().expect("No initrd specified")
+    } else {
+        match mount_root(root_dev, cfg.get_str(ConfigKey::RootFsType)) {
+            Ok(root) => {
+                infoln!("Mounted root filesystem from {}", root_dev);
+                root
+            }
+            Err(e) => match &initrd {
+                Some(initrd) => {
+                    warnln!("Failed to mount root {:?}: {:?}, using initrd", root_dev, e);
+                    initrd.clone()
+                }
+                None => panic!("Failed to mount root {:?}: {:?}", root_dev, e),
+            },
+        }
+    };
 
-    let ioctx = Ioctx::new(root, UserId::root(), GroupId::root());
+    let ioctx = Ioctx::builder(root.clone())
+        .ids(UserId::root(), GroupId::root())
+        .caps(proc.capabilities())
+        .build();
+    if !initrd.as_ref().map_or(false, |initrd| Rc::ptr_eq(initrd, &root)) {
+        setup_root(&ioctx, initrd);
+    }
 
     let node = ioctx.find(None, "/init", true).unwrap();
     let file = node.open(OpenFlags::O_RDONLY | OpenFlags::O_EXEC).unwrap();
@@ -39,12 +103,15 @@ pub extern "C" fn init_fn(_arg: usize) -> ! {
     // Open stdin/stdout/stderr
     {
         let devfs_root = devfs::root();
-        let tty_node = if console.is_empty() {
-            devfs_root.lookup("ttyS0")
-        } else {
-            devfs_root.lookup(console)
-        }
-        .expect("Failed to open stdout for init process");
+        // First terminal listed in console=, kernel log levels stripped
+        let tty_name = console
+            .split(',')
+            .filter_map(|item| item.split(':').next())
+            .find(|name| name.starts_with("tty"))
+            .unwrap_or("ttyS0");
+        let tty_node = devfs_root
+            .lookup(tty_name)
+            .expect("Failed to open stdout for init process");
 
         let mut io = proc.io.lock();
         let stdin = tty_node.open(OpenFlags::O_RDONLY).unwrap();
@@ -59,6 +126,14 @@ pub extern "C" fn init_fn(_arg: usize) -> ! {
 
     drop(cfg);
 
-    Process::execve(|space| elf::load_elf(space, file), &["/init"]).unwrap();
+    Process::execve(
+        ProcessName::from_str_truncated("init"),
+        |space| elf::load_elf(space, file),
+        &["/init"],
+        &[],
+        None,
+        None,
+    )
+    .expect("Failed to load /init");
     panic!("Unreachable");
 }
@@ -0,0 +1,415 @@
+//! Experimental loadable kernel module support.
+//!
+//! Modules are relocatable AArch64 ELF objects (.ko-style). Undefined
+//! symbols are resolved against the kernel symbol table populated with
+//! [export_symbol!] and the module's `module_init` function is called once
+//! the image is relocated.
+use crate::mem::{
+    self,
+    phys::{self, PageUsage},
+};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use core::mem::size_of;
+use libsys::{dev::ModuleInfo, error::Errno, traits::Read, FixedStr};
+use vfs::FileRef;
+
+mod reloc;
+
+/// Kernel symbol table entry
+#[repr(C)]
+pub struct KernelSymbol {
+    /// Symbol name
+    pub name: &'static str,
+    /// Symbol address
+    pub addr: *const (),
+}
+
+unsafe impl Sync for KernelSymbol {}
+
+/// Loaded module information
+#[derive(Debug)]
+pub struct Module {
+    /// Module name
+    pub name: String,
+    /// Virtual address of the module image
+    pub base: usize,
+    /// Count of pages occupied by the image
+    pub pages: usize,
+}
+
+/// Adds a function or a static to the kernel symbol table, making it
+/// available to loadable modules
+#[macro_export]
+macro_rules! export_symbol {
+    ($name:path, $sym:literal) => {
+        const _: () = {
+            #[used]
+            #[link_section = ".ksymtab"]
+            static __KSYM: $crate::kmod::KernelSymbol = $crate::kmod::KernelSymbol {
+                name: $sym,
+                addr: $name as *const (),
+            };
+        };
+    };
+}
+
+static MODULES: IrqSafeSpinLock<Vec<Module>> = IrqSafeSpinLock::new(Vec::new());
+
+const ET_REL: u16 = 1;
+const EM_AARCH64: u16 = 183;
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHF_ALLOC: u64 = 0x2;
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xFF00;
+const SHN_ABS: u16 = 0xFFF1;
+const SHN_COMMON: u16 = 0xFFF2;
+
+#[repr(C)]
+struct Ehdr {
+    ident: [u8; 16],
+    typ: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[repr(C)]
+struct Shdr {
+    name: u32,
+    typ: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    addralign: u64,
+    entsize: u64,
+}
+
+#[repr(C)]
+struct Sym {
+    name: u32,
+    info: u8,
+    other: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+#[repr(C)]
+struct Rela {
+    offset: u64,
+    info: u64,
+    addend: i64,
+}
+
+/// Kernel-side printing facility for modules
+extern "C" fn kmod_print(ptr: *const u8, len: usize) {
+    let bytes = unsafe { core::slice::from_raw_parts(ptr, len) };
+    if let Ok(msg) = core::str::from_utf8(bytes) {
+        infoln!("{}", msg);
+    }
+}
+export_symbol!(kmod_print, "kmod_print");
+
+fn kernel_symbols() -> &'static [KernelSymbol] {
+    extern "C" {
+        static __ksymtab_start: KernelSymbol;
+        static __ksymtab_end: KernelSymbol;
+    }
+    unsafe {
+        let start = &__ksymtab_start as *const KernelSymbol;
+        let end = &__ksymtab_end as *const KernelSymbol;
+        core::slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// Looks up an exported kernel symbol by its name
+pub fn find_symbol(name: &str) -> Option<usize> {
+    kernel_symbols()
+        .iter()
+        .find(|sym| sym.name == name)
+        .map(|sym| sym.addr as usize)
+}
+
+/// Returns `size` bytes of `data` at `offset`, checking the range against
+/// the image bounds
+fn range(data: &[u8], offset: u64, size: u64) -> Result<&[u8], Errno> {
+    let start = usize::try_from(offset).map_err(|_| Errno::BadExecutable)?;
+    let size = usize::try_from(size).map_err(|_| Errno::BadExecutable)?;
+    let end = start.checked_add(size).ok_or(Errno::BadExecutable)?;
+    data.get(start..end).ok_or(Errno::BadExecutable)
+}
+
+fn table<T>(data: &[u8], offset: usize, count: usize) -> Result<&[T], Errno> {
+    let size = count.checked_mul(size_of::<T>()).ok_or(Errno::BadExecutable)?;
+    let bytes = range(data, offset as u64, size as u64)?;
+    if bytes.as_ptr() as usize % core::mem::align_of::<T>() != 0 {
+        return Err(Errno::BadExecutable);
+    }
+    Ok(unsafe { core::slice::from_raw_parts(bytes.as_ptr() as *const T, count) })
+}
+
+/// Reads the whole of `file` into `data`
+fn read_all(file: &FileRef, data: &mut [u8]) -> Result<(), Errno> {
+    let mut file = file.borrow_mut();
+    let mut off = 0;
+    while off < data.len() {
+        match file.read(&mut data[off..])? {
+            0 => return Err(Errno::InvalidFile),
+            count => off += count,
+        }
+    }
+    Ok(())
+}
+
+fn c_str(data: &[u8], offset: usize) -> Result<&str, Errno> {
+    let data = data.get(offset..).ok_or(Errno::BadExecutable)?;
+    let len = data.iter().position(|&c| c == 0).ok_or(Errno::BadExecutable)?;
+    core::str::from_utf8(&data[..len]).map_err(|_| Errno::BadExecutable)
+}
+
+fn sync_icache(base: usize, size: usize) {
+    for line in (base..base + size).step_by(64) {
+        unsafe {
+            asm!("dc cvau, {}", in(reg) line);
+        }
+    }
+    unsafe {
+        asm!("dsb ish; ic iallu; dsb ish; isb");
+    }
+}
+
+/// Loads a relocatable module image from `file` and runs its
+/// `module_init` function
+pub fn load(name: &str, file: FileRef) -> Result<(), Errno> {
+    {
+        let mut modules = MODULES.lock();
+        if modules.iter().any(|m| m.name == name) {
+            return Err(Errno::AlreadyExists);
+        }
+        // Reserves the name, the image is loaded without the lock held
+        modules.push(Module {
+            name: name.to_owned(),
+            base: 0,
+            pages: 0,
+        });
+    }
+
+    let res = load_module(name, file);
+    let mut modules = MODULES.lock();
+    let index = modules.iter().position(|m| m.name == name).unwrap();
+    match res {
+        Ok((base, pages)) => {
+            modules[index].base = base;
+            modules[index].pages = pages;
+            Ok(())
+        }
+        Err(e) => {
+            modules.remove(index);
+            Err(e)
+        }
+    }
+}
+
+fn load_module(name: &str, file: FileRef) -> Result<(usize, usize), Errno> {
+    let size = file.borrow().node().ok_or(Errno::InvalidFile)?.size()?;
+    // u64 storage keeps ELF tables properly aligned
+    let mut storage = vec![0u64; (size + 7) / 8];
+    let data = unsafe { core::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, size) };
+    read_all(&file, data)?;
+    let data = &*data;
+
+    let ehdr = &table::<Ehdr>(data, 0, 1)?[0];
+    if &ehdr.ident[0..4] != b"\x7FELF" || ehdr.typ != ET_REL || ehdr.machine != EM_AARCH64 {
+        return Err(Errno::BadExecutable);
+    }
+    let shdrs = table::<Shdr>(data, ehdr.shoff as usize, ehdr.shnum as usize)?;
+
+    // 1. Lay out allocated sections
+    let mut section_offsets = vec![None; shdrs.len()];
+    let mut image_size = 0usize;
+    for (i, shdr) in shdrs.iter().enumerate() {
+        if shdr.flags & SHF_ALLOC != 0 && shdr.size != 0 {
+            let align = core::cmp::max(shdr.addralign as usize, 1);
+            if !align.is_power_of_two() {
+                return Err(Errno::BadExecutable);
+            }
+            let size = usize::try_from(shdr.size).map_err(|_| Errno::BadExecutable)?;
+            image_size = image_size
+                .checked_add(align - 1)
+                .ok_or(Errno::BadExecutable)?
+                & !(align - 1);
+            section_offsets[i] = Some(image_size);
+            image_size = image_size.checked_add(size).ok_or(Errno::BadExecutable)?;
+        }
+    }
+    if image_size == 0 {
+        return Err(Errno::BadExecutable);
+    }
+
+    let pages = (image_size + mem::PAGE_SIZE - 1) / mem::PAGE_SIZE;
+    let base = mem::virtualize(phys::alloc_contiguous_pages(PageUsage::Kernel, pages)?);
+
+    let res = load_image(data, shdrs, &section_offsets, base, image_size);
+    match res {
+        Ok(init) => {
+            sync_icache(base, image_size);
+            if let Some(init) = init {
+                let init: extern "C" fn() -> i32 = unsafe { core::mem::transmute(init) };
+                let status = init();
+                if status != 0 {
+                    warnln!("Module {:?} init failed: {}", name, status);
+                    free_image(base, pages);
+                    return Err(Errno::InvalidArgument);
+                }
+            }
+
+            infoln!("Loaded module {:?} at {:#x}", name, base);
+            Ok((base, pages))
+        }
+        Err(e) => {
+            free_image(base, pages);
+            Err(e)
+        }
+    }
+}
+
+fn free_image(base: usize, pages: usize) {
+    for i in 0..pages {
+        unsafe {
+            phys::free_page(base - mem::KERNEL_OFFSET + i * mem::PAGE_SIZE).unwrap();
+        }
+    }
+}
+
+fn load_image(
+    data: &[u8],
+    shdrs: &[Shdr],
+    section_offsets: &[Option<usize>],
+    base: usize,
+    image_size: usize,
+) -> Result<Option<usize>, Errno> {
+    let image = unsafe { core::slice::from_raw_parts_mut(base as *mut u8, image_size) };
+    image.fill(0);
+
+    // 2. Copy section contents
+    for (shdr, offset) in shdrs.iter().zip(section_offsets.iter()) {
+        if let Some(offset) = *offset {
+            if shdr.typ != SHT_NOBITS {
+                let src = range(data, shdr.offset, shdr.size)?;
+                image[offset..offset + src.len()].copy_from_slice(src);
+            }
+        }
+    }
+
+    // 3. Resolve symbols
+    let symtab = shdrs
+        .iter()
+        .find(|s| s.typ == SHT_SYMTAB)
+        .ok_or(Errno::BadExecutable)?;
+    let strtab = shdrs.get(symtab.link as usize).ok_or(Errno::BadExecutable)?;
+    let strings = range(data, strtab.offset, strtab.size)?;
+    let syms = table::<Sym>(
+        data,
+        symtab.offset as usize,
+        symtab.size as usize / size_of::<Sym>(),
+    )?;
+
+    let mut sym_values = Vec::with_capacity(syms.len());
+    let mut init = None;
+    for sym in syms.iter() {
+        let name = c_str(strings, sym.name as usize)?;
+        let value = match sym.shndx {
+            SHN_UNDEF if name.is_empty() => 0,
+            SHN_UNDEF => find_symbol(name).ok_or_else(|| {
+                warnln!("Module refers to undefined symbol {:?}", name);
+                Errno::DoesNotExist
+            })?,
+            SHN_ABS => sym.value as usize,
+            SHN_COMMON => {
+                // Modules are expected to be built with -fno-common
+                warnln!("Module has a common symbol {:?}", name);
+                return Err(Errno::BadExecutable);
+            }
+            index if index >= SHN_LORESERVE || index as usize >= shdrs.len() => {
+                return Err(Errno::BadExecutable);
+            }
+            index => {
+                let offset = section_offsets[index as usize].unwrap_or(0);
+                base.wrapping_add(offset).wrapping_add(sym.value as usize)
+            }
+        };
+        if name == "module_init" && sym.shndx != SHN_UNDEF {
+            init = Some(value);
+        }
+        sym_values.push(value);
+    }
+
+    // 4. Apply relocations
+    for shdr in shdrs.iter().filter(|s| s.typ == SHT_RELA) {
+        let index = shdr.info as usize;
+        let (target, target_size) = match section_offsets.get(index).copied().flatten() {
+            Some(offset) => (base + offset, shdrs[index].size),
+            None => continue,
+        };
+        let relas = table::<Rela>(
+            data,
+            shdr.offset as usize,
+            shdr.size as usize / size_of::<Rela>(),
+        )?;
+
+        for rela in relas.iter() {
+            let sym = *sym_values
+                .get((rela.info >> 32) as usize)
+                .ok_or(Errno::BadExecutable)?;
+            let typ = (rela.info & 0xFFFFFFFF) as u32;
+            let s = (sym as i64).wrapping_add(rela.addend) as usize;
+            match rela.offset.checked_add(reloc::size(typ)) {
+                Some(end) if end <= target_size => {}
+                _ => return Err(Errno::BadExecutable),
+            }
+            let p = target + rela.offset as usize;
+            unsafe {
+                reloc::apply(typ, p, s)?;
+            }
+        }
+    }
+
+    Ok(init)
+}
+
+/// Calls `f` for each of the loaded modules
+pub fn for_each<F: FnMut(&Module)>(mut f: F) {
+    // Entries with no pages are still being loaded
+    for module in MODULES.lock().iter().filter(|m| m.pages != 0) {
+        f(module);
+    }
+}
+
+/// Returns the list of loaded modules as reported to userspace
+pub fn list() -> Vec<ModuleInfo> {
+    MODULES
+        .lock()
+        .iter()
+        .filter(|module| module.pages != 0)
+        .map(|module| ModuleInfo {
+            name: FixedStr::from_str_truncated(&module.name),
+            base: module.base,
+            size: module.pages * mem::PAGE_SIZE,
+        })
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! AArch64 ELF relocation types
+use libsys::error::Errno;
+
+const R_AARCH64_ABS64: u32 = 257;
+const R_AARCH64_ABS32: u32 = 258;
+const R_AARCH64_PREL64: u32 = 260;
+const R_AARCH64_PREL32: u32 = 261;
+const R_AARCH64_ADR_PREL_PG_HI21: u32 = 275;
+const R_AARCH64_ADD_ABS_LO12_NC: u32 = 277;
+const R_AARCH64_LDST8_ABS_LO12_NC: u32 = 278;
+const R_AARCH64_JUMP26: u32 = 282;
+const R_AARCH64_CALL26: u32 = 283;
+const R_AARCH64_LDST16_ABS_LO12_NC: u32 = 284;
+const R_AARCH64_LDST32_ABS_LO12_NC: u32 = 285;
+const R_AARCH64_LDST64_ABS_LO12_NC: u32 = 286;
+const R_AARCH64_LDST128_ABS_LO12_NC: u32 = 299;
+
+unsafe fn patch_insn(p: usize, mask: u32, value: u32) {
+    let insn = (p as *const u32).read_unaligned();
+    (p as *mut u32).write_unaligned((insn & !mask) | (value & mask));
+}
+
+/// Returns the number of bytes patched by relocation `typ`
+pub const fn size(typ: u32) -> u64 {
+    match typ {
+        R_AARCH64_ABS64 | R_AARCH64_PREL64 => 8,
+        _ => 4,
+    }
+}
+
+/// Applies relocation `typ` at address `p` with resolved value `s`
+/// (symbol value + addend).
+///
+/// # Safety
+///
+/// Unsafe: writes to arbitrary memory location `p`.
+pub unsafe fn apply(typ: u32, p: usize, s: usize) -> Result<(), Errno> {
+    let prel = (s as i64).wrapping_sub(p as i64);
+
+    match typ {
+        R_AARCH64_ABS64 => (p as *mut u64).write_unaligned(s as u64),
+        R_AARCH64_ABS32 => (p as *mut u32).write_unaligned(s as u32),
+        R_AARCH64_PREL64 => (p as *mut i64).write_unaligned(prel),
+        R_AARCH64_PREL32 => {
+            if prel < i32::MIN as i64 || prel > i32::MAX as i64 {
+                return Err(Errno::BadExecutable);
+            }
+            (p as *mut i32).write_unaligned(prel as i32)
+        }
+        R_AARCH64_JUMP26 | R_AARCH64_CALL26 => {
+            if !(-(1 << 27)..(1 << 27)).contains(&prel) {
+                return Err(Errno::BadExecutable);
+            }
+            patch_insn(p, 0x03FFFFFF, (prel >> 2) as u32);
+        }
+        R_AARCH64_ADR_PREL_PG_HI21 => {
+            let page = ((s & !0xFFF) as i64).wrapping_sub((p & !0xFFF) as i64) >> 12;
+            if !(-(1 << 20)..(1 << 20)).contains(&page) {
+                return Err(Errno::BadExecutable);
+            }
+            let page = page as u32;
+            // immlo: bits 29..30, immhi: bits 5..23
+            patch_insn(p, (0x3 << 29) | (0x7FFFF << 5), ((page & 0x3) << 29) | ((page >> 2) << 5));
+        }
+        R_AARCH64_ADD_ABS_LO12_NC | R_AARCH64_LDST8_ABS_LO12_NC => {
+            patch_insn(p, 0xFFF << 10, ((s & 0xFFF) as u32) << 10);
+        }
+        R_AARCH64_LDST16_ABS_LO12_NC => {
+            patch_insn(p, 0xFFF << 10, (((s & 0xFFF) >> 1) as u32) << 10);
+        }
+        R_AARCH64_LDST32_ABS_LO12_NC => {
+            patch_insn(p, 0xFFF << 10, (((s & 0xFFF) >> 2) as u32) << 10);
+        }
+        R_AARCH64_LDST64_ABS_LO12_NC => {
+            patch_insn(p, 0xFFF << 10, (((s & 0xFFF) >> 3) as u32) << 10);
+        }
+        R_AARCH64_LDST128_ABS_LO12_NC => {
+            patch_insn(p, 0xFFF << 10, (((s & 0xFFF) >> 4) as u32) << 10);
+        }
+        _ => {
+            warnln!("Unsupported module relocation type: {}", typ);
+            return Err(Errno::NotImplemented);
+        }
+    }
+
+    Ok(())
+}
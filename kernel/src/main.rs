@@ -32,12 +32,18 @@ pub mod debug;
 pub mod arch;
 pub mod config;
 pub mod dev;
+#[cfg(feature = "fault_inject")]
+pub mod fault;
 pub mod fs;
 pub mod init;
+#[cfg(feature = "kmod")]
+pub mod kmod;
 pub mod mem;
+pub mod panic;
 pub mod proc;
 pub mod sync;
 pub mod syscall;
+pub mod time;
 pub mod util;
 
 #[panic_handler]
@@ -46,7 +52,5 @@ fn panic_handler(pi: &core::panic::PanicInfo) -> ! {
         asm!("msr daifset, #2");
     }
 
-    errorln!("Panic: {:?}", pi);
-    // TODO
-    loop {}
+    panic::handle(pi)
 }
@@ -0,0 +1,93 @@
+//! Copy-on-Write and fork() accounting
+use crate::sync::IrqSafeSpinLock;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use libsys::proc::ForkStatistics;
+
+/// Number of most recent fork() latencies percentiles are computed from
+const LATENCY_SAMPLES: usize = 256;
+
+struct LatencyLog {
+    samples: [u64; LATENCY_SAMPLES],
+    count: usize,
+    next: usize,
+}
+
+static FORKS: AtomicUsize = AtomicUsize::new(0);
+static PAGES_SHARED: AtomicUsize = AtomicUsize::new(0);
+static COW_FAULTS: AtomicUsize = AtomicUsize::new(0);
+static PAGES_COPIED: AtomicUsize = AtomicUsize::new(0);
+static LATENCY: IrqSafeSpinLock<LatencyLog> = IrqSafeSpinLock::new(LatencyLog {
+    samples: [0; LATENCY_SAMPLES],
+    count: 0,
+    next: 0,
+});
+
+/// Records a writable page being shared as Copy-on-Write by fork()
+pub fn page_shared() {
+    PAGES_SHARED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records a resolved Copy-on-Write fault. `copied` is `false` if the last
+/// remaining mapping took the page over without copying it.
+pub fn fault(copied: bool) {
+    COW_FAULTS.fetch_add(1, Ordering::Relaxed);
+    if copied {
+        PAGES_COPIED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records a completed fork() which took `time`
+pub fn fork_done(time: Duration) {
+    FORKS.fetch_add(1, Ordering::Relaxed);
+    let mut log = LATENCY.lock();
+    let index = log.next;
+    log.samples[index] = time.as_nanos() as u64;
+    log.next = (index + 1) % LATENCY_SAMPLES;
+    log.count = core::cmp::min(log.count + 1, LATENCY_SAMPLES);
+}
+
+/// Resets all the counters and drops collected latency samples
+pub fn reset() {
+    let mut log = LATENCY.lock();
+    log.count = 0;
+    log.next = 0;
+    FORKS.store(0, Ordering::Relaxed);
+    PAGES_SHARED.store(0, Ordering::Relaxed);
+    COW_FAULTS.store(0, Ordering::Relaxed);
+    PAGES_COPIED.store(0, Ordering::Relaxed);
+}
+
+/// Returns current counter values and latency percentiles
+pub fn statistics() -> ForkStatistics {
+    let mut samples = [0; LATENCY_SAMPLES];
+    let count = {
+        let log = LATENCY.lock();
+        samples[..log.count].copy_from_slice(&log.samples[..log.count]);
+        log.count
+    };
+    let samples = &mut samples[..count];
+    samples.sort_unstable();
+    let percentile = |p: usize| {
+        if count == 0 {
+            0
+        } else {
+            samples[(count * p / 100).min(count - 1)]
+        }
+    };
+
+    let faults = COW_FAULTS.load(Ordering::Relaxed);
+    let copied = PAGES_COPIED.load(Ordering::Relaxed);
+    ForkStatistics {
+        forks: FORKS.load(Ordering::Relaxed),
+        pages_shared: PAGES_SHARED.load(Ordering::Relaxed),
+        cow_faults: faults,
+        pages_copied: copied,
+        pages_reused: faults.saturating_sub(copied),
+        latency_samples: count,
+        latency_p50_ns: percentile(50),
+        latency_p90_ns: percentile(90),
+        latency_p99_ns: percentile(99),
+        latency_max_ns: samples.last().copied().unwrap_or(0),
+    }
+}
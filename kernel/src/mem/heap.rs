@@ -41,6 +41,12 @@ impl Heap {
     unsafe fn dealloc(&mut self, _ptr: *mut u8, _layout: Layout) {}
 }
 
+/// Returns the kernel heap size and the number of bytes allocated from it
+pub fn statistics() -> (usize, usize) {
+    let heap = HEAP.get().lock();
+    (heap.size, heap.ptr)
+}
+
 #[alloc_error_handler]
 fn alloc_error_handler(layout: Layout) -> ! {
     panic!("Allocation failed: {:?}", layout)
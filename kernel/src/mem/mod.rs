@@ -1,7 +1,12 @@
 //! Memory management and functions module
+use crate::fs::pcache;
+use libsys::proc::MemoryStatus;
 
+pub mod cow;
 pub mod heap;
 pub mod phys;
+pub mod ring;
+pub mod swap;
 pub mod virt;
 
 /// Virtual offset applied to kernel address space
@@ -19,6 +24,34 @@ pub fn virtualize(addr: usize) -> usize {
     addr + KERNEL_OFFSET
 }
 
+/// Returns system-wide memory usage summary
+pub fn status() -> MemoryStatus {
+    let stat = phys::statistics();
+    let (cached_pages, dirty_pages) = pcache::statistics();
+    let (heap_size, heap_used) = heap::statistics();
+    MemoryStatus {
+        total_pages: stat.available
+            + stat.kernel
+            + stat.kernel_heap
+            + stat.paging
+            + stat.user_private
+            + stat.filesystem
+            + stat.offline,
+        available_pages: stat.available,
+        kernel_pages: stat.kernel,
+        kernel_heap_pages: stat.kernel_heap,
+        paging_pages: stat.paging,
+        user_pages: stat.user_private,
+        filesystem_pages: stat.filesystem,
+        offline_pages: stat.offline,
+        cached_pages,
+        dirty_pages,
+        heap_size,
+        heap_used,
+        swap: swap::status(),
+    }
+}
+
 /// Returns the physical address of kernel's end in memory.
 pub fn kernel_end_phys() -> usize {
     extern "C" {
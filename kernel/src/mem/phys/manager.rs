@@ -10,6 +10,7 @@ pub unsafe trait Manager {
     fn free_page(&mut self, page: usize) -> Result<(), Errno>;
     fn copy_cow_page(&mut self, src: usize) -> Result<usize, Errno>;
     fn fork_page(&mut self, src: usize) -> Result<usize, Errno>;
+    fn page_info(&self, page: usize) -> (PageUsage, usize);
     fn statistics(&self) -> PageStatistics;
     // TODO status()
 }
@@ -42,7 +43,8 @@ impl SimpleManager {
                 kernel_heap: 0,
                 paging: 0,
                 user_private: 0,
-                filesystem: 0
+                filesystem: 0,
+                offline: 0,
             },
             pages,
         }
@@ -87,24 +89,26 @@ impl SimpleManager {
             PageUsage::Paging => &mut self.stats.paging,
             PageUsage::UserPrivate => &mut self.stats.user_private,
             PageUsage::Filesystem => &mut self.stats.filesystem,
+            PageUsage::Offline => &mut self.stats.offline,
             _ => panic!("TODO {:?}", pu),
         };
         *field += count;
         self.stats.available -= count;
     }
 
-    // fn update_stats_free(&mut self, pu: PageUsage, count: usize) {
-    //     let field = match pu {
-    //         PageUsage::Kernel => &mut self.stats.kernel,
-    //         PageUsage::KernelHeap => &mut self.stats.kernel_heap,
-    //         PageUsage::Paging => &mut self.stats.paging,
-    //         PageUsage::UserPrivate => &mut self.stats.user_private,
-    //         PageUsage::Filesystem => &mut self.stats.filesystem,
-    //         _ => panic!("TODO {:?}", pu),
-    //     };
-    //     *field -= count;
-    //     self.stats.available += count;
-    // }
+    fn update_stats_free(&mut self, pu: PageUsage, count: usize) {
+        let field = match pu {
+            PageUsage::Kernel => &mut self.stats.kernel,
+            PageUsage::KernelHeap => &mut self.stats.kernel_heap,
+            PageUsage::Paging => &mut self.stats.paging,
+            PageUsage::UserPrivate => &mut self.stats.user_private,
+            PageUsage::Filesystem => &mut self.stats.filesystem,
+            PageUsage::Offline => &mut self.stats.offline,
+            _ => panic!("TODO {:?}", pu),
+        };
+        *field -= count;
+        self.stats.available += count;
+    }
 }
 unsafe impl Manager for SimpleManager {
     fn alloc_page(&mut self, pu: PageUsage) -> Result<usize, Errno> {
@@ -143,15 +147,14 @@ unsafe impl Manager for SimpleManager {
             page.refcount -= 1;
         } else {
             assert_eq!(page.refcount, 1);
+            let usage = page.usage;
             page.usage = PageUsage::Available;
             page.refcount = 0;
 
             self.last_index = index;
+            self.update_stats_free(usage, 1);
         }
 
-        // FIXME
-        // self.update_stats_free(usage, 1);
-
         Ok(())
     }
 
@@ -174,6 +177,7 @@ unsafe impl Manager for SimpleManager {
             Ok(src)
         } else {
             let dst_index = self.alloc_single_index(usage)?;
+            self.update_stats_alloc(usage, 1);
             let dst = (self.base_index + dst_index) * PAGE_SIZE;
             unsafe {
                 memcpy(virtualize(dst) as *mut u8, virtualize(src) as *mut u8, 4096);
@@ -185,15 +189,23 @@ unsafe impl Manager for SimpleManager {
     fn fork_page(&mut self, src: usize) -> Result<usize, Errno> {
         let src_index = self.page_index(src);
         let page = &mut self.pages[src_index];
-        let usage = page.usage;
-        if usage != PageUsage::UserPrivate {
-            todo!("Handle page types other than UserPrivate")
-        } else {
-            page.refcount += 1;
+        match page.usage {
+            // Kernel pages are only mapped to userspace for sharing
+            PageUsage::UserPrivate | PageUsage::Kernel => page.refcount += 1,
+            usage => todo!("Handle page type {:?}", usage),
         }
         Ok(src)
     }
 
+    fn page_info(&self, page: usize) -> (PageUsage, usize) {
+        // Pages outside of managed memory (e.g. MMIO) are reported as reserved
+        (page / PAGE_SIZE)
+            .checked_sub(self.base_index)
+            .and_then(|index| self.pages.get(index))
+            .map(|page| (page.usage, page.refcount))
+            .unwrap_or((PageUsage::Reserved, 0))
+    }
+
     fn statistics(&self) -> PageStatistics {
         self.stats.clone()
     }
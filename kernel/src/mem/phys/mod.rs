@@ -9,7 +9,7 @@ mod manager;
 mod reserved;
 
 use manager::{Manager, SimpleManager, MANAGER};
-pub use reserved::ReservedRegion;
+pub use reserved::{reserve_boot_region, ReservedRegion};
 
 type ManagerImpl = SimpleManager;
 
@@ -30,6 +30,8 @@ pub enum PageUsage {
     UserPrivate,
     /// Filesystem data and blocks
     Filesystem,
+    /// Page taken away from the system (e.g. given back to the hypervisor)
+    Offline,
 }
 
 /// Represents counts of allocated/available pages
@@ -42,6 +44,7 @@ pub struct PageStatistics {
     pub paging: usize,
     pub user_private: usize,
     pub filesystem: usize,
+    pub offline: usize,
 }
 
 /// Data structure representing a single physical memory page
@@ -108,6 +111,10 @@ fn trace_free(loc: &core::panic::Location, page: usize) {
 /// Allocates a contiguous range of `count` physical memory pages.
 #[cfg_attr(feature = "verbose", track_caller)]
 pub fn alloc_contiguous_pages(pu: PageUsage, count: usize) -> Result<usize, Errno> {
+    #[cfg(feature = "fault_inject")]
+    if crate::fault::should_fail(crate::fault::FaultKind::Alloc) {
+        return Err(Errno::OutOfMemory);
+    }
     let res = MANAGER
         .lock()
         .as_mut()
@@ -123,6 +130,10 @@ pub fn alloc_contiguous_pages(pu: PageUsage, count: usize) -> Result<usize, Errn
 /// Allocates a single physical memory page.
 #[cfg_attr(feature = "verbose", track_caller)]
 pub fn alloc_page(pu: PageUsage) -> Result<usize, Errno> {
+    #[cfg(feature = "fault_inject")]
+    if crate::fault::should_fail(crate::fault::FaultKind::Alloc) {
+        return Err(Errno::OutOfMemory);
+    }
     let res = MANAGER.lock().as_mut().unwrap().alloc_page(pu);
     #[cfg(feature = "verbose")]
     if let Ok(base) = res {
@@ -145,11 +156,44 @@ pub unsafe fn free_page(page: usize) -> Result<(), Errno> {
     MANAGER.lock().as_mut().unwrap().free_page(page)
 }
 
+/// Takes a page out of use, so its memory can be handed over to the
+/// hypervisor. Returns the page address.
+pub fn offline_page() -> Result<usize, Errno> {
+    MANAGER.lock().as_mut().unwrap().alloc_page(PageUsage::Offline)
+}
+
+/// Returns an offline page back to the allocator.
+///
+/// # Safety
+///
+/// Unsafe: accepts arbitrary `page` arguments
+pub unsafe fn online_page(page: usize) -> Result<(), Errno> {
+    let mut manager = MANAGER.lock();
+    let manager = manager.as_mut().unwrap();
+    if manager.page_info(page).0 != PageUsage::Offline {
+        return Err(Errno::InvalidArgument);
+    }
+    manager.free_page(page)
+}
+
 /// Returns current statistics for page allocation
 pub fn statistics() -> PageStatistics {
     MANAGER.lock().as_ref().unwrap().statistics()
 }
 
+/// Same as [statistics], but returns `None` instead of waiting if the
+/// page manager is locked. Used by the panic console.
+pub fn try_statistics() -> Option<PageStatistics> {
+    MANAGER.lock_nowait()?.as_ref().map(|manager| manager.statistics())
+}
+
+/// Returns the usage and reference count of a physical page.
+///
+/// A page with refcount of 1 is owned by a single mapping.
+pub fn page_info(page: usize) -> (PageUsage, usize) {
+    MANAGER.lock().as_ref().unwrap().page_info(page)
+}
+
 /// Clones the source page.
 ///
 /// If returned address is the same as `page`, this means
@@ -163,6 +207,16 @@ pub unsafe fn fork_page(page: usize) -> Result<usize, Errno> {
     MANAGER.lock().as_mut().unwrap().fork_page(page)
 }
 
+/// Adds a reference to a kernel `page` about to be mapped into userspace,
+/// so the page outlives the mapping
+///
+/// # Safety
+///
+/// Unsafe: accepts arbitrary `page` arguments
+pub unsafe fn share_page(page: usize) -> Result<(), Errno> {
+    MANAGER.lock().as_mut().unwrap().fork_page(page).map(|_| ())
+}
+
 /// Copies a Copy-on-Write page. If refcount is already 1,
 /// page does not need to be copied and the same address is returned.
 ///
@@ -1,6 +1,7 @@
 use crate::mem::{kernel_end_phys, PAGE_SIZE};
 use core::mem::MaybeUninit;
 use core::ptr::null_mut;
+use libsys::error::Errno;
 
 /// Data structure representing a region of unusable memory
 pub struct ReservedRegion {
@@ -41,6 +42,14 @@ static mut RESERVED_REGION_KERNEL: MaybeUninit<ReservedRegion> = MaybeUninit::un
 static mut RESERVED_REGION_INITRD: MaybeUninit<ReservedRegion> = MaybeUninit::uninit();
 static mut RESERVED_REGION_PAGES: MaybeUninit<ReservedRegion> = MaybeUninit::uninit();
 
+/// Maximum number of regions reported by the bootloader
+const MAX_BOOT_REGIONS: usize = 16;
+#[allow(clippy::declare_interior_mutable_const)]
+const BOOT_REGION_INIT: MaybeUninit<ReservedRegion> = MaybeUninit::uninit();
+static mut RESERVED_REGIONS_BOOT: [MaybeUninit<ReservedRegion>; MAX_BOOT_REGIONS] =
+    [BOOT_REGION_INIT; MAX_BOOT_REGIONS];
+static mut RESERVED_REGIONS_BOOT_COUNT: usize = 0;
+
 /// Adds a `region` to reserved memory region list.
 ///
 /// # Safety
@@ -79,6 +88,28 @@ pub(super) unsafe fn reserve_initrd() {
     }
 }
 
+/// Reserves a `start..end` region of physical memory handed over by the
+/// bootloader, such as the device tree blob or its reserved memory
+/// entries. The region is extended to page boundaries.
+///
+/// # Safety
+///
+/// Unsafe: must be called before physical memory manager is initialized.
+pub unsafe fn reserve_boot_region(usage: &str, start: usize, end: usize) -> Result<(), Errno> {
+    if RESERVED_REGIONS_BOOT_COUNT == MAX_BOOT_REGIONS {
+        warnln!("Too many boot regions, cannot reserve {:?}", usage);
+        return Err(Errno::OutOfMemory);
+    }
+    let region = &mut RESERVED_REGIONS_BOOT[RESERVED_REGIONS_BOOT_COUNT];
+    RESERVED_REGIONS_BOOT_COUNT += 1;
+    region.write(ReservedRegion::new(
+        start & !(PAGE_SIZE - 1),
+        (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1),
+    ));
+    reserve(usage, region.as_mut_ptr());
+    Ok(())
+}
+
 /// Returns `true` if physical memory referred to by `page` cannot be
 /// used and/or allocated
 pub fn is_reserved(page: usize) -> bool {
@@ -0,0 +1,169 @@
+//! Producer side of kernel-user shared ring buffers.
+//!
+//! See [libsys::ring] for the layout. The ring's pages stay owned by the
+//! kernel, mappings into processes hold extra references to them.
+use crate::mem::{
+    self,
+    phys::{self, PageUsage},
+    virt::{MapAttributes, Space},
+    PAGE_SIZE,
+};
+use core::mem::size_of;
+use core::ptr;
+use core::sync::atomic::Ordering;
+use libsys::{
+    error::Errno,
+    ring::{
+        record_size, RecordHeader, RingConsumer, RingHeader, RingMapping, RECORD_PAD,
+        RING_HEADER_SIZE,
+    },
+};
+
+/// Ring buffer written by the kernel and readable by userspace
+pub struct SharedRing {
+    base: usize,
+    data_pages: usize,
+}
+
+// Userspace mappings are placed in the same range as anonymous memory
+const MAP_START: usize = 0x100000000;
+const MAP_END: usize = 0xF00000000;
+
+impl SharedRing {
+    /// Allocates a ring with a data area of `data_pages` pages
+    pub fn new(data_pages: usize) -> Result<Self, Errno> {
+        let base = phys::alloc_contiguous_pages(PageUsage::Kernel, 1 + data_pages)?;
+        unsafe {
+            ptr::write_bytes(mem::virtualize(base) as *mut u8, 0, RING_HEADER_SIZE);
+        }
+        let ring = Self { base, data_pages };
+        unsafe {
+            let header = mem::virtualize(base) as *mut RingHeader;
+            (*header).size = (data_pages * PAGE_SIZE) as u64;
+        }
+        Ok(ring)
+    }
+
+    fn header(&self) -> &RingHeader {
+        unsafe { &*(mem::virtualize(self.base) as *const RingHeader) }
+    }
+
+    fn data(&self) -> *mut u8 {
+        (mem::virtualize(self.base) + RING_HEADER_SIZE) as *mut u8
+    }
+
+    /// Appends a record. Returns `false` if the record was dropped because
+    /// the ring is full.
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: calls must be serialized by the caller.
+    pub unsafe fn push(&self, kind: u32, payload: &[u8]) -> bool {
+        let header = self.header();
+        let size = self.data_pages * PAGE_SIZE;
+        let need = record_size(payload.len());
+
+        let head = header.head.load(Ordering::Relaxed);
+        let tail = header.tail.load(Ordering::Acquire);
+        let offset = (head % size as u64) as usize;
+        let pad = if offset + need > size { size - offset } else { 0 };
+
+        // Tail is written by userspace, don't trust it to be sane
+        let used = head.wrapping_sub(tail) as usize;
+        if need > size / 2 || used > size || used + pad + need > size {
+            header.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        let data = self.data();
+        if pad != 0 {
+            ptr::write_volatile(
+                data.add(offset) as *mut RecordHeader,
+                RecordHeader {
+                    len: RECORD_PAD,
+                    kind: 0,
+                },
+            );
+        }
+        let offset = (offset + pad) % size;
+        ptr::write_volatile(
+            data.add(offset) as *mut RecordHeader,
+            RecordHeader {
+                len: payload.len() as u32,
+                kind,
+            },
+        );
+        ptr::copy_nonoverlapping(
+            payload.as_ptr(),
+            data.add(offset + size_of::<RecordHeader>()),
+            payload.len(),
+        );
+
+        header
+            .head
+            .store(head + (pad + need) as u64, Ordering::Release);
+        true
+    }
+
+    /// Returns `true` if there are no unread records
+    pub fn is_empty(&self) -> bool {
+        self.consumer().is_empty()
+    }
+
+    /// Returns a consumer for reading the ring from the kernel. It shares
+    /// the read position with userspace consumers.
+    pub fn consumer(&self) -> RingConsumer {
+        unsafe { RingConsumer::new(mem::virtualize(self.base)) }
+    }
+
+    /// Maps the ring into `space`: the header page writable, the data area
+    /// read-only
+    pub fn map_user(&self, space: &mut Space) -> Result<RingMapping, Errno> {
+        let pages = 1 + self.data_pages;
+        Ok(RingMapping {
+            base: map_shared(space, self.base, pages, 1)? as u64,
+            size: (pages * PAGE_SIZE) as u64,
+        })
+    }
+}
+
+/// Maps `count` physically contiguous kernel pages starting at `base` into
+/// `space`. The first `writable` pages are mapped read-write, the rest
+/// read-only. Returns the virtual address of the mapping.
+pub fn map_shared(
+    space: &mut Space,
+    base: usize,
+    count: usize,
+    writable: usize,
+) -> Result<usize, Errno> {
+    let virt = space.find_free(MAP_START, MAP_END, count)?;
+
+    let attrs = MapAttributes::NOT_GLOBAL
+        | MapAttributes::SH_OUTER
+        | MapAttributes::PXN
+        | MapAttributes::UXN;
+    for i in 0..count {
+        let access = if i < writable {
+            MapAttributes::AP_BOTH_READWRITE
+        } else {
+            MapAttributes::AP_BOTH_READONLY
+        };
+        let page = base + i * PAGE_SIZE;
+        let res = unsafe { phys::share_page(page) }.and_then(|_| {
+            space
+                .map(virt + i * PAGE_SIZE, page, attrs | access)
+                .map_err(|err| {
+                    unsafe {
+                        phys::free_page(page).ok();
+                    }
+                    err
+                })
+        });
+        if let Err(err) = res {
+            space.free(virt, i).ok();
+            return Err(err);
+        }
+    }
+
+    Ok(virt)
+}
@@ -0,0 +1,210 @@
+//! Swapping of anonymous user memory to a block device
+//!
+//! Swapped-out pages are represented by non-present translation entries
+//! carrying a swap slot number (see [crate::mem::virt::table]). A kernel
+//! process ([swapd_fn]) scans the access flags of all the processes' pages
+//! on every pass, evicts pages which were not accessed since the previous
+//! one, and page faults bring them back.
+use crate::fs::{self, pcache};
+use crate::mem::{
+    phys::{self, PageStatistics},
+    virtualize, PAGE_SIZE,
+};
+use crate::proc::{wait, Process};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{vec, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use libsys::{error::Errno, proc::SwapStatus};
+use vfs::BlockDevice;
+
+struct SwapArea {
+    dev: &'static dyn BlockDevice,
+    // Reference count of each slot, 0 means free
+    slots: Vec<u8>,
+    used: usize,
+    last: usize,
+}
+
+/// Interval between swapper passes
+const SWAPD_INTERVAL: Duration = Duration::from_millis(1000);
+/// Maximum number of pages evicted per swapper pass
+const SWAPD_BATCH: usize = 64;
+/// Maximum number of unused vnodes dropped per swapper pass
+const VNODE_RECLAIM_BATCH: usize = 64;
+
+static SWAP: IrqSafeSpinLock<Option<SwapArea>> = IrqSafeSpinLock::new(None);
+static SWAPPINESS: AtomicUsize = AtomicUsize::new(60);
+static PAGE_INS: AtomicUsize = AtomicUsize::new(0);
+static PAGE_OUTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts using `dev` as a swap area
+pub fn enable(dev: &'static dyn BlockDevice) -> Result<(), Errno> {
+    let count = dev.size()? / PAGE_SIZE;
+    if count == 0 {
+        return Err(Errno::InvalidArgument);
+    }
+
+    let mut swap = SWAP.lock();
+    if swap.is_some() {
+        return Err(Errno::Busy);
+    }
+    let slots = vec![0; count];
+    infoln!("Swap enabled: {}K", count * 4);
+    *swap = Some(SwapArea {
+        dev,
+        slots,
+        used: 0,
+        last: 0,
+    });
+    Ok(())
+}
+
+/// Sets how eagerly anonymous memory is swapped out: 0 disables the swapper,
+/// 100 starts evicting when half of the memory is in use
+pub fn set_swappiness(value: usize) -> Result<(), Errno> {
+    if value > 100 {
+        return Err(Errno::InvalidArgument);
+    }
+    SWAPPINESS.store(value, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns current swap usage and activity counters
+pub fn status() -> SwapStatus {
+    let (total, used) = SWAP
+        .lock()
+        .as_ref()
+        .map(|swap| (swap.slots.len(), swap.used))
+        .unwrap_or((0, 0));
+    SwapStatus {
+        total_pages: total,
+        used_pages: used,
+        swappiness: SWAPPINESS.load(Ordering::Relaxed),
+        page_ins: PAGE_INS.load(Ordering::Relaxed),
+        page_outs: PAGE_OUTS.load(Ordering::Relaxed),
+    }
+}
+
+/// Allocates a swap slot and writes the page at `phys` to it
+pub fn page_out(phys: usize) -> Result<usize, Errno> {
+    let (dev, slot) = {
+        let mut lock = SWAP.lock();
+        let swap = lock.as_mut().ok_or(Errno::DoesNotExist)?;
+        let count = swap.slots.len();
+        let slot = (0..count)
+            .map(|i| (swap.last + i) % count)
+            .find(|&i| swap.slots[i] == 0)
+            .ok_or(Errno::NoSpace)?;
+        swap.slots[slot] = 1;
+        swap.used += 1;
+        swap.last = slot;
+        (swap.dev, slot)
+    };
+
+    let data = unsafe { core::slice::from_raw_parts(virtualize(phys) as *const u8, PAGE_SIZE) };
+    if let Err(e) = dev.write_blocks(slot * PAGE_SIZE, data) {
+        free_slot(slot);
+        return Err(e);
+    }
+    PAGE_OUTS.fetch_add(1, Ordering::Relaxed);
+    Ok(slot)
+}
+
+/// Reads contents of swap `slot` into the page at `phys` and drops the
+/// reference to the slot
+pub fn page_in(slot: usize, phys: usize) -> Result<(), Errno> {
+    let dev = SWAP.lock().as_ref().ok_or(Errno::DoesNotExist)?.dev;
+    let data = unsafe { core::slice::from_raw_parts_mut(virtualize(phys) as *mut u8, PAGE_SIZE) };
+    dev.read_blocks(slot * PAGE_SIZE, data)?;
+    free_slot(slot);
+    PAGE_INS.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Adds a reference to swap `slot` (used when the address space is forked)
+pub fn dup_slot(slot: usize) -> Result<(), Errno> {
+    let mut lock = SWAP.lock();
+    let swap = lock.as_mut().unwrap();
+    if swap.slots[slot] == u8::MAX {
+        return Err(Errno::OutOfMemory);
+    }
+    swap.slots[slot] += 1;
+    Ok(())
+}
+
+/// Drops a reference to swap `slot`. The slot is discarded on the device
+/// once unused, so e.g. zram can release the memory behind it.
+pub fn free_slot(slot: usize) {
+    let dev = {
+        let mut lock = SWAP.lock();
+        let swap = lock.as_mut().unwrap();
+        assert_ne!(swap.slots[slot], 0);
+        swap.slots[slot] -= 1;
+        if swap.slots[slot] != 0 {
+            return;
+        }
+        swap.used -= 1;
+        swap.dev
+    };
+    // Not supported by every device and only an optimization
+    dev.discard(slot * PAGE_SIZE, PAGE_SIZE).ok();
+}
+
+/// Returns the number of pages the swapper should try to evict
+fn reclaim_target(stat: &PageStatistics) -> usize {
+    let swappiness = SWAPPINESS.load(Ordering::Relaxed);
+    let total = stat.available
+        + stat.kernel
+        + stat.kernel_heap
+        + stat.paging
+        + stat.user_private
+        + stat.filesystem;
+    // Free memory watermark: 0% at swappiness 0, 50% at 100
+    let low = total * swappiness / 200;
+    low.saturating_sub(stat.available).min(SWAPD_BATCH)
+}
+
+/// Evicts up to `count` least recently used anonymous pages to the swap area.
+/// Returns the number of pages evicted.
+pub fn reclaim(count: usize) -> usize {
+    if SWAP.lock().is_none() {
+        return 0;
+    }
+
+    let mut evicted = 0;
+    for proc in Process::list() {
+        if evicted == count {
+            break;
+        }
+        match proc.try_manipulate_space(|space| space.page_out(count - evicted)) {
+            Some(Ok(n)) => evicted += n,
+            Some(Err(e)) => {
+                warnln!("Page-out failed: {:?}", e);
+                break;
+            }
+            None => {}
+        }
+    }
+    evicted
+}
+
+/// Kernel process function which keeps free memory above the watermark
+pub fn swapd_fn() -> ! {
+    loop {
+        for proc in Process::list() {
+            proc.scan_memory();
+        }
+
+        let target = reclaim_target(&phys::statistics());
+        if target != 0 {
+            // Dropping unused vnodes orphans their cached pages, and unused
+            // executable pages are cheaper to drop than to swap out
+            fs::reclaim_vnodes(VNODE_RECLAIM_BATCH);
+            let freed = pcache::shrink();
+            reclaim(target.saturating_sub(freed));
+        }
+        let mut remaining = Duration::ZERO;
+        wait::sleep(SWAPD_INTERVAL, &mut remaining).ok();
+    }
+}
@@ -1,11 +1,16 @@
 //! Translation table manipulation facilities
 
 use crate::mem::{
-    self,
+    self, cow,
     phys::{self, PageUsage},
+    swap,
 };
 use core::ops::{Index, IndexMut};
-use libsys::{error::Errno, mem::memset};
+use libsys::{
+    error::Errno,
+    mem::memset,
+    proc::{MemoryAdvice, ProcessMemory},
+};
 
 /// Transparent wrapper structure representing a single
 /// translation table entry
@@ -40,6 +45,8 @@ bitflags! {
 
         /// Pages marked with this bit are Copy-on-Write
         const EX_COW = 1 << 55;
+        /// Pages marked with this bit are swapped out without a second chance
+        const EX_SEQUENTIAL = 1 << 56;
 
         /// UXN bit -- if set, page may not be used for instruction fetching from EL0
         const UXN = 1 << 54;
@@ -118,6 +125,8 @@ impl Entry {
     const PRESENT: u64 = 1 << 0;
     const TABLE: u64 = 1 << 1;
     const PHYS_MASK: u64 = 0x0000FFFFFFFFF000;
+    const ZERO_FILL: u64 = 1 << 57;
+    const IDLE: u64 = 1 << 58;
 
     /// Constructs a single non-present mapping
     pub const fn invalid() -> Self {
@@ -145,6 +154,86 @@ impl Entry {
         self.0 & Self::TABLE != 0
     }
 
+    /// Returns `true` if this entry refers to a page in the swap area.
+    ///
+    /// Swap entries keep the attributes of the original mapping, but have
+    /// the present bit cleared and a swap slot number in place of the address.
+    pub const fn is_swap(self) -> bool {
+        self.0 & (Self::PRESENT | Self::TABLE | Self::ZERO_FILL) == Self::TABLE
+    }
+
+    /// Returns `true` if this entry refers to a discarded page, which is
+    /// replaced with a zeroed one on next access
+    pub const fn is_zero_fill(self) -> bool {
+        self.0 & (Self::PRESENT | Self::ZERO_FILL) == Self::ZERO_FILL
+    }
+
+    const fn swap_slot(self) -> usize {
+        ((self.0 & Self::PHYS_MASK) >> 12) as usize
+    }
+
+    fn swap_out(&mut self, slot: usize) {
+        self.0 &= !(Self::PHYS_MASK | Self::PRESENT);
+        self.0 |= ((slot as u64) << 12) & Self::PHYS_MASK;
+    }
+
+    fn swap_in(&mut self, phys: usize) {
+        self.0 &= !(Self::PHYS_MASK | Self::IDLE);
+        self.0 |= (phys as u64 & Self::PHYS_MASK) | Self::PRESENT | MapAttributes::ACCESS.bits();
+    }
+
+    fn discard(&mut self) {
+        if self.is_cow() {
+            self.clear_cow();
+        }
+        self.0 &= !(Self::PHYS_MASK | Self::PRESENT);
+        self.0 |= Self::ZERO_FILL;
+    }
+
+    fn zero_fill(&mut self, phys: usize) {
+        self.0 &= !(Self::PHYS_MASK | Self::ZERO_FILL | Self::IDLE);
+        self.0 |= (phys as u64 & Self::PHYS_MASK) | Self::PRESENT | MapAttributes::ACCESS.bits();
+    }
+
+    #[inline]
+    fn is_private(self) -> bool {
+        // Read-only pages may be shared with the page cache, only writable
+        // (possibly not yet copied) pages belong to the address space
+        self.0 & MapAttributes::UXN.bits() != 0
+            && (self.0 & MapAttributes::AP_BOTH_READONLY.bits()
+                == MapAttributes::AP_BOTH_READWRITE.bits()
+                || self.is_cow())
+    }
+
+    fn set_sequential(&mut self, sequential: bool) {
+        if sequential {
+            self.0 |= MapAttributes::EX_SEQUENTIAL.bits();
+        } else {
+            self.0 &= !MapAttributes::EX_SEQUENTIAL.bits();
+        }
+    }
+
+    /// Returns `true` if the page was not accessed during the last
+    /// [Space::scan_access] interval
+    #[inline]
+    fn is_idle(self) -> bool {
+        self.0 & Self::IDLE != 0
+    }
+
+    #[inline]
+    fn is_accessed(self) -> bool {
+        self.0 & MapAttributes::ACCESS.bits() != 0
+    }
+
+    fn set_accessed(&mut self, accessed: bool) {
+        if accessed {
+            self.0 |= MapAttributes::ACCESS.bits();
+            self.0 &= !Self::IDLE;
+        } else {
+            self.0 &= !MapAttributes::ACCESS.bits();
+        }
+    }
+
     /// Returns the target address of this translation entry.
     ///
     /// # Safety
@@ -200,7 +289,8 @@ impl Space {
         let l1_table = self.0.next_level_table_or_alloc(l0i)?;
         let l2_table = l1_table.next_level_table_or_alloc(l1i)?;
 
-        if l2_table[l2i].is_present() {
+        let entry = l2_table[l2i];
+        if entry.is_present() || entry.is_swap() || entry.is_zero_fill() {
             Err(Errno::AlreadyExists)
         } else {
             l2_table[l2i] = Entry::table(phys, flags | MapAttributes::ACCESS);
@@ -230,6 +320,201 @@ impl Space {
         }
     }
 
+    fn entry_mut(&mut self, virt: usize) -> Option<&'static mut Entry> {
+        let l0i = virt >> 30;
+        let l1i = (virt >> 21) & 0x1FF;
+        let l2i = (virt >> 12) & 0x1FF;
+
+        let l1_table = self.0.next_level_table(l0i)?;
+        let l2_table = l1_table.next_level_table(l1i)?;
+
+        Some(&mut l2_table[l2i])
+    }
+
+    fn entry_mut_or_alloc(&mut self, virt: usize) -> Result<&'static mut Entry, Errno> {
+        let l0i = virt >> 30;
+        let l1i = (virt >> 21) & 0x1FF;
+        let l2i = (virt >> 12) & 0x1FF;
+
+        let l1_table = self.0.next_level_table_or_alloc(l0i)?;
+        let l2_table = l1_table.next_level_table_or_alloc(l1i)?;
+
+        Ok(&mut l2_table[l2i])
+    }
+
+    /// Returns `true` if `virt` is mapped, including pages which are
+    /// currently swapped out or discarded
+    pub fn is_mapped(&mut self, virt: usize) -> bool {
+        self.entry_mut(virt)
+            .map(|entry| entry.is_present() || entry.is_swap() || entry.is_zero_fill())
+            .unwrap_or(false)
+    }
+
+    /// Attempts to resolve a page fault at `virt` address: reads swapped-out
+    /// pages back, allocates zeroed pages in place of discarded ones,
+    /// restores the access flag cleared by the swapper or (for `write`
+    /// accesses) copies a Copy-on-Write page
+    pub fn resolve_fault(&mut self, virt: usize, write: bool) -> Result<(), Errno> {
+        let virt = virt & !0xFFF;
+        let entry = self.entry_mut(virt).ok_or(Errno::DoesNotExist)?;
+
+        if entry.is_swap() {
+            let phys = phys::alloc_page(PageUsage::UserPrivate)?;
+            if let Err(err) = swap::page_in(entry.swap_slot(), phys) {
+                unsafe {
+                    phys::free_page(phys)?;
+                }
+                return Err(err);
+            }
+            entry.swap_in(phys);
+            Ok(())
+        } else if entry.is_zero_fill() {
+            let phys = phys::alloc_page(PageUsage::UserPrivate)?;
+            unsafe {
+                memset(mem::virtualize(phys) as *mut u8, 0, 4096);
+            }
+            entry.zero_fill(phys);
+            Ok(())
+        } else if entry.is_present() && !entry.is_accessed() {
+            entry.set_accessed(true);
+            Ok(())
+        } else if write {
+            self.try_cow_copy(virt)
+        } else {
+            Err(Errno::DoesNotExist)
+        }
+    }
+
+    /// Translates `virt` for an access done by the kernel on behalf of the
+    /// space owner. Faults are resolved as if the owner performed the
+    /// access itself, pages not accessible to it are rejected.
+    pub fn translate_user(&mut self, virt: usize, write: bool) -> Result<usize, Errno> {
+        let ap_mask = MapAttributes::AP_BOTH_READONLY.bits();
+        loop {
+            let entry = self.entry_mut(virt).ok_or(Errno::DoesNotExist)?;
+            if entry.is_present() && entry.is_accessed() {
+                let ap = entry.0 & ap_mask;
+                if ap == MapAttributes::AP_BOTH_READWRITE.bits() || (!write && ap == ap_mask) {
+                    return Ok(unsafe { entry.address_unchecked() } | (virt & 0xFFF));
+                }
+            }
+            // Every resolution step changes the entry state, so this terminates
+            self.resolve_fault(virt, write)?;
+        }
+    }
+
+    /// Evicts up to `count` anonymous pages to the swap area. Only pages found
+    /// idle by the last [Space::scan_access] pass and pages advised as
+    /// [MemoryAdvice::Sequential] are evicted. Returns the number of pages
+    /// evicted.
+    ///
+    /// Shared (Copy-on-Write) and executable pages are never evicted.
+    pub fn page_out(&mut self, count: usize) -> Result<usize, Errno> {
+        let mut evicted = 0;
+        for l0i in 0..512 {
+            let l1_table = match self.0.next_level_table(l0i) {
+                Some(table) => table,
+                None => continue,
+            };
+            for l1i in 0..512 {
+                let l2_table = match l1_table.next_level_table(l1i) {
+                    Some(table) => table,
+                    None => continue,
+                };
+                for l2i in 0..512 {
+                    if evicted == count {
+                        return Ok(evicted);
+                    }
+
+                    let entry = &mut l2_table[l2i];
+                    if !entry.is_present() || entry.0 & MapAttributes::UXN.bits() == 0 {
+                        continue;
+                    }
+                    let phys = unsafe { entry.address_unchecked() };
+                    if phys::page_info(phys) != (PageUsage::UserPrivate, 1) {
+                        continue;
+                    }
+
+                    let sequential = entry.0 & MapAttributes::EX_SEQUENTIAL.bits() != 0;
+                    if !entry.is_idle() && !sequential {
+                        continue;
+                    }
+
+                    let slot = match swap::page_out(phys) {
+                        Ok(slot) => slot,
+                        Err(Errno::NoSpace) => return Ok(evicted),
+                        Err(err) => return Err(err),
+                    };
+                    entry.swap_out(slot);
+                    unsafe {
+                        phys::free_page(phys)?;
+                    }
+                    evicted += 1;
+
+                    let virt = (l0i << 30) | (l1i << 21) | (l2i << 12);
+                    unsafe {
+                        asm!("tlbi vaae1, {}", in(reg) virt >> 12);
+                    }
+                }
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Ages the resident pages: pages accessed since the previous pass get
+    /// their access flag cleared, the rest are marked idle. Returns the
+    /// memory usage of the space, with the working set being the pages
+    /// accessed since the previous pass.
+    ///
+    /// Executable pages are always counted as accessed, as instruction
+    /// fetch faults are not resolved.
+    pub fn scan_access(&mut self) -> ProcessMemory {
+        let mut usage = ProcessMemory::default();
+        for l0i in 0..512 {
+            let l1_table = match self.0.next_level_table(l0i) {
+                Some(table) => table,
+                None => continue,
+            };
+            for l1i in 0..512 {
+                let l2_table = match l1_table.next_level_table(l1i) {
+                    Some(table) => table,
+                    None => continue,
+                };
+                for l2i in 0..512 {
+                    let entry = &mut l2_table[l2i];
+                    if entry.is_swap() {
+                        usage.swapped_pages += 1;
+                        continue;
+                    }
+                    if !entry.is_present() {
+                        continue;
+                    }
+
+                    usage.resident_pages += 1;
+                    if entry.0 & MapAttributes::UXN.bits() == 0 {
+                        usage.working_set_pages += 1;
+                        continue;
+                    }
+
+                    if entry.is_accessed() {
+                        usage.working_set_pages += 1;
+                        entry.set_accessed(false);
+                        entry.0 &= !Entry::IDLE;
+
+                        let virt = (l0i << 30) | (l1i << 21) | (l2i << 12);
+                        unsafe {
+                            asm!("tlbi vaae1, {}", in(reg) virt >> 12);
+                        }
+                    } else {
+                        usage.idle_pages += 1;
+                        entry.0 |= Entry::IDLE;
+                    }
+                }
+            }
+        }
+        usage
+    }
+
     /// Attempts to resolve a page fault at `virt` address by copying the
     /// underlying Copy-on-Write mapping (if any is present)
     pub fn try_cow_copy(&mut self, virt: usize) -> Result<(), Errno> {
@@ -259,6 +544,7 @@ impl Space {
         }
 
         let dst_phys = unsafe { phys::copy_cow_page(src_phys)? };
+        cow::fault(dst_phys != src_phys);
         unsafe {
             l2_table[l2i].set_address(dst_phys);
         }
@@ -277,17 +563,22 @@ impl Space {
         flags: MapAttributes,
         usage: PageUsage,
     ) -> Result<usize, Errno> {
+        let page = self.find_free(start, end, len)?;
+        for i in 0..len {
+            let phys = phys::alloc_page(usage).unwrap();
+            self.map(page + i * 0x1000, phys, flags).unwrap();
+        }
+        Ok(page)
+    }
+
+    /// Finds `len` contiguous unmapped pages between `start` and `end`
+    pub fn find_free(&mut self, start: usize, end: usize, len: usize) -> Result<usize, Errno> {
         'l0: for page in (start..end).step_by(0x1000) {
             for i in 0..len {
-                if self.translate(page + i * 0x1000).is_ok() {
+                if self.is_mapped(page + i * 0x1000) {
                     continue 'l0;
                 }
             }
-
-            for i in 0..len {
-                let phys = phys::alloc_page(usage).unwrap();
-                self.map(page + i * 0x1000, phys, flags).unwrap();
-            }
             return Ok(page);
         }
         Err(Errno::OutOfMemory)
@@ -305,6 +596,15 @@ impl Space {
 
         let entry = l2_table[l2i];
 
+        if entry.is_swap() {
+            swap::free_slot(entry.swap_slot());
+            l2_table[l2i] = Entry::invalid();
+            return Ok(());
+        }
+        if entry.is_zero_fill() {
+            l2_table[l2i] = Entry::invalid();
+            return Ok(());
+        }
         if !entry.is_present() {
             return Err(Errno::DoesNotExist);
         }
@@ -332,6 +632,54 @@ impl Space {
         Ok(())
     }
 
+    /// Applies `advice` to a range of virtual pages:
+    ///
+    /// * [MemoryAdvice::WillNeed] reads swapped-out pages back.
+    /// * [MemoryAdvice::DontNeed] releases the private pages, which are
+    ///   replaced with zeroed ones on next access. Read-only pages are kept.
+    /// * [MemoryAdvice::Sequential] makes the swapper evict the pages
+    ///   without giving them a second chance, other advice resets this.
+    pub fn advise(&mut self, start: usize, len: usize, advice: MemoryAdvice) -> Result<(), Errno> {
+        for i in 0..len {
+            if !self.is_mapped(start + i * 0x1000) {
+                return Err(Errno::DoesNotExist);
+            }
+        }
+
+        for i in 0..len {
+            let virt = start + i * 0x1000;
+            let entry = self.entry_mut(virt).unwrap();
+
+            match advice {
+                MemoryAdvice::WillNeed => {
+                    if entry.is_swap() {
+                        self.resolve_fault(virt, false)?;
+                    }
+                    continue;
+                }
+                MemoryAdvice::DontNeed => {
+                    if !entry.is_private() {
+                        continue;
+                    }
+                    if entry.is_swap() {
+                        swap::free_slot(entry.swap_slot());
+                    } else if entry.is_present() {
+                        unsafe {
+                            phys::free_page(entry.address_unchecked())?;
+                        }
+                    }
+                    entry.discard();
+                }
+                _ => entry.set_sequential(advice == MemoryAdvice::Sequential),
+            }
+
+            unsafe {
+                asm!("tlbi vaae1, {}", in(reg) virt >> 12);
+            }
+        }
+        Ok(())
+    }
+
     /// Performs a copy of the address space, cloning data owned by it
     pub fn fork(&mut self) -> Result<&'static mut Self, Errno> {
         let res = Self::alloc_empty()?;
@@ -341,18 +689,33 @@ impl Space {
                     if let Some(l2_table) = l1_table.next_level_table(l1i) {
                         for l2i in 0..512 {
                             let entry = l2_table[l2i];
+                            let virt_addr = (l0i << 30) | (l1i << 21) | (l2i << 12);
 
+                            if entry.is_swap() {
+                                // Both spaces refer to the same slot until
+                                // each of them reads the page back
+                                swap::dup_slot(entry.swap_slot())?;
+                                *res.entry_mut_or_alloc(virt_addr)? = entry;
+                                continue;
+                            }
+                            if entry.is_zero_fill() {
+                                *res.entry_mut_or_alloc(virt_addr)? = entry;
+                                continue;
+                            }
                             if !entry.is_present() {
                                 continue;
                             }
 
                             assert!(entry.is_table());
                             let src_phys = unsafe { entry.address_unchecked() };
-                            let virt_addr = (l0i << 30) | (l1i << 21) | (l2i << 12);
                             let dst_phys = unsafe { phys::fork_page(src_phys)? };
 
                             let mut flags = unsafe { entry.fork_flags() };
-                            if dst_phys != src_phys {
+                            if phys::page_info(src_phys).0 == PageUsage::Kernel {
+                                // Kernel pages shared with userspace stay
+                                // shared
+                                res.map(virt_addr, dst_phys, flags)?;
+                            } else if dst_phys != src_phys {
                                 todo!();
                                 // res.map(virt_addr, dst_phys, flags)?;
                             } else {
@@ -363,6 +726,7 @@ impl Space {
                                     flags |=
                                         MapAttributes::AP_BOTH_READONLY | MapAttributes::EX_COW;
                                     l2_table[l2i].set_cow();
+                                    cow::page_shared();
 
                                     unsafe {
                                         asm!("tlbi vaae1, {}", in(reg) virt_addr);
@@ -406,6 +770,10 @@ impl Space {
 
                 for l2i in 0..512 {
                     let entry = l2_table[l2i];
+                    if entry.is_swap() {
+                        swap::free_slot(entry.swap_slot());
+                        continue;
+                    }
                     if !entry.is_present() {
                         continue;
                     }
@@ -0,0 +1,157 @@
+//! Kernel panic handling.
+//!
+//! The panic handler takes synchronous ownership of the console: IRQs stay
+//! masked and console devices are used directly, even if the panicking
+//! context held their locks. What happens next is selected by `panic=`
+//! kernel parameter:
+//!
+//! * `halt` (default): the machine halts
+//! * `reboot`: the board is reset
+//! * `prompt`: a minimal interactive prompt is offered on the console,
+//!   which can dump processes and memory statistics or reset the board
+use crate::arch::machine;
+use crate::config::{ConfigKey, CONFIG};
+use crate::debug;
+use crate::dev::{led, serial::SerialDevice};
+use crate::mem::phys;
+use crate::proc::{self, Process};
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Copy, PartialEq)]
+enum PanicAction {
+    Halt,
+    Reboot,
+    Prompt,
+}
+
+struct ConsoleOutput(&'static dyn SerialDevice);
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+impl fmt::Write for ConsoleOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if byte == b'\n' {
+                self.0.send(b'\r').ok();
+            }
+            self.0.send(byte).ok();
+        }
+        Ok(())
+    }
+}
+
+fn action() -> PanicAction {
+    // The config may have been locked by the panicking context
+    match CONFIG.lock_nowait() {
+        Some(cfg) => match cfg.get_str(ConfigKey::Panic) {
+            "reboot" => PanicAction::Reboot,
+            "prompt" => PanicAction::Prompt,
+            _ => PanicAction::Halt,
+        },
+        None => PanicAction::Halt,
+    }
+}
+
+fn halt() -> ! {
+    loop {
+        cortex_a::asm::wfe();
+    }
+}
+
+fn read_line<'a>(console: &'static dyn SerialDevice, buf: &'a mut [u8]) -> &'a str {
+    let mut len = 0;
+    loop {
+        let byte = match console.recv(true) {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
+        match byte {
+            b'\r' | b'\n' => break,
+            0x7F | 0x08 if len > 0 => {
+                len -= 1;
+                ConsoleOutput(console).write_str("\x08 \x08").ok();
+            }
+            0x20..=0x7E if len < buf.len() => {
+                buf[len] = byte;
+                len += 1;
+                console.send(byte).ok();
+            }
+            _ => (),
+        }
+    }
+    ConsoleOutput(console).write_str("\n").ok();
+    core::str::from_utf8(&buf[..len]).unwrap_or("")
+}
+
+fn prompt(console: &'static dyn SerialDevice) -> ! {
+    let mut out = ConsoleOutput(console);
+    let mut buf = [0; 32];
+
+    writeln!(out, "\nEmergency prompt, type \"help\" for commands").ok();
+    loop {
+        write!(out, "panic> ").ok();
+        match read_line(console, &mut buf).trim() {
+            "" => (),
+            "help" => {
+                writeln!(out, "ps      list processes").ok();
+                writeln!(out, "mem     show physical memory usage").ok();
+                writeln!(out, "reboot  reset the board").ok();
+                writeln!(out, "halt    stop the machine").ok();
+            }
+            "ps" => {
+                if !Process::dump(&mut out) {
+                    writeln!(out, "Process table is locked").ok();
+                }
+            }
+            "mem" => match phys::try_statistics() {
+                Some(stat) => {
+                    writeln!(out, "Pages: {} available", stat.available).ok();
+                    writeln!(
+                        out,
+                        "       {} kernel, {} heap, {} paging",
+                        stat.kernel, stat.kernel_heap, stat.paging
+                    )
+                    .ok();
+                    writeln!(
+                        out,
+                        "       {} user, {} filesystem, {} offline",
+                        stat.user_private, stat.filesystem, stat.offline
+                    )
+                    .ok();
+                }
+                None => {
+                    writeln!(out, "Page manager is locked").ok();
+                }
+            },
+            "reboot" => unsafe { machine::reset_board() },
+            "halt" => halt(),
+            cmd => {
+                writeln!(out, "Unknown command: {:?}", cmd).ok();
+            }
+        }
+    }
+}
+
+/// Reports the panic and halts, resets or drops into the emergency prompt
+pub fn handle(pi: &PanicInfo) -> ! {
+    if PANICKED.swap(true, Ordering::AcqRel) {
+        // Panic while panicking, e.g. in a console driver
+        halt();
+    }
+
+    let console = unsafe { debug::take_over() };
+    errorln!("Panic: {:?}", pi);
+    if let Some(name) = proc::try_current_thread().and_then(|thread| thread.try_name()) {
+        errorln!("Current thread: {}", name);
+    }
+    debug::flush_early();
+    led::panic();
+
+    match (action(), console) {
+        (PanicAction::Reboot, _) => unsafe { machine::reset_board() },
+        (PanicAction::Prompt, Some(console)) => prompt(console),
+        _ => halt(),
+    }
+}
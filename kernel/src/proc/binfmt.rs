@@ -0,0 +1,182 @@
+//! Executable format registry.
+//!
+//! Each registered [BinaryFormat] is probed against the first bytes of the
+//! file being executed. The first format with matching magic takes over
+//! loading of the image.
+use crate::mem::virt::Space;
+use crate::proc::elf;
+use crate::sync::IrqSafeSpinLock;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use libsys::{
+    error::Errno,
+    stat::{FileMode, GroupId, MountFlags, OpenFlags, UserId},
+    traits::{Read, Seek, SeekDir},
+};
+use vfs::{FileRef, Ioctx};
+
+/// Native image loader function: maps the image into the address space
+/// and returns its entry point
+pub type LoadFn = fn(&mut Space, FileRef) -> Result<usize, Errno>;
+
+/// Describes how a matched executable is handled
+#[derive(Clone, Copy)]
+pub enum Handler {
+    /// Image is loaded directly into the process address space
+    Native(LoadFn),
+    /// Image is passed as an argument to an interpreter program
+    Interpreter(&'static str),
+    /// Image is a script with "#!" interpreter line
+    Script,
+}
+
+/// Executable format descriptor
+pub struct BinaryFormat {
+    /// Format name
+    pub name: &'static str,
+    /// Magic bytes the file has to start with
+    pub magic: &'static [u8],
+    /// Loading strategy
+    pub handler: Handler,
+}
+
+/// Result of executable resolution
+pub struct Resolved {
+    /// File to be loaded
+    pub file: FileRef,
+    /// Loader function
+    pub load: LoadFn,
+    /// Final argument list, with interpreters prepended
+    pub argv: Vec<String>,
+    /// Effective user ID the image runs with, if the file is set-user-ID
+    pub euid: Option<UserId>,
+    /// Effective group ID the image runs with, if the file is set-group-ID
+    pub egid: Option<GroupId>,
+}
+
+const HEADER_SIZE: usize = 128;
+const MAX_INTERPRETER_DEPTH: usize = 4;
+
+static FORMATS: IrqSafeSpinLock<Vec<&'static BinaryFormat>> = IrqSafeSpinLock::new(Vec::new());
+
+static FORMAT_ELF: BinaryFormat = BinaryFormat {
+    name: "elf",
+    magic: b"\x7FELF",
+    handler: Handler::Native(elf::load_elf),
+};
+static FORMAT_FLAT: BinaryFormat = BinaryFormat {
+    name: "flat",
+    magic: elf::FLAT_MAGIC,
+    handler: Handler::Native(elf::load_flat),
+};
+static FORMAT_SCRIPT: BinaryFormat = BinaryFormat {
+    name: "script",
+    magic: b"#!",
+    handler: Handler::Script,
+};
+static FORMAT_WASM: BinaryFormat = BinaryFormat {
+    name: "wasm",
+    magic: b"\0asm",
+    handler: Handler::Interpreter("/bin/wasm"),
+};
+
+/// Registers an executable format. Formats registered later take
+/// precedence over the earlier ones.
+pub fn register(format: &'static BinaryFormat) {
+    infoln!("Register binary format: {}", format.name);
+    FORMATS.lock().insert(0, format);
+}
+
+/// Registers built-in executable formats
+pub fn init() {
+    register(&FORMAT_WASM);
+    register(&FORMAT_SCRIPT);
+    register(&FORMAT_FLAT);
+    register(&FORMAT_ELF);
+}
+
+fn read_header(file: &FileRef, buf: &mut [u8]) -> Result<usize, Errno> {
+    let mut file = file.borrow_mut();
+    file.seek(0, SeekDir::Set)?;
+    // Disk filesystems may return less than asked for
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..])? {
+            0 => break,
+            count => len += count,
+        }
+    }
+    Ok(len)
+}
+
+fn find_handler(header: &[u8]) -> Option<Handler> {
+    FORMATS
+        .lock()
+        .iter()
+        .find(|f| header.starts_with(f.magic))
+        .map(|f| f.handler)
+}
+
+fn parse_script(header: &[u8]) -> Result<(String, Option<String>), Errno> {
+    let line = &header[2..];
+    let end = line.iter().position(|&c| c == b'\n').ok_or(Errno::BadExecutable)?;
+    let line = core::str::from_utf8(&line[..end]).map_err(|_| Errno::BadExecutable)?;
+    let mut iter = line.trim().splitn(2, ' ');
+    let interp = iter.next().filter(|s| !s.is_empty()).ok_or(Errno::BadExecutable)?;
+    let arg = iter.next().map(str::trim).filter(|s| !s.is_empty());
+    Ok((interp.to_owned(), arg.map(str::to_owned)))
+}
+
+/// Finds the handler for executable at `path` and resolves any
+/// interpreter indirections
+pub fn resolve(ioctx: &Ioctx, path: &str, argv: &[&str]) -> Result<Resolved, Errno> {
+    let mut path = path.to_owned();
+    let mut argv: Vec<String> = argv.iter().map(|&e| e.to_owned()).collect();
+    let mut header = [0u8; HEADER_SIZE];
+
+    for _ in 0..MAX_INTERPRETER_DEPTH {
+        let node = ioctx.find(None, &path, true)?;
+        if node.mount_flags().contains(MountFlags::MS_NOEXEC) {
+            return Err(Errno::PermissionDenied);
+        }
+        let file = node.open(OpenFlags::O_RDONLY | OpenFlags::O_EXEC)?;
+        let len = read_header(&file, &mut header)?;
+
+        let (interp, arg) = match find_handler(&header[..len]).ok_or(Errno::BadExecutable)? {
+            Handler::Native(load) => {
+                // Set-ID bits of scripts are ignored, only the ones of the
+                // native image (which may be the interpreter) apply
+                let (euid, egid) = if node.mount_flags().contains(MountFlags::MS_NOSUID) {
+                    (None, None)
+                } else {
+                    let props = node.props();
+                    (
+                        props.mode.contains(FileMode::SETUID).then(|| props.uid),
+                        props.mode.contains(FileMode::SETGID).then(|| props.gid),
+                    )
+                };
+                return Ok(Resolved {
+                    file,
+                    load,
+                    argv,
+                    euid,
+                    egid,
+                });
+            }
+            Handler::Interpreter(interp) => (interp.to_owned(), None),
+            Handler::Script => parse_script(&header[..len])?,
+        };
+
+        // argv[0] gets replaced by the script path
+        if !argv.is_empty() {
+            argv.remove(0);
+        }
+        argv.insert(0, path);
+        if let Some(arg) = arg {
+            argv.insert(0, arg);
+        }
+        argv.insert(0, interp.clone());
+        path = interp;
+    }
+
+    Err(Errno::BadExecutable)
+}
@@ -1,4 +1,6 @@
 //! Executable and Linkable Format binary loader module
+use crate::fs::pcache;
+use crate::proc::Process;
 use crate::mem::{
     self,
     phys::{self, PageUsage},
@@ -9,7 +11,7 @@ use libsys::{
     error::Errno,
     traits::{Read, Seek, SeekDir}
 };
-use vfs::FileRef;
+use vfs::{FileRef, VnodeRef};
 
 trait Elf {
     type Addr;
@@ -65,7 +67,7 @@ struct Phdr<E: Elf> {
     align: E::Xword,
 }
 
-fn map_flags(elf_flags: usize) -> MapAttributes {
+fn map_flags(elf_flags: usize) -> Result<MapAttributes, Errno> {
     let mut dst_flags = MapAttributes::NOT_GLOBAL | MapAttributes::SH_OUTER;
 
     if elf_flags & (1 << 0) /* PF_X */ == 0 {
@@ -73,10 +75,8 @@ fn map_flags(elf_flags: usize) -> MapAttributes {
     }
 
     match (elf_flags & (3 << 1)) >> 1 {
-        // No access: not sure if such mapping should exist at all
-        0 => todo!(),
-        // Write-only: not sure if such mapping should exist at all
-        1 => todo!(),
+        // No access or write-only segments are not supported
+        0 | 1 => return Err(Errno::BadExecutable),
         // Read-only
         2 => dst_flags |= MapAttributes::AP_BOTH_READONLY,
         // Read+Write
@@ -84,7 +84,16 @@ fn map_flags(elf_flags: usize) -> MapAttributes {
         _ => unreachable!(),
     };
 
-    dst_flags
+    Ok(dst_flags)
+}
+
+/// Checks that a region of `size` bytes at `start` fits into the user
+/// image area below the stack, returning its end address
+fn user_region(start: usize, size: usize) -> Result<usize, Errno> {
+    match start.checked_add(size) {
+        Some(end) if end <= Process::USTACK_VIRT_BOTTOM => Ok(end),
+        _ => Err(Errno::BadExecutable),
+    }
 }
 
 unsafe fn load_bytes<F>(
@@ -97,6 +106,7 @@ unsafe fn load_bytes<F>(
 where
     F: FnMut(usize, &mut [u8]) -> Result<(), Errno>,
 {
+    let attrs = map_flags(flags)?;
     let dst_page_off = dst_virt & 0xFFF;
     let dst_page = dst_virt & !0xFFF;
     let mut off = 0usize;
@@ -111,7 +121,7 @@ where
 
         // TODO fetch existing mapping and test flag equality instead
         //      if flags differ, bail out
-        if let Err(e) = space.map(dst_page + page_idx * mem::PAGE_SIZE, page, map_flags(flags)) {
+        if let Err(e) = space.map(dst_page + page_idx * mem::PAGE_SIZE, page, attrs) {
             if e != Errno::AlreadyExists {
                 return Err(e);
             }
@@ -129,38 +139,88 @@ where
     Ok(())
 }
 
+/// Maps the pages of a read-only file-backed region which are fully
+/// covered by it straight from the page cache, so they are shared with
+/// other processes running the same file. Returns the mapped sub-region.
+fn map_shared(
+    space: &mut Space,
+    node: &VnodeRef,
+    dst_virt: usize,
+    offset: usize,
+    size: usize,
+    flags: usize,
+) -> Result<(usize, usize), Errno> {
+    let start = (dst_virt + mem::PAGE_SIZE - 1) & !0xFFF;
+    let end = (dst_virt + size) & !0xFFF;
+    if start >= end {
+        return Ok((dst_virt + size, dst_virt + size));
+    }
+
+    let attrs = map_flags(flags)?;
+    for virt in (start..end).step_by(mem::PAGE_SIZE) {
+        let page = pcache::get_page(node, offset + (virt - dst_virt))?;
+        if let Err(e) = space.map(virt, page, attrs) {
+            unsafe {
+                phys::free_page(page)?;
+            }
+            if e != Errno::AlreadyExists {
+                return Err(e);
+            }
+        }
+    }
+
+    Ok((start, end))
+}
+
+/// Fills `dst` with file data at `pos`. Reads from disk filesystems may
+/// return less than requested, so these are repeated until the end of
+/// the file.
+fn read_exact(source: &FileRef, pos: usize, dst: &mut [u8]) -> Result<(), Errno> {
+    let mut source = source.borrow_mut();
+    source.seek(pos as isize, SeekDir::Set)?;
+    let mut off = 0;
+    while off < dst.len() {
+        match source.read(&mut dst[off..])? {
+            0 => return Err(Errno::InvalidFile),
+            count => off += count,
+        }
+    }
+    Ok(())
+}
+
 unsafe fn read_struct<T>(src: &FileRef, pos: usize) -> Result<T, Errno> {
-    let mut src_borrow = src.borrow_mut();
     let mut storage: MaybeUninit<T> = MaybeUninit::uninit();
-    let size = size_of::<T>();
-    src_borrow.seek(pos as isize, SeekDir::Set)?;
-    let res = src_borrow.read(core::slice::from_raw_parts_mut(
-        storage.as_mut_ptr() as *mut u8,
-        size,
-    ))?;
-    if res != size {
-        Err(Errno::InvalidFile)
-    } else {
-        Ok(storage.assume_init())
-    }
+    read_exact(
+        src,
+        pos,
+        core::slice::from_raw_parts_mut(storage.as_mut_ptr() as *mut u8, size_of::<T>()),
+    )?;
+    Ok(storage.assume_init())
 }
 
 /// Loads an ELF program from `source` into target `space`
 pub fn load_elf(space: &mut Space, source: FileRef) -> Result<usize, Errno> {
-    let ehdr: Ehdr<Elf64> = unsafe { read_struct(&source, 0).unwrap() };
+    let ehdr: Ehdr<Elf64> = unsafe { read_struct(&source, 0)? };
 
     if &ehdr.ident[0..4] != b"\x7FELF" {
         return Err(Errno::BadExecutable);
     }
+    if (ehdr.phentsize as usize) < size_of::<Phdr<Elf64>>() {
+        return Err(Errno::BadExecutable);
+    }
 
     for i in 0..(ehdr.phnum as usize) {
-        let phdr: Phdr<Elf64> = unsafe {
-            read_struct(&source, ehdr.phoff as usize + ehdr.phentsize as usize * i).unwrap()
-        };
+        let phdr: Phdr<Elf64> =
+            unsafe { read_struct(&source, ehdr.phoff as usize + ehdr.phentsize as usize * i)? };
 
         if phdr.typ == 1
         /* PT_LOAD */
         {
+            if phdr.filesz > phdr.memsz {
+                return Err(Errno::BadExecutable);
+            }
+            user_region(phdr.vaddr as usize, phdr.memsz as usize)?;
+
             debugln!(
                 "Load region {:#x}..{:#x}..{:#x}",
                 phdr.vaddr,
@@ -169,22 +229,40 @@ pub fn load_elf(space: &mut Space, source: FileRef) -> Result<usize, Errno> {
             );
 
             if phdr.filesz > 0 {
-                unsafe {
-                    load_bytes(
-                        space,
-                        phdr.vaddr as usize,
-                        |off, dst| {
-                            let mut source = source.borrow_mut();
-                            source.seek(phdr.offset as isize + off as isize, SeekDir::Set)?;
-                            if source.read(dst)? == dst.len() {
-                                Ok(())
-                            } else {
-                                Err(Errno::InvalidFile)
-                            }
-                        },
-                        phdr.filesz as usize,
-                        phdr.flags as usize,
-                    )?;
+                let vaddr = phdr.vaddr as usize;
+                let offset = phdr.offset as usize;
+                let size = phdr.filesz as usize;
+                let end = vaddr + size;
+
+                // Read-only segments which are laid out in the file the same
+                // way as in memory can be shared
+                let shareable = phdr.flags & (1 << 1) /* PF_W */ == 0
+                    && vaddr.wrapping_sub(offset) % mem::PAGE_SIZE == 0;
+                let node = source.borrow().node();
+                let (shared_start, shared_end) = match node {
+                    Some(node) if shareable => {
+                        map_shared(space, &node, vaddr, offset, size, phdr.flags as usize)?
+                    }
+                    _ => (end, end),
+                };
+
+                // Load the parts of the segment which were not shared
+                for (start, len) in [
+                    (vaddr, shared_start - vaddr),
+                    (shared_end, end - shared_end),
+                ] {
+                    if len == 0 {
+                        continue;
+                    }
+                    unsafe {
+                        load_bytes(
+                            space,
+                            start,
+                            |off, dst| read_exact(&source, offset + (start - vaddr) + off, dst),
+                            len,
+                            phdr.flags as usize,
+                        )?;
+                    }
                 }
             }
 
@@ -208,3 +286,77 @@ pub fn load_elf(space: &mut Space, source: FileRef) -> Result<usize, Errno> {
 
     Ok(ehdr.entry as usize)
 }
+
+/// Magic bytes of a flat binary image
+pub const FLAT_MAGIC: &[u8] = b"bFLT";
+
+#[repr(C)]
+struct FlatHeader {
+    magic: [u8; 4],
+    version: u32,
+    load_addr: u64,
+    entry: u64,
+    mem_size: u64,
+}
+
+/// Loads a flat binary image from `source` into target `space`.
+///
+/// The image consists of a [FlatHeader] followed by the raw contents,
+/// which are mapped read-write-execute at `load_addr`. The remainder up to
+/// `mem_size` is zero-filled.
+pub fn load_flat(space: &mut Space, source: FileRef) -> Result<usize, Errno> {
+    let hdr: FlatHeader = unsafe { read_struct(&source, 0)? };
+
+    if hdr.magic != FLAT_MAGIC || hdr.version != 1 {
+        return Err(Errno::BadExecutable);
+    }
+
+    let data_offset = size_of::<FlatHeader>();
+    let file_size = source
+        .borrow()
+        .node()
+        .ok_or(Errno::InvalidFile)?
+        .size()?
+        .checked_sub(data_offset)
+        .ok_or(Errno::BadExecutable)?;
+    let load_addr = hdr.load_addr as usize;
+    let mem_size = hdr.mem_size as usize;
+    if mem_size < file_size {
+        return Err(Errno::BadExecutable);
+    }
+    let end = user_region(load_addr, mem_size)?;
+    // PF_R | PF_W | PF_X
+    let flags = 7;
+
+    debugln!(
+        "Load flat image {:#x}..{:#x}..{:#x}",
+        load_addr,
+        load_addr + file_size,
+        end
+    );
+
+    unsafe {
+        load_bytes(
+            space,
+            load_addr,
+            |off, dst| read_exact(&source, data_offset + off, dst),
+            file_size,
+            flags,
+        )?;
+
+        if mem_size > file_size {
+            load_bytes(
+                space,
+                load_addr + file_size,
+                |_, dst| {
+                    dst.fill(0);
+                    Ok(())
+                },
+                mem_size - file_size,
+                flags,
+            )?;
+        }
+    }
+
+    Ok(hdr.entry as usize)
+}
@@ -1,19 +1,81 @@
 //! Process file descriptors and I/O context
+use crate::fs::fifo;
+use crate::proc::{KernelObject, KernelObjectType};
 use alloc::collections::BTreeMap;
-use libsys::{error::Errno, stat::{FileDescriptor, UserId, GroupId}};
-use vfs::{FileRef, Ioctx, VnodeRef, VnodeKind};
+use alloc::rc::{Rc, Weak};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use libsys::{error::Errno, proc::Capabilities, stat::{FileDescriptor, UserId, GroupId}};
+use vfs::{File, FileRef, Ioctx, VnodeRef, VnodeKind};
 
-/// Process I/O context. Contains file tables, root/cwd info etc.
+/// Incremented on every change to any descriptor table, invalidating
+/// per-thread lookup caches
+static FD_TABLE_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Single-entry descriptor lookup cache of a thread. Lets repeated
+/// lookups of the same descriptor (e.g. read/write loops) bypass the
+/// [ProcessIo] lock.
+///
+/// The cache only holds a weak reference, so closing the descriptor
+/// still releases the open file description immediately.
+#[derive(Default)]
+pub struct FileLookupCache {
+    entry: Option<(u32, usize, Weak<RefCell<File>>)>,
+}
+
+/// Descriptor table entry: refers to a (possibly shared) kernel object,
+/// e.g. an open file description, and holds per-descriptor flags
+#[derive(Clone)]
+struct DescriptorEntry {
+    object: KernelObject,
+    cloexec: bool,
+}
+
+/// Process I/O context. Contains descriptor tables, root/cwd info etc.
 pub struct ProcessIo {
     ioctx: Option<Ioctx>,
-    files: BTreeMap<u32, FileRef>,
+    files: BTreeMap<u32, DescriptorEntry>,
     ctty: Option<VnodeRef>,
 }
 
+impl FileLookupCache {
+    /// Constructs an empty cache
+    pub const fn new() -> Self {
+        Self { entry: None }
+    }
+
+    /// Returns [File] referred to by `fd`, either from the cache or, if the
+    /// cached entry is missing or stale, through the `slow` lookup path
+    pub fn lookup<F: FnOnce() -> Result<FileRef, Errno>>(
+        &mut self,
+        fd: FileDescriptor,
+        slow: F,
+    ) -> Result<FileRef, Errno> {
+        let idx = u32::from(fd);
+        let gen = FD_TABLE_GENERATION.load(Ordering::Acquire);
+        if let Some((cached_idx, cached_gen, file)) = self.entry.as_ref() {
+            if *cached_idx == idx && *cached_gen == gen {
+                if let Some(file) = file.upgrade() {
+                    return Ok(file);
+                }
+            }
+        }
+
+        let file = slow()?;
+        self.entry = Some((idx, gen, Rc::downgrade(&file)));
+        Ok(file)
+    }
+}
+
 impl ProcessIo {
-    /// Clones this I/O context
+    #[inline(always)]
+    fn invalidate_lookups() {
+        FD_TABLE_GENERATION.fetch_add(1, Ordering::Release);
+    }
+
+    /// Clones this I/O context. Descriptors of the new context refer to the
+    /// same open file descriptions.
     pub fn fork(&self) -> Result<ProcessIo, Errno> {
-        // TODO
         let mut dst = ProcessIo::new();
         for (&fd, entry) in self.files.iter() {
             dst.files.insert(fd, entry.clone());
@@ -45,53 +107,87 @@ impl ProcessIo {
         self.ioctx.as_ref().unwrap().gid
     }
 
-    /// Changes (if permitted) user ID of the process
+    /// Returns effective user ID of the process
     #[inline(always)]
-    pub fn set_uid(&mut self, uid: UserId) -> Result<(), Errno> {
-        let old_uid = self.uid();
-        if old_uid == uid {
-            Ok(())
-        } else if !old_uid.is_root() {
-            Err(Errno::PermissionDenied)
-        } else {
-            self.ioctx.as_mut().unwrap().uid = uid;
-            Ok(())
-        }
+    pub fn euid(&self) -> UserId {
+        self.ioctx.as_ref().unwrap().euid
     }
 
-    /// Changes (if permitted) group ID of the process
+    /// Returns effective group ID of the process
     #[inline(always)]
-    pub fn set_gid(&mut self, gid: GroupId) -> Result<(), Errno> {
-        let old_gid = self.gid();
-        if old_gid == gid {
-            Ok(())
-        } else if !old_gid.is_root() {
-            Err(Errno::PermissionDenied)
-        } else {
-            self.ioctx.as_mut().unwrap().gid = gid;
-            Ok(())
-        }
+    pub fn egid(&self) -> GroupId {
+        self.ioctx.as_ref().unwrap().egid
     }
 
-    /// Clones a file descriptor into an available slot or, if specified, requested one
+    /// Changes both real and effective user IDs of the process. Permission
+    /// checks are the responsibility of the caller (see [Capabilities::SETUID]).
+    ///
+    /// [Capabilities::SETUID]: libsys::proc::Capabilities::SETUID
+    #[inline(always)]
+    pub fn set_uid(&mut self, uid: UserId) {
+        let ioctx = self.ioctx.as_mut().unwrap();
+        ioctx.uid = uid;
+        ioctx.euid = uid;
+    }
+
+    /// Changes both real and effective group IDs of the process. Permission
+    /// checks are the responsibility of the caller (see [Capabilities::SETUID]).
+    ///
+    /// [Capabilities::SETUID]: libsys::proc::Capabilities::SETUID
+    #[inline(always)]
+    pub fn set_gid(&mut self, gid: GroupId) {
+        let ioctx = self.ioctx.as_mut().unwrap();
+        ioctx.gid = gid;
+        ioctx.egid = gid;
+    }
+
+    /// Changes effective user ID of the process
+    #[inline(always)]
+    pub fn set_euid(&mut self, uid: UserId) {
+        self.ioctx.as_mut().unwrap().euid = uid;
+    }
+
+    /// Changes effective group ID of the process
+    #[inline(always)]
+    pub fn set_egid(&mut self, gid: GroupId) {
+        self.ioctx.as_mut().unwrap().egid = gid;
+    }
+
+    /// Clones a descriptor into an available slot or, if specified, requested one.
+    /// The new descriptor shares the kernel object, but not the close-on-exec flag.
     pub fn duplicate_file(&mut self, src: FileDescriptor, dst: Option<FileDescriptor>) -> Result<FileDescriptor, Errno> {
-        let file_ref = self.file(src)?;
+        let object = self.object(src)?;
         if let Some(dst) = dst {
-            let idx = u32::from(dst);
-            if self.files.get(&idx).is_some() {
-                return Err(Errno::AlreadyExists);
-            }
-
-            self.files.insert(idx, file_ref);
+            self.set_object(dst, object)?;
             Ok(dst)
         } else {
-            self.place_file(file_ref)
+            self.place_object(object, false)
         }
     }
 
+    /// Returns the kernel object referred to by descriptor `fd`
+    pub fn object(&self, fd: FileDescriptor) -> Result<KernelObject, Errno> {
+        self.files
+            .get(&u32::from(fd))
+            .map(|e| e.object.clone())
+            .ok_or(Errno::InvalidFile)
+    }
+
+    /// Returns the kernel object of type `T` referred to by descriptor `fd`.
+    /// Fails with [Errno::InvalidFile] if the descriptor refers to an object of
+    /// another type.
+    pub fn get<T: KernelObjectType>(&self, fd: FileDescriptor) -> Result<T, Errno> {
+        self.files
+            .get(&u32::from(fd))
+            .and_then(|e| T::downcast(&e.object))
+            .ok_or(Errno::InvalidFile)
+    }
+
     /// Returns [File] struct referred to by file descriptor `idx`
+    ///
+    /// [File]: vfs::File
     pub fn file(&mut self, fd: FileDescriptor) -> Result<FileRef, Errno> {
-        self.files.get(&u32::from(fd)).cloned().ok_or(Errno::InvalidFile)
+        self.get(fd)
     }
 
     /// Returns [Ioctx] structure reference of this I/O context
@@ -99,21 +195,31 @@ impl ProcessIo {
         self.ioctx.as_mut().unwrap()
     }
 
-    /// Allocates a file descriptor and associates a [File] struct with it
-    pub fn place_file(&mut self, file: FileRef) -> Result<FileDescriptor, Errno> {
+    /// Allocates a descriptor and associates a kernel object with it
+    pub fn place_object(&mut self, object: KernelObject, cloexec: bool) -> Result<FileDescriptor, Errno> {
         for idx in 0..64 {
             if self.files.get(&idx).is_none() {
-                self.files.insert(idx, file);
+                self.files.insert(idx, DescriptorEntry { object, cloexec });
+                Self::invalidate_lookups();
                 return Ok(FileDescriptor::from(idx));
             }
         }
         Err(Errno::TooManyDescriptors)
     }
 
-    /// Performs [File] close and releases its associated file descriptor `idx`
+    /// Allocates a file descriptor and associates a [File] struct with it
+    ///
+    /// [File]: vfs::File
+    pub fn place_file(&mut self, file: FileRef, cloexec: bool) -> Result<FileDescriptor, Errno> {
+        self.place_object(KernelObject::File(file), cloexec)
+    }
+
+    /// Releases descriptor `idx` and the reference to its kernel object
     pub fn close_file(&mut self, idx: FileDescriptor) -> Result<(), Errno> {
         let res = self.files.remove(&u32::from(idx));
         assert!(res.is_some());
+        Self::invalidate_lookups();
+        res.unwrap().object.on_close();
         Ok(())
     }
 
@@ -126,30 +232,47 @@ impl ProcessIo {
         }
     }
 
-    /// Assigns a descriptor number to an open file. If the number is not available,
-    /// returns [Errno::AlreadyExists].
-    pub fn set_file(&mut self, idx: FileDescriptor, file: FileRef) -> Result<(), Errno> {
+    /// Assigns a descriptor number to a kernel object. If the number is not
+    /// available, returns [Errno::AlreadyExists].
+    pub fn set_object(&mut self, idx: FileDescriptor, object: KernelObject) -> Result<(), Errno> {
         let idx = u32::from(idx);
         if self.files.get(&idx).is_none() {
-            self.files.insert(idx, file);
+            self.files.insert(idx, DescriptorEntry { object, cloexec: false });
+            Self::invalidate_lookups();
             Ok(())
         } else {
             Err(Errno::AlreadyExists)
         }
     }
 
+    /// Assigns a descriptor number to an open file. If the number is not available,
+    /// returns [Errno::AlreadyExists].
+    pub fn set_file(&mut self, idx: FileDescriptor, file: FileRef) -> Result<(), Errno> {
+        self.set_object(idx, KernelObject::File(file))
+    }
+
+    /// Updates the capability set used for filesystem permission checks
+    pub fn set_capabilities(&mut self, caps: Capabilities) {
+        if let Some(ioctx) = self.ioctx.as_mut() {
+            ioctx.caps = caps;
+        }
+    }
+
     /// Changes process I/O context: root and cwd
     pub fn set_ioctx(&mut self, ioctx: Ioctx) {
         self.ioctx.replace(ioctx);
     }
 
     pub(super) fn handle_cloexec(&mut self) {
-        self.files.retain(|_, entry| !entry.borrow().is_cloexec());
+        self.files.retain(|_, entry| !entry.cloexec);
+        Self::invalidate_lookups();
     }
 
     pub(super) fn handle_exit(&mut self) {
         self.files.clear();
         self.ioctx.take();
+        Self::invalidate_lookups();
+        fifo::notify();
     }
 }
 
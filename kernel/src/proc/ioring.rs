@@ -0,0 +1,363 @@
+//! Asynchronous I/O submission rings, see [libsys::ioring].
+//!
+//! Entering a ring queues it to the `ioring` kernel thread, which takes the
+//! submitted requests and executes them one by one against the files of
+//! the process which entered the ring. User buffers are accessed through
+//! their physical pages, which are referenced for the duration of the
+//! transfer. Only regular files and block devices are accepted, so the
+//! thread never blocks waiting for e.g. terminal input.
+use crate::arch::machine;
+use crate::dev::timer::TimestampSource;
+use crate::mem::{
+    self,
+    phys::{self, PageUsage},
+    ring::map_shared,
+    PAGE_SIZE,
+};
+use crate::proc::{
+    wait::{Wait, WAIT_SELECT},
+    KernelObject, Process, ProcessRef,
+};
+use crate::sync::IrqSafeSpinLock;
+use alloc::{rc::Rc, vec::Vec};
+use core::cmp::min;
+use core::convert::TryFrom;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+use libsys::{
+    error::Errno,
+    ioring::{
+        cq_offset, ring_size, sq_offset, Completion, IoOp, IoRingHeader, Submission,
+        IORING_MAX_ENTRIES, OFFSET_CURRENT,
+    },
+    ring::RingMapping,
+    stat::FileDescriptor,
+    traits::{RandomRead, RandomWrite, Read, Write},
+};
+use vfs::{FileRef, VnodeKind};
+
+/// Kernel side of an asynchronous I/O ring
+pub struct IoRing {
+    base: usize,
+    pages: usize,
+    entries: u32,
+    queued: AtomicBool,
+    wait: Wait,
+}
+
+/// Rings with requests to be taken, along with the processes which entered
+/// them
+static QUEUE: IrqSafeSpinLock<Vec<(Rc<IoRing>, ProcessRef)>> = IrqSafeSpinLock::new(Vec::new());
+static WORKER_WAIT: Wait = Wait::new("ioring_worker");
+
+/// Upper bound of the delay between a wakeup and its waiter noticing it
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl IoRing {
+    /// Allocates a ring with `entries` slots per queue
+    pub fn new(entries: u32) -> Result<Self, Errno> {
+        if entries == 0 || entries > IORING_MAX_ENTRIES || !entries.is_power_of_two() {
+            return Err(Errno::InvalidArgument);
+        }
+        let pages = ring_size(entries) / PAGE_SIZE;
+        let base = phys::alloc_contiguous_pages(PageUsage::Kernel, pages)?;
+        unsafe {
+            ptr::write_bytes(mem::virtualize(base) as *mut u8, 0, pages * PAGE_SIZE);
+            (*(mem::virtualize(base) as *mut IoRingHeader)).entries = entries;
+        }
+        Ok(Self {
+            base,
+            pages,
+            entries,
+            queued: AtomicBool::new(false),
+            wait: Wait::new("ioring"),
+        })
+    }
+
+    fn header(&self) -> &IoRingHeader {
+        unsafe { &*(mem::virtualize(self.base) as *const IoRingHeader) }
+    }
+
+    fn sq(&self) -> *const Submission {
+        (mem::virtualize(self.base) + sq_offset()) as *const Submission
+    }
+
+    fn cq(&self) -> *mut Completion {
+        (mem::virtualize(self.base) + cq_offset(self.entries)) as *mut Completion
+    }
+
+    /// Returns the number of slots in each queue
+    pub fn entries(&self) -> u32 {
+        self.entries
+    }
+
+    /// Returns the number of completions not yet reaped by userspace
+    pub fn completed(&self) -> u32 {
+        let header = self.header();
+        header
+            .cq_tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(header.cq_head.load(Ordering::Acquire))
+    }
+
+    /// Returns the number of submitted requests not yet taken
+    pub fn submitted(&self) -> u32 {
+        let header = self.header();
+        header
+            .sq_tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(header.sq_head.load(Ordering::Acquire))
+    }
+
+    /// Maps the ring into `space`. Only the completion queue is read-only
+    /// for userspace.
+    pub fn map_user(&self, space: &mut mem::virt::Space) -> Result<RingMapping, Errno> {
+        let writable = cq_offset(self.entries) / PAGE_SIZE;
+        Ok(RingMapping {
+            base: map_shared(space, self.base, self.pages, writable)? as u64,
+            size: (self.pages * PAGE_SIZE) as u64,
+        })
+    }
+
+    fn can_take(&self) -> bool {
+        self.submitted() != 0 && self.completed() < self.entries
+    }
+
+    /// Takes submitted requests while there is room for their completions
+    fn process(&self, proc: &Process) {
+        let header = self.header();
+        let mask = self.entries - 1;
+        loop {
+            // Also picks up requests submitted while the ring is processed
+            if !self.can_take() {
+                self.queued.store(false, Ordering::Release);
+                // Requests submitted after the check have not queued the ring
+                if self.can_take() && !self.queued.swap(true, Ordering::AcqRel) {
+                    continue;
+                }
+                break;
+            }
+
+            let head = header.sq_head.load(Ordering::Relaxed);
+            if self.submitted() > self.entries {
+                // Userspace has broken the queue, drop its contents
+                header
+                    .sq_head
+                    .store(header.sq_tail.load(Ordering::Acquire), Ordering::Release);
+                continue;
+            }
+            let sqe = unsafe { ptr::read_volatile(self.sq().add((head & mask) as usize)) };
+            header
+                .sq_head
+                .store(head.wrapping_add(1), Ordering::Release);
+
+            let result = match execute(proc, &sqe) {
+                Ok(count) => count as isize,
+                Err(err) => err.to_negative_isize(),
+            };
+
+            let tail = header.cq_tail.load(Ordering::Relaxed);
+            unsafe {
+                ptr::write_volatile(
+                    self.cq().add((tail & mask) as usize),
+                    Completion {
+                        user_data: sqe.user_data,
+                        result,
+                    },
+                );
+            }
+            header
+                .cq_tail
+                .store(tail.wrapping_add(1), Ordering::Release);
+            self.wait.wakeup_all();
+            WAIT_SELECT.wakeup_all();
+        }
+    }
+}
+
+impl Drop for IoRing {
+    fn drop(&mut self) {
+        for i in 0..self.pages {
+            unsafe {
+                phys::free_page(self.base + i * PAGE_SIZE).unwrap();
+            }
+        }
+    }
+}
+
+/// Creates a ring, maps it into `proc` and allocates a descriptor for it
+pub fn setup(proc: &Process, entries: u32) -> Result<(FileDescriptor, RingMapping), Errno> {
+    let ring = Rc::new(IoRing::new(entries)?);
+    let mapping = proc.manipulate_space(|space| ring.map_user(space))?;
+    match proc
+        .io
+        .lock()
+        .place_object(KernelObject::IoRing(ring), false)
+    {
+        Ok(fd) => Ok((fd, mapping)),
+        Err(err) => {
+            proc.manipulate_space(|space| space.free(mapping.base as usize, mapping.size as usize / PAGE_SIZE))?;
+            Err(err)
+        }
+    }
+}
+
+/// Hands submitted requests of `ring` over to the worker and waits until at
+/// least `min_complete` completions are ready. Returns the number of ready
+/// completions.
+pub fn enter(ring: Rc<IoRing>, proc: ProcessRef, min_complete: u32) -> Result<usize, Errno> {
+    if min_complete > ring.entries {
+        return Err(Errno::InvalidArgument);
+    }
+    if ring.submitted() != 0 && !ring.queued.swap(true, Ordering::AcqRel) {
+        QUEUE.lock().push((ring.clone(), proc));
+        WORKER_WAIT.wakeup_one();
+    }
+
+    loop {
+        let ready = ring.completed();
+        if ready >= min_complete {
+            return Ok(ready as usize);
+        }
+        let deadline = machine::local_timer().timestamp()? + POLL_INTERVAL;
+        match ring.wait.wait(Some(deadline)) {
+            Ok(()) | Err(Errno::TimedOut) => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn execute(proc: &Process, sqe: &Submission) -> Result<usize, Errno> {
+    let op = IoOp::try_from(sqe.op)?;
+    if op == IoOp::Nop {
+        return Ok(0);
+    }
+
+    let file = proc.io.lock().file(FileDescriptor::from(sqe.fd))?;
+    let node = file.borrow().node().ok_or(Errno::InvalidFile)?;
+    if !matches!(node.kind(), VnodeKind::Regular | VnodeKind::Block) {
+        return Err(Errno::InvalidOperation);
+    }
+
+    match op {
+        IoOp::Read => transfer(proc, &file, sqe, false).map(|count| {
+            proc.io_counters().account_read(count);
+            count
+        }),
+        IoOp::Write => transfer(proc, &file, sqe, true).map(|count| {
+            proc.io_counters().account_write(count);
+            count
+        }),
+        IoOp::Fsync => node.sync().map(|_| 0),
+        IoOp::Nop => unreachable!(),
+    }
+}
+
+/// Returns the page containing `virt` with an extra reference, which keeps
+/// it from being freed or swapped out
+fn pin_user_page(proc: &Process, virt: usize, write: bool) -> Result<usize, Errno> {
+    let res = proc.try_manipulate_space(|space| {
+        let page = space.translate_user(virt, write)? & !(PAGE_SIZE - 1);
+        match phys::page_info(page).0 {
+            PageUsage::UserPrivate | PageUsage::Kernel => unsafe { phys::share_page(page)? },
+            // E.g. read-only mappings of cached file data
+            _ => return Err(Errno::InvalidArgument),
+        }
+        Ok(page)
+    });
+    let page = res.ok_or(Errno::DoesNotExist)??;
+    if write {
+        // The page may have been copied on write
+        proc.invalidate_tlb();
+    }
+    Ok(page)
+}
+
+/// Moves data between the file and the user buffer page by page. Stops at
+/// the first short transfer.
+fn transfer(proc: &Process, file: &FileRef, sqe: &Submission, write: bool) -> Result<usize, Errno> {
+    match sqe.buf.checked_add(sqe.len) {
+        Some(end) if end <= mem::KERNEL_OFFSET => (),
+        _ => return Err(Errno::InvalidArgument),
+    }
+    let pos = if sqe.offset == OFFSET_CURRENT {
+        None
+    } else {
+        match (sqe.offset as usize).checked_add(sqe.len) {
+            Some(_) => Some(sqe.offset as usize),
+            None => return Err(Errno::InvalidArgument),
+        }
+    };
+
+    let mut done = 0;
+    while done < sqe.len {
+        let virt = sqe.buf + done;
+        let offset = virt & (PAGE_SIZE - 1);
+        let count = min(sqe.len - done, PAGE_SIZE - offset);
+        // Reading the file writes to the buffer and vice versa
+        let page = match pin_user_page(proc, virt, !write) {
+            Ok(page) => page,
+            Err(err) if done == 0 => return Err(err),
+            Err(_) => break,
+        };
+        let data = (mem::virtualize(page) + offset) as *mut u8;
+
+        // The file may be in use by a thread of the process, don't wait for it
+        let res = match file.try_borrow_mut() {
+            Ok(mut file) => unsafe {
+                match (write, pos) {
+                    (false, None) => file.read(core::slice::from_raw_parts_mut(data, count)),
+                    (false, Some(pos)) => {
+                        file.pread(pos + done, core::slice::from_raw_parts_mut(data, count))
+                    }
+                    (true, None) => file.write(core::slice::from_raw_parts(data, count)),
+                    (true, Some(pos)) => {
+                        file.pwrite(pos + done, core::slice::from_raw_parts(data, count))
+                    }
+                }
+            },
+            Err(_) => Err(Errno::Busy),
+        };
+        unsafe {
+            phys::free_page(page).unwrap();
+        }
+
+        match res {
+            Ok(transferred) => {
+                done += transferred;
+                if transferred < count {
+                    break;
+                }
+            }
+            Err(err) if done == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(done)
+}
+
+/// Kernel process function executing requests of entered rings
+pub fn ioring_fn() -> ! {
+    loop {
+        let next = {
+            let mut queue = QUEUE.lock();
+            if queue.is_empty() {
+                None
+            } else {
+                Some(queue.remove(0))
+            }
+        };
+        if let Some((ring, proc)) = next {
+            ring.process(&proc);
+            continue;
+        }
+
+        // Rings may be queued between the check and the wait
+        let deadline = machine::local_timer().timestamp().unwrap() + POLL_INTERVAL;
+        match WORKER_WAIT.wait(Some(deadline)) {
+            Ok(()) | Err(Errno::TimedOut) | Err(Errno::Interrupt) => {}
+            Err(err) => panic!("ioring worker wait failed: {:?}", err),
+        }
+    }
+}
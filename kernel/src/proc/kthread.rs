@@ -0,0 +1,44 @@
+//! Typed spawning of named kernel threads
+//!
+//! Kernel workers are started as separate kernel processes. Instead of
+//! passing a raw `usize` to an `extern "C"` entry, the entry is given as
+//! a closure which is boxed and handed over to the new thread, which
+//! takes ownership of it before running it. If the closure returns, the
+//! kernel process exits.
+use crate::proc::{Process, ProcessRef};
+use alloc::boxed::Box;
+use libsys::{error::Errno, proc::ExitCode};
+
+extern "C" fn kthread_entry<F: FnOnce() + 'static>(arg: usize) -> ! {
+    // Move the closure out of its box, freeing the allocation before
+    // the closure is entered
+    let f = unsafe { *Box::from_raw(arg as *mut F) };
+    f();
+
+    Process::current().exit(ExitCode::from(()));
+    panic!("This code should never run");
+}
+
+/// Creates a kernel process named `name` running closure `f`. The
+/// process is not enqueued.
+pub fn create<F: FnOnce() + 'static>(name: &str, f: F) -> Result<ProcessRef, Errno> {
+    let arg = Box::into_raw(Box::new(f));
+    Process::new_kernel(name, kthread_entry::<F>, arg as usize).map_err(|err| {
+        // The thread was never created, reclaim the closure
+        drop(unsafe { Box::from_raw(arg) });
+        err
+    })
+}
+
+/// Creates and enqueues a kernel process named `name` running closure `f`
+pub fn spawn<F: FnOnce() + 'static>(name: &str, f: F) -> Result<ProcessRef, Errno> {
+    let proc = create(name, f)?;
+    proc.enqueue();
+    Ok(proc)
+}
+
+/// Creates and enqueues a kernel process named `name` running `entry`
+/// with argument `arg`
+pub fn spawn_with<T: 'static>(name: &str, entry: fn(T), arg: T) -> Result<ProcessRef, Errno> {
+    spawn(name, move || entry(arg))
+}
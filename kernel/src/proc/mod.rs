@@ -3,8 +3,9 @@
 use crate::init;
 use crate::sync::IrqSafeSpinLock;
 use alloc::collections::BTreeMap;
-use libsys::proc::{Tid, Pid};
+use libsys::proc::{CpuStatistics, Tid, Pid};
 
+pub mod binfmt;
 pub mod elf;
 pub mod thread;
 pub use thread::{Thread, ThreadRef, State as ThreadState};
@@ -13,6 +14,10 @@ pub mod process;
 pub use process::{Process, ProcessRef, ProcessState};
 pub mod io;
 pub use io::ProcessIo;
+pub mod ioring;
+pub mod kthread;
+pub mod object;
+pub use object::{KernelObject, KernelObjectType};
 
 pub mod wait;
 
@@ -27,6 +32,27 @@ pub fn switch() {
     SCHED.switch(false);
 }
 
+/// Prints the current thread and the run queue.
+///
+/// See [Scheduler::dump]
+pub fn dump_run_queue(out: &mut dyn core::fmt::Write) {
+    SCHED.dump(out);
+}
+
+/// Returns CPU time accounting of each CPU.
+///
+/// See [Scheduler::cpu_statistics]
+pub fn cpu_statistics() -> [CpuStatistics; 1] {
+    [SCHED.cpu_statistics()]
+}
+
+/// Returns the current thread, if it can be found without waiting.
+///
+/// See [Scheduler::try_current_thread]
+pub fn try_current_thread() -> Option<ThreadRef> {
+    SCHED.try_current_thread()
+}
+
 pub(self) static PROCESSES: IrqSafeSpinLock<BTreeMap<Pid, ProcessRef>> =
     IrqSafeSpinLock::new(BTreeMap::new());
 
@@ -42,6 +68,17 @@ pub(self) static THREADS: IrqSafeSpinLock<BTreeMap<Tid, ThreadRef>> =
 /// Unsafe: May only be called once.
 pub unsafe fn enter() -> ! {
     SCHED.init();
-    Process::new_kernel(init::init_fn, 0).unwrap().enqueue();
+    binfmt::init();
+    kthread::spawn("init", || init::init_fn()).unwrap();
+    crate::dev::media::enable_poller();
+    kthread::spawn("ledd", || crate::dev::led::led_fn()).unwrap();
+    kthread::spawn("swapd", || crate::mem::swap::swapd_fn()).unwrap();
+    kthread::spawn("flushd", || crate::fs::pcache::flushd_fn()).unwrap();
+    kthread::spawn("ioringd", || ioring::ioring_fn()).unwrap();
+    crate::dev::irq::start_irq_threads().unwrap();
+    #[cfg(feature = "virtio")]
+    if crate::dev::virtio::balloon::is_present() {
+        kthread::spawn("balloond", || crate::dev::virtio::balloon::balloon_fn()).unwrap();
+    }
     SCHED.enter();
 }
@@ -0,0 +1,75 @@
+//! Kernel objects which can be referred to by process descriptors
+use crate::fs::fifo;
+use crate::proc::ioring::IoRing;
+use alloc::rc::Rc;
+use libsys::error::Errno;
+use vfs::FileRef;
+
+/// Object referred to by an entry in a process descriptor table
+#[derive(Clone)]
+pub enum KernelObject {
+    /// Open file description: regular files, devices, named pipes etc.
+    File(FileRef),
+    /// Asynchronous I/O ring
+    IoRing(Rc<IoRing>),
+}
+
+/// Trait for types which can be extracted from a [KernelObject]
+pub trait KernelObjectType: Sized {
+    /// Returns a reference to the object if it's of the requested type
+    fn downcast(object: &KernelObject) -> Option<Self>;
+}
+
+impl KernelObject {
+    /// Returns `true` if the object is ready for reading (or writing if
+    /// `write` is set) without blocking
+    pub fn is_ready(&self, write: bool) -> Result<bool, Errno> {
+        match self {
+            Self::File(file) => file.borrow().is_ready(write),
+            // Readable when completions are ready, writable when requests
+            // can be submitted
+            Self::IoRing(ring) => Ok(if write {
+                ring.submitted() < ring.entries()
+            } else {
+                ring.completed() != 0
+            }),
+        }
+    }
+
+    /// Performs cleanup when a descriptor referring to the object is closed
+    pub fn on_close(&self) {
+        match self {
+            Self::File(file) => {
+                if file.borrow().is_fifo() {
+                    // Wake up the opposite end of the pipe
+                    fifo::notify();
+                }
+            }
+            Self::IoRing(_) => {}
+        }
+    }
+}
+
+impl From<FileRef> for KernelObject {
+    fn from(file: FileRef) -> Self {
+        Self::File(file)
+    }
+}
+
+impl KernelObjectType for FileRef {
+    fn downcast(object: &KernelObject) -> Option<Self> {
+        match object {
+            KernelObject::File(file) => Some(file.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl KernelObjectType for Rc<IoRing> {
+    fn downcast(object: &KernelObject) -> Option<Self> {
+        match object {
+            KernelObject::IoRing(ring) => Some(ring.clone()),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,8 @@
 //! Process data and control
-use crate::arch::aarch64::exception::ExceptionFrame;
+use crate::arch::{aarch64::exception::ExceptionFrame, machine};
+use crate::dev::timer::TimestampSource;
 use crate::mem::{
-    self,
+    self, cow,
     phys::{self, PageUsage},
     virt::{MapAttributes, Space},
 };
@@ -10,18 +11,29 @@ use crate::proc::{
 };
 use crate::sync::{IrqSafeSpinLock};
 use alloc::{rc::Rc, vec::Vec};
+use core::fmt;
 use core::sync::atomic::{AtomicU32, Ordering};
 use libsys::{
+    abi::{FilterAction, SyscallFilter, SystemCall, ABI_VERSION},
     error::Errno,
     mem::memcpy,
-    proc::{ExitCode, Pid},
+    proc::{Capabilities, ExitCode, Pid, ProcessMemory, ProcessName, UnshareFlags},
     signal::Signal,
-    ProgramArgs,
+    stat::{FileDescriptor, GroupId, UserId},
+    FixedStr, ProgramArgs,
 };
+use vfs::{FileRef, IoCounters};
 
 /// Wrapper type for a process struct reference
 pub type ProcessRef = Rc<Process>;
 
+/// Maximum length of a hostname
+pub const HOSTNAME_MAX: usize = 64;
+/// Hostname storage type
+pub type Hostname = FixedStr<HOSTNAME_MAX>;
+
+static HOSTNAME: IrqSafeSpinLock<Hostname> = IrqSafeSpinLock::new(FixedStr::empty());
+
 /// List of possible process states
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ProcessState {
@@ -35,11 +47,16 @@ struct ProcessInner {
     space: Option<&'static mut Space>,
     state: ProcessState,
     id: Pid,
+    name: ProcessName,
     pgid: Pid,
     ppid: Option<Pid>,
     sid: Pid,
     exit: Option<ExitCode>,
+    caps: Capabilities,
+    filter: Option<SyscallFilter>,
+    hostname: Option<Rc<IrqSafeSpinLock<Hostname>>>,
     threads: Vec<Tid>,
+    memory: ProcessMemory,
 }
 
 /// Structure describing an operating system process
@@ -48,6 +65,7 @@ pub struct Process {
     inner: IrqSafeSpinLock<ProcessInner>,
     exit_wait: Wait,
     signal_state: AtomicU32,
+    io_counters: IoCounters,
     /// Process I/O context
     pub io: IrqSafeSpinLock<ProcessIo>,
 }
@@ -55,6 +73,8 @@ pub struct Process {
 impl Process {
     const USTACK_VIRT_TOP: usize = 0x100000000;
     const USTACK_PAGES: usize = 4;
+    /// Lowest address of the user stack. Program images are loaded below it.
+    pub const USTACK_VIRT_BOTTOM: usize = Self::USTACK_VIRT_TOP - Self::USTACK_PAGES * mem::PAGE_SIZE;
 
     /// Returns the process ID
     #[inline]
@@ -90,12 +110,127 @@ impl Process {
         self.inner.lock().sid = sid;
     }
 
+    /// Returns the process name
+    pub fn name(&self) -> ProcessName {
+        self.inner.lock().name
+    }
+
+    /// Changes the process name. Its threads keep their own names.
+    pub fn set_name(&self, name: ProcessName) {
+        self.inner.lock().name = name;
+    }
+
+    /// Returns the capability set of the process
+    #[inline]
+    pub fn capabilities(&self) -> Capabilities {
+        self.inner.lock().caps
+    }
+
+    /// Returns `true` if the process holds all of `caps`
+    #[inline]
+    pub fn has_capability(&self, caps: Capabilities) -> bool {
+        self.inner.lock().caps.contains(caps)
+    }
+
+    /// Returns [Errno::PermissionDenied] if the process lacks any of `caps`
+    pub fn check_capability(&self, caps: Capabilities) -> Result<(), Errno> {
+        if self.has_capability(caps) {
+            Ok(())
+        } else {
+            Err(Errno::PermissionDenied)
+        }
+    }
+
+    /// Returns the hostname as seen by the process
+    pub fn hostname(&self) -> Hostname {
+        match &self.inner.lock().hostname {
+            Some(name) => *name.lock(),
+            None => *HOSTNAME.lock(),
+        }
+    }
+
+    /// Changes the hostname of the process' namespace (or the global one
+    /// if the process has not unshared it)
+    pub fn set_hostname(&self, name: &str) -> Result<(), Errno> {
+        if name.len() > HOSTNAME_MAX {
+            return Err(Errno::InvalidArgument);
+        }
+        match &self.inner.lock().hostname {
+            Some(hostname) => hostname.lock().copy_from_str(name),
+            None => HOSTNAME.lock().copy_from_str(name),
+        }
+        Ok(())
+    }
+
+    /// Gives the process private copies of the requested context parts.
+    /// Children forked afterwards share the copies with the process.
+    pub fn unshare(&self, flags: UnshareFlags) {
+        if flags.contains(UnshareFlags::HOSTNAME) {
+            let name = self.hostname();
+            self.inner.lock().hostname = Some(Rc::new(IrqSafeSpinLock::new(name)));
+        }
+        if flags.contains(UnshareFlags::MOUNT) {
+            self.io.lock().ioctx().unshare_mounts();
+        }
+    }
+
+    /// Attaches a system call filter to the process. A filter cannot be
+    /// removed or replaced: if the process already has one, the new filter
+    /// is combined with it, so the set of permitted calls can only shrink.
+    pub fn set_syscall_filter(&self, filter: SyscallFilter) -> Result<(), Errno> {
+        let mut lock = self.inner.lock();
+        let filter = match &lock.filter {
+            Some(old) => old.intersect(&filter),
+            None => filter,
+        };
+        lock.filter = Some(filter);
+        Ok(())
+    }
+
+    /// Checks `num` against the process system call filter. Returns
+    /// the action to take if the call has to be rejected.
+    pub fn check_syscall(&self, num: SystemCall) -> Option<FilterAction> {
+        let lock = self.inner.lock();
+        match &lock.filter {
+            Some(filter) if !filter.is_permitted(num) => filter.action(),
+            _ => None,
+        }
+    }
+
+    /// Removes `caps` from the capability set of the process. Dropped
+    /// capabilities cannot be regained. Children get a copy of the reduced
+    /// set on fork, so they lack the dropped capabilities as well.
+    ///
+    /// The I/O context copy of the set, used for filesystem permission
+    /// checks, is updated too, so the I/O lock must not be held.
+    pub fn drop_capabilities(&self, caps: Capabilities) {
+        let caps = {
+            let mut inner = self.inner.lock();
+            inner.caps.remove(caps);
+            inner.caps
+        };
+        self.io.lock().set_capabilities(caps);
+    }
+
     /// Returns [Rc]-reference to current process
     #[inline]
     pub fn current() -> ProcessRef {
         Thread::current().owner().unwrap()
     }
 
+    /// Returns [File] referred to by descriptor `fd` of the current process.
+    ///
+    /// Unlike [ProcessIo::file], repeated lookups of the same descriptor are
+    /// served from a per-thread cache and do not take the [ProcessIo] lock.
+    ///
+    /// [File]: vfs::File
+    pub fn current_file(fd: FileDescriptor) -> Result<FileRef, Errno> {
+        let thread = Thread::current();
+        let proc = thread.owner().unwrap();
+        // Safety: the cache is only accessed by its own thread
+        unsafe { thread.fd_cache() }.lookup(fd, || proc.io.lock().file(fd))
+    }
+
     /// Executes a closure performing manipulations on the process address space
     #[inline]
     pub fn manipulate_space<R, F>(&self, f: F) -> R
@@ -105,19 +240,61 @@ impl Process {
         f(self.inner.lock().space.as_mut().unwrap())
     }
 
-    /// Creates a new kernel process
-    pub fn new_kernel(entry: extern "C" fn(usize) -> !, arg: usize) -> Result<ProcessRef, Errno> {
+    /// Same as [Process::manipulate_space], but returns `None` if the process
+    /// has no address space (kernel or finished processes)
+    pub fn try_manipulate_space<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Space) -> R,
+    {
+        self.inner.lock().space.as_mut().map(|space| f(space))
+    }
+
+    /// Returns counters of data read and written by the process
+    #[inline]
+    pub fn io_counters(&self) -> &IoCounters {
+        &self.io_counters
+    }
+
+    /// Returns the memory usage of the process as of the last
+    /// [Process::scan_memory] call
+    pub fn memory_usage(&self) -> ProcessMemory {
+        self.inner.lock().memory
+    }
+
+    /// Ages the process pages and updates its memory usage and working
+    /// set estimate
+    pub fn scan_memory(&self) {
+        let mut lock = self.inner.lock();
+        let inner = &mut *lock;
+        if let Some(space) = inner.space.as_mut() {
+            inner.memory = space.scan_access();
+        }
+    }
+
+    /// Creates a new kernel process named `name`.
+    ///
+    /// See [kthread](super::kthread) for the typed interface.
+    pub(super) fn new_kernel(
+        name: &str,
+        entry: extern "C" fn(usize) -> !,
+        arg: usize,
+    ) -> Result<ProcessRef, Errno> {
         let id = new_kernel_pid();
-        let thread = Thread::new_kernel(Some(id), entry, arg)?;
+        let thread = Thread::new_kernel(Some(id), name, entry, arg)?;
         let mut inner = ProcessInner {
             threads: Vec::new(),
             id,
+            name: thread.name(),
             pgid: id,
             ppid: None,
             sid: id,
             exit: None,
+            caps: Capabilities::all(),
+            filter: None,
+            hostname: None,
             space: None,
             state: ProcessState::Active,
+            memory: ProcessMemory::default(),
         };
         inner.threads.push(thread.id());
 
@@ -125,9 +302,10 @@ impl Process {
             exit_wait: Wait::new("process_exit"),
             io: IrqSafeSpinLock::new(ProcessIo::new()),
             signal_state: AtomicU32::new(0),
+            io_counters: IoCounters::new(),
             inner: IrqSafeSpinLock::new(inner),
         });
-        debugln!("New kernel process: {:?}", id);
+        debugln!("New kernel process: {:?} {:?}", id, name);
         let prev = PROCESSES.lock().insert(id, res.clone());
         assert!(prev.is_none());
         Ok(res)
@@ -146,6 +324,37 @@ impl Process {
         PROCESSES.lock().get(&pid).cloned()
     }
 
+    /// Returns references to all the processes in the system
+    pub fn list() -> Vec<ProcessRef> {
+        PROCESSES.lock().values().cloned().collect()
+    }
+
+    /// Prints the process table without allocating or waiting for locks,
+    /// for use by the panic console. Returns `false` if the table itself
+    /// is locked.
+    pub fn dump(out: &mut dyn fmt::Write) -> bool {
+        let processes = match PROCESSES.lock_nowait() {
+            Some(processes) => processes,
+            None => return false,
+        };
+        for (pid, proc) in processes.iter() {
+            match proc.inner.lock_nowait() {
+                Some(inner) => writeln!(
+                    out,
+                    "{:?} {:?} ppid={:?} {:?} threads={}",
+                    pid,
+                    inner.name,
+                    inner.ppid,
+                    inner.state,
+                    inner.threads.len()
+                ),
+                None => writeln!(out, "{:?} (locked)", pid),
+            }
+            .ok();
+        }
+        true
+    }
+
     fn find1(a: u32) -> Option<usize> {
         for i in 0..32 {
             if a & (1 << i) != 0 {
@@ -218,7 +427,7 @@ impl Process {
         let space_phys = lock.space.as_mut().unwrap().address_phys();
         let ttbr0 = space_phys | ((lock.id.asid() as usize) << 48);
 
-        let thread = Thread::new_user(lock.id, entry, stack, arg, ttbr0)?;
+        let thread = Thread::new_user(lock.id, lock.name, entry, stack, arg, ttbr0)?;
         let tid = thread.id();
         lock.threads.push(tid);
         SCHED.enqueue(tid);
@@ -229,6 +438,8 @@ impl Process {
     /// Creates a "fork" of the process, cloning its address space and
     /// resources
     pub fn fork(&self, frame: &mut ExceptionFrame) -> Result<Pid, Errno> {
+        let timer = machine::local_timer();
+        let start = timer.timestamp()?;
         let src_io = self.io.lock();
         let mut src_inner = self.inner.lock();
 
@@ -238,22 +449,28 @@ impl Process {
         let dst_ttbr0 = dst_space_phys | ((dst_id.asid() as usize) << 48);
 
         let mut threads = Vec::new();
-        let tid = Thread::fork(Some(dst_id), frame, dst_ttbr0)?.id();
+        let tid = Thread::fork(Some(dst_id), Thread::current().name(), frame, dst_ttbr0)?.id();
         threads.push(tid);
 
         let dst = Rc::new(Self {
             exit_wait: Wait::new("process_exit"),
             io: IrqSafeSpinLock::new(src_io.fork()?),
             signal_state: AtomicU32::new(0),
+            io_counters: IoCounters::new(),
             inner: IrqSafeSpinLock::new(ProcessInner {
                 threads,
                 exit: None,
                 space: Some(dst_space),
                 state: ProcessState::Active,
                 id: dst_id,
+                name: src_inner.name,
                 pgid: src_inner.pgid,
                 ppid: Some(src_inner.id),
                 sid: src_inner.sid,
+                caps: src_inner.caps,
+                filter: src_inner.filter.clone(),
+                hostname: src_inner.hostname.clone(),
+                memory: src_inner.memory,
             }),
         });
 
@@ -262,6 +479,9 @@ impl Process {
 
         SCHED.enqueue(tid);
 
+        if let Ok(end) = timer.timestamp() {
+            cow::fork_done(end - start);
+        }
         Ok(dst_id)
     }
 
@@ -371,66 +591,49 @@ impl Process {
     }
 
     fn write_paged<T>(space: &mut Space, dst: usize, src: T) -> Result<(), Errno> {
-        let size = core::mem::size_of::<T>();
-        if (size + (dst % 4096)) > 4096 {
-            todo!("Object crossed page boundary");
-        }
-
-        let page_virt = dst & !4095;
-        let page_phys = if let Ok(phys) = space.translate(dst) {
-            phys
-        } else {
-            let page = phys::alloc_page(PageUsage::UserPrivate)?;
-            let flags = MapAttributes::SH_OUTER
-                | MapAttributes::NOT_GLOBAL
-                | MapAttributes::UXN
-                | MapAttributes::PXN
-                | MapAttributes::AP_BOTH_READONLY;
-            space.map(page_virt, page, flags)?;
-            page
+        let bytes = unsafe {
+            core::slice::from_raw_parts(&src as *const T as *const u8, core::mem::size_of::<T>())
         };
-
-        unsafe {
-            core::ptr::write((mem::virtualize(page_phys) + (dst % 4096)) as *mut T, src);
-        }
-        Ok(())
+        Self::write_paged_bytes(space, dst, bytes)
     }
 
-    fn write_paged_bytes(space: &mut Space, dst: usize, src: &[u8]) -> Result<(), Errno> {
-        if (src.len() + (dst % 4096)) > 4096 {
-            todo!("Object crossed page boundary");
-        }
-        let page_virt = dst & !4095;
-        let page_phys = if let Ok(phys) = space.translate(dst) {
-            phys
-        } else {
-            let page = phys::alloc_page(PageUsage::UserPrivate)?;
-            let flags = MapAttributes::SH_OUTER
-                | MapAttributes::NOT_GLOBAL
-                | MapAttributes::UXN
-                | MapAttributes::PXN
-                | MapAttributes::AP_BOTH_READONLY;
-            space.map(page_virt, page, flags)?;
-            page
-        };
+    fn write_paged_bytes(space: &mut Space, mut dst: usize, mut src: &[u8]) -> Result<(), Errno> {
+        while !src.is_empty() {
+            let len = core::cmp::min(src.len(), 4096 - (dst % 4096));
+            let page_virt = dst & !4095;
+            let page_phys = if let Ok(phys) = space.translate(dst) {
+                phys
+            } else {
+                let page = phys::alloc_page(PageUsage::UserPrivate)?;
+                let flags = MapAttributes::SH_OUTER
+                    | MapAttributes::NOT_GLOBAL
+                    | MapAttributes::UXN
+                    | MapAttributes::PXN
+                    | MapAttributes::AP_BOTH_READONLY;
+                space.map(page_virt, page, flags)?;
+                page
+            };
 
-        unsafe {
-            memcpy(
-                (mem::virtualize(page_phys) + (dst % 4096)) as *mut u8,
-                src.as_ptr(),
-                src.len(),
-            );
+            unsafe {
+                memcpy(
+                    (mem::virtualize(page_phys) + (dst % 4096)) as *mut u8,
+                    src.as_ptr(),
+                    len,
+                );
+            }
+            dst += len;
+            src = &src[len..];
         }
         Ok(())
     }
 
-    fn store_arguments(space: &mut Space, argv: &[&str]) -> Result<usize, Errno> {
+    fn store_arguments(space: &mut Space, argv: &[&str], envp: &[&str]) -> Result<usize, Errno> {
         let mut offset = 0usize;
         // TODO vmalloc?
         let base = 0x60000000;
 
-        // 1. Store program argument string bytes
-        for arg in argv.iter() {
+        // 1. Store program argument and environment string bytes
+        for arg in argv.iter().chain(envp.iter()) {
             Self::write_paged_bytes(space, base + offset, arg.as_bytes())?;
             offset += arg.len();
         }
@@ -438,9 +641,9 @@ impl Process {
         offset = (offset + 15) & !15;
         let argv_offset = offset;
 
-        // 2. Store arg pointers
+        // 2. Store arg pointers, followed by environment pointers
         let mut data_offset = 0usize;
-        for arg in argv.iter() {
+        for arg in argv.iter().chain(envp.iter()) {
             // XXX this is really unsafe and I am not really sure ABI will stay like this XXX
             Self::write_paged(space, base + offset, base + data_offset)?;
             Self::write_paged(space, base + offset + 8, arg.len())?;
@@ -452,8 +655,11 @@ impl Process {
         let data = ProgramArgs {
             argc: argv.len(),
             argv: base + argv_offset,
+            envc: envp.len(),
+            envp: base + argv_offset + argv.len() * 16,
             storage: base,
             size: offset + core::mem::size_of::<ProgramArgs>(),
+            abi_version: ABI_VERSION as usize,
         };
         Self::write_paged(space, base + offset, data)?;
 
@@ -475,23 +681,78 @@ impl Process {
         }
     }
 
-    /// Loads a new program into current process address space
+    /// Maps the user stack, the image and the arguments into a fresh
+    /// address space `space`. Returns the image entry point and the
+    /// argument pointer.
+    fn load_image<F: FnOnce(&mut Space) -> Result<usize, Errno>>(
+        space: &mut Space,
+        loader: F,
+        argv: &[&str],
+        envp: &[&str],
+    ) -> Result<(usize, usize), Errno> {
+        let ustack_virt_bottom = Self::USTACK_VIRT_BOTTOM;
+        for i in 0..Self::USTACK_PAGES {
+            let page = phys::alloc_page(PageUsage::UserPrivate)?;
+            let flags = MapAttributes::SH_OUTER
+                | MapAttributes::NOT_GLOBAL
+                | MapAttributes::UXN
+                | MapAttributes::PXN
+                | MapAttributes::AP_BOTH_READWRITE;
+            if let Err(err) = space.map(ustack_virt_bottom + i * mem::PAGE_SIZE, page, flags) {
+                unsafe {
+                    phys::free_page(page).ok();
+                }
+                return Err(err);
+            }
+        }
+
+        let entry = loader(space)?;
+        let arg = Self::store_arguments(space, argv, envp)?;
+        Ok((entry, arg))
+    }
+
+    /// Loads a new program into current process address space. The process
+    /// and its thread are renamed to `name` and, if given, the effective
+    /// user/group IDs are changed to `euid`/`egid`.
+    ///
+    /// The image is fully loaded before anything about the process is
+    /// changed, so if loading fails, the error is returned and the caller
+    /// continues running its old image.
     pub fn execve<F: FnOnce(&mut Space) -> Result<usize, Errno>>(
+        name: ProcessName,
         loader: F,
         argv: &[&str],
+        envp: &[&str],
+        euid: Option<UserId>,
+        egid: Option<GroupId>,
     ) -> Result<(), Errno> {
+        let proc = Process::current();
+
+        if proc.inner.lock().threads.len() != 1 {
+            return Err(Errno::NotImplemented);
+        }
+
+        let new_space = Space::alloc_empty()?;
+        let new_space_phys = (new_space as *mut _ as usize) - mem::KERNEL_OFFSET;
+        let (entry, arg) = match Self::load_image(new_space, loader, argv, envp) {
+            Ok(res) => res,
+            Err(err) => {
+                unsafe {
+                    Space::release(new_space);
+                    phys::free_page(new_space_phys).ok();
+                }
+                return Err(err);
+            }
+        };
+
+        // Point of no return: nothing below can fail
+
         unsafe {
             // Run with interrupts disabled
             asm!("msr daifset, #2");
         }
 
-        let proc = Process::current();
         let mut process_lock = proc.inner.lock();
-
-        if process_lock.threads.len() != 1 {
-            todo!();
-        }
-
         let thread = Thread::get(process_lock.threads[0]).unwrap();
 
         if process_lock.id.is_kernel() {
@@ -511,29 +772,23 @@ impl Process {
 
         thread.set_owner(process_lock.id);
 
-        proc.io.lock().handle_cloexec();
-
-        let new_space = Space::alloc_empty()?;
-        let new_space_phys = (new_space as *mut _ as usize) - mem::KERNEL_OFFSET;
-
-        let ustack_virt_bottom = Self::USTACK_VIRT_TOP - Self::USTACK_PAGES * mem::PAGE_SIZE;
-        for i in 0..Self::USTACK_PAGES {
-            let page = phys::alloc_page(PageUsage::UserPrivate).unwrap();
-            let flags = MapAttributes::SH_OUTER
-                | MapAttributes::NOT_GLOBAL
-                | MapAttributes::UXN
-                | MapAttributes::PXN
-                | MapAttributes::AP_BOTH_READWRITE;
-            new_space
-                .map(ustack_virt_bottom + i * mem::PAGE_SIZE, page, flags)
-                .unwrap();
+        {
+            let mut io = proc.io.lock();
+            io.handle_cloexec();
+            // Set-ID images only change effective IDs, capabilities
+            // are left as they are
+            if let Some(uid) = euid {
+                io.set_euid(uid);
+            }
+            if let Some(gid) = egid {
+                io.set_egid(gid);
+            }
         }
 
-        let entry = loader(new_space)?;
-        let arg = Self::store_arguments(new_space, argv)?;
-
         // TODO drop old address space
         process_lock.space = Some(new_space);
+        process_lock.name = name;
+        thread.set_name(name);
 
         unsafe {
             // TODO drop old context
@@ -1,14 +1,33 @@
 //!
+use crate::arch::machine;
+use crate::dev::timer::{OneShotTimer, TimestampSource};
 use crate::proc::{Thread, ThreadRef, THREADS};
 use crate::sync::IrqSafeSpinLock;
 use crate::util::InitOnce;
-use libsys::proc::Tid;
+use core::fmt;
+use core::time::Duration;
+use libsys::proc::{CpuStatistics, ProcessName, Tid};
 use alloc::{collections::VecDeque, rc::Rc};
 
+/// Time a thread may run before being preempted by another runnable one
+pub const TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// Kind of thread CPU time is accounted to
+#[derive(Clone, Copy, PartialEq)]
+enum CpuClass {
+    User,
+    Kernel,
+    Idle,
+}
+
 struct SchedulerInner {
     queue: VecDeque<Tid>,
     idle: Option<Tid>,
     current: Option<Tid>,
+    current_class: CpuClass,
+    // Time the current thread was switched to
+    last_switch: Duration,
+    stats: CpuStatistics,
 }
 
 /// Process scheduler state and queues
@@ -22,14 +41,48 @@ impl SchedulerInner {
             queue: VecDeque::new(),
             idle: None,
             current: None,
+            current_class: CpuClass::Idle,
+            last_switch: Duration::ZERO,
+            stats: CpuStatistics::default(),
         };
 
-        let idle = Thread::new_kernel(None, idle_fn, 0).unwrap().id();
+        let idle = Thread::new_kernel(None, "idle", idle_fn, 0).unwrap().id();
         assert_eq!(idle, Tid::IDLE);
         this.idle = Some(idle);
 
         this
     }
+
+    /// Accounts the time since the last switch to the current thread and
+    /// makes `next` (a thread of `class`) current
+    fn account_switch(&mut self, now: Duration, next: Tid, class: CpuClass) {
+        let ns = now.saturating_sub(self.last_switch).as_nanos() as u64;
+        self.current_class.charge(&mut self.stats, ns);
+        self.stats.switches += 1;
+        self.current = Some(next);
+        self.current_class = class;
+        self.last_switch = now;
+    }
+}
+
+impl CpuClass {
+    fn of(tid: Tid, thread: &Thread) -> Self {
+        if tid == Tid::IDLE {
+            Self::Idle
+        } else if thread.owner_id().map_or(true, |pid| pid.is_kernel()) {
+            Self::Kernel
+        } else {
+            Self::User
+        }
+    }
+
+    fn charge(self, stats: &mut CpuStatistics, ns: u64) {
+        match self {
+            Self::User => stats.user_ns += ns,
+            Self::Kernel => stats.kernel_ns += ns,
+            Self::Idle => stats.idle_ns += ns,
+        }
+    }
 }
 
 impl Scheduler {
@@ -41,9 +94,24 @@ impl Scheduler {
         self.inner.init(IrqSafeSpinLock::new(SchedulerInner::new()));
     }
 
-    /// Schedules a thread for execution
+    /// Schedules a thread for execution. Arms the timer to end the current
+    /// thread's time slice (or the idle loop) if none is armed yet.
     pub fn enqueue(&self, tid: Tid) {
-        self.inner.get().lock().queue.push_back(tid);
+        let idle = {
+            let mut inner = self.inner.get().lock();
+            inner.queue.push_back(tid);
+            inner.current.map(|t| t == Tid::IDLE).unwrap_or(true)
+        };
+
+        let timer = machine::local_timer();
+        let now = timer.timestamp().unwrap();
+        let slice_end = if idle { now } else { now + TIME_SLICE };
+        timer.arm_before(slice_end).unwrap();
+    }
+
+    /// Returns `true` if there are threads waiting for CPU time
+    pub fn has_queued(&self) -> bool {
+        !self.inner.get().lock().queue.is_empty()
     }
 
     /// Removes given `tid` from execution queue
@@ -65,8 +133,11 @@ impl Scheduler {
                 inner.queue.pop_front().unwrap()
             };
 
+            let thread = THREADS.lock().get(&id).unwrap().clone();
             inner.current = Some(id);
-            THREADS.lock().get(&id).unwrap().clone()
+            inner.current_class = CpuClass::of(id, &thread);
+            inner.last_switch = machine::local_timer().timestamp().unwrap();
+            thread
         };
 
         asm!("msr daifset, #2");
@@ -107,7 +178,6 @@ impl Scheduler {
                 inner.queue.pop_front().unwrap()
             };
 
-            inner.current = Some(next);
             let (from, to) = {
                 let lock = THREADS.lock();
                 (
@@ -115,6 +185,10 @@ impl Scheduler {
                     lock.get(&next).unwrap().clone(),
                 )
             };
+            if next != current {
+                let now = machine::local_timer().timestamp().unwrap();
+                inner.account_switch(now, next, CpuClass::of(next, &to));
+            }
 
             (from, to)
         };
@@ -127,6 +201,47 @@ impl Scheduler {
         }
     }
 
+    /// Prints the current thread and the run queue
+    pub fn dump(&self, out: &mut dyn fmt::Write) {
+        let inner = self.inner.get().lock();
+        let threads = THREADS.lock_nowait();
+        // Threads which are locked (or the whole table) are left unnamed
+        let name = |tid: &Tid| {
+            threads
+                .as_ref()
+                .and_then(|threads| threads.get(tid))
+                .and_then(|thread| thread.try_name())
+                .unwrap_or_else(|| ProcessName::from_str_truncated("?"))
+        };
+        match &inner.current {
+            Some(tid) => writeln!(out, "current: {:?} {}", tid, name(tid)),
+            None => writeln!(out, "current: none"),
+        }
+        .ok();
+        writeln!(out, "queued: {}", inner.queue.len()).ok();
+        for tid in inner.queue.iter() {
+            writeln!(out, "  {:?} {}", tid, name(tid)).ok();
+        }
+    }
+
+    /// Returns CPU time accounting, including the time the current thread
+    /// has been running for
+    pub fn cpu_statistics(&self) -> CpuStatistics {
+        let inner = self.inner.get().lock();
+        let mut stats = inner.stats;
+        let now = machine::local_timer().timestamp().unwrap();
+        let ns = now.saturating_sub(inner.last_switch).as_nanos() as u64;
+        inner.current_class.charge(&mut stats, ns);
+        stats
+    }
+
+    /// Returns the currently running thread without waiting for any locks.
+    /// Returns `None` if the scheduler is not running or is locked.
+    pub fn try_current_thread(&self) -> Option<ThreadRef> {
+        let id = self.inner.get_if_initialized()?.lock_nowait()?.current?;
+        THREADS.lock_nowait()?.get(&id).cloned()
+    }
+
     /// Returns a [Rc]-reference to currently running Thread
     pub fn current_thread(&self) -> ThreadRef {
         let inner = self.inner.get().lock();
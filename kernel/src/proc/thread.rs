@@ -2,6 +2,7 @@
 //! execution in the operating system
 use crate::arch::aarch64::exception::ExceptionFrame;
 use crate::proc::{
+    io::FileLookupCache,
     wait::{Wait, WaitStatus},
     Process, ProcessRef, SCHED, THREADS,
 };
@@ -12,7 +13,7 @@ use core::cell::UnsafeCell;
 use core::sync::atomic::{AtomicU32, Ordering};
 use libsys::{
     error::Errno,
-    proc::{ExitCode, Pid, Tid},
+    proc::{ExitCode, Pid, ProcessName, Tid},
     signal::Signal,
 };
 
@@ -36,6 +37,7 @@ pub enum State {
 
 struct ThreadInner {
     id: Tid,
+    name: ProcessName,
     state: State,
     owner: Option<Pid>,
     pending_wait: Option<&'static Wait>,
@@ -52,6 +54,7 @@ pub struct Thread {
     pub(super) ctx: UnsafeCell<Context>,
     signal_ctx: UnsafeCell<Context>,
     signal_pending: AtomicU32,
+    fd_cache: UnsafeCell<FileLookupCache>,
 }
 
 impl Thread {
@@ -78,6 +81,16 @@ impl Thread {
         self.inner.lock().owner.and_then(Process::get)
     }
 
+    /// Returns the descriptor lookup cache of the thread.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call from the thread itself.
+    #[allow(clippy::mut_from_ref)]
+    pub(super) unsafe fn fd_cache(&self) -> &mut FileLookupCache {
+        &mut *self.fd_cache.get()
+    }
+
     /// Returns [Pid] of the owner process
     pub fn owner_id(&self) -> Option<Pid> {
         self.inner.lock().owner
@@ -86,6 +99,7 @@ impl Thread {
     /// Creates a new kernel process
     pub fn new_kernel(
         owner: Option<Pid>,
+        name: &str,
         entry: extern "C" fn(usize) -> !,
         arg: usize,
     ) -> Result<ThreadRef, Errno> {
@@ -95,19 +109,21 @@ impl Thread {
             ctx: UnsafeCell::new(Context::kernel(entry as usize, arg)),
             signal_ctx: UnsafeCell::new(Context::empty()),
             signal_pending: AtomicU32::new(0),
+            fd_cache: UnsafeCell::new(FileLookupCache::new()),
             exit_wait: Wait::new("thread_exit"),
             exit_status: InitOnce::new(),
             inner: IrqSafeSpinLock::new(ThreadInner {
                 signal_entry: 0,
                 signal_stack: 0,
                 id,
+                name: ProcessName::from_str_truncated(name),
                 owner,
                 pending_wait: None,
                 wait_status: WaitStatus::Done,
                 state: State::Ready,
             }),
         });
-        debugln!("New kernel thread: {:?}", id);
+        debugln!("New kernel thread: {:?} {:?}", id, name);
         assert!(THREADS.lock().insert(id, res.clone()).is_none());
         Ok(res)
     }
@@ -115,6 +131,7 @@ impl Thread {
     /// Creates a new userspace process
     pub fn new_user(
         owner: Pid,
+        name: ProcessName,
         entry: usize,
         stack: usize,
         arg: usize,
@@ -126,12 +143,14 @@ impl Thread {
             ctx: UnsafeCell::new(Context::user(entry, arg, ttbr0, stack)),
             signal_ctx: UnsafeCell::new(Context::empty()),
             signal_pending: AtomicU32::new(0),
+            fd_cache: UnsafeCell::new(FileLookupCache::new()),
             exit_wait: Wait::new("thread_exit"),
             exit_status: InitOnce::new(),
             inner: IrqSafeSpinLock::new(ThreadInner {
                 signal_entry: 0,
                 signal_stack: 0,
                 id,
+                name,
                 owner: Some(owner),
                 pending_wait: None,
                 wait_status: WaitStatus::Done,
@@ -146,6 +165,7 @@ impl Thread {
     /// Creates a fork thread cloning `frame` context
     pub fn fork(
         owner: Option<Pid>,
+        name: ProcessName,
         frame: &ExceptionFrame,
         ttbr0: usize,
     ) -> Result<ThreadRef, Errno> {
@@ -155,12 +175,14 @@ impl Thread {
             ctx: UnsafeCell::new(Context::fork(frame, ttbr0)),
             signal_ctx: UnsafeCell::new(Context::empty()),
             signal_pending: AtomicU32::new(0),
+            fd_cache: UnsafeCell::new(FileLookupCache::new()),
             exit_wait: Wait::new("thread_exit"),
             exit_status: InitOnce::new(),
             inner: IrqSafeSpinLock::new(ThreadInner {
                 signal_entry: 0,
                 signal_stack: 0,
                 id,
+                name,
                 owner,
                 pending_wait: None,
                 wait_status: WaitStatus::Done,
@@ -178,6 +200,21 @@ impl Thread {
         self.inner.lock().id
     }
 
+    /// Returns the thread name
+    pub fn name(&self) -> ProcessName {
+        self.inner.lock().name
+    }
+
+    /// Returns the thread name, or `None` if the thread is locked
+    pub fn try_name(&self) -> Option<ProcessName> {
+        self.inner.lock_nowait().map(|inner| inner.name)
+    }
+
+    /// Changes the thread name
+    pub fn set_name(&self, name: ProcessName) {
+        self.inner.lock().name = name;
+    }
+
     /// Schedules an initial thread for execution
     ///
     /// # Safety
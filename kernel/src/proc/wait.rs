@@ -1,8 +1,11 @@
 //! Facilities for process suspension and sleep
 
 use crate::arch::machine;
-use crate::dev::timer::TimestampSource;
-use crate::proc::{sched::SCHED, Thread, ThreadRef};
+use crate::dev::timer::{OneShotTimer, TimestampSource};
+use crate::proc::{
+    sched::{SCHED, TIME_SLICE},
+    Thread, ThreadRef,
+};
 use crate::sync::IrqSafeSpinLock;
 use alloc::collections::LinkedList;
 use core::time::Duration;
@@ -54,6 +57,22 @@ pub fn tick() {
     }
 }
 
+/// Returns the time the local timer has to fire next: the nearest wait
+/// deadline or, if other threads are waiting for CPU time, the end of a
+/// time slice starting `now`
+pub fn next_deadline(now: Duration) -> Option<Duration> {
+    let nearest = TICK_LIST.lock().iter().map(|item| item.deadline).min();
+    let slice_end = if SCHED.has_queued() {
+        Some(now + TIME_SLICE)
+    } else {
+        None
+    };
+    match (nearest, slice_end) {
+        (Some(a), Some(b)) => Some(core::cmp::min(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
 /// Suspends current process for given duration
 pub fn sleep(timeout: Duration, remaining: &mut Duration) -> Result<(), Errno> {
     // Dummy wait descriptor which will never receive notifications
@@ -92,13 +111,12 @@ pub fn select(
 
     let deadline = timeout.map(|v| v + machine::local_timer().timestamp().unwrap());
     let proc = thread.owner().unwrap();
-    let mut io = proc.io.lock();
+    let io = proc.io.lock();
 
     loop {
         if let Some(read) = &read {
             for fd in read.iter() {
-                let file = io.file(fd)?;
-                if file.borrow().is_ready(false)? {
+                if io.object(fd)?.is_ready(false)? {
                     rfds.as_mut().unwrap().set(fd);
                     return Ok(1);
                 }
@@ -106,8 +124,7 @@ pub fn select(
         }
         if let Some(write) = &write {
             for fd in write.iter() {
-                let file = io.file(fd)?;
-                if file.borrow().is_ready(true)? {
+                if io.object(fd)?.is_ready(true)? {
                     wfds.as_mut().unwrap().set(fd);
                     return Ok(1);
                 }
@@ -216,6 +233,7 @@ impl Wait {
                 tid: thread.id(),
                 deadline,
             });
+            machine::local_timer().arm_before(deadline)?;
         }
 
         loop {
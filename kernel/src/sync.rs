@@ -41,6 +41,16 @@ impl<T> IrqSafeSpinLock<T> {
         cortex_a::asm::sev();
     }
 
+    /// Releases the lock regardless of who holds it
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: the holder must never access the value again. Only for use
+    /// by the panic handler, which takes over the system.
+    pub unsafe fn force_unlock(&self) {
+        self.force_release();
+    }
+
     /// Returns [IrqSafeSpinLockGuard] for this lock
     #[inline]
     pub fn lock(&self) -> IrqSafeSpinLockGuard<T> {
@@ -55,6 +65,32 @@ impl<T> IrqSafeSpinLock<T> {
             irq_state,
         }
     }
+
+    /// Returns [IrqSafeSpinLockGuard] for this lock, or `None` if it is
+    /// already held, instead of waiting
+    #[inline]
+    pub fn lock_nowait(&self) -> Option<IrqSafeSpinLockGuard<T>> {
+        let irq_state = unsafe { irq_mask_save() };
+
+        loop {
+            match self.try_lock() {
+                Ok(_) => break,
+                // Spurious failure of compare_exchange_weak()
+                Err(false) => continue,
+                Err(true) => {
+                    unsafe {
+                        irq_restore(irq_state);
+                    }
+                    return None;
+                }
+            }
+        }
+
+        Some(IrqSafeSpinLockGuard {
+            lock: self,
+            irq_state,
+        })
+    }
 }
 
 impl<T> Deref for IrqSafeSpinLockGuard<'_, T> {
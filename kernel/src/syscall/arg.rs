@@ -1,8 +1,15 @@
 //! System call argument ABI helpers
 
 use crate::mem;
+use alloc::vec::Vec;
 use core::alloc::Layout;
-use libsys::error::Errno;
+use libsys::{
+    abi::{SyscallFilter, UserBuffer, UserString, MAX_STRING_LEN},
+    error::Errno,
+    proc::DirtyLimits,
+    stat::{FileLimits, MountFlags, MountOptions},
+    termios::{ModemLines, Termios, TermiosCflag, TermiosIflag, TermiosLflag, TermiosOflag},
+};
 use crate::proc::Process;
 
 // TODO _mut() versions checking whether pages are actually writable
@@ -23,6 +30,85 @@ macro_rules! invalid_memory {
     }
 }
 
+/// Structures read from user memory. Besides pointer alignment and size
+/// checks done by [struct_copy] and friends, their contents are checked
+/// before the value reaches the system call handler. Checks are always
+/// done on a copy in kernel memory, so other threads of the process
+/// cannot change the value once it has been validated.
+pub trait UserInput {
+    /// Checks that the fields hold valid values and any memory they
+    /// refer to is accessible
+    fn validate(&self) -> Result<(), Errno>;
+}
+
+macro_rules! plain_user_input {
+    ($($ty:ty),+) => {
+        $(
+            impl UserInput for $ty {
+                #[inline(always)]
+                fn validate(&self) -> Result<(), Errno> {
+                    Ok(())
+                }
+            }
+        )+
+    }
+}
+
+plain_user_input!(u32, i64, DirtyLimits, FileLimits);
+
+impl UserInput for &str {
+    fn validate(&self) -> Result<(), Errno> {
+        let s = UserString::from_raw(self.as_ptr() as usize, self.len());
+        user_string(s).map(|_| ())
+    }
+}
+
+impl UserInput for SyscallFilter {
+    fn validate(&self) -> Result<(), Errno> {
+        if self.action().is_none() {
+            invalid_memory!("Invalid syscall filter action: {:?}", self);
+        }
+        Ok(())
+    }
+}
+
+impl UserInput for MountOptions<'_> {
+    fn validate(&self) -> Result<(), Errno> {
+        if MountFlags::from_bits(self.flags.bits()).is_none() {
+            invalid_memory!("Invalid mount flags: {:#x}", self.flags.bits());
+        }
+        if let Some(device) = self.device {
+            device.validate()?;
+        }
+        if let Some(fs) = self.fs {
+            fs.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl UserInput for ModemLines {
+    fn validate(&self) -> Result<(), Errno> {
+        if ModemLines::from_bits(self.bits()).is_none() {
+            invalid_memory!("Invalid modem line flags: {:#x}", self.bits());
+        }
+        Ok(())
+    }
+}
+
+impl UserInput for Termios {
+    fn validate(&self) -> Result<(), Errno> {
+        if TermiosIflag::from_bits(self.iflag.bits()).is_none()
+            || TermiosOflag::from_bits(self.oflag.bits()).is_none()
+            || TermiosLflag::from_bits(self.lflag.bits()).is_none()
+            || TermiosCflag::from_bits(self.cflag.bits()).is_none()
+        {
+            invalid_memory!("Invalid termios flags: {:?}", self);
+        }
+        Ok(())
+    }
+}
+
 #[inline(always)]
 fn is_el0_accessible(virt: usize, write: bool) -> bool {
     let mut res: usize;
@@ -36,8 +122,18 @@ fn is_el0_accessible(virt: usize, write: bool) -> bool {
     res & 1 == 0
 }
 
-/// Checks given argument and interprets it as a `T` reference
-pub fn struct_ref<'a, T>(base: usize) -> Result<&'a T, Errno> {
+fn array_layout<T>(count: usize) -> Result<Layout, Errno> {
+    match Layout::array::<T>(count) {
+        Ok(layout) => Ok(layout),
+        Err(_) => {
+            invalid_memory!("User array size overflows: count={:#x}", count);
+        }
+    }
+}
+
+/// Copies a `T` from user memory and validates the copy, so that user
+/// changes made afterwards do not affect the kernel
+pub fn struct_copy<T: UserInput + Clone>(base: usize) -> Result<T, Errno> {
     let layout = Layout::new::<T>();
     if base % layout.align() != 0 {
         invalid_memory!(
@@ -47,7 +143,9 @@ pub fn struct_ref<'a, T>(base: usize) -> Result<&'a T, Errno> {
         );
     }
     let bytes = buf_ref(base, layout.size())?;
-    Ok(unsafe { &*(bytes.as_ptr() as *const T) })
+    let value = unsafe { &*(bytes.as_ptr() as *const T) }.clone();
+    value.validate()?;
+    Ok(value)
 }
 
 /// Checks given argument and interprets it as a `T` mutable reference
@@ -64,9 +162,10 @@ pub fn struct_mut<'a, T>(base: usize) -> Result<&'a mut T, Errno> {
     Ok(unsafe { &mut *(bytes.as_mut_ptr() as *mut T) })
 }
 
-/// Checks given argument and interprets it as a `T` array buffer of size `count`
-pub fn struct_buf_ref<'a, T>(base: usize, count: usize) -> Result<&'a [T], Errno> {
-    let layout = Layout::array::<T>(count).unwrap();
+/// Copies an array of `count` `T`s from user memory and validates the
+/// copied items
+pub fn struct_buf_copy<T: UserInput + Clone>(base: usize, count: usize) -> Result<Vec<T>, Errno> {
+    let layout = array_layout::<T>(count)?;
     if base % layout.align() != 0 {
         invalid_memory!(
             "Structure pointer is misaligned: base={:#x}, expected {:?}",
@@ -75,12 +174,16 @@ pub fn struct_buf_ref<'a, T>(base: usize, count: usize) -> Result<&'a [T], Errno
         );
     }
     let bytes = buf_ref(base, layout.size())?;
-    Ok(unsafe { core::slice::from_raw_parts(bytes.as_ptr() as *const T, count) })
+    let items = unsafe { core::slice::from_raw_parts(bytes.as_ptr() as *const T, count) }.to_vec();
+    for item in items.iter() {
+        item.validate()?;
+    }
+    Ok(items)
 }
 
 /// Checks given argument and interprets it as a `T` array buffer of size `count`
 pub fn struct_buf_mut<'a, T>(base: usize, count: usize) -> Result<&'a mut [T], Errno> {
-    let layout = Layout::array::<T>(count).unwrap();
+    let layout = array_layout::<T>(count)?;
     if base % layout.align() != 0 {
         invalid_memory!(
             "Structure pointer is misaligned: base={:#x}, expected {:?}",
@@ -92,12 +195,12 @@ pub fn struct_buf_mut<'a, T>(base: usize, count: usize) -> Result<&'a mut [T], E
     Ok(unsafe { core::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, count) })
 }
 
-/// Checks given argument and interprets it as a `Option<&'a T>`
-pub fn option_struct_ref<'a, T>(base: usize) -> Result<Option<&'a T>, Errno> {
+/// Copies a possibly NULL `T` argument from user memory, see [struct_copy]
+pub fn option_struct_copy<T: UserInput + Clone>(base: usize) -> Result<Option<T>, Errno> {
     if base == 0 {
         Ok(None)
     } else {
-        struct_ref(base).map(Some)
+        struct_copy(base).map(Some)
     }
 }
 
@@ -113,7 +216,13 @@ pub fn option_struct_mut<'a, T>(base: usize) -> Result<Option<&'a mut T>, Errno>
 /// Validates that the argument pointer is accessible for requested operation
 /// for current process
 pub fn validate_ptr(base: usize, len: usize, write: bool) -> Result<(), Errno> {
-    if base > mem::KERNEL_OFFSET || base + len > mem::KERNEL_OFFSET {
+    let end = match base.checked_add(len) {
+        Some(end) => end,
+        None => {
+            invalid_memory!("User region overflows: base={:#x}, len={:#x}", base, len);
+        }
+    };
+    if base > mem::KERNEL_OFFSET || end > mem::KERNEL_OFFSET {
         invalid_memory!(
             "User region refers to kernel memory: base={:#x}, len={:#x}",
             base,
@@ -124,19 +233,16 @@ pub fn validate_ptr(base: usize, len: usize, write: bool) -> Result<(), Errno> {
     let process = Process::current();
     let asid = process.asid();
 
-    for i in (base / mem::PAGE_SIZE)..((base + len + mem::PAGE_SIZE - 1) / mem::PAGE_SIZE) {
-        if !is_el0_accessible(i * mem::PAGE_SIZE, write) {
-            // It's possible a CoW page hasn't yet been cloned when trying
-            // a write access
-            let res = if write {
-                process.manipulate_space(|space| {
-                    space.try_cow_copy(i * mem::PAGE_SIZE)?;
-                    Process::invalidate_asid(asid);
-                    Ok(())
-                })
-            } else {
-                Err(Errno::DoesNotExist)
-            };
+    for i in (base / mem::PAGE_SIZE)..((end + mem::PAGE_SIZE - 1) / mem::PAGE_SIZE) {
+        // The page may be swapped out or a CoW page may not yet
+        // have been cloned when trying a write access. Each step of
+        // resolution changes the page state, so this terminates.
+        while !is_el0_accessible(i * mem::PAGE_SIZE, write) {
+            let res = process.manipulate_space(|space| {
+                space.resolve_fault(i * mem::PAGE_SIZE, write)?;
+                Process::invalidate_asid(asid);
+                Ok(())
+            });
 
             if res.is_ok() {
                 continue;
@@ -197,3 +303,27 @@ pub fn str_ref<'a>(base: usize, len: usize) -> Result<&'a str, Errno> {
         Errno::InvalidArgument
     })
 }
+
+fn user_string<'a>(s: UserString) -> Result<&'a str, Errno> {
+    if s.len() > MAX_STRING_LEN {
+        invalid_memory!("User string is too long: base={:#x}, len={:#x}", s.base(), s.len());
+    }
+    str_ref(s.base(), s.len())
+}
+
+/// Validates a [UserString] passed in `args[index]` and `args[index + 1]`
+pub fn string<'a>(args: &[usize], index: usize) -> Result<&'a str, Errno> {
+    user_string(UserString::from_raw(args[index], args[index + 1]))
+}
+
+/// Validates a [UserBuffer] passed in `args[index]` and `args[index + 1]`
+pub fn buffer<'a>(args: &[usize], index: usize) -> Result<&'a [u8], Errno> {
+    let buf = UserBuffer::from_raw(args[index], args[index + 1]);
+    buf_ref(buf.base(), buf.len())
+}
+
+/// Validates a writable [UserBuffer] passed in `args[index]` and `args[index + 1]`
+pub fn buffer_mut<'a>(args: &[usize], index: usize) -> Result<&'a mut [u8], Errno> {
+    let buf = UserBuffer::from_raw(args[index], args[index + 1]);
+    buf_mut(buf.base(), buf.len())
+}
@@ -1,28 +1,41 @@
 //! System call implementation
 
-use crate::arch::{machine, platform::exception::ExceptionFrame};
+use crate::arch::platform::exception::ExceptionFrame;
 use crate::debug::Level;
-use crate::dev::timer::TimestampSource;
-use crate::fs::create_filesystem;
-use crate::mem::{phys::PageUsage, virt::MapAttributes};
-use crate::proc::{self, elf, wait, Process, ProcessIo, Thread};
-use core::mem::size_of;
+use crate::dev::{self, pci, rtc};
+use crate::fs::{self, create_filesystem, devfs, fifo, pcache};
+use crate::mem::{self, phys::PageUsage, swap, virt::MapAttributes};
+use crate::proc::{
+    self, binfmt,
+    ioring::{self, IoRing},
+    wait, Process, ProcessIo, Thread,
+};
+use crate::time;
+use alloc::{rc::Rc, string::String, vec::Vec};
 use core::ops::DerefMut;
 use core::time::Duration;
 use libsys::{
-    abi::SystemCall,
+    abi::{FilterAction, SyscallFilter, SystemCall},
     debug::TraceLevel,
+    dev::{DeviceInfo, PciFunction},
     error::Errno,
     ioctl::IoctlCmd,
-    proc::{ExitCode, MemoryAccess, Pid, Tid},
+    path::path_component_right,
+    proc::{
+        Capabilities, CpuStatistics, DirtyLimits, ExitCode, ForkStatistics, MemoryAccess,
+        MemoryAdvice, MemoryStatus, NameTarget, Pid, ProcessMemory, ProcessName, SwapStatus, Tid,
+        UnshareFlags,
+    },
+    ring::RingMapping,
     signal::{Signal, SignalDestination},
     stat::{
-        AccessMode, DirectoryEntry, FdSet, FileDescriptor, FileMode, GroupId, MountOptions,
-        OpenFlags, Stat, UserId, AT_EMPTY_PATH,
+        AccessMode, DirectoryEntry, DirectoryEntryPlus, FdSet, FileAdvice, FileDescriptor,
+        FileLimits, FileMode, FileUsage, GroupId, IoStatistics, MountOptions, OpenFlags, Stat,
+        UserId, AT_EACCESS, AT_EMPTY_PATH, AT_REMOVEDIR, AT_SYMLINK_NOFOLLOW,
     },
-    traits::{Read, Write},
+    time::ClockId,
 };
-use vfs::VnodeRef;
+use vfs::{VnodeKind, VnodeRef};
 
 pub mod arg;
 
@@ -41,7 +54,7 @@ fn find_at_node<T: DerefMut<Target = ProcessIo>>(
     io: &mut T,
     at_fd: Option<FileDescriptor>,
     filename: &str,
-    empty_path: bool,
+    flags: u32,
 ) -> Result<VnodeRef, Errno> {
     let at = if let Some(at_fd) = at_fd {
         io.file(at_fd)?.borrow().node()
@@ -49,10 +62,12 @@ fn find_at_node<T: DerefMut<Target = ProcessIo>>(
         None
     };
 
-    if empty_path && filename.is_empty() {
+    if flags & AT_EMPTY_PATH != 0 && filename.is_empty() {
         at.ok_or(Errno::InvalidArgument)
     } else {
-        io.ioctx().find(at, filename, true)
+        let ioctx = io.ioctx();
+        let follow = ioctx.follows(flags & AT_SYMLINK_NOFOLLOW != 0);
+        ioctx.find(at, filename, follow)
     }
 }
 
@@ -60,24 +75,26 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
     match num {
         // I/O
         SystemCall::Read => {
-            let proc = Process::current();
             let fd = FileDescriptor::from(args[0] as u32);
-            let mut io = proc.io.lock();
-            let buf = arg::buf_mut(args[1], args[2])?;
+            let buf = arg::buffer_mut(args, 1)?;
+            #[cfg(feature = "fault_inject")]
+            let buf = crate::fault::short_buffer(buf);
 
-            io.file(fd)?.borrow_mut().read(buf)
+            let count = fifo::read(&Process::current_file(fd)?, buf)?;
+            Process::current().io_counters().account_read(count);
+            Ok(count)
         }
         SystemCall::Write => {
-            let proc = Process::current();
             let fd = FileDescriptor::from(args[0] as u32);
-            let mut io = proc.io.lock();
-            let buf = arg::buf_ref(args[1], args[2])?;
+            let buf = arg::buffer(args, 1)?;
 
-            io.file(fd)?.borrow_mut().write(buf)
+            let count = fifo::write(&Process::current_file(fd)?, buf)?;
+            Process::current().io_counters().account_write(count);
+            Ok(count)
         }
         SystemCall::Open => {
             let at_fd = FileDescriptor::from_i32(args[0] as i32)?;
-            let path = arg::str_ref(args[1], args[2])?;
+            let path = arg::string(args, 1)?;
             let mode = FileMode::from_bits(args[3] as u32).ok_or(Errno::InvalidArgument)?;
             let opts = OpenFlags::from_bits(args[4] as u32).ok_or(Errno::InvalidArgument)?;
 
@@ -90,8 +107,45 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
                 None
             };
 
-            let file = io.ioctx().open(at, path, mode, opts)?;
-            Ok(u32::from(io.place_file(file)?) as usize)
+            let follow = io.ioctx().follows(opts.contains(OpenFlags::O_NOFOLLOW));
+            let file = match io.ioctx().find(at.clone(), path, follow) {
+                // Device nodes outside of devfs refer to devfs nodes by their number
+                Ok(node)
+                    if (node.kind() == VnodeKind::Char || node.kind() == VnodeKind::Block)
+                        && node.fs().is_some() =>
+                {
+                    let rdev = node.props().rdev;
+                    devfs::find_device(node.kind(), rdev)
+                        .ok_or(Errno::DoesNotExist)?
+                        .open(opts)?
+                }
+                _ => io.ioctx().open(at, path, mode, opts)?,
+            };
+            fifo::open(&file)?;
+            let cloexec = opts.contains(OpenFlags::O_CLOEXEC);
+            Ok(u32::from(io.place_file(file, cloexec)?) as usize)
+        }
+        SystemCall::CreateNode => {
+            let at_fd = FileDescriptor::from_i32(args[0] as i32)?;
+            let path = arg::string(args, 1)?;
+            let mode = FileMode::from_bits(args[3] as u32).ok_or(Errno::InvalidArgument)?;
+            let rdev = args[4] as u32;
+
+            let kind = mode & FileMode::FILE_TYPE;
+            let proc = Process::current();
+            if kind == FileMode::S_IFCHR || kind == FileMode::S_IFBLK {
+                proc.check_capability(Capabilities::SYS_ADMIN)?;
+            }
+            let mut io = proc.io.lock();
+
+            let at = if let Some(fd) = at_fd {
+                io.file(fd)?.borrow().node()
+            } else {
+                None
+            };
+
+            io.ioctx().mknod(at, path, mode, rdev)?;
+            Ok(0)
         }
         SystemCall::Close => {
             let proc = Process::current();
@@ -103,25 +157,91 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
         }
         SystemCall::FileStatus => {
             let at_fd = FileDescriptor::from_i32(args[0] as i32)?;
-            let filename = arg::str_ref(args[1], args[2])?;
+            let filename = arg::string(args, 1)?;
             let buf = arg::struct_mut::<Stat>(args[3])?;
             let flags = args[4] as u32;
 
             let proc = Process::current();
             let mut io = proc.io.lock();
             let stat =
-                find_at_node(&mut io, at_fd, filename, flags & AT_EMPTY_PATH != 0)?.stat()?;
+                find_at_node(&mut io, at_fd, filename, flags)?.stat()?;
             *buf = stat;
             Ok(0)
         }
-        SystemCall::Ioctl => {
-            let fd = FileDescriptor::from(args[0] as u32);
-            let cmd = IoctlCmd::try_from(args[1] as u32)?;
+        SystemCall::Unlink => {
+            let at_fd = FileDescriptor::from_i32(args[0] as i32)?;
+            let path = arg::string(args, 1)?;
+            let flags = args[3] as u32;
 
             let proc = Process::current();
             let mut io = proc.io.lock();
+            let at = if let Some(fd) = at_fd {
+                io.file(fd)?.borrow().node()
+            } else {
+                None
+            };
+
+            io.ioctx().unlink(at, path, flags & AT_REMOVEDIR != 0)?;
+            Ok(0)
+        }
+        SystemCall::Truncate => {
+            let path = arg::string(args, 0)?;
+            let size = args[2];
+
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+            let (uid, gid) = (io.euid(), io.egid());
+            let node = io.ioctx().find(None, path, true)?;
+
+            let dac_override = proc.has_capability(Capabilities::DAC_OVERRIDE);
+            node.check_access_as(uid, gid, AccessMode::W_OK, dac_override)?;
+            node.truncate(size)?;
+            Ok(0)
+        }
+        SystemCall::FileTruncate => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let size = args[1];
+
+            let file = Process::current_file(fd)?;
+            let file = file.borrow();
+            if !file.is_writable() {
+                return Err(Errno::InvalidArgument);
+            }
+            file.node().ok_or(Errno::InvalidFile)?.truncate(size)?;
+            Ok(0)
+        }
+        SystemCall::FileSync => {
+            let fd = FileDescriptor::from(args[0] as u32);
+
+            let node = Process::current_file(fd)?
+                .borrow()
+                .node()
+                .ok_or(Errno::InvalidFile)?;
+            node.sync()?;
+            Ok(0)
+        }
+        SystemCall::FileAdvise => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let advice = FileAdvice::try_from(args[3] as u32)?;
+
+            let node = Process::current_file(fd)?
+                .borrow()
+                .node()
+                .ok_or(Errno::InvalidFile)?;
+            node.advise(args[1], args[2], advice)?;
+            Ok(0)
+        }
+        SystemCall::Ioctl => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let cmd = IoctlCmd::from_request(args[1] as u32)?;
+            if args[3] != cmd.argument().0 {
+                return Err(Errno::InvalidArgument);
+            }
 
-            let node = io.file(fd)?.borrow().node().ok_or(Errno::InvalidFile)?;
+            let node = Process::current_file(fd)?
+                .borrow()
+                .node()
+                .ok_or(Errno::InvalidFile)?;
             node.ioctl(cmd, args[2], args[3])
         }
         SystemCall::Select => {
@@ -135,26 +255,51 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
 
             wait::select(Thread::current(), rfds, wfds, timeout)
         }
+        SystemCall::IoRingSetup => {
+            let mapping = arg::struct_mut::<RingMapping>(args[1])?;
+
+            let (fd, ring) = ioring::setup(&Process::current(), args[0] as u32)?;
+            *mapping = ring;
+            Ok(u32::from(fd) as usize)
+        }
+        SystemCall::IoRingEnter => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let proc = Process::current();
+
+            let ring = proc.io.lock().get::<Rc<IoRing>>(fd)?;
+            ioring::enter(ring, proc, args[1] as u32)
+        }
         SystemCall::Access => {
             let at_fd = FileDescriptor::from_i32(args[0] as i32)?;
-            let path = arg::str_ref(args[1], args[2])?;
+            let path = arg::string(args, 1)?;
             let mode = AccessMode::from_bits(args[3] as u32).ok_or(Errno::InvalidArgument)?;
             let flags = args[4] as u32;
 
             let proc = Process::current();
             let mut io = proc.io.lock();
 
-            find_at_node(&mut io, at_fd, path, flags & AT_EMPTY_PATH != 0)?
-                .check_access(io.ioctx(), mode)?;
+            let node = find_at_node(&mut io, at_fd, path, flags)?;
+            // Unlike other permission checks, access() uses real IDs by default
+            let (uid, gid) = if flags & AT_EACCESS != 0 {
+                (io.euid(), io.egid())
+            } else {
+                (io.uid(), io.gid())
+            };
+            let dac_override = proc.has_capability(Capabilities::DAC_OVERRIDE);
+            node.check_access_as(uid, gid, mode, dac_override)?;
             Ok(0)
         }
         SystemCall::ReadDirectory => {
-            let proc = Process::current();
             let fd = FileDescriptor::from(args[0] as u32);
-            let mut io = proc.io.lock();
             let buf = arg::struct_buf_mut::<DirectoryEntry>(args[1], args[2])?;
 
-            io.file(fd)?.borrow_mut().readdir(buf)
+            Process::current_file(fd)?.borrow_mut().readdir(buf)
+        }
+        SystemCall::ReadDirectoryPlus => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let buf = arg::struct_buf_mut::<DirectoryEntryPlus>(args[1], args[2])?;
+
+            Process::current_file(fd)?.borrow_mut().readdir_plus(buf)
         }
         SystemCall::GetUserId => {
             let proc = Process::current();
@@ -180,23 +325,74 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
         SystemCall::SetUserId => {
             let uid = UserId::from(args[0] as u32);
             let proc = Process::current();
-            proc.io.lock().set_uid(uid)?;
+            let mut io = proc.io.lock();
+            if io.uid() != uid {
+                proc.check_capability(Capabilities::SETUID)?;
+                io.set_uid(uid);
+                drop(io);
+                if !uid.is_root() {
+                    // Leaving root drops all the privileges
+                    proc.drop_capabilities(Capabilities::all());
+                }
+            }
             Ok(0)
         }
         SystemCall::SetGroupId => {
             let gid = GroupId::from(args[0] as u32);
             let proc = Process::current();
-            proc.io.lock().set_gid(gid)?;
+            let mut io = proc.io.lock();
+            if io.gid() != gid {
+                proc.check_capability(Capabilities::SETUID)?;
+                io.set_gid(gid);
+            }
+            Ok(0)
+        }
+        SystemCall::GetEffectiveUserId => {
+            let proc = Process::current();
+            let uid = proc.io.lock().euid();
+            Ok(u32::from(uid) as usize)
+        }
+        SystemCall::GetEffectiveGroupId => {
+            let proc = Process::current();
+            let gid = proc.io.lock().egid();
+            Ok(u32::from(gid) as usize)
+        }
+        SystemCall::SetEffectiveUserId => {
+            let uid = UserId::from(args[0] as u32);
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+            // Switching back to the real ID is always allowed
+            if io.uid() != uid {
+                proc.check_capability(Capabilities::SETUID)?;
+            }
+            io.set_euid(uid);
+            Ok(0)
+        }
+        SystemCall::SetEffectiveGroupId => {
+            let gid = GroupId::from(args[0] as u32);
+            let proc = Process::current();
+            let mut io = proc.io.lock();
+            if io.gid() != gid {
+                proc.check_capability(Capabilities::SETUID)?;
+            }
+            io.set_egid(gid);
             Ok(0)
         }
         SystemCall::SetCurrentDirectory => {
-            let path = arg::str_ref(args[0], args[1])?;
+            let path = arg::string(args, 0)?;
             let proc = Process::current();
             proc.io.lock().ioctx().chdir(path)?;
             Ok(0)
         }
         SystemCall::GetCurrentDirectory => {
-            todo!()
+            let buf = arg::buffer_mut(args, 0)?;
+            let path = Process::current().io.lock().ioctx().cwd_path();
+            let bytes = path.as_bytes();
+            if buf.len() < bytes.len() {
+                return Err(Errno::InvalidArgument);
+            }
+            buf[..bytes.len()].copy_from_slice(bytes);
+            Ok(bytes.len())
         }
         SystemCall::Seek => {
             todo!()
@@ -247,6 +443,22 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
             proc.manipulate_space(move |space| space.free(addr, len / 4096))?;
             Ok(0)
         }
+        SystemCall::MemoryAdvise => {
+            let addr = args[0];
+            let len = args[1];
+            let advice = MemoryAdvice::try_from(args[2] as u32)?;
+
+            if addr == 0 || len == 0 || addr & 0xFFF != 0 || len & 0xFFF != 0 {
+                return Err(Errno::InvalidArgument);
+            }
+            if addr >= mem::KERNEL_OFFSET || len > mem::KERNEL_OFFSET - addr {
+                return Err(Errno::InvalidArgument);
+            }
+
+            let proc = Process::current();
+            proc.manipulate_space(move |space| space.advise(addr, len / 4096, advice))?;
+            Ok(0)
+        }
 
         // Process
         SystemCall::Clone => {
@@ -259,23 +471,29 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
                 .map(|e| u32::from(e) as usize)
         }
         SystemCall::Exec => {
-            let filename = arg::str_ref(args[0], args[1])?;
-            let argv = arg::struct_buf_ref::<&str>(args[2], args[3])?;
-            // Validate each argument as well
-            for item in argv.iter() {
-                arg::validate_ptr(item.as_ptr() as usize, item.len(), false)?;
-            }
-            let node = {
-                let proc = Process::current();
-                let mut io = proc.io.lock();
-                // TODO argv, envp array passing ABI?
-                let node = io.ioctx().find(None, filename, true)?;
-                drop(io);
-                node
+            let filename = arg::string(args, 0)?;
+            let argv = arg::struct_buf_copy::<&str>(args[2], args[3])?;
+            let envp = arg::struct_buf_copy::<&str>(args[4], args[5])?;
+            // Copied, the caller's address space is gone after the switch
+            let envp: Vec<String> = envp.iter().map(|&e| String::from(e)).collect();
+            let name = ProcessName::from_str_truncated(path_component_right(filename).1);
+            let binfmt::Resolved {
+                file,
+                load,
+                argv,
+                euid,
+                egid,
+            } = {
+                // The header probe does file I/O, so it must not run under
+                // the spinlock
+                let ioctx = Process::current().io.lock().ioctx().clone();
+                binfmt::resolve(&ioctx, filename, &argv)?
             };
-            let file = node.open(OpenFlags::O_RDONLY)?;
-            Process::execve(move |space| elf::load_elf(space, file), argv).unwrap();
-            panic!();
+            let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+            let envp: Vec<&str> = envp.iter().map(String::as_str).collect();
+            // Only returns if the image could not be loaded
+            Process::execve(name, move |space| load(space, file), &argv, &envp, euid, egid)?;
+            unreachable!();
         }
         SystemCall::Exit => {
             let status = ExitCode::from(args[0] as i32);
@@ -313,13 +531,12 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
         SystemCall::GetPid => Ok(u32::from(Process::current().id()) as usize),
         SystemCall::GetTid => Ok(u32::from(Thread::current().id()) as usize),
         SystemCall::Sleep => {
-            let rem_buf = arg::option_buf_ref(args[1], size_of::<u64>() * 2)?;
+            let rem_buf = arg::option_struct_mut::<[u64; 2]>(args[1])?;
             let mut rem = Duration::new(0, 0);
             let res = wait::sleep(Duration::from_nanos(args[0] as u64), &mut rem);
             if res == Err(Errno::Interrupt) {
-                warnln!("Sleep interrupted, {:?} remaining", rem);
-                if rem_buf.is_some() {
-                    todo!()
+                if let Some(rem_buf) = rem_buf {
+                    *rem_buf = [rem.as_secs(), rem.subsec_nanos() as u64];
                 }
             }
             res.map(|_| 0)
@@ -338,9 +555,17 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
 
             match target {
                 SignalDestination::This => Process::current().set_signal(signal),
-                SignalDestination::Process(pid) => Process::get(pid)
-                    .ok_or(Errno::DoesNotExist)?
-                    .set_signal(signal),
+                SignalDestination::Process(pid) => {
+                    let target = Process::get(pid).ok_or(Errno::DoesNotExist)?;
+                    let current = Process::current();
+                    if !current.has_capability(Capabilities::KILL) {
+                        let uid = current.io.lock().uid();
+                        if target.io.lock().uid() != uid {
+                            return Err(Errno::PermissionDenied);
+                        }
+                    }
+                    target.set_signal(signal)
+                }
                 _ => todo!(),
             };
             Ok(0)
@@ -409,35 +634,300 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
 
             Ok(u32::from(proc.pgid()) as usize)
         }
+        SystemCall::GetCapabilities => {
+            Ok(Process::current().capabilities().bits() as usize)
+        }
+        SystemCall::SetSyscallFilter => {
+            let filter = arg::struct_copy::<SyscallFilter>(args[0])?;
+            Process::current().set_syscall_filter(filter)?;
+            Ok(0)
+        }
+        SystemCall::Unshare => {
+            let flags = UnshareFlags::from_bits(args[0] as u32).ok_or(Errno::InvalidArgument)?;
+            let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
+            proc.unshare(flags);
+            Ok(0)
+        }
+        SystemCall::SetName => {
+            let name = ProcessName::from_str_truncated(arg::string(args, 1)?);
+            match NameTarget::try_from(args[0] as u32)? {
+                NameTarget::Process => Process::current().set_name(name),
+                NameTarget::Thread => Thread::current().set_name(name),
+            }
+            Ok(0)
+        }
+        SystemCall::GetName => {
+            let name = match NameTarget::try_from(args[0] as u32)? {
+                NameTarget::Process => Process::get(Pid::try_from(args[1] as u32)?)
+                    .ok_or(Errno::DoesNotExist)?
+                    .name(),
+                NameTarget::Thread => Thread::get(Tid::from(args[1] as u32))
+                    .ok_or(Errno::DoesNotExist)?
+                    .name(),
+            };
+            let buf = arg::buffer_mut(args, 2)?;
+            let bytes = name.as_str().as_bytes();
+            if buf.len() < bytes.len() {
+                return Err(Errno::InvalidArgument);
+            }
+            buf[..bytes.len()].copy_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        SystemCall::GetProcessList => {
+            let buf = arg::struct_buf_mut::<Pid>(args[0], args[1])?;
+            let processes = Process::list();
+            for (dst, proc) in buf.iter_mut().zip(processes.iter()) {
+                *dst = proc.id();
+            }
+            Ok(processes.len())
+        }
+        SystemCall::DropCapabilities => {
+            let caps = Capabilities::from_bits(args[0] as u32).ok_or(Errno::InvalidArgument)?;
+            Process::current().drop_capabilities(caps);
+            Ok(0)
+        }
 
         // System
-        SystemCall::GetCpuTime => {
-            let time = machine::local_timer().timestamp()?;
-            Ok(time.as_nanos() as usize)
-        }
+        SystemCall::GetCpuTime => Ok(time::monotonic().as_nanos() as usize),
         SystemCall::Mount => {
-            let target = arg::str_ref(args[0], args[1])?;
-            let options = arg::struct_ref::<MountOptions>(args[2])?;
+            let target = arg::string(args, 0)?;
+            let options = arg::struct_copy::<MountOptions>(args[2])?;
 
             let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
             let mut io = proc.io.lock();
 
             debugln!("mount(target={:?}, options={:#x?})", target, options);
 
             let target_node = io.ioctx().find(None, target, true)?;
-            let root = create_filesystem(options)?;
+            let root = create_filesystem(&options)?;
 
-            target_node.mount(root)?;
+            io.ioctx().mount(target_node, root, options.flags)?;
 
             Ok(0)
         }
+        SystemCall::Unmount => {
+            let target = arg::string(args, 0)?;
+
+            let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
+            let mut io = proc.io.lock();
+
+            debugln!("unmount(target={:?})", target);
+
+            let root = io.ioctx().find(None, target, true)?;
+            io.ioctx().unmount(&root)?;
+
+            Ok(0)
+        }
+        SystemCall::GetHostname => {
+            let buf = arg::buffer_mut(args, 0)?;
+            let name = Process::current().hostname();
+            let bytes = name.as_str().as_bytes();
+            if buf.len() < bytes.len() {
+                return Err(Errno::InvalidArgument);
+            }
+            buf[..bytes.len()].copy_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        SystemCall::SetHostname => {
+            let name = arg::string(args, 0)?;
+            let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
+            proc.set_hostname(name)?;
+            Ok(0)
+        }
+        SystemCall::SwapOn => {
+            let path = arg::string(args, 0)?;
+            let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
+            let node = proc.io.lock().ioctx().find(None, path, true)?;
+            if node.kind() != VnodeKind::Block {
+                return Err(Errno::InvalidArgument);
+            }
+            let dev = devfs::block_device(node.props().rdev).ok_or(Errno::DoesNotExist)?;
+            swap::enable(dev)?;
+            Ok(0)
+        }
+        SystemCall::GetSwapStatus => {
+            let status = arg::struct_mut::<SwapStatus>(args[0])?;
+            *status = swap::status();
+            Ok(0)
+        }
+        SystemCall::SetSwappiness => {
+            Process::current().check_capability(Capabilities::SYS_ADMIN)?;
+            swap::set_swappiness(args[0])?;
+            Ok(0)
+        }
+        SystemCall::GetMemoryStatus => {
+            let status = arg::struct_mut::<MemoryStatus>(args[0])?;
+            *status = mem::status();
+            Ok(0)
+        }
+        SystemCall::GetCpuStatistics => {
+            let buf = arg::struct_buf_mut::<CpuStatistics>(args[0], args[1])?;
+            let stats = proc::cpu_statistics();
+            for (dst, src) in buf.iter_mut().zip(stats.iter()) {
+                *dst = *src;
+            }
+            Ok(stats.len())
+        }
+        SystemCall::GetForkStatistics => {
+            let stats = arg::struct_mut::<ForkStatistics>(args[0])?;
+            let reset = args[1] != 0;
+            if reset {
+                Process::current().check_capability(Capabilities::SYS_ADMIN)?;
+            }
+            *stats = mem::cow::statistics();
+            if reset {
+                mem::cow::reset();
+            }
+            Ok(0)
+        }
+        SystemCall::GetProcessMemory => {
+            let pid = Pid::try_from(args[0] as u32)?;
+            let usage = arg::struct_mut::<ProcessMemory>(args[1])?;
+            *usage = Process::get(pid).ok_or(Errno::DoesNotExist)?.memory_usage();
+            Ok(0)
+        }
+        SystemCall::GetProcessIo => {
+            let pid = Pid::try_from(args[0] as u32)?;
+            let stats = arg::struct_mut::<IoStatistics>(args[1])?;
+            *stats = Process::get(pid)
+                .ok_or(Errno::DoesNotExist)?
+                .io_counters()
+                .statistics();
+            Ok(0)
+        }
+        SystemCall::GetFilesystemIo => {
+            let fd = FileDescriptor::from(args[0] as u32);
+            let stats = arg::struct_mut::<IoStatistics>(args[1])?;
+
+            let node = Process::current_file(fd)?
+                .borrow()
+                .node()
+                .ok_or(Errno::InvalidFile)?;
+            let fs = node.fs().ok_or(Errno::NotImplemented)?;
+            *stats = fs
+                .io_counters()
+                .ok_or(Errno::NotImplemented)?
+                .statistics();
+            Ok(0)
+        }
+        SystemCall::GetDirtyLimits => {
+            let limits = arg::struct_mut::<DirtyLimits>(args[0])?;
+            *limits = pcache::dirty_limits();
+            Ok(0)
+        }
+        SystemCall::SetDirtyLimits => {
+            let limits = arg::struct_copy::<DirtyLimits>(args[0])?;
+            Process::current().check_capability(Capabilities::SYS_ADMIN)?;
+            pcache::set_dirty_limits(limits)?;
+            Ok(0)
+        }
+        SystemCall::GetFileLimits => {
+            let limits = arg::struct_mut::<FileLimits>(args[0])?;
+            *limits = fs::file_limits();
+            Ok(0)
+        }
+        SystemCall::SetFileLimits => {
+            let limits = arg::struct_copy::<FileLimits>(args[0])?;
+            Process::current().check_capability(Capabilities::SYS_ADMIN)?;
+            fs::set_file_limits(limits)?;
+            Ok(0)
+        }
+        SystemCall::GetFileUsage => {
+            let usage = arg::struct_mut::<FileUsage>(args[0])?;
+            *usage = fs::file_usage();
+            Ok(0)
+        }
+        SystemCall::GetDeviceList => {
+            let buf = arg::struct_buf_mut::<DeviceInfo>(args[0], args[1])?;
+            let devices = dev::list();
+            for (dst, src) in buf.iter_mut().zip(devices.iter()) {
+                *dst = *src;
+            }
+            Ok(devices.len())
+        }
+        SystemCall::GetPciFunctions => {
+            let buf = arg::struct_buf_mut::<PciFunction>(args[0], args[1])?;
+            let functions = pci::functions();
+            for (dst, src) in buf.iter_mut().zip(functions.iter()) {
+                *dst = *src;
+            }
+            Ok(functions.len())
+        }
+        SystemCall::DropCaches => {
+            Process::current().check_capability(Capabilities::SYS_ADMIN)?;
+            pcache::drop_caches();
+            Ok(0)
+        }
+        SystemCall::GetClock => {
+            let clock = ClockId::from_repr(args[0]).ok_or(Errno::InvalidArgument)?;
+            Ok(time::read(clock).as_nanos() as usize)
+        }
+        SystemCall::SetClock => {
+            let clock = ClockId::from_repr(args[0]).ok_or(Errno::InvalidArgument)?;
+            if clock != ClockId::Realtime {
+                return Err(Errno::InvalidArgument);
+            }
+            Process::current().check_capability(Capabilities::SYS_TIME)?;
+            let value = Duration::from_nanos(args[1] as u64);
+            time::set_realtime(value);
+            if let Err(err) = rtc::write_time(value) {
+                warnln!("Failed to update RTC: {:?}", err);
+            }
+            Ok(0)
+        }
+        SystemCall::AdjustClock => {
+            let delta = arg::option_struct_copy::<i64>(args[0])?;
+            let old = arg::option_struct_mut::<i64>(args[1])?;
+            let prev = if let Some(delta) = delta {
+                Process::current().check_capability(Capabilities::SYS_TIME)?;
+                time::adjust_realtime(delta)
+            } else {
+                time::pending_adjustment()
+            };
+            if let Some(old) = old {
+                *old = prev;
+            }
+            Ok(0)
+        }
+        #[cfg(feature = "kmod")]
+        SystemCall::LoadModule => {
+            let path = arg::string(args, 0)?;
+            let proc = Process::current();
+            proc.check_capability(Capabilities::SYS_ADMIN)?;
+            let file = {
+                let io = proc.io.lock();
+                let node = io.ioctx().find(None, path, true)?;
+                node.open(OpenFlags::O_RDONLY)?
+            };
+            let name = path.rsplit('/').next().unwrap_or(path);
+            crate::kmod::load(name, file)?;
+            Ok(0)
+        }
+        #[cfg(not(feature = "kmod"))]
+        SystemCall::LoadModule => Err(Errno::NotImplemented),
+        #[cfg(feature = "kmod")]
+        SystemCall::GetModuleList => {
+            let buf = arg::struct_buf_mut::<libsys::dev::ModuleInfo>(args[0], args[1])?;
+            let modules = crate::kmod::list();
+            for (dst, src) in buf.iter_mut().zip(modules.iter()) {
+                *dst = *src;
+            }
+            Ok(modules.len())
+        }
+        #[cfg(not(feature = "kmod"))]
+        SystemCall::GetModuleList => Err(Errno::NotImplemented),
 
         // Debugging
         SystemCall::DebugTrace => {
             let level = TraceLevel::from_repr(args[0])
                 .map(Level::from)
                 .ok_or(Errno::InvalidArgument)?;
-            let buf = arg::str_ref(args[1], args[2])?;
+            let buf = arg::string(args, 1)?;
             let thread = Thread::current();
             let proc = thread.owner().unwrap();
             println!(level, "[trace {:?}:{:?}] {}", proc.id(), thread.id(), buf);
@@ -449,10 +939,27 @@ fn _syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
     }
 }
 
+/// Checks if current process is allowed to perform `num` system call
+/// and applies the filter action if it's not
+pub fn filter(num: SystemCall) -> Result<(), Errno> {
+    let thread = Thread::current();
+    let process = thread.owner().unwrap();
+    match process.check_syscall(num) {
+        None => Ok(()),
+        Some(FilterAction::Error) => Err(Errno::PermissionDenied),
+        Some(FilterAction::Kill) => {
+            warnln!("{:?}: system call {:?} rejected by filter", process.id(), num);
+            process.enter_fault_signal(thread, Signal::InvalidSystemCall);
+            Err(Errno::PermissionDenied)
+        }
+    }
+}
+
 /// Main system call dispatcher function
 pub fn syscall(num: SystemCall, args: &[usize]) -> Result<usize, Errno> {
     let thread = Thread::current();
     let process = thread.owner().unwrap();
+    filter(num)?;
     let result = _syscall(num, args);
     if !thread.is_handling_signal() {
         process.handle_pending_signals();
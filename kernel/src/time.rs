@@ -0,0 +1,113 @@
+//! System clocks.
+//!
+//! All kernel timeouts are measured by the monotonic clock, which is
+//! the local timer's counter and never jumps. The wall clock is kept as
+//! an offset from it, so stepping or slewing the wall clock does not
+//! affect any pending waits.
+
+use crate::arch::machine;
+use crate::dev::timer::TimestampSource;
+use crate::sync::IrqSafeSpinLock;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use libsys::time::ClockId;
+
+/// Maximum rate of wall clock slewing, in parts per million
+/// (0.5 ms of adjustment per second)
+pub const SLEW_RATE_PPM: u64 = 500;
+
+struct Realtime {
+    // Wall clock minus monotonic time, in nanoseconds
+    offset: i64,
+    // Adjustment not yet applied to `offset`
+    slew: i64,
+    // Monotonic time the current adjustment started at
+    slew_start: u64,
+}
+
+static REALTIME: IrqSafeSpinLock<Realtime> = IrqSafeSpinLock::new(Realtime {
+    offset: 0,
+    slew: 0,
+    slew_start: 0,
+});
+static SUSPEND_TIME: AtomicU64 = AtomicU64::new(0);
+
+impl Realtime {
+    // Part of the pending adjustment already applied at `now`
+    fn applied(&self, now: u64) -> i64 {
+        let max = (now.saturating_sub(self.slew_start) * SLEW_RATE_PPM / 1_000_000) as i64;
+        self.slew.clamp(-max, max)
+    }
+
+    fn commit(&mut self, now: u64) {
+        let applied = self.applied(now);
+        self.offset += applied;
+        self.slew -= applied;
+        self.slew_start = now;
+    }
+
+    fn get(&self, now: u64) -> Duration {
+        let value = now as i64 + self.offset + self.applied(now);
+        Duration::from_nanos(value.max(0) as u64)
+    }
+}
+
+/// Returns time since boot, not including time spent suspended
+pub fn monotonic() -> Duration {
+    machine::local_timer().timestamp().unwrap()
+}
+
+/// Returns time since boot, including time spent suspended
+pub fn boottime() -> Duration {
+    monotonic() + Duration::from_nanos(SUSPEND_TIME.load(Ordering::Acquire))
+}
+
+/// Returns current wall clock time since the Unix epoch
+pub fn realtime() -> Duration {
+    let now = monotonic().as_nanos() as u64;
+    REALTIME.lock().get(now)
+}
+
+/// Reads current value of `clock`
+pub fn read(clock: ClockId) -> Duration {
+    match clock {
+        ClockId::Monotonic => monotonic(),
+        ClockId::Realtime => realtime(),
+        ClockId::Boottime => boottime(),
+    }
+}
+
+/// Steps the wall clock to `value`, cancelling any adjustment in progress
+pub fn set_realtime(value: Duration) {
+    let now = monotonic().as_nanos() as u64;
+    let mut rt = REALTIME.lock();
+    rt.offset = value.as_nanos() as i64 - now as i64;
+    rt.slew = 0;
+    rt.slew_start = now;
+}
+
+/// Starts gradual adjustment of the wall clock by `delta` nanoseconds
+/// (replacing the one in progress, if any) and returns the part of the
+/// previous adjustment that was not applied yet
+pub fn adjust_realtime(delta: i64) -> i64 {
+    let now = monotonic().as_nanos() as u64;
+    let mut rt = REALTIME.lock();
+    rt.commit(now);
+    let old = rt.slew;
+    rt.slew = delta;
+    old
+}
+
+/// Returns the part of the wall clock adjustment that was not applied yet
+pub fn pending_adjustment() -> i64 {
+    let now = monotonic().as_nanos() as u64;
+    let rt = REALTIME.lock();
+    rt.slew - rt.applied(now)
+}
+
+/// Accounts time the system spent suspended in [ClockId::Boottime].
+/// To be called by platform code on resume.
+#[allow(dead_code)]
+pub fn add_suspend_time(time: Duration) {
+    SUSPEND_TIME.fetch_add(time.as_nanos() as u64, Ordering::AcqRel);
+}
@@ -1,21 +1,35 @@
 //! Various utilities used by the kernel
 
+use core::any::type_name;
 use core::cell::UnsafeCell;
 use core::mem::MaybeUninit;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::panic::Location;
+use core::sync::atomic::{AtomicU8, Ordering};
+use libsys::error::Errno;
 
 /// Wrapper structure to guarantee single initialization
-/// of a value
+/// of a value.
+///
+/// The place the value was initialized from is remembered, so that
+/// double initialization and accesses before (or after a failed)
+/// initialization are reported along with it.
 pub struct InitOnce<T> {
-    state: AtomicBool,
+    state: AtomicU8,
+    location: UnsafeCell<Option<&'static Location<'static>>>,
     inner: UnsafeCell<MaybeUninit<T>>,
 }
 
 impl<T> InitOnce<T> {
+    const UNINIT: u8 = 0;
+    const BUSY: u8 = 1;
+    const READY: u8 = 2;
+    const FAILED: u8 = 3;
+
     /// Constructs a new instance of [InitOnce<T>]
     pub const fn new() -> Self {
         Self {
-            state: AtomicBool::new(false),
+            state: AtomicU8::new(Self::UNINIT),
+            location: UnsafeCell::new(None),
             inner: UnsafeCell::new(MaybeUninit::uninit()),
         }
     }
@@ -23,30 +37,126 @@ impl<T> InitOnce<T> {
     /// Returns `true` if this [InitOnce<T>] can be used
     #[inline(always)]
     pub fn is_initialized(&self) -> bool {
-        self.state.load(Ordering::Acquire)
+        self.state.load(Ordering::Acquire) == Self::READY
+    }
+
+    /// Returns the place the value was (or was attempted to be)
+    /// initialized from
+    pub fn initialized_at(&self) -> Option<&'static Location<'static>> {
+        match self.state.load(Ordering::Acquire) {
+            Self::READY | Self::FAILED => unsafe { *self.location.get() },
+            _ => None,
+        }
     }
 
     /// Returns the initialized value. Will panic if the value has not
-    /// yet been initialized.
+    /// yet been initialized, telling where it was accessed from.
     #[allow(clippy::mut_from_ref)]
+    #[track_caller]
     pub fn get(&self) -> &mut T {
-        assert!(self.is_initialized(), "Access to uninitialized InitOnce<T>");
-        unsafe { (*self.inner.get()).assume_init_mut() }
+        match self.state.load(Ordering::Acquire) {
+            Self::READY => unsafe { (*self.inner.get()).assume_init_mut() },
+            Self::FAILED => panic!(
+                "Access to InitOnce<{}> whose initialization at {} failed",
+                type_name::<T>(),
+                self.initialized_at().unwrap()
+            ),
+            _ => panic!("Access to uninitialized InitOnce<{}>", type_name::<T>()),
+        }
+    }
+
+    /// Returns the initialized value, or [Errno::DoesNotExist] if the value
+    /// is not (yet) initialized. Unlike [InitOnce::get], the failed access
+    /// is logged with its caller and the caller can back out gracefully.
+    #[allow(clippy::mut_from_ref)]
+    #[track_caller]
+    pub fn try_get(&self) -> Result<&mut T, Errno> {
+        if self.is_initialized() {
+            Ok(unsafe { (*self.inner.get()).assume_init_mut() })
+        } else {
+            warnln!(
+                "{}: InitOnce<{}> is not initialized",
+                Location::caller(),
+                type_name::<T>()
+            );
+            Err(Errno::DoesNotExist)
+        }
+    }
+
+    /// Returns the initialized value, or `None`. Intended for values which
+    /// are optional, where their absence is not an error.
+    #[allow(clippy::mut_from_ref)]
+    pub fn get_if_initialized(&self) -> Option<&mut T> {
+        if self.is_initialized() {
+            Some(unsafe { (*self.inner.get()).assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    #[track_caller]
+    fn begin_init(&self) {
+        // A failed initialization may be retried
+        let res = self.state.compare_exchange(
+            Self::UNINIT,
+            Self::BUSY,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        );
+        let res = match res {
+            Err(Self::FAILED) => self.state.compare_exchange(
+                Self::FAILED,
+                Self::BUSY,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ),
+            res => res,
+        };
+        match res {
+            Ok(_) => unsafe {
+                *self.location.get() = Some(Location::caller());
+            },
+            Err(Self::READY) => panic!(
+                "Double initialization of InitOnce<{}>, first initialized at {}",
+                type_name::<T>(),
+                self.initialized_at().unwrap()
+            ),
+            Err(_) => panic!(
+                "Concurrent initialization of InitOnce<{}>",
+                type_name::<T>()
+            ),
+        }
     }
 
     /// Initializes the storage with `value`. Will panic if the storage has
     /// already been initialized.
+    #[track_caller]
     pub fn init(&self, value: T) {
-        assert!(
-            self.state
-                .compare_exchange_weak(false, true, Ordering::Release, Ordering::Relaxed)
-                .is_ok(),
-            "Double-initialization of InitOnce<T>"
-        );
-
+        self.begin_init();
         unsafe {
             (*self.inner.get()).write(value);
         }
+        self.state.store(Self::READY, Ordering::Release);
+    }
+
+    /// Initializes the storage with the value returned by `f`. If `f` fails,
+    /// the storage is marked as failed: accesses report the failure and
+    /// the initialization may be retried.
+    #[allow(clippy::mut_from_ref)]
+    #[track_caller]
+    pub fn try_init_with<E, F: FnOnce() -> Result<T, E>>(&self, f: F) -> Result<&mut T, E> {
+        self.begin_init();
+        match f() {
+            Ok(value) => {
+                let value = unsafe { (*self.inner.get()).write(value) };
+                self.state.store(Self::READY, Ordering::Release);
+                Ok(value)
+            }
+            Err(err) => {
+                self.state.store(Self::FAILED, Ordering::Release);
+                Err(err)
+            }
+        }
     }
 }
 
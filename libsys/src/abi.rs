@@ -1,3 +1,4 @@
+use crate::error::Errno;
 use enum_repr::EnumRepr;
 
 #[EnumRepr(type = "usize")]
@@ -23,6 +24,18 @@ pub enum SystemCall {
     Seek = 17,
     MapMemory = 18,
     UnmapMemory = 19,
+    CreateNode = 20,
+    GetEffectiveUserId = 21,
+    GetEffectiveGroupId = 22,
+    SetEffectiveUserId = 23,
+    SetEffectiveGroupId = 24,
+    Truncate = 25,
+    FileTruncate = 26,
+    Unlink = 27,
+    FileSync = 28,
+    MemoryAdvise = 29,
+    FileAdvise = 30,
+    ReadDirectoryPlus = 31,
 
     // Process manipulation
     Fork = 32,
@@ -43,9 +56,231 @@ pub enum SystemCall {
     GetPpid = 47,
     SetSid = 48,
     SetPgid = 49,
+    GetCapabilities = 50,
+    DropCapabilities = 51,
+    SetSyscallFilter = 52,
+    Unshare = 53,
+    SetName = 54,
+    GetName = 55,
+    GetProcessList = 56,
+    GetCpuStatistics = 57,
+    GetForkStatistics = 58,
     // System
     GetCpuTime = 64,
     Mount = 65,
+    GetHostname = 66,
+    SetHostname = 67,
+    LoadModule = 68,
+    SwapOn = 69,
+    GetSwapStatus = 70,
+    SetSwappiness = 71,
+    DropCaches = 72,
+    GetMemoryStatus = 73,
+    GetProcessMemory = 74,
+    GetClock = 75,
+    SetClock = 76,
+    AdjustClock = 77,
+    Unmount = 78,
+    IoRingSetup = 79,
+    IoRingEnter = 80,
+    GetProcessIo = 81,
+    GetFilesystemIo = 82,
+    GetDirtyLimits = 83,
+    SetDirtyLimits = 84,
+    GetDeviceList = 85,
+    GetPciFunctions = 86,
+    GetFileLimits = 87,
+    SetFileLimits = 88,
+    GetFileUsage = 89,
+    GetModuleList = 90,
     // Debugging
     DebugTrace = 128
 }
+
+/// Version of the system call interface structures, incremented on
+/// incompatible layout changes. The kernel passes its version to new
+/// programs in [ProgramArgs](crate::ProgramArgs).
+///
+/// * 2: [DirectoryEntry](crate::stat::DirectoryEntry) carries the file type
+pub const ABI_VERSION: u32 = 2;
+
+/// Maximum length of a string (path, name, message) passed to the kernel
+pub const MAX_STRING_LEN: usize = 4096;
+
+/// String argument of a system call. Passed in two consecutive
+/// registers as (base, length), the data itself is never copied.
+#[derive(Clone, Copy, Debug)]
+pub struct UserString {
+    base: usize,
+    len: usize,
+}
+
+/// Memory buffer argument of a system call, passed the same way as
+/// [UserString]
+#[derive(Clone, Copy, Debug)]
+pub struct UserBuffer {
+    base: usize,
+    len: usize,
+}
+
+impl UserString {
+    /// Wraps `s` for passing to the kernel, rejecting strings longer
+    /// than [MAX_STRING_LEN]
+    pub fn new(s: &str) -> Result<Self, Errno> {
+        if s.len() > MAX_STRING_LEN {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok(Self {
+            base: s.as_ptr() as usize,
+            len: s.len(),
+        })
+    }
+
+    /// Reassembles the argument from raw register values
+    pub const fn from_raw(base: usize, len: usize) -> Self {
+        Self { base, len }
+    }
+
+    /// Returns the address of string data
+    pub const fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Returns the string length in bytes
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the string is empty
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl UserBuffer {
+    /// Wraps `buf` for passing to the kernel
+    pub fn new(buf: &[u8]) -> Self {
+        Self {
+            base: buf.as_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+
+    /// Wraps `buf` for passing to the kernel for writing
+    pub fn new_mut(buf: &mut [u8]) -> Self {
+        Self {
+            base: buf.as_mut_ptr() as usize,
+            len: buf.len(),
+        }
+    }
+
+    /// Reassembles the argument from raw register values
+    pub const fn from_raw(base: usize, len: usize) -> Self {
+        Self { base, len }
+    }
+
+    /// Returns the buffer address
+    pub const fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Returns the buffer size in bytes
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer is empty
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Action taken by the kernel when a process invokes a system call
+/// rejected by its [SyscallFilter]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FilterAction {
+    /// Return [Errno::PermissionDenied](crate::error::Errno::PermissionDenied) to the caller
+    Error = 1,
+    /// Deliver [Signal::InvalidSystemCall](crate::signal::Signal::InvalidSystemCall) to the caller
+    Kill = 2,
+}
+
+/// System call allowlist/denylist attachable to a process
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct SyscallFilter {
+    // Plain integers here: the structure is read from untrusted memory
+    allow: u32,
+    action: u32,
+    bits: [u64; 4],
+}
+
+impl SyscallFilter {
+    /// Constructs a filter which rejects every system call not explicitly
+    /// added with [SyscallFilter::add]
+    pub const fn allowlist(action: FilterAction) -> Self {
+        Self {
+            allow: 1,
+            action: action as u32,
+            bits: [0; 4],
+        }
+    }
+
+    /// Constructs a filter which only rejects system calls explicitly
+    /// added with [SyscallFilter::add]
+    pub const fn denylist(action: FilterAction) -> Self {
+        Self {
+            allow: 0,
+            action: action as u32,
+            bits: [0; 4],
+        }
+    }
+
+    /// Adds a system call to the filter list
+    pub fn add(&mut self, num: SystemCall) -> &mut Self {
+        let num = num.repr();
+        self.bits[num / 64] |= 1 << (num % 64);
+        self
+    }
+
+    /// Returns action to take when a call is rejected, or [None] if
+    /// the filter is malformed
+    pub const fn action(&self) -> Option<FilterAction> {
+        match self.action {
+            1 => Some(FilterAction::Error),
+            2 => Some(FilterAction::Kill),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the filter permits `num` system call
+    pub fn is_permitted(&self, num: SystemCall) -> bool {
+        let num = num.repr();
+        let listed = self.bits[num / 64] & (1 << (num % 64)) != 0;
+        listed == (self.allow != 0)
+    }
+
+    /// Combines the filter with `other` into an allowlist which only
+    /// permits system calls permitted by both, taking the stricter of the
+    /// two actions
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut bits = [0; 4];
+        for (i, dst) in bits.iter_mut().enumerate() {
+            *dst = self.permitted_bits(i) & other.permitted_bits(i);
+        }
+        Self {
+            allow: 1,
+            action: core::cmp::max(self.action, other.action),
+            bits,
+        }
+    }
+
+    fn permitted_bits(&self, index: usize) -> u64 {
+        if self.allow != 0 {
+            self.bits[index]
+        } else {
+            !self.bits[index]
+        }
+    }
+}
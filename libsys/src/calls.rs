@@ -1,15 +1,24 @@
-use crate::abi::SystemCall;
+use crate::abi::{SyscallFilter, SystemCall, UserString};
 use crate::{
     debug::TraceLevel,
+    dev::{DeviceInfo, ModuleInfo, PciFunction},
     error::Errno,
-    ioctl::IoctlCmd,
-    proc::{ExitCode, MemoryAccess, MemoryMap, Pid, Tid},
+    ioctl::{IoctlArgument, IoctlCmd},
+    proc::{
+        Capabilities, CpuStatistics, DirtyLimits, ExitCode, ForkStatistics, MemoryAccess,
+        MemoryAdvice, MemoryMap, MemoryStatus, NameTarget, Pid, ProcessMemory, SwapStatus, Tid,
+        UnshareFlags,
+    },
+    ring::RingMapping,
     signal::{Signal, SignalDestination},
     stat::{
-        AccessMode, DirectoryEntry, FdSet, FileDescriptor, FileMode, GroupId, MountOptions,
-        OpenFlags, Stat, UserId,
+        AccessMode, DirectoryEntry, DirectoryEntryPlus, FdSet, FileAdvice, FileDescriptor,
+        FileLimits, FileMode, FileUsage, GroupId, IoStatistics, MountOptions, OpenFlags, Stat,
+        UserId,
     },
+    time::ClockId,
 };
+use core::mem::size_of;
 use core::time::Duration;
 
 // TODO document the syscall ABI
@@ -56,6 +65,14 @@ macro_rules! syscall {
              in("x3") $a3, in("x4") $a4, in("x8") $num.repr(), options(nostack));
         res
     }};
+    ($num:expr, $a0:expr, $a1:expr, $a2:expr, $a3:expr, $a4:expr, $a5:expr) => {{
+        let mut res: usize = $a0;
+        asm!("svc #0",
+             inout("x0") res, in("x1") $a1, in("x2") $a2,
+             in("x3") $a3, in("x4") $a4, in("x5") $a5,
+             in("x8") $num.repr(), options(nostack));
+        res
+    }};
 }
 
 /// Integer/size argument
@@ -88,6 +105,9 @@ pub fn sys_close(fd: FileDescriptor) -> Result<(), Errno> {
     Errno::from_syscall_unit(unsafe { syscall!(SystemCall::Close, argn!(u32::from(fd))) })
 }
 
+/// Suspends the calling thread for `ns` nanoseconds. If interrupted by a
+/// signal, [Errno::Interrupt] is returned and the remaining time is written
+/// to `rem` as (seconds, nanoseconds).
 #[inline(always)]
 pub fn sys_ex_nanosleep(ns: u64, rem: &mut [u64; 2]) -> Result<(), Errno> {
     Errno::from_syscall_unit(unsafe {
@@ -114,11 +134,12 @@ pub fn sys_openat(
     mode: FileMode,
     flags: OpenFlags,
 ) -> Result<FileDescriptor, Errno> {
+    let pathname = UserString::new(pathname)?;
     Errno::from_syscall(unsafe {
         syscall!(
             SystemCall::Open,
             argn!(FileDescriptor::into_i32(at)),
-            argp!(pathname.as_ptr()),
+            argp!(pathname.base()),
             argn!(pathname.len()),
             argn!(mode.bits()),
             argn!(flags.bits())
@@ -127,6 +148,69 @@ pub fn sys_openat(
     .map(|e| FileDescriptor::from(e as u32))
 }
 
+/// Creates a filesystem node: a device (with device number `dev`),
+/// named pipe or regular file, depending on `mode`'s file type bits
+#[inline(always)]
+pub fn sys_mknodat(
+    at: Option<FileDescriptor>,
+    pathname: &str,
+    mode: FileMode,
+    dev: u32,
+) -> Result<(), Errno> {
+    let pathname = UserString::new(pathname)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::CreateNode,
+            argn!(FileDescriptor::into_i32(at)),
+            argp!(pathname.base()),
+            argn!(pathname.len()),
+            argn!(mode.bits()),
+            argn!(dev)
+        )
+    })
+}
+
+/// Removes a file or, if `AT_REMOVEDIR` is set in `flags`, an empty directory
+#[inline(always)]
+pub fn sys_unlinkat(at: Option<FileDescriptor>, pathname: &str, flags: u32) -> Result<(), Errno> {
+    let pathname = UserString::new(pathname)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::Unlink,
+            argn!(FileDescriptor::into_i32(at)),
+            argp!(pathname.base()),
+            argn!(pathname.len()),
+            argn!(flags)
+        )
+    })
+}
+
+#[inline(always)]
+pub fn sys_truncate(pathname: &str, size: usize) -> Result<(), Errno> {
+    let pathname = UserString::new(pathname)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::Truncate,
+            argp!(pathname.base()),
+            argn!(pathname.len()),
+            argn!(size)
+        )
+    })
+}
+
+#[inline(always)]
+pub fn sys_ftruncate(fd: FileDescriptor, size: usize) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::FileTruncate, argn!(u32::from(fd)), argn!(size))
+    })
+}
+
+/// Writes modified cached data of the file back to its storage
+#[inline(always)]
+pub fn sys_fsync(fd: FileDescriptor) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe { syscall!(SystemCall::FileSync, argn!(u32::from(fd))) })
+}
+
 #[inline(always)]
 pub fn sys_read(fd: FileDescriptor, data: &mut [u8]) -> Result<usize, Errno> {
     Errno::from_syscall(unsafe {
@@ -158,11 +242,12 @@ pub fn sys_fstatat(
     statbuf: &mut Stat,
     flags: u32,
 ) -> Result<(), Errno> {
+    let pathname = UserString::new(pathname)?;
     Errno::from_syscall_unit(unsafe {
         syscall!(
             SystemCall::FileStatus,
             argn!(FileDescriptor::into_i32(at)),
-            argp!(pathname.as_ptr()),
+            argp!(pathname.base()),
             argn!(pathname.len()),
             argp!(statbuf as *mut Stat),
             argn!(flags)
@@ -185,14 +270,17 @@ pub unsafe fn sys_fork() -> Result<Option<Pid>, Errno> {
 }
 
 #[inline(always)]
-pub fn sys_execve(pathname: &str, argv: &[&str]) -> Result<(), Errno> {
+pub fn sys_execve(pathname: &str, argv: &[&str], envp: &[&str]) -> Result<(), Errno> {
+    let pathname = UserString::new(pathname)?;
     Errno::from_syscall_unit(unsafe {
         syscall!(
             SystemCall::Exec,
-            argp!(pathname.as_ptr()),
+            argp!(pathname.base()),
             argn!(pathname.len()),
             argp!(argv.as_ptr()),
-            argn!(argv.len())
+            argn!(argv.len()),
+            argp!(envp.as_ptr()),
+            argn!(envp.len())
         )
     })
 }
@@ -219,13 +307,35 @@ pub fn sys_ioctl(
         syscall!(
             SystemCall::Ioctl,
             argn!(u32::from(fd)),
-            argn!(cmd),
+            argn!(cmd.request()),
             argn!(ptr),
             argn!(len)
         )
     })
 }
 
+/// Performs a control request which returns its result in `value`
+#[inline(always)]
+pub fn sys_ioctl_get<T: IoctlArgument>(
+    fd: FileDescriptor,
+    cmd: IoctlCmd,
+    value: &mut T,
+) -> Result<(), Errno> {
+    cmd.check_argument::<T>()?;
+    sys_ioctl(fd, cmd, value as *mut T as usize, size_of::<T>()).map(|_| ())
+}
+
+/// Performs a control request which takes `value` as its argument
+#[inline(always)]
+pub fn sys_ioctl_set<T: IoctlArgument>(
+    fd: FileDescriptor,
+    cmd: IoctlCmd,
+    value: &T,
+) -> Result<(), Errno> {
+    cmd.check_argument::<T>()?;
+    sys_ioctl(fd, cmd, value as *const T as usize, size_of::<T>()).map(|_| ())
+}
+
 #[inline(always)]
 pub fn sys_ex_getcputime() -> Result<Duration, Errno> {
     Errno::from_syscall(unsafe { syscall!(SystemCall::GetCpuTime) })
@@ -313,11 +423,12 @@ pub fn sys_faccessat(
     mode: AccessMode,
     flags: u32,
 ) -> Result<(), Errno> {
+    let name = UserString::new(name)?;
     Errno::from_syscall_unit(unsafe {
         syscall!(
             SystemCall::Access,
             argn!(FileDescriptor::into_i32(fd)),
-            argp!(name.as_ptr()),
+            argp!(name.base()),
             argn!(name.len()),
             argn!(mode.bits()),
             argn!(flags)
@@ -365,6 +476,23 @@ pub fn sys_readdir(fd: FileDescriptor, buf: &mut [DirectoryEntry]) -> Result<usi
     })
 }
 
+/// Reads directory entries along with their file status, saving a
+/// separate fstatat() call per entry
+#[inline(always)]
+pub fn sys_readdir_plus(
+    fd: FileDescriptor,
+    buf: &mut [DirectoryEntryPlus],
+) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::ReadDirectoryPlus,
+            argn!(u32::from(fd)),
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
 #[inline(always)]
 pub fn sys_getuid() -> UserId {
     UserId::from(unsafe { syscall!(SystemCall::GetUserId) as u32 })
@@ -375,24 +503,396 @@ pub fn sys_getgid() -> GroupId {
     GroupId::from(unsafe { syscall!(SystemCall::GetGroupId) as u32 })
 }
 
+#[inline(always)]
+pub fn sys_geteuid() -> UserId {
+    UserId::from(unsafe { syscall!(SystemCall::GetEffectiveUserId) as u32 })
+}
+
+#[inline(always)]
+pub fn sys_getegid() -> GroupId {
+    GroupId::from(unsafe { syscall!(SystemCall::GetEffectiveGroupId) as u32 })
+}
+
 #[inline(always)]
 pub fn sys_setsid() -> Result<Pid, Errno> {
     Errno::from_syscall(unsafe { syscall!(SystemCall::SetSid) })
         .and_then(|e| Pid::try_from(e as u32))
 }
 
+#[inline(always)]
+pub fn sys_ex_capget() -> Capabilities {
+    Capabilities::from_bits_truncate(unsafe { syscall!(SystemCall::GetCapabilities) as u32 })
+}
+
+#[inline(always)]
+pub fn sys_ex_capdrop(caps: Capabilities) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::DropCapabilities, argn!(caps.bits()))
+    })
+}
+
+#[inline(always)]
+pub fn sys_ex_setfilter(filter: &SyscallFilter) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::SetSyscallFilter, argp!(filter as *const _))
+    })
+}
+
+#[inline(always)]
+pub fn sys_ex_unshare(flags: UnshareFlags) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe { syscall!(SystemCall::Unshare, argn!(flags.bits())) })
+}
+
+/// Changes the name of the current process or thread. Names longer than
+/// [PROCESS_NAME_MAX](crate::proc::PROCESS_NAME_MAX) are truncated.
+#[inline(always)]
+pub fn sys_ex_set_name(target: NameTarget, name: &str) -> Result<(), Errno> {
+    let name = UserString::new(name)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::SetName,
+            argn!(target as u32),
+            argp!(name.base()),
+            argn!(name.len())
+        )
+    })
+}
+
+/// Reads the name of process or thread `id` into `buf`, returns its length
+#[inline(always)]
+pub fn sys_ex_get_name(target: NameTarget, id: u32, buf: &mut [u8]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetName,
+            argn!(target as u32),
+            argn!(id),
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Stores the IDs of running processes into `buf`. Returns the total
+/// number of processes, which may exceed the length of `buf`.
+#[inline(always)]
+pub fn sys_ex_process_list(buf: &mut [Pid]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetProcessList,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Stores time accounting of each CPU into `buf`. Returns the number of
+/// CPUs, which may exceed the length of `buf`.
+#[inline(always)]
+pub fn sys_ex_cpu_statistics(buf: &mut [CpuStatistics]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetCpuStatistics,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Stores Copy-on-Write and fork() counters into `stats`. If `reset` is
+/// set, the counters are cleared afterwards, which requires
+/// [Capabilities::SYS_ADMIN].
+#[inline(always)]
+pub fn sys_ex_fork_statistics(stats: &mut ForkStatistics, reset: bool) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::GetForkStatistics,
+            argp!(stats as *mut _),
+            argn!(reset as usize)
+        )
+    })
+}
+
+#[inline(always)]
+pub fn sys_gethostname(buf: &mut [u8]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetHostname,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+#[inline(always)]
+pub fn sys_sethostname(name: &str) -> Result<(), Errno> {
+    let name = UserString::new(name)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::SetHostname,
+            argp!(name.base()),
+            argn!(name.len())
+        )
+    })
+}
+
+/// Loads a relocatable kernel module from `path`
+#[inline(always)]
+pub fn sys_ex_load_module(path: &str) -> Result<(), Errno> {
+    let path = UserString::new(path)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::LoadModule,
+            argp!(path.base()),
+            argn!(path.len())
+        )
+    })
+}
+
+/// Stores the list of loaded kernel modules into `buf`. Returns the total
+/// number of modules, which may exceed the length of `buf`.
+#[inline(always)]
+pub fn sys_ex_module_list(buf: &mut [ModuleInfo]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetModuleList,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Starts using the block device at `path` as a swap area
+#[inline(always)]
+pub fn sys_ex_swapon(path: &str) -> Result<(), Errno> {
+    let path = UserString::new(path)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::SwapOn,
+            argp!(path.base()),
+            argn!(path.len())
+        )
+    })
+}
+
+/// Returns swap area usage and activity counters
+#[inline(always)]
+pub fn sys_ex_swap_status(status: &mut SwapStatus) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::GetSwapStatus, argp!(status as *mut _))
+    })
+}
+
+/// Sets swapper eagerness (0..=100)
+#[inline(always)]
+pub fn sys_ex_set_swappiness(value: usize) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe { syscall!(SystemCall::SetSwappiness, argn!(value)) })
+}
+
+/// Returns system-wide memory usage summary
+#[inline(always)]
+pub fn sys_ex_memory_status(status: &mut MemoryStatus) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::GetMemoryStatus, argp!(status as *mut _))
+    })
+}
+
+/// Returns memory usage and working set estimate of process `pid`
+#[inline(always)]
+pub fn sys_ex_process_memory(pid: Pid, usage: &mut ProcessMemory) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::GetProcessMemory,
+            argn!(u32::from(pid)),
+            argp!(usage as *mut _)
+        )
+    })
+}
+
+/// Returns I/O counters of process `pid`
+#[inline(always)]
+pub fn sys_ex_process_io(pid: Pid, stats: &mut IoStatistics) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::GetProcessIo,
+            argn!(u32::from(pid)),
+            argp!(stats as *mut _)
+        )
+    })
+}
+
+/// Returns I/O counters of the filesystem containing file `fd`
+#[inline(always)]
+pub fn sys_ex_filesystem_io(fd: FileDescriptor, stats: &mut IoStatistics) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::GetFilesystemIo,
+            argn!(u32::from(fd)),
+            argp!(stats as *mut _)
+        )
+    })
+}
+
+/// Returns the page cache writeback thresholds
+#[inline(always)]
+pub fn sys_ex_dirty_limits(limits: &mut DirtyLimits) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::GetDirtyLimits, argp!(limits as *mut _))
+    })
+}
+
+/// Changes the page cache writeback thresholds
+#[inline(always)]
+pub fn sys_ex_set_dirty_limits(limits: &DirtyLimits) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::SetDirtyLimits, argp!(limits as *const _))
+    })
+}
+
+/// Returns the system-wide caps on open files, vnodes and pipes
+#[inline(always)]
+pub fn sys_ex_file_limits(limits: &mut FileLimits) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::GetFileLimits, argp!(limits as *mut _))
+    })
+}
+
+/// Changes the system-wide caps on open files, vnodes and pipes
+#[inline(always)]
+pub fn sys_ex_set_file_limits(limits: &FileLimits) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::SetFileLimits, argp!(limits as *const _))
+    })
+}
+
+/// Returns the number of open files, vnodes and pipes in the system
+#[inline(always)]
+pub fn sys_ex_file_usage(usage: &mut FileUsage) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::GetFileUsage, argp!(usage as *mut _))
+    })
+}
+
+/// Stores the list of devices known to the kernel into `buf`. Returns the
+/// total number of devices, which may exceed the length of `buf`.
+#[inline(always)]
+pub fn sys_ex_device_list(buf: &mut [DeviceInfo]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetDeviceList,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Stores the list of discovered PCI functions into `buf`. Returns the
+/// total number of functions, which may exceed the length of `buf`.
+#[inline(always)]
+pub fn sys_ex_pci_functions(buf: &mut [PciFunction]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetPciFunctions,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
+/// Writes back and drops all the clean unused pages from the page cache
+#[inline(always)]
+pub fn sys_ex_drop_caches() -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe { syscall!(SystemCall::DropCaches) })
+}
+
+/// Reads current time of `clock`
+#[inline(always)]
+pub fn sys_ex_clock_get(clock: ClockId) -> Result<Duration, Errno> {
+    Errno::from_syscall(unsafe { syscall!(SystemCall::GetClock, argn!(clock.repr())) })
+        .map(|e| Duration::from_nanos(e as u64))
+}
+
+/// Steps `clock` to `value`. Only [ClockId::Realtime] can be set.
+#[inline(always)]
+pub fn sys_ex_clock_set(clock: ClockId, value: Duration) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::SetClock,
+            argn!(clock.repr()),
+            argn!(value.as_nanos() as u64)
+        )
+    })
+}
+
+/// Gradually adjusts the wall clock by `delta` nanoseconds, replacing any
+/// adjustment still in progress. The remaining part of the previous
+/// adjustment is written to `old`. Passing [None] as `delta` only queries it.
+#[inline(always)]
+pub fn sys_ex_adjtime(delta: Option<i64>, old: Option<&mut i64>) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::AdjustClock,
+            argp!(delta
+                .as_ref()
+                .map(|e| e as *const _)
+                .unwrap_or(core::ptr::null())),
+            argp!(old.map(|e| e as *mut _).unwrap_or(core::ptr::null_mut()))
+        )
+    })
+}
+
 #[inline(always)]
 pub fn sys_mount(target: &str, options: &MountOptions) -> Result<(), Errno> {
+    let target = UserString::new(target)?;
     Errno::from_syscall_unit(unsafe {
         syscall!(
             SystemCall::Mount,
-            argp!(target.as_ptr()),
+            argp!(target.base()),
             argn!(target.len()),
             argp!(options as *const _)
         )
     })
 }
 
+#[inline(always)]
+pub fn sys_unmount(target: &str) -> Result<(), Errno> {
+    let target = UserString::new(target)?;
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::Unmount,
+            argp!(target.base()),
+            argn!(target.len())
+        )
+    })
+}
+
+/// Creates an asynchronous I/O ring with `entries` slots per queue and maps
+/// it into the caller, see [crate::ioring]
+#[inline(always)]
+pub fn sys_ioring_setup(entries: u32, mapping: &mut RingMapping) -> Result<FileDescriptor, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::IoRingSetup,
+            argn!(entries),
+            argp!(mapping as *mut RingMapping)
+        )
+    })
+    .map(|e| FileDescriptor::from(e as u32))
+}
+
+/// Hands queued requests of ring `fd` over to the kernel and waits until
+/// at least `min_complete` completions are ready. Returns the number of
+/// completions ready.
+#[inline(always)]
+pub fn sys_ioring_enter(fd: FileDescriptor, min_complete: u32) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::IoRingEnter,
+            argn!(u32::from(fd)),
+            argn!(min_complete)
+        )
+    })
+}
+
 #[inline(always)]
 pub fn sys_dup(src: FileDescriptor, dst: Option<FileDescriptor>) -> Result<FileDescriptor, Errno> {
     Errno::from_syscall(unsafe {
@@ -415,12 +915,38 @@ pub fn sys_setgid(gid: GroupId) -> Result<(), Errno> {
     Errno::from_syscall_unit(unsafe { syscall!(SystemCall::SetGroupId, u32::from(gid) as usize) })
 }
 
+#[inline(always)]
+pub fn sys_seteuid(uid: UserId) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::SetEffectiveUserId, u32::from(uid) as usize)
+    })
+}
+
+#[inline(always)]
+pub fn sys_setegid(gid: GroupId) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(SystemCall::SetEffectiveGroupId, u32::from(gid) as usize)
+    })
+}
+
+#[inline(always)]
+pub fn sys_getcwd(buf: &mut [u8]) -> Result<usize, Errno> {
+    Errno::from_syscall(unsafe {
+        syscall!(
+            SystemCall::GetCurrentDirectory,
+            argp!(buf.as_mut_ptr()),
+            argn!(buf.len())
+        )
+    })
+}
+
 #[inline(always)]
 pub fn sys_chdir(path: &str) -> Result<(), Errno> {
+    let path = UserString::new(path)?;
     Errno::from_syscall_unit(unsafe {
         syscall!(
             SystemCall::SetCurrentDirectory,
-            argp!(path.as_ptr()),
+            argp!(path.base()),
             argn!(path.len())
         )
     })
@@ -452,3 +978,38 @@ pub fn sys_mmap(
 pub unsafe fn sys_munmap(addr: usize, len: usize) -> Result<(), Errno> {
     Errno::from_syscall_unit(syscall!(SystemCall::UnmapMemory, argn!(addr), argn!(len)))
 }
+
+/// Advises the kernel about expected use of memory range `addr..addr + len`
+///
+/// # Safety
+///
+/// System call. [MemoryAdvice::DontNeed] discards the contents of the range.
+#[inline(always)]
+pub unsafe fn sys_madvise(addr: usize, len: usize, advice: MemoryAdvice) -> Result<(), Errno> {
+    Errno::from_syscall_unit(syscall!(
+        SystemCall::MemoryAdvise,
+        argn!(addr),
+        argn!(len),
+        argn!(advice as u32)
+    ))
+}
+
+/// Advises the kernel about expected access pattern of file data in range
+/// `offset..offset + len`. Zero `len` means "until the end of the file".
+#[inline(always)]
+pub fn sys_fadvise(
+    fd: FileDescriptor,
+    offset: usize,
+    len: usize,
+    advice: FileAdvice,
+) -> Result<(), Errno> {
+    Errno::from_syscall_unit(unsafe {
+        syscall!(
+            SystemCall::FileAdvise,
+            argn!(u32::from(fd)),
+            argn!(offset),
+            argn!(len),
+            argn!(advice as u32)
+        )
+    })
+}
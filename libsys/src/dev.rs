@@ -0,0 +1,140 @@
+//! Device information reported by the kernel
+use crate::FixedStr;
+
+/// Maximum length of a device name in [DeviceInfo]
+pub const DEVICE_NAME_MAX: usize = 48;
+
+/// Maximum length of a module name in [ModuleInfo]
+pub const MODULE_NAME_MAX: usize = 48;
+
+/// Number of base address registers of a PCI function
+pub const PCI_BAR_COUNT: usize = 6;
+
+/// Power/initialization state of a device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum DeviceState {
+    /// Device is initialized and running
+    Enabled = 1,
+    /// Device initialization has failed
+    Failed = 2,
+    /// Device state has been saved and the device is powered down
+    Suspended = 3,
+}
+
+/// Entry of the kernel device list
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DeviceInfo {
+    /// Device state
+    pub state: DeviceState,
+    /// Device type/driver name
+    pub name: FixedStr<DEVICE_NAME_MAX>,
+}
+
+bitflags! {
+    /// Kind of a PCI base address register
+    #[derive(Default)]
+    pub struct PciBarFlags: u32 {
+        /// Register maps I/O port space instead of memory
+        const IO = 1 << 0;
+        /// Register is 64 bits wide, occupying two slots
+        const MEM64 = 1 << 1;
+        /// Memory region is prefetchable
+        const PREFETCH = 1 << 2;
+    }
+}
+
+/// PCI base address register. Unused registers have zero `size`.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct PciBar {
+    /// Bus address the region is assigned at
+    pub address: u64,
+    /// Region size in bytes
+    pub size: u64,
+    /// Region kind
+    pub flags: PciBarFlags,
+}
+
+/// Entry of the PCI function list
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct PciFunction {
+    /// Function address, see [PciFunction::bus], [PciFunction::dev] and
+    /// [PciFunction::func]
+    pub address: u32,
+    /// Vendor ID
+    pub vendor_id: u16,
+    /// Device ID
+    pub device_id: u16,
+    /// Class, subclass, programming interface and revision, most
+    /// significant byte first
+    pub class: u32,
+    /// Interrupt pin (1 = INTA .. 4 = INTD), 0 if the function does not
+    /// use legacy interrupts
+    pub irq_pin: u8,
+    /// Interrupt line as left in config space
+    pub irq_line: u8,
+    /// Base address registers
+    pub bars: [PciBar; PCI_BAR_COUNT],
+}
+
+/// Entry of the loaded kernel module list
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ModuleInfo {
+    /// Module name
+    pub name: FixedStr<MODULE_NAME_MAX>,
+    /// Kernel virtual address of the module image
+    pub base: usize,
+    /// Size of the module image in bytes
+    pub size: usize,
+}
+
+impl Default for DeviceInfo {
+    fn default() -> Self {
+        Self {
+            state: DeviceState::Enabled,
+            name: FixedStr::empty(),
+        }
+    }
+}
+
+impl Default for ModuleInfo {
+    fn default() -> Self {
+        Self {
+            name: FixedStr::empty(),
+            base: 0,
+            size: 0,
+        }
+    }
+}
+
+impl DeviceState {
+    /// Returns the state as a lowercase string
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Enabled => "enabled",
+            Self::Failed => "failed",
+            Self::Suspended => "suspended",
+        }
+    }
+}
+
+impl PciFunction {
+    /// Returns the bus number
+    pub const fn bus(&self) -> u8 {
+        (self.address >> 8) as u8
+    }
+
+    /// Returns the device number on the bus
+    pub const fn dev(&self) -> u8 {
+        ((self.address >> 3) as u8) & 0x1F
+    }
+
+    /// Returns the function number
+    pub const fn func(&self) -> u8 {
+        (self.address as u8) & 0x7
+    }
+}
@@ -3,6 +3,7 @@
 pub enum Errno {
     AlreadyExists,
     BadExecutable,
+    BrokenPipe,
     Busy,
     DeviceError,
     DoesNotExist,
@@ -12,7 +13,9 @@ pub enum Errno {
     InvalidFile,
     InvalidOperation,
     IsADirectory,
+    NoSpace,
     NotADirectory,
+    NotEmpty,
     NotImplemented,
     OutOfMemory,
     PermissionDenied,
@@ -1,13 +1,136 @@
+//! Device control requests.
+//!
+//! Every [IoctlCmd] has a single argument structure with a fixed layout:
+//! `#[repr(C)]`, with explicitly sized fields only, so it is the same for
+//! 32- and 64-bit code. The layout is described by [IoctlArgument]. The
+//! request word passed to the kernel carries the layout version the caller
+//! was built against, and the kernel rejects requests whose version or
+//! argument size don't match its own, instead of misinterpreting them.
 use core::convert::TryFrom;
+use core::mem::size_of;
 use crate::error::Errno;
+use crate::ring::RingMapping;
+use crate::termios::{ModemLines, Termios, WindowSize};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 #[non_exhaustive]
 pub enum IoctlCmd {
     TtySetAttributes = 1,
     TtyGetAttributes = 2,
     TtySetPgrp = 3,
+    ZramGetStats = 4,
+    TtyGetModemLines = 5,
+    TtySetModemLines = 6,
+    TtySendBreak = 7,
+    RingMap = 8,
+    TtyGetPgrp = 9,
+    TtyDrain = 10,
+    TtyFlush = 11,
+    TtyGetWindowSize = 12,
+    TtySetWindowSize = 13,
+}
+
+/// Fixed-layout structure passed as an ioctl argument
+///
+/// # Safety
+///
+/// Unsafe: implementors must have the same layout regardless of the
+/// pointer width, i.e. no `usize`, pointer or other target-dependent
+/// fields.
+pub unsafe trait IoctlArgument: Sized {
+    /// Layout version, to be bumped on any change of the structure
+    const VERSION: u16;
+}
+
+/// Compressed RAM device usage statistics
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct ZramStats {
+    /// Uncompressed size of the stored data
+    pub data_size: u64,
+    /// Heap memory used for compressed data
+    pub compressed_size: u64,
+    /// Blocks filled with a single byte value (take no memory)
+    pub same_pages: u64,
+    /// Blocks stored compressed
+    pub compressed_pages: u64,
+    /// Incompressible blocks stored in whole pages
+    pub raw_pages: u64,
+}
+
+unsafe impl IoctlArgument for () {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for u32 {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for Termios {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for ModemLines {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for WindowSize {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for ZramStats {
+    const VERSION: u16 = 1;
+}
+unsafe impl IoctlArgument for RingMapping {
+    const VERSION: u16 = 1;
+}
+
+// Catch accidental layout changes which were not reflected in the version
+const _: () = assert!(size_of::<Termios>() == 28);
+const _: () = assert!(size_of::<ModemLines>() == 4);
+const _: () = assert!(size_of::<WindowSize>() == 4);
+const _: () = assert!(size_of::<ZramStats>() == 40);
+const _: () = assert!(size_of::<RingMapping>() == 16);
+
+impl IoctlCmd {
+    /// Returns the size and layout version of the command's argument
+    pub const fn argument(self) -> (usize, u16) {
+        const fn of<T: IoctlArgument>() -> (usize, u16) {
+            (size_of::<T>(), T::VERSION)
+        }
+
+        match self {
+            Self::TtySetAttributes | Self::TtyGetAttributes => of::<Termios>(),
+            Self::TtySetPgrp | Self::TtyGetPgrp | Self::TtySendBreak | Self::TtyFlush => {
+                of::<u32>()
+            }
+            Self::TtyDrain => of::<()>(),
+            Self::ZramGetStats => of::<ZramStats>(),
+            Self::TtyGetModemLines | Self::TtySetModemLines => of::<ModemLines>(),
+            Self::TtyGetWindowSize | Self::TtySetWindowSize => of::<WindowSize>(),
+            Self::RingMap => of::<RingMapping>(),
+        }
+    }
+
+    /// Returns the request word for the command: the command number in
+    /// the low 16 bits, the argument layout version in the high 16 bits
+    pub const fn request(self) -> u32 {
+        (self as u32) | ((self.argument().1 as u32) << 16)
+    }
+
+    /// Decodes a request word, checking the argument layout version
+    pub fn from_request(request: u32) -> Result<Self, Errno> {
+        let cmd = Self::try_from(request & 0xFFFF)?;
+        if (request >> 16) as u16 != cmd.argument().1 {
+            return Err(Errno::InvalidArgument);
+        }
+        Ok(cmd)
+    }
+
+    /// Checks that `T` is the argument type of the command
+    pub fn check_argument<T: IoctlArgument>(self) -> Result<(), Errno> {
+        if self.argument() == (size_of::<T>(), T::VERSION) {
+            Ok(())
+        } else {
+            Err(Errno::InvalidArgument)
+        }
+    }
 }
 
 impl TryFrom<u32> for IoctlCmd {
@@ -19,6 +142,16 @@ impl TryFrom<u32> for IoctlCmd {
             1 => Ok(Self::TtySetAttributes),
             2 => Ok(Self::TtyGetAttributes),
             3 => Ok(Self::TtySetPgrp),
+            4 => Ok(Self::ZramGetStats),
+            5 => Ok(Self::TtyGetModemLines),
+            6 => Ok(Self::TtySetModemLines),
+            7 => Ok(Self::TtySendBreak),
+            8 => Ok(Self::RingMap),
+            9 => Ok(Self::TtyGetPgrp),
+            10 => Ok(Self::TtyDrain),
+            11 => Ok(Self::TtyFlush),
+            12 => Ok(Self::TtyGetWindowSize),
+            13 => Ok(Self::TtySetWindowSize),
             _ => Err(Errno::InvalidArgument)
         }
     }
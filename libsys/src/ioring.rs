@@ -0,0 +1,194 @@
+//! Asynchronous I/O submission rings.
+//!
+//! A ring mapping starts with a page holding [IoRingHeader], followed by
+//! the submission queue (an array of [Submission]) and the completion
+//! queue (an array of [Completion]), each starting on a page boundary.
+//! Both queues have `entries` slots, indexed by free-running counters
+//! modulo `entries`.
+//!
+//! Userspace fills a submission slot at `sq_tail` and advances `sq_tail`,
+//! the kernel advances `sq_head` as it takes requests. The kernel posts
+//! completions at `cq_tail`, userspace advances `cq_head` after reaping
+//! them. Requests are only taken while there is room for their
+//! completion, so the completion queue never overflows.
+use crate::error::Errno;
+use core::convert::TryFrom;
+use core::mem::size_of;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Maximum number of queue entries of a ring
+pub const IORING_MAX_ENTRIES: u32 = 256;
+/// [Submission::offset] value requesting I/O at the current file position
+pub const OFFSET_CURRENT: u64 = u64::MAX;
+
+/// Ring state, located at the start of the mapping
+#[repr(C)]
+pub struct IoRingHeader {
+    /// Number of requests taken by the kernel
+    pub sq_head: AtomicU32,
+    /// Number of requests submitted by userspace
+    pub sq_tail: AtomicU32,
+    /// Number of completions reaped by userspace
+    pub cq_head: AtomicU32,
+    /// Number of completions posted by the kernel
+    pub cq_tail: AtomicU32,
+    /// Number of slots in each queue, a power of two
+    pub entries: u32,
+}
+
+/// Operation requested by a [Submission]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum IoOp {
+    /// Does nothing, completes with zero
+    Nop = 0,
+    /// Reads from the file into the buffer
+    Read = 1,
+    /// Writes the buffer to the file
+    Write = 2,
+    /// Writes back cached file data, like fsync()
+    Fsync = 3,
+}
+
+/// Submission queue entry
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct Submission {
+    /// Raw [IoOp] value
+    pub op: u32,
+    /// File descriptor of the submitting process
+    pub fd: u32,
+    /// File position, or [OFFSET_CURRENT]
+    pub offset: u64,
+    /// Buffer address
+    pub buf: usize,
+    /// Buffer length
+    pub len: usize,
+    /// Value passed back in the [Completion]
+    pub user_data: u64,
+}
+
+/// Completion queue entry
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct Completion {
+    /// [Submission::user_data] of the completed request
+    pub user_data: u64,
+    /// Number of bytes transferred, or a negated [Errno]
+    pub result: isize,
+}
+
+/// User side of a mapped ring
+pub struct IoRingUser {
+    header: *const IoRingHeader,
+    sq: *mut Submission,
+    cq: *const Completion,
+}
+
+impl TryFrom<u32> for IoOp {
+    type Error = Errno;
+
+    fn try_from(raw: u32) -> Result<IoOp, Errno> {
+        match raw {
+            0 => Ok(Self::Nop),
+            1 => Ok(Self::Read),
+            2 => Ok(Self::Write),
+            3 => Ok(Self::Fsync),
+            _ => Err(Errno::InvalidArgument),
+        }
+    }
+}
+
+impl Completion {
+    /// Converts the raw result into a [Result]
+    pub fn result(&self) -> Result<usize, Errno> {
+        if self.result < 0 {
+            Err(Errno::from((-self.result) as usize))
+        } else {
+            Ok(self.result as usize)
+        }
+    }
+}
+
+const fn page_align(size: usize) -> usize {
+    (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+/// Returns the offset of the submission queue within a ring mapping
+pub const fn sq_offset() -> usize {
+    PAGE_SIZE
+}
+
+/// Returns the offset of the completion queue within a ring mapping
+pub const fn cq_offset(entries: u32) -> usize {
+    sq_offset() + page_align(entries as usize * size_of::<Submission>())
+}
+
+/// Returns the total size of a ring mapping with `entries` slots per queue
+pub const fn ring_size(entries: u32) -> usize {
+    cq_offset(entries) + page_align(entries as usize * size_of::<Completion>())
+}
+
+impl IoRingUser {
+    /// Constructs a user side for the ring mapped at `base`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: `base` must point to a valid ring mapping, which outlives
+    /// the returned value.
+    pub unsafe fn new(base: usize) -> Self {
+        let header = base as *const IoRingHeader;
+        let entries = (*header).entries;
+        Self {
+            header,
+            sq: (base + sq_offset()) as *mut Submission,
+            cq: (base + cq_offset(entries)) as *const Completion,
+        }
+    }
+
+    fn header(&self) -> &IoRingHeader {
+        unsafe { &*self.header }
+    }
+
+    /// Queues a request. Returns `false` if the submission queue is full.
+    /// The kernel only picks up queued requests once the ring is entered.
+    pub fn push(&mut self, sqe: Submission) -> bool {
+        let header = self.header();
+        let tail = header.sq_tail.load(Ordering::Relaxed);
+        let head = header.sq_head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= header.entries {
+            return false;
+        }
+        let index = (tail & (header.entries - 1)) as usize;
+        unsafe {
+            ptr::write_volatile(self.sq.add(index), sqe);
+        }
+        header.sq_tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Returns the number of completions ready to be reaped
+    pub fn completed(&self) -> u32 {
+        let header = self.header();
+        header
+            .cq_tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(header.cq_head.load(Ordering::Relaxed))
+    }
+
+    /// Reaps the next completion, if any
+    pub fn pop(&mut self) -> Option<Completion> {
+        let header = self.header();
+        let head = header.cq_head.load(Ordering::Relaxed);
+        if head == header.cq_tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let index = (head & (header.entries - 1)) as usize;
+        let cqe = unsafe { ptr::read_volatile(self.cq.add(index)) };
+        header.cq_head.store(head.wrapping_add(1), Ordering::Release);
+        Some(cqe)
+    }
+}
@@ -6,22 +6,43 @@ extern crate bitflags;
 
 pub mod abi;
 pub mod debug;
+pub mod dev;
 pub mod error;
 pub mod ioctl;
+pub mod ioring;
 pub mod mem;
 pub mod path;
 pub mod proc;
+pub mod ring;
 pub mod signal;
 pub mod stat;
 pub mod termios;
+pub mod time;
 pub mod traits;
 
 #[derive(Debug)]
 pub struct ProgramArgs {
     pub argv: usize,
     pub argc: usize,
+    pub envp: usize,
+    pub envc: usize,
     pub storage: usize,
-    pub size: usize
+    pub size: usize,
+    /// [abi::ABI_VERSION] of the kernel which started the program
+    pub abi_version: usize,
+}
+
+impl ProgramArgs {
+    /// Returns the ABI version reported by the kernel, or [None] if it
+    /// was started by a kernel which predates the field
+    pub fn abi_version(&self) -> Option<u32> {
+        // The structure is stored at the end of the argument storage
+        let end = self as *const Self as usize + core::mem::size_of::<Self>();
+        if end > self.storage + self.size {
+            return None;
+        }
+        Some(self.abi_version as u32)
+    }
 }
 
 // TODO utils
@@ -49,6 +70,18 @@ impl<const N: usize> FixedStr<N> {
         self.data[..self.len].copy_from_slice(src.as_bytes());
     }
 
+    /// Creates a string from `src`, cutting it at a char boundary if it
+    /// does not fit
+    pub fn from_str_truncated(src: &str) -> Self {
+        let mut len = src.len().min(N);
+        while !src.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut res = Self::empty();
+        res.copy_from_str(&src[..len]);
+        res
+    }
+
     pub fn as_str(&self) -> &str {
         unsafe {
             core::str::from_utf8_unchecked(&self.data[..self.len])
@@ -1,4 +1,5 @@
 use crate::error::Errno;
+use crate::FixedStr;
 use core::convert::TryFrom;
 use core::fmt;
 
@@ -29,6 +30,68 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Privileged operation permissions held by a process
+    pub struct Capabilities: u32 {
+        /// Mount/unmount filesystems and other administrative tasks
+        const SYS_ADMIN = 1 << 0;
+        /// Bypass file read/write permission checks
+        const DAC_OVERRIDE = 1 << 1;
+        /// Send signals to processes owned by other users
+        const KILL = 1 << 2;
+        /// Change user/group IDs of the process
+        const SETUID = 1 << 3;
+        /// Change network interface configuration
+        const NET_ADMIN = 1 << 4;
+        /// Reboot or power off the system
+        const SYS_BOOT = 1 << 5;
+        /// Change process root directory
+        const SYS_CHROOT = 1 << 6;
+        /// Set or adjust the wall clock
+        const SYS_TIME = 1 << 7;
+        /// Bypass file ownership checks: removal from sticky directories
+        /// and set-group-ID bit clearing
+        const FOWNER = 1 << 8;
+    }
+}
+
+bitflags! {
+    /// Process context parts to be detached from the parent by unshare()
+    pub struct UnshareFlags: u32 {
+        /// Private copy of the mount table
+        const MOUNT = 1 << 0;
+        /// Private copy of the hostname
+        const HOSTNAME = 1 << 1;
+    }
+}
+
+/// Maximum length of a process or thread name
+pub const PROCESS_NAME_MAX: usize = 32;
+/// Process or thread name storage type
+pub type ProcessName = FixedStr<PROCESS_NAME_MAX>;
+
+/// Kind of object whose name is accessed by get_name()/set_name()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum NameTarget {
+    /// Name of a process
+    Process = 0,
+    /// Name of a single thread
+    Thread = 1,
+}
+
+impl TryFrom<u32> for NameTarget {
+    type Error = Errno;
+
+    fn try_from(raw: u32) -> Result<NameTarget, Errno> {
+        match raw {
+            0 => Ok(Self::Process),
+            1 => Ok(Self::Thread),
+            _ => Err(Errno::InvalidArgument),
+        }
+    }
+}
+
 bitflags! {
     pub struct MemoryMap: u32 {
         const BACKEND = 0x3;
@@ -39,6 +102,186 @@ bitflags! {
     }
 }
 
+/// Expected use of a memory range, passed to madvise()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MemoryAdvice {
+    /// No special treatment
+    Normal = 0,
+    /// Pages will be accessed in random order
+    Random = 1,
+    /// Pages will be accessed in sequential order
+    Sequential = 2,
+    /// Pages will be accessed soon: read them back from swap now
+    WillNeed = 3,
+    /// Pages are no longer needed: their memory is released and they read
+    /// back as zeros on next access
+    DontNeed = 4,
+}
+
+impl TryFrom<u32> for MemoryAdvice {
+    type Error = Errno;
+
+    fn try_from(raw: u32) -> Result<MemoryAdvice, Errno> {
+        match raw {
+            0 => Ok(Self::Normal),
+            1 => Ok(Self::Random),
+            2 => Ok(Self::Sequential),
+            3 => Ok(Self::WillNeed),
+            4 => Ok(Self::DontNeed),
+            _ => Err(Errno::InvalidArgument),
+        }
+    }
+}
+
+/// Swap area usage and activity counters
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct SwapStatus {
+    /// Size of the swap area in pages (0 if swap is disabled)
+    pub total_pages: usize,
+    /// Number of swap slots in use
+    pub used_pages: usize,
+    /// Swapper eagerness, 0..=100
+    pub swappiness: usize,
+    /// Pages read back from the swap area
+    pub page_ins: usize,
+    /// Pages written to the swap area
+    pub page_outs: usize,
+}
+
+/// Memory usage of a process, in pages
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct ProcessMemory {
+    /// Pages present in memory
+    pub resident_pages: usize,
+    /// Pages in the swap area
+    pub swapped_pages: usize,
+    /// Resident pages accessed during the last scan interval
+    pub working_set_pages: usize,
+    /// Resident pages not accessed during the last scan interval
+    pub idle_pages: usize,
+}
+
+/// Page cache writeback thresholds
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct DirtyLimits {
+    /// Percentage of file data memory which may be dirty before writers
+    /// are throttled, 1..=100
+    pub throttle_ratio: usize,
+    /// Percentage of file data memory which may be dirty before background
+    /// writeback starts, at most `throttle_ratio`
+    pub background_ratio: usize,
+    /// Percentage of the throttling threshold a single process may dirty
+    /// before it is throttled, 1..=100
+    pub process_ratio: usize,
+}
+
+/// Time accounting of a single CPU since system start. Busy time is
+/// split by the kind of thread that was running.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct CpuStatistics {
+    /// Nanoseconds spent running threads of user processes
+    pub user_ns: u64,
+    /// Nanoseconds spent running kernel threads
+    pub kernel_ns: u64,
+    /// Nanoseconds spent in the idle thread
+    pub idle_ns: u64,
+    /// Number of context switches
+    pub switches: u64,
+}
+
+/// Copy-on-Write and fork() counters since system start or the last reset.
+/// Latency percentiles cover a window of the most recent forks.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct ForkStatistics {
+    /// Number of completed fork() calls
+    pub forks: usize,
+    /// Writable pages shared as Copy-on-Write by fork()
+    pub pages_shared: usize,
+    /// Write faults on Copy-on-Write pages
+    pub cow_faults: usize,
+    /// Copy-on-Write faults resolved by copying the page
+    pub pages_copied: usize,
+    /// Copy-on-Write faults resolved by taking over the last reference
+    pub pages_reused: usize,
+    /// Number of fork() latencies the percentiles are computed from
+    pub latency_samples: usize,
+    /// Median fork() latency, in nanoseconds
+    pub latency_p50_ns: u64,
+    /// 90th percentile of fork() latency, in nanoseconds
+    pub latency_p90_ns: u64,
+    /// 99th percentile of fork() latency, in nanoseconds
+    pub latency_p99_ns: u64,
+    /// Largest fork() latency in the window, in nanoseconds
+    pub latency_max_ns: u64,
+}
+
+/// Description of a fatal process fault, read from `/dev/crash`. Register
+/// values are the ones at the time of the fault.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct CrashRecord {
+    /// Faulting process
+    pub pid: u32,
+    /// Faulting thread
+    pub tid: u32,
+    /// Signal the fault was reported with
+    pub signal: u32,
+    /// Exception syndrome register
+    pub esr: u32,
+    /// Faulting address
+    pub far: u64,
+    /// Address of the faulting instruction
+    pub pc: u64,
+    /// Stack pointer
+    pub sp: u64,
+    /// Saved processor status
+    pub pstate: u64,
+    /// General-purpose registers x0-x30
+    pub x: [u64; 31],
+    /// Time of the fault, in seconds since the epoch
+    pub time: u64,
+    /// Name of the faulting process
+    pub name: ProcessName,
+}
+
+/// System-wide memory usage summary, sizes are in pages unless noted
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct MemoryStatus {
+    /// Pages managed by the kernel, excluding reserved ones
+    pub total_pages: usize,
+    /// Free pages
+    pub available_pages: usize,
+    /// Kernel data pages
+    pub kernel_pages: usize,
+    /// Pages backing the kernel heap
+    pub kernel_heap_pages: usize,
+    /// Translation table pages
+    pub paging_pages: usize,
+    /// Userspace pages, including the page cache
+    pub user_pages: usize,
+    /// Filesystem data and block pages
+    pub filesystem_pages: usize,
+    /// Pages given away (e.g. to the hypervisor)
+    pub offline_pages: usize,
+    /// Pages in the page cache
+    pub cached_pages: usize,
+    /// Page cache pages not yet written back
+    pub dirty_pages: usize,
+    /// Size of the kernel heap in bytes
+    pub heap_size: usize,
+    /// Kernel heap bytes in use
+    pub heap_used: usize,
+    /// Swap area status
+    pub swap: SwapStatus,
+}
+
 impl From<i32> for ExitCode {
     fn from(f: i32) -> Self {
         Self(f)
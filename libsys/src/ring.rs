@@ -0,0 +1,133 @@
+//! Single-producer/single-consumer ring buffer shared between the kernel
+//! and userspace.
+//!
+//! A ring mapping starts with a page holding [RingHeader], followed by the
+//! data area. The kernel appends records, each a [RecordHeader] followed
+//! by the payload and padded to [RECORD_ALIGN]. A record never wraps
+//! around the end of the data area, the producer fills the rest of it
+//! with a [RECORD_PAD] record instead. The consumer advances `tail` after
+//! reading a record.
+use core::mem::size_of;
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Size of the header page preceding the data area
+pub const RING_HEADER_SIZE: usize = 4096;
+/// Alignment of records in the data area
+pub const RECORD_ALIGN: usize = 8;
+/// Record length marking padding up to the end of the data area
+pub const RECORD_PAD: u32 = u32::MAX;
+
+/// Ring state, located at the start of the mapping
+#[repr(C)]
+pub struct RingHeader {
+    /// Total number of bytes written, updated by the producer
+    pub head: AtomicU64,
+    /// Total number of bytes consumed, updated by the consumer
+    pub tail: AtomicU64,
+    /// Size of the data area in bytes
+    pub size: u64,
+    /// Number of records dropped because the ring was full
+    pub dropped: AtomicU64,
+}
+
+/// Header of a single record in the data area
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct RecordHeader {
+    /// Payload length in bytes, or [RECORD_PAD]
+    pub len: u32,
+    /// Producer-defined record type
+    pub kind: u32,
+}
+
+/// Location of a ring mapped into the caller's address space
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct RingMapping {
+    /// Address of the [RingHeader] page
+    pub base: u64,
+    /// Total size of the mapping, including the header page
+    pub size: u64,
+}
+
+/// Consumer side of a ring
+pub struct RingConsumer {
+    header: *const RingHeader,
+    data: *const u8,
+}
+
+/// Returns the space taken by a record with `len` bytes of payload
+pub const fn record_size(len: usize) -> usize {
+    (size_of::<RecordHeader>() + len + RECORD_ALIGN - 1) & !(RECORD_ALIGN - 1)
+}
+
+impl RingConsumer {
+    /// Constructs a consumer for the ring mapped at `base`
+    ///
+    /// # Safety
+    ///
+    /// Unsafe: `base` must point to a valid ring mapping, which outlives
+    /// the consumer.
+    pub const unsafe fn new(base: usize) -> Self {
+        Self {
+            header: base as *const RingHeader,
+            data: (base + RING_HEADER_SIZE) as *const u8,
+        }
+    }
+
+    fn header(&self) -> &RingHeader {
+        unsafe { &*self.header }
+    }
+
+    /// Returns `true` if there are no records to read
+    pub fn is_empty(&self) -> bool {
+        let header = self.header();
+        header.tail.load(Ordering::Relaxed) == header.head.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of records dropped by the producer so far
+    pub fn dropped(&self) -> u64 {
+        self.header().dropped.load(Ordering::Relaxed)
+    }
+
+    /// Reads the next record into `buf`, truncating its payload if `buf`
+    /// is too short. Returns the record's kind and full payload length.
+    pub fn pop(&mut self, buf: &mut [u8]) -> Option<(u32, usize)> {
+        let header = self.header();
+        let size = header.size;
+        loop {
+            let tail = header.tail.load(Ordering::Relaxed);
+            let head = header.head.load(Ordering::Acquire);
+            if tail == head {
+                return None;
+            }
+
+            let offset = (tail % size) as usize;
+            let record = unsafe { ptr::read_volatile(self.data.add(offset) as *const RecordHeader) };
+            if record.len == RECORD_PAD {
+                header.tail.store(tail + size - offset as u64, Ordering::Release);
+                continue;
+            }
+
+            let len = record.len as usize;
+            if offset + record_size(len) > size as usize {
+                // Either side has broken the ring, drop its contents
+                header.tail.store(head, Ordering::Release);
+                return None;
+            }
+            let count = core::cmp::min(len, buf.len());
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    self.data.add(offset + size_of::<RecordHeader>()),
+                    buf.as_mut_ptr(),
+                    count,
+                );
+            }
+            header
+                .tail
+                .store(tail + record_size(len) as u64, Ordering::Release);
+            return Some((record.kind, len));
+        }
+    }
+}
@@ -9,6 +9,7 @@ pub enum Signal {
     FloatError = 8,
     Kill = 9,
     SegmentationFault = 11,
+    WindowChange = 28,
     InvalidSystemCall = 31
 }
 
@@ -56,6 +57,7 @@ impl TryFrom<u32> for Signal {
             8 => Ok(Self::FloatError),
             9 => Ok(Self::Kill),
             11 => Ok(Self::SegmentationFault),
+            28 => Ok(Self::WindowChange),
             31 => Ok(Self::InvalidSystemCall),
             _ => Err(Errno::InvalidArgument)
         }
@@ -1,10 +1,62 @@
 // TODO split up this file
 use crate::error::Errno;
+use core::convert::TryFrom;
 use core::str::FromStr;
 use core::fmt;
 
 const AT_FDCWD: i32 = -2;
 pub const AT_EMPTY_PATH: u32 = 1 << 16;
+/// Do not dereference the last path component if it is a symbolic link
+pub const AT_SYMLINK_NOFOLLOW: u32 = 1 << 8;
+/// faccessat(): check permissions using effective user/group IDs
+pub const AT_EACCESS: u32 = 1 << 9;
+/// unlinkat(): remove a directory instead of a file
+pub const AT_REMOVEDIR: u32 = 1 << 10;
+
+/// I/O activity counters of a process or a filesystem
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct IoStatistics {
+    /// Number of bytes read
+    pub read_bytes: u64,
+    /// Number of bytes written
+    pub write_bytes: u64,
+    /// Number of successful read requests
+    pub read_calls: u64,
+    /// Number of successful write requests
+    pub write_calls: u64,
+}
+
+/// Expected access pattern of file data, passed to fadvise()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FileAdvice {
+    /// Default read-ahead
+    Normal = 0,
+    /// Data is accessed in random order: no read-ahead
+    Random = 1,
+    /// Data is accessed sequentially: aggressive read-ahead
+    Sequential = 2,
+    /// Data will be accessed soon: read it into the page cache now
+    WillNeed = 3,
+    /// Data will not be accessed soon: drop it from the page cache
+    DontNeed = 4,
+}
+
+impl TryFrom<u32> for FileAdvice {
+    type Error = Errno;
+
+    fn try_from(raw: u32) -> Result<FileAdvice, Errno> {
+        match raw {
+            0 => Ok(Self::Normal),
+            1 => Ok(Self::Random),
+            2 => Ok(Self::Sequential),
+            3 => Ok(Self::WillNeed),
+            4 => Ok(Self::DontNeed),
+            _ => Err(Errno::InvalidArgument),
+        }
+    }
+}
 
 bitflags! {
     pub struct OpenFlags: u32 {
@@ -18,6 +70,9 @@ bitflags! {
         const O_CLOEXEC =   1 << 6;
         const O_DIRECTORY = 1 << 7;
         const O_CTTY =      1 << 8;
+        const O_NONBLOCK =  1 << 9;
+        const O_APPEND =    1 << 10;
+        const O_NOFOLLOW =  1 << 11;
     }
 }
 
@@ -27,6 +82,16 @@ bitflags! {
         const S_IFREG = 0x8 << 12;
         const S_IFDIR = 0x4 << 12;
         const S_IFCHR = 0x2 << 12;
+        const S_IFBLK = 0x6 << 12;
+        const S_IFIFO = 0x1 << 12;
+
+        /// Executing the file sets effective user ID to its owner
+        const SETUID = 1 << 11;
+        /// Executing the file sets effective group ID to its group
+        const SETGID = 1 << 10;
+        /// Entries of the directory may only be removed by their owner or
+        /// the directory owner
+        const STICKY = 1 << 9;
 
         const USER_READ = 1 << 8;
         const USER_WRITE = 1 << 7;
@@ -49,11 +114,48 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct MountFlags: u32 {
+        /// Filesystem cannot be written to
+        const MS_RDONLY =   1 << 0;
+        /// Files cannot be executed from the filesystem
+        const MS_NOEXEC =   1 << 1;
+        /// Set-user-ID bits are ignored
+        const MS_NOSUID =   1 << 2;
+    }
+}
+
+/// System-wide caps on file-related kernel objects
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct FileLimits {
+    /// Open file handles
+    pub max_files: usize,
+    /// Vnodes present in memory
+    pub max_vnodes: usize,
+    /// Pipe buffers
+    pub max_pipes: usize,
+}
+
+/// Number of file-related kernel objects in use
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct FileUsage {
+    /// Open file handles
+    pub files: usize,
+    /// Vnodes present in memory
+    pub vnodes: usize,
+    /// Pipe buffers
+    pub pipes: usize,
+}
+
 #[derive(Clone, Debug)]
 pub struct MountOptions<'a> {
     pub device: Option<&'a str>,
     pub fs: Option<&'a str>,
-    // TODO flags etc.
+    pub flags: MountFlags,
+    /// Size limit of memory-backed filesystems in bytes, 0 for the default
+    pub size: usize,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -122,8 +224,19 @@ pub struct FdSet {
 pub struct FileDescriptor(u32);
 
 #[derive(Clone, Copy)]
+#[repr(C)]
 pub struct DirectoryEntry {
     name: [u8; 64],
+    file_type: u32,
+}
+
+/// Directory entry with file status embedded, filled by readdir_plus
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct DirectoryEntryPlus {
+    entry: DirectoryEntry,
+    stat: Stat,
+    has_stat: u32,
 }
 
 struct FdSetIter<'a> {
@@ -137,11 +250,35 @@ pub struct Stat {
     pub mode: FileMode,
     pub size: u64,
     pub blksize: u32,
+    /// Device number for character/block device nodes
+    pub rdev: u32,
 }
 
 impl DirectoryEntry {
     pub const fn empty() -> Self {
-        Self { name: [0; 64] }
+        Self {
+            name: [0; 64],
+            file_type: 0,
+        }
+    }
+
+    /// Constructs an entry `name` of `file_type` (file type bits of
+    /// [FileMode], other bits are ignored)
+    pub fn new(name: &str, file_type: FileMode) -> Result<Self, Errno> {
+        let mut res = Self::from_str(name)?;
+        res.file_type = (file_type & FileMode::FILE_TYPE).bits();
+        Ok(res)
+    }
+
+    /// Returns the file type bits of the entry, if the filesystem
+    /// reported them
+    pub fn file_type(&self) -> Option<FileMode> {
+        FileMode::from_bits(self.file_type).filter(|mode| !mode.is_empty())
+    }
+
+    /// Returns `true` if the entry is known to be a directory
+    pub fn is_directory(&self) -> bool {
+        self.file_type() == Some(FileMode::S_IFDIR)
     }
 
     pub fn as_str(&self) -> &str {
@@ -150,12 +287,56 @@ impl DirectoryEntry {
     }
 }
 
+impl DirectoryEntryPlus {
+    pub const fn empty() -> Self {
+        Self {
+            entry: DirectoryEntry::empty(),
+            stat: Stat {
+                mode: FileMode::empty(),
+                size: 0,
+                blksize: 0,
+                rdev: 0,
+            },
+            has_stat: 0,
+        }
+    }
+
+    pub fn new(entry: DirectoryEntry, stat: Option<Stat>) -> Self {
+        Self {
+            entry,
+            has_stat: stat.is_some() as u32,
+            stat: stat.unwrap_or_default(),
+        }
+    }
+
+    pub fn entry(&self) -> &DirectoryEntry {
+        &self.entry
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.entry.as_str()
+    }
+
+    /// Returns the file status, if the filesystem could provide one
+    pub fn stat(&self) -> Option<&Stat> {
+        if self.has_stat != 0 {
+            Some(&self.stat)
+        } else {
+            None
+        }
+    }
+}
+
 impl FromStr for DirectoryEntry {
     type Err = Errno;
 
     fn from_str(i: &str) -> Result<Self, Errno> {
-        let mut res = DirectoryEntry { name: [0; 64] };
+        let mut res = DirectoryEntry::empty();
         let bytes = i.as_bytes();
+        // Has to be NUL-terminated
+        if bytes.len() >= res.name.len() {
+            return Err(Errno::InvalidArgument);
+        }
         res.name[..bytes.len()].copy_from_slice(bytes);
         Ok(res)
     }
@@ -165,6 +346,7 @@ impl fmt::Debug for DirectoryEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("DirectoryEntry")
             .field("name", &self.as_str())
+            .field("file_type", &self.file_type())
             .finish()
     }
 }
@@ -249,6 +431,17 @@ fn choose<T>(q: bool, a: T, b: T) -> T {
     if q { a } else { b }
 }
 
+/// Returns the execute column character: `special` if the special bit is
+/// set (uppercase if the execute bit is not), 'x' or '-' otherwise
+fn exec_char(exec: bool, special: bool, c: char) -> char {
+    match (special, exec) {
+        (true, true) => c,
+        (true, false) => c.to_ascii_uppercase(),
+        (false, true) => 'x',
+        (false, false) => '-',
+    }
+}
+
 impl Default for FileMode {
     fn default() -> Self {
         unsafe { Self::from_bits_unchecked(0) }
@@ -263,6 +456,8 @@ impl fmt::Display for FileMode {
             // File type
             match *self & Self::FILE_TYPE {
                 Self::S_IFCHR => 'c',
+                Self::S_IFBLK => 'b',
+                Self::S_IFIFO => 'p',
                 Self::S_IFDIR => 'd',
                 Self::S_IFREG => '-',
                 _ => '?'
@@ -270,19 +465,37 @@ impl fmt::Display for FileMode {
             // User
             choose(self.contains(Self::USER_READ), 'r', '-'),
             choose(self.contains(Self::USER_WRITE), 'w', '-'),
-            choose(self.contains(Self::USER_EXEC), 'x', '-'),
+            exec_char(self.contains(Self::USER_EXEC), self.contains(Self::SETUID), 's'),
             // Group
             choose(self.contains(Self::GROUP_READ), 'r', '-'),
             choose(self.contains(Self::GROUP_WRITE), 'w', '-'),
-            choose(self.contains(Self::GROUP_EXEC), 'x', '-'),
+            exec_char(self.contains(Self::GROUP_EXEC), self.contains(Self::SETGID), 's'),
             // Other
             choose(self.contains(Self::OTHER_READ), 'r', '-'),
             choose(self.contains(Self::OTHER_WRITE), 'w', '-'),
-            choose(self.contains(Self::OTHER_EXEC), 'x', '-'),
+            exec_char(self.contains(Self::OTHER_EXEC), self.contains(Self::STICKY), 't'),
         )
     }
 }
 
+/// Constructs a device number from its major and minor parts
+#[inline(always)]
+pub const fn makedev(major: u32, minor: u32) -> u32 {
+    (major << 20) | (minor & 0xFFFFF)
+}
+
+/// Returns the major part of device number `dev`
+#[inline(always)]
+pub const fn major(dev: u32) -> u32 {
+    dev >> 20
+}
+
+/// Returns the minor part of device number `dev`
+#[inline(always)]
+pub const fn minor(dev: u32) -> u32 {
+    dev & 0xFFFFF
+}
+
 impl FileDescriptor {
     pub const STDIN: Self = Self(0);
     pub const STDOUT: Self = Self(1);
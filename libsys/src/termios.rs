@@ -1,9 +1,56 @@
+use crate::error::Errno;
+use core::convert::TryFrom;
+
 bitflags! {
     pub struct TermiosIflag: u32 {
         /// Translate NL to CR on input
         const INLCR = 1 << 0;
         /// Translate CR to NL on input
         const ICRNL = 1 << 1;
+        /// Ignore break conditions
+        const IGNBRK = 1 << 2;
+        /// Break condition sends SIGINT to the foreground group (otherwise
+        /// it is read as a NUL byte)
+        const BRKINT = 1 << 3;
+    }
+
+    pub struct TermiosCflag: u32 {
+        /// Character size mask
+        const CSIZE = 3 << 0;
+        /// 5 data bits
+        const CS5 = 0 << 0;
+        /// 6 data bits
+        const CS6 = 1 << 0;
+        /// 7 data bits
+        const CS7 = 2 << 0;
+        /// 8 data bits
+        const CS8 = 3 << 0;
+        /// Two stop bits instead of one
+        const CSTOPB = 1 << 2;
+        /// Enable receiver
+        const CREAD = 1 << 3;
+        /// Enable parity generation and checking
+        const PARENB = 1 << 4;
+        /// Odd parity instead of even
+        const PARODD = 1 << 5;
+        /// Ignore modem control lines
+        const CLOCAL = 1 << 6;
+    }
+
+    /// Modem control and status lines
+    pub struct ModemLines: u32 {
+        /// Data Terminal Ready (output)
+        const DTR = 1 << 0;
+        /// Request To Send (output)
+        const RTS = 1 << 1;
+        /// Clear To Send (input)
+        const CTS = 1 << 2;
+        /// Data Set Ready (input)
+        const DSR = 1 << 3;
+        /// Data Carrier Detect (input)
+        const DCD = 1 << 4;
+        /// Ring Indicator (input)
+        const RI = 1 << 5;
     }
 
     pub struct TermiosOflag: u32 {
@@ -37,12 +84,35 @@ pub struct TermiosChars {
     pub werase: u8,
 }
 
+/// Queues discarded by [crate::ioctl::IoctlCmd::TtyFlush]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum FlushQueue {
+    /// Received data not yet read
+    Input = 0,
+    /// Written data not yet transmitted
+    Output = 1,
+    /// Both of the above
+    Both = 2,
+}
+
+/// Terminal dimensions, in characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+}
+
 #[derive(Debug, Clone)]
 #[repr(C)]
 pub struct Termios {
     pub iflag: TermiosIflag,
     pub oflag: TermiosOflag,
     pub lflag: TermiosLflag,
+    pub cflag: TermiosCflag,
+    /// Line speed, in bits per second
+    pub speed: u32,
     pub chars: TermiosChars
 }
 
@@ -59,6 +129,12 @@ impl TermiosChars {
     }
 }
 
+impl WindowSize {
+    pub const fn new() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
 impl Termios {
     pub const fn new() -> Self {
         Self {
@@ -70,6 +146,12 @@ impl Termios {
                     (1 << 0) | (1 << 1) | (1 << 2) | (1 << 3) | (1 << 4) | (1 << 5),
                 )
             },
+            cflag: unsafe {
+                TermiosCflag::from_bits_unchecked(
+                    TermiosCflag::CS8.bits() | TermiosCflag::CREAD.bits() | TermiosCflag::CLOCAL.bits(),
+                )
+            },
+            speed: 115200,
             chars: TermiosChars::new()
         }
     }
@@ -78,3 +160,16 @@ impl Termios {
         self.lflag.contains(TermiosLflag::ICANON)
     }
 }
+
+impl TryFrom<u32> for FlushQueue {
+    type Error = Errno;
+
+    fn try_from(raw: u32) -> Result<Self, Errno> {
+        match raw {
+            0 => Ok(Self::Input),
+            1 => Ok(Self::Output),
+            2 => Ok(Self::Both),
+            _ => Err(Errno::InvalidArgument),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use core::fmt;
+use enum_repr::EnumRepr;
+
+/// System clock selector
+#[EnumRepr(type = "usize")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockId {
+    /// Time since boot. Never jumps or gets adjusted and is used for
+    /// all kernel timeouts
+    Monotonic = 0,
+    /// Wall clock time since the Unix epoch. Can be set or slewed by
+    /// privileged processes
+    Realtime = 1,
+    /// Same as [ClockId::Monotonic], but also includes the time
+    /// the system spent suspended
+    Boottime = 2,
+}
+
+/// Broken-down UTC calendar time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u32,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Day of week, 0 is Sunday
+    pub weekday: u8,
+}
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+impl DateTime {
+    /// Returns `true` if `year` has February 29th
+    pub const fn is_leap_year(year: u32) -> bool {
+        (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+    }
+
+    /// Converts `secs` since the Unix epoch to calendar time
+    pub fn from_timestamp(secs: u64) -> Self {
+        let days = secs / SECONDS_PER_DAY;
+        let rem = secs % SECONDS_PER_DAY;
+
+        // Days are counted from 0000-03-01 so that the leap day ends the year
+        let days = days + 719468;
+        let era = days / 146097;
+        let doe = days % 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as u64;
+
+        Self {
+            year: year as u32,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+            // 1970-01-01 was a Thursday
+            weekday: ((secs / SECONDS_PER_DAY + 4) % 7) as u8,
+        }
+    }
+
+    /// Converts calendar time to seconds since the Unix epoch. `weekday` is
+    /// ignored.
+    pub fn timestamp(&self) -> u64 {
+        let (year, month) = if self.month <= 2 {
+            (self.year as u64 - 1, self.month as u64 + 9)
+        } else {
+            (self.year as u64, self.month as u64 - 3)
+        };
+        let era = year / 400;
+        let yoe = year % 400;
+        let doy = (153 * month + 2) / 5 + self.day as u64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+
+        days * SECONDS_PER_DAY
+            + self.hour as u64 * 3600
+            + self.minute as u64 * 60
+            + self.second as u64
+    }
+
+    /// Returns `true` if all the fields are in range and the time is not
+    /// before the Unix epoch
+    pub fn is_valid(&self) -> bool {
+        let days_in_month = match self.month {
+            2 if Self::is_leap_year(self.year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return false,
+        };
+        self.year >= 1970
+            && self.day >= 1
+            && self.day <= days_in_month
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
@@ -1,5 +1,5 @@
 use crate::trace;
-use alloc::vec::Vec;
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
 use libsys::{
     debug::TraceLevel,
     ProgramArgs,
@@ -11,11 +11,69 @@ mod shadow;
 pub use shadow::UserShadow;
 
 static mut PROGRAM_ARGS: Vec<&'static str> = Vec::new();
+// "NAME=value" entries
+static mut ENVIRONMENT: Vec<String> = Vec::new();
 
 pub fn args() -> &'static [&'static str] {
     unsafe { &PROGRAM_ARGS }
 }
 
+fn find_var(name: &str) -> Option<usize> {
+    unsafe {
+        ENVIRONMENT
+            .iter()
+            .position(|e| e.split_once('=').map_or(false, |(key, _)| key == name))
+    }
+}
+
+/// Returns the value of environment variable `name`
+pub fn var(name: &str) -> Option<String> {
+    find_var(name).map(|i| unsafe { ENVIRONMENT[i].split_once('=').unwrap().1.to_owned() })
+}
+
+/// Returns all the environment variables as (name, value) pairs
+pub fn vars() -> Vec<(String, String)> {
+    unsafe {
+        ENVIRONMENT
+            .iter()
+            .filter_map(|e| e.split_once('='))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+}
+
+/// Sets environment variable `name` for this process and the programs it
+/// executes
+pub fn set_var(name: &str, value: &str) {
+    assert!(!name.is_empty() && !name.contains('='));
+    let entry = name.to_owned() + "=" + value;
+    match find_var(name) {
+        Some(i) => unsafe { ENVIRONMENT[i] = entry },
+        None => unsafe { ENVIRONMENT.push(entry) },
+    }
+}
+
+/// Removes environment variable `name`
+pub fn remove_var(name: &str) {
+    if let Some(i) = find_var(name) {
+        unsafe {
+            ENVIRONMENT.remove(i);
+        }
+    }
+}
+
+/// Removes all the environment variables
+pub fn clear_vars() {
+    unsafe {
+        ENVIRONMENT.clear();
+    }
+}
+
+/// Returns the environment in the "NAME=value" form passed to execve()
+pub fn envp() -> Vec<String> {
+    unsafe { ENVIRONMENT.clone() }
+}
+
 pub(crate) unsafe fn setup_env(arg: &ProgramArgs) {
     for i in 0..arg.argc {
         let base = core::ptr::read((arg.argv + i * 16) as *const *const u8);
@@ -24,7 +82,22 @@ pub(crate) unsafe fn setup_env(arg: &ProgramArgs) {
         let string = core::str::from_utf8(core::slice::from_raw_parts(base, len)).unwrap();
         PROGRAM_ARGS.push(string);
     }
+    for i in 0..arg.envc {
+        let base = core::ptr::read((arg.envp + i * 16) as *const *const u8);
+        let len = core::ptr::read((arg.envp + i * 16 + 8) as *const usize);
+
+        let string = core::str::from_utf8(core::slice::from_raw_parts(base, len)).unwrap();
+        ENVIRONMENT.push(string.to_owned());
+    }
 
     #[cfg(feature = "verbose")]
     trace!(TraceLevel::Debug, "args = {:?}", PROGRAM_ARGS);
 }
+
+/// Search path used when there is no `PATH` in the environment
+pub const DEFAULT_PATH: &str = "/bin:/sbin";
+
+/// Returns the colon-separated list of directories searched for commands
+pub fn search_path() -> String {
+    var("PATH").unwrap_or_else(|| DEFAULT_PATH.to_owned())
+}
@@ -1,6 +1,8 @@
-use crate::io::{AsRawFd, Error, Read};
+use crate::io::{AsRawFd, Error, FromRawFd, Read, Write};
+use core::fmt;
 use libsys::{
-    calls::{sys_openat, sys_read, sys_close},
+    calls::{sys_close, sys_fsync, sys_ftruncate, sys_openat, sys_read, sys_write},
+    error::Errno,
     stat::{FileDescriptor, FileMode, OpenFlags},
 };
 
@@ -14,6 +16,28 @@ impl File {
             .map_err(Error::from)?;
         Ok(File { fd })
     }
+
+    pub fn create(path: &str) -> Result<File, Error> {
+        let fd = sys_openat(
+            None,
+            path,
+            FileMode::default_reg(),
+            OpenFlags::O_WRONLY | OpenFlags::O_CREAT,
+        )
+        .map_err(Error::from)?;
+        Ok(File { fd })
+    }
+
+    /// Truncates or extends the file to `size` bytes. The file has to be
+    /// opened for writing.
+    pub fn set_len(&self, size: usize) -> Result<(), Error> {
+        sys_ftruncate(self.fd, size).map_err(Error::from)
+    }
+
+    /// Writes all the cached modifications of the file to its storage
+    pub fn sync_all(&self) -> Result<(), Error> {
+        sys_fsync(self.fd).map_err(Error::from)
+    }
 }
 
 impl AsRawFd for File {
@@ -22,6 +46,12 @@ impl AsRawFd for File {
     }
 }
 
+impl FromRawFd for File {
+    unsafe fn from_raw_fd(fd: FileDescriptor) -> Self {
+        Self { fd }
+    }
+}
+
 impl Drop for File {
     fn drop(&mut self) {
         sys_close(self.fd).ok();
@@ -33,3 +63,26 @@ impl Read for File {
         sys_read(self.fd, bytes).map_err(Error::from)
     }
 }
+
+impl fmt::Write for File {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut data = s.as_bytes();
+        while !data.is_empty() {
+            match sys_write(self.fd, data) {
+                Ok(0) | Err(_) => return Err(fmt::Error),
+                Ok(count) => data = &data[count..],
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Error> {
+        sys_write(self.fd, bytes).map_err(Error::from)
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), Error> {
+        fmt::Write::write_fmt(self, args).map_err(|_| Error::from(Errno::DeviceError))
+    }
+}
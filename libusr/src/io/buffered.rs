@@ -0,0 +1,125 @@
+use crate::io::{Error, ErrorKind, Read};
+use alloc::{string::String, vec, vec::Vec};
+
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// Reader which fetches data from the underlying reader in large chunks
+pub struct BufReader<R: Read> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the buffered data, reading more if the buffer is empty. An
+    /// empty slice means end of input.
+    pub fn fill_buf(&mut self) -> Result<&[u8], Error> {
+        if self.pos == self.len {
+            self.len = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    /// Marks `amount` bytes of the buffered data as consumed
+    pub fn consume(&mut self, amount: usize) {
+        self.pos = core::cmp::min(self.pos + amount, self.len);
+    }
+
+    /// Reads bytes up to and including `delim` into `out`. Returns the
+    /// number of bytes read, zero at end of input.
+    pub fn read_until(&mut self, delim: u8, out: &mut Vec<u8>) -> Result<usize, Error> {
+        let mut total = 0;
+        loop {
+            let data = self.fill_buf()?;
+            if data.is_empty() {
+                return Ok(total);
+            }
+            let (count, done) = match data.iter().position(|&b| b == delim) {
+                Some(index) => (index + 1, true),
+                None => (data.len(), false),
+            };
+            out.extend_from_slice(&data[..count]);
+            self.consume(count);
+            total += count;
+            if done {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Reads a line, including the trailing newline, and appends it to
+    /// `out`. Returns the number of bytes read, zero at end of input.
+    pub fn read_line(&mut self, out: &mut String) -> Result<usize, Error> {
+        let mut bytes = Vec::new();
+        let count = self.read_until(b'\n', &mut bytes)?;
+        let line = core::str::from_utf8(&bytes).map_err(|_| Error::new(ErrorKind::InvalidData))?;
+        out.push_str(line);
+        Ok(count)
+    }
+
+    /// Returns an iterator over the lines of the input, without their
+    /// trailing newlines
+    pub fn lines(self) -> Lines<R> {
+        Lines { reader: self }
+    }
+}
+
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, bytes: &mut [u8]) -> Result<usize, Error> {
+        // Large reads bypass the buffer when it's empty
+        if self.pos == self.len && bytes.len() >= self.buf.len() {
+            return self.inner.read(bytes);
+        }
+        let data = self.fill_buf()?;
+        let count = core::cmp::min(data.len(), bytes.len());
+        bytes[..count].copy_from_slice(&data[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+/// Iterator over the lines of a [BufReader]
+pub struct Lines<R: Read> {
+    reader: BufReader<R>,
+}
+
+impl<R: Read> Iterator for Lines<R> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
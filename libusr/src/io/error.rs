@@ -2,7 +2,6 @@ use libsys::error::Errno;
 
 #[derive(Debug)]
 pub struct Error {
-    #[allow(dead_code)]
     repr: Repr,
 }
 
@@ -10,7 +9,15 @@ pub struct Error {
 pub enum ErrorKind {
     NotFound,
     PermissionDenied,
+    AlreadyExists,
+    BrokenPipe,
+    WouldBlock,
+    TimedOut,
+    /// The operation was interrupted by a signal and may be retried
+    Interrupted,
+    UnexpectedEof,
     InvalidData,
+    Other,
 }
 
 #[derive(Debug)]
@@ -25,6 +32,39 @@ impl Error {
             repr: Repr::Simple(kind),
         }
     }
+
+    /// Returns the kind of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self.repr {
+            Repr::Os(e) => ErrorKind::from(e),
+            Repr::Simple(kind) => kind,
+        }
+    }
+
+    /// Returns the system error code, if the error originates from a
+    /// system call
+    pub fn raw_os_error(&self) -> Option<Errno> {
+        match self.repr {
+            Repr::Os(e) => Some(e),
+            Repr::Simple(_) => None,
+        }
+    }
+}
+
+impl From<Errno> for ErrorKind {
+    fn from(e: Errno) -> Self {
+        match e {
+            Errno::DoesNotExist => Self::NotFound,
+            Errno::PermissionDenied => Self::PermissionDenied,
+            Errno::AlreadyExists => Self::AlreadyExists,
+            Errno::BrokenPipe => Self::BrokenPipe,
+            Errno::WouldBlock => Self::WouldBlock,
+            Errno::TimedOut => Self::TimedOut,
+            Errno::Interrupt => Self::Interrupted,
+            Errno::EndOfFile => Self::UnexpectedEof,
+            _ => Self::Other,
+        }
+    }
 }
 
 impl From<Errno> for Error {
@@ -1,39 +1,141 @@
 use libsys::{
-    calls::{sys_fstatat, sys_ioctl},
+    calls::{sys_fstatat, sys_ioctl_get, sys_ioctl_set},
     stat::{FileDescriptor, Stat},
     ioctl::IoctlCmd,
     error::Errno,
-    proc::Pid
+    proc::Pid,
+    termios::{FlushQueue, Termios, TermiosIflag, TermiosLflag, WindowSize},
 };
-use core::mem::size_of;
 use core::fmt;
 
 mod error;
 pub use error::{Error, ErrorKind};
+mod buffered;
+pub use buffered::{BufReader, Lines};
 mod writer;
 pub use writer::{_print};
 mod stdio;
 pub use stdio::{stderr, stdin, stdout, Stderr, Stdin, Stdout};
+mod retry;
+pub use retry::{retry, Retry};
 
 pub trait Read {
     fn read(&mut self, bytes: &mut [u8]) -> Result<usize, Error>;
+
+    /// Reads exactly `bytes.len()` bytes, restarting reads interrupted
+    /// by signals
+    fn read_exact(&mut self, mut bytes: &mut [u8]) -> Result<(), Error> {
+        while !bytes.is_empty() {
+            match retry(|| self.read(bytes))? {
+                0 => return Err(Error::new(ErrorKind::UnexpectedEof)),
+                count => bytes = &mut bytes[count..],
+            }
+        }
+        Ok(())
+    }
 }
 
 pub trait Write {
     fn write(&mut self, bytes: &[u8]) -> Result<usize, Error>;
     fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), Error>;
+
+    /// Writes all of `bytes`, restarting writes interrupted by signals
+    fn write_all(&mut self, mut bytes: &[u8]) -> Result<(), Error> {
+        while !bytes.is_empty() {
+            match retry(|| self.write(bytes))? {
+                0 => return Err(Errno::NoSpace.into()),
+                count => bytes = &bytes[count..],
+            }
+        }
+        Ok(())
+    }
 }
 
 pub trait AsRawFd {
     fn as_raw_fd(&self) -> FileDescriptor;
 }
 
-pub fn tcgetpgrp(_fd: FileDescriptor) -> Result<Pid, Errno> {
-    todo!()
+pub trait FromRawFd {
+    /// Constructs the object from an open file descriptor, taking
+    /// ownership of it.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be open and not owned by anything else.
+    unsafe fn from_raw_fd(fd: FileDescriptor) -> Self;
+}
+
+pub fn tcgetpgrp(fd: FileDescriptor) -> Result<Pid, Errno> {
+    let mut pgid = 0u32;
+    sys_ioctl_get(fd, IoctlCmd::TtyGetPgrp, &mut pgid)?;
+    Pid::try_from(pgid)
 }
 
 pub fn tcsetpgrp(fd: FileDescriptor, pgid: Pid) -> Result<(), Errno> {
-    sys_ioctl(fd, IoctlCmd::TtySetPgrp, &pgid as *const _ as usize, size_of::<Pid>()).map(|_| ())
+    sys_ioctl_set(fd, IoctlCmd::TtySetPgrp, &u32::from(pgid))
+}
+
+pub fn tcgetattr(fd: FileDescriptor) -> Result<Termios, Errno> {
+    let mut termios = Termios::new();
+    sys_ioctl_get(fd, IoctlCmd::TtyGetAttributes, &mut termios)?;
+    Ok(termios)
+}
+
+pub fn tcsetattr(fd: FileDescriptor, termios: &Termios) -> Result<(), Errno> {
+    sys_ioctl_set(fd, IoctlCmd::TtySetAttributes, termios)
+}
+
+pub fn tcgetwinsize(fd: FileDescriptor) -> Result<WindowSize, Errno> {
+    let mut winsize = WindowSize::new();
+    sys_ioctl_get(fd, IoctlCmd::TtyGetWindowSize, &mut winsize)?;
+    Ok(winsize)
+}
+
+pub fn tcsetwinsize(fd: FileDescriptor, winsize: &WindowSize) -> Result<(), Errno> {
+    sys_ioctl_set(fd, IoctlCmd::TtySetWindowSize, winsize)
+}
+
+/// Returns `true` if `fd` refers to a terminal
+pub fn isatty(fd: FileDescriptor) -> bool {
+    tcgetwinsize(fd).is_ok()
+}
+
+pub fn tcdrain(fd: FileDescriptor) -> Result<(), Errno> {
+    sys_ioctl_set(fd, IoctlCmd::TtyDrain, &())
+}
+
+pub fn tcflush(fd: FileDescriptor, queue: FlushQueue) -> Result<(), Errno> {
+    sys_ioctl_set(fd, IoctlCmd::TtyFlush, &(queue as u32))
+}
+
+/// Puts a terminal into raw mode: input is not echoed and is returned
+/// byte by byte, special characters lose their meaning. The previous mode
+/// is restored when the value is dropped.
+pub struct RawMode {
+    fd: FileDescriptor,
+    saved: Termios,
+}
+
+impl RawMode {
+    pub fn enter(fd: FileDescriptor) -> Result<Self, Errno> {
+        let saved = tcgetattr(fd)?;
+        let mut raw = saved.clone();
+        raw.iflag &= !(TermiosIflag::ICRNL | TermiosIflag::INLCR | TermiosIflag::BRKINT);
+        raw.lflag &= !(TermiosLflag::ICANON
+            | TermiosLflag::ECHO
+            | TermiosLflag::ECHOE
+            | TermiosLflag::ECHOK
+            | TermiosLflag::ECHONL
+            | TermiosLflag::ISIG);
+        tcsetattr(fd, &raw)?;
+        Ok(Self { fd, saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        tcsetattr(self.fd, &self.saved).ok();
+    }
 }
 
 pub fn stat(pathname: &str) -> Result<Stat, Error> {
@@ -0,0 +1,102 @@
+use crate::io::{AsRawFd, Error, ErrorKind, Read, Write};
+use core::fmt;
+use libsys::stat::FileDescriptor;
+
+/// Wraps a reader or writer so that operations interrupted by a signal
+/// ([ErrorKind::Interrupted]) are transparently restarted
+pub struct Retry<T> {
+    inner: T,
+    enabled: bool,
+}
+
+/// Calls `f` until it completes with anything other than an
+/// [ErrorKind::Interrupted] error
+pub fn retry<T, F: FnMut() -> Result<T, Error>>(mut f: F) -> Result<T, Error> {
+    loop {
+        match f() {
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            res => return res,
+        }
+    }
+}
+
+impl<T> Retry<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            enabled: true,
+        }
+    }
+
+    /// Sets whether interrupted operations are restarted. When disabled,
+    /// [ErrorKind::Interrupted] errors are returned to the caller.
+    pub fn set_retry(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for Retry<T> {
+    fn read(&mut self, bytes: &mut [u8]) -> Result<usize, Error> {
+        if self.enabled {
+            retry(|| self.inner.read(bytes))
+        } else {
+            self.inner.read(bytes)
+        }
+    }
+}
+
+impl<T: Write> Write for Retry<T> {
+    fn write(&mut self, bytes: &[u8]) -> Result<usize, Error> {
+        if self.enabled {
+            retry(|| self.inner.write(bytes))
+        } else {
+            self.inner.write(bytes)
+        }
+    }
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> Result<(), Error> {
+        // Format through write_all so that each chunk is retried on its
+        // own instead of restarting the whole formatted output
+        struct Adapter<'a, T: Write> {
+            inner: &'a mut Retry<T>,
+            error: Option<Error>,
+        }
+
+        impl<T: Write> fmt::Write for Adapter<'_, T> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.inner.write_all(s.as_bytes()).map_err(|e| {
+                    self.error = Some(e);
+                    fmt::Error
+                })
+            }
+        }
+
+        let mut adapter = Adapter {
+            inner: self,
+            error: None,
+        };
+        fmt::Write::write_fmt(&mut adapter, args).map_err(|_| {
+            adapter
+                .error
+                .unwrap_or_else(|| Error::new(ErrorKind::Other))
+        })
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for Retry<T> {
+    fn as_raw_fd(&self) -> FileDescriptor {
+        self.inner.as_raw_fd()
+    }
+}
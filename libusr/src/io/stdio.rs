@@ -113,17 +113,15 @@ impl Write for Stderr {
 //     }
 // }
 
-lazy_static! {
-    static ref STDIN: Mutex<InputInner> = Mutex::new(InputInner {
-        fd: FileDescriptor::STDIN
-    });
-    static ref STDOUT: Mutex<OutputInner> = Mutex::new(OutputInner {
-        fd: FileDescriptor::STDOUT
-    });
-    static ref STDERR: Mutex<OutputInner> = Mutex::new(OutputInner {
-        fd: FileDescriptor::STDOUT
-    });
-}
+static STDIN: Mutex<InputInner> = Mutex::new(InputInner {
+    fd: FileDescriptor::STDIN,
+});
+static STDOUT: Mutex<OutputInner> = Mutex::new(OutputInner {
+    fd: FileDescriptor::STDOUT,
+});
+static STDERR: Mutex<OutputInner> = Mutex::new(OutputInner {
+    fd: FileDescriptor::STDOUT,
+});
 
 pub fn stdin() -> Stdin {
     Stdin { inner: &STDIN }
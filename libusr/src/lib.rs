@@ -1,23 +1,24 @@
 #![feature(asm, alloc_error_handler)]
 #![no_std]
 
-#[macro_use]
-extern crate lazy_static;
-
 extern crate alloc;
 
 use core::panic::PanicInfo;
-use libsys::{debug::TraceLevel, ProgramArgs, proc::ExitCode};
+use libsys::{abi::ABI_VERSION, debug::TraceLevel, ProgramArgs, proc::ExitCode};
 
 mod allocator;
 pub mod env;
 pub mod file;
 pub mod io;
 pub mod os;
+pub mod process;
+pub mod readline;
 pub mod sys;
 pub mod sync;
 pub mod thread;
 pub mod signal;
+pub mod sntp;
+pub mod utmp;
 
 #[link_section = ".text._start"]
 #[no_mangle]
@@ -26,6 +27,17 @@ extern "C" fn _start(arg: &'static ProgramArgs) -> ! {
         fn main() -> i32;
     }
 
+    // Structure layouts may differ, refuse to run rather than misread them
+    if arg.abi_version() != Some(ABI_VERSION) {
+        trace!(
+            TraceLevel::Error,
+            "ABI version mismatch: kernel {:?}, program {}",
+            arg.abi_version(),
+            ABI_VERSION
+        );
+        sys::sys_exit(ExitCode::from(-1));
+    }
+
     unsafe {
         allocator::init();
         thread::init_main();
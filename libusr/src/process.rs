@@ -0,0 +1,102 @@
+//! Program execution helpers: command lookup through the search path and
+//! fallback to the shell for scripts the kernel can't execute on its own.
+//! Also lists the processes running in the system.
+use crate::env;
+use alloc::{string::String, vec, vec::Vec};
+use libsys::{
+    calls::{sys_ex_get_name, sys_ex_process_list, sys_execve, sys_faccessat},
+    error::Errno,
+    proc::{NameTarget, Pid, ProcessName, PROCESS_NAME_MAX},
+    stat::AccessMode,
+};
+
+/// Interpreter used for executables without a recognized format
+pub const SHELL: &str = "/bin/shell";
+
+fn is_executable(path: &str) -> bool {
+    sys_faccessat(None, path, AccessMode::X_OK, 0).is_ok()
+}
+
+/// Finds the executable to run for `cmd`. Names containing a slash are
+/// used as given, others are looked up in each directory of `path`.
+pub fn resolve_in(cmd: &str, path: &str) -> Result<String, Errno> {
+    if cmd.contains('/') {
+        return if is_executable(cmd) {
+            Ok(cmd.into())
+        } else {
+            Err(Errno::DoesNotExist)
+        };
+    }
+
+    for dir in path.split(':').filter(|dir| !dir.is_empty()) {
+        let mut candidate = String::from(dir);
+        if !candidate.ends_with('/') {
+            candidate.push('/');
+        }
+        candidate.push_str(cmd);
+        if is_executable(&candidate) {
+            return Ok(candidate);
+        }
+    }
+    Err(Errno::DoesNotExist)
+}
+
+/// Same as [resolve_in], using the current search path
+pub fn resolve(cmd: &str) -> Result<String, Errno> {
+    resolve_in(cmd, &env::search_path())
+}
+
+/// Executes the program at `pathname` with the current environment. Files
+/// the kernel rejects as [Errno::BadExecutable] are run as scripts by
+/// [SHELL]. Only returns on failure.
+pub fn exec(pathname: &str, argv: &[&str]) -> Errno {
+    let envp = env::envp();
+    let envp: Vec<&str> = envp.iter().map(String::as_str).collect();
+
+    match sys_execve(pathname, argv, &envp) {
+        Err(Errno::BadExecutable) => (),
+        Err(err) => return err,
+        Ok(()) => unreachable!(),
+    }
+
+    let mut args = Vec::with_capacity(argv.len() + 1);
+    args.push(SHELL);
+    args.push(pathname);
+    args.extend_from_slice(argv.get(1..).unwrap_or(&[]));
+    match sys_execve(SHELL, &args, &envp) {
+        Err(err) => err,
+        Ok(()) => unreachable!(),
+    }
+}
+
+/// Looks `argv[0]` up in the search path and executes it, see [exec].
+/// Only returns on failure.
+pub fn execvp(argv: &[&str]) -> Errno {
+    match argv.first().map(|cmd| resolve(cmd)) {
+        Some(Ok(pathname)) => exec(&pathname, argv),
+        Some(Err(err)) => err,
+        None => Errno::InvalidArgument,
+    }
+}
+
+/// Returns the IDs of all the processes in the system
+pub fn list() -> Result<Vec<Pid>, Errno> {
+    let mut pids = vec![Pid::user(1); 32];
+    loop {
+        let count = sys_ex_process_list(&mut pids)?;
+        if count <= pids.len() {
+            pids.truncate(count);
+            return Ok(pids);
+        }
+        // Processes may have been created in between, leave some room
+        pids.resize(count + 8, Pid::user(1));
+    }
+}
+
+/// Returns the name of process `pid`
+pub fn name(pid: Pid) -> Result<ProcessName, Errno> {
+    let mut buf = [0; PROCESS_NAME_MAX];
+    let len = sys_ex_get_name(NameTarget::Process, u32::from(pid), &mut buf)?;
+    let name = core::str::from_utf8(&buf[..len]).map_err(|_| Errno::InvalidArgument)?;
+    Ok(ProcessName::from_str_truncated(name))
+}
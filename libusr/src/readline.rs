@@ -0,0 +1,296 @@
+//! Interactive line editing: cursor movement, history and tab completion
+//! of command and file names. Falls back to reading plain lines when the
+//! input is not a terminal.
+use crate::env;
+use crate::io::{self, RawMode, Read, Write};
+use alloc::{string::String, vec::Vec};
+use libsys::{
+    calls::{sys_close, sys_openat, sys_readdir_plus},
+    stat::{DirectoryEntryPlus, FileDescriptor, FileMode, OpenFlags},
+};
+
+const MAX_HISTORY: usize = 64;
+
+const CTRL_A: u8 = 0x01;
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const CTRL_U: u8 = 0x15;
+const BACKSPACE: u8 = 0x7F;
+
+/// Line editor state kept between lines
+#[derive(Default)]
+pub struct LineEditor {
+    history: Vec<String>,
+}
+
+struct Line<'a> {
+    prompt: &'a str,
+    chars: Vec<char>,
+    cursor: usize,
+    cols: usize,
+}
+
+fn read_byte<F: Read>(input: &mut F) -> Result<Option<u8>, io::Error> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Returns the names in `dir` starting with `prefix`, marking directories
+/// with a trailing slash
+fn list_matches(dir: &str, prefix: &str, out: &mut Vec<String>) {
+    let fd = match sys_openat(
+        None,
+        dir,
+        FileMode::default_dir(),
+        OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY,
+    ) {
+        Ok(fd) => fd,
+        Err(_) => return,
+    };
+    let mut buffer = [DirectoryEntryPlus::empty(); 8];
+    while let Ok(count) = sys_readdir_plus(fd, &mut buffer) {
+        if count == 0 {
+            break;
+        }
+        for item in &buffer[..count] {
+            let name = item.as_str();
+            if name == "." || name == ".." || !name.starts_with(prefix) {
+                continue;
+            }
+            let mut name = String::from(name);
+            if let Some(stat) = item.stat() {
+                if stat.mode & FileMode::FILE_TYPE == FileMode::S_IFDIR {
+                    name.push('/');
+                }
+            }
+            if !out.contains(&name) {
+                out.push(name);
+            }
+        }
+    }
+    sys_close(fd).ok();
+}
+
+fn common_prefix(names: &[String]) -> &str {
+    let first = names[0].as_str();
+    let mut len = first.len();
+    for name in &names[1..] {
+        len = first[..len]
+            .char_indices()
+            .zip(name.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(name.len()), |((i, _), _)| i);
+    }
+    &first[..len]
+}
+
+impl<'a> Line<'a> {
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Redraws the line, scrolling it horizontally to keep the cursor
+    /// visible
+    fn draw(&self) -> Result<(), io::Error> {
+        let prompt_len = self.prompt.chars().count();
+        let width = self.cols.saturating_sub(prompt_len + 1).max(1);
+        let start = self.cursor.saturating_sub(width - 1);
+        let end = (start + width).min(self.chars.len());
+
+        let mut out = String::from("\r");
+        out.push_str(self.prompt);
+        out.extend(&self.chars[start..end]);
+        out.push_str("\x1B[K");
+        let back = end - self.cursor;
+        if back != 0 {
+            out.push_str(&alloc::format!("\x1B[{}D", back));
+        }
+        io::stdout().write(out.as_bytes())?;
+        Ok(())
+    }
+
+    fn insert(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.chars.insert(self.cursor, ch);
+            self.cursor += 1;
+        }
+    }
+}
+
+impl LineEditor {
+    /// Creates an editor which completes command names from the
+    /// directories in the search path
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        if self.history.len() == MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(line.into());
+    }
+
+    fn complete(&self, line: &mut Line) -> Result<(), io::Error> {
+        let before: String = line.chars[..line.cursor].iter().collect();
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let is_command = before[..start].trim().is_empty() && !word.contains('/');
+
+        let mut matches = Vec::new();
+        let prefix = if is_command {
+            let path = env::search_path();
+            for dir in path.split(':').filter(|dir| !dir.is_empty()) {
+                list_matches(dir, word, &mut matches);
+            }
+            word
+        } else {
+            let (dir, prefix) = match word.rfind('/') {
+                Some(0) => ("/", &word[1..]),
+                Some(i) => (&word[..i], &word[i + 1..]),
+                None => (".", word),
+            };
+            list_matches(dir, prefix, &mut matches);
+            prefix
+        };
+
+        match matches.len() {
+            0 => (),
+            1 => {
+                line.insert(&matches[0][prefix.len()..]);
+                if !matches[0].ends_with('/') {
+                    line.insert(" ");
+                }
+            }
+            _ => {
+                let common = common_prefix(&matches);
+                if common.len() > prefix.len() {
+                    line.insert(&common[prefix.len()..]);
+                } else {
+                    matches.sort();
+                    let mut out = String::from("\r\n");
+                    for name in &matches {
+                        out.push_str(name);
+                        out.push_str("  ");
+                    }
+                    out.push_str("\r\n");
+                    io::stdout().write(out.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn edit(&mut self, prompt: &str) -> Result<Option<String>, io::Error> {
+        let mut input = io::stdin();
+        let cols = io::tcgetwinsize(FileDescriptor::STDOUT).map_or(80, |size| size.cols as usize);
+        let mut line = Line {
+            prompt,
+            chars: Vec::new(),
+            cursor: 0,
+            cols,
+        };
+        // Position in history, the line being edited is kept aside while
+        // browsing it
+        let mut index = self.history.len();
+        let mut edited = String::new();
+
+        loop {
+            line.draw()?;
+            let byte = match read_byte(&mut input)? {
+                Some(byte) => byte,
+                None => return Ok(None),
+            };
+
+            match byte {
+                b'\r' | b'\n' => break,
+                CTRL_D if line.chars.is_empty() => return Ok(None),
+                CTRL_C => {
+                    io::stdout().write(b"^C\r\n")?;
+                    line.set("");
+                    index = self.history.len();
+                }
+                CTRL_A => line.cursor = 0,
+                CTRL_E => line.cursor = line.chars.len(),
+                CTRL_U => {
+                    line.chars.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                BACKSPACE | 0x08 if line.cursor > 0 => {
+                    line.cursor -= 1;
+                    line.chars.remove(line.cursor);
+                }
+                b'\t' => self.complete(&mut line)?,
+                0x1B => {
+                    if read_byte(&mut input)? != Some(b'[') {
+                        continue;
+                    }
+                    match read_byte(&mut input)? {
+                        Some(b'A') if index > 0 => {
+                            if index == self.history.len() {
+                                edited = line.text();
+                            }
+                            index -= 1;
+                            line.set(&self.history[index]);
+                        }
+                        Some(b'B') if index < self.history.len() => {
+                            index += 1;
+                            match self.history.get(index) {
+                                Some(entry) => line.set(entry),
+                                None => line.set(&edited),
+                            }
+                        }
+                        Some(b'C') if line.cursor < line.chars.len() => line.cursor += 1,
+                        Some(b'D') if line.cursor > 0 => line.cursor -= 1,
+                        Some(b'H') => line.cursor = 0,
+                        Some(b'F') => line.cursor = line.chars.len(),
+                        Some(b'3') => {
+                            // Delete, followed by '~'
+                            read_byte(&mut input)?;
+                            if line.cursor < line.chars.len() {
+                                line.chars.remove(line.cursor);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                0x20..=0x7E => line.insert(core::str::from_utf8(&[byte]).unwrap()),
+                _ => (),
+            }
+        }
+
+        io::stdout().write(b"\r\n")?;
+        let text = line.text();
+        self.add_history(&text);
+        Ok(Some(text))
+    }
+
+    /// Prints `prompt` and reads a line of input. Returns `None` at end
+    /// of input.
+    pub fn read_line(&mut self, prompt: &str) -> Result<Option<String>, io::Error> {
+        match RawMode::enter(FileDescriptor::STDIN) {
+            Ok(_raw) => self.edit(prompt),
+            Err(_) => {
+                // Not a terminal
+                io::stdout().write(prompt.as_bytes())?;
+                let mut buf = [0; 256];
+                match io::read_line(&mut io::stdin(), &mut buf) {
+                    Ok(line) => Ok(line.map(String::from)),
+                    Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData)),
+                }
+            }
+        }
+    }
+}
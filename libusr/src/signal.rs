@@ -14,7 +14,12 @@ pub enum SignalHandler {
 }
 
 // TODO per-thread signal handler table
-static mut SIGNAL_HANDLERS: [SignalHandler; 32] = [SignalHandler::Terminate; 32];
+static mut SIGNAL_HANDLERS: [SignalHandler; 32] = {
+    let mut handlers = [SignalHandler::Terminate; 32];
+    // Only programs interested in terminal size changes handle it
+    handlers[Signal::WindowChange as usize] = SignalHandler::Ignore;
+    handlers
+};
 
 pub fn set_handler(sig: Signal, handler: SignalHandler) -> SignalHandler {
     unsafe {
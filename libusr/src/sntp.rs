@@ -0,0 +1,112 @@
+//! Simple Network Time Protocol (RFC 4330) client side.
+//!
+//! Only the packet format and the clock discipline live here: the
+//! transport (UDP port 123 of the configured server) is up to the caller.
+
+use crate::sys::{self, time::ClockId, Errno};
+use core::time::Duration;
+
+/// Default NTP server port
+pub const PORT: u16 = 123;
+/// Size of an SNTP packet without authentication fields
+pub const PACKET_SIZE: usize = 48;
+/// Offsets smaller than this are slewed, larger ones step the clock
+pub const STEP_THRESHOLD: Duration = Duration::from_millis(128);
+
+// Seconds between 1900-01-01 (NTP era 0) and 1970-01-01
+const UNIX_EPOCH_OFFSET: u64 = 2208988800;
+
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+const VERSION: u8 = 4;
+
+/// Result of a single request-response exchange
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    /// Signed difference between server and local wall clock, in nanoseconds
+    pub offset: i64,
+    /// Round-trip delay, excluding server processing time
+    pub delay: Duration,
+}
+
+fn put_timestamp(buf: &mut [u8], time: Duration) {
+    let secs = (time.as_secs() + UNIX_EPOCH_OFFSET) as u32;
+    let frac = ((time.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    buf[0..4].copy_from_slice(&secs.to_be_bytes());
+    buf[4..8].copy_from_slice(&(frac as u32).to_be_bytes());
+}
+
+fn get_timestamp(buf: &[u8]) -> Duration {
+    let secs = u32::from_be_bytes(buf[0..4].try_into().unwrap()) as u64;
+    let frac = u32::from_be_bytes(buf[4..8].try_into().unwrap()) as u64;
+    // Timestamps before 1970 are only valid in the next NTP era (2036+)
+    let secs = if secs < UNIX_EPOCH_OFFSET {
+        secs + (1 << 32) - UNIX_EPOCH_OFFSET
+    } else {
+        secs - UNIX_EPOCH_OFFSET
+    };
+    let nanos = (frac * 1_000_000_000) >> 32;
+    Duration::new(secs, nanos as u32)
+}
+
+fn nanos(time: Duration) -> i64 {
+    time.as_nanos() as i64
+}
+
+/// Fills `buf` with a client request. `transmit` is the local wall
+/// clock time, which has to be passed to [parse_response] later.
+pub fn build_request(buf: &mut [u8; PACKET_SIZE], transmit: Duration) {
+    buf.fill(0);
+    buf[0] = (VERSION << 3) | MODE_CLIENT;
+    put_timestamp(&mut buf[40..48], transmit);
+}
+
+/// Validates a server response to a request sent at `transmit` and
+/// received at `receive` (both local wall clock time)
+pub fn parse_response(
+    buf: &[u8],
+    transmit: Duration,
+    receive: Duration,
+) -> Result<Sample, Errno> {
+    if buf.len() < PACKET_SIZE {
+        return Err(Errno::InvalidArgument);
+    }
+    let leap = buf[0] >> 6;
+    let mode = buf[0] & 7;
+    let stratum = buf[1];
+    // Leap indicator 3 means the server clock is not synchronized,
+    // stratum 0 is a "kiss-of-death" reply
+    if mode != MODE_SERVER || leap == 3 || stratum == 0 {
+        return Err(Errno::InvalidArgument);
+    }
+    // The server echoes our transmit timestamp as its originate one
+    let mut expected = [0; 8];
+    put_timestamp(&mut expected, transmit);
+    if buf[24..32] != expected {
+        return Err(Errno::InvalidArgument);
+    }
+    let t1 = nanos(transmit);
+    let t2 = nanos(get_timestamp(&buf[32..40]));
+    let t3 = nanos(get_timestamp(&buf[40..48]));
+    let t4 = nanos(receive);
+
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    let delay = (t4 - t1) - (t3 - t2);
+
+    Ok(Sample {
+        offset,
+        delay: Duration::from_nanos(delay.max(0) as u64),
+    })
+}
+
+/// Corrects the wall clock by `sample.offset`: small offsets are slewed
+/// to keep the clock monotonic, large ones step it
+pub fn apply(sample: &Sample) -> Result<(), Errno> {
+    if sample.offset.unsigned_abs() < STEP_THRESHOLD.as_nanos() as u64 {
+        sys::sys_ex_adjtime(Some(sample.offset), None)
+    } else {
+        let now = nanos(sys::sys_ex_clock_get(ClockId::Realtime)?);
+        let value = (now + sample.offset).max(0) as u64;
+        sys::sys_ex_clock_set(ClockId::Realtime, Duration::from_nanos(value))
+    }
+}
@@ -1,6 +1,10 @@
+use core::any::type_name;
 use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
 use core::ops::{Deref, DerefMut};
-use crate::sys::RawMutex;
+use core::panic::Location;
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::sys::{sys_ex_yield, RawMutex};
 
 pub struct Mutex<T> {
     inner: RawMutex,
@@ -13,7 +17,7 @@ pub struct MutexGuard<'a, T> {
 }
 
 impl<T> Mutex<T> {
-    pub fn new(t: T) -> Self {
+    pub const fn new(t: T) -> Self {
         Self {
             inner: RawMutex::new(),
             data: UnsafeCell::new(t)
@@ -52,3 +56,148 @@ impl<'a, T> DerefMut for MutexGuard<'a, T> {
 }
 
 unsafe impl<T> Sync for Mutex<T> {}
+
+const UNINIT: u8 = 0;
+const BUSY: u8 = 1;
+const READY: u8 = 2;
+const FAILED: u8 = 3;
+
+/// Cell which can be written to only once. Remembers where it was
+/// initialized from, so misuse is reported with that location.
+pub struct OnceCell<T> {
+    state: AtomicU8,
+    location: UnsafeCell<Option<&'static Location<'static>>>,
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Value initialized on first access, replacement for `lazy_static!`
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: F,
+}
+
+impl<T> OnceCell<T> {
+    /// Constructs an empty cell
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(UNINIT),
+            location: UnsafeCell::new(None),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the value, if the cell has been initialized
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) == READY {
+            Some(unsafe { (*self.data.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the place the cell was (or was attempted to be) initialized
+    /// from
+    pub fn initialized_at(&self) -> Option<&'static Location<'static>> {
+        match self.state.load(Ordering::Acquire) {
+            READY | FAILED => unsafe { *self.location.get() },
+            _ => None,
+        }
+    }
+
+    /// Stores `value` in the cell. Panics if the cell is already
+    /// initialized, telling where that happened.
+    #[track_caller]
+    pub fn set(&self, value: T) {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        if value.is_some() {
+            panic!(
+                "Double initialization of OnceCell<{}>, first initialized at {}",
+                type_name::<T>(),
+                self.initialized_at().unwrap()
+            );
+        }
+    }
+
+    /// Returns the value, initializing the cell with `f` if needed
+    #[track_caller]
+    pub fn get_or_init<F: FnOnce() -> T>(&self, f: F) -> &T {
+        match self.get_or_try_init(|| Ok::<T, ()>(f())) {
+            Ok(value) => value,
+            Err(()) => unreachable!(),
+        }
+    }
+
+    /// Returns the value, initializing the cell with `f` if needed. If `f`
+    /// fails, the error is returned and the next access will retry.
+    #[track_caller]
+    pub fn get_or_try_init<E, F: FnOnce() -> Result<T, E>>(&self, f: F) -> Result<&T, E> {
+        let caller = Location::caller();
+        loop {
+            match self.state.compare_exchange_weak(
+                UNINIT,
+                BUSY,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(READY) => return Ok(unsafe { (*self.data.get()).assume_init_ref() }),
+                Err(FAILED) => {
+                    // Retry: the previous initializer has reported its error
+                    self.state
+                        .compare_exchange(FAILED, UNINIT, Ordering::Relaxed, Ordering::Relaxed)
+                        .ok();
+                }
+                // Initialization is in progress in another thread
+                Err(_) => sys_ex_yield(),
+            }
+        }
+
+        unsafe {
+            *self.location.get() = Some(caller);
+        }
+        match f() {
+            Ok(value) => {
+                let value = unsafe { (*self.data.get()).write(value) };
+                self.state.store(READY, Ordering::Release);
+                Ok(value)
+            }
+            Err(err) => {
+                self.state.store(FAILED, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == READY {
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+unsafe impl<T: Send> Send for OnceCell<T> {}
+
+impl<T, F> Lazy<T, F> {
+    /// Constructs a value which will be initialized by `init` on first
+    /// access
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+}
+
+impl<T, F: Fn() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.cell.get_or_init(&self.init)
+    }
+}
+
+unsafe impl<T: Send + Sync, F: Sync> Sync for Lazy<T, F> {}
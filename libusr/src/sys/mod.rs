@@ -1,11 +1,13 @@
 pub use libsys::signal::{Signal, SignalDestination};
 pub use libsys::proc::{self, ExitCode};
 pub use libsys::termios;
+pub use libsys::time;
 pub use libsys::abi;
 pub use libsys::calls::*;
 pub use libsys::stat::{self, AccessMode, FileDescriptor};
 pub use libsys::error::Errno;
 pub use libsys::debug;
+pub use libsys::dev;
 
 use core::sync::atomic::{Ordering, AtomicBool};
 
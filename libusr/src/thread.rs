@@ -4,8 +4,13 @@ use core::any::Any;
 use core::cell::UnsafeCell;
 use core::fmt;
 use core::mem::MaybeUninit;
+use core::time::Duration;
 use libsys::{
-    calls::{sys_ex_clone, sys_ex_gettid, sys_ex_signal, sys_ex_thread_exit, sys_ex_thread_wait},
+    calls::{
+        sys_ex_clone, sys_ex_gettid, sys_ex_nanosleep, sys_ex_signal, sys_ex_thread_exit,
+        sys_ex_thread_wait,
+    },
+    error::Errno,
     proc::{ExitCode, Tid},
 };
 
@@ -89,6 +94,21 @@ pub fn current() -> Thread {
     Thread { id: Tid::from(id as u32) }
 }
 
+/// Suspends the current thread for at least `duration`. Sleeping is
+/// resumed after signal handlers interrupt it.
+pub fn sleep(duration: Duration) {
+    let mut duration = duration;
+    while !duration.is_zero() {
+        let mut rem = [0; 2];
+        let ns = duration.as_nanos().min(u64::MAX as u128) as u64;
+        match sys_ex_nanosleep(ns, &mut rem) {
+            Err(Errno::Interrupt) => duration = Duration::new(rem[0], rem[1] as u32),
+            // Durations longer than u64::MAX ns keep going
+            _ => duration = duration.saturating_sub(Duration::from_nanos(ns)),
+        }
+    }
+}
+
 pub fn spawn<F, T>(f: F) -> JoinHandle<T>
 where
     F: FnOnce() -> T,
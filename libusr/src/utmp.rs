@@ -0,0 +1,198 @@
+//! Login session accounting.
+//!
+//! Sessions are recorded into a binary file of fixed-size entries. Entries
+//! are only ever appended, each in a single write, so concurrent writers
+//! do not need to coordinate: a logout is recorded as a separate entry
+//! instead of updating the login one in place.
+use crate::io::{Error, ErrorKind, Read};
+use alloc::vec::Vec;
+use libsys::{
+    calls::{sys_close, sys_ex_clock_get, sys_openat, sys_write},
+    proc::Pid,
+    stat::{FileMode, OpenFlags},
+    time::ClockId,
+    FixedStr,
+};
+
+/// Path of the session record file
+pub const UTMP_PATH: &str = "/var/log/wtmp";
+
+/// Maximum length of a user name in a record
+pub const USER_MAX: usize = 32;
+/// Maximum length of a terminal name in a record
+pub const TTY_MAX: usize = 32;
+
+const RECORD_SIZE: usize = 16 + USER_MAX + TTY_MAX;
+
+/// Kind of event a [Record] describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum RecordKind {
+    /// The system has started, sessions recorded before are gone
+    Boot = 1,
+    /// A user has logged in
+    Login = 2,
+    /// A session has ended
+    Logout = 3,
+}
+
+/// Single session accounting entry
+#[derive(Clone, Copy, Debug)]
+pub struct Record {
+    kind: RecordKind,
+    pid: u32,
+    time: u64,
+    user: FixedStr<USER_MAX>,
+    tty: FixedStr<TTY_MAX>,
+}
+
+impl RecordKind {
+    fn from_u32(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Boot),
+            2 => Some(Self::Login),
+            3 => Some(Self::Logout),
+            _ => None,
+        }
+    }
+}
+
+fn now() -> u64 {
+    sys_ex_clock_get(ClockId::Realtime).map_or(0, |time| time.as_secs())
+}
+
+fn copy_field(dst: &mut [u8], src: &str) {
+    dst[..src.len()].copy_from_slice(src.as_bytes());
+}
+
+fn read_field<const N: usize>(src: &[u8]) -> Option<FixedStr<N>> {
+    let len = src.iter().position(|&b| b == 0).unwrap_or(src.len());
+    core::str::from_utf8(&src[..len])
+        .ok()
+        .map(FixedStr::from_str_truncated)
+}
+
+impl Record {
+    fn new(kind: RecordKind, pid: Option<Pid>, user: &str, tty: &str) -> Self {
+        Self {
+            kind,
+            pid: Pid::from_option(pid),
+            time: now(),
+            user: FixedStr::from_str_truncated(user),
+            tty: FixedStr::from_str_truncated(tty.strip_prefix("/dev/").unwrap_or(tty)),
+        }
+    }
+
+    /// Creates a record of system startup
+    pub fn boot() -> Self {
+        Self::new(RecordKind::Boot, None, "", "")
+    }
+
+    /// Creates a record of `user` logging in on `tty`, with session
+    /// process `pid`
+    pub fn login(user: &str, tty: &str, pid: Pid) -> Self {
+        Self::new(RecordKind::Login, Some(pid), user, tty)
+    }
+
+    /// Creates a record of session process `pid` on `tty` exiting
+    pub fn logout(tty: &str, pid: Pid) -> Self {
+        Self::new(RecordKind::Logout, Some(pid), "", tty)
+    }
+
+    /// Returns the kind of the record
+    pub fn kind(&self) -> RecordKind {
+        self.kind
+    }
+
+    /// Returns the session process ID
+    pub fn pid(&self) -> Option<Pid> {
+        Pid::to_option(self.pid)
+    }
+
+    /// Returns the time of the event, in seconds since the epoch
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Returns the user name (empty unless the record is a login)
+    pub fn user(&self) -> &str {
+        self.user.as_str()
+    }
+
+    /// Returns the terminal name, without the "/dev/" prefix
+    pub fn tty(&self) -> &str {
+        self.tty.as_str()
+    }
+
+    /// Returns `true` if `other` ends the session started by `self`
+    pub fn is_ended_by(&self, other: &Record) -> bool {
+        match other.kind {
+            RecordKind::Boot => true,
+            RecordKind::Logout => other.pid == self.pid && other.tty() == self.tty(),
+            RecordKind::Login => false,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut buf = [0; RECORD_SIZE];
+        buf[0..4].copy_from_slice(&(self.kind as u32).to_le_bytes());
+        buf[4..8].copy_from_slice(&self.pid.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.time.to_le_bytes());
+        copy_field(&mut buf[16..16 + USER_MAX], self.user());
+        copy_field(&mut buf[16 + USER_MAX..], self.tty());
+        buf
+    }
+
+    fn from_bytes(buf: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let mut word = [0; 4];
+        let mut time = [0; 8];
+        word.copy_from_slice(&buf[0..4]);
+        let kind = RecordKind::from_u32(u32::from_le_bytes(word))?;
+        word.copy_from_slice(&buf[4..8]);
+        let pid = u32::from_le_bytes(word);
+        if pid != 0 {
+            Pid::try_from(pid).ok()?;
+        }
+        time.copy_from_slice(&buf[8..16]);
+        Some(Self {
+            kind,
+            pid,
+            time: u64::from_le_bytes(time),
+            user: read_field(&buf[16..16 + USER_MAX])?,
+            tty: read_field(&buf[16 + USER_MAX..])?,
+        })
+    }
+}
+
+/// Appends `record` to the session record file, creating it if needed
+pub fn append(record: &Record) -> Result<(), Error> {
+    let fd = sys_openat(
+        None,
+        UTMP_PATH,
+        FileMode::default_reg(),
+        OpenFlags::O_WRONLY | OpenFlags::O_CREAT | OpenFlags::O_APPEND,
+    )?;
+    let res = sys_write(fd, &record.to_bytes());
+    sys_close(fd).ok();
+    match res? {
+        RECORD_SIZE => Ok(()),
+        _ => Err(Error::new(ErrorKind::InvalidData)),
+    }
+}
+
+/// Reads all the records from `input`, oldest first. Damaged records are
+/// skipped.
+pub fn read_records<F: Read>(mut input: F) -> Result<Vec<Record>, Error> {
+    let mut records = Vec::new();
+    let mut buf = [0; RECORD_SIZE];
+    loop {
+        let mut len = 0;
+        while len < RECORD_SIZE {
+            match input.read(&mut buf[len..])? {
+                0 => return Ok(records),
+                count => len += count,
+            }
+        }
+        records.extend(Record::from_bytes(&buf));
+    }
+}
@@ -0,0 +1,130 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+use core::time::Duration;
+use libusr::sys::{
+    sys_ex_clock_get, sys_ex_clock_set,
+    time::{ClockId, DateTime},
+    Errno,
+};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const DEFAULT_FORMAT: &str = "%a %b %e %H:%M:%S UTC %Y";
+
+fn format(out: &mut String, fmt: &str, secs: u64) {
+    let t = DateTime::from_timestamp(secs);
+    let mut chars = fmt.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        let res = match chars.next() {
+            Some('Y') => write!(out, "{}", t.year),
+            Some('m') => write!(out, "{:02}", t.month),
+            Some('d') => write!(out, "{:02}", t.day),
+            Some('e') => write!(out, "{:2}", t.day),
+            Some('H') => write!(out, "{:02}", t.hour),
+            Some('M') => write!(out, "{:02}", t.minute),
+            Some('S') => write!(out, "{:02}", t.second),
+            Some('a') => write!(out, "{}", WEEKDAYS[t.weekday as usize]),
+            Some('b') => write!(out, "{}", MONTHS[t.month as usize - 1]),
+            Some('s') => write!(out, "{}", secs),
+            Some('F') => write!(out, "{}-{:02}-{:02}", t.year, t.month, t.day),
+            Some('T') => write!(out, "{:02}:{:02}:{:02}", t.hour, t.minute, t.second),
+            Some('%') => write!(out, "%"),
+            Some(other) => write!(out, "%{}", other),
+            None => write!(out, "%"),
+        };
+        res.ok();
+    }
+}
+
+fn parse_fields(text: &str, sep: char) -> Option<Vec<u32>> {
+    text.split(sep).map(|s| s.parse().ok()).collect()
+}
+
+/// Parses "@SECONDS" or "YYYY-MM-DD[ T]HH:MM[:SS]"
+fn parse_time(text: &str) -> Option<u64> {
+    if let Some(secs) = text.strip_prefix('@') {
+        return secs.parse().ok();
+    }
+
+    let (date, time) = text.split_once(|c| c == ' ' || c == 'T')?;
+    let date = parse_fields(date, '-')?;
+    let time = parse_fields(time, ':')?;
+    if date.len() != 3 || !(2..=3).contains(&time.len()) {
+        return None;
+    }
+
+    let value = DateTime {
+        year: date[0],
+        month: u8::try_from(date[1]).ok()?,
+        day: u8::try_from(date[2]).ok()?,
+        hour: u8::try_from(time[0]).ok()?,
+        minute: u8::try_from(time[1]).ok()?,
+        second: u8::try_from(time.get(2).copied().unwrap_or(0)).ok()?,
+        weekday: 0,
+    };
+    if value.is_valid() {
+        Some(value.timestamp())
+    } else {
+        None
+    }
+}
+
+fn usage(name: &str) -> i32 {
+    eprintln!("Usage: {} [+FORMAT]", name);
+    eprintln!("       {} -s @SECONDS|YYYY-MM-DD HH:MM[:SS]", name);
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut fmt = DEFAULT_FORMAT;
+
+    match args.get(1) {
+        None => (),
+        Some(&"-s") if args.len() > 2 => {
+            let text = args[2..].join(" ");
+            let secs = match parse_time(&text) {
+                Some(secs) => secs,
+                None => {
+                    eprintln!("{}: invalid date: {}", args[0], text);
+                    return -1;
+                }
+            };
+            if let Err(e) = sys_ex_clock_set(ClockId::Realtime, Duration::from_secs(secs)) {
+                match e {
+                    Errno::PermissionDenied => eprintln!("{}: only root can set the time", args[0]),
+                    _ => eprintln!("{}: {:?}", args[0], e),
+                }
+                return -1;
+            }
+        }
+        Some(arg) if arg.starts_with('+') && args.len() == 2 => fmt = &arg[1..],
+        Some(_) => return usage(args[0]),
+    }
+
+    let now = match sys_ex_clock_get(ClockId::Realtime) {
+        Ok(now) => now,
+        Err(e) => {
+            eprintln!("{}: {:?}", args[0], e);
+            return -1;
+        }
+    };
+    let mut out = String::new();
+    format(&mut out, fmt, now.as_secs());
+    println!("{}", out);
+    0
+}
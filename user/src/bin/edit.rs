@@ -0,0 +1,387 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write as _;
+use core::sync::atomic::{AtomicBool, Ordering};
+use libusr::file::File;
+use libusr::io::{self, BufReader, RawMode, Read, Write};
+use libusr::signal::{self, SignalHandler};
+use libusr::sys::{stat::FileDescriptor, Errno, Signal};
+
+const CTRL_Q: u8 = 0x11;
+const CTRL_S: u8 = 0x13;
+const BACKSPACE: u8 = 0x7F;
+const TAB_WIDTH: usize = 4;
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+enum Key {
+    Char(char),
+    Control(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+}
+
+struct Editor {
+    path: String,
+    lines: Vec<String>,
+    // Cursor position: line and char index in it
+    cy: usize,
+    cx: usize,
+    // First visible line and column
+    top: usize,
+    left: usize,
+    rows: usize,
+    cols: usize,
+    modified: bool,
+    quit_pending: bool,
+    message: String,
+}
+
+fn byte_index(line: &str, cx: usize) -> usize {
+    line.char_indices().nth(cx).map_or(line.len(), |(i, _)| i)
+}
+
+fn read_byte<F: Read>(input: &mut F) -> Result<Option<u8>, io::Error> {
+    let mut byte = [0];
+    match input.read(&mut byte) {
+        Ok(0) => Ok(None),
+        Ok(_) => Ok(Some(byte[0])),
+        // A window size change
+        Err(_) if RESIZED.load(Ordering::Acquire) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_key<F: Read>(input: &mut F) -> Result<Option<Key>, io::Error> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+    match byte {
+        0x1B => (),
+        0x00..=0x1F | BACKSPACE => return Ok(Some(Key::Control(byte))),
+        0x20..=0x7E => return Ok(Some(Key::Char(byte as char))),
+        _ => {
+            // UTF-8 sequence, the leading byte tells its length
+            let len = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                _ => 4,
+            };
+            let mut buf = [byte, 0, 0, 0];
+            for slot in buf.iter_mut().take(len).skip(1) {
+                *slot = read_byte(input)?.unwrap_or(0);
+            }
+            return Ok(core::str::from_utf8(&buf[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map(Key::Char));
+        }
+    }
+
+    if read_byte(input)? != Some(b'[') {
+        return Ok(Some(Key::Control(byte)));
+    }
+    let key = match read_byte(input)? {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(code @ (b'3' | b'5' | b'6')) => {
+            // Followed by '~'
+            read_byte(input)?;
+            match code {
+                b'3' => Key::Delete,
+                b'5' => Key::PageUp,
+                _ => Key::PageDown,
+            }
+        }
+        _ => Key::Control(byte),
+    };
+    Ok(Some(key))
+}
+
+impl Editor {
+    fn open(path: &str) -> Result<Self, io::Error> {
+        let mut lines = Vec::new();
+        let message = match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    lines.push(line?);
+                }
+                format!("\"{}\" {} lines", path, lines.len())
+            }
+            Err(_) => format!("\"{}\" [New]", path),
+        };
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        Ok(Self {
+            path: path.into(),
+            lines,
+            cy: 0,
+            cx: 0,
+            top: 0,
+            left: 0,
+            rows: 0,
+            cols: 0,
+            modified: false,
+            quit_pending: false,
+            message,
+        })
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.cy].chars().count()
+    }
+
+    fn update_size(&mut self) -> Result<(), Errno> {
+        let size = io::tcgetwinsize(FileDescriptor::STDOUT)?;
+        // Leave room for the status and message lines
+        self.rows = (size.rows as usize).max(3) - 2;
+        self.cols = (size.cols as usize).max(1);
+        Ok(())
+    }
+
+    fn scroll(&mut self) {
+        if self.cy < self.top {
+            self.top = self.cy;
+        } else if self.cy >= self.top + self.rows {
+            self.top = self.cy + 1 - self.rows;
+        }
+        if self.cx < self.left {
+            self.left = self.cx;
+        } else if self.cx >= self.left + self.cols {
+            self.left = self.cx + 1 - self.cols;
+        }
+    }
+
+    fn draw(&mut self) -> Result<(), io::Error> {
+        self.scroll();
+
+        let mut screen = String::new();
+        screen.push_str("\x1B[H\x1B[2J");
+        for row in 0..self.rows {
+            if let Some(line) = self.lines.get(self.top + row) {
+                // Tabs would throw off the cursor position
+                let visible = line.chars().skip(self.left).take(self.cols);
+                screen.extend(visible.map(|ch| if ch == '\t' { ' ' } else { ch }));
+            } else {
+                screen.push('~');
+            }
+            screen.push_str("\r\n");
+        }
+
+        let status = format!(
+            " {}{} - {}:{}",
+            self.path,
+            if self.modified { " [+]" } else { "" },
+            self.cy + 1,
+            self.cx + 1
+        );
+        screen.push_str("\x1B[7m");
+        screen.extend(status.chars().take(self.cols));
+        for _ in status.chars().count()..self.cols {
+            screen.push(' ');
+        }
+        screen.push_str("\x1B[0m\r\n");
+        screen.extend(self.message.chars().take(self.cols));
+
+        write!(
+            screen,
+            "\x1B[{};{}H",
+            self.cy - self.top + 1,
+            self.cx - self.left + 1
+        )
+        .ok();
+        io::stdout().write(screen.as_bytes())?;
+        Ok(())
+    }
+
+    fn insert(&mut self, ch: char) {
+        let index = byte_index(&self.lines[self.cy], self.cx);
+        self.lines[self.cy].insert(index, ch);
+        self.cx += 1;
+        self.modified = true;
+    }
+
+    fn newline(&mut self) {
+        let index = byte_index(&self.lines[self.cy], self.cx);
+        let rest = self.lines[self.cy].split_off(index);
+        self.lines.insert(self.cy + 1, rest);
+        self.cy += 1;
+        self.cx = 0;
+        self.modified = true;
+    }
+
+    fn backspace(&mut self) {
+        if self.cx > 0 {
+            self.cx -= 1;
+            let index = byte_index(&self.lines[self.cy], self.cx);
+            self.lines[self.cy].remove(index);
+            self.modified = true;
+        } else if self.cy > 0 {
+            let line = self.lines.remove(self.cy);
+            self.cy -= 1;
+            self.cx = self.line_len();
+            self.lines[self.cy].push_str(&line);
+            self.modified = true;
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cx < self.line_len() {
+            self.cx += 1;
+            self.backspace();
+        } else if self.cy + 1 < self.lines.len() {
+            self.cy += 1;
+            self.cx = 0;
+            self.backspace();
+        }
+    }
+
+    /// Writes the buffer to the file. The file is only truncated after the
+    /// new contents have been written, so it never appears empty.
+    fn save(&mut self) -> Result<(), io::Error> {
+        let mut file = File::create(&self.path)?;
+        let mut size = 0;
+        for line in &self.lines {
+            file.write_all(line.as_bytes())?;
+            file.write_all(b"\n")?;
+            size += line.len() + 1;
+        }
+        file.set_len(size)?;
+        file.sync_all()?;
+
+        self.modified = false;
+        self.message = format!("\"{}\" {} lines written", self.path, self.lines.len());
+        Ok(())
+    }
+
+    /// Handles a key press, returns `false` when the editor should exit
+    fn handle(&mut self, key: Key) -> bool {
+        let quit_pending = core::mem::replace(&mut self.quit_pending, false);
+        match key {
+            Key::Char(ch) => self.insert(ch),
+            Key::Control(b'\r' | b'\n') => self.newline(),
+            Key::Control(b'\t') => loop {
+                self.insert(' ');
+                if self.cx % TAB_WIDTH == 0 {
+                    break;
+                }
+            },
+            Key::Control(BACKSPACE | 0x08) => self.backspace(),
+            Key::Delete => self.delete(),
+            Key::Control(CTRL_S) => {
+                if let Err(e) = self.save() {
+                    self.message = format!("Save failed: {:?}", e);
+                }
+            }
+            Key::Control(CTRL_Q) => {
+                if !self.modified || quit_pending {
+                    return false;
+                }
+                self.quit_pending = true;
+                self.message = "Unsaved changes, press ^Q again to quit".into();
+            }
+            Key::Up => self.cy = self.cy.saturating_sub(1),
+            Key::Down => self.cy = (self.cy + 1).min(self.lines.len() - 1),
+            Key::Left => {
+                if self.cx > 0 {
+                    self.cx -= 1;
+                } else if self.cy > 0 {
+                    self.cy -= 1;
+                    self.cx = self.line_len();
+                }
+            }
+            Key::Right => {
+                if self.cx < self.line_len() {
+                    self.cx += 1;
+                } else if self.cy + 1 < self.lines.len() {
+                    self.cy += 1;
+                    self.cx = 0;
+                }
+            }
+            Key::Home => self.cx = 0,
+            Key::End => self.cx = self.line_len(),
+            Key::PageUp => self.cy = self.cy.saturating_sub(self.rows),
+            Key::PageDown => self.cy = (self.cy + self.rows).min(self.lines.len() - 1),
+            Key::Control(_) => (),
+        }
+        self.cx = self.cx.min(self.line_len());
+        true
+    }
+
+    fn run(&mut self) -> Result<(), io::Error> {
+        let mut keys = io::stdin();
+        self.update_size()?;
+
+        loop {
+            if RESIZED.swap(false, Ordering::AcqRel) {
+                self.update_size()?;
+            }
+            self.draw()?;
+
+            match read_key(&mut keys)? {
+                Some(key) => {
+                    if !self.handle(key) {
+                        break;
+                    }
+                }
+                // Redraw after a window size change
+                None if RESIZED.load(Ordering::Acquire) => (),
+                None => break,
+            }
+        }
+
+        io::stdout().write(b"\x1B[H\x1B[2J")?;
+        Ok(())
+    }
+}
+
+fn window_changed(_signal: Signal) {
+    RESIZED.store(true, Ordering::Release);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if args.len() != 2 {
+        eprintln!("Usage: {} FILE", args[0]);
+        return -1;
+    }
+    if !io::isatty(FileDescriptor::STDIN) || !io::isatty(FileDescriptor::STDOUT) {
+        eprintln!("{}: not a terminal", args[0]);
+        return -1;
+    }
+
+    let res = Editor::open(args[1]).and_then(|mut editor| {
+        let _raw = RawMode::enter(FileDescriptor::STDIN)?;
+        signal::set_handler(Signal::WindowChange, SignalHandler::Func(window_changed));
+        editor.run()
+    });
+
+    if let Err(e) = res {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
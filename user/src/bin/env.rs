@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::{env, process};
+
+fn usage(name: &str) -> i32 {
+    eprintln!(
+        "Usage: {} [-i] [-u NAME]... [NAME=VALUE]... [COMMAND [ARG]...]",
+        name
+    );
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = env::args();
+    let mut index = 1;
+
+    while index < args.len() {
+        let arg = args[index];
+        match arg {
+            "-i" => env::clear_vars(),
+            "-u" => match args.get(index + 1) {
+                Some(name) => {
+                    env::remove_var(name);
+                    index += 1;
+                }
+                None => return usage(args[0]),
+            },
+            _ if arg.starts_with('-') => return usage(args[0]),
+            _ => match arg.split_once('=') {
+                Some((name, value)) if !name.is_empty() => env::set_var(name, value),
+                Some(_) => return usage(args[0]),
+                None => break,
+            },
+        }
+        index += 1;
+    }
+
+    if index == args.len() {
+        for (name, value) in env::vars() {
+            println!("{}={}", name, value);
+        }
+        return 0;
+    }
+
+    let err = process::execvp(&args[index..]);
+    eprintln!("{}: {:?}", args[index], err);
+    -1
+}
@@ -0,0 +1,127 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::vec;
+use libusr::sys::{
+    proc::ForkStatistics, sys_ex_fork_statistics, sys_exit, sys_fork, sys_waitpid, Errno, ExitCode,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+struct Options {
+    forks: usize,
+    pages: usize,
+    touch: usize,
+    reset: bool,
+}
+
+fn statistics(reset: bool) -> Result<ForkStatistics, Errno> {
+    let mut stats = ForkStatistics::default();
+    sys_ex_fork_statistics(&mut stats, reset)?;
+    Ok(stats)
+}
+
+fn run(opts: &Options) -> Result<(), Errno> {
+    // Make the buffer resident so that every fork has to share it
+    let mut buffer = vec![1u8; opts.pages * PAGE_SIZE];
+
+    let before = statistics(opts.reset)?;
+    for _ in 0..opts.forks {
+        match unsafe { sys_fork()? } {
+            Some(pid) => {
+                let mut status = 0;
+                sys_waitpid(pid, &mut status)?;
+            }
+            None => {
+                for page in buffer.chunks_mut(PAGE_SIZE).take(opts.touch) {
+                    page[0] = 2;
+                }
+                sys_exit(ExitCode::from(0));
+            }
+        }
+    }
+    let after = if opts.reset {
+        statistics(false)?
+    } else {
+        let mut after = statistics(false)?;
+        after.forks -= before.forks;
+        after.pages_shared -= before.pages_shared;
+        after.cow_faults -= before.cow_faults;
+        after.pages_copied -= before.pages_copied;
+        after.pages_reused -= before.pages_reused;
+        after
+    };
+
+    let forks = after.forks.max(1);
+    println!(
+        "{} forks of {} pages, {} touched by each child",
+        after.forks, opts.pages, opts.touch
+    );
+    println!(
+        "shared {} pages ({}/fork), {} CoW faults: {} copied, {} reused",
+        after.pages_shared,
+        after.pages_shared / forks,
+        after.cow_faults,
+        after.pages_copied,
+        after.pages_reused
+    );
+    println!(
+        "fork latency over {} samples: p50 {}us, p90 {}us, p99 {}us, max {}us",
+        after.latency_samples,
+        after.latency_p50_ns / 1000,
+        after.latency_p90_ns / 1000,
+        after.latency_p99_ns / 1000,
+        after.latency_max_ns / 1000
+    );
+    Ok(())
+}
+
+fn usage(name: &str) -> i32 {
+    eprintln!(
+        "Usage: {} [-n FORKS] [-p PAGES] [-t TOUCHED_PAGES] [-r]",
+        name
+    );
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut opts = Options {
+        forks: 100,
+        pages: 64,
+        touch: 16,
+        reset: false,
+    };
+
+    let mut rest = &args[1..];
+    while !rest.is_empty() {
+        if rest[0] == "-r" {
+            opts.reset = true;
+            rest = &rest[1..];
+            continue;
+        }
+        let value = rest.get(1).and_then(|value| value.parse::<usize>().ok());
+        match (rest[0], value) {
+            ("-n", Some(count)) if count > 0 => opts.forks = count,
+            ("-p", Some(count)) => opts.pages = count,
+            ("-t", Some(count)) => opts.touch = count,
+            _ => return usage(args[0]),
+        }
+        rest = &rest[2..];
+    }
+    if opts.touch > opts.pages {
+        return usage(args[0]);
+    }
+
+    if let Err(e) = run(&opts) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
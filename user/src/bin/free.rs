@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{
+    proc::{MemoryStatus, Pid, ProcessMemory},
+    sys_ex_memory_status, sys_ex_process_memory, Errno,
+};
+
+fn show_status(detailed: bool) -> Result<(), Errno> {
+    let mut status = MemoryStatus::default();
+    sys_ex_memory_status(&mut status)?;
+
+    let used = status.total_pages - status.available_pages - status.offline_pages;
+    println!("            total       used       free     cached");
+    println!(
+        "Mem:  {:>9}K {:>9}K {:>9}K {:>9}K",
+        status.total_pages * 4,
+        used * 4,
+        status.available_pages * 4,
+        status.cached_pages * 4
+    );
+    println!(
+        "Swap: {:>9}K {:>9}K {:>9}K",
+        status.swap.total_pages * 4,
+        status.swap.used_pages * 4,
+        (status.swap.total_pages - status.swap.used_pages) * 4
+    );
+
+    if detailed {
+        println!("Kernel:     {}K", status.kernel_pages * 4);
+        println!("KernelHeap: {}K", status.kernel_heap_pages * 4);
+        println!("Heap:       {}K/{}K", status.heap_used / 1024, status.heap_size / 1024);
+        println!("Paging:     {}K", status.paging_pages * 4);
+        println!("User:       {}K", status.user_pages * 4);
+        println!("Filesystem: {}K", status.filesystem_pages * 4);
+        println!("Offline:    {}K", status.offline_pages * 4);
+        println!("Dirty:      {}K", status.dirty_pages * 4);
+    }
+    Ok(())
+}
+
+fn show_process(pid: &str) -> Result<(), Errno> {
+    let pid = Pid::try_from(pid.parse::<u32>().map_err(|_| Errno::InvalidArgument)?)?;
+    let mut usage = ProcessMemory::default();
+    sys_ex_process_memory(pid, &mut usage)?;
+    println!("Resident:    {}K", usage.resident_pages * 4);
+    println!("Swapped:     {}K", usage.swapped_pages * 4);
+    println!("Working set: {}K", usage.working_set_pages * 4);
+    println!("Idle:        {}K", usage.idle_pages * 4);
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let res = match &args[1..] {
+        [] => show_status(false),
+        ["-v"] => show_status(true),
+        ["-p", pid] => show_process(pid),
+        _ => {
+            eprintln!("Usage: {} [-v | -p PID]", args[0]);
+            return -1;
+        }
+    };
+
+    if let Err(e) = res {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
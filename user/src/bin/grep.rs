@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use libusr::file::File;
+use libusr::io::{self, BufReader, Read};
+
+#[derive(Clone, Copy, Default)]
+struct Options {
+    invert: bool,
+    line_numbers: bool,
+    count: bool,
+}
+
+/// Prints matching lines of the input, prefixed with `name` if given.
+/// Returns `true` if there were any.
+fn do_grep<F: Read>(
+    input: F,
+    pattern: &str,
+    name: Option<&str>,
+    opts: Options,
+) -> Result<bool, io::Error> {
+    let mut matches = 0;
+
+    for (index, line) in BufReader::new(input).lines().enumerate() {
+        let line = line?;
+        if line.contains(pattern) == opts.invert {
+            continue;
+        }
+        matches += 1;
+        if opts.count {
+            continue;
+        }
+        if let Some(name) = name {
+            print!("{}:", name);
+        }
+        if opts.line_numbers {
+            print!("{}:", index + 1);
+        }
+        println!("{}", line);
+    }
+
+    if opts.count {
+        match name {
+            Some(name) => println!("{}:{}", name, matches),
+            None => println!("{}", matches),
+        }
+    }
+    Ok(matches != 0)
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut opts = Options::default();
+    let mut rest = &args[1..];
+
+    while let Some(&arg) = rest.first() {
+        match arg {
+            "-v" => opts.invert = true,
+            "-n" => opts.line_numbers = true,
+            "-c" => opts.count = true,
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    let (pattern, files) = match rest.split_first() {
+        Some((pattern, files)) => (*pattern, files),
+        None => {
+            eprintln!("Usage: {} [-v] [-n] [-c] PATTERN [FILE...]", args[0]);
+            return 2;
+        }
+    };
+
+    let mut found = false;
+    let mut failed = false;
+    if files.is_empty() {
+        match do_grep(io::stdin(), pattern, None, opts) {
+            Ok(res) => found |= res,
+            Err(e) => {
+                eprintln!(".: {:?}", e);
+                failed = true;
+            }
+        }
+    } else {
+        let name = |arg| if files.len() > 1 { Some(arg) } else { None };
+        for &arg in files {
+            match File::open(arg).and_then(|f| do_grep(f, pattern, name(arg), opts)) {
+                Ok(res) => found |= res,
+                Err(e) => {
+                    eprintln!("{}: {:?}", arg, e);
+                    failed = true;
+                }
+            }
+        }
+    }
+
+    // Same as POSIX grep: 0 if anything matched, 1 if not, 2 on errors
+    match (failed, found) {
+        (true, _) => 2,
+        (false, true) => 0,
+        (false, false) => 1,
+    }
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use libusr::file::File;
+use libusr::io::{self, BufReader, Read, Write};
+
+fn do_head<F: Read>(input: F, count: usize) -> Result<(), io::Error> {
+    let mut reader = BufReader::new(input);
+    let mut out = io::stdout();
+    let mut line = Vec::new();
+
+    for _ in 0..count {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        out.write(&line)?;
+    }
+
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let (count, files) = match args.get(1) {
+        Some(&"-n") => match args.get(2).and_then(|n| n.parse().ok()) {
+            Some(count) => (count, &args[3..]),
+            None => {
+                eprintln!("Usage: {} [-n LINES] [FILE...]", args[0]);
+                return -1;
+            }
+        },
+        _ => (10, &args[1..]),
+    };
+    let mut res = 0;
+
+    if files.is_empty() {
+        if let Err(e) = do_head(io::stdin(), count) {
+            eprintln!(".: {:?}", e);
+            res = -1;
+        }
+    } else {
+        for (i, &arg) in files.iter().enumerate() {
+            if files.len() > 1 {
+                if i != 0 {
+                    println!();
+                }
+                println!("==> {} <==", arg);
+            }
+            if let Err(e) = File::open(arg).and_then(|f| do_head(f, count)) {
+                eprintln!("{}: {:?}", arg, e);
+                res = -1;
+            }
+        }
+    }
+
+    res
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::file::File;
+use libusr::io::{AsRawFd, Error};
+use libusr::sys::{proc::Pid, stat::IoStatistics, sys_ex_filesystem_io, sys_ex_process_io, Errno};
+
+fn show(stats: &IoStatistics) {
+    println!(
+        "Read:    {}K in {} calls",
+        stats.read_bytes / 1024,
+        stats.read_calls
+    );
+    println!(
+        "Written: {}K in {} calls",
+        stats.write_bytes / 1024,
+        stats.write_calls
+    );
+}
+
+fn show_process(pid: &str) -> Result<(), Error> {
+    let pid = Pid::try_from(pid.parse::<u32>().map_err(|_| Errno::InvalidArgument)?)?;
+    let mut stats = IoStatistics::default();
+    sys_ex_process_io(pid, &mut stats)?;
+    show(&stats);
+    Ok(())
+}
+
+fn show_filesystem(path: &str) -> Result<(), Error> {
+    let file = File::open(path)?;
+    let mut stats = IoStatistics::default();
+    sys_ex_filesystem_io(file.as_raw_fd(), &mut stats)?;
+    show(&stats);
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let res = match &args[1..] {
+        ["-p", pid] => show_process(pid),
+        [path] => show_filesystem(path),
+        _ => {
+            eprintln!("Usage: {} -p PID | PATH", args[0]);
+            return -1;
+        }
+    };
+
+    if let Err(e) = res {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,91 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{format, string::String};
+use libusr::file::File;
+use libusr::io;
+use libusr::sys::time::DateTime;
+use libusr::utmp::{self, RecordKind};
+
+fn format_time(secs: u64) -> String {
+    let t = DateTime::from_timestamp(secs);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        t.year, t.month, t.day, t.hour, t.minute
+    )
+}
+
+fn show(limit: Option<usize>) -> Result<(), io::Error> {
+    let records = utmp::read_records(File::open(utmp::UTMP_PATH)?)?;
+
+    // Most recent first, logouts are shown along with their logins
+    let shown = records
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, record)| record.kind() != RecordKind::Logout);
+    for (index, record) in shown.take(limit.unwrap_or(usize::MAX)) {
+        let start = format_time(record.time());
+        match record.kind() {
+            RecordKind::Boot => println!("{:<10} {:<10} {}", "reboot", "system", start),
+            RecordKind::Login => {
+                let end = records[index + 1..]
+                    .iter()
+                    .find(|other| record.is_ended_by(other));
+                let end = match end {
+                    None => String::from("still logged in"),
+                    // The system went down without the session ending
+                    Some(other) if other.kind() == RecordKind::Boot => String::from("- crash"),
+                    Some(other) => {
+                        let secs = other.time().saturating_sub(record.time());
+                        format!(
+                            "- {}  ({:02}:{:02})",
+                            format_time(other.time()),
+                            secs / 3600,
+                            secs / 60 % 60
+                        )
+                    }
+                };
+                println!(
+                    "{:<10} {:<10} {} {}",
+                    record.user(),
+                    record.tty(),
+                    start,
+                    end
+                );
+            }
+            RecordKind::Logout => (),
+        }
+    }
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let limit = match &args[1..] {
+        [] => None,
+        ["-n", count] => match count.parse() {
+            Ok(count) => Some(count),
+            Err(_) => {
+                eprintln!("{}: invalid count: {}", args[0], count);
+                return -1;
+            }
+        },
+        _ => {
+            eprintln!("Usage: {} [-n COUNT]", args[0]);
+            return -1;
+        }
+    };
+
+    if let Err(e) = show(limit) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
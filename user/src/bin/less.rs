@@ -0,0 +1,160 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{string::String, vec::Vec};
+use libusr::file::File;
+use libusr::io::{self, BufReader, RawMode, Read, Write};
+use libusr::sys::stat::FileDescriptor;
+
+const TAB_WIDTH: usize = 8;
+
+enum Key {
+    Char(u8),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+}
+
+fn load<F: Read>(input: F) -> Result<Vec<String>, io::Error> {
+    let mut lines = Vec::new();
+    for line in BufReader::new(input).lines() {
+        let line = line?;
+        // Tabs are expanded so that every char takes a single column
+        let mut expanded = String::with_capacity(line.len());
+        for ch in line.chars() {
+            if ch == '\t' {
+                let width = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+                expanded.extend(core::iter::repeat(' ').take(width));
+            } else {
+                expanded.push(ch);
+            }
+        }
+        lines.push(expanded);
+    }
+    Ok(lines)
+}
+
+fn read_byte<F: Read>(input: &mut F) -> Result<Option<u8>, io::Error> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn read_key<F: Read>(input: &mut F) -> Result<Option<Key>, io::Error> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+    if byte != 0x1B {
+        return Ok(Some(Key::Char(byte)));
+    }
+    if read_byte(input)? != Some(b'[') {
+        return Ok(Some(Key::Char(byte)));
+    }
+    let key = match read_byte(input)? {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(code @ (b'5' | b'6')) => {
+            // Followed by '~'
+            read_byte(input)?;
+            if code == b'5' {
+                Key::PageUp
+            } else {
+                Key::PageDown
+            }
+        }
+        _ => Key::Char(byte),
+    };
+    Ok(Some(key))
+}
+
+fn draw(lines: &[String], top: usize, rows: usize, cols: usize) -> Result<(), io::Error> {
+    let mut out = io::stdout();
+    out.write(b"\x1B[H\x1B[2J")?;
+    for line in lines.iter().skip(top).take(rows - 1) {
+        let end = line.char_indices().nth(cols).map_or(line.len(), |(i, _)| i);
+        out.write(line[..end].as_bytes())?;
+        out.write(b"\r\n")?;
+    }
+    if top + rows - 1 >= lines.len() {
+        out.write(b"\x1B[7m(END)\x1B[0m")?;
+    } else {
+        out.write(b":")?;
+    }
+    Ok(())
+}
+
+fn page(lines: &[String]) -> Result<(), io::Error> {
+    let mut keys = io::stdin();
+    let _raw = RawMode::enter(FileDescriptor::STDIN)?;
+    let mut top = 0;
+
+    loop {
+        // Picks up window size changes as well
+        let size = io::tcgetwinsize(FileDescriptor::STDOUT)?;
+        let rows = (size.rows as usize).max(2);
+        let height = rows - 1;
+        let last = lines.len().saturating_sub(height);
+        top = top.min(last);
+        draw(lines, top, rows, size.cols as usize)?;
+
+        match read_key(&mut keys)? {
+            None | Some(Key::Char(b'q' | 0x03)) => break,
+            Some(Key::Char(b'j' | b'\r' | b'\n') | Key::Down) => top += 1,
+            Some(Key::Char(b'k') | Key::Up) => top = top.saturating_sub(1),
+            Some(Key::Char(b' ' | b'f') | Key::PageDown) => top += height,
+            Some(Key::Char(b'b') | Key::PageUp) => top = top.saturating_sub(height),
+            Some(Key::Char(b'g') | Key::Home) => top = 0,
+            Some(Key::Char(b'G') | Key::End) => top = last,
+            Some(Key::Char(_)) => (),
+        }
+    }
+
+    io::stdout().write(b"\r\x1B[K")?;
+    Ok(())
+}
+
+fn do_less<F: Read>(input: F) -> Result<(), io::Error> {
+    let lines = load(input)?;
+    if io::isatty(FileDescriptor::STDIN) && io::isatty(FileDescriptor::STDOUT) {
+        page(&lines)
+    } else {
+        // Nothing to navigate with or nothing to page, behave like cat
+        for line in lines {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    let res = match args.len() {
+        1 if !io::isatty(FileDescriptor::STDIN) => do_less(io::stdin()),
+        2 => File::open(args[1]).and_then(do_less),
+        _ => {
+            eprintln!("Usage: {} FILE", args[0]);
+            return -1;
+        }
+    };
+
+    if let Err(e) = res {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
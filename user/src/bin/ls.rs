@@ -3,19 +3,101 @@
 
 #[macro_use]
 extern crate libusr;
-#[macro_use]
 extern crate alloc;
 
-use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use libusr::io;
 use libusr::sys::{
-    stat::{DirectoryEntry, FileMode, OpenFlags, Stat},
-    sys_close, sys_fstatat, sys_openat, sys_readdir, Errno,
+    stat::{self, DirectoryEntryPlus, FileDescriptor, FileMode, OpenFlags},
+    sys_close, sys_openat, sys_readdir_plus, Errno,
 };
 
-fn list_directory(path: &str) -> Result<(), Errno> {
-    let mut buffer = [DirectoryEntry::empty(); 8];
-    let mut stat = Stat::default();
-    let mut data = vec![];
+#[derive(Clone, Copy)]
+struct Options {
+    long: bool,
+    /// Terminal width for multi-column output, if any
+    columns: Option<usize>,
+    color: bool,
+}
+
+/// Returns the SGR color sequence for an entry, if it should be colored
+fn color_of(item: &DirectoryEntryPlus) -> Option<&'static str> {
+    // Fall back to the type reported in the entry itself if stat failed
+    let mode = match item.stat() {
+        Some(stat) => stat.mode,
+        None => item.entry().file_type()?,
+    };
+    let kind = mode & FileMode::FILE_TYPE;
+    if kind == FileMode::S_IFDIR {
+        Some("\x1B[1;34m")
+    } else if kind == FileMode::S_IFCHR || kind == FileMode::S_IFBLK {
+        Some("\x1B[1;33m")
+    } else if kind == FileMode::S_IFIFO {
+        Some("\x1B[33m")
+    } else if mode.intersects(FileMode::USER_EXEC | FileMode::GROUP_EXEC | FileMode::OTHER_EXEC) {
+        Some("\x1B[1;32m")
+    } else {
+        None
+    }
+}
+
+fn print_name(item: &DirectoryEntryPlus, opts: &Options) {
+    match color_of(item).filter(|_| opts.color) {
+        Some(color) => print!("{}{}\x1B[0m", color, item.as_str()),
+        None => print!("{}", item.as_str()),
+    }
+}
+
+fn print_long(data: &[DirectoryEntryPlus], opts: &Options) {
+    for item in data {
+        if let Some(stat) = item.stat() {
+            print!("{} ", stat.mode);
+            let kind = stat.mode & FileMode::FILE_TYPE;
+            if kind == FileMode::S_IFCHR || kind == FileMode::S_IFBLK {
+                print!(
+                    "{:3}, {:3} ",
+                    stat::major(stat.rdev),
+                    stat::minor(stat.rdev)
+                );
+            } else {
+                print!("{:8} ", stat.size);
+            }
+        } else {
+            print!("?????????? ");
+        }
+        print_name(item, opts);
+        println!();
+    }
+}
+
+/// Prints names in as many columns as fit, sorted down the columns
+fn print_columns(data: &[DirectoryEntryPlus], width: usize, opts: &Options) {
+    if data.is_empty() {
+        return;
+    }
+    let column_width = data.iter().map(|item| item.as_str().len()).max().unwrap() + 2;
+    let columns = (width / column_width).max(1);
+    let rows = (data.len() + columns - 1) / columns;
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let index = column * rows + row;
+            if index >= data.len() {
+                break;
+            }
+            let item = &data[index];
+            print_name(item, opts);
+            if (column + 1) * rows + row < data.len() {
+                print!("{:1$}", "", column_width - item.as_str().len());
+            }
+        }
+        println!();
+    }
+}
+
+fn list_directory(path: &str, opts: &Options) -> Result<(), Errno> {
+    let mut buffer = [DirectoryEntryPlus::empty(); 8];
+    let mut data = Vec::new();
 
     let fd = sys_openat(
         None,
@@ -25,27 +107,24 @@ fn list_directory(path: &str) -> Result<(), Errno> {
     )?;
 
     loop {
-        let count = sys_readdir(fd, &mut buffer)?;
+        let count = sys_readdir_plus(fd, &mut buffer)?;
         if count == 0 {
             break;
         }
 
-        buffer.iter().take(count).for_each(|e| {
-            data.push(e.as_str().to_owned());
-        });
+        data.extend_from_slice(&buffer[..count]);
     }
 
-    data.sort();
+    data.sort_by(|a, b| a.as_str().cmp(b.as_str()));
 
-    data.iter().for_each(|item| {
-        let stat = sys_fstatat(Some(fd), item, &mut stat, 0).map(|_| &stat);
-        if let Ok(stat) = stat {
-            print!("{} ", stat.mode);
-        } else {
-            print!("?????????? ");
-        }
-        println!("{}", item);
-    });
+    match (opts.long, opts.columns) {
+        (false, Some(width)) => print_columns(&data, width, opts),
+        (false, None) => data.iter().for_each(|item| {
+            print_name(item, opts);
+            println!();
+        }),
+        (true, _) => print_long(&data, opts),
+    }
 
     sys_close(fd)
 }
@@ -53,19 +132,47 @@ fn list_directory(path: &str) -> Result<(), Errno> {
 #[no_mangle]
 fn main() -> i32 {
     let args = libusr::env::args();
+    let tty = io::isatty(FileDescriptor::STDOUT);
+    let mut opts = Options {
+        long: false,
+        columns: if tty {
+            io::tcgetwinsize(FileDescriptor::STDOUT)
+                .ok()
+                .map(|size| size.cols as usize)
+        } else {
+            None
+        },
+        color: tty,
+    };
+    let mut paths = Vec::new();
     let mut res = 0;
 
-    if args.len() == 1 {
-        if let Err(e) = list_directory(".") {
-            eprintln!(".: {:?}", e);
-            res = -1;
-        }
-    } else {
-        for arg in &args[1..] {
-            if let Err(e) = list_directory(arg) {
-                eprintln!("{}: {:?}", arg, e);
-                res = -1;
+    for &arg in &args[1..] {
+        match arg {
+            "-l" => opts.long = true,
+            "-1" => opts.columns = None,
+            "--color=always" => opts.color = true,
+            "--color=never" => opts.color = false,
+            "--color=auto" => opts.color = tty,
+            _ if arg.starts_with('-') => {
+                eprintln!(
+                    "Usage: {} [-l] [-1] [--color=always|never|auto] [PATH...]",
+                    args[0]
+                );
+                return -1;
             }
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(".");
+    }
+
+    for path in paths {
+        if let Err(e) = list_directory(path, &opts) {
+            eprintln!("{}: {:?}", path, e);
+            res = -1;
         }
     }
 
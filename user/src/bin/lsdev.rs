@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use libusr::sys::{dev::DeviceInfo, sys_ex_device_list, Errno};
+
+fn device_list() -> Result<Vec<DeviceInfo>, Errno> {
+    let mut devices = vec![DeviceInfo::default(); 32];
+    loop {
+        let count = sys_ex_device_list(&mut devices)?;
+        if count <= devices.len() {
+            devices.truncate(count);
+            return Ok(devices);
+        }
+        devices.resize(count, DeviceInfo::default());
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 1 {
+        eprintln!("Usage: {}", args[0]);
+        return -1;
+    }
+
+    match device_list() {
+        Ok(devices) => {
+            println!("STATE     NAME");
+            for dev in devices {
+                println!("{:<9} {}", dev.state.as_str(), dev.name);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {:?}", args[0], e);
+            -1
+        }
+    }
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use libusr::sys::{dev::ModuleInfo, sys_ex_module_list, Errno};
+
+fn module_list() -> Result<Vec<ModuleInfo>, Errno> {
+    let mut modules = vec![ModuleInfo::default(); 16];
+    loop {
+        let count = sys_ex_module_list(&mut modules)?;
+        if count <= modules.len() {
+            modules.truncate(count);
+            return Ok(modules);
+        }
+        modules.resize(count, ModuleInfo::default());
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 1 {
+        eprintln!("Usage: {}", args[0]);
+        return -1;
+    }
+
+    match module_list() {
+        Ok(modules) => {
+            println!("BASE               SIZE     NAME");
+            for module in modules {
+                println!("{:#018x} {:<8} {}", module.base, module.size, module.name);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {:?}", args[0], e);
+            -1
+        }
+    }
+}
@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use libusr::sys::{
+    dev::{PciBarFlags, PciFunction},
+    sys_ex_pci_functions, Errno,
+};
+
+fn pci_functions() -> Result<Vec<PciFunction>, Errno> {
+    let mut functions = vec![PciFunction::default(); 32];
+    loop {
+        let count = sys_ex_pci_functions(&mut functions)?;
+        if count <= functions.len() {
+            functions.truncate(count);
+            return Ok(functions);
+        }
+        functions.resize(count, PciFunction::default());
+    }
+}
+
+fn show_function(function: &PciFunction, verbose: bool) {
+    println!(
+        "{:02x}:{:02x}.{} {:04x}: {:04x}:{:04x} (rev {:02x})",
+        function.bus(),
+        function.dev(),
+        function.func(),
+        function.class >> 16,
+        function.vendor_id,
+        function.device_id,
+        function.class & 0xFF
+    );
+    if !verbose {
+        return;
+    }
+
+    if (1..=4).contains(&function.irq_pin) {
+        println!(
+            "\tInterrupt: pin {}, line {}",
+            (b'A' + function.irq_pin - 1) as char,
+            function.irq_line
+        );
+    }
+    for (index, bar) in function.bars.iter().enumerate() {
+        if bar.size == 0 {
+            continue;
+        }
+        let kind = if bar.flags.contains(PciBarFlags::IO) {
+            "I/O ports"
+        } else if bar.flags.contains(PciBarFlags::PREFETCH) {
+            "Memory (prefetchable)"
+        } else {
+            "Memory"
+        };
+        let width = if bar.flags.contains(PciBarFlags::MEM64) {
+            ", 64-bit"
+        } else {
+            ""
+        };
+        println!(
+            "\tBAR{}: {} at {:#x}{} [size={:#x}]",
+            index, kind, bar.address, width, bar.size
+        );
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let verbose = match args.len() {
+        1 => false,
+        2 if args[1] == "-v" => true,
+        _ => {
+            eprintln!("Usage: {} [-v]", args[0]);
+            return -1;
+        }
+    };
+
+    match pci_functions() {
+        Ok(functions) => {
+            for function in functions.iter() {
+                show_function(function, verbose);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {:?}", args[0], e);
+            -1
+        }
+    }
+}
@@ -0,0 +1,147 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{
+    proc::{MemoryAccess, MemoryMap, MemoryStatus},
+    sys_ex_memory_status, sys_exit, sys_fork, sys_mmap, sys_waitpid, ExitCode,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+
+struct Options {
+    chunk: usize,
+    limit: Option<usize>,
+    touch: bool,
+}
+
+fn print_status(when: &str) {
+    let mut status = MemoryStatus::default();
+    if sys_ex_memory_status(&mut status).is_ok() {
+        println!(
+            "{}: {}K free, {}K cached, {}K of {}K swap used",
+            when,
+            status.available_pages * 4,
+            status.cached_pages * 4,
+            status.swap.used_pages * 4,
+            status.swap.total_pages * 4
+        );
+    }
+}
+
+// Exit statuses of the eater
+const LIMIT_REACHED: i32 = 0;
+const MAP_FAILED: i32 = 1;
+
+/// Maps and dirties memory until the kernel refuses or the limit is hit
+fn eat(opts: &Options) -> i32 {
+    let mut total = 0;
+    loop {
+        if opts.limit.map_or(false, |limit| total >= limit) {
+            println!("Reached the limit of {}K", total / 1024);
+            return LIMIT_REACHED;
+        }
+
+        let base = match sys_mmap(
+            0,
+            opts.chunk,
+            MemoryAccess::READ | MemoryAccess::WRITE,
+            MemoryMap::ANONYMOUS | MemoryMap::PRIVATE,
+        ) {
+            Ok(base) => base,
+            Err(e) => {
+                println!(
+                    "mmap() of {}K failed after {}K: {:?}",
+                    opts.chunk / 1024,
+                    total / 1024,
+                    e
+                );
+                return MAP_FAILED;
+            }
+        };
+        if opts.touch {
+            // Pages are only backed once written to
+            for offset in (0..opts.chunk).step_by(PAGE_SIZE) {
+                unsafe {
+                    core::ptr::write_volatile((base + offset) as *mut u8, 0xA5);
+                }
+            }
+        }
+
+        total += opts.chunk;
+        println!("{}K", total / 1024);
+    }
+}
+
+fn parse_size(arg: Option<&&str>) -> Option<usize> {
+    arg?.parse::<usize>()
+        .ok()
+        .filter(|&kib| kib != 0)
+        .map(|kib| kib * 1024)
+}
+
+fn usage(name: &str) -> i32 {
+    eprintln!("Usage: {} [-c CHUNK_KIB] [-l LIMIT_KIB] [-n]", name);
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut opts = Options {
+        chunk: 1024 * 1024,
+        limit: None,
+        touch: true,
+    };
+
+    let mut index = 1;
+    while index < args.len() {
+        match args[index] {
+            "-c" => match parse_size(args.get(index + 1)) {
+                Some(size) => opts.chunk = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1),
+                None => return usage(args[0]),
+            },
+            "-l" => match parse_size(args.get(index + 1)) {
+                Some(size) => opts.limit = Some(size),
+                None => return usage(args[0]),
+            },
+            "-n" => {
+                opts.touch = false;
+                index += 1;
+                continue;
+            }
+            _ => return usage(args[0]),
+        }
+        index += 2;
+    }
+
+    print_status("Before");
+
+    // The allocation runs in a child so that it can be reported on even
+    // if the kernel kills it
+    match unsafe { sys_fork() } {
+        Ok(Some(pid)) => {
+            let mut status = 0;
+            if let Err(e) = sys_waitpid(pid, &mut status) {
+                eprintln!("waitpid(): {:?}", e);
+                return -1;
+            }
+            match status {
+                LIMIT_REACHED | MAP_FAILED => (),
+                _ => println!("Eater was killed, status {}", status),
+            }
+            print_status("After");
+            0
+        }
+        Ok(None) => {
+            // Memory is released by exiting
+            sys_exit(ExitCode::from(eat(&opts)));
+        }
+        Err(e) => {
+            eprintln!("fork(): {:?}", e);
+            -1
+        }
+    }
+}
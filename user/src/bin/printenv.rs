@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::env;
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = env::args();
+
+    if args.len() == 1 {
+        for (name, value) in env::vars() {
+            println!("{}={}", name, value);
+        }
+        return 0;
+    }
+
+    let mut res = 0;
+    for name in &args[1..] {
+        match env::var(name) {
+            Some(value) => println!("{}", value),
+            None => res = -1,
+        }
+    }
+    res
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::format;
+use libusr::process;
+use libusr::sys::{
+    proc::{Pid, ProcessMemory},
+    sys_ex_process_memory,
+};
+
+fn show_process(pid: Pid) {
+    // The process may have exited since the list was taken
+    let name = match process::name(pid) {
+        Ok(name) => name,
+        Err(_) => return,
+    };
+    let mut usage = ProcessMemory::default();
+    sys_ex_process_memory(pid, &mut usage).ok();
+
+    if pid.is_kernel() {
+        let id = u32::from(pid) & !(1 << 31);
+        println!("{:>5} {:>8} [{}]", format!("K{}", id), "-", name);
+    } else {
+        println!(
+            "{:>5} {:>7}K {}",
+            u32::from(pid),
+            usage.resident_pages * 4,
+            name
+        );
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 1 {
+        eprintln!("Usage: {}", args[0]);
+        return -1;
+    }
+
+    match process::list() {
+        Ok(pids) => {
+            println!("  PID      RSS NAME");
+            for pid in pids {
+                show_process(pid);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {:?}", args[0], e);
+            -1
+        }
+    }
+}
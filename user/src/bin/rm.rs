@@ -0,0 +1,94 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+#[macro_use]
+extern crate alloc;
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use libusr::sys::{
+    stat::{DirectoryEntry, FileMode, OpenFlags, Stat, AT_REMOVEDIR},
+    sys_close, sys_fstatat, sys_openat, sys_readdir, sys_unlinkat, Errno,
+};
+
+fn read_directory(path: &str) -> Result<Vec<String>, Errno> {
+    let mut buffer = [DirectoryEntry::empty(); 8];
+    let mut data = vec![];
+
+    let fd = sys_openat(
+        None,
+        path,
+        FileMode::default_dir(),
+        OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY,
+    )?;
+
+    loop {
+        let count = match sys_readdir(fd, &mut buffer) {
+            Ok(count) => count,
+            Err(e) => {
+                sys_close(fd).ok();
+                return Err(e);
+            }
+        };
+        if count == 0 {
+            break;
+        }
+
+        buffer.iter().take(count).for_each(|e| {
+            let name = e.as_str();
+            if name != "." && name != ".." {
+                data.push(name.to_owned());
+            }
+        });
+    }
+
+    sys_close(fd)?;
+    Ok(data)
+}
+
+fn remove(path: &str, recursive: bool) -> Result<(), Errno> {
+    let mut stat = Stat::default();
+    sys_fstatat(None, path, &mut stat, 0)?;
+
+    if stat.mode & FileMode::FILE_TYPE == FileMode::S_IFDIR {
+        if !recursive {
+            return Err(Errno::IsADirectory);
+        }
+
+        for entry in read_directory(path)? {
+            remove(&format!("{}/{}", path.trim_end_matches('/'), entry), true)?;
+        }
+
+        sys_unlinkat(None, path, AT_REMOVEDIR)
+    } else {
+        sys_unlinkat(None, path, 0)
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut res = 0;
+    let mut recursive = false;
+    let mut paths = &args[1..];
+
+    if paths.first() == Some(&"-r") {
+        recursive = true;
+        paths = &paths[1..];
+    }
+
+    if paths.is_empty() {
+        eprintln!("Usage: {} [-r] PATH...", args[0]);
+        return -1;
+    }
+
+    for path in paths {
+        if let Err(e) = remove(path, recursive) {
+            eprintln!("{}: {:?}", path, e);
+            res = -1;
+        }
+    }
+
+    res
+}
@@ -5,12 +5,16 @@
 extern crate libusr;
 extern crate alloc;
 
-use alloc::{borrow::ToOwned, vec::Vec};
-use libusr::io::{self, Read};
+use alloc::{format, string::String, vec::Vec};
+use libusr::env::{self, UserInfo};
+use libusr::file::File;
+use libusr::io::{self, BufReader};
+use libusr::process;
+use libusr::readline::LineEditor;
 use libusr::signal::{self, SignalHandler};
 use libusr::sys::{
-    sys_chdir, sys_execve, sys_exit, sys_faccessat, sys_fork, sys_getpgid, sys_setpgid,
-    sys_waitpid, AccessMode, Errno, ExitCode, FileDescriptor, Signal,
+    stat::UserId, sys_chdir, sys_exit, sys_fork, sys_getcwd, sys_geteuid, sys_getpgid, sys_setpgid,
+    sys_waitpid, Errno, ExitCode, FileDescriptor, Signal,
 };
 
 struct Builtin {
@@ -30,25 +34,68 @@ fn cmd_cd(args: &[&str]) -> ExitCode {
     }
 }
 
-static BUILTINS: [Builtin; 1] = [Builtin {
-    name: "cd",
-    func: cmd_cd,
-}];
+fn cmd_export(args: &[&str]) -> ExitCode {
+    if args.len() == 1 {
+        for (name, value) in env::vars() {
+            println!("export {}={}", name, value);
+        }
+        return ExitCode::from(0);
+    }
 
-fn readline<'a, F: Read>(f: &mut F, bytes: &'a mut [u8]) -> Result<Option<&'a str>, io::Error> {
-    let size = f.read(bytes)?;
-    Ok(if size == 0 {
-        None
-    } else {
-        Some(
-            core::str::from_utf8(&bytes[..size])
-                .unwrap()
-                .trim_end_matches('\n'),
-        )
-    })
+    let mut res = 0;
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            Some((name, value)) if !name.is_empty() => env::set_var(name, value),
+            _ => {
+                eprintln!("export: {}: expected NAME=VALUE", arg);
+                res = -1;
+            }
+        }
+    }
+    ExitCode::from(res)
+}
+
+fn cmd_unset(args: &[&str]) -> ExitCode {
+    for name in &args[1..] {
+        env::remove_var(name);
+    }
+    ExitCode::from(0)
+}
+
+static BUILTINS: [Builtin; 3] = [
+    Builtin {
+        name: "cd",
+        func: cmd_cd,
+    },
+    Builtin {
+        name: "export",
+        func: cmd_export,
+    },
+    Builtin {
+        name: "unset",
+        func: cmd_unset,
+    },
+];
+
+fn prompt() -> String {
+    let uid = sys_geteuid();
+    let user = UserInfo::find(|ent| ent.uid() == uid);
+    let mut buf = [0; 256];
+    let cwd = sys_getcwd(&mut buf)
+        .ok()
+        .and_then(|len| core::str::from_utf8(&buf[..len]).ok())
+        .unwrap_or("?");
+
+    let mark = if uid == UserId::root() { '#' } else { '>' };
+    match &user {
+        Ok(user) => format!("{}:{}{} ", user.name(), cwd, mark),
+        Err(_) => format!("{:?}:{}{} ", uid, cwd, mark),
+    }
 }
 
-fn execute(line: &str) -> Result<ExitCode, Errno> {
+/// Runs a command line. Interactive commands are put into their own
+/// process group in the foreground of the terminal.
+fn execute(line: &str, interactive: bool) -> Result<ExitCode, Errno> {
     // TODO proper arg handling
     let args: Vec<&str> = line.split(' ').collect();
     let cmd = args[0];
@@ -59,45 +106,80 @@ fn execute(line: &str) -> Result<ExitCode, Errno> {
         }
     }
 
-    let filename = "/bin/".to_owned() + cmd;
-    sys_faccessat(None, &filename, AccessMode::X_OK, 0)?;
+    let filename = process::resolve(cmd)?;
 
     if let Some(pid) = unsafe { sys_fork()? } {
         let mut status = 0;
         sys_waitpid(pid, &mut status)?;
-        let pgid = sys_getpgid(None).unwrap();
-        io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
+        if interactive {
+            let pgid = sys_getpgid(None).unwrap();
+            io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
+        }
         Ok(ExitCode::from(status))
     } else {
-        let pgid = sys_setpgid(None, None).unwrap();
-        io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
-        sys_execve(&filename, &args).unwrap();
+        if interactive {
+            let pgid = sys_setpgid(None, None).unwrap();
+            io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
+        }
+        let err = process::exec(&filename, &args);
+        eprintln!("{}: {:?}", cmd, err);
         sys_exit(ExitCode::from(-1));
     }
 }
 
+/// Runs the commands in a script file, returns the status of the last one
+fn run_script(path: &str) -> Result<ExitCode, io::Error> {
+    let mut status = ExitCode::from(0);
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim_start_matches(' ');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        status = match execute(line, false) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("{}: {:?}", line.split(' ').next().unwrap(), e);
+                ExitCode::from(-1)
+            }
+        };
+    }
+    Ok(status)
+}
+
 #[no_mangle]
 fn main() -> i32 {
-    let mut buf = [0; 256];
-    let mut stdin = io::stdin();
+    let args = env::args();
+    if args.len() > 1 {
+        return match run_script(args[1]) {
+            Ok(status) => status.into(),
+            Err(e) => {
+                eprintln!("{}: {:?}", args[1], e);
+                -1
+            }
+        };
+    }
+
+    let mut editor = LineEditor::new();
 
     signal::set_handler(Signal::Interrupt, SignalHandler::Ignore);
     let pgid = sys_setpgid(None, None).unwrap();
     io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
 
     loop {
-        print!("> ");
-        match readline(&mut stdin, &mut buf) {
+        match editor.read_line(&prompt()) {
             Ok(line) => {
-                if line.is_none() {
-                    break;
-                }
-                let line = line.unwrap().trim_start_matches(' ');
+                let line = match line {
+                    Some(line) => line,
+                    None => break,
+                };
+                let line = line.trim_start_matches(' ');
                 if line.is_empty() {
                     continue;
                 }
 
-                if let Err(e) = execute(line) {
+                if let Err(e) = execute(line, true) {
                     eprintln!("{}: {:?}", line.split(' ').next().unwrap(), e);
                 }
             }
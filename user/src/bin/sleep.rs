@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use core::time::Duration;
+use libusr::thread;
+
+/// Parses "N[.FRACTION][s|m|h]"
+fn parse_duration(text: &str) -> Option<Duration> {
+    let (number, unit) = match text.as_bytes().last()? {
+        b's' => (&text[..text.len() - 1], 1),
+        b'm' => (&text[..text.len() - 1], 60),
+        b'h' => (&text[..text.len() - 1], 3600),
+        _ => (text, 1),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if fraction.len() > 9 || !fraction.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let secs: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let mut nanos = 0u64;
+    for (i, digit) in fraction.bytes().enumerate() {
+        nanos += (digit - b'0') as u64 * 10u64.pow(8 - i as u32);
+    }
+    let total = Duration::new(secs, nanos as u32);
+    total.checked_mul(unit)
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 2 {
+        eprintln!("Usage: {} N[.FRACTION][s|m|h]", args[0]);
+        return -1;
+    }
+
+    match parse_duration(args[1]) {
+        Some(duration) => {
+            thread::sleep(duration);
+            0
+        }
+        None => {
+            eprintln!("{}: invalid time interval: {}", args[0], args[1]);
+            -1
+        }
+    }
+}
@@ -0,0 +1,184 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{format, string::String, vec::Vec};
+use core::time::Duration;
+use libusr::file::File;
+use libusr::io::{self, Read, Write};
+use libusr::sys::{
+    proc::Pid, sys_ex_clock_get, sys_exit, sys_fork, sys_getpid, sys_unlinkat, sys_waitpid,
+    time::ClockId, Errno, ExitCode,
+};
+use libusr::thread;
+
+const IO_BLOCK: usize = 4096;
+const IO_BLOCKS: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+enum Load {
+    Cpu,
+    Fork,
+    Io,
+}
+
+struct Worker {
+    load: Load,
+    index: usize,
+    pid: Pid,
+}
+
+fn now() -> Duration {
+    sys_ex_clock_get(ClockId::Monotonic).unwrap()
+}
+
+fn cpu_round(seed: &mut u64) {
+    for _ in 0..10000 {
+        // xorshift, just something the compiler can't skip
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+    }
+}
+
+fn fork_round() -> Result<(), Errno> {
+    match unsafe { sys_fork()? } {
+        Some(pid) => {
+            let mut status = 0;
+            sys_waitpid(pid, &mut status)
+        }
+        None => sys_exit(ExitCode::from(0)),
+    }
+}
+
+fn io_round(path: &str, block: &mut [u8]) -> Result<(), io::Error> {
+    {
+        let mut file = File::create(path)?;
+        for _ in 0..IO_BLOCKS {
+            if file.write(block)? != block.len() {
+                return Err(Errno::NoSpace.into());
+            }
+        }
+        file.sync_all()?;
+    }
+    {
+        let mut file = File::open(path)?;
+        while file.read(block)? != 0 {}
+    }
+    sys_unlinkat(None, path, 0)?;
+    Ok(())
+}
+
+/// Runs one kind of load until `deadline`, returns the number of rounds
+/// done
+fn run(load: Load, deadline: Duration, dir: &str) -> usize {
+    let mut rounds = 0;
+    let mut failures = 0;
+    let mut seed = 0x2545F4914F6CDD1D;
+    let mut block = [0x5A; IO_BLOCK];
+    let path = format!("{}/stress.{}", dir, u32::from(sys_getpid()));
+
+    while now() < deadline {
+        let res = match load {
+            Load::Cpu => {
+                cpu_round(&mut seed);
+                Ok(())
+            }
+            Load::Fork => fork_round().map_err(io::Error::from),
+            Load::Io => io_round(&path, &mut block),
+        };
+        match res {
+            Ok(()) => rounds += 1,
+            Err(e) => {
+                // Report the first few failures only, then back off
+                if failures < 4 {
+                    eprintln!("{:?} worker: {:?}", load, e);
+                }
+                failures += 1;
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    if matches!(load, Load::Io) {
+        sys_unlinkat(None, &path, 0).ok();
+    }
+    if failures != 0 {
+        eprintln!("{:?} worker: {} rounds failed", load, failures);
+    }
+    rounds
+}
+
+fn usage(name: &str) -> i32 {
+    eprintln!(
+        "Usage: {} [-c CPU] [-f FORK] [-i IO] [-d IO_DIR] [-t SECONDS]",
+        name
+    );
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut counts = [(Load::Cpu, 0), (Load::Fork, 0), (Load::Io, 0)];
+    let mut dir = String::from("/");
+    let mut seconds = 10;
+
+    let mut index = 1;
+    while index < args.len() {
+        let value = match args.get(index + 1) {
+            Some(value) => *value,
+            None => return usage(args[0]),
+        };
+        let count = value.parse::<usize>();
+        match (args[index], count) {
+            ("-c", Ok(count)) => counts[0].1 = count,
+            ("-f", Ok(count)) => counts[1].1 = count,
+            ("-i", Ok(count)) => counts[2].1 = count,
+            ("-t", Ok(count)) if count != 0 => seconds = count as u64,
+            ("-d", _) => dir = String::from(value.trim_end_matches('/')),
+            _ => return usage(args[0]),
+        }
+        index += 2;
+    }
+    if counts.iter().all(|&(_, count)| count == 0) {
+        counts[0].1 = 1;
+    }
+
+    let deadline = now() + Duration::from_secs(seconds);
+    let mut workers = Vec::new();
+    for &(load, count) in &counts {
+        for index in 0..count {
+            match unsafe { sys_fork() } {
+                Ok(Some(pid)) => workers.push(Worker { load, index, pid }),
+                Ok(None) => {
+                    let rounds = run(load, deadline, &dir);
+                    sys_exit(ExitCode::from(rounds.min(i32::MAX as usize) as i32));
+                }
+                Err(e) => eprintln!("Failed to start {:?} worker: {:?}", load, e),
+            }
+        }
+    }
+    println!("Running {} workers for {}s", workers.len(), seconds);
+
+    let mut res = 0;
+    for worker in workers {
+        let mut status = 0;
+        let name = format!("{:?}#{}", worker.load, worker.index);
+        match sys_waitpid(worker.pid, &mut status) {
+            Ok(()) if status >= 0 => println!("{}: {} rounds", name, status),
+            Ok(()) => {
+                println!("{}: killed, status {}", name, status);
+                res = -1;
+            }
+            Err(e) => {
+                println!("{}: waitpid(): {:?}", name, e);
+                res = -1;
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::io;
+use libusr::sys::{stat::FileDescriptor, Errno};
+
+fn parse(value: &str) -> Result<u16, Errno> {
+    value.parse().map_err(|_| Errno::InvalidArgument)
+}
+
+fn do_stty(args: &[&str]) -> Result<(), Errno> {
+    let fd = FileDescriptor::STDIN;
+    match args {
+        [] => {
+            let termios = io::tcgetattr(fd)?;
+            let winsize = io::tcgetwinsize(fd)?;
+            println!(
+                "speed {} baud; rows {}; columns {}",
+                termios.speed, winsize.rows, winsize.cols
+            );
+            Ok(())
+        }
+        ["size"] => {
+            let winsize = io::tcgetwinsize(fd)?;
+            println!("{} {}", winsize.rows, winsize.cols);
+            Ok(())
+        }
+        ["rows", rows, "cols", cols] => {
+            let mut winsize = io::tcgetwinsize(fd)?;
+            winsize.rows = parse(rows)?;
+            winsize.cols = parse(cols)?;
+            io::tcsetwinsize(fd, &winsize)
+        }
+        _ => Err(Errno::InvalidArgument),
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if !io::isatty(FileDescriptor::STDIN) {
+        eprintln!("{}: standard input is not a terminal", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = do_stty(&args[1..]) {
+        eprintln!("{}: {:?}", args[0], e);
+        eprintln!("Usage: {} [size | rows ROWS cols COLUMNS]", args[0]);
+        -1
+    } else {
+        0
+    }
+}
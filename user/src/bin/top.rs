@@ -0,0 +1,132 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+use libusr::io;
+use libusr::process;
+use libusr::sys::{
+    proc::{CpuStatistics, MemoryStatus, ProcessMemory},
+    stat::FileDescriptor,
+    sys_ex_cpu_statistics, sys_ex_memory_status, sys_ex_process_memory, Errno,
+};
+use libusr::thread;
+
+fn cpu_statistics() -> Result<Vec<CpuStatistics>, Errno> {
+    let mut stats = vec![CpuStatistics::default(); 4];
+    let count = sys_ex_cpu_statistics(&mut stats)?;
+    if count > stats.len() {
+        stats.resize(count, CpuStatistics::default());
+        sys_ex_cpu_statistics(&mut stats)?;
+    }
+    stats.truncate(count);
+    Ok(stats)
+}
+
+/// Returns `part` as a percentage of `total` with one decimal place
+fn percent(part: u64, total: u64) -> (u64, u64) {
+    let permille = (part * 1000).checked_div(total).unwrap_or(0);
+    (permille / 10, permille % 10)
+}
+
+fn show_cpu(index: usize, prev: &CpuStatistics, cur: &CpuStatistics, interval: Duration) {
+    let user = cur.user_ns - prev.user_ns;
+    let kernel = cur.kernel_ns - prev.kernel_ns;
+    let idle = cur.idle_ns - prev.idle_ns;
+    let total = user + kernel + idle;
+    let switches = (cur.switches - prev.switches) * 1000 / (interval.as_millis() as u64).max(1);
+
+    let (user, user_frac) = percent(user, total);
+    let (kernel, kernel_frac) = percent(kernel, total);
+    let (idle, idle_frac) = percent(idle, total);
+    println!(
+        "CPU{}: {:>3}.{}% user {:>3}.{}% kernel {:>3}.{}% idle, {} switches/s",
+        index, user, user_frac, kernel, kernel_frac, idle, idle_frac, switches
+    );
+}
+
+fn show_processes() -> Result<(), Errno> {
+    println!("\n  PID      RSS NAME");
+    for pid in process::list()? {
+        // Kernel threads own no memory and would only clutter the list
+        if pid.is_kernel() {
+            continue;
+        }
+        let name = match process::name(pid) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let mut usage = ProcessMemory::default();
+        sys_ex_process_memory(pid, &mut usage).ok();
+        println!(
+            "{:>5} {:>7}K {}",
+            u32::from(pid),
+            usage.resident_pages * 4,
+            name
+        );
+    }
+    Ok(())
+}
+
+fn run(interval: Duration, iterations: Option<usize>) -> Result<(), Errno> {
+    let clear = io::isatty(FileDescriptor::STDOUT);
+    let mut prev = cpu_statistics()?;
+    let mut count = 0;
+
+    while iterations.map_or(true, |limit| count < limit) {
+        thread::sleep(interval);
+        let cur = cpu_statistics()?;
+        let mut mem = MemoryStatus::default();
+        sys_ex_memory_status(&mut mem)?;
+
+        if clear {
+            print!("\x1B[H\x1B[2J");
+        }
+        for (index, (prev, cur)) in prev.iter().zip(cur.iter()).enumerate() {
+            show_cpu(index, prev, cur, interval);
+        }
+        println!(
+            "Mem: {}K total, {}K free, {}K cached",
+            mem.total_pages * 4,
+            mem.available_pages * 4,
+            mem.cached_pages * 4
+        );
+        show_processes()?;
+
+        prev = cur;
+        count += 1;
+    }
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut interval = Duration::from_secs(2);
+    let mut iterations = None;
+
+    let mut rest = &args[1..];
+    while !rest.is_empty() {
+        let value = rest.get(1).and_then(|value| value.parse::<u64>().ok());
+        match (rest[0], value) {
+            ("-d", Some(secs)) if secs > 0 => interval = Duration::from_secs(secs),
+            ("-n", Some(count)) => iterations = Some(count as usize),
+            _ => {
+                eprintln!("Usage: {} [-d SECONDS] [-n ITERATIONS]", args[0]);
+                return -1;
+            }
+        }
+        rest = &rest[2..];
+    }
+
+    if let Err(e) = run(interval, iterations) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use core::time::Duration;
+use libusr::thread;
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 2 {
+        eprintln!("Usage: {} MICROSECONDS", args[0]);
+        return -1;
+    }
+
+    match args[1].parse() {
+        Ok(us) => {
+            thread::sleep(Duration::from_micros(us));
+            0
+        }
+        Err(_) => {
+            eprintln!("{}: invalid time interval: {}", args[0], args[1]);
+            -1
+        }
+    }
+}
@@ -0,0 +1,25 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::process;
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() < 2 {
+        eprintln!("Usage: {} COMMAND...", args[0]);
+        return -1;
+    }
+
+    let mut res = 0;
+    for cmd in &args[1..] {
+        match process::resolve(cmd) {
+            Ok(path) => println!("{}", path),
+            Err(_) => res = -1,
+        }
+    }
+    res
+}
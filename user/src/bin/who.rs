@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use libusr::file::File;
+use libusr::io;
+use libusr::sys::time::DateTime;
+use libusr::utmp::{self, Record, RecordKind};
+
+fn show() -> Result<(), io::Error> {
+    let records = utmp::read_records(File::open(utmp::UTMP_PATH)?)?;
+
+    let mut sessions: Vec<Record> = Vec::new();
+    for record in records {
+        if record.kind() == RecordKind::Login {
+            sessions.push(record);
+        } else {
+            sessions.retain(|session| !session.is_ended_by(&record));
+        }
+    }
+
+    for session in sessions {
+        let t = DateTime::from_timestamp(session.time());
+        println!(
+            "{:<10} {:<10} {}-{:02}-{:02} {:02}:{:02}",
+            session.user(),
+            session.tty(),
+            t.year,
+            t.month,
+            t.day,
+            t.hour,
+            t.minute
+        );
+    }
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    if args.len() != 1 {
+        eprintln!("Usage: {}", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = show() {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
@@ -1,22 +1,64 @@
-#![feature(asm)]
 #![no_std]
 #![no_main]
 
 #[macro_use]
 extern crate libusr;
 
-use libusr::sys::{stat::MountOptions, sys_execve, sys_fork, sys_mount, sys_waitpid};
+use core::time::Duration;
+use libusr::sys::{
+    stat::{MountFlags, MountOptions},
+    sys_execve, sys_exit, sys_fork, sys_mount, sys_waitpid, Errno, ExitCode,
+};
+use libusr::thread;
+use libusr::utmp::{self, Record};
+
+/// Size limit of /tmp
+const TMP_SIZE: usize = 16 << 20;
+
+/// Starts a background service, without waiting for it
+fn spawn(path: &str, argv: &[&str]) {
+    match unsafe { sys_fork() } {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            if let Err(e) = sys_execve(path, argv, &[]) {
+                eprintln!("{}: {:?}", path, e);
+            }
+            sys_exit(ExitCode::from(-1));
+        }
+        Err(e) => eprintln!("Failed to start {}: {:?}", path, e),
+    }
+}
 
 #[no_mangle]
 fn main() -> i32 {
-    sys_mount(
+    // Already mounted by the kernel when booting from a root= filesystem
+    match sys_mount(
         "/dev",
         &MountOptions {
             device: None,
             fs: Some("devfs"),
+            flags: MountFlags::MS_NOEXEC,
+            size: 0,
         },
-    )
-    .expect("Failed to mount devfs");
+    ) {
+        Ok(()) | Err(Errno::Busy) => (),
+        Err(e) => panic!("Failed to mount devfs: {:?}", e),
+    }
+    if let Err(e) = sys_mount(
+        "/tmp",
+        &MountOptions {
+            device: None,
+            fs: Some("tmpfs"),
+            flags: MountFlags::MS_NOSUID,
+            size: TMP_SIZE,
+        },
+    ) {
+        eprintln!("Failed to mount /tmp: {:?}", e);
+    }
+
+    utmp::append(&Record::boot()).ok();
+    spawn("/sbin/crashd", &["/sbin/crashd"]);
+    spawn("/sbin/tmpclean", &["/sbin/tmpclean"]);
 
     if let Some(pid) = unsafe { sys_fork().unwrap() } {
         let mut status = 0;
@@ -24,12 +66,10 @@ fn main() -> i32 {
         println!("Process {:?} exited with status {}", pid, status);
 
         loop {
-            unsafe {
-                asm!("nop");
-            }
+            thread::sleep(Duration::from_secs(60));
         }
     } else {
-        sys_execve("/sbin/login", &["/sbin/login", "/dev/ttyS0"]).unwrap();
+        sys_execve("/sbin/login", &["/sbin/login", "/dev/ttyS0"], &[]).unwrap();
         unreachable!();
     }
 }
@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{format, string::String};
+use core::mem::size_of;
+use libusr::io;
+use libusr::sys::{
+    proc::CrashRecord,
+    stat::{FileDescriptor, FileMode, OpenFlags},
+    sys_close, sys_openat, sys_read, sys_unlinkat, sys_write,
+    time::DateTime,
+    Errno, Signal,
+};
+
+const CRASH_DEVICE: &str = "/dev/crash";
+const DEFAULT_LOG: &str = "/var/log/crash.log";
+/// Default size limit of the report log, in KiB
+const DEFAULT_QUOTA: u64 = 64;
+
+fn read_record(fd: FileDescriptor) -> Result<CrashRecord, Errno> {
+    let mut buf = [0; size_of::<CrashRecord>()];
+    loop {
+        match sys_read(fd, &mut buf) {
+            Ok(len) if len == buf.len() => break,
+            Ok(_) => return Err(Errno::InvalidArgument),
+            Err(Errno::Interrupt) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const CrashRecord) })
+}
+
+fn format_report(record: &CrashRecord) -> String {
+    let t = DateTime::from_timestamp(record.time);
+    let signal = match Signal::try_from(record.signal) {
+        Ok(signal) => format!("{:?}", signal),
+        Err(_) => format!("{}", record.signal),
+    };
+    let mut out = format!(
+        "=== {}-{:02}-{:02} {:02}:{:02}:{:02}: {} (pid {}, thread {}) crashed\n\
+         Signal: {}, ESR {:#010x}\n\
+         Fault address: {:#018x}\n\
+         pc  {:#018x} sp  {:#018x} pstate {:#010x}\n",
+        t.year,
+        t.month,
+        t.day,
+        t.hour,
+        t.minute,
+        t.second,
+        record.name,
+        record.pid,
+        record.tid,
+        signal,
+        record.esr,
+        record.far,
+        record.pc,
+        record.sp,
+        record.pstate
+    );
+    for (index, value) in record.x.iter().enumerate() {
+        out.push_str(&format!("{:<3} {:#018x}", format!("x{}", index), value));
+        let last = index % 3 == 2 || index == record.x.len() - 1;
+        out.push(if last { '\n' } else { ' ' });
+    }
+    out.push('\n');
+    out
+}
+
+fn append_report(log: &str, quota: u64, report: &str) -> Result<(), Errno> {
+    // Older reports are dropped as a whole when the log would exceed its quota
+    if let Ok(stat) = io::stat(log) {
+        if stat.size + report.len() as u64 > quota {
+            sys_unlinkat(None, log, 0)?;
+        }
+    }
+    let fd = sys_openat(
+        None,
+        log,
+        FileMode::default_reg(),
+        OpenFlags::O_WRONLY | OpenFlags::O_CREAT | OpenFlags::O_APPEND,
+    )?;
+    let res = sys_write(fd, report.as_bytes());
+    sys_close(fd).ok();
+    res.map(|_| ())
+}
+
+fn run(log: &str, quota: u64) -> Result<(), Errno> {
+    let fd = sys_openat(None, CRASH_DEVICE, FileMode::empty(), OpenFlags::O_RDONLY)?;
+    loop {
+        let record = read_record(fd)?;
+        let report = format_report(&record);
+        if let Err(e) = append_report(log, quota, &report) {
+            eprintln!("crashd: {}: {:?}", log, e);
+            print!("{}", report);
+        }
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut log = DEFAULT_LOG;
+    let mut quota = DEFAULT_QUOTA;
+
+    let mut rest = &args[1..];
+    while !rest.is_empty() {
+        let kib = rest.get(1).and_then(|value| value.parse::<u64>().ok());
+        match (rest[0], rest.get(1), kib) {
+            ("-o", Some(&path), _) => log = path,
+            ("-s", _, Some(kib)) if kib > 0 => quota = kib,
+            _ => {
+                eprintln!("Usage: {} [-o LOG] [-s QUOTA_KIB]", args[0]);
+                return -1;
+            }
+        }
+        rest = &rest[2..];
+    }
+
+    if let Err(e) = run(log, quota * 1024) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
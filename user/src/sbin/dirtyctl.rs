@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{proc::DirtyLimits, sys_ex_dirty_limits, sys_ex_set_dirty_limits, Errno};
+
+fn show_limits() -> Result<(), Errno> {
+    let mut limits = DirtyLimits::default();
+    sys_ex_dirty_limits(&mut limits)?;
+    println!("Throttle:   {}%", limits.throttle_ratio);
+    println!("Background: {}%", limits.background_ratio);
+    println!("Process:    {}%", limits.process_ratio);
+    Ok(())
+}
+
+fn parse(value: &str) -> Result<usize, Errno> {
+    value.parse().map_err(|_| Errno::InvalidArgument)
+}
+
+fn do_dirtyctl(args: &[&str]) -> Result<(), Errno> {
+    match args {
+        [] => show_limits(),
+        [throttle, background, process] => sys_ex_set_dirty_limits(&DirtyLimits {
+            throttle_ratio: parse(throttle)?,
+            background_ratio: parse(background)?,
+            process_ratio: parse(process)?,
+        }),
+        _ => Err(Errno::InvalidArgument),
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if args.len() != 1 && args.len() != 4 {
+        eprintln!("Usage: {} [THROTTLE BACKGROUND PROCESS]", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = do_dirtyctl(&args[1..]) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
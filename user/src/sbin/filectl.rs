@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{
+    stat::{FileLimits, FileUsage},
+    sys_ex_file_limits, sys_ex_file_usage, sys_ex_set_file_limits, Errno,
+};
+
+fn show_limits() -> Result<(), Errno> {
+    let mut limits = FileLimits::default();
+    let mut usage = FileUsage::default();
+    sys_ex_file_limits(&mut limits)?;
+    sys_ex_file_usage(&mut usage)?;
+    println!("Files:  {:>6} / {}", usage.files, limits.max_files);
+    println!("Vnodes: {:>6} / {}", usage.vnodes, limits.max_vnodes);
+    println!("Pipes:  {:>6} / {}", usage.pipes, limits.max_pipes);
+    Ok(())
+}
+
+fn parse(value: &str) -> Result<usize, Errno> {
+    value.parse().map_err(|_| Errno::InvalidArgument)
+}
+
+fn do_filectl(args: &[&str]) -> Result<(), Errno> {
+    match args {
+        [] => show_limits(),
+        [files, vnodes, pipes] => sys_ex_set_file_limits(&FileLimits {
+            max_files: parse(files)?,
+            max_vnodes: parse(vnodes)?,
+            max_pipes: parse(pipes)?,
+        }),
+        _ => Err(Errno::InvalidArgument),
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if args.len() != 1 && args.len() != 4 {
+        eprintln!("Usage: {} [FILES VNODES PIPES]", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = do_filectl(&args[1..]) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
@@ -3,18 +3,19 @@
 
 #[macro_use]
 extern crate libusr;
+extern crate alloc;
 
 use libsys::{
     calls::{
-        sys_close, sys_dup, sys_fork, sys_getgid, sys_getpgid, sys_getuid, sys_ioctl, sys_openat,
+        sys_close, sys_dup, sys_fork, sys_getgid, sys_getpgid, sys_getuid, sys_openat,
         sys_read, sys_setgid, sys_setpgid, sys_setsid, sys_setuid, sys_waitpid, sys_execve
     },
     error::Errno,
-    ioctl::IoctlCmd,
-    stat::{FileDescriptor, FileMode, GroupId, OpenFlags, UserId},
+    stat::{FileDescriptor, FileMode, OpenFlags},
     termios::{Termios, TermiosLflag},
 };
-use libusr::{env::{self, UserInfo, UserShadow}, io};
+use libusr::{env::{self, UserInfo, UserShadow}, io, utmp::{self, Record}};
+use alloc::{string::String, vec::Vec};
 use core::str::FromStr;
 
 struct HiddenInput {
@@ -24,24 +25,11 @@ struct HiddenInput {
 
 impl HiddenInput {
     fn open(fd: FileDescriptor) -> Result<Self, Errno> {
-        use core::mem::{size_of, MaybeUninit};
-        let mut termios: MaybeUninit<Termios> = MaybeUninit::uninit();
-        sys_ioctl(
-            fd,
-            IoctlCmd::TtyGetAttributes,
-            termios.as_mut_ptr() as usize,
-            size_of::<Termios>(),
-        )?;
-        let termios = unsafe { termios.assume_init() };
+        let termios = io::tcgetattr(fd)?;
 
         let mut new_termios = termios.clone();
         new_termios.lflag &= !(TermiosLflag::ECHO | TermiosLflag::ECHOK | TermiosLflag::ECHOE);
-        sys_ioctl(
-            fd,
-            IoctlCmd::TtySetAttributes,
-            &new_termios as *const _ as usize,
-            size_of::<Termios>(),
-        )?;
+        io::tcsetattr(fd, &new_termios)?;
 
         Ok(Self { fd, termios })
     }
@@ -53,14 +41,7 @@ impl HiddenInput {
 
 impl Drop for HiddenInput {
     fn drop(&mut self) {
-        use core::mem::size_of;
-        sys_ioctl(
-            self.fd,
-            IoctlCmd::TtySetAttributes,
-            &self.termios as *const _ as usize,
-            size_of::<Termios>(),
-        )
-        .ok();
+        io::tcsetattr(self.fd, &self.termios).ok();
     }
 }
 
@@ -74,26 +55,40 @@ fn readline(fd: FileDescriptor, buf: &mut [u8]) -> Result<&str, Errno> {
     }
 }
 
-fn login(uid: UserId, gid: GroupId, shell: &str) -> Result<(), Errno> {
+fn login(ent: &UserInfo, tty: &str) -> Result<(), Errno> {
     if let Some(pid) = unsafe { sys_fork() }? {
+        // Accounting failures (e.g. a read-only root) do not prevent logins
+        utmp::append(&Record::login(ent.name(), tty, pid)).ok();
         let mut status = 0;
         sys_waitpid(pid, &mut status).ok();
+        utmp::append(&Record::logout(tty, pid)).ok();
         let pgid = sys_getpgid(None).unwrap();
         io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
         Ok(())
     } else {
-        sys_setuid(uid).expect("setuid failed");
-        sys_setgid(gid).expect("setgid failed");
+        // Group has to be changed first: setuid() drops the privileges
+        sys_setgid(ent.gid()).expect("setgid failed");
+        sys_setuid(ent.uid()).expect("setuid failed");
         let pgid = sys_setpgid(None, None).unwrap();
         io::tcsetpgrp(FileDescriptor::STDIN, pgid).unwrap();
-        sys_execve(shell, &[shell]).expect("execve() failed");
+
+        env::clear_vars();
+        env::set_var("PATH", env::DEFAULT_PATH);
+        env::set_var("HOME", ent.home());
+        env::set_var("USER", ent.name());
+        env::set_var("SHELL", ent.shell());
+        let envp = env::envp();
+        let envp: Vec<&str> = envp.iter().map(String::as_str).collect();
+
+        let shell = ent.shell();
+        sys_execve(shell, &[shell], &envp).expect("execve() failed");
         panic!();
     }
 }
 
-fn login_as(name: &str) -> Result<(), Errno> {
+fn login_as(name: &str, tty: &str) -> Result<(), Errno> {
     let ent = UserInfo::by_name(name).map_err(|_| Errno::DoesNotExist)?;
-    login(ent.uid(), ent.gid(), ent.shell())
+    login(&ent, tty)
 }
 
 // TODO baud rate and misc port settings
@@ -158,6 +153,6 @@ fn main() -> i32 {
             }
         }
 
-        login_as(username);
+        login_as(username, args[1]);
     }
 }
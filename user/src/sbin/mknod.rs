@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{
+    stat::{makedev, FileMode},
+    sys_mknodat, Errno,
+};
+
+fn parse_num(s: &str) -> Result<u32, Errno> {
+    s.parse().map_err(|_| Errno::InvalidArgument)
+}
+
+fn do_mknod(args: &[&str]) -> Result<(), Errno> {
+    let (kind, dev) = match args {
+        [_, "p"] => (FileMode::S_IFIFO, 0),
+        [_, "c", major, minor] => (FileMode::S_IFCHR, makedev(parse_num(major)?, parse_num(minor)?)),
+        [_, "b", major, minor] => (FileMode::S_IFBLK, makedev(parse_num(major)?, parse_num(minor)?)),
+        _ => return Err(Errno::InvalidArgument),
+    };
+    let mode = FileMode::from_bits(0o644).unwrap() | kind;
+
+    sys_mknodat(None, args[0], mode, dev)
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if args.len() < 3 {
+        eprintln!("Usage: {} NAME p | NAME c|b MAJOR MINOR", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = do_mknod(&args[1..]) {
+        eprintln!("{}: {:?}", args[1], e);
+        -1
+    } else {
+        0
+    }
+}
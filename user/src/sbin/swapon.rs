@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libusr::sys::{
+    proc::SwapStatus, sys_ex_set_swappiness, sys_ex_swap_status, sys_ex_swapon, Errno,
+};
+
+fn show_status() -> Result<(), Errno> {
+    let mut status = SwapStatus::default();
+    sys_ex_swap_status(&mut status)?;
+    println!("Total:      {}K", status.total_pages * 4);
+    println!("Used:       {}K", status.used_pages * 4);
+    println!("Swappiness: {}", status.swappiness);
+    println!("Page-ins:   {}", status.page_ins);
+    println!("Page-outs:  {}", status.page_outs);
+    Ok(())
+}
+
+fn do_swapon(args: &[&str]) -> Result<(), Errno> {
+    match args {
+        ["-s"] => show_status(),
+        ["-p", value] => {
+            sys_ex_set_swappiness(value.parse().map_err(|_| Errno::InvalidArgument)?)
+        }
+        [dev] => sys_ex_swapon(dev),
+        _ => Err(Errno::InvalidArgument),
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+
+    if args.len() < 2 {
+        eprintln!("Usage: {} DEVICE | -s | -p SWAPPINESS", args[0]);
+        return -1;
+    }
+
+    if let Err(e) = do_swapon(&args[1..]) {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
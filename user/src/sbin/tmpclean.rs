@@ -0,0 +1,179 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::time::Duration;
+use libusr::sys::{
+    stat::{DirectoryEntryPlus, FileMode, OpenFlags, AT_REMOVEDIR},
+    sys_close, sys_ex_clock_get, sys_openat, sys_readdir_plus, sys_unlinkat,
+    time::ClockId,
+    Errno,
+};
+use libusr::thread;
+
+const DEFAULT_DIR: &str = "/tmp";
+/// Default age after which entries are removed, in seconds
+const DEFAULT_AGE: u64 = 24 * 3600;
+/// Default interval between scans, in seconds
+const DEFAULT_INTERVAL: u64 = 3600;
+
+/// Entry size and the time it was first seen with that size. Files carry no
+/// timestamps, so this is how long an entry is known to be unmodified.
+type Seen = BTreeMap<String, (u64, Duration)>;
+
+fn read_directory(path: &str) -> Result<Vec<DirectoryEntryPlus>, Errno> {
+    let mut buffer = [DirectoryEntryPlus::empty(); 8];
+    let mut data = Vec::new();
+
+    let fd = sys_openat(
+        None,
+        path,
+        FileMode::default_dir(),
+        OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY,
+    )?;
+
+    loop {
+        let count = match sys_readdir_plus(fd, &mut buffer) {
+            Ok(count) => count,
+            Err(e) => {
+                sys_close(fd).ok();
+                return Err(e);
+            }
+        };
+        if count == 0 {
+            break;
+        }
+        data.extend(
+            buffer[..count]
+                .iter()
+                .filter(|e| e.as_str() != "." && e.as_str() != "..")
+                .copied(),
+        );
+    }
+
+    sys_close(fd)?;
+    Ok(data)
+}
+
+fn is_directory(entry: &DirectoryEntryPlus) -> bool {
+    entry.stat().map_or(false, |stat| {
+        stat.mode & FileMode::FILE_TYPE == FileMode::S_IFDIR
+    })
+}
+
+/// Removes everything below `dir`
+fn remove_all(dir: &str) -> Result<(), Errno> {
+    for entry in read_directory(dir)? {
+        let path = format!("{}/{}", dir.trim_end_matches('/'), entry.as_str());
+        let res = if is_directory(&entry) {
+            remove_all(&path).and_then(|_| sys_unlinkat(None, &path, AT_REMOVEDIR))
+        } else {
+            sys_unlinkat(None, &path, 0)
+        };
+        if let Err(e) = res {
+            eprintln!("tmpclean: {}: {:?}", path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Removes entries below `dir` which stayed unmodified for `age`, records
+/// the remaining ones in `next`
+fn clean(dir: &str, now: Duration, age: Duration, seen: &Seen, next: &mut Seen) {
+    let entries = match read_directory(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("tmpclean: {}: {:?}", dir, e);
+            return;
+        }
+    };
+
+    for entry in entries {
+        let path = format!("{}/{}", dir.trim_end_matches('/'), entry.as_str());
+        let size = match entry.stat() {
+            Some(stat) => stat.size,
+            None => continue,
+        };
+        let directory = is_directory(&entry);
+        if directory {
+            clean(&path, now, age, seen, next);
+        }
+
+        let since = match seen.get(&path) {
+            Some(&(last_size, since)) if last_size == size => since,
+            _ => now,
+        };
+        if now - since >= age {
+            let flags = if directory { AT_REMOVEDIR } else { 0 };
+            // Open files and non-empty directories are left for later
+            if sys_unlinkat(None, &path, flags).is_ok() {
+                continue;
+            }
+        }
+        next.insert(path, (size, since));
+    }
+}
+
+fn run(dir: &str, age: Duration, interval: Duration) -> Result<(), Errno> {
+    let mut seen = Seen::new();
+    loop {
+        let now = sys_ex_clock_get(ClockId::Monotonic)?;
+        let mut next = Seen::new();
+        clean(dir, now, age, &seen, &mut next);
+        seen = next;
+        thread::sleep(interval);
+    }
+}
+
+fn usage(name: &str) -> i32 {
+    eprintln!(
+        "Usage: {} [-o] [-a AGE_SECONDS] [-i INTERVAL_SECONDS] [DIR]",
+        name
+    );
+    -1
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let mut once = false;
+    let mut age = DEFAULT_AGE;
+    let mut interval = DEFAULT_INTERVAL;
+    let mut dir = DEFAULT_DIR;
+
+    let mut rest = &args[1..];
+    while !rest.is_empty() {
+        if rest[0] == "-o" {
+            once = true;
+            rest = &rest[1..];
+            continue;
+        }
+        if !rest[0].starts_with('-') && rest.len() == 1 {
+            dir = rest[0];
+            break;
+        }
+        let value = rest.get(1).and_then(|value| value.parse::<u64>().ok());
+        match (rest[0], value) {
+            ("-a", Some(secs)) => age = secs,
+            ("-i", Some(secs)) if secs > 0 => interval = secs,
+            _ => return usage(args[0]),
+        }
+        rest = &rest[2..];
+    }
+
+    let res = if once {
+        remove_all(dir)
+    } else {
+        run(dir, Duration::from_secs(age), Duration::from_secs(interval))
+    };
+    if let Err(e) = res {
+        eprintln!("{}: {:?}", args[0], e);
+        -1
+    } else {
+        0
+    }
+}
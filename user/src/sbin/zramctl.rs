@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libusr;
+
+use libsys::{
+    calls::{sys_close, sys_ioctl_get, sys_openat},
+    error::Errno,
+    ioctl::{IoctlCmd, ZramStats},
+    stat::{FileMode, OpenFlags},
+};
+
+fn show_stats(path: &str) -> Result<(), Errno> {
+    let fd = sys_openat(None, path, FileMode::empty(), OpenFlags::O_RDONLY)?;
+    let mut stats = ZramStats::default();
+    let res = sys_ioctl_get(fd, IoctlCmd::ZramGetStats, &mut stats);
+    sys_close(fd).ok();
+    res?;
+
+    let used = stats.compressed_size + stats.raw_pages * 4096;
+    println!("Data:       {}K", stats.data_size / 1024);
+    println!("Memory:     {}K", used / 1024);
+    if used != 0 {
+        let ratio = stats.data_size * 100 / used;
+        println!("Ratio:      {}.{:02}", ratio / 100, ratio % 100);
+    }
+    println!("Same-fill:  {} pages", stats.same_pages);
+    println!("Compressed: {} pages", stats.compressed_pages);
+    println!("Raw:        {} pages", stats.raw_pages);
+    Ok(())
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let args = libusr::env::args();
+    let path = args.get(1).copied().unwrap_or("/dev/zram0");
+
+    if let Err(e) = show_stats(path) {
+        eprintln!("{}: {:?}", path, e);
+        -1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,94 @@
+//! Program arguments and environment variables
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::slice;
+
+pub use libusr::env::{remove_var, set_var};
+
+/// Iterator over the program arguments, see [args]
+pub struct Args {
+    inner: slice::Iter<'static, &'static str>,
+}
+
+/// Iterator over the environment variables, see [vars]
+pub struct Vars {
+    inner: alloc::vec::IntoIter<(String, String)>,
+}
+
+/// Error returned by [var]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VarError {
+    NotPresent,
+}
+
+/// Returns the arguments the program was started with, including the
+/// program name
+pub fn args() -> Args {
+    Args {
+        inner: libusr::env::args().iter(),
+    }
+}
+
+/// Returns the value of environment variable `key`
+pub fn var(key: &str) -> Result<String, VarError> {
+    libusr::env::var(key).ok_or(VarError::NotPresent)
+}
+
+/// Returns a snapshot of the environment variables
+pub fn vars() -> Vars {
+    Vars {
+        inner: libusr::env::vars().into_iter(),
+    }
+}
+
+/// Returns the current working directory
+pub fn current_dir() -> crate::io::Result<String> {
+    let mut buf = [0; 256];
+    let len = libsys::calls::sys_getcwd(&mut buf)?;
+    core::str::from_utf8(&buf[..len])
+        .map(ToString::to_string)
+        .map_err(|_| crate::io::Error::new(crate::io::ErrorKind::InvalidData))
+}
+
+/// Changes the current working directory to `path`
+pub fn set_current_dir(path: &str) -> crate::io::Result<()> {
+    libsys::calls::sys_chdir(path).map_err(crate::io::Error::from)
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.inner.next().map(|s| s.to_string())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Args {}
+
+impl DoubleEndedIterator for Args {
+    fn next_back(&mut self) -> Option<String> {
+        self.inner.next_back().map(|s| s.to_string())
+    }
+}
+
+impl Iterator for Vars {
+    type Item = (String, String);
+
+    fn next(&mut self) -> Option<(String, String)> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl fmt::Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("environment variable not found")
+    }
+}
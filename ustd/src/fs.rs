@@ -0,0 +1,260 @@
+//! Filesystem manipulation
+//!
+//! Paths are plain strings: functions take `AsRef<str>` where `std` takes
+//! `AsRef<Path>`.
+use crate::io::{self, AsRawFd, FromRawFd, Read, Write};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use libsys::{
+    calls::{sys_fstatat, sys_openat, sys_readdir, sys_unlinkat},
+    error::Errno,
+    stat::{DirectoryEntry, FileMode, OpenFlags, Stat, AT_REMOVEDIR},
+};
+
+pub use libusr::file::File;
+
+/// Options for opening a [File], see [OpenOptions::open]
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+}
+
+/// Type of a filesystem node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileType(FileMode);
+
+/// Information about a filesystem node, see [metadata]
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata(Stat);
+
+/// Iterator over the entries of a directory, see [read_dir]
+pub struct ReadDir {
+    dir: File,
+    path: String,
+    buffer: VecDeque<DirectoryEntry>,
+    done: bool,
+}
+
+/// An entry returned by [ReadDir]
+#[derive(Debug)]
+pub struct DirEntry {
+    path: String,
+    entry: DirectoryEntry,
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    pub fn open<P: AsRef<str>>(&self, path: P) -> io::Result<File> {
+        let mut flags = match (self.read, self.write || self.append) {
+            (true, true) => OpenFlags::O_RDWR,
+            (false, true) => OpenFlags::O_WRONLY,
+            (true, false) => OpenFlags::O_RDONLY,
+            (false, false) => return Err(Errno::InvalidArgument.into()),
+        };
+        if self.append {
+            flags |= OpenFlags::O_APPEND;
+        }
+        if self.create {
+            flags |= OpenFlags::O_CREAT;
+        }
+
+        let fd = sys_openat(None, path.as_ref(), FileMode::default_reg(), flags)?;
+        let file = unsafe { File::from_raw_fd(fd) };
+        // There's no O_TRUNC, truncate explicitly
+        if self.truncate && !self.append {
+            file.set_len(0)?;
+        }
+        Ok(file)
+    }
+}
+
+impl FileType {
+    pub fn is_dir(&self) -> bool {
+        self.0 == FileMode::S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.0 == FileMode::S_IFREG
+    }
+}
+
+impl Metadata {
+    pub fn file_type(&self) -> FileType {
+        FileType(self.0.mode & FileMode::FILE_TYPE)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.0.size
+    }
+
+    /// Returns the full mode of the node, including the file type and
+    /// permission bits
+    pub fn mode(&self) -> FileMode {
+        self.0.mode
+    }
+}
+
+impl DirEntry {
+    /// Returns the full path of the entry: the path passed to [read_dir]
+    /// joined with the entry name
+    pub fn path(&self) -> String {
+        format!(
+            "{}/{}",
+            self.path.trim_end_matches('/'),
+            self.entry.as_str()
+        )
+    }
+
+    pub fn file_name(&self) -> String {
+        self.entry.as_str().to_string()
+    }
+
+    pub fn file_type(&self) -> io::Result<FileType> {
+        match self.entry.file_type() {
+            Some(mode) => Ok(FileType(mode)),
+            None => metadata(self.path()).map(|m| m.file_type()),
+        }
+    }
+
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        metadata(self.path())
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.buffer.pop_front() {
+                if entry.as_str() == "." || entry.as_str() == ".." {
+                    continue;
+                }
+                return Some(Ok(DirEntry {
+                    path: self.path.clone(),
+                    entry,
+                }));
+            }
+            if self.done {
+                return None;
+            }
+
+            let mut buf = [DirectoryEntry::empty(); 8];
+            match sys_readdir(self.dir.as_raw_fd(), &mut buf) {
+                Ok(0) => self.done = true,
+                Ok(count) => self.buffer.extend(buf.iter().take(count)),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+}
+
+/// Returns information about the node at `path`
+pub fn metadata<P: AsRef<str>>(path: P) -> io::Result<Metadata> {
+    let mut stat = Stat::default();
+    sys_fstatat(None, path.as_ref(), &mut stat, 0)?;
+    Ok(Metadata(stat))
+}
+
+/// Returns an iterator over the entries of directory `path`, except
+/// for `.` and `..`
+pub fn read_dir<P: AsRef<str>>(path: P) -> io::Result<ReadDir> {
+    let fd = sys_openat(
+        None,
+        path.as_ref(),
+        FileMode::default_dir(),
+        OpenFlags::O_DIRECTORY | OpenFlags::O_RDONLY,
+    )?;
+    Ok(ReadDir {
+        dir: unsafe { File::from_raw_fd(fd) },
+        path: path.as_ref().to_string(),
+        buffer: VecDeque::new(),
+        done: false,
+    })
+}
+
+/// Reads the whole contents of file `path`
+pub fn read<P: AsRef<str>>(path: P) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path.as_ref())?;
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        match io::retry(|| file.read(&mut buf))? {
+            0 => break,
+            count => data.extend_from_slice(&buf[..count]),
+        }
+    }
+    Ok(data)
+}
+
+/// Reads the whole contents of file `path` as an UTF-8 string
+pub fn read_to_string<P: AsRef<str>>(path: P) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|_| io::Error::new(io::ErrorKind::InvalidData))
+}
+
+/// Replaces the contents of file `path` with `contents`, creating the
+/// file if it does not exist
+pub fn write<P: AsRef<str>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?
+        .write_all(contents.as_ref())
+}
+
+/// Removes file `path`
+pub fn remove_file<P: AsRef<str>>(path: P) -> io::Result<()> {
+    sys_unlinkat(None, path.as_ref(), 0).map_err(io::Error::from)
+}
+
+/// Removes empty directory `path`
+pub fn remove_dir<P: AsRef<str>>(path: P) -> io::Result<()> {
+    sys_unlinkat(None, path.as_ref(), AT_REMOVEDIR).map_err(io::Error::from)
+}
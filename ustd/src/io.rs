@@ -0,0 +1,8 @@
+//! Traits and helpers for I/O
+pub use libusr::io::{
+    retry, stderr, stdin, stdout, AsRawFd, BufReader, Error, ErrorKind, FromRawFd, Lines, Read,
+    Retry, Stderr, Stdin, Stdout, Write,
+};
+
+/// Result type for I/O operations
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,72 @@
+//! A subset of the `std` interface on top of [libusr], to ease porting
+//! simple Rust programs.
+//!
+//! A program depends on this crate under the name `std`:
+//!
+//! ```toml
+//! [dependencies]
+//! std = { package = "ustd", path = "../ustd" }
+//! ```
+//!
+//! and, as with any other program for this OS, is `#![no_std]` and
+//! `#![no_main]` with a `#[no_mangle] fn main() -> i32` entry. The
+//! prelude has to be imported explicitly (`use std::prelude::v1::*`),
+//! after which `std::io`, `std::fs`, `std::env`, `std::process`, ...
+//! paths resolve to this crate.
+//!
+//! Only what libusr can back is provided. Notable differences from
+//! `std`: there is no `HashMap`/`HashSet`, `sync::Mutex::lock` does not
+//! return a `LockResult`, and there is no `process::Command` or
+//! `fs::create_dir`.
+#![no_std]
+
+extern crate alloc;
+
+pub use alloc::{borrow, boxed, fmt, rc, str, string, vec};
+pub use core::{
+    any, array, cell, char, clone, cmp, convert, default, hash, hint, iter, marker, mem, num, ops,
+    option, primitive, ptr, result, slice,
+};
+
+// `vec` above brings in both the module and the macro
+pub use alloc::format;
+pub use core::{
+    assert, assert_eq, assert_ne, debug_assert, debug_assert_eq, debug_assert_ne, matches, panic,
+    todo, unimplemented, unreachable, write, writeln,
+};
+pub use libusr::{eprint, eprintln, print, println};
+
+pub mod env;
+pub mod fs;
+pub mod io;
+pub mod process;
+pub mod time;
+
+pub mod collections {
+    pub use alloc::collections::*;
+}
+
+pub mod sync {
+    pub use alloc::sync::{Arc, Weak};
+    pub use core::sync::atomic;
+    pub use libusr::sync::{Lazy, Mutex, MutexGuard, OnceCell};
+}
+
+pub mod thread {
+    pub use libusr::thread::{current, sleep, spawn, JoinHandle, Thread};
+}
+
+pub mod prelude {
+    pub mod v1 {
+        pub use alloc::borrow::ToOwned;
+        pub use alloc::boxed::Box;
+        pub use alloc::string::{String, ToString};
+        pub use alloc::vec::Vec;
+        pub use alloc::{format, vec};
+        pub use libusr::{eprint, eprintln, print, println};
+    }
+
+    pub mod rust_2021 {
+        pub use super::v1::*;
+    }
+}
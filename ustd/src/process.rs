@@ -0,0 +1,20 @@
+//! Process control
+use libsys::{
+    calls::{sys_exit, sys_getpid},
+    proc::ExitCode,
+};
+
+/// Terminates the current process with exit status `code`
+pub fn exit(code: i32) -> ! {
+    sys_exit(ExitCode::from(code))
+}
+
+/// Terminates the current process abnormally, the same way a panic does
+pub fn abort() -> ! {
+    exit(-1)
+}
+
+/// Returns the ID of the current process
+pub fn id() -> u32 {
+    u32::from(sys_getpid())
+}
@@ -0,0 +1,130 @@
+//! Temporal quantification
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use libsys::{calls::sys_ex_clock_get, time::ClockId};
+
+pub use core::time::Duration;
+
+/// A point in time of the monotonic clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+/// A point in time of the wall clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemTime(Duration);
+
+/// Error returned by [SystemTime::duration_since] when the other time is
+/// later, holding the difference
+#[derive(Clone, Debug)]
+pub struct SystemTimeError(Duration);
+
+/// The Unix epoch, 1970-01-01 00:00:00 UTC
+pub const UNIX_EPOCH: SystemTime = SystemTime(Duration::ZERO);
+
+fn clock(id: ClockId) -> Duration {
+    sys_ex_clock_get(id).expect("Failed to read the system clock")
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Self(clock(ClockId::Monotonic))
+    }
+
+    /// Returns the time elapsed since `earlier`, or zero if `earlier` is
+    /// later than `self`
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Self::now().duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Self)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+impl SystemTime {
+    pub const UNIX_EPOCH: SystemTime = UNIX_EPOCH;
+
+    pub fn now() -> Self {
+        Self(clock(ClockId::Realtime))
+    }
+
+    pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+        self.0
+            .checked_sub(earlier.0)
+            .ok_or_else(|| SystemTimeError(earlier.0 - self.0))
+    }
+
+    pub fn elapsed(&self) -> Result<Duration, SystemTimeError> {
+        Self::now().duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_add(duration).map(Self)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<SystemTime> {
+        self.0.checked_sub(duration).map(Self)
+    }
+}
+
+impl SystemTimeError {
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+macro_rules! impl_time_ops {
+    ($ty:ident) => {
+        impl Add<Duration> for $ty {
+            type Output = $ty;
+
+            fn add(self, rhs: Duration) -> $ty {
+                self.checked_add(rhs)
+                    .expect("Overflow when adding a duration")
+            }
+        }
+
+        impl AddAssign<Duration> for $ty {
+            fn add_assign(&mut self, rhs: Duration) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl Sub<Duration> for $ty {
+            type Output = $ty;
+
+            fn sub(self, rhs: Duration) -> $ty {
+                self.checked_sub(rhs)
+                    .expect("Overflow when subtracting a duration")
+            }
+        }
+
+        impl SubAssign<Duration> for $ty {
+            fn sub_assign(&mut self, rhs: Duration) {
+                *self = *self - rhs;
+            }
+        }
+    };
+}
+
+impl_time_ops!(Instant);
+impl_time_ops!(SystemTime);
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}